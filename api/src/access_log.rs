@@ -0,0 +1,325 @@
+// HTTP access log
+// This module records one line per HTTP request, as JSON or in Common Log Format, apart from the application logs
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::HttpBody as _;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::client_ip::ClientIpResolver;
+use crate::config::{AccessLogConfig, AccessLogFormat};
+
+/// Header carrying the request ID, taken from the request when valid and set on the response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID accepted from a client; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Lines waiting for the writer; lines are dropped while the queue is full
+const QUEUE_CAPACITY: usize = 8192;
+
+/// ID of the request, available to handlers as a request extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// One request as recorded in the access log
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    /// When the request was received
+    pub time: DateTime<Utc>,
+    pub method: String,
+    /// Request path, without the query string (which can carry personal data)
+    pub path: String,
+    /// Protocol version, e.g. "HTTP/1.1"
+    pub protocol: String,
+    pub status: u16,
+    pub latency: Duration,
+    /// Response body size, when known before streaming it
+    pub bytes: Option<u64>,
+    /// Client IP resolved through the trusted proxies
+    pub client_ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub request_id: String,
+}
+
+impl AccessLogEntry {
+    /// The entry as one line in `format`, without the line break
+    pub fn format(&self, format: AccessLogFormat) -> String {
+        match format {
+            AccessLogFormat::Json => self.to_json(),
+            AccessLogFormat::Common => self.to_common(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::json!({
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "method": self.method,
+            "path": self.path,
+            "protocol": self.protocol,
+            "status": self.status,
+            "latency_ms": latency_ms(self.latency),
+            "bytes": self.bytes,
+            "client_ip": self.client_ip.map(|ip| ip.to_string()),
+            "user_agent": self.user_agent,
+            "request_id": self.request_id,
+        })
+        .to_string()
+    }
+
+    /// `host - - [time] "request" status bytes`, then `"user-agent" request-id latency-ms`
+    fn to_common(&self) -> String {
+        let dash = || "-".to_string();
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {} \"{}\" {} {:.3}",
+            self.client_ip.map(|ip| ip.to_string()).unwrap_or_else(dash),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            self.path,
+            self.protocol,
+            self.status,
+            self.bytes.map(|bytes| bytes.to_string()).unwrap_or_else(dash),
+            self.user_agent.as_deref().map(escape_quoted).unwrap_or_else(dash),
+            self.request_id,
+            latency_ms(self.latency),
+        )
+    }
+}
+
+/// Writes access log lines to standard output or a file from a background task
+///
+/// Requests only queue their line, so a slow destination never delays responses;
+/// lines are dropped (with a warning) when the writer falls behind.
+pub struct AccessLogger {
+    format: AccessLogFormat,
+    resolver: Arc<ClientIpResolver>,
+    sender: mpsc::Sender<String>,
+}
+
+impl AccessLogger {
+    /// Open the destination in `config` and spawn the writer task
+    ///
+    /// # Arguments
+    /// * `config` - Access log settings
+    /// * `resolver` - Resolves the client IP behind trusted proxies
+    ///
+    /// # Errors
+    /// Returns an error if the log file cannot be opened
+    pub async fn new(config: &AccessLogConfig, resolver: Arc<ClientIpResolver>) -> std::io::Result<Self> {
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = match &config.path {
+            Some(path) => Box::new(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?,
+            ),
+            None => Box::new(tokio::io::stdout()),
+        };
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(write_lines(receiver, writer));
+        Ok(AccessLogger {
+            format: config.format,
+            resolver,
+            sender,
+        })
+    }
+
+    fn log(&self, entry: &AccessLogEntry) {
+        if self.sender.try_send(entry.format(self.format)).is_err() {
+            tracing::warn!(request_id = %entry.request_id, "Access log writer is behind, line dropped");
+        }
+    }
+}
+
+/// Write queued lines until every sender is gone
+async fn write_lines(mut receiver: mpsc::Receiver<String>, mut writer: Box<dyn AsyncWrite + Send + Unpin>) {
+    while let Some(line) = receiver.recv().await {
+        let mut buffer = line.into_bytes();
+        buffer.push(b'\n');
+        // Batch whatever else is already queued into the same write
+        while let Ok(line) = receiver.try_recv() {
+            buffer.extend(line.into_bytes());
+            buffer.push(b'\n');
+        }
+        let written = match writer.write_all(&buffer).await {
+            Ok(()) => writer.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            tracing::error!(error = %e, "Failed to write the access log");
+        }
+    }
+}
+
+/// Middleware logging every request through `logger`
+///
+/// Also assigns the request ID: the client's `X-Request-Id` when it is a short
+/// printable value, a new UUID otherwise. The ID is available to handlers as a
+/// `RequestId` extension and is returned in the `X-Request-Id` response header.
+pub async fn log_requests(State(logger): State<Arc<AccessLogger>>, mut request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let time = Utc::now();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| logger.resolver.resolve(peer.ip(), request.headers()));
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let protocol = format!("{:?}", request.version());
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    logger.log(&AccessLogEntry {
+        time,
+        method,
+        path,
+        protocol,
+        status: response.status().as_u16(),
+        latency: started.elapsed(),
+        bytes: response.body().size_hint().exact(),
+        client_ip,
+        user_agent,
+        request_id,
+    });
+    response
+}
+
+fn latency_ms(latency: Duration) -> f64 {
+    latency.as_micros() as f64 / 1000.0
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Escape `"` and `\` for a quoted Common Log Format field
+fn escape_quoted(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use chrono::TimeZone;
+    use tower::Service;
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            time: Utc.with_ymd_and_hms(2024, 1, 1, 12, 30, 0).unwrap(),
+            method: "GET".to_string(),
+            path: "/track/".to_string(),
+            protocol: "HTTP/1.1".to_string(),
+            status: 200,
+            latency: Duration::from_micros(1500),
+            bytes: Some(17),
+            client_ip: Some("203.0.113.7".parse().unwrap()),
+            user_agent: Some("Mozilla/5.0 \"test\"".to_string()),
+            request_id: "req-1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            entry().format(AccessLogFormat::Common),
+            "203.0.113.7 - - [01/Jan/2024:12:30:00 +0000] \"GET /track/ HTTP/1.1\" 200 17 \"Mozilla/5.0 \\\"test\\\"\" req-1 1.500"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&entry().format(AccessLogFormat::Json)).unwrap();
+        assert_eq!(json["time"], "2024-01-01T12:30:00.000Z");
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["latency_ms"], 1.5);
+        assert_eq!(json["client_ip"], "203.0.113.7");
+        assert_eq!(json["request_id"], "req-1");
+
+        let unknown = AccessLogEntry {
+            bytes: None,
+            client_ip: None,
+            user_agent: None,
+            ..entry()
+        };
+        assert!(unknown.format(AccessLogFormat::Common).starts_with("- - - ["));
+        assert!(unknown.format(AccessLogFormat::Common).contains(" 200 - \"-\" req-1 "));
+    }
+
+    #[tokio::test]
+    async fn test_logs_requests_with_their_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let config = AccessLogConfig {
+            enabled: true,
+            format: AccessLogFormat::Json,
+            path: Some(path.to_str().unwrap().to_string()),
+        };
+        let logger = Arc::new(AccessLogger::new(&config, Arc::new(ClientIpResolver::default())).await.unwrap());
+        let mut app = Router::new()
+            .route("/health", get(|| async { "OK" }))
+            .layer(axum::middleware::from_fn_with_state(logger, log_requests));
+
+        let mut request = axum::http::Request::builder()
+            .uri("/health?u_email=someone@example.com")
+            .header(REQUEST_ID_HEADER, "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 4], 5000))));
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+
+        let response = app
+            .call(axum::http::Request::builder().uri("/missing").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER].len(), 36);
+
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .collect();
+            if lines.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], "/health");
+        assert_eq!(lines[0]["status"], 200);
+        assert_eq!(lines[0]["bytes"], 2);
+        assert_eq!(lines[0]["client_ip"], "198.51.100.4");
+        assert_eq!(lines[0]["request_id"], "abc-123");
+        assert_eq!(lines[1]["status"], 404);
+        assert!(lines[1]["client_ip"].is_null());
+    }
+}
@@ -0,0 +1,271 @@
+// Raw request audit log
+// This module records a sample of raw ingest requests, with personal data redacted, to reproduce data-quality bugs
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, Method};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::config::{AuditConfig, KafkaConfig, REDACTED};
+use crate::enrichment::pii::mask_value;
+use crate::streaming::create_kafka_producer;
+
+/// Records waiting for the writer; records are dropped while the queue is full
+const QUEUE_CAPACITY: usize = 1024;
+
+/// One request as received, before validation
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AuditRecord {
+    pub received_at: String,
+    pub endpoint: &'static str,
+    pub method: String,
+    /// Query parameters in request order, repeated ones included
+    pub query: Vec<(String, String)>,
+    /// Form body parameters in request order
+    pub form: Vec<(String, String)>,
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Where the records are written
+enum AuditWriter {
+    File(Box<dyn AsyncWrite + Send + Unpin>),
+    Kafka { producer: FutureProducer, topic: String },
+}
+
+/// Records a sample of the raw ingest requests from a background task
+///
+/// Requests only queue their record, so a slow destination never delays responses;
+/// records are dropped (with a warning) when the writer falls behind.
+pub struct AuditLog {
+    sample_rate: f64,
+    redact_params: Vec<String>,
+    redact_headers: HashSet<String>,
+    sender: mpsc::Sender<String>,
+}
+
+impl AuditLog {
+    /// Open the destination in `config` and spawn the writer task
+    ///
+    /// # Arguments
+    /// * `config` - Audit log settings
+    /// * `kafka` - Kafka sink settings, whose brokers receive the records of `config.topic`
+    ///
+    /// # Errors
+    /// Returns a description of the failure if the file cannot be opened or the Kafka
+    /// producer cannot be created
+    pub async fn new(config: &AuditConfig, kafka: Option<&KafkaConfig>) -> Result<Self, String> {
+        let writer = match (&config.path, &config.topic) {
+            (Some(path), _) => {
+                let file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| format!("cannot open {}: {}", path, e))?;
+                AuditWriter::File(Box::new(file))
+            }
+            (None, Some(topic)) => {
+                let kafka = kafka.ok_or_else(|| "an audit topic needs the streaming.kafka section".to_string())?;
+                let producer = create_kafka_producer(kafka).map_err(|e| e.to_string())?;
+                AuditWriter::Kafka {
+                    producer,
+                    topic: topic.clone(),
+                }
+            }
+            (None, None) => return Err("an audit path or topic is required".to_string()),
+        };
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(write_records(receiver, writer));
+        Ok(Self::with_sender(config, sender))
+    }
+
+    fn with_sender(config: &AuditConfig, sender: mpsc::Sender<String>) -> Self {
+        AuditLog {
+            sample_rate: config.sample_rate,
+            redact_params: config.redact_params.clone(),
+            redact_headers: config.redact_headers.iter().map(|name| name.to_ascii_lowercase()).collect(),
+            sender,
+        }
+    }
+
+    /// Record the request if it falls in the sample
+    pub fn record(
+        &self,
+        endpoint: &'static str,
+        method: &Method,
+        headers: &HeaderMap,
+        query: &[(String, String)],
+        form: &[(String, String)],
+    ) {
+        if !is_sampled(self.sample_rate) {
+            return;
+        }
+        let record = self.build_record(Utc::now(), endpoint, method, headers, query, form);
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to serialize audit record");
+                return;
+            }
+        };
+        if self.sender.try_send(line).is_err() {
+            tracing::warn!(endpoint = endpoint, "Audit log writer is behind, record dropped");
+        }
+    }
+
+    /// The redacted record of a request
+    pub fn build_record(
+        &self,
+        received_at: DateTime<Utc>,
+        endpoint: &'static str,
+        method: &Method,
+        headers: &HeaderMap,
+        query: &[(String, String)],
+        form: &[(String, String)],
+    ) -> AuditRecord {
+        let mut recorded_headers: BTreeMap<String, String> = BTreeMap::new();
+        for (name, value) in headers {
+            let value = if self.redact_headers.contains(name.as_str()) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            recorded_headers
+                .entry(name.as_str().to_string())
+                .and_modify(|values| {
+                    values.push_str(", ");
+                    values.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+        AuditRecord {
+            received_at: received_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            endpoint,
+            method: method.to_string(),
+            query: self.redact_params(query),
+            form: self.redact_params(form),
+            headers: recorded_headers,
+        }
+    }
+
+    fn redact_params(&self, params: &[(String, String)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.is_redacted_param(name) { mask_value(value) } else { value.clone() };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn is_redacted_param(&self, name: &str) -> bool {
+        self.redact_params.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+    }
+}
+
+/// Whether a request falls in a `sample_rate` share of requests
+fn is_sampled(sample_rate: f64) -> bool {
+    if sample_rate >= 1.0 {
+        return true;
+    }
+    let bits = (uuid::Uuid::new_v4().as_u128() >> 64) as u64;
+    ((bits >> 11) as f64 / (1u64 << 53) as f64) < sample_rate
+}
+
+/// Write queued records until every sender is gone
+async fn write_records(mut receiver: mpsc::Receiver<String>, mut writer: AuditWriter) {
+    while let Some(line) = receiver.recv().await {
+        let written = match &mut writer {
+            AuditWriter::File(file) => {
+                let mut buffer = line.into_bytes();
+                buffer.push(b'\n');
+                match file.write_all(&buffer).await {
+                    Ok(()) => file.flush().await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            AuditWriter::Kafka { producer, topic } => producer
+                .send(FutureRecord::<(), _>::to(topic.as_str()).payload(line.as_str()), Duration::from_secs(0))
+                .await
+                .map(|_| ())
+                .map_err(|(e, _)| e.to_string()),
+        };
+        if let Err(e) = written {
+            tracing::error!(error = %e, "Failed to write an audit record");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_records_are_redacted() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let audit = AuditLog::with_sender(&AuditConfig::default(), sender);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", "Mozilla/5.0".parse().unwrap());
+        headers.insert("X-Api-Key", "sdk-secret".parse().unwrap());
+        headers.append("x-forwarded-for", "203.0.113.7".parse().unwrap());
+
+        let record = audit.build_record(
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap(),
+            "/track/",
+            &Method::POST,
+            &headers,
+            &pairs(&[("project", "shop"), ("u_email", "jane@example.com"), ("tag", "a"), ("tag", "b")]),
+            &pairs(&[("ip", "203.0.113.7")]),
+        );
+        assert_eq!(record.received_at, "2024-01-01T12:00:00.000Z");
+        assert_eq!(
+            record.query,
+            pairs(&[("project", "shop"), ("u_email", "j***@example.com"), ("tag", "a"), ("tag", "b")])
+        );
+        assert_eq!(record.form, pairs(&[("ip", "*******13.7")]));
+        assert_eq!(record.headers["user-agent"], "Mozilla/5.0");
+        assert_eq!(record.headers["x-api-key"], REDACTED);
+        assert_eq!(record.headers["x-forwarded-for"], REDACTED);
+    }
+
+    #[tokio::test]
+    async fn test_writes_sampled_records_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let config = AuditConfig {
+            sample_rate: 1.0,
+            path: Some(path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let audit = AuditLog::new(&config, None).await.unwrap();
+        audit.record("/identify", &Method::GET, &HeaderMap::new(), &pairs(&[("u_id", "user123")]), &[]);
+
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if !contents.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let record: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(record["endpoint"], "/identify");
+        assert_eq!(record["query"], serde_json::json!([["u_id", "*******"]]));
+
+        assert!(!is_sampled(0.0));
+    }
+}
@@ -0,0 +1,142 @@
+// Replay tool
+// Re-sends events stored by the file/fallback sink through the configured streaming service
+//
+// Usage:
+//   replay <events.jsonl> [--config config.yaml] [--profile NAME] [--rate N] [--skip N] [--progress N] [--dry-run]
+
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Instant;
+
+use api::config::{load_config_with_profile, PROFILE_ENV};
+use api::logging::init_logging;
+use api::replay::{format_rate, replay_file, ReplayOptions};
+use api::streaming::create_streaming_service;
+
+const USAGE: &str = "Usage: replay <events.jsonl> [--config <path>] [--profile <name>] [--rate <events/sec>] \
+[--skip <lines>] [--progress <lines>] [--dry-run]";
+
+struct Args {
+    input: PathBuf,
+    config_path: String,
+    /// Configuration profile, defaulting to the ANALYTICS_PROFILE environment variable
+    profile: Option<String>,
+    options: ReplayOptions,
+}
+
+/// Parse command-line arguments
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut input = None;
+    let mut config_path = "config.yaml".to_string();
+    let mut profile = None;
+    let mut options = ReplayOptions::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--config" => config_path = value("--config")?,
+            "--profile" => profile = Some(value("--profile")?),
+            "--rate" => {
+                options.rate_per_sec = Some(
+                    value("--rate")?
+                        .parse()
+                        .map_err(|_| "--rate must be a positive integer".to_string())?,
+                )
+            }
+            "--skip" => {
+                options.skip_lines = value("--skip")?
+                    .parse()
+                    .map_err(|_| "--skip must be a non-negative integer".to_string())?
+            }
+            "--progress" => {
+                options.progress_every = value("--progress")?
+                    .parse()
+                    .map_err(|_| "--progress must be a non-negative integer".to_string())?
+            }
+            "--dry-run" => options.dry_run = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other if other.starts_with("--") => return Err(format!("Unknown option: {}", other)),
+            other => {
+                if input.replace(PathBuf::from(other)).is_some() {
+                    return Err("Only one input file may be given".to_string());
+                }
+            }
+        }
+    }
+
+    Ok(Args {
+        input: input.ok_or_else(|| USAGE.to_string())?,
+        config_path,
+        profile: profile.or_else(|| std::env::var(PROFILE_ENV).ok()),
+        options,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            exit(2);
+        }
+    };
+
+    let mut config = match load_config_with_profile(&args.config_path, args.profile.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {}", e);
+            exit(1);
+        }
+    };
+    init_logging(&config.logging.level);
+
+    // Replay straight into the broker: a fallback sink could write events back into
+    // the very file being replayed
+    config.streaming.fallback = None;
+
+    let service = match create_streaming_service(&config.streaming).await {
+        Ok(service) => service,
+        Err(e) => {
+            eprintln!("Failed to initialize streaming service: {}", e);
+            exit(1);
+        }
+    };
+
+    println!(
+        "Replaying {} into {}{}",
+        args.input.display(),
+        config.streaming.service_type.as_str(),
+        if args.options.dry_run { " (dry run)" } else { "" }
+    );
+
+    let started = Instant::now();
+    let result = replay_file(&args.input, service.as_ref(), &args.options, |progress| {
+        println!(
+            "  lines={} sent={} invalid={} rate={}",
+            progress.lines_read,
+            progress.sent,
+            progress.invalid,
+            format_rate(progress.sent, started.elapsed())
+        );
+    })
+    .await;
+
+    match result {
+        Ok(progress) => {
+            println!(
+                "Replay complete: {} events sent, {} invalid lines, {:.1}s",
+                progress.sent,
+                progress.invalid,
+                started.elapsed().as_secs_f64()
+            );
+        }
+        Err(e) => {
+            eprintln!("Replay stopped: {}", e);
+            exit(1);
+        }
+    }
+}
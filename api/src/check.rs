@@ -0,0 +1,121 @@
+// Configuration check
+// This module looks for problems a valid configuration only shows at runtime: unreadable files and unreachable sinks
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{Config, GeoIpConfig, StreamingConfig};
+use crate::enrichment::geoip::GeoIpLookup;
+use crate::streaming::create_streaming_service;
+
+/// Problems found in a loaded configuration, in the order checked
+///
+/// Checks that the GeoIP databases and the fallback directory exist; with `connect`,
+/// also creates the streaming service (residency routes included) and runs its health
+/// check. An empty list means the configuration is ready to serve.
+pub async fn check_config(config: &Config, connect: bool) -> Vec<String> {
+    let mut problems = check_geoip(&config.geoip);
+    problems.extend(check_fallback(&config.streaming));
+    if connect {
+        problems.extend(check_sinks(&config.streaming).await);
+    }
+    problems
+}
+
+/// Unreadable or invalid GeoIP databases
+fn check_geoip(config: &GeoIpConfig) -> Vec<String> {
+    if config.database_path.is_empty() {
+        return Vec::new();
+    }
+    let paths = [
+        ("geoip.database_path", Some(&config.database_path)),
+        ("geoip.isp_database_path", config.isp_database_path.as_ref()),
+        ("geoip.connection_type_database_path", config.connection_type_database_path.as_ref()),
+        ("geoip.anonymous_ip_database_path", config.anonymous_ip_database_path.as_ref()),
+    ];
+    let problems: Vec<String> = paths
+        .into_iter()
+        .filter_map(|(field, path)| {
+            let path = path?;
+            std::fs::File::open(path)
+                .err()
+                .map(|e| format!("{}: cannot read {}: {}", field, path, e))
+        })
+        .collect();
+    if !problems.is_empty() {
+        return problems;
+    }
+    match GeoIpLookup::from_config(config) {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![format!("geoip: {}", e)],
+    }
+}
+
+/// Missing directory of the fallback file
+fn check_fallback(config: &StreamingConfig) -> Vec<String> {
+    let Some(fallback) = &config.fallback else {
+        return Vec::new();
+    };
+    match Path::new(&fallback.path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => {
+            vec![format!("streaming.fallback.path: directory {} does not exist", dir.display())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Sinks that cannot be created or fail their health check
+async fn check_sinks(config: &StreamingConfig) -> Vec<String> {
+    let service = match create_streaming_service(config).await {
+        Ok(service) => service,
+        Err(e) => return vec![format!("streaming: {}", e)],
+    };
+    let timeout = Duration::from_secs(config.health_check.timeout_secs);
+    match tokio::time::timeout(timeout, service.health_check()).await {
+        Ok(Ok(())) => Vec::new(),
+        Ok(Err(e)) => vec![format!("streaming: health check failed: {}", e)],
+        Err(_) => vec![format!(
+            "streaming: health check timed out after {}s",
+            config.health_check.timeout_secs
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FallbackConfig;
+
+    #[tokio::test]
+    async fn test_default_config_has_no_problems() {
+        assert!(check_config(&Config::default(), true).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lists_every_missing_file() {
+        let config = Config {
+            geoip: GeoIpConfig {
+                database_path: "/nonexistent/GeoLite2-City.mmdb".to_string(),
+                isp_database_path: Some("/nonexistent/GeoIP2-ISP.mmdb".to_string()),
+                ..Default::default()
+            },
+            streaming: StreamingConfig {
+                fallback: Some(FallbackConfig {
+                    path: "/nonexistent/fallback.jsonl".to_string(),
+                    failure_threshold: 5,
+                    open_secs: 30,
+                    replay_interval_secs: 60,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let problems = check_config(&config, false).await;
+
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("geoip.database_path: cannot read /nonexistent/GeoLite2-City.mmdb"));
+        assert!(problems[1].starts_with("geoip.isp_database_path: cannot read /nonexistent/GeoIP2-ISP.mmdb"));
+        assert_eq!(problems[2], "streaming.fallback.path: directory /nonexistent does not exist");
+    }
+}
@@ -0,0 +1,225 @@
+// CIDR network ranges
+// This module parses and matches IPv4/IPv6 CIDR blocks such as "10.0.0.0/8"
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network in CIDR notation
+///
+/// A bare address (no `/prefix`) is treated as a single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+/// Error returned when a CIDR string cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CidrParseError(String);
+
+impl fmt::Display for CidrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid CIDR: {}", self.0)
+    }
+}
+
+impl std::error::Error for CidrParseError {}
+
+impl IpCidr {
+    /// Network address (host bits cleared)
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Prefix length in bits
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Whether `ip` falls inside this network
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) match IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = prefix_mask_u32(self.prefix_len);
+                u32::from(net) == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = prefix_mask_u128(self.prefix_len);
+                u128::from(net) == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_mask_u32(prefix_len: u8) -> u32 {
+    if prefix_len == 0 {
+        0
+    } else {
+        u32::MAX << (32 - u32::from(prefix_len))
+    }
+}
+
+fn prefix_mask_u128(prefix_len: u8) -> u128 {
+    if prefix_len == 0 {
+        0
+    } else {
+        u128::MAX << (128 - u32::from(prefix_len))
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = CidrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| CidrParseError(s.to_string()))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max_len)
+                .ok_or_else(|| CidrParseError(s.to_string()))?,
+            None => max_len,
+        };
+
+        let network = match addr {
+            IpAddr::V4(v4) => IpAddr::V4((u32::from(v4) & prefix_mask_u32(prefix_len)).into()),
+            IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & prefix_mask_u128(prefix_len)).into()),
+        };
+
+        Ok(IpCidr { network, prefix_len })
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// Whether `ip` is a non-public address: private (RFC 1918, IPv6 ULA), loopback,
+/// link-local, carrier-grade NAT (100.64.0.0/10), unspecified, multicast, broadcast
+/// or reserved (240.0.0.0/4)
+///
+/// IPv4-mapped IPv6 addresses are checked as IPv4.
+pub fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_internal_ipv4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal_ipv4(v4),
+            None => is_internal_ipv6(v6),
+        },
+    }
+}
+
+fn is_internal_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_broadcast()
+        || (first == 100 && second & 0xc0 == 64)
+        || first == 0
+        || first >= 240
+}
+
+fn is_internal_ipv6(ip: Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || first_segment & 0xfe00 == 0xfc00
+        || first_segment & 0xffc0 == 0xfe80
+}
+
+/// Parse a list of CIDR strings, reporting the first invalid entry
+pub fn parse_cidrs(values: &[String]) -> Result<Vec<IpCidr>, CidrParseError> {
+    values.iter().map(|v| v.parse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv4_contains() {
+        let cidr: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_host_bits_are_cleared() {
+        let cidr: IpCidr = "192.168.1.77/24".parse().unwrap();
+        assert_eq!(cidr.to_string(), "192.168.1.0/24");
+    }
+
+    #[test]
+    fn test_bare_address_is_single_host() {
+        let cidr: IpCidr = "203.0.113.5".parse().unwrap();
+        assert_eq!(cidr.prefix_len(), 32);
+        assert!(cidr.contains("203.0.113.5".parse().unwrap()));
+        assert!(!cidr.contains("203.0.113.6".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ipv6_contains() {
+        let cidr: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(cidr.contains("fd12:3456::1".parse().unwrap()));
+        assert!(!cidr.contains("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_ipv6_matches_ipv4_network() {
+        let cidr: IpCidr = "127.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("::ffff:127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_zero_prefix_matches_everything_in_family() {
+        let cidr: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(cidr.contains("8.8.8.8".parse().unwrap()));
+        assert!(!cidr.contains("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_internal_ips() {
+        for ip in [
+            "10.1.2.3", "172.16.0.1", "192.168.1.1", "127.0.0.1", "169.254.1.1", "100.64.0.1",
+            "100.127.255.255", "0.0.0.0", "255.255.255.255", "::1", "fe80::1", "fd12::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(is_internal_ip(ip.parse().unwrap()), "{} should be internal", ip);
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "172.32.0.1", "2001:4860:4860::8888", "::ffff:8.8.8.8"] {
+            assert!(!is_internal_ip(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_invalid_cidrs() {
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("not-an-ip/8".parse::<IpCidr>().is_err());
+        assert!("10.0.0.0/abc".parse::<IpCidr>().is_err());
+        assert!(parse_cidrs(&["10.0.0.0/8".to_string(), "bad".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+// Client IP resolution
+// This module determines the real client IP behind trusted reverse proxies and load balancers
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::HeaderMap;
+
+use crate::cidr::{parse_cidrs, CidrParseError, IpCidr};
+use crate::config::IpOverrideConfig;
+
+/// Request parameter carrying the visitor IP for server-side SDKs
+pub const IP_PARAM: &str = "ip";
+
+/// Header carrying the API key that authorizes an IP override
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Resolves the client IP from the peer address and proxy forwarding headers
+///
+/// Forwarding headers are only honored when the TCP peer is a trusted proxy. The
+/// forwarding chain is then walked from the right (closest hop) and the first address
+/// that is not a trusted proxy is the client. Headers are checked in this order:
+/// `Forwarded` (RFC 7239), `X-Forwarded-For`, `X-Real-IP`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<IpCidr>,
+    ip_override: IpOverride,
+}
+
+/// Callers allowed to replace the resolved client IP with the `ip` parameter
+#[derive(Debug, Clone, Default)]
+pub struct IpOverride {
+    allowed_sources: Vec<IpCidr>,
+    api_keys: Vec<String>,
+}
+
+impl IpOverride {
+    /// Create an allowlist from caller ranges and API keys
+    pub fn new(allowed_sources: Vec<IpCidr>, api_keys: Vec<String>) -> Self {
+        IpOverride { allowed_sources, api_keys }
+    }
+
+    /// Create an allowlist from configuration
+    ///
+    /// # Errors
+    /// Returns `CidrParseError` if any allowed source is not a valid address or CIDR range
+    pub fn from_config(config: &IpOverrideConfig) -> Result<Self, CidrParseError> {
+        Ok(Self::new(parse_cidrs(&config.allowed_sources)?, config.api_keys.clone()))
+    }
+
+    /// Whether a caller at `caller` sending `headers` may override the client IP
+    ///
+    /// The unspecified address (Unix socket clients) is never an allowed source.
+    pub fn is_authorized(&self, caller: IpAddr, headers: &HeaderMap) -> bool {
+        if !caller.is_unspecified() && self.allowed_sources.iter().any(|range| range.contains(caller)) {
+            return true;
+        }
+        let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        self.api_keys.iter().any(|allowed| constant_time_eq(allowed.as_bytes(), key.trim().as_bytes()))
+    }
+}
+
+impl ClientIpResolver {
+    /// Create a resolver trusting the given proxy ranges
+    pub fn new(trusted_proxies: Vec<IpCidr>) -> Self {
+        ClientIpResolver {
+            trusted_proxies,
+            ip_override: IpOverride::default(),
+        }
+    }
+
+    /// Allow the given callers to set the client IP with the `ip` parameter
+    pub fn with_ip_override(mut self, ip_override: IpOverride) -> Self {
+        self.ip_override = ip_override;
+        self
+    }
+
+    /// Create a resolver from configured CIDR strings
+    ///
+    /// # Errors
+    /// Returns `CidrParseError` if any entry is not a valid address or CIDR range
+    pub fn from_config(trusted_proxies: &[String]) -> Result<Self, CidrParseError> {
+        Ok(Self::new(parse_cidrs(trusted_proxies)?))
+    }
+
+    /// Whether `ip` belongs to a trusted proxy
+    ///
+    /// The unspecified address (Unix socket clients) is never trusted.
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        !ip.is_unspecified() && self.trusted_proxies.iter().any(|range| range.contains(ip))
+    }
+
+    /// Determine the client IP of a request
+    ///
+    /// # Arguments
+    /// * `peer` - Address of the TCP peer (from `ConnectInfo`)
+    /// * `headers` - HTTP request headers
+    ///
+    /// # Returns
+    /// The rightmost untrusted address in the forwarding chain, or `peer` when the peer
+    /// is not a trusted proxy or no usable forwarding header is present
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let chain = forwarded_chain(headers)
+            .or_else(|| header_values(headers, "x-forwarded-for"))
+            .or_else(|| header_values(headers, "x-real-ip"));
+        let Some(chain) = chain else {
+            return peer;
+        };
+
+        // Walk from the closest hop outwards; anything we cannot parse ends the walk,
+        // since hops further left were reported by an unverified party
+        let mut client = peer;
+        for hop in chain.iter().rev() {
+            match parse_node(hop) {
+                Some(ip) => {
+                    client = ip;
+                    if !self.is_trusted(ip) {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+        client
+    }
+
+    /// Determine the client IP of a request, honoring the `ip` parameter of trusted callers
+    ///
+    /// The caller is the address returned by [`resolve`](Self::resolve), so an SDK server
+    /// behind a trusted proxy is matched on its own address rather than the proxy's.
+    ///
+    /// # Returns
+    /// The `ip` parameter when the caller is authorized and the value is a valid address,
+    /// otherwise the resolved caller address
+    pub fn resolve_with_params(&self, peer: IpAddr, headers: &HeaderMap, params: &HashMap<String, String>) -> IpAddr {
+        let caller = self.resolve(peer, headers);
+        let Some(value) = params.get(IP_PARAM) else {
+            return caller;
+        };
+
+        if !self.ip_override.is_authorized(caller, headers) {
+            tracing::debug!(caller = %caller, "Ignoring ip parameter from unauthorized caller");
+            return caller;
+        }
+        match value.trim().parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => {
+                tracing::debug!(caller = %caller, value = %value, "Ignoring invalid ip parameter");
+                caller
+            }
+        }
+    }
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Comma-separated values of every occurrence of `name`, in order
+fn header_values(headers: &HeaderMap, name: &str) -> Option<Vec<String>> {
+    let values: Vec<String> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+/// `for=` nodes of the RFC 7239 `Forwarded` header, in order
+fn forwarded_chain(headers: &HeaderMap) -> Option<Vec<String>> {
+    let elements = header_values(headers, "forwarded")?;
+    let nodes: Vec<String> = elements
+        .iter()
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| value.trim().trim_matches('"').to_string())
+            })
+        })
+        .collect();
+    if nodes.is_empty() {
+        None
+    } else {
+        Some(nodes)
+    }
+}
+
+/// Parse a forwarding node: "1.2.3.4", "1.2.3.4:80", "2001:db8::1" or "[2001:db8::1]:80"
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .and_then(|ip| ip.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn resolver() -> ClientIpResolver {
+        ClientIpResolver::from_config(&["10.0.0.0/8".to_string(), "2001:db8::/32".to_string()]).unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_headers() {
+        let headers = headers(&[("x-forwarded-for", "198.51.100.7")]);
+        assert_eq!(resolver().resolve(ip("203.0.113.1"), &headers), ip("203.0.113.1"));
+    }
+
+    #[test]
+    fn test_rightmost_untrusted_x_forwarded_for() {
+        // The leftmost entry is client-supplied and must not be trusted
+        let headers = headers(&[("x-forwarded-for", "1.1.1.1, 198.51.100.7, 10.0.0.5")]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_multiple_x_forwarded_for_headers() {
+        let headers = headers(&[("x-forwarded-for", "198.51.100.7"), ("x-forwarded-for", "10.0.0.5")]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_forwarded_header_takes_precedence() {
+        let headers = headers(&[
+            ("forwarded", "for=198.51.100.7;proto=https, for=\"[2001:db8::1]:4711\""),
+            ("x-forwarded-for", "192.0.2.1"),
+        ]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_x_real_ip() {
+        let headers = headers(&[("x-real-ip", "198.51.100.7")]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn test_all_trusted_returns_leftmost() {
+        let headers = headers(&[("x-forwarded-for", "10.1.1.1, 10.0.0.5")]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("10.1.1.1"));
+    }
+
+    #[test]
+    fn test_unparseable_hop_stops_walk() {
+        let headers = headers(&[("x-forwarded-for", "198.51.100.7, unknown, 10.0.0.5")]);
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &headers), ip("10.0.0.5"));
+    }
+
+    #[test]
+    fn test_no_trusted_proxies_uses_peer() {
+        let headers = headers(&[("x-forwarded-for", "198.51.100.7")]);
+        assert_eq!(ClientIpResolver::default().resolve(ip("10.0.0.1"), &headers), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_unspecified_peer_is_never_trusted() {
+        let resolver = ClientIpResolver::from_config(&["0.0.0.0/0".to_string()]).unwrap();
+        let headers = headers(&[("x-forwarded-for", "198.51.100.7")]);
+        assert_eq!(resolver.resolve(ip("0.0.0.0"), &headers), ip("0.0.0.0"));
+    }
+
+    fn params(ip_value: &str) -> HashMap<String, String> {
+        HashMap::from([(IP_PARAM.to_string(), ip_value.to_string())])
+    }
+
+    fn override_resolver() -> ClientIpResolver {
+        resolver().with_ip_override(
+            IpOverride::from_config(&IpOverrideConfig {
+                allowed_sources: vec!["192.0.2.0/24".to_string()],
+                api_keys: vec!["sdk-secret".to_string()],
+            })
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_ip_param_ignored_by_default() {
+        let resolved = resolver().resolve_with_params(ip("192.0.2.10"), &HeaderMap::new(), &params("198.51.100.7"));
+        assert_eq!(resolved, ip("192.0.2.10"));
+    }
+
+    #[test]
+    fn test_ip_param_from_allowed_source() {
+        let resolver = override_resolver();
+        let resolved = resolver.resolve_with_params(ip("192.0.2.10"), &HeaderMap::new(), &params("198.51.100.7"));
+        assert_eq!(resolved, ip("198.51.100.7"));
+
+        // The SDK server is matched on its own address when it sits behind a proxy
+        let headers = headers(&[("x-forwarded-for", "192.0.2.10")]);
+        let resolved = resolver.resolve_with_params(ip("10.0.0.1"), &headers, &params("2001:db8::7"));
+        assert_eq!(resolved, ip("2001:db8::7"));
+
+        let resolved = resolver.resolve_with_params(ip("203.0.113.1"), &HeaderMap::new(), &params("198.51.100.7"));
+        assert_eq!(resolved, ip("203.0.113.1"));
+    }
+
+    #[test]
+    fn test_ip_param_with_api_key() {
+        let resolver = override_resolver();
+        let resolved = resolver.resolve_with_params(
+            ip("203.0.113.1"),
+            &headers(&[("x-api-key", "sdk-secret")]),
+            &params("198.51.100.7"),
+        );
+        assert_eq!(resolved, ip("198.51.100.7"));
+
+        let resolved = resolver.resolve_with_params(
+            ip("203.0.113.1"),
+            &headers(&[("x-api-key", "wrong")]),
+            &params("198.51.100.7"),
+        );
+        assert_eq!(resolved, ip("203.0.113.1"));
+    }
+
+    #[test]
+    fn test_invalid_ip_param_falls_back_to_caller() {
+        let resolved =
+            override_resolver().resolve_with_params(ip("192.0.2.10"), &HeaderMap::new(), &params("not-an-ip"));
+        assert_eq!(resolved, ip("192.0.2.10"));
+    }
+}
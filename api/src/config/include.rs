@@ -0,0 +1,180 @@
+// Configuration includes
+// This module reads a configuration file or directory, merging included files into one document
+
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use super::ConfigError;
+
+/// Setting listing the files merged under the file that contains it
+pub(super) const INCLUDE_KEY: &str = "include";
+
+/// Extensions of the files read from a configuration directory
+const CONFIG_EXTENSIONS: &[&str] = &["yaml", "yml", "json", "toml"];
+
+/// Read the configuration at `path` into a single document
+///
+/// * Files are parsed as JSON (`.json`), TOML (`.toml`) or YAML (any other extension).
+/// * A directory is read as its `.yaml`, `.yml`, `.json` and `.toml` files in file name
+///   order, each merged over the previous ones.
+/// * A file's `include` setting (a path or a list of paths, relative to the file) names
+///   files or directories merged first, in order; the file's own settings are merged last.
+///
+/// Merging is deep: mappings are merged key by key, any other value (lists included)
+/// replaces the earlier one. An empty file is an empty mapping.
+pub fn load_document(path: &Path) -> Result<Value, ConfigError> {
+    load_path(path, &mut Vec::new())
+}
+
+/// Merge `overlay` into `base`, key by key for mappings
+pub fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Read a file or directory; `stack` holds the files being read, to detect cycles
+fn load_path(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, ConfigError> {
+    if path.is_dir() {
+        load_directory(path, stack)
+    } else {
+        load_file(path, stack)
+    }
+}
+
+fn load_directory(dir: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, ConfigError> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_config_file(path))
+        .collect();
+    files.sort();
+
+    let mut document = Value::Mapping(Mapping::new());
+    for file in files {
+        merge_values(&mut document, load_file(&file, stack)?);
+    }
+    Ok(document)
+}
+
+fn load_file(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, ConfigError> {
+    let canonical = path.canonicalize()?;
+    if stack.contains(&canonical) {
+        return Err(ConfigError::Include(format!("include cycle through {}", path.display())));
+    }
+
+    let contents = std::fs::read_to_string(path)?;
+    let mut value = parse_document(path, &contents)?;
+    if value.is_null() {
+        value = Value::Mapping(Mapping::new());
+    }
+    let includes = match &mut value {
+        Value::Mapping(mapping) => mapping.remove(INCLUDE_KEY),
+        _ => None,
+    };
+    let Some(includes) = includes else {
+        return Ok(value);
+    };
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut document = Value::Mapping(Mapping::new());
+    for include in include_paths(path, includes)? {
+        let included = base_dir.join(include);
+        let included_value = load_path(&included, stack).map_err(|e| match e {
+            ConfigError::FileNotFound(e) => {
+                ConfigError::Include(format!("{} (included by {}): {}", included.display(), path.display(), e))
+            }
+            other => other,
+        })?;
+        merge_values(&mut document, included_value);
+    }
+    stack.pop();
+
+    merge_values(&mut document, value);
+    Ok(document)
+}
+
+/// Parse `contents` in the format given by the extension of `path`
+pub(super) fn parse_document(path: &Path, contents: &str) -> Result<Value, ConfigError> {
+    if contents.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(serde_json::from_str(contents)?),
+        Some("toml") => Ok(toml::from_str(contents)?),
+        _ => Ok(serde_yaml::from_str(contents)?),
+    }
+}
+
+/// The paths of an `include` setting: a string or a list of strings
+fn include_paths(path: &Path, includes: Value) -> Result<Vec<String>, ConfigError> {
+    let invalid = || ConfigError::Include(format!("{}: include must be a path or a list of paths", path.display()));
+    match includes {
+        Value::String(include) => Ok(vec![include]),
+        Value::Sequence(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(include) => Ok(include),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+fn is_config_file(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    !hidden && CONFIG_EXTENSIONS.contains(&extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_values() {
+        let mut base: Value = serde_yaml::from_str("a: 1\nb: {c: 2, d: [1, 2]}\n").unwrap();
+        let overlay: Value = serde_yaml::from_str("b: {d: [3], e: 4}\nf: 5\n").unwrap();
+        merge_values(&mut base, overlay);
+        let expected: Value = serde_yaml::from_str("a: 1\nb: {c: 2, d: [3], e: 4}\nf: 5\n").unwrap();
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn test_formats_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("10-server.json"), r#"{"server": {"port": 9090, "host": "127.0.0.1"}}"#).unwrap();
+        std::fs::write(dir.path().join("20-logging.toml"), "[logging]\nlevel = \"debug\"\n\n[server]\nport = 9091\n").unwrap();
+        std::fs::write(dir.path().join("30-empty.json"), "").unwrap();
+        let document = load_document(dir.path()).unwrap();
+        let expected: Value = serde_yaml::from_str("server: {port: 9091, host: \"127.0.0.1\"}\nlogging: {level: debug}\n").unwrap();
+        assert_eq!(document, expected);
+
+        std::fs::write(dir.path().join("40-broken.toml"), "[logging\n").unwrap();
+        assert!(matches!(load_document(dir.path()), Err(ConfigError::InvalidToml(_))));
+    }
+
+    #[test]
+    fn test_include_cycle_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.yaml"), "include: b.yaml\n").unwrap();
+        std::fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\n").unwrap();
+        let result = load_document(&dir.path().join("a.yaml"));
+        assert!(matches!(result, Err(ConfigError::Include(msg)) if msg.starts_with("include cycle through") && msg.ends_with("a.yaml")));
+    }
+}
@@ -1,27 +1,220 @@
 // Configuration management module
 // This module handles loading and parsing YAML configuration files
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::cidr::parse_cidrs;
+use crate::enrichment::computed::Expression;
+use crate::enrichment::geohash::MAX_PRECISION as MAX_GEOHASH_PRECISION;
+use crate::enrichment::geoip::EU_COUNTRY_CODES;
+use crate::streaming::TopicTemplate;
+use crate::transformer::campaign::CAMPAIGN_FIELDS;
+
+mod include;
+mod redact;
+mod remote;
+mod secrets;
+mod units;
+
+pub use redact::{effective_config, REDACTED};
+pub use remote::{fetch_remote, is_remote, wait_for_change, RemoteDocument};
+pub use secrets::resolve_secrets;
+pub use units::parse_duration;
 
 /// Main configuration structure containing all application settings
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Config {
+    /// HTTP listener (default 0.0.0.0:8080)
+    #[serde(default)]
     pub server: ServerConfig,
+    /// Event sink (default: JSON lines on stdout)
+    #[serde(default)]
     pub streaming: StreamingConfig,
+    /// GeoIP databases (default: no geolocation)
+    #[serde(default)]
     pub geoip: GeoIpConfig,
+    /// Log output (default level: info)
+    #[serde(default)]
     pub logging: LoggingConfig,
+    /// Rules for dropping events before they reach the streaming service
+    #[serde(default)]
+    pub filters: FilterConfig,
+    /// Event name normalization and allowlists for /track/
+    #[serde(default)]
+    pub events: EventNameConfig,
+    /// UTM/campaign parameter extraction
+    #[serde(default)]
+    pub campaign: CampaignConfig,
+    /// Conversion of e_*/u_* parameter values
+    #[serde(default)]
+    pub parameters: ParameterConfig,
+    /// Parameters accepted or dropped per project
+    #[serde(default)]
+    pub fields: FieldPolicyConfig,
+    /// User-Agent parser selection
+    #[serde(default)]
+    pub user_agent: UserAgentConfig,
+    /// Salted client IP hashing (disabled when unset)
+    #[serde(default)]
+    pub ip_hash: Option<IpHashConfig>,
+    /// Device fingerprint hashing (disabled when unset)
+    #[serde(default)]
+    pub fingerprint: Option<FingerprintConfig>,
+    /// Session tracking (disabled when unset)
+    #[serde(default)]
+    pub session: Option<SessionConfig>,
+    /// Enrichment stages and their order
+    #[serde(default)]
+    pub enrichment: EnrichmentConfig,
+    /// Registered projects: API keys, allowed origins, topic, sampling and schema
+    #[serde(default)]
+    pub projects: HashMap<String, ProjectConfig>,
+    /// Subsystems switched off to shed load (default: all on)
+    #[serde(default)]
+    pub features: FeaturesConfig,
+}
+
+/// Subsystems that can be switched off, e.g. to shed load during an incident
+///
+/// All can be changed at runtime through /admin/settings, except that `geoip` can only
+/// be switched back on if it was on at startup (the database is not loaded otherwise).
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FeaturesConfig {
+    /// GeoIP lookups (the `geoip` enrichment stage); off at startup skips loading the database
+    #[serde(default = "default_true")]
+    pub geoip: bool,
+    /// User-Agent parsing (the `user_agent` enrichment stage); bot and headless
+    /// filter rules need it
+    #[serde(default = "default_true")]
+    pub user_agent: bool,
+    /// Session stitching (the `session` enrichment stage)
+    #[serde(default = "default_true")]
+    pub sessions: bool,
+    /// Checks of `projects.<id>.schema.required` on /track/ and /identify
+    #[serde(default = "default_true")]
+    pub schema_validation: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        FeaturesConfig {
+            geoip: true,
+            user_agent: true,
+            sessions: true,
+            schema_validation: true,
+        }
+    }
 }
 
 /// Server configuration for HTTP API
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ServerConfig {
+    #[serde(default = "default_server_host")]
     pub host: String,
+    #[serde(default = "default_server_port")]
     pub port: u16,
+    /// Reverse proxies / load balancers (addresses or CIDR ranges) whose
+    /// X-Forwarded-For, Forwarded and X-Real-IP headers are trusted
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// Callers allowed to override the client IP with the `ip` parameter
+    #[serde(default)]
+    pub ip_override: IpOverrideConfig,
+    /// Listeners serving the API; when empty, plain HTTP on `host`:`port`
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Keys authorizing the admin endpoints (sent in X-Api-Key); disabled when empty
+    #[serde(default)]
+    pub admin_api_keys: Vec<String>,
+    /// File holding settings changed through the admin API with `persist=true`,
+    /// merged over the configuration at startup
+    #[serde(default)]
+    pub runtime_settings_path: Option<String>,
+}
+
+/// A socket the API is served on; every listener serves the same endpoints
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ListenerConfig {
+    /// Plain HTTP on a TCP address such as "0.0.0.0:8080"
+    Http { address: String },
+    /// HTTPS (HTTP/1.1 and HTTP/2) on a TCP address, with PEM-encoded certificate
+    /// chain and private key files
+    Https {
+        address: String,
+        cert_path: String,
+        key_path: String,
+    },
+    /// Plain HTTP on a Unix domain socket, e.g. behind a local reverse proxy
+    ///
+    /// Requests arriving here have the peer address 127.0.0.1; list it in
+    /// `trusted_proxies` to use the proxy's forwarded headers. `permissions` is the
+    /// octal file mode of the socket (e.g. "660").
+    Unix {
+        path: String,
+        #[serde(default)]
+        permissions: Option<String>,
+    },
+}
+
+impl ListenerConfig {
+    /// Address or socket path, for logs
+    pub fn describe(&self) -> String {
+        match self {
+            ListenerConfig::Http { address } => format!("http://{}", address),
+            ListenerConfig::Https { address, .. } => format!("https://{}", address),
+            ListenerConfig::Unix { path, .. } => format!("unix:{}", path),
+        }
+    }
+}
+
+/// Octal file mode of a Unix socket listener, e.g. "660"
+pub fn parse_socket_permissions(permissions: &str) -> Option<u32> {
+    u32::from_str_radix(permissions, 8).ok().filter(|mode| *mode <= 0o777)
+}
+
+fn default_server_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_server_port() -> u16 {
+    8080
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: default_server_host(),
+            port: default_server_port(),
+            trusted_proxies: Vec::new(),
+            ip_override: IpOverrideConfig::default(),
+            listeners: Vec::new(),
+            admin_api_keys: Vec::new(),
+            runtime_settings_path: None,
+        }
+    }
+}
+
+/// Trusted `ip` parameter for server-side SDKs forwarding events on behalf of visitors
+///
+/// The parameter is only honored when the caller's address is in `allowed_sources`
+/// or the request carries one of `api_keys` in the `X-Api-Key` header. With both
+/// lists empty (the default) the parameter is ignored.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct IpOverrideConfig {
+    /// Caller addresses or CIDR ranges allowed to set the client IP
+    #[serde(default)]
+    pub allowed_sources: Vec<String>,
+    /// API keys allowed to set the client IP
+    #[serde(default)]
+    pub api_keys: Vec<String>,
 }
 
 /// Streaming service configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct StreamingConfig {
+    #[serde(default)]
     pub service_type: StreamingServiceType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kafka: Option<KafkaConfig>,
@@ -29,178 +222,2903 @@ pub struct StreamingConfig {
     pub kinesis: Option<KinesisConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pulsar: Option<PulsarConfig>,
+    /// Background health probing of the streaming service
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    /// Secondary sink used while the primary sink's circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<FallbackConfig>,
+    /// What an HTTP 200 acknowledges: broker delivery or acceptance into a local queue
+    #[serde(default)]
+    pub ack_mode: AckMode,
+    /// Local queue used when `ack_mode` is `queued`
+    #[serde(default)]
+    pub queue: QueueConfig,
+    /// Collector metadata attached to every outgoing event (disabled when unset)
+    #[serde(default)]
+    pub metadata: Option<MetadataConfig>,
+    /// Data-residency routes: events from the listed countries go to a dedicated sink
+    #[serde(default)]
+    pub residency: Vec<ResidencyRule>,
+    /// Payload layout of events sent to the broker sinks
+    #[serde(default)]
+    pub output: OutputConfig,
+    /// Retention TTLs stamped on outgoing events as `expires_at` (disabled when unset)
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    /// Topic/stream template per project, copied from `projects.<id>.topic` when the
+    /// configuration is loaded
+    #[serde(skip)]
+    pub project_topics: HashMap<String, String>,
+}
+
+/// Payload layout of streamed events
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct OutputConfig {
+    /// Nested JSON (default) or a single flat object
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// How nested keys are joined in `flat` format
+    #[serde(default)]
+    pub separator: KeySeparator,
+}
+
+/// JSON layout of streamed events
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The event structure as is, with visit, profile, ... objects
+    #[default]
+    Nested,
+    /// One object without nesting, e.g. `visit.url` instead of `{"visit": {"url": ...}}`
+    Flat,
+}
+
+/// Serialization of the payload bytes a broker sink writes
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// UTF-8 JSON text
+    #[default]
+    Json,
+    /// MessagePack, with field names kept as map keys
+    #[serde(rename = "msgpack")]
+    MsgPack,
+    /// CBOR (RFC 8949)
+    Cbor,
+}
+
+impl WireFormat {
+    /// Name used in configuration and logs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "json",
+            WireFormat::MsgPack => "msgpack",
+            WireFormat::Cbor => "cbor",
+        }
+    }
+
+    /// Media type sent in the `content_type` message header
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::MsgPack => "application/msgpack",
+            WireFormat::Cbor => "application/cbor",
+        }
+    }
+}
+
+/// Separator joining nested keys in `flat` output
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySeparator {
+    /// `visit.url`
+    #[default]
+    Dot,
+    /// `visit_url`
+    Underscore,
+}
+
+impl KeySeparator {
+    /// Separator placed between the parts of a flattened key
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeySeparator::Dot => ".",
+            KeySeparator::Underscore => "_",
+        }
+    }
+}
+
+/// Data-residency routing rule
+///
+/// Events whose GeoIP country matches `countries` are sent to this rule's sink
+/// instead of the default one. Rules are checked in order; the first match wins.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ResidencyRule {
+    /// Name used in logs (e.g. "eu")
+    pub name: String,
+    /// ISO 3166-1 alpha-2 country codes; "EU" expands to all EU member states
+    pub countries: Vec<String>,
+    /// Sink type for this rule
+    pub service_type: StreamingServiceType,
+    #[serde(default)]
+    pub kafka: Option<KafkaConfig>,
+    #[serde(default)]
+    pub kinesis: Option<KinesisConfig>,
+    #[serde(default)]
+    pub pulsar: Option<PulsarConfig>,
+}
+
+impl ResidencyRule {
+    /// Upper-cased country codes this rule matches, with "EU" expanded
+    pub fn country_codes(&self) -> Vec<String> {
+        expand_country_codes(&self.countries)
+    }
+
+    /// Streaming configuration containing only this rule's sink
+    pub fn sink_config(&self) -> StreamingConfig {
+        StreamingConfig {
+            service_type: self.service_type.clone(),
+            kafka: self.kafka.clone(),
+            kinesis: self.kinesis.clone(),
+            pulsar: self.pulsar.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Upper-case, sort and deduplicate country codes, expanding "EU" to all EU member states
+pub fn expand_country_codes(countries: &[String]) -> Vec<String> {
+    let mut codes = Vec::new();
+    for country in countries {
+        if country.eq_ignore_ascii_case("EU") {
+            codes.extend(EU_COUNTRY_CODES.iter().map(|code| code.to_string()));
+        } else {
+            codes.push(country.to_ascii_uppercase());
+        }
+    }
+    codes.sort();
+    codes.dedup();
+    codes
+}
+
+/// First entry that is not a two-letter country code ("EU" counts as one)
+fn find_invalid_country_code(countries: &[String]) -> Option<&String> {
+    countries
+        .iter()
+        .find(|c| c.len() != 2 || !c.chars().all(|ch| ch.is_ascii_alphabetic()))
+}
+
+/// Collector metadata configuration
+///
+/// When present, every streamed event carries a `_meta` object with these values
+/// plus the ingest timestamp.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MetadataConfig {
+    /// Hostname to report (defaults to the `HOSTNAME` environment variable or system hostname)
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Deployment region to report (e.g. "eu-west-1")
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Pipeline version to report (defaults to the crate version)
+    #[serde(default)]
+    pub pipeline_version: Option<String>,
+}
+
+/// Retention time-to-live stamped on streamed events
+///
+/// Events get `expires_at` = event timestamp + TTL, so storage layers can expire
+/// records without a per-project retention table of their own. Events of projects
+/// without a TTL (and with no `default_days`) are not stamped.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RetentionConfig {
+    /// TTL in days for projects not listed in `projects`
+    #[serde(default)]
+    pub default_days: Option<u32>,
+    /// TTL in days per project
+    #[serde(default)]
+    pub projects: HashMap<String, u32>,
+}
+
+impl RetentionConfig {
+    /// TTL in days of `project`'s events, if any
+    pub fn ttl_days(&self, project: Option<&str>) -> Option<u32> {
+        project
+            .and_then(|project| self.projects.get(project).copied())
+            .or(self.default_days)
+    }
+}
+
+/// Acknowledgment mode for tracking requests
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AckMode {
+    /// Respond once the streaming service has acknowledged the event (durable)
+    #[default]
+    Broker,
+    /// Respond once the event is in the local in-memory queue (lowest latency)
+    Queued,
+}
+
+/// Local event queue configuration (used with `ack_mode: queued`)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct QueueConfig {
+    /// Maximum number of queued events; sends fail while the queue is full
+    #[serde(default = "default_queue_capacity")]
+    pub capacity: usize,
+    /// Number of background tasks delivering queued events
+    #[serde(default = "default_queue_workers")]
+    pub workers: usize,
+    /// How long shutdown waits for the queued events to be delivered, in milliseconds
+    #[serde(default = "default_queue_drain_timeout_ms", deserialize_with = "units::millis")]
+    pub drain_timeout_ms: u64,
+}
+
+fn default_queue_capacity() -> usize {
+    10_000
+}
+
+fn default_queue_workers() -> usize {
+    16
+}
+
+fn default_queue_drain_timeout_ms() -> u64 {
+    30_000
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capacity: default_queue_capacity(),
+            workers: default_queue_workers(),
+            drain_timeout_ms: default_queue_drain_timeout_ms(),
+        }
+    }
+}
+
+/// Fallback sink configuration
+///
+/// Events are appended as JSON lines to `path` while the primary sink is failing
+/// and replayed into the primary once it recovers.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FallbackConfig {
+    /// Path of the JSON-lines file that receives fallback events
+    pub path: String,
+    /// Consecutive primary failures that open the circuit breaker
+    #[serde(default = "default_fallback_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds the breaker stays open before a trial send to the primary
+    #[serde(default = "default_fallback_open_secs", deserialize_with = "units::secs")]
+    pub open_secs: u64,
+    /// Seconds between attempts to replay stored events into the primary
+    #[serde(default = "default_fallback_replay_interval_secs", deserialize_with = "units::secs")]
+    pub replay_interval_secs: u64,
+}
+
+fn default_fallback_failure_threshold() -> u32 {
+    5
+}
+
+fn default_fallback_open_secs() -> u64 {
+    30
+}
+
+fn default_fallback_replay_interval_secs() -> u64 {
+    60
+}
+
+/// Background health check configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthCheckConfig {
+    /// Seconds between health probes
+    #[serde(default = "default_health_check_interval_secs", deserialize_with = "units::secs")]
+    pub interval_secs: u64,
+    /// Seconds to wait for a single probe before marking the service unhealthy
+    #[serde(default = "default_health_check_timeout_secs", deserialize_with = "units::secs")]
+    pub timeout_secs: u64,
+    /// Buffered events above which /health/details reports the queue as degraded
+    #[serde(default)]
+    pub max_queue_depth: Option<u64>,
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_health_check_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        HealthCheckConfig {
+            interval_secs: default_health_check_interval_secs(),
+            timeout_secs: default_health_check_timeout_secs(),
+            max_queue_depth: None,
+        }
+    }
 }
 
 /// Enum representing the type of streaming service to use
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum StreamingServiceType {
+    /// JSON lines on standard output, for local development
+    #[default]
+    Stdout,
     Kafka,
     Kinesis,
     Pulsar,
 }
 
+impl StreamingServiceType {
+    /// Lowercase name of the service type, as written in the configuration
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamingServiceType::Stdout => "stdout",
+            StreamingServiceType::Kafka => "kafka",
+            StreamingServiceType::Kinesis => "kinesis",
+            StreamingServiceType::Pulsar => "pulsar",
+        }
+    }
+}
+
 /// Kafka-specific configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct KafkaConfig {
     pub brokers: Vec<String>,
+    /// Topic name, optionally templated with `{project}` / `{event}`
     pub topic: String,
+    /// Per-event-name topic overrides (e.g. identify → "analytics-profiles")
+    #[serde(default)]
+    pub event_topics: HashMap<String, String>,
+    /// Maximum time in milliseconds to wait for a send before treating it as failed (default 5000)
+    #[serde(default, deserialize_with = "units::opt_millis")]
+    pub send_timeout_ms: Option<u64>,
+    /// Maximum concurrent in-flight sends (unlimited when unset)
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Payload serialization: json (default), msgpack or cbor
+    #[serde(default)]
+    pub format: WireFormat,
+    /// Enables exactly-once transactional mode when set
+    /// Must be unique per producer instance (e.g. include the pod name)
+    #[serde(default)]
+    pub transactional_id: Option<String>,
+    /// Create missing topics at startup (opt-in)
+    #[serde(default)]
+    pub create_topics: Option<KafkaTopicCreationConfig>,
+    /// SASL authentication, e.g. for managed Kafka services
+    #[serde(default)]
+    pub sasl: Option<KafkaSaslConfig>,
+}
+
+/// Kafka SASL credentials
+///
+/// Keep the password out of the file with `password_file` or a secret reference
+/// such as `password: "vault://secret/kafka#password"`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KafkaSaslConfig {
+    /// PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512
+    #[serde(default = "default_kafka_sasl_mechanism")]
+    pub mechanism: String,
+    /// SASL_SSL (default) or SASL_PLAINTEXT
+    #[serde(default = "default_kafka_security_protocol")]
+    pub security_protocol: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Supported `streaming.kafka.sasl.mechanism` values
+pub const KAFKA_SASL_MECHANISMS: &[&str] = &["PLAIN", "SCRAM-SHA-256", "SCRAM-SHA-512"];
+
+/// Supported `streaming.kafka.sasl.security_protocol` values
+pub const KAFKA_SECURITY_PROTOCOLS: &[&str] = &["SASL_SSL", "SASL_PLAINTEXT"];
+
+fn default_kafka_sasl_mechanism() -> String {
+    "PLAIN".to_string()
+}
+
+fn default_kafka_security_protocol() -> String {
+    "SASL_SSL".to_string()
+}
+
+/// Settings for topics created at startup when missing
+///
+/// Only static topic names (the topic and event_topics without placeholders) can be
+/// created up front; templated topics rely on broker-side auto-creation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KafkaTopicCreationConfig {
+    /// Number of partitions for each created topic
+    #[serde(default = "default_topic_partitions")]
+    pub partitions: i32,
+    /// Replication factor for each created topic
+    #[serde(default = "default_topic_replication_factor")]
+    pub replication_factor: i32,
+    /// Topic `retention.ms`; broker default when unset, -1 for unlimited
+    #[serde(default, deserialize_with = "units::opt_signed_millis")]
+    pub retention_ms: Option<i64>,
+}
+
+fn default_topic_partitions() -> i32 {
+    1
+}
+
+fn default_topic_replication_factor() -> i32 {
+    1
 }
 
 /// AWS Kinesis-specific configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct KinesisConfig {
     pub region: String,
+    /// Stream name, optionally templated with `{project}` / `{event}`
     pub stream_name: String,
+    /// Per-event-name stream overrides
+    #[serde(default)]
+    pub event_streams: HashMap<String, String>,
+    /// Maximum time in milliseconds to wait for a send before treating it as failed (default 5000)
+    #[serde(default, deserialize_with = "units::opt_millis")]
+    pub send_timeout_ms: Option<u64>,
+    /// Maximum concurrent in-flight sends (unlimited when unset)
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Payload serialization: json (default), msgpack or cbor
+    #[serde(default)]
+    pub format: WireFormat,
 }
 
 /// Apache Pulsar-specific configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct PulsarConfig {
     pub url: String,
+    /// Topic name, optionally templated with `{project}` / `{event}`
     pub topic: String,
+    /// Per-event-name topic overrides
+    #[serde(default)]
+    pub event_topics: HashMap<String, String>,
+    /// Maximum time in milliseconds to wait for a send before treating it as failed (default 5000)
+    #[serde(default, deserialize_with = "units::opt_millis")]
+    pub send_timeout_ms: Option<u64>,
+    /// Maximum concurrent in-flight sends (unlimited when unset)
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Payload serialization: json (default), msgpack or cbor
+    #[serde(default)]
+    pub format: WireFormat,
+    /// Topic receiving events that could not be delivered, optionally templated
+    #[serde(default)]
+    pub dead_letter_topic: Option<String>,
+    /// Additional delivery attempts before an event is dead-lettered (or the send fails)
+    #[serde(default)]
+    pub max_redeliveries: u32,
 }
 
 /// GeoIP database configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct GeoIpConfig {
+    /// GeoIP database file; geolocation is disabled when empty
+    #[serde(default)]
     pub database_path: String,
+    /// Database format of `database_path`
+    #[serde(default)]
+    pub backend: GeoIpBackendType,
+    /// GeoIP2-ISP or GeoLite2-ASN database (ISP, organization, ASN)
+    #[serde(default)]
+    pub isp_database_path: Option<String>,
+    /// GeoIP2-Connection-Type database
+    #[serde(default)]
+    pub connection_type_database_path: Option<String>,
+    /// GeoIP2-Anonymous-IP database (VPN, proxy, hosting, Tor flags)
+    #[serde(default)]
+    pub anonymous_ip_database_path: Option<String>,
+    /// Seconds between checks for updated database files (0 disables reloading)
+    #[serde(default, deserialize_with = "units::secs")]
+    pub reload_interval_secs: u64,
+    /// Cache of recent lookup results (disabled when unset)
+    #[serde(default)]
+    pub cache: Option<GeoIpCacheConfig>,
+    /// Look up private, loopback, link-local and CGNAT addresses too (e.g. for a
+    /// custom database mapping office ranges); they are skipped by default
+    #[serde(default)]
+    pub lookup_internal_ips: bool,
+    /// Mark events from skipped internal addresses with `network.is_internal`
+    #[serde(default)]
+    pub tag_internal_ips: bool,
+    /// Days after its build date a database is reported as degraded on /health/details
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+}
+
+/// GeoIP database backends
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoIpBackendType {
+    /// MaxMind GeoIP2/GeoLite2 .mmdb files
+    #[default]
+    MaxMind,
+    /// IP2Location .BIN files
+    Ip2Location,
+}
+
+/// GeoIP lookup cache configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GeoIpCacheConfig {
+    /// Maximum number of cached results
+    #[serde(default = "default_geoip_cache_max_entries")]
+    pub max_entries: usize,
+    /// Seconds a cached result stays valid
+    #[serde(default = "default_geoip_cache_ttl_secs", deserialize_with = "units::secs")]
+    pub ttl_secs: u64,
+    /// IPv4 prefix length used as the cache key (32 = per address, 24 = per /24)
+    #[serde(default = "default_geoip_cache_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    /// IPv6 prefix length used as the cache key (128 = per address, 64 = per /64)
+    #[serde(default = "default_geoip_cache_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+}
+
+fn default_geoip_cache_max_entries() -> usize {
+    10_000
+}
+
+fn default_geoip_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_geoip_cache_ipv4_prefix() -> u8 {
+    32
+}
+
+fn default_geoip_cache_ipv6_prefix() -> u8 {
+    128
+}
+
+impl Default for GeoIpCacheConfig {
+    fn default() -> Self {
+        GeoIpCacheConfig {
+            max_entries: default_geoip_cache_max_entries(),
+            ttl_secs: default_geoip_cache_ttl_secs(),
+            ipv4_prefix: default_geoip_cache_ipv4_prefix(),
+            ipv6_prefix: default_geoip_cache_ipv6_prefix(),
+        }
+    }
 }
 
 /// Logging configuration
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
     pub level: String,
+    /// Push metrics to an OpenTelemetry collector (disabled when absent)
+    #[serde(default)]
+    pub otlp: Option<OtlpConfig>,
+    /// Send metrics to a StatsD server or DogStatsD agent over UDP (disabled when absent)
+    #[serde(default)]
+    pub statsd: Option<StatsdConfig>,
+    /// HTTP access log, written apart from the application logs
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    /// Which projects get their own label on the ingest metrics
+    #[serde(default)]
+    pub project_metrics: ProjectMetricsConfig,
+    /// Which topics get their own label on the sink metrics
+    #[serde(default)]
+    pub topic_metrics: TopicMetricsConfig,
+    /// Sample of raw ingest requests kept to reproduce data-quality bugs (disabled when absent)
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
 }
 
-/// Error type for configuration loading failures
-#[derive(Debug)]
-pub enum ConfigError {
-    FileNotFound(std::io::Error),
-    InvalidYaml(serde_yaml::Error),
-    MissingFields(String),
+fn default_log_level() -> String {
+    "info".to_string()
 }
 
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConfigError::FileNotFound(e) => write!(f, "Configuration file not found: {}", e),
-            ConfigError::InvalidYaml(e) => write!(f, "Invalid YAML syntax: {}", e),
-            ConfigError::MissingFields(msg) => write!(f, "Missing required fields: {}", msg),
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: default_log_level(),
+            otlp: None,
+            statsd: None,
+            access_log: AccessLogConfig::default(),
+            project_metrics: ProjectMetricsConfig::default(),
+            topic_metrics: TopicMetricsConfig::default(),
+            audit: None,
         }
     }
 }
 
-impl std::error::Error for ConfigError {}
+/// OTLP metrics export configuration
+///
+/// The sink metrics served on `/metrics` are also pushed every `interval_secs` to
+/// `{endpoint}/v1/metrics` as OTLP/HTTP with JSON encoding.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OtlpConfig {
+    /// Collector base URL (e.g. "http://otel-collector:4318")
+    pub endpoint: String,
+    /// Seconds between pushes
+    #[serde(default = "default_otlp_interval_secs", deserialize_with = "units::secs")]
+    pub interval_secs: u64,
+    /// Timeout of a push in milliseconds
+    #[serde(default = "default_otlp_timeout_ms", deserialize_with = "units::millis")]
+    pub timeout_ms: u64,
+    /// Extra request headers (e.g. an API key for a hosted collector)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// `service.name` resource attribute
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+    /// Additional resource attributes (e.g. `deployment.environment: production`)
+    #[serde(default)]
+    pub resource_attributes: HashMap<String, String>,
+}
 
-impl From<std::io::Error> for ConfigError {
-    fn from(err: std::io::Error) -> Self {
-        ConfigError::FileNotFound(err)
-    }
+fn default_otlp_interval_secs() -> u64 {
+    60
 }
 
-impl From<serde_yaml::Error> for ConfigError {
-    fn from(err: serde_yaml::Error) -> Self {
-        ConfigError::InvalidYaml(err)
-    }
+fn default_otlp_timeout_ms() -> u64 {
+    10_000
 }
 
-/// Load configuration from a YAML file
+fn default_otlp_service_name() -> String {
+    "rust-analytics-api".to_string()
+}
+
+/// StatsD metrics export configuration
 ///
-/// # Arguments
-/// * `path` - Path to the YAML configuration file
+/// The metrics served on `/metrics` are also sent every `interval_secs` as StatsD
+/// lines over UDP: counters as the increment since the last flush, gauges as their
+/// value and histograms as the new observations of each bucket.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StatsdConfig {
+    /// `host:port` of the StatsD server or DogStatsD agent
+    #[serde(default = "default_statsd_address")]
+    pub address: String,
+    /// Line protocol: DogStatsD tags, or labels folded into plain StatsD metric names
+    #[serde(default)]
+    pub flavor: StatsdFlavor,
+    /// Prepended to every metric name, followed by a dot (e.g. "penrose")
+    #[serde(default)]
+    pub prefix: String,
+    /// Tags added to every line (DogStatsD only, e.g. `env: production`)
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// Seconds between flushes
+    #[serde(default = "default_statsd_interval_secs", deserialize_with = "units::secs")]
+    pub interval_secs: u64,
+    /// Largest UDP datagram sent; lines are batched up to this size
+    #[serde(default = "default_statsd_max_packet_bytes")]
+    pub max_packet_bytes: usize,
+}
+
+/// StatsD line protocol
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsdFlavor {
+    /// Labels sent as `|#name:value` tags
+    #[default]
+    Dogstatsd,
+    /// Label values appended to the metric name (`name.value`); `tags` are ignored
+    Statsd,
+}
+
+/// Characters with a meaning in StatsD lines, not allowed in metric and tag names
+pub const STATSD_RESERVED: &[char] = &[':', '|', '@', '#', ','];
+
+fn default_statsd_address() -> String {
+    "127.0.0.1:8125".to_string()
+}
+
+fn default_statsd_interval_secs() -> u64 {
+    10
+}
+
+fn default_statsd_max_packet_bytes() -> usize {
+    1432
+}
+
+/// HTTP access log configuration
 ///
-/// # Returns
-/// * `Ok(Config)` - Successfully loaded and parsed configuration
-/// * `Err(ConfigError)` - Error loading or parsing the configuration
+/// One line per request (method, path, status, latency, response bytes, client IP,
+/// user agent, request ID). Lines bypass the application log level and filters.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AccessLogConfig {
+    /// Write the access log
+    #[serde(default)]
+    pub enabled: bool,
+    /// Line format
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    /// File the lines are appended to (standard output when unset)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Format of the access log lines
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// One JSON object per line
+    #[default]
+    Json,
+    /// Common Log Format, followed by the user agent, request ID and latency
+    Common,
+}
+
+/// Raw request audit log configuration
 ///
-/// # Errors
-/// * `ConfigError::FileNotFound` - Configuration file does not exist
-/// * `ConfigError::InvalidYaml` - YAML syntax is invalid
-/// * `ConfigError::MissingFields` - Required configuration fields are missing
+/// A random `sample_rate` share of the requests to /track/, /identify and /update is
+/// recorded as received (query and form parameters in order, headers), before any
+/// validation, with personal data redacted. Records go to the file at `path` or,
+/// with a Kafka sink, to the Kafka topic `topic`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AuditConfig {
+    /// Share of the requests recorded, between 0 and 1
+    #[serde(default = "default_audit_sample_rate")]
+    pub sample_rate: f64,
+    /// File the records are appended to, one JSON object per line
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Kafka topic the records are produced to, on the brokers of `streaming.kafka`
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Parameters whose values are masked; a trailing `*` matches any suffix
+    #[serde(default = "default_audit_redact_params")]
+    pub redact_params: Vec<String>,
+    /// Headers whose values are replaced with `********` (case-insensitive)
+    #[serde(default = "default_audit_redact_headers")]
+    pub redact_headers: Vec<String>,
+}
+
+fn default_audit_sample_rate() -> f64 {
+    0.001
+}
+
+fn default_audit_redact_params() -> Vec<String> {
+    ["u_*", "ip", "payload"].iter().map(|name| name.to_string()).collect()
+}
+
+fn default_audit_redact_headers() -> Vec<String> {
+    [
+        "authorization",
+        "cookie",
+        "proxy-authorization",
+        "x-api-key",
+        "x-forwarded-for",
+        "x-real-ip",
+        "forwarded",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            sample_rate: default_audit_sample_rate(),
+            path: None,
+            topic: None,
+            redact_params: default_audit_redact_params(),
+            redact_headers: default_audit_redact_headers(),
+        }
+    }
+}
+
+/// Cardinality controls of the `project` label on the ingest metrics
 ///
-/// # Example
-/// ```no_run
-/// use api::config::load_config;
+/// Every distinct label value is a new series in the metrics backend, and project
+/// names come from the request, so only a bounded set of projects is labeled by name;
+/// the others are counted under `other`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProjectMetricsConfig {
+    /// Projects labeled by name. When empty, the first `max_projects` projects seen
+    /// since start are labeled instead.
+    #[serde(default)]
+    pub allowed_projects: Vec<String>,
+    /// Most projects labeled by name when `allowed_projects` is empty (0 labels none)
+    #[serde(default = "default_max_project_labels")]
+    pub max_projects: usize,
+}
+
+fn default_max_project_labels() -> usize {
+    100
+}
+
+impl Default for ProjectMetricsConfig {
+    fn default() -> Self {
+        ProjectMetricsConfig {
+            allowed_projects: Vec::new(),
+            max_projects: default_max_project_labels(),
+        }
+    }
+}
+
+/// Cardinality controls of the `topic` label on the sink metrics
 ///
-/// let config = load_config("config.yaml").expect("Failed to load config");
-/// println!("Server will run on {}:{}", config.server.host, config.server.port);
-/// ```
-pub fn load_config(path: &str) -> Result<Config, ConfigError> {
-    // Read the file contents
-    let contents = std::fs::read_to_string(path)?;
-    
-    // Parse YAML into Config struct
-    let config: Config = serde_yaml::from_str(&contents)?;
-    
-    // Validate that required fields are present
-    validate_config(&config)?;
-    
-    Ok(config)
+/// Topic templates with `{event}` or `{project}` render topic names from the request,
+/// so like projects only a bounded set of topics is labeled by name; the others are
+/// counted under `other`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TopicMetricsConfig {
+    /// Topics labeled by name. When empty, the first `max_topics` topics sent to
+    /// since start are labeled instead.
+    #[serde(default)]
+    pub allowed_topics: Vec<String>,
+    /// Most topics labeled by name when `allowed_topics` is empty (0 labels none)
+    #[serde(default = "default_max_topic_labels")]
+    pub max_topics: usize,
 }
 
-/// Validate that all required configuration fields are present and valid
-fn validate_config(config: &Config) -> Result<(), ConfigError> {
-    // Validate server config
-    if config.server.host.is_empty() {
-        return Err(ConfigError::MissingFields("server.host is empty".to_string()));
+fn default_max_topic_labels() -> usize {
+    100
+}
+
+impl Default for TopicMetricsConfig {
+    fn default() -> Self {
+        TopicMetricsConfig {
+            allowed_topics: Vec::new(),
+            max_topics: default_max_topic_labels(),
+        }
     }
-    if config.server.port == 0 {
-        return Err(ConfigError::MissingFields("server.port must be non-zero".to_string()));
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        OtlpConfig {
+            endpoint: String::new(),
+            interval_secs: default_otlp_interval_secs(),
+            timeout_ms: default_otlp_timeout_ms(),
+            headers: HashMap::new(),
+            service_name: default_otlp_service_name(),
+            resource_attributes: HashMap::new(),
+        }
     }
-    
-    // Validate streaming config based on service type
-    match config.streaming.service_type {
-        StreamingServiceType::Kafka => {
-            if let Some(ref kafka) = config.streaming.kafka {
-                if kafka.brokers.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.kafka.brokers is empty".to_string()));
-                }
-                if kafka.topic.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.kafka.topic is empty".to_string()));
-                }
-            } else {
-                return Err(ConfigError::MissingFields("streaming.kafka configuration is required when service_type is kafka".to_string()));
-            }
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        StatsdConfig {
+            address: default_statsd_address(),
+            flavor: StatsdFlavor::default(),
+            prefix: String::new(),
+            tags: HashMap::new(),
+            interval_secs: default_statsd_interval_secs(),
+            max_packet_bytes: default_statsd_max_packet_bytes(),
         }
-        StreamingServiceType::Kinesis => {
-            if let Some(ref kinesis) = config.streaming.kinesis {
-                if kinesis.region.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.kinesis.region is empty".to_string()));
-                }
-                if kinesis.stream_name.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.kinesis.stream_name is empty".to_string()));
+    }
+}
+
+/// Event name normalization and allowlist configuration for /track/
+///
+/// Names are trimmed and lowercased (when enabled), then mapped through `aliases`.
+/// When an allowlist applies to the event's project, names it does not match are
+/// rejected or renamed to `bucket_name`, depending on `unknown`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EventNameConfig {
+    /// Strip surrounding whitespace
+    #[serde(default)]
+    pub trim: bool,
+    /// Lowercase names
+    #[serde(default)]
+    pub lowercase: bool,
+    /// Canonical name per alias (e.g. "Page View" -> "pageview")
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Names (or `*`/`?` globs) accepted for projects without their own list
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    /// Names (or globs) accepted per project, replacing `allowed`
+    #[serde(default)]
+    pub projects: HashMap<String, Vec<String>>,
+    /// What happens to names outside the allowlist
+    #[serde(default)]
+    pub unknown: UnknownEventAction,
+    /// Name given to events outside the allowlist with `unknown: bucket`
+    #[serde(default = "default_unknown_event_bucket")]
+    pub bucket_name: String,
+}
+
+fn default_unknown_event_bucket() -> String {
+    "other".to_string()
+}
+
+impl Default for EventNameConfig {
+    fn default() -> Self {
+        EventNameConfig {
+            trim: false,
+            lowercase: false,
+            aliases: HashMap::new(),
+            allowed: Vec::new(),
+            projects: HashMap::new(),
+            unknown: UnknownEventAction::default(),
+            bucket_name: default_unknown_event_bucket(),
+        }
+    }
+}
+
+/// Parameter allowlist and blocklist, globally and per project
+///
+/// Parameters (or `*`/`?` globs such as `e_*`) outside the allowlist or on the
+/// blocklist are dropped before transformation. Project rules replace the global
+/// ones. `project`, `event`, `id`, `timestamp` and `cookie` are always accepted.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FieldPolicyConfig {
+    /// Parameters accepted for projects without their own rules (empty: all)
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    /// Parameters dropped for projects without their own rules
+    #[serde(default)]
+    pub blocked: Vec<String>,
+    /// Rules per project, replacing `allowed` and `blocked`
+    #[serde(default)]
+    pub projects: HashMap<String, FieldRules>,
+}
+
+/// Allowlist and blocklist of one project
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FieldRules {
+    /// Parameters accepted (empty: all)
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    /// Parameters dropped, even when allowed
+    #[serde(default)]
+    pub blocked: Vec<String>,
+}
+
+/// Settings of one project in the `projects` registry
+///
+/// Requests for projects without an entry are accepted with the global settings.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ProjectConfig {
+    /// Keys of which one must be sent in the X-Api-Key header (no key needed when empty)
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// Origins allowed to send events, e.g. "https://*.example.com" (any when empty)
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Topic/stream for the project's events, optionally templated with `{event}`;
+    /// replaces the sink's topic and event routes
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Fraction of visitors whose /track/ events are kept (default 1.0)
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+    /// Parameters and event names the project accepts
+    #[serde(default)]
+    pub schema: ProjectSchemaConfig,
+}
+
+/// What a project's requests must and may contain
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ProjectSchemaConfig {
+    /// Parameters /track/ and /identify requests must send
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Event names (or globs) accepted on /track/, like `events.projects.<id>`
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Parameter allowlist and blocklist, like `fields.projects.<id>`
+    #[serde(default)]
+    pub fields: Option<FieldRules>,
+}
+
+/// Handling of event names outside the allowlist
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownEventAction {
+    /// Reject the request with HTTP 400
+    #[default]
+    Reject,
+    /// Accept the event under `bucket_name`, keeping the sent name in `raw_event`
+    Bucket,
+}
+
+/// Pre-sink event filter configuration
+///
+/// Events matching any rule are acknowledged to the client but never streamed.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FilterConfig {
+    /// Drop events whose User-Agent is a known bot or crawler
+    #[serde(default)]
+    pub drop_bots: bool,
+    /// Drop events from client IPs inside these CIDR ranges (e.g. "10.0.0.0/8")
+    #[serde(default)]
+    pub drop_ip_ranges: Vec<String>,
+    /// Drop events whose name matches one of these glob patterns (e.g. "debug_*")
+    #[serde(default)]
+    pub drop_event_names: Vec<String>,
+    /// Drop events from VPNs, Tor exit nodes and public or residential proxies
+    #[serde(default)]
+    pub drop_anonymous_ips: bool,
+    /// Drop events from hosting providers / datacenters
+    #[serde(default)]
+    pub drop_hosting_ips: bool,
+    /// Drop events from headless or automated browsers (`is_headless`)
+    #[serde(default)]
+    pub drop_headless: bool,
+}
+
+/// Enrichment pipeline configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EnrichmentConfig {
+    /// Stages to run, in order; leave a stage out to disable it
+    #[serde(default = "default_enrichment_pipeline")]
+    pub pipeline: Vec<EnricherKind>,
+    /// External HTTP lookup used by the `http` stage
+    #[serde(default)]
+    pub http: Option<HttpEnricherConfig>,
+    /// Rhai scripts run by the `script` stage
+    #[serde(default)]
+    pub script: Option<ScriptEnricherConfig>,
+    /// Settings of the `email` stage
+    #[serde(default)]
+    pub email: EmailConfig,
+    /// Profile PII policies applied by the `pii` stage
+    #[serde(default)]
+    pub pii: Option<PiiConfig>,
+    /// Enriched fields removed from every event after the pipeline ran
+    #[serde(default)]
+    pub exclude_fields: Vec<EnrichedField>,
+    /// Countries flagged by the `is_eu` stage; "EU" expands to all EU member states
+    #[serde(default = "default_eu_countries")]
+    pub eu_countries: Vec<String>,
+    /// VPN, Tor and hosting ranges used by the `ip_reputation` stage
+    #[serde(default)]
+    pub ip_reputation: Option<IpReputationConfig>,
+    /// Length of the geohash added by the `geohash` stage (1-12)
+    #[serde(default = "default_geohash_precision")]
+    pub geohash_precision: usize,
+    /// Fields derived from expressions by the `computed` stage, in evaluation order
+    #[serde(default)]
+    pub computed: Vec<ComputedFieldConfig>,
+    /// Record the stages each event went through, and their outcomes, in its `provenance` field
+    #[serde(default)]
+    pub provenance: bool,
+}
+
+/// A field added to `attributes` by the `computed` stage
+///
+/// `expression` is evaluated against the event, e.g. `device == "Mobile"` or
+/// `referer is empty`; see `enrichment::computed::Expression` for the syntax.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ComputedFieldConfig {
+    /// Attribute name
+    pub name: String,
+    /// Expression computing the value
+    pub expression: String,
+}
+
+fn default_enrichment_pipeline() -> Vec<EnricherKind> {
+    vec![
+        EnricherKind::Campaign,
+        EnricherKind::UserAgent,
+        EnricherKind::Geoip,
+        EnricherKind::Timezone,
+        EnricherKind::Currency,
+        EnricherKind::IsEu,
+        EnricherKind::Geohash,
+        EnricherKind::IpHash,
+        EnricherKind::Fingerprint,
+        EnricherKind::Session,
+    ]
+}
+
+/// EU member states, the other EEA countries and the United Kingdom
+fn default_eu_countries() -> Vec<String> {
+    ["EU", "IS", "LI", "NO", "GB"].iter().map(|code| code.to_string()).collect()
+}
+
+/// About 1.2 km × 0.6 km cells
+fn default_geohash_precision() -> usize {
+    6
+}
+
+impl Default for EnrichmentConfig {
+    fn default() -> Self {
+        EnrichmentConfig {
+            pipeline: default_enrichment_pipeline(),
+            http: None,
+            script: None,
+            email: EmailConfig::default(),
+            exclude_fields: Vec::new(),
+            eu_countries: default_eu_countries(),
+            pii: None,
+            ip_reputation: None,
+            geohash_precision: default_geohash_precision(),
+            computed: Vec::new(),
+            provenance: false,
+        }
+    }
+}
+
+/// Anonymizer and datacenter IP ranges for the `ip_reputation` stage
+///
+/// Complements the MaxMind Anonymous-IP database with self-maintained lists, e.g. a
+/// Tor exit list or the published ranges of cloud providers.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct IpReputationConfig {
+    /// VPN provider ranges
+    #[serde(default)]
+    pub vpn: IpRangeListConfig,
+    /// Tor exit node addresses
+    #[serde(default)]
+    pub tor: IpRangeListConfig,
+    /// Hosting provider / datacenter ranges
+    #[serde(default)]
+    pub hosting: IpRangeListConfig,
+}
+
+/// A list of CIDR ranges given inline and/or in a file
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct IpRangeListConfig {
+    /// Ranges in CIDR notation (bare addresses are single hosts)
+    #[serde(default)]
+    pub ranges: Vec<String>,
+    /// File with one range per line (`#` starts a comment), read at startup
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// External HTTP enrichment configuration
+///
+/// The value of the request parameter `key_param` replaces `{key}` in `url`; fields of
+/// the JSON object returned are added to the event's `attributes`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HttpEnricherConfig {
+    /// Lookup URL containing a `{key}` placeholder
+    pub url: String,
+    /// Request parameter whose value is looked up (e.g. "u_account")
+    pub key_param: String,
+    /// Response fields to copy (all fields when empty)
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Extra request headers (e.g. Authorization)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Per-attempt timeout in milliseconds
+    #[serde(default = "default_http_enricher_timeout_ms", deserialize_with = "units::millis")]
+    pub timeout_ms: u64,
+    /// Retries after a failed attempt
+    #[serde(default = "default_http_enricher_retries")]
+    pub retries: u32,
+    /// Seconds a lookup result is cached (0 disables caching)
+    #[serde(default = "default_http_enricher_cache_ttl_secs", deserialize_with = "units::secs")]
+    pub cache_ttl_secs: u64,
+    /// Maximum number of cached lookup results
+    #[serde(default = "default_http_enricher_cache_max_entries")]
+    pub cache_max_entries: usize,
+    /// Consecutive failed lookups that open the circuit breaker
+    #[serde(default = "default_fallback_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds the circuit breaker stays open before a trial lookup
+    #[serde(default = "default_fallback_open_secs", deserialize_with = "units::secs")]
+    pub open_secs: u64,
+}
+
+fn default_http_enricher_timeout_ms() -> u64 {
+    200
+}
+
+fn default_http_enricher_retries() -> u32 {
+    1
+}
+
+fn default_http_enricher_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_http_enricher_cache_max_entries() -> usize {
+    10_000
+}
+
+impl Default for HttpEnricherConfig {
+    fn default() -> Self {
+        HttpEnricherConfig {
+            url: String::new(),
+            key_param: String::new(),
+            fields: Vec::new(),
+            headers: HashMap::new(),
+            timeout_ms: default_http_enricher_timeout_ms(),
+            retries: default_http_enricher_retries(),
+            cache_ttl_secs: default_http_enricher_cache_ttl_secs(),
+            cache_max_entries: default_http_enricher_cache_max_entries(),
+            failure_threshold: default_fallback_failure_threshold(),
+            open_secs: default_fallback_open_secs(),
+        }
+    }
+}
+
+/// Profile email handling for the `email` stage
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EmailConfig {
+    /// Trim and lowercase the address
+    #[serde(default = "default_true")]
+    pub lowercase: bool,
+    /// Check the address syntax and set `email_valid`
+    #[serde(default = "default_true")]
+    pub validate: bool,
+    /// Remove addresses that fail validation from the profile
+    #[serde(default)]
+    pub remove_invalid: bool,
+    /// Add `email_domain` and `email_free_provider`
+    #[serde(default = "default_true")]
+    pub extract_domain: bool,
+    /// Additional free-provider domains
+    #[serde(default)]
+    pub free_providers: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            lowercase: true,
+            validate: true,
+            remove_invalid: false,
+            extract_domain: true,
+            free_providers: Vec::new(),
+        }
+    }
+}
+
+/// Profile PII handling for the `pii` stage
+///
+/// Each profile property named in `properties` is hashed, masked or removed before the
+/// event is streamed. Project policies override the default action for the properties
+/// they name, e.g. to keep `email` for one project or remove `phone` for another.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PiiConfig {
+    /// Secret salt mixed into hashed values (required when any property is hashed)
+    #[serde(default)]
+    pub salt: String,
+    /// Action per profile property, for every project
+    #[serde(default)]
+    pub properties: HashMap<String, PiiAction>,
+    /// Actions per project, overriding `properties` for the properties they name
+    #[serde(default)]
+    pub projects: HashMap<String, HashMap<String, PiiAction>>,
+}
+
+/// What the `pii` stage does with a profile property
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiAction {
+    /// Leave the value unchanged
+    Keep,
+    /// Replace the value with a salted SHA-256 hash of its trimmed, lowercased form
+    Hash,
+    /// Replace all but the last characters (or, for emails, the local part) with `*`
+    Mask,
+    /// Remove the property
+    Remove,
+}
+
+/// Event fields added by enrichment that can be switched off
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrichedField {
+    Browser,
+    BrowserVersion,
+    Os,
+    OsVersion,
+    OsFamily,
+    OsMajor,
+    Device,
+    DeviceBrand,
+    DeviceModel,
+    InAppBrowser,
+    IsHeadless,
+    BotName,
+    BotCategory,
+    Country,
+    CountryCode,
+    ContinentCode,
+    Region,
+    City,
+    PostalCode,
+    Latitude,
+    Longitude,
+    AccuracyRadius,
+    Network,
+    Timezone,
+    UtcOffsetMinutes,
+    LocalHour,
+    LocalDayOfWeek,
+    Currency,
+    IsEu,
+    Geohash,
+    Campaign,
+    ClickIds,
+    IpHash,
+    Fingerprint,
+}
+
+/// Scripted enrichment configuration
+///
+/// Each script sees the event as the map variable `event`, may modify it, and drops it
+/// by setting `drop = true`. Scripts run in the order listed.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScriptEnricherConfig {
+    /// Scripts to run
+    pub scripts: Vec<ScriptConfig>,
+    /// Operation budget per script run (guards against runaway loops)
+    #[serde(default = "default_script_max_operations")]
+    pub max_operations: u64,
+}
+
+fn default_script_max_operations() -> u64 {
+    100_000
+}
+
+impl Default for ScriptEnricherConfig {
+    fn default() -> Self {
+        ScriptEnricherConfig {
+            scripts: Vec::new(),
+            max_operations: default_script_max_operations(),
+        }
+    }
+}
+
+/// A single enrichment script
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ScriptConfig {
+    /// Path to the Rhai script file
+    pub path: String,
+    /// Projects the script applies to (all projects when empty)
+    #[serde(default)]
+    pub projects: Vec<String>,
+}
+
+/// Built-in enrichment stages
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnricherKind {
+    /// UTM campaign attribution
+    Campaign,
+    /// Browser, OS and device from the User-Agent header
+    UserAgent,
+    /// Location from the client IP (requires geoip.database_path)
+    Geoip,
+    /// Visitor time zone from the `tz` parameter or GeoIP (run after `geoip`)
+    Timezone,
+    /// Currency of the visitor's country (run after `geoip`)
+    Currency,
+    /// Whether the visitor's country is in `enrichment.eu_countries` (run after `geoip`)
+    IsEu,
+    /// Geohash of the GeoIP coordinates (`enrichment.geohash_precision`; run after `geoip`)
+    Geohash,
+    /// Salted client IP hash (requires `ip_hash`)
+    IpHash,
+    /// Salted device fingerprint (requires `fingerprint`)
+    Fingerprint,
+    /// Session id, start flag and event index (requires `session`; run after `fingerprint`)
+    Session,
+    /// VPN, Tor and hosting provider flags from CIDR lists (requires
+    /// `enrichment.ip_reputation`; run after `geoip`)
+    IpReputation,
+    /// Profile email normalization, validation and domain (`enrichment.email`)
+    Email,
+    /// Profile property hashing, masking and removal (requires `enrichment.pii`;
+    /// run after `email`)
+    Pii,
+    /// Fields from an external HTTP service (requires `enrichment.http`)
+    Http,
+    /// User-provided Rhai scripts (requires `enrichment.script`)
+    Script,
+    /// Fields derived from expressions (requires `enrichment.computed`; run last)
+    Computed,
+}
+
+impl EnricherKind {
+    /// Name of the stage as written in configuration
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnricherKind::Campaign => "campaign",
+            EnricherKind::UserAgent => "user_agent",
+            EnricherKind::Geoip => "geoip",
+            EnricherKind::Timezone => "timezone",
+            EnricherKind::Currency => "currency",
+            EnricherKind::IsEu => "is_eu",
+            EnricherKind::Geohash => "geohash",
+            EnricherKind::IpHash => "ip_hash",
+            EnricherKind::Fingerprint => "fingerprint",
+            EnricherKind::Session => "session",
+            EnricherKind::IpReputation => "ip_reputation",
+            EnricherKind::Email => "email",
+            EnricherKind::Pii => "pii",
+            EnricherKind::Http => "http",
+            EnricherKind::Script => "script",
+            EnricherKind::Computed => "computed",
+        }
+    }
+}
+
+/// Salted IP hash configuration
+///
+/// When present, events carry an `ip_hash` field: a SHA-256 hash of the salt and the
+/// client IP. The raw IP address is never included in events.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct IpHashConfig {
+    /// Secret salt mixed into every hash
+    pub salt: String,
+    /// Derive a new salt every UTC day so hashes cannot be linked across days
+    #[serde(default)]
+    pub rotate_daily: bool,
+}
+
+/// Device fingerprint configuration
+///
+/// When present, events carry a `fingerprint` field: a SHA-256 hash of the salt and the
+/// selected request attributes. It is a best-effort visitor key for cookie-less setups;
+/// visitors sharing a browser build, screen, language and network get the same value.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FingerprintConfig {
+    /// Secret salt mixed into every hash
+    pub salt: String,
+    /// Request attributes hashed, in order
+    #[serde(default = "default_fingerprint_components")]
+    pub components: Vec<FingerprintComponent>,
+    /// IPv4 bits of the client IP kept before hashing
+    #[serde(default = "default_fingerprint_ipv4_prefix")]
+    pub ipv4_prefix: u8,
+    /// IPv6 bits of the client IP kept before hashing
+    #[serde(default = "default_fingerprint_ipv6_prefix")]
+    pub ipv6_prefix: u8,
+    /// Derive a new salt every UTC day so fingerprints cannot be linked across days
+    #[serde(default)]
+    pub rotate_daily: bool,
+}
+
+fn default_fingerprint_components() -> Vec<FingerprintComponent> {
+    vec![
+        FingerprintComponent::UserAgent,
+        FingerprintComponent::Screen,
+        FingerprintComponent::Language,
+        FingerprintComponent::Ip,
+    ]
+}
+
+fn default_fingerprint_ipv4_prefix() -> u8 {
+    24
+}
+
+fn default_fingerprint_ipv6_prefix() -> u8 {
+    48
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        FingerprintConfig {
+            salt: String::new(),
+            components: default_fingerprint_components(),
+            ipv4_prefix: default_fingerprint_ipv4_prefix(),
+            ipv6_prefix: default_fingerprint_ipv6_prefix(),
+            rotate_daily: false,
+        }
+    }
+}
+
+/// Request attributes that can make up a fingerprint
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintComponent {
+    /// Raw User-Agent header
+    UserAgent,
+    /// `screen` parameter
+    Screen,
+    /// `language` parameter (case-insensitive)
+    Language,
+    /// Client IP truncated to `ipv4_prefix` / `ipv6_prefix`
+    Ip,
+}
+
+impl FingerprintComponent {
+    /// Name of the component as written in configuration
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FingerprintComponent::UserAgent => "user_agent",
+            FingerprintComponent::Screen => "screen",
+            FingerprintComponent::Language => "language",
+            FingerprintComponent::Ip => "ip",
+        }
+    }
+}
+
+/// Session tracking configuration
+///
+/// Events of a visitor belong to the same session until no event arrives for
+/// `timeout_secs`. Sessions are tracked per project.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SessionConfig {
+    /// Where sessions are kept
+    #[serde(default)]
+    pub store: SessionStoreType,
+    /// Redis URL (required for the `redis` store)
+    #[serde(default)]
+    pub redis_url: String,
+    /// Prefix of the Redis session keys
+    #[serde(default = "default_session_key_prefix")]
+    pub key_prefix: String,
+    /// Inactivity in seconds after which a session ends
+    #[serde(default = "default_session_timeout_secs", deserialize_with = "units::secs")]
+    pub timeout_secs: u64,
+    /// Event fields identifying the visitor, in order of preference
+    #[serde(default = "default_session_identity")]
+    pub identity: Vec<SessionIdentity>,
+}
+
+fn default_session_key_prefix() -> String {
+    "session:".to_string()
+}
+
+fn default_session_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_session_identity() -> Vec<SessionIdentity> {
+    vec![SessionIdentity::Cookie, SessionIdentity::Fingerprint]
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            store: SessionStoreType::default(),
+            redis_url: String::new(),
+            key_prefix: default_session_key_prefix(),
+            timeout_secs: default_session_timeout_secs(),
+            identity: default_session_identity(),
+        }
+    }
+}
+
+/// Session store backends
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStoreType {
+    /// Shared through Redis, so all collector instances agree on sessions
+    #[default]
+    Redis,
+    /// Process memory (single-instance deployments)
+    Memory,
+}
+
+impl SessionStoreType {
+    /// Name used in configuration and logs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionStoreType::Redis => "redis",
+            SessionStoreType::Memory => "memory",
+        }
+    }
+}
+
+/// Event fields that can identify a visitor for sessions
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionIdentity {
+    /// Visitor cookie (`cookie` parameter)
+    Cookie,
+    /// Device fingerprint (requires the `fingerprint` stage)
+    Fingerprint,
+}
+
+/// User-Agent parser configuration
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct UserAgentConfig {
+    /// Parser backend
+    #[serde(default)]
+    pub parser: UserAgentParserType,
+    /// Path of the uap-core regexes.yaml (or a custom rule file); required for `uap`
+    #[serde(default)]
+    pub regexes_path: Option<String>,
+    /// `os_family` overrides: parser OS name (case-insensitive) → family
+    #[serde(default)]
+    pub os_families: HashMap<String, String>,
+}
+
+/// User-Agent parser backends
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UserAgentParserType {
+    /// Built-in woothee rules
+    #[default]
+    Woothee,
+    /// uap-core regexes loaded from `regexes_path`
+    Uap,
+}
+
+/// Campaign parameter extraction configuration
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct CampaignConfig {
+    /// Extra parameter names per campaign field, checked after `utm_<field>`
+    /// (e.g. `source: ["ref", "src"]`)
+    #[serde(default)]
+    pub aliases: HashMap<String, Vec<String>>,
+}
+
+/// Prefixed parameter routing and e_*/u_* value configuration
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ParameterConfig {
+    /// Extra or overriding prefix rules, applied on top of the built-in
+    /// e_ → event_param, u_ → profile, s_ → session and p_ → project rules
+    #[serde(default)]
+    pub prefixes: Vec<PrefixRule>,
+    /// Store "true"/"false" as booleans and plain decimal values as numbers
+    /// instead of strings
+    #[serde(default)]
+    pub infer_types: bool,
+    /// Embed values holding a JSON object or array as structured JSON
+    #[serde(default)]
+    pub parse_json: bool,
+    /// Largest JSON value (in bytes) that is parsed; longer values stay strings
+    #[serde(default = "default_max_json_bytes", deserialize_with = "units::bytes")]
+    pub max_json_bytes: usize,
+    /// Deepest nesting of objects and arrays that is accepted
+    #[serde(default = "default_max_json_depth")]
+    pub max_json_depth: usize,
+    /// Size limits for prefixed parameters
+    #[serde(default)]
+    pub limits: ParameterLimits,
+    /// Handling of session/project properties whose names collide with standard
+    /// root-level fields or with each other
+    #[serde(default)]
+    pub root_collisions: RootCollisionStrategy,
+    /// Decoding of the base64-encoded JSON `payload` parameter
+    #[serde(default)]
+    pub payload: PayloadConfig,
+    /// Normalization of custom property names for downstream column names
+    #[serde(default)]
+    pub sanitize_keys: KeySanitizationConfig,
+    /// Canonical form of the page URL stored next to the raw one
+    #[serde(default)]
+    pub canonical_url: CanonicalUrlConfig,
+}
+
+/// Canonical page URL (`visit.canonical_url`)
+///
+/// When enabled, the `url` parameter is also stored normalized: lowercase scheme and
+/// host, no default port, no credentials, no trailing slash, no fragment (unless
+/// `keep_fragment`) and without the query parameters matching `strip_params`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CanonicalUrlConfig {
+    /// Store `visit.canonical_url`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Query parameter names (or `*`/`?` globs, case-insensitive) removed from the URL
+    #[serde(default = "default_strip_params")]
+    pub strip_params: Vec<String>,
+    /// Keep the `#fragment`, e.g. for hash-routed single-page apps
+    #[serde(default)]
+    pub keep_fragment: bool,
+}
+
+fn default_strip_params() -> Vec<String> {
+    ["utm_*", "gclid", "fbclid", "msclkid"].iter().map(|name| name.to_string()).collect()
+}
+
+impl Default for CanonicalUrlConfig {
+    fn default() -> Self {
+        CanonicalUrlConfig {
+            enabled: false,
+            strip_params: default_strip_params(),
+            keep_fragment: false,
+        }
+    }
+}
+
+/// Normalization of custom property names (event_param, profile, session, project
+/// and custom objects) so they are valid column names in BigQuery, ClickHouse, ...
+///
+/// When enabled, names are lowercased (optional), spaces, dashes and dots become
+/// `replacement`, other characters outside `[A-Za-z0-9_]` are removed, a leading digit
+/// gets a `_` prefix and names are cut to `max_length`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KeySanitizationConfig {
+    /// Normalize property names
+    #[serde(default)]
+    pub enabled: bool,
+    /// Lowercase property names
+    #[serde(default = "default_true")]
+    pub lowercase: bool,
+    /// Replacement for spaces, dashes and dots (letters, digits and `_` only; may be empty)
+    #[serde(default = "default_key_replacement")]
+    pub replacement: String,
+    /// Longest property name (in bytes) after normalization
+    #[serde(default = "default_sanitized_key_length")]
+    pub max_length: usize,
+}
+
+fn default_key_replacement() -> String {
+    "_".to_string()
+}
+
+fn default_sanitized_key_length() -> usize {
+    128
+}
+
+impl Default for KeySanitizationConfig {
+    fn default() -> Self {
+        KeySanitizationConfig {
+            enabled: false,
+            lowercase: true,
+            replacement: default_key_replacement(),
+            max_length: default_sanitized_key_length(),
+        }
+    }
+}
+
+/// Base64-encoded JSON `payload` parameter
+///
+/// When enabled, `payload=<base64(JSON object)>` is decoded and its members are
+/// merged into the request parameters; parameters sent directly win.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PayloadConfig {
+    /// Decode the `payload` parameter (otherwise it is an ordinary parameter)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest encoded payload (in bytes); longer payloads reject the request
+    #[serde(default = "default_payload_max_bytes", deserialize_with = "units::bytes")]
+    pub max_bytes: usize,
+    /// Most members a payload may hold
+    #[serde(default = "default_payload_max_params")]
+    pub max_params: usize,
+}
+
+fn default_payload_max_bytes() -> usize {
+    16384
+}
+
+fn default_payload_max_params() -> usize {
+    200
+}
+
+impl Default for PayloadConfig {
+    fn default() -> Self {
+        PayloadConfig {
+            enabled: false,
+            max_bytes: default_payload_max_bytes(),
+            max_params: default_payload_max_params(),
+        }
+    }
+}
+
+/// Handling of flattened session/project properties that collide with a root field
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RootCollisionStrategy {
+    /// Keep the property under its prefixed name (`s_event`, `p_event`)
+    #[default]
+    Prefix,
+    /// Move the property into the `session` / `project_props` object
+    Nest,
+    /// Reject the request with HTTP 400
+    Reject,
+}
+
+/// Size limits enforced on prefixed parameters during transformation
+///
+/// Parameters with longer names are dropped, longer values are truncated and
+/// properties beyond `max_properties` per target object are dropped; each case is
+/// reported in the event's `warnings`. Repeated event parameters keep at most
+/// `max_repeated_values` values. Durations above `max_duration_ms` are discarded.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ParameterLimits {
+    /// Longest parameter name (in bytes, prefix included)
+    #[serde(default = "default_max_key_length")]
+    pub max_key_length: usize,
+    /// Longest parameter value (in bytes)
+    #[serde(default = "default_max_value_length")]
+    pub max_value_length: usize,
+    /// Most properties per target object (event_param, profile, ...)
+    #[serde(default = "default_max_properties")]
+    pub max_properties: usize,
+    /// Most values kept for a repeated parameter (`e_tag=a&e_tag=b`)
+    #[serde(default = "default_max_repeated_values")]
+    pub max_repeated_values: usize,
+    /// Longest accepted `duration` (in milliseconds); longer and negative values are dropped
+    #[serde(default = "default_max_duration_ms", deserialize_with = "units::millis")]
+    pub max_duration_ms: u64,
+}
+
+fn default_max_key_length() -> usize {
+    128
+}
+
+fn default_max_value_length() -> usize {
+    4096
+}
+
+fn default_max_properties() -> usize {
+    100
+}
+
+fn default_max_repeated_values() -> usize {
+    100
+}
+
+/// One day
+fn default_max_duration_ms() -> u64 {
+    86_400_000
+}
+
+impl Default for ParameterLimits {
+    fn default() -> Self {
+        ParameterLimits {
+            max_key_length: default_max_key_length(),
+            max_value_length: default_max_value_length(),
+            max_properties: default_max_properties(),
+            max_repeated_values: default_max_repeated_values(),
+            max_duration_ms: default_max_duration_ms(),
+        }
+    }
+}
+
+fn default_max_json_bytes() -> usize {
+    4096
+}
+
+fn default_max_json_depth() -> usize {
+    5
+}
+
+impl Default for ParameterConfig {
+    fn default() -> Self {
+        ParameterConfig {
+            prefixes: Vec::new(),
+            infer_types: false,
+            parse_json: false,
+            max_json_bytes: default_max_json_bytes(),
+            max_json_depth: default_max_json_depth(),
+            limits: ParameterLimits::default(),
+            root_collisions: RootCollisionStrategy::default(),
+            payload: PayloadConfig::default(),
+            sanitize_keys: KeySanitizationConfig::default(),
+            canonical_url: CanonicalUrlConfig::default(),
+        }
+    }
+}
+
+impl ParameterConfig {
+    /// Effective prefix rules: the built-in rules, replaced by configured rules with
+    /// the same prefix, followed by the remaining configured rules
+    pub fn prefix_rules(&self) -> Vec<PrefixRule> {
+        let mut rules: Vec<PrefixRule> = default_prefix_rules()
+            .into_iter()
+            .filter(|rule| !self.prefixes.iter().any(|custom| custom.prefix == rule.prefix))
+            .collect();
+        rules.extend(self.prefixes.iter().cloned());
+        rules
+    }
+}
+
+/// Routes parameters starting with `prefix` (prefix removed) to `target`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PrefixRule {
+    pub prefix: String,
+    pub target: PrefixTarget,
+}
+
+impl PrefixRule {
+    fn new(prefix: &str, target: PrefixTarget) -> Self {
+        PrefixRule {
+            prefix: prefix.to_string(),
+            target,
+        }
+    }
+}
+
+/// Where prefixed parameters are stored
+///
+/// Any name other than the built-in targets creates an object of that name in the
+/// event's `objects` section.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum PrefixTarget {
+    /// The `event_param` object
+    EventParam,
+    /// The `profile` object
+    Profile,
+    /// Root-level session properties
+    Session,
+    /// Root-level project properties
+    Project,
+    /// A custom object in `objects`
+    Object(String),
+}
+
+impl From<String> for PrefixTarget {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "event_param" => PrefixTarget::EventParam,
+            "profile" => PrefixTarget::Profile,
+            "session" => PrefixTarget::Session,
+            "project" => PrefixTarget::Project,
+            _ => PrefixTarget::Object(name),
+        }
+    }
+}
+
+impl From<PrefixTarget> for String {
+    fn from(target: PrefixTarget) -> Self {
+        match target {
+            PrefixTarget::EventParam => "event_param".to_string(),
+            PrefixTarget::Profile => "profile".to_string(),
+            PrefixTarget::Session => "session".to_string(),
+            PrefixTarget::Project => "project".to_string(),
+            PrefixTarget::Object(name) => name,
+        }
+    }
+}
+
+fn default_prefix_rules() -> Vec<PrefixRule> {
+    vec![
+        PrefixRule::new("e_", PrefixTarget::EventParam),
+        PrefixRule::new("u_", PrefixTarget::Profile),
+        PrefixRule::new("s_", PrefixTarget::Session),
+        PrefixRule::new("p_", PrefixTarget::Project),
+    ]
+}
+
+/// Error type for configuration loading failures
+#[derive(Debug)]
+pub enum ConfigError {
+    FileNotFound(std::io::Error),
+    InvalidYaml(serde_yaml::Error),
+    InvalidJson(serde_json::Error),
+    InvalidToml(toml::de::Error),
+    /// The configuration parsed but has invalid or missing settings, all listed
+    Invalid(Vec<ValidationIssue>),
+    /// A `*_file` setting or secret reference could not be resolved
+    Secret(String),
+    /// An `include` setting is invalid or names a missing file
+    Include(String),
+    /// A remote configuration could not be fetched
+    Remote(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::FileNotFound(e) => write!(f, "Configuration file not found: {}", e),
+            ConfigError::InvalidYaml(e) => write!(f, "Invalid YAML syntax: {}", e),
+            ConfigError::InvalidJson(e) => write!(f, "Invalid JSON syntax: {}", e),
+            ConfigError::InvalidToml(e) => write!(f, "Invalid TOML syntax: {}", e),
+            ConfigError::Invalid(issues) => {
+                write!(f, "Invalid configuration ({} problem(s)):", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+            ConfigError::Secret(msg) => write!(f, "Failed to resolve secret: {}", msg),
+            ConfigError::Include(msg) => write!(f, "Failed to include configuration: {}", msg),
+            ConfigError::Remote(msg) => write!(f, "Failed to fetch remote configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A setting that failed validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// Path of the setting, e.g. `streaming.kafka.brokers` or `server.listeners[1].address`
+    pub field: String,
+    /// What the setting must hold
+    pub expected: String,
+    /// The offending value, when there is one to show (secrets are never shown)
+    pub got: Option<String>,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: expected {}", self.field, self.expected)?;
+        if let Some(got) = &self.got {
+            write!(f, ", got {}", got)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigError::FileNotFound(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(err: serde_yaml::Error) -> Self {
+        ConfigError::InvalidYaml(err)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(err: serde_json::Error) -> Self {
+        ConfigError::InvalidJson(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::InvalidToml(err)
+    }
+}
+
+/// Load configuration from a YAML, JSON or TOML file, a directory of such files or a remote source
+///
+/// # Arguments
+/// * `path` - Path to the configuration file (format by extension, YAML by default) or
+///   directory, or an `http(s)://` URL or `s3://<bucket>/<key>` object
+///
+/// # Returns
+/// * `Ok(Config)` - Successfully loaded and parsed configuration
+/// * `Err(ConfigError)` - Error loading or parsing the configuration
+///
+/// A directory's configuration files and the files named by `include` settings are deep-merged
+/// into one document first; see `include::load_document`. `*_file` settings and secret
+/// references (`env://`, `aws-secrets://`, `vault://`) are then resolved before the
+/// configuration is parsed; see `resolve_secrets`.
+///
+/// # Errors
+/// * `ConfigError::FileNotFound` - Configuration file does not exist
+/// * `ConfigError::InvalidYaml` / `InvalidJson` / `InvalidToml` - File syntax is invalid
+/// * `ConfigError::Invalid` - Settings are missing or invalid; every problem is listed
+/// * `ConfigError::Secret` - A secret file or reference cannot be read
+/// * `ConfigError::Include` - An `include` setting is invalid, cyclic or names a missing file
+/// * `ConfigError::Remote` - A remote configuration cannot be fetched
+///
+/// # Example
+/// ```no_run
+/// use api::config::load_config;
+///
+/// let config = load_config("config.yaml").expect("Failed to load config");
+/// println!("Server will run on {}:{}", config.server.host, config.server.port);
+/// ```
+pub fn load_config(path: &str) -> Result<Config, ConfigError> {
+    load_config_with_profile(path, None)
+}
+
+/// Environment variable selecting the profile when none is given on the command line
+pub const PROFILE_ENV: &str = "ANALYTICS_PROFILE";
+
+/// Setting holding the named profiles
+const PROFILES_KEY: &str = "profiles";
+
+/// Load configuration like `load_config`, with the settings of a profile merged on top
+///
+/// `profiles.<name>` holds settings that override the rest of the configuration for
+/// that profile (e.g. dev, staging, prod), merged like included files. Without a
+/// profile, `profiles` is ignored.
+///
+/// # Errors
+/// As `load_config`, plus `ConfigError::Invalid` when the profile is not defined
+pub fn load_config_with_profile(path: &str, profile: Option<&str>) -> Result<Config, ConfigError> {
+    // Read and merge the files (an empty file selects every default)
+    let mut value = if is_remote(path) {
+        remote::load_document(path)?
+    } else {
+        include::load_document(std::path::Path::new(path))?
+    };
+    apply_profile(&mut value, profile.filter(|name| !name.is_empty()))?;
+    apply_runtime_settings(&mut value)?;
+    
+    // Replace secret references, then build the Config struct
+    resolve_secrets(&mut value, "")?;
+    let mut config: Config = serde_yaml::from_value(value)?;
+    
+    // Validate that required fields are present
+    validate_config(&config)?;
+    config.apply_project_registry();
+    
+    Ok(config)
+}
+
+/// Remove `profiles` from `value`, merging the selected profile's settings into it
+fn apply_profile(value: &mut serde_yaml::Value, profile: Option<&str>) -> Result<(), ConfigError> {
+    let profiles = match value {
+        serde_yaml::Value::Mapping(mapping) => mapping.remove(PROFILES_KEY),
+        _ => None,
+    };
+    let Some(name) = profile else {
+        return Ok(());
+    };
+    let overlay = profiles.as_ref().and_then(|profiles| profiles.get(name)).cloned().ok_or_else(|| {
+        let defined: Vec<&str> = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.as_mapping())
+            .map(|profiles| profiles.keys().filter_map(|key| key.as_str()).collect())
+            .unwrap_or_default();
+        ConfigError::Invalid(vec![ValidationIssue {
+            field: PROFILES_KEY.to_string(),
+            expected: format!("a profile named {:?}", name),
+            got: Some(format!("{:?}", defined)),
+        }])
+    })?;
+    include::merge_values(value, overlay);
+    Ok(())
+}
+
+/// Merge the settings persisted through the admin API, if `server.runtime_settings_path`
+/// names a file that exists
+fn apply_runtime_settings(value: &mut serde_yaml::Value) -> Result<(), ConfigError> {
+    let Some(path) = value
+        .get("server")
+        .and_then(|server| server.get("runtime_settings_path"))
+        .and_then(|path| path.as_str())
+    else {
+        return Ok(());
+    };
+    let path = std::path::Path::new(path);
+    if !path.exists() {
+        return Ok(());
+    }
+    let overlay = include::load_document(path)?;
+    include::merge_values(value, overlay);
+    Ok(())
+}
+
+impl Config {
+    /// Copy the topics and schemas of `projects` into the streaming, event name and
+    /// field policy settings that enforce them
+    ///
+    /// Called by `load_config`; hand-built configurations with projects call it themselves.
+    pub fn apply_project_registry(&mut self) {
+        for (id, project) in &self.projects {
+            if let Some(topic) = &project.topic {
+                self.streaming.project_topics.insert(id.clone(), topic.clone());
+            }
+            if !project.schema.events.is_empty() {
+                self.events.projects.insert(id.clone(), project.schema.events.clone());
+            }
+            if let Some(fields) = &project.schema.fields {
+                self.fields.projects.insert(id.clone(), fields.clone());
+            }
+        }
+    }
+}
+
+/// Problems found while validating a configuration
+///
+/// Checks record every problem instead of stopping at the first, so a single run
+/// reports everything that needs fixing.
+#[derive(Default)]
+struct Validator {
+    issues: Vec<ValidationIssue>,
+}
+
+impl Validator {
+    /// Record that `field` must hold `expected`
+    fn expect(&mut self, field: impl Into<String>, expected: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            field: field.into(),
+            expected: expected.into(),
+            got: None,
+        });
+    }
+
+    /// Record that `field` must hold `expected` but holds `got`
+    fn reject(&mut self, field: impl Into<String>, expected: impl Into<String>, got: impl std::fmt::Display) {
+        self.issues.push(ValidationIssue {
+            field: field.into(),
+            expected: expected.into(),
+            got: Some(got.to_string()),
+        });
+    }
+
+    /// `Ok` when no problem was recorded, otherwise `ConfigError::Invalid` with all of them
+    fn finish(self) -> Result<(), ConfigError> {
+        if self.issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(self.issues))
+        }
+    }
+}
+
+/// Validate one entry of the `projects` registry
+fn validate_project(v: &mut Validator, config: &Config, id: &str, project: &ProjectConfig) {
+    let prefix = format!("projects.{}", id);
+    if id.trim().is_empty() {
+        v.reject("projects", "non-empty project ids", format!("{:?}", id));
+    }
+    if project.api_keys.iter().any(|key| key.trim().is_empty()) {
+        v.reject(format!("{}.api_keys", prefix), "non-empty keys", "an empty key");
+    }
+    for origin in &project.allowed_origins {
+        if let Err(e) = crate::projects::OriginPattern::parse(origin) {
+            v.reject(format!("{}.allowed_origins", prefix), "origins such as https://example.com or https://*.example.com", e);
+        }
+    }
+    if let Some(topic) = &project.topic {
+        validate_topic_template(v, &format!("{}.topic", prefix), topic);
+    }
+    if project.schema.required.iter().any(|name| name.trim().is_empty()) {
+        v.reject(format!("{}.schema.required", prefix), "non-empty field names", "an empty name");
+    }
+    if !project.schema.events.is_empty() && config.events.projects.contains_key(id) {
+        v.expect(
+            format!("{}.schema.events", prefix),
+            format!("to be unset when events.projects.{} is set", id),
+        );
+    }
+    if project.schema.events.iter().any(|name| name.trim().is_empty()) {
+        v.reject(format!("{}.schema.events", prefix), "non-empty event names", "an empty name");
+    }
+    if let Some(fields) = &project.schema.fields {
+        if config.fields.projects.contains_key(id) {
+            v.expect(
+                format!("{}.schema.fields", prefix),
+                format!("to be unset when fields.projects.{} is set", id),
+            );
+        }
+        if fields.allowed.iter().chain(&fields.blocked).any(|name| name.trim().is_empty()) {
+            v.reject(format!("{}.schema.fields", prefix), "non-empty patterns", "an empty pattern");
+        }
+    }
+}
+
+/// Validate that all required configuration fields are present and valid
+///
+/// Every problem is collected; the error lists them all in the order checked.
+fn validate_config(config: &Config) -> Result<(), ConfigError> {
+    let mut v = Validator::default();
+
+    // Validate server config
+    if config.server.host.is_empty() {
+        v.reject("server.host", "a host name or address", "\"\"");
+    }
+    if config.server.port == 0 {
+        v.reject("server.port", "a non-zero port", 0);
+    }
+    if let Err(e) = parse_cidrs(&config.server.trusted_proxies) {
+        v.reject("server.trusted_proxies", "IP addresses or CIDR ranges", e);
+    }
+    if let Err(e) = parse_cidrs(&config.server.ip_override.allowed_sources) {
+        v.reject("server.ip_override.allowed_sources", "IP addresses or CIDR ranges", e);
+    }
+    if config.server.ip_override.api_keys.iter().any(|key| key.trim().is_empty()) {
+        v.reject("server.ip_override.api_keys", "non-empty keys", "an empty key");
+    }
+    if config.server.admin_api_keys.iter().any(|key| key.trim().is_empty()) {
+        v.reject("server.admin_api_keys", "non-empty keys", "an empty key");
+    }
+    for (index, listener) in config.server.listeners.iter().enumerate() {
+        validate_listener(&mut v, &format!("server.listeners[{}]", index), listener);
+    }
+
+    // Validate streaming config based on service type
+    validate_sink(&mut v, "streaming", &config.streaming);
+    for (index, rule) in config.streaming.residency.iter().enumerate() {
+        let prefix = format!("streaming.residency[{}]", index);
+        if rule.name.is_empty() {
+            v.reject(format!("{}.name", prefix), "a route name", "\"\"");
+        }
+        if rule.countries.is_empty() {
+            v.reject(format!("{}.countries", prefix), "at least one country code", "an empty list");
+        }
+        if let Some(country) = find_invalid_country_code(&rule.countries) {
+            v.reject(format!("{}.countries", prefix), "ISO 3166-1 alpha-2 country codes", format!("'{}'", country));
+        }
+        validate_sink(&mut v, &prefix, &rule.sink_config());
+    }
+
+    if config.streaming.health_check.interval_secs == 0 {
+        v.reject("streaming.health_check.interval_secs", "a non-zero duration", 0);
+    }
+    if config.streaming.health_check.timeout_secs == 0 {
+        v.reject("streaming.health_check.timeout_secs", "a non-zero duration", 0);
+    }
+    if config.streaming.ack_mode == AckMode::Queued {
+        if config.streaming.queue.capacity == 0 {
+            v.reject("streaming.queue.capacity", "a non-zero capacity", 0);
+        }
+        if config.streaming.queue.workers == 0 {
+            v.reject("streaming.queue.workers", "a non-zero number of workers", 0);
+        }
+    }
+    if let Some(ref retention) = config.streaming.retention {
+        if retention.default_days == Some(0) {
+            v.reject("streaming.retention.default_days", "a non-zero number of days", 0);
+        }
+        let mut projects: Vec<&String> = retention
+            .projects
+            .iter()
+            .filter(|(_, days)| **days == 0)
+            .map(|(project, _)| project)
+            .collect();
+        projects.sort();
+        for project in projects {
+            v.reject(format!("streaming.retention.projects.{}", project), "a non-zero number of days", 0);
+        }
+    }
+    if let Some(ref fallback) = config.streaming.fallback {
+        if fallback.path.is_empty() {
+            v.reject("streaming.fallback.path", "a file path", "\"\"");
+        }
+        if fallback.replay_interval_secs == 0 {
+            v.reject("streaming.fallback.replay_interval_secs", "a non-zero duration", 0);
+        }
+    }
+
+    let mut field_rules: Vec<(String, &Vec<String>, &Vec<String>)> = config
+        .fields
+        .projects
+        .iter()
+        .map(|(project, rules)| (format!("fields.projects.{}", project), &rules.allowed, &rules.blocked))
+        .collect();
+    field_rules.sort_by(|a, b| a.0.cmp(&b.0));
+    field_rules.insert(0, ("fields".to_string(), &config.fields.allowed, &config.fields.blocked));
+    for (prefix, allowed, blocked) in field_rules {
+        if allowed.iter().chain(blocked.iter()).any(|p| p.is_empty()) {
+            v.reject(prefix, "non-empty patterns", "an empty pattern");
+        }
+    }
+    let mut campaign_fields: Vec<&String> = config.campaign.aliases.keys().collect();
+    campaign_fields.sort();
+    for field in campaign_fields {
+        if !CAMPAIGN_FIELDS.contains(&field.as_str()) {
+            v.reject(
+                format!("campaign.aliases.{}", field),
+                format!("a campaign field, one of {:?}", CAMPAIGN_FIELDS),
+                field,
+            );
+        } else if config.campaign.aliases[field].iter().any(|a| a.is_empty()) {
+            v.reject(format!("campaign.aliases.{}", field), "non-empty parameter names", "an empty name");
+        }
+    }
+
+    if config.user_agent.parser == UserAgentParserType::Uap
+        && config.user_agent.regexes_path.as_deref().unwrap_or("").is_empty()
+    {
+        v.expect("user_agent.regexes_path", "a regexes.yaml path when user_agent.parser is uap");
+    }
+
+    if let Some(ref ip_hash) = config.ip_hash {
+        if ip_hash.salt.is_empty() {
+            v.reject("ip_hash.salt", "a non-empty salt", "\"\"");
+        }
+    }
+
+    if let Some(ref fingerprint) = config.fingerprint {
+        if fingerprint.salt.is_empty() {
+            v.reject("fingerprint.salt", "a non-empty salt", "\"\"");
+        }
+        if fingerprint.components.is_empty() {
+            v.reject("fingerprint.components", "at least one component", "an empty list");
+        }
+        if fingerprint.ipv4_prefix > 32 {
+            v.reject("fingerprint.ipv4_prefix", "a prefix length of at most 32", fingerprint.ipv4_prefix);
+        }
+        if fingerprint.ipv6_prefix > 128 {
+            v.reject("fingerprint.ipv6_prefix", "a prefix length of at most 128", fingerprint.ipv6_prefix);
+        }
+    }
+
+    if let Some(ref session) = config.session {
+        if session.store == SessionStoreType::Redis && session.redis_url.is_empty() {
+            v.expect("session.redis_url", "a Redis URL when session.store is redis");
+        }
+        if session.timeout_secs == 0 {
+            v.reject("session.timeout_secs", "a non-zero duration", 0);
+        }
+        if session.identity.is_empty() {
+            v.reject("session.identity", "at least one identity field", "an empty list");
+        }
+    }
+
+    for (index, kind) in config.enrichment.pipeline.iter().enumerate() {
+        if config.enrichment.pipeline[..index].contains(kind) {
+            v.reject("enrichment.pipeline", "each stage at most once", format!("{} listed twice", kind.as_str()));
+        }
+    }
+
+    if let Some(country) = find_invalid_country_code(&config.enrichment.eu_countries) {
+        v.reject("enrichment.eu_countries", "ISO 3166-1 alpha-2 country codes", format!("'{}'", country));
+    }
+
+    for (index, rule) in config.parameters.prefixes.iter().enumerate() {
+        if rule.prefix.is_empty() {
+            v.reject(format!("parameters.prefixes[{}].prefix", index), "a non-empty prefix", "\"\"");
+        }
+        if rule.target == PrefixTarget::Object(String::new()) {
+            v.reject(format!("parameters.prefixes[{}].target", index), "a target object", "\"\"");
+        }
+        if config.parameters.prefixes[..index].iter().any(|other| other.prefix == rule.prefix) {
+            v.reject(
+                format!("parameters.prefixes[{}].prefix", index),
+                "a prefix not configured by an earlier rule",
+                format!("'{}'", rule.prefix),
+            );
+        }
+    }
+
+    if config.events.aliases.iter().any(|(alias, name)| alias.trim().is_empty() || name.trim().is_empty()) {
+        v.reject("events.aliases", "non-empty event names", "an empty name");
+    }
+    if config.events.unknown == UnknownEventAction::Bucket && config.events.bucket_name.trim().is_empty() {
+        v.expect("events.bucket_name", "an event name when events.unknown is bucket");
+    }
+
+    let limits = &config.parameters.limits;
+    let limit_values = [
+        ("max_key_length", limits.max_key_length as u64),
+        ("max_value_length", limits.max_value_length as u64),
+        ("max_properties", limits.max_properties as u64),
+        ("max_repeated_values", limits.max_repeated_values as u64),
+        ("max_duration_ms", limits.max_duration_ms),
+    ];
+    for (name, value) in limit_values {
+        if value == 0 {
+            v.reject(format!("parameters.limits.{}", name), "a non-zero limit", 0);
+        }
+    }
+    if config.parameters.parse_json {
+        if config.parameters.max_json_bytes == 0 {
+            v.reject("parameters.max_json_bytes", "a non-zero size when parse_json is enabled", 0);
+        }
+        if config.parameters.max_json_depth == 0 {
+            v.reject("parameters.max_json_depth", "a non-zero depth when parse_json is enabled", 0);
+        }
+    }
+    let payload = &config.parameters.payload;
+    if payload.enabled {
+        if payload.max_bytes == 0 {
+            v.reject("parameters.payload.max_bytes", "a non-zero size", 0);
+        }
+        if payload.max_params == 0 {
+            v.reject("parameters.payload.max_params", "a non-zero limit", 0);
+        }
+    }
+    let sanitize_keys = &config.parameters.sanitize_keys;
+    if sanitize_keys.enabled {
+        if sanitize_keys.max_length == 0 {
+            v.reject("parameters.sanitize_keys.max_length", "a non-zero length", 0);
+        }
+        if !sanitize_keys.replacement.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            v.reject(
+                "parameters.sanitize_keys.replacement",
+                "only letters, digits and _",
+                format!("{:?}", sanitize_keys.replacement),
+            );
+        }
+    }
+    if config.parameters.canonical_url.strip_params.iter().any(|p| p.is_empty()) {
+        v.reject("parameters.canonical_url.strip_params", "non-empty patterns", "an empty pattern");
+    }
+
+    if !(1..=MAX_GEOHASH_PRECISION).contains(&config.enrichment.geohash_precision) {
+        v.reject(
+            "enrichment.geohash_precision",
+            format!("a precision between 1 and {}", MAX_GEOHASH_PRECISION),
+            config.enrichment.geohash_precision,
+        );
+    }
+
+    if config.enrichment.pipeline.contains(&EnricherKind::Pii) && config.enrichment.pii.is_none() {
+        v.expect("enrichment.pii", "a pii section when the pipeline includes pii");
+    }
+    if let Some(ref pii) = config.enrichment.pii {
+        let hashes = pii
+            .properties
+            .values()
+            .chain(pii.projects.values().flat_map(|policy| policy.values()))
+            .any(|action| *action == PiiAction::Hash);
+        if hashes && pii.salt.is_empty() {
+            v.expect("enrichment.pii.salt", "a salt when properties are hashed");
+        }
+    }
+
+    if config.enrichment.pipeline.contains(&EnricherKind::Computed) && config.enrichment.computed.is_empty() {
+        v.expect("enrichment.computed", "at least one field when the pipeline includes computed");
+    }
+    for (index, field) in config.enrichment.computed.iter().enumerate() {
+        if field.name.is_empty() {
+            v.reject(format!("enrichment.computed[{}].name", index), "a field name", "\"\"");
+        }
+        if config.enrichment.computed[..index].iter().any(|other| other.name == field.name) {
+            v.reject(
+                format!("enrichment.computed[{}].name", index),
+                "a name not used by an earlier field",
+                format!("'{}'", field.name),
+            );
+        }
+        if let Err(e) = Expression::parse(&field.expression) {
+            v.reject(
+                format!("enrichment.computed[{}].expression", index),
+                "a valid expression",
+                format!("{:?} ({})", field.expression, e),
+            );
+        }
+    }
+
+    if config.enrichment.pipeline.contains(&EnricherKind::IpReputation) && config.enrichment.ip_reputation.is_none() {
+        v.expect("enrichment.ip_reputation", "an ip_reputation section when the pipeline includes ip_reputation");
+    }
+    if let Some(ref ip_reputation) = config.enrichment.ip_reputation {
+        let lists = [
+            ("vpn", &ip_reputation.vpn),
+            ("tor", &ip_reputation.tor),
+            ("hosting", &ip_reputation.hosting),
+        ];
+        for (name, list) in lists {
+            if let Err(e) = parse_cidrs(&list.ranges) {
+                v.reject(format!("enrichment.ip_reputation.{}.ranges", name), "IP addresses or CIDR ranges", e);
+            }
+            if list.file.as_deref() == Some("") {
+                v.reject(format!("enrichment.ip_reputation.{}.file", name), "a file path", "\"\"");
+            }
+        }
+    }
+
+    if config.enrichment.pipeline.contains(&EnricherKind::Http) && config.enrichment.http.is_none() {
+        v.expect("enrichment.http", "an http section when the pipeline includes http");
+    }
+    if let Some(ref http) = config.enrichment.http {
+        if !http.url.contains("{key}") {
+            v.reject("enrichment.http.url", "a URL with a {key} placeholder", format!("{:?}", http.url));
+        }
+        if http.key_param.is_empty() {
+            v.reject("enrichment.http.key_param", "a parameter name", "\"\"");
+        }
+        if http.timeout_ms == 0 {
+            v.reject("enrichment.http.timeout_ms", "a non-zero duration", 0);
+        }
+    }
+
+    if config.enrichment.pipeline.contains(&EnricherKind::Script) && config.enrichment.script.is_none() {
+        v.expect("enrichment.script", "a script section when the pipeline includes script");
+    }
+    if let Some(ref script) = config.enrichment.script {
+        if script.max_operations == 0 {
+            v.reject("enrichment.script.max_operations", "a non-zero limit", 0);
+        }
+        for (index, entry) in script.scripts.iter().enumerate() {
+            if entry.path.is_empty() {
+                v.reject(format!("enrichment.script.scripts[{}].path", index), "a script path", "\"\"");
+            }
+        }
+    }
+
+    // GeoIP config is optional - empty path means GeoIP enrichment is disabled
+    let extra_databases = [
+        ("isp_database_path", &config.geoip.isp_database_path),
+        ("connection_type_database_path", &config.geoip.connection_type_database_path),
+        ("anonymous_ip_database_path", &config.geoip.anonymous_ip_database_path),
+    ];
+    for (name, path) in extra_databases {
+        if path.is_some() && config.geoip.database_path.is_empty() {
+            v.expect(format!("geoip.{}", name), "to be unset when geoip.database_path is empty");
+        }
+        if path.is_some() && config.geoip.backend != GeoIpBackendType::MaxMind {
+            v.expect(format!("geoip.{}", name), "to be unset unless geoip.backend is maxmind");
+        }
+    }
+    if let Some(ref cache) = config.geoip.cache {
+        if cache.max_entries == 0 {
+            v.reject("geoip.cache.max_entries", "a non-zero limit", 0);
+        }
+        if cache.ttl_secs == 0 {
+            v.reject("geoip.cache.ttl_secs", "a non-zero duration", 0);
+        }
+        if cache.ipv4_prefix > 32 {
+            v.reject("geoip.cache.ipv4_prefix", "a prefix length of at most 32", cache.ipv4_prefix);
+        }
+        if cache.ipv6_prefix > 128 {
+            v.reject("geoip.cache.ipv6_prefix", "a prefix length of at most 128", cache.ipv6_prefix);
+        }
+    }
+
+    let mut project_ids: Vec<&String> = config.projects.keys().collect();
+    project_ids.sort();
+    for id in project_ids {
+        validate_project(&mut v, config, id, &config.projects[id]);
+    }
+
+    if let Some(ref otlp) = config.logging.otlp {
+        if !(otlp.endpoint.starts_with("http://") || otlp.endpoint.starts_with("https://")) {
+            v.reject("logging.otlp.endpoint", "an http:// or https:// URL", format!("{:?}", otlp.endpoint));
+        }
+        if otlp.interval_secs == 0 {
+            v.reject("logging.otlp.interval_secs", "a non-zero duration", 0);
+        }
+        if otlp.timeout_ms == 0 {
+            v.reject("logging.otlp.timeout_ms", "a non-zero duration", 0);
+        }
+    }
+    if let Some(ref statsd) = config.logging.statsd {
+        let port = statsd.address.rsplit_once(':').filter(|(host, _)| !host.is_empty()).map(|(_, port)| port);
+        if !port.is_some_and(|port| port.parse::<u16>().is_ok_and(|port| port != 0)) {
+            v.reject("logging.statsd.address", "a host:port address", format!("{:?}", statsd.address));
+        }
+        if statsd.prefix.contains(|c: char| STATSD_RESERVED.contains(&c) || c.is_whitespace()) {
+            v.reject("logging.statsd.prefix", "a prefix without ':|@#,' or spaces", format!("{:?}", statsd.prefix));
+        }
+        let mut tags: Vec<(&String, &String)> = statsd.tags.iter().collect();
+        tags.sort();
+        for (name, value) in tags {
+            if name.is_empty() || name.contains(STATSD_RESERVED) || value.contains(['|', '#', ',']) {
+                v.reject(
+                    "logging.statsd.tags",
+                    "tag names without ':|@#,' and values without '|#,'",
+                    format!("{:?}: {:?}", name, value),
+                );
+            }
+        }
+        if statsd.interval_secs == 0 {
+            v.reject("logging.statsd.interval_secs", "a non-zero duration", 0);
+        }
+        if !(512..=65507).contains(&statsd.max_packet_bytes) {
+            v.reject("logging.statsd.max_packet_bytes", "a size between 512 and 65507", statsd.max_packet_bytes);
+        }
+    }
+    if config.logging.project_metrics.allowed_projects.iter().any(|project| project.is_empty()) {
+        v.reject("logging.project_metrics.allowed_projects", "project names", "an empty name");
+    }
+    if config.logging.topic_metrics.allowed_topics.iter().any(|topic| topic.is_empty()) {
+        v.reject("logging.topic_metrics.allowed_topics", "topic names", "an empty name");
+    }
+    if let Some(ref audit) = config.logging.audit {
+        if !(0.0..=1.0).contains(&audit.sample_rate) {
+            v.reject("logging.audit.sample_rate", "a number between 0 and 1", audit.sample_rate);
+        }
+        match (&audit.path, &audit.topic) {
+            (Some(_), Some(_)) | (None, None) => {
+                v.reject("logging.audit", "exactly one of path and topic", "both or neither");
+            }
+            (Some(path), None) if path.is_empty() => v.reject("logging.audit.path", "a file path", "\"\""),
+            (None, Some(topic)) if topic.is_empty() => v.reject("logging.audit.topic", "a topic name", "\"\""),
+            (None, Some(_)) if config.streaming.kafka.is_none() => {
+                v.reject("logging.audit.topic", "a streaming.kafka section for the brokers", "none");
+            }
+            _ => {}
+        }
+    }
+    if config.logging.access_log.path.as_deref() == Some("") {
+        v.reject("logging.access_log.path", "a file path", "\"\"");
+    }
+
+    validate_tunable(&mut v, config);
+    v.finish()
+}
+
+/// Validate the settings that can be changed at runtime through the admin API
+///
+/// Used for a changed configuration before it is applied, since the rest of
+/// `validate_config` does not hold once `apply_project_registry` ran.
+///
+/// # Errors
+/// Returns `ConfigError::Invalid` listing every problem found
+pub fn validate_runtime_settings(config: &Config) -> Result<(), ConfigError> {
+    let mut v = Validator::default();
+    validate_tunable(&mut v, config);
+    v.finish()
+}
+
+/// Checks of the runtime-tunable settings, shared by `validate_config`
+fn validate_tunable(v: &mut Validator, config: &Config) {
+    if let Some(ref fallback) = config.streaming.fallback {
+        if fallback.failure_threshold == 0 {
+            v.reject("streaming.fallback.failure_threshold", "a non-zero threshold", 0);
+        }
+    }
+    if let Err(e) = parse_cidrs(&config.filters.drop_ip_ranges) {
+        v.reject("filters.drop_ip_ranges", "IP addresses or CIDR ranges", e);
+    }
+    if config.filters.drop_event_names.iter().any(|p| p.is_empty()) {
+        v.reject("filters.drop_event_names", "non-empty patterns", "an empty pattern");
+    }
+    if let Some(ref http) = config.enrichment.http {
+        if http.failure_threshold == 0 {
+            v.reject("enrichment.http.failure_threshold", "a non-zero threshold", 0);
+        }
+    }
+
+    let mut project_ids: Vec<&String> = config.projects.keys().collect();
+    project_ids.sort();
+    for id in project_ids {
+        if let Some(rate) = config.projects[id].sample_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                v.reject(format!("projects.{}.sample_rate", id), "a number between 0 and 1", rate);
+            }
+        }
+    }
+
+    // Validate logging config
+    let valid_levels = ["trace", "debug", "info", "warn", "error"];
+    if !valid_levels.contains(&config.logging.level.to_lowercase().as_str()) {
+        v.reject(
+            "logging.level",
+            format!("one of {:?}", valid_levels),
+            format!("{:?}", config.logging.level),
+        );
+    }
+}
+
+/// Validate the sink section matching `streaming.service_type`
+///
+/// `prefix` is the config path of `streaming`, used in error messages.
+fn validate_sink(v: &mut Validator, prefix: &str, streaming: &StreamingConfig) {
+    match streaming.service_type {
+        StreamingServiceType::Stdout => {}
+        StreamingServiceType::Kafka => {
+            let Some(ref kafka) = streaming.kafka else {
+                v.expect(format!("{}.kafka", prefix), "a kafka section when service_type is kafka");
+                return;
+            };
+            if kafka.brokers.is_empty() {
+                v.reject(format!("{}.kafka.brokers", prefix), "at least one broker", "an empty list");
+            }
+            if kafka.topic.is_empty() {
+                v.reject(format!("{}.kafka.topic", prefix), "a topic name", "\"\"");
+            } else {
+                validate_topic_template(v, &format!("{}.kafka.topic", prefix), &kafka.topic);
+            }
+            validate_topic_routes(v, &format!("{}.kafka.event_topics", prefix), &kafka.event_topics);
+            validate_send_timeout(v, &format!("{}.kafka.send_timeout_ms", prefix), kafka.send_timeout_ms);
+            validate_max_in_flight(v, &format!("{}.kafka.max_in_flight", prefix), kafka.max_in_flight);
+            if kafka.transactional_id.as_deref() == Some("") {
+                v.reject(format!("{}.kafka.transactional_id", prefix), "a non-empty id", "\"\"");
+            }
+            if let Some(ref create_topics) = kafka.create_topics {
+                if create_topics.partitions < 1 {
+                    v.reject(
+                        format!("{}.kafka.create_topics.partitions", prefix),
+                        "at least 1",
+                        create_topics.partitions,
+                    );
+                }
+                if create_topics.replication_factor < 1 {
+                    v.reject(
+                        format!("{}.kafka.create_topics.replication_factor", prefix),
+                        "at least 1",
+                        create_topics.replication_factor,
+                    );
+                }
+                if let Some(ms) = create_topics.retention_ms.filter(|ms| *ms == 0 || *ms < -1) {
+                    v.reject(format!("{}.kafka.create_topics.retention_ms", prefix), "a positive duration or -1", ms);
+                }
+            }
+            if let Some(ref sasl) = kafka.sasl {
+                if !KAFKA_SASL_MECHANISMS.contains(&sasl.mechanism.as_str()) {
+                    v.reject(
+                        format!("{}.kafka.sasl.mechanism", prefix),
+                        format!("one of {:?}", KAFKA_SASL_MECHANISMS),
+                        format!("{:?}", sasl.mechanism),
+                    );
+                }
+                if !KAFKA_SECURITY_PROTOCOLS.contains(&sasl.security_protocol.as_str()) {
+                    v.reject(
+                        format!("{}.kafka.sasl.security_protocol", prefix),
+                        format!("one of {:?}", KAFKA_SECURITY_PROTOCOLS),
+                        format!("{:?}", sasl.security_protocol),
+                    );
                 }
+                if sasl.username.is_empty() {
+                    v.reject(format!("{}.kafka.sasl.username", prefix), "a user name", "\"\"");
+                }
+                if sasl.password.is_empty() {
+                    v.reject(format!("{}.kafka.sasl.password", prefix), "a password", "\"\"");
+                }
+            }
+        }
+        StreamingServiceType::Kinesis => {
+            let Some(ref kinesis) = streaming.kinesis else {
+                v.expect(format!("{}.kinesis", prefix), "a kinesis section when service_type is kinesis");
+                return;
+            };
+            if kinesis.region.is_empty() {
+                v.reject(format!("{}.kinesis.region", prefix), "an AWS region", "\"\"");
+            }
+            if kinesis.stream_name.is_empty() {
+                v.reject(format!("{}.kinesis.stream_name", prefix), "a stream name", "\"\"");
             } else {
-                return Err(ConfigError::MissingFields("streaming.kinesis configuration is required when service_type is kinesis".to_string()));
+                validate_topic_template(v, &format!("{}.kinesis.stream_name", prefix), &kinesis.stream_name);
             }
+            validate_topic_routes(v, &format!("{}.kinesis.event_streams", prefix), &kinesis.event_streams);
+            validate_send_timeout(v, &format!("{}.kinesis.send_timeout_ms", prefix), kinesis.send_timeout_ms);
+            validate_max_in_flight(v, &format!("{}.kinesis.max_in_flight", prefix), kinesis.max_in_flight);
         }
         StreamingServiceType::Pulsar => {
-            if let Some(ref pulsar) = config.streaming.pulsar {
-                if pulsar.url.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.pulsar.url is empty".to_string()));
+            let Some(ref pulsar) = streaming.pulsar else {
+                v.expect(format!("{}.pulsar", prefix), "a pulsar section when service_type is pulsar");
+                return;
+            };
+            if pulsar.url.is_empty() {
+                v.reject(format!("{}.pulsar.url", prefix), "a Pulsar service URL", "\"\"");
+            }
+            if pulsar.topic.is_empty() {
+                v.reject(format!("{}.pulsar.topic", prefix), "a topic name", "\"\"");
+            } else {
+                validate_topic_template(v, &format!("{}.pulsar.topic", prefix), &pulsar.topic);
+            }
+            validate_topic_routes(v, &format!("{}.pulsar.event_topics", prefix), &pulsar.event_topics);
+            validate_send_timeout(v, &format!("{}.pulsar.send_timeout_ms", prefix), pulsar.send_timeout_ms);
+            validate_max_in_flight(v, &format!("{}.pulsar.max_in_flight", prefix), pulsar.max_in_flight);
+            match pulsar.dead_letter_topic.as_deref() {
+                Some("") => v.reject(format!("{}.pulsar.dead_letter_topic", prefix), "a topic name", "\"\""),
+                Some(topic) => validate_topic_template(v, &format!("{}.pulsar.dead_letter_topic", prefix), topic),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Validate one entry of `server.listeners`
+fn validate_listener(v: &mut Validator, field: &str, listener: &ListenerConfig) {
+    match listener {
+        ListenerConfig::Http { address } | ListenerConfig::Https { address, .. } => {
+            if address.parse::<std::net::SocketAddr>().is_err() {
+                v.reject(
+                    format!("{}.address", field),
+                    "an IP address and port, e.g. \"0.0.0.0:8080\"",
+                    format!("{:?}", address),
+                );
+            }
+            if let ListenerConfig::Https { cert_path, key_path, .. } = listener {
+                if cert_path.is_empty() {
+                    v.reject(format!("{}.cert_path", field), "a certificate file path", "\"\"");
                 }
-                if pulsar.topic.is_empty() {
-                    return Err(ConfigError::MissingFields("streaming.pulsar.topic is empty".to_string()));
+                if key_path.is_empty() {
+                    v.reject(format!("{}.key_path", field), "a private key file path", "\"\"");
                 }
-            } else {
-                return Err(ConfigError::MissingFields("streaming.pulsar configuration is required when service_type is pulsar".to_string()));
+            }
+        }
+        ListenerConfig::Unix { path, permissions } => {
+            if !cfg!(unix) {
+                v.expect(field.to_string(), "a listener type supported on this platform (Unix sockets are not)");
+            }
+            if path.is_empty() {
+                v.reject(format!("{}.path", field), "a socket path", "\"\"");
+            }
+            if let Some(permissions) = permissions.as_deref().filter(|p| parse_socket_permissions(p).is_none()) {
+                v.reject(
+                    format!("{}.permissions", field),
+                    "an octal mode such as \"660\"",
+                    format!("{:?}", permissions),
+                );
             }
         }
     }
-    
-    // GeoIP config is optional - empty path means GeoIP enrichment is disabled
-    // No validation needed
-    
-    // Validate logging config
-    let valid_levels = ["trace", "debug", "info", "warn", "error"];
-    if !valid_levels.contains(&config.logging.level.to_lowercase().as_str()) {
-        return Err(ConfigError::MissingFields(
-            format!("logging.level must be one of: {:?}", valid_levels)
-        ));
+}
+
+/// Validate a topic/stream name template such as "analytics-{project}"
+fn validate_topic_template(v: &mut Validator, field: &str, template: &str) {
+    if let Err(e) = TopicTemplate::parse(template) {
+        v.reject(field.to_string(), "a name with {project} / {event} placeholders", e);
+    }
+}
+
+/// Validate event name → topic overrides
+fn validate_topic_routes(v: &mut Validator, field: &str, routes: &HashMap<String, String>) {
+    let mut event_names: Vec<&String> = routes.keys().collect();
+    event_names.sort();
+    for event_name in event_names {
+        if event_name.is_empty() {
+            v.reject(field.to_string(), "non-empty event names", "an empty name");
+        } else {
+            validate_topic_template(v, &format!("{}.{}", field, event_name), &routes[event_name]);
+        }
+    }
+}
+
+/// Validate a per-sink send timeout
+fn validate_send_timeout(v: &mut Validator, field: &str, send_timeout_ms: Option<u64>) {
+    if send_timeout_ms == Some(0) {
+        v.reject(field.to_string(), "a non-zero duration", 0);
+    }
+}
+
+/// Validate a per-sink concurrency limit
+fn validate_max_in_flight(v: &mut Validator, field: &str, max_in_flight: Option<usize>) {
+    if max_in_flight == Some(0) {
+        v.reject(field.to_string(), "a non-zero limit", 0);
     }
-    
-    Ok(())
 }
 
 #[cfg(test)]
@@ -0,0 +1,190 @@
+// Effective configuration
+// This module renders the loaded configuration, defaults included, with secret values masked
+
+use serde_yaml::Value;
+
+use super::Config;
+
+/// Shown in place of a secret value
+pub const REDACTED: &str = "********";
+
+/// Settings whose values are secrets, wherever they appear
+const SECRET_SETTINGS: &[&str] = &["password", "salt", "headers"];
+
+/// Name endings of settings whose values are secrets (e.g. `admin_api_keys`)
+const SECRET_SUFFIXES: &[&str] = &["api_keys", "_key", "token"];
+
+/// The configuration as the process runs with it, with secrets masked
+///
+/// Every setting is included, defaults too. Values of `password`, `salt` and `headers`
+/// settings, of settings ending in `api_keys`, `_key` or `token`, and passwords
+/// embedded in URLs (`redis://:secret@host`) are replaced by `REDACTED`.
+pub fn effective_config(config: &Config) -> Value {
+    let mut value = serde_yaml::to_value(config).unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Failed to serialize configuration");
+        Value::Null
+    });
+    redact(&mut value);
+    value
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                if key.as_str().is_some_and(is_secret_setting) {
+                    mask(value);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(redact),
+        Value::String(s) => {
+            if let Some(masked) = mask_url_password(s) {
+                *s = masked;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_secret_setting(key: &str) -> bool {
+    SECRET_SETTINGS.contains(&key) || SECRET_SUFFIXES.iter().any(|suffix| key.ends_with(suffix))
+}
+
+/// Replace every string in `value`, keeping the shape of lists and mappings
+fn mask(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => mapping.iter_mut().for_each(|(_, value)| mask(value)),
+        Value::Sequence(items) => items.iter_mut().for_each(mask),
+        Value::String(s) if !s.is_empty() => *s = REDACTED.to_string(),
+        _ => {}
+    }
+}
+
+/// `url` with its password masked, or None when it is not a URL with a password
+fn mask_url_password(url: &str) -> Option<String> {
+    if !url.contains("://") || !url.contains('@') {
+        return None;
+    }
+    let mut parsed = url::Url::parse(url).ok()?;
+    parsed.password()?;
+    parsed.set_password(Some(REDACTED)).ok()?;
+    Some(parsed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        FingerprintConfig, HttpEnricherConfig, IpHashConfig, IpOverrideConfig, KafkaConfig, KafkaSaslConfig,
+        OtlpConfig, PiiConfig, ProjectConfig, SessionConfig, StreamingConfig,
+    };
+
+    #[test]
+    fn test_secrets_are_masked() {
+        let config = Config {
+            streaming: StreamingConfig {
+                kafka: Some(KafkaConfig {
+                    brokers: vec!["localhost:9092".to_string()],
+                    topic: "analytics".to_string(),
+                    sasl: Some(KafkaSaslConfig {
+                        mechanism: "PLAIN".to_string(),
+                        security_protocol: "SASL_SSL".to_string(),
+                        username: "collector".to_string(),
+                        password: "s3cret".to_string(),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ip_hash: Some(IpHashConfig {
+                salt: "pepper".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let value = effective_config(&config);
+        let sasl = &value["streaming"]["kafka"]["sasl"];
+        assert_eq!(sasl["username"], "collector");
+        assert_eq!(sasl["password"], REDACTED);
+        assert_eq!(value["ip_hash"]["salt"], REDACTED);
+        // Defaults are included
+        assert_eq!(value["server"]["port"], 8080);
+        assert_eq!(value["logging"]["level"], "info");
+        let rendered = serde_yaml::to_string(&value).unwrap();
+        assert!(!rendered.contains("s3cret") && !rendered.contains("pepper"));
+    }
+
+    #[test]
+    fn test_every_secret_setting_is_masked() {
+        let secret = "t0p-s3cret";
+        let mut config = Config::default();
+        config.server.admin_api_keys = vec![secret.to_string()];
+        config.server.ip_override = IpOverrideConfig {
+            api_keys: vec![secret.to_string()],
+            ..Default::default()
+        };
+        config.streaming.kafka = Some(KafkaConfig {
+            sasl: Some(KafkaSaslConfig {
+                mechanism: "PLAIN".to_string(),
+                security_protocol: "SASL_SSL".to_string(),
+                username: "collector".to_string(),
+                password: secret.to_string(),
+            }),
+            ..Default::default()
+        });
+        config.ip_hash = Some(IpHashConfig {
+            salt: secret.to_string(),
+            ..Default::default()
+        });
+        config.fingerprint = Some(FingerprintConfig {
+            salt: secret.to_string(),
+            ..Default::default()
+        });
+        config.enrichment.pii = Some(PiiConfig {
+            salt: secret.to_string(),
+            ..Default::default()
+        });
+        config.enrichment.http = Some(HttpEnricherConfig {
+            url: "https://crm.internal/accounts/{key}".to_string(),
+            key_param: "u_account".to_string(),
+            headers: [("authorization".to_string(), secret.to_string())].into(),
+            ..Default::default()
+        });
+        config.logging.otlp = Some(OtlpConfig {
+            headers: [("x-api-key".to_string(), secret.to_string())].into(),
+            ..Default::default()
+        });
+        config.projects.insert(
+            "shop".to_string(),
+            ProjectConfig {
+                api_keys: vec![secret.to_string()],
+                ..Default::default()
+            },
+        );
+        config.session = Some(SessionConfig {
+            redis_url: format!("redis://:{}@redis.internal:6379", secret),
+            ..Default::default()
+        });
+
+        let rendered = serde_yaml::to_string(&effective_config(&config)).unwrap();
+        assert!(!rendered.contains(secret), "secret left in:\n{}", rendered);
+        // Settings only named like secrets are kept
+        assert!(rendered.contains("u_account"));
+        assert!(is_secret_setting("admin_api_keys") && is_secret_setting("auth_token"));
+        assert!(!is_secret_setting("key_param") && !is_secret_setting("sanitize_keys"));
+    }
+
+    #[test]
+    fn test_url_passwords_are_masked() {
+        assert_eq!(
+            mask_url_password("redis://:hunter2@redis.internal:6379/0").as_deref(),
+            Some("redis://:********@redis.internal:6379/0")
+        );
+        assert_eq!(mask_url_password("redis://redis.internal:6379"), None);
+        assert_eq!(mask_url_password("user@example.com"), None);
+    }
+}
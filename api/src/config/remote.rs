@@ -0,0 +1,166 @@
+// Remote configuration
+// This module fetches the configuration from an HTTP(S) URL or an S3 object
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde_yaml::{Mapping, Value};
+
+use super::include::parse_document;
+use super::secrets::run_blocking;
+use super::ConfigError;
+
+/// Configuration object in S3: `s3://<bucket>/<key>`
+const S3_SCHEME: &str = "s3://";
+
+/// A fetched configuration document
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteDocument {
+    pub contents: String,
+    /// Version of the document, sent back to only fetch it again once it changed
+    pub etag: Option<String>,
+}
+
+/// Whether `location` names a remote configuration rather than a file or directory
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://") || location.starts_with(S3_SCHEME)
+}
+
+/// Fetch the configuration at `location` and parse it like a local file
+///
+/// The format follows the extension of the URL path or object key (JSON, TOML,
+/// YAML otherwise). Remote configurations cannot `include` other files.
+pub fn load_document(location: &str) -> Result<Value, ConfigError> {
+    let owned = location.to_string();
+    let document = run_blocking(async move {
+        fetch_remote(&owned, None)
+            .await
+            .map(|document| document.map(|document| document.contents).unwrap_or_default())
+    })
+    .map_err(ConfigError::Remote)?;
+
+    let mut value = parse_document(Path::new(document_path(location)), &document)?;
+    if value.is_null() {
+        value = Value::Mapping(Mapping::new());
+    }
+    if value.get(super::include::INCLUDE_KEY).is_some() {
+        return Err(ConfigError::Remote(format!("{}: include is not supported in remote configurations", location)));
+    }
+    Ok(value)
+}
+
+/// Fetch `location`; with `etag`, `None` means the document has not changed since
+pub async fn fetch_remote(location: &str, etag: Option<&str>) -> Result<Option<RemoteDocument>, String> {
+    match location.strip_prefix(S3_SCHEME) {
+        Some(object) => fetch_s3(object, etag).await,
+        None => fetch_http(location, etag).await,
+    }
+}
+
+/// Poll `location` every `interval` until it holds a new configuration that loads
+///
+/// `current` is the version in use. A version is new when its ETag differs (or its
+/// contents, for sources that send no ETag). New versions that fail to load with
+/// `profile` are logged and skipped, so a broken push never takes a collector down.
+pub async fn wait_for_change(location: String, profile: Option<String>, interval: Duration, current: RemoteDocument) {
+    let mut current = current;
+    loop {
+        tokio::time::sleep(interval).await;
+        let latest = match fetch_remote(&location, current.etag.as_deref()).await {
+            Ok(Some(latest)) if latest != current => latest,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!(location = %location, error = %e, "Failed to check the remote configuration");
+                continue;
+            }
+        };
+        let (check_location, check_profile) = (location.clone(), profile.clone());
+        let loaded = tokio::task::spawn_blocking(move || {
+            super::load_config_with_profile(&check_location, check_profile.as_deref()).map(|_| ())
+        })
+        .await;
+        match loaded {
+            Ok(Ok(())) => {
+                tracing::info!(location = %location, etag = ?latest.etag, "Remote configuration changed");
+                return;
+            }
+            Ok(Err(e)) => {
+                tracing::error!(location = %location, etag = ?latest.etag, error = %e, "Ignoring invalid remote configuration");
+            }
+            Err(e) => {
+                tracing::error!(location = %location, error = %e, "Failed to load the remote configuration");
+            }
+        }
+        current = latest;
+    }
+}
+
+/// The path part of `location`, whose extension selects the format
+fn document_path(location: &str) -> &str {
+    let location = location.split(['?', '#']).next().unwrap_or(location);
+    location.rsplit('/').next().unwrap_or(location)
+}
+
+async fn fetch_http(url: &str, etag: Option<&str>) -> Result<Option<RemoteDocument>, String> {
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let response = request.send().await.map_err(|e| format!("cannot fetch {}: {}", url, e))?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status().map_err(|e| format!("cannot fetch {}: {}", url, e))?;
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let contents = response.text().await.map_err(|e| format!("cannot fetch {}: {}", url, e))?;
+    Ok(Some(RemoteDocument { contents, etag }))
+}
+
+async fn fetch_s3(object: &str, etag: Option<&str>) -> Result<Option<RemoteDocument>, String> {
+    let (bucket, key) = object
+        .split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(|| format!("{}{} must be s3://<bucket>/<key>", S3_SCHEME, object))?;
+    let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).load().await;
+    let output = aws_sdk_s3::Client::new(&aws_config)
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .set_if_none_match(etag.map(str::to_string))
+        .send()
+        .await;
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.raw_response().is_some_and(|response| response.status().as_u16() == 304) => return Ok(None),
+        Err(e) => return Err(format!("cannot fetch {}{}: {}", S3_SCHEME, object, e)),
+    };
+    let etag = output.e_tag().map(str::to_string);
+    let bytes = output
+        .body
+        .collect()
+        .await
+        .map_err(|e| format!("cannot fetch {}{}: {}", S3_SCHEME, object, e))?
+        .into_bytes();
+    let contents = String::from_utf8(bytes.to_vec()).map_err(|_| format!("{}{} is not UTF-8 text", S3_SCHEME, object))?;
+    Ok(Some(RemoteDocument { contents, etag }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_locations() {
+        assert!(is_remote("https://config.internal/analytics.yaml"));
+        assert!(is_remote("s3://configs/analytics/prod.toml"));
+        assert!(!is_remote("config.yaml"));
+        assert!(!is_remote("/etc/analytics/conf.d"));
+
+        assert_eq!(document_path("https://config.internal/analytics.json?version=3"), "analytics.json");
+        assert_eq!(document_path("s3://configs/analytics/prod.toml"), "prod.toml");
+    }
+}
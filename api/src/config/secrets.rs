@@ -0,0 +1,307 @@
+// Secret references
+// This module resolves `*_file` settings and secret URIs in the raw configuration before it is parsed
+
+use serde_yaml::{Mapping, Value};
+
+use super::ConfigError;
+
+/// Suffix of settings whose value is read from a file
+const FILE_SUFFIX: &str = "_file";
+
+/// Value read from an environment variable: `env://NAME`
+const ENV_SCHEME: &str = "env://";
+/// Secret stored in AWS Secrets Manager: `aws-secrets://<secret id>[#<json key>]`
+const AWS_SECRETS_SCHEME: &str = "aws-secrets://";
+/// Secret stored in HashiCorp Vault: `vault://<path>#<key>`
+const VAULT_SCHEME: &str = "vault://";
+
+/// Maps keyed by project or header names whose values are settings: their keys are
+/// never `*_file` settings, but their values may reference secrets
+const NAMED_SETTINGS: &[&str] = &[
+    "projects",
+    "fields.projects",
+    "logging.otlp.headers",
+    "enrichment.http.headers",
+];
+
+/// Maps of user data (event names, topics, properties, ...), taken as written
+///
+/// Paths leave out list indices (`streaming.residency[0].kafka` is `streaming.residency.kafka`).
+const DATA_MAPS: &[&str] = &[
+    "streaming.project_topics",
+    "streaming.retention.projects",
+    "streaming.kafka.event_topics",
+    "streaming.kinesis.event_streams",
+    "streaming.pulsar.event_topics",
+    "streaming.residency.kafka.event_topics",
+    "streaming.residency.kinesis.event_streams",
+    "streaming.residency.pulsar.event_topics",
+    "logging.otlp.resource_attributes",
+    "logging.statsd.tags",
+    "events.aliases",
+    "events.projects",
+    "campaign.aliases",
+    "user_agent.os_families",
+    "enrichment.pii.properties",
+    "enrichment.pii.projects",
+];
+
+/// Replace every secret reference in `value`, the configuration as parsed from YAML
+///
+/// * A `<name>_file` setting is replaced by `<name>` holding the file's contents
+///   (without the trailing newline); setting both is an error.
+/// * A string value `env://NAME`, `aws-secrets://<secret id>[#<json key>]` or
+///   `vault://<path>#<key>` is replaced by the referenced secret. AWS credentials and
+///   region come from the environment; Vault is reached at `VAULT_ADDR` with `VAULT_TOKEN`.
+///
+/// Maps keyed by user data (`event_topics`, `events.aliases`, `*.headers`, ...) are not
+/// settings: their keys are never `*_file` settings, and only the values of `projects`
+/// and `headers` may reference secrets.
+///
+/// Secrets are resolved once, at startup. `path` names the setting in error messages.
+pub fn resolve_secrets(value: &mut Value, path: &str) -> Result<(), ConfigError> {
+    let setting = setting_path(path);
+    if DATA_MAPS.contains(&setting.as_str()) {
+        return Ok(());
+    }
+    match value {
+        Value::Mapping(mapping) => {
+            if !NAMED_SETTINGS.contains(&setting.as_str()) {
+                inline_files(mapping, path)?;
+            }
+            for (key, value) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default();
+                resolve_secrets(value, &join(path, key))?;
+            }
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                resolve_secrets(item, &format!("{}[{}]", path, index))?;
+            }
+        }
+        Value::String(s) => {
+            if let Some(secret) = resolve_reference(s).map_err(|e| secret_error(path, e))? {
+                *s = secret;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replace the `<name>_file` entries of `mapping` by `<name>` entries with the file contents
+fn inline_files(mapping: &mut Mapping, path: &str) -> Result<(), ConfigError> {
+    let file_keys: Vec<String> = mapping
+        .iter()
+        .filter_map(|(key, value)| {
+            let key = key.as_str()?;
+            (key.len() > FILE_SUFFIX.len() && key.ends_with(FILE_SUFFIX) && value.is_string()).then(|| key.to_string())
+        })
+        .collect();
+    for file_key in file_keys {
+        let name = &file_key[..file_key.len() - FILE_SUFFIX.len()];
+        if mapping.contains_key(name) {
+            return Err(secret_error(
+                &join(path, name),
+                format!("set either {} or {}, not both", name, file_key),
+            ));
+        }
+        let file = mapping.remove(&file_key).and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+        let contents = std::fs::read_to_string(&file)
+            .map_err(|e| secret_error(&join(path, &file_key), format!("cannot read {}: {}", file, e)))?;
+        let secret = contents.trim_end_matches(['\r', '\n']).to_string();
+        mapping.insert(Value::String(name.to_string()), Value::String(secret));
+    }
+    Ok(())
+}
+
+/// The secret `reference` points to, or None when it is a plain value
+fn resolve_reference(reference: &str) -> Result<Option<String>, String> {
+    if let Some(name) = reference.strip_prefix(ENV_SCHEME) {
+        return std::env::var(name)
+            .map(Some)
+            .map_err(|_| format!("environment variable {} is not set", name));
+    }
+    if let Some(id) = reference.strip_prefix(AWS_SECRETS_SCHEME) {
+        let (id, key) = split_key(id);
+        let secret = run_blocking(fetch_aws_secret(id.to_string()))?;
+        return match key {
+            Some(key) => json_field(&secret, &[key]).map(Some),
+            None => Ok(Some(secret)),
+        };
+    }
+    if let Some(path) = reference.strip_prefix(VAULT_SCHEME) {
+        let (path, key) = split_key(path);
+        let key = key.ok_or_else(|| format!("{} needs a #<key> suffix", reference))?;
+        let response = run_blocking(fetch_vault_secret(path.to_string()))?;
+        // KV version 2 nests the secret in data.data, version 1 in data
+        return json_field(&response, &["data", "data", key])
+            .or_else(|_| json_field(&response, &["data", key]))
+            .map(Some);
+    }
+    Ok(None)
+}
+
+/// Split `<location>#<key>` into its parts
+fn split_key(reference: &str) -> (&str, Option<&str>) {
+    match reference.split_once('#') {
+        Some((location, key)) => (location, Some(key)),
+        None => (reference, None),
+    }
+}
+
+/// String at `path` in the JSON document `json`
+fn json_field(json: &str, path: &[&str]) -> Result<String, String> {
+    let document: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("secret is not JSON: {}", e))?;
+    let field = path
+        .iter()
+        .try_fold(&document, |value, key| value.get(key))
+        .ok_or_else(|| format!("secret has no field {}", path.join(".")))?;
+    match field {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Run a secret lookup to completion from synchronous code
+///
+/// Configuration is loaded both inside and outside the Tokio runtime, so lookups run on
+/// a separate thread with their own runtime.
+pub(super) fn run_blocking<F>(lookup: F) -> Result<String, String>
+where
+    F: std::future::Future<Output = Result<String, String>> + Send + 'static,
+{
+    std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("cannot start secret lookup: {}", e))?
+            .block_on(lookup)
+    })
+    .join()
+    .map_err(|_| "secret lookup panicked".to_string())?
+}
+
+async fn fetch_aws_secret(id: String) -> Result<String, String> {
+    let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest()).load().await;
+    let output = aws_sdk_secretsmanager::Client::new(&aws_config)
+        .get_secret_value()
+        .secret_id(&id)
+        .send()
+        .await
+        .map_err(|e| format!("cannot read AWS secret {}: {}", id, e))?;
+    output
+        .secret_string()
+        .map(str::to_string)
+        .ok_or_else(|| format!("AWS secret {} has no string value", id))
+}
+
+async fn fetch_vault_secret(path: String) -> Result<String, String> {
+    let address = std::env::var("VAULT_ADDR").map_err(|_| "VAULT_ADDR is not set".to_string())?;
+    let token = std::env::var("VAULT_TOKEN").map_err(|_| "VAULT_TOKEN is not set".to_string())?;
+    let url = format!("{}/v1/{}", address.trim_end_matches('/'), path.trim_start_matches('/'));
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("cannot read Vault secret {}: {}", path, e))?;
+    response
+        .text()
+        .await
+        .map_err(|e| format!("cannot read Vault secret {}: {}", path, e))
+}
+
+/// `path` without list indices, as listed in `NAMED_SETTINGS` and `DATA_MAPS`
+fn setting_path(path: &str) -> String {
+    let mut setting = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            c if !in_index => setting.push(c),
+            _ => {}
+        }
+    }
+    setting
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn secret_error(path: &str, message: impl std::fmt::Display) -> ConfigError {
+    ConfigError::Secret(format!("{}: {}", path, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_field() {
+        let kv2 = r#"{"data": {"data": {"password": "s3cret", "port": 9092}}}"#;
+        assert_eq!(json_field(kv2, &["data", "data", "password"]).unwrap(), "s3cret");
+        assert_eq!(json_field(kv2, &["data", "data", "port"]).unwrap(), "9092");
+        assert!(json_field(kv2, &["data", "password"]).is_err());
+        assert!(json_field("plain", &["password"]).is_err());
+    }
+
+    #[test]
+    fn test_plain_values_are_kept() {
+        let mut value: Value = serde_yaml::from_str("url: \"pulsar://localhost:6650\"\nbrokers: [a, b]\nport: 8080").unwrap();
+        let original = value.clone();
+        resolve_secrets(&mut value, "").unwrap();
+        assert_eq!(value, original);
+    }
+
+    #[test]
+    fn test_user_keyed_maps_are_not_settings() {
+        use std::io::Write;
+
+        let mut salt = tempfile::NamedTempFile::new().unwrap();
+        writeln!(salt, "s3cret").unwrap();
+        let yaml = format!(
+            r#"
+ip_hash:
+  salt_file: "{}"
+streaming:
+  kafka:
+    event_topics:
+      upload_file: analytics-uploads
+      signup: "env://PENROSE_UNSET_TOPIC"
+  residency:
+    - kafka:
+        event_topics:
+          export_file: analytics-exports
+logging:
+  otlp:
+    headers:
+      x-token_file: "/nonexistent"
+"#,
+            salt.path().display()
+        );
+        let mut value: Value = serde_yaml::from_str(&yaml).unwrap();
+        resolve_secrets(&mut value, "").unwrap();
+
+        assert_eq!(value["ip_hash"]["salt"], Value::from("s3cret"));
+        let topics = &value["streaming"]["kafka"]["event_topics"];
+        assert_eq!(topics["upload_file"], Value::from("analytics-uploads"));
+        assert_eq!(topics["signup"], Value::from("env://PENROSE_UNSET_TOPIC"));
+        let residency_topics = &value["streaming"]["residency"][0]["kafka"]["event_topics"];
+        assert_eq!(residency_topics["export_file"], Value::from("analytics-exports"));
+        assert_eq!(value["logging"]["otlp"]["headers"]["x-token_file"], Value::from("/nonexistent"));
+    }
+
+    #[test]
+    fn test_setting_path() {
+        assert_eq!(setting_path("streaming.residency[2].kafka.event_topics"), "streaming.residency.kafka.event_topics");
+        assert_eq!(setting_path("server.admin_api_keys[0]"), "server.admin_api_keys");
+    }
+}
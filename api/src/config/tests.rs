@@ -111,6 +111,110 @@ logging:
         }
     }
 
+    #[test]
+    fn test_near_empty_config_uses_defaults() {
+        for config_content in ["", "# development\n", "streaming:\n  output:\n    format: flat\n"] {
+            let temp_file = create_temp_config(config_content);
+            let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+            assert_eq!(config.server.host, "0.0.0.0");
+            assert_eq!(config.server.port, 8080);
+            assert_eq!(config.logging.level, "info");
+            assert_eq!(config.geoip.database_path, "");
+            assert_eq!(config.streaming.service_type, StreamingServiceType::Stdout);
+        }
+
+        let temp_file = create_temp_config("server:\n  port: 9090\n");
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 9090);
+    }
+
+    #[test]
+    fn test_config_includes_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("base.yaml"),
+            "server:\n  port: 9090\n  trusted_proxies: [\"10.0.0.0/8\"]\nlogging:\n  level: debug\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("conf.d")).unwrap();
+        std::fs::write(dir.path().join("conf.d/10-kafka.yaml"), "streaming:\n  service_type: kafka\n  kafka:\n    brokers: [\"a:9092\"]\n    topic: analytics\n").unwrap();
+        std::fs::write(dir.path().join("conf.d/20-prod.yml"), "streaming:\n  kafka:\n    brokers: [\"prod:9092\"]\n").unwrap();
+        std::fs::write(dir.path().join("conf.d/README.md"), "not configuration").unwrap();
+        let main = dir.path().join("config.yaml");
+        std::fs::write(&main, "include:\n  - base.yaml\n  - conf.d\nserver:\n  host: \"127.0.0.1\"\n").unwrap();
+
+        let config = load_config(main.to_str().unwrap()).unwrap();
+        // The including file wins over included files, later files over earlier ones
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.server.trusted_proxies, vec!["10.0.0.0/8"]);
+        assert_eq!(config.logging.level, "debug");
+        let kafka = config.streaming.kafka.as_ref().unwrap();
+        assert_eq!(kafka.brokers, vec!["prod:9092"]);
+        assert_eq!(kafka.topic, "analytics");
+
+        // A directory can be loaded directly
+        let config = load_config(dir.path().join("conf.d").to_str().unwrap()).unwrap();
+        assert_eq!(config.streaming.kafka.unwrap().brokers, vec!["prod:9092"]);
+
+        std::fs::write(&main, "include: missing.yaml\n").unwrap();
+        let result = load_config(main.to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Include(msg)) if msg.contains("missing.yaml (included by")));
+    }
+
+    #[test]
+    fn test_profiles_override_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "server:\n  port: 8080\nlogging:\n  level: info\nprofiles:\n  dev:\n    logging:\n      level: debug\n  prod:\n    server:\n      port: 9090\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let config = load_config_with_profile(path, Some("dev")).unwrap();
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.server.port, 8080);
+
+        let config = load_config_with_profile(path, Some("prod")).unwrap();
+        assert_eq!(config.logging.level, "info");
+        assert_eq!(config.server.port, 9090);
+
+        // Without a profile the profiles are ignored
+        let config = load_config_with_profile(path, None).unwrap();
+        assert_eq!(config.logging.level, "info");
+        assert_eq!(config.server.port, 8080);
+
+        let result = load_config_with_profile(path, Some("missing"));
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "profiles: expected a profile named \"missing\", got [\"dev\", \"prod\"]"));
+    }
+
+    #[test]
+    fn test_runtime_settings_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let runtime_path = dir.path().join("runtime.yaml");
+        std::fs::write(
+            &path,
+            format!("server:\n  runtime_settings_path: {:?}\nlogging:\n  level: info\n", runtime_path.to_str().unwrap()),
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        // A missing file is not an error: nothing was persisted yet
+        assert_eq!(load_config(path).unwrap().logging.level, "info");
+
+        std::fs::write(&runtime_path, "logging:\n  level: debug\nfilters:\n  drop_bots: true\n").unwrap();
+        let config = load_config(path).unwrap();
+        assert_eq!(config.logging.level, "debug");
+        assert!(config.filters.drop_bots);
+
+        std::fs::write(&runtime_path, "logging:\n  level: loud\n").unwrap();
+        assert!(matches!(load_config(path), Err(ConfigError::Invalid(issues)) if issues[0].field == "logging.level"));
+    }
+
     #[test]
     fn test_invalid_yaml_syntax() {
         let config_content = r#"
@@ -152,13 +256,60 @@ logging:
         let result = load_config(temp_file.path().to_str().unwrap());
         assert!(result.is_err());
         match result {
-            Err(ConfigError::MissingFields(msg)) => {
-                assert!(msg.contains("server.host"));
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("server.host"));
             },
-            _ => panic!("Expected MissingFields error"),
+            _ => panic!("Expected Invalid error"),
         }
     }
 
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let config_content = r#"
+server:
+  host: ""
+  port: 0
+streaming:
+  service_type: kafka
+  kafka:
+    brokers: []
+    topic: "analytics"
+    sasl:
+      mechanism: "GSSAPI"
+      username: "collector"
+      password: "hunter2"
+logging:
+  level: "verbose"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let issues = match load_config(temp_file.path().to_str().unwrap()) {
+            Err(ConfigError::Invalid(issues)) => issues,
+            other => panic!("Expected Invalid error, got {:?}", other.map(|_| ())),
+        };
+        let fields: Vec<&str> = issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "server.host",
+                "server.port",
+                "streaming.kafka.brokers",
+                "streaming.kafka.sasl.mechanism",
+                "logging.level",
+            ]
+        );
+        assert_eq!(
+            issues[4],
+            ValidationIssue {
+                field: "logging.level".to_string(),
+                expected: "one of [\"trace\", \"debug\", \"info\", \"warn\", \"error\"]".to_string(),
+                got: Some("\"verbose\"".to_string()),
+            }
+        );
+        let message = ConfigError::Invalid(issues).to_string();
+        assert!(message.starts_with("Invalid configuration (5 problem(s)):\n  server.host: expected a host name or address, got \"\""));
+        assert!(!message.contains("hunter2"));
+    }
+
     #[test]
     fn test_missing_kafka_config() {
         let config_content = r#"
@@ -179,10 +330,10 @@ logging:
         let result = load_config(temp_file.path().to_str().unwrap());
         assert!(result.is_err());
         match result {
-            Err(ConfigError::MissingFields(msg)) => {
-                assert!(msg.contains("kafka"));
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("kafka"));
             },
-            _ => panic!("Expected MissingFields error"),
+            _ => panic!("Expected Invalid error"),
         }
     }
 
@@ -209,10 +360,10 @@ logging:
         let result = load_config(temp_file.path().to_str().unwrap());
         assert!(result.is_err());
         match result {
-            Err(ConfigError::MissingFields(msg)) => {
-                assert!(msg.contains("brokers"));
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("brokers"));
             },
-            _ => panic!("Expected MissingFields error"),
+            _ => panic!("Expected Invalid error"),
         }
     }
 
@@ -240,10 +391,10 @@ logging:
         let result = load_config(temp_file.path().to_str().unwrap());
         assert!(result.is_err());
         match result {
-            Err(ConfigError::MissingFields(msg)) => {
-                assert!(msg.contains("logging.level"));
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("logging.level"));
             },
-            _ => panic!("Expected MissingFields error"),
+            _ => panic!("Expected Invalid error"),
         }
     }
 
@@ -271,10 +422,1702 @@ logging:
         let result = load_config(temp_file.path().to_str().unwrap());
         assert!(result.is_err());
         match result {
-            Err(ConfigError::MissingFields(msg)) => {
-                assert!(msg.contains("port"));
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("port"));
+            },
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_templated_kafka_topic() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics-{project}"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).expect("Failed to load config");
+        assert_eq!(config.streaming.kafka.unwrap().topic, "analytics-{project}");
+    }
+
+    #[test]
+    fn test_unknown_topic_placeholder() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics-{tenant}"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("streaming.kafka.topic"));
+            },
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_zero_send_timeout() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+    send_timeout_ms: 0
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("send_timeout_ms"));
+            },
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_duration_and_size_units() {
+        let config_content = r#"
+streaming:
+  service_type: kafka
+  kafka:
+    brokers: ["localhost:9092"]
+    topic: "analytics"
+    send_timeout_ms: 2s
+    create_topics:
+      retention_ms: 7d
+  health_check:
+    interval_secs: 1m
+    timeout_secs: 5
+parameters:
+  max_json_bytes: 4KiB
+  payload:
+    max_bytes: "1 MB"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).expect("Failed to load config");
+        let kafka = config.streaming.kafka.as_ref().unwrap();
+        assert_eq!(kafka.send_timeout_ms, Some(2_000));
+        assert_eq!(kafka.create_topics.as_ref().unwrap().retention_ms, Some(604_800_000));
+        assert_eq!(config.streaming.health_check.interval_secs, 60);
+        assert_eq!(config.streaming.health_check.timeout_secs, 5);
+        assert_eq!(config.parameters.max_json_bytes, 4_096);
+        assert_eq!(config.parameters.payload.max_bytes, 1_000_000);
+
+        let temp_file = create_temp_config("streaming:\n  health_check:\n    timeout_secs: 1500ms\n");
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::InvalidYaml(e)) if e.to_string().contains("not a whole number of seconds")));
+    }
+
+    #[test]
+    fn test_filter_rules() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+filters:
+  drop_bots: true
+  drop_ip_ranges:
+    - "10.0.0.0/8"
+    - "fd00::/8"
+  drop_event_names:
+    - "debug_*"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.filters.drop_bots);
+        assert_eq!(config.filters.drop_ip_ranges.len(), 2);
+        assert_eq!(config.filters.drop_event_names, vec!["debug_*".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_filter_ip_range() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+filters:
+  drop_ip_ranges:
+    - "10.0.0.0/99"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("filters.drop_ip_ranges"));
+            },
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_pulsar_dead_letter_topic() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: pulsar
+  pulsar:
+    url: "pulsar://localhost:6650"
+    topic: "analytics-events"
+    dead_letter_topic: "analytics-dlq-{project}"
+    max_redeliveries: 3
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let pulsar = config.streaming.pulsar.unwrap();
+        assert_eq!(pulsar.dead_letter_topic.as_deref(), Some("analytics-dlq-{project}"));
+        assert_eq!(pulsar.max_redeliveries, 3);
+    }
+
+    #[test]
+    fn test_pulsar_empty_dead_letter_topic() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: pulsar
+  pulsar:
+    url: "pulsar://localhost:6650"
+    topic: "analytics-events"
+    dead_letter_topic: ""
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("streaming.pulsar.dead_letter_topic"));
             },
-            _ => panic!("Expected MissingFields error"),
+            _ => panic!("Expected Invalid error"),
         }
     }
+
+    #[test]
+    fn test_kafka_create_topics() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+    create_topics:
+      partitions: 6
+      retention_ms: 86400000
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let create_topics = config.streaming.kafka.unwrap().create_topics.unwrap();
+        assert_eq!(create_topics.partitions, 6);
+        assert_eq!(create_topics.replication_factor, 1);
+        assert_eq!(create_topics.retention_ms, Some(86400000));
+    }
+
+    #[test]
+    fn test_kafka_create_topics_zero_partitions() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+    create_topics:
+      partitions: 0
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert!(issues[0].field.contains("create_topics.partitions"));
+            },
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_kafka_sasl_secrets() {
+        let mut password_file = NamedTempFile::new().unwrap();
+        password_file.write_all(b"s3cret\n").unwrap();
+        std::env::set_var("TEST_KAFKA_SASL_USERNAME", "collector");
+        let config_content = format!(
+            r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+    sasl:
+      mechanism: SCRAM-SHA-512
+      username: "env://TEST_KAFKA_SASL_USERNAME"
+      password_file: "{}"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#,
+            password_file.path().display()
+        );
+        let temp_file = create_temp_config(&config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let sasl = config.streaming.kafka.unwrap().sasl.unwrap();
+        assert_eq!(sasl.mechanism, "SCRAM-SHA-512");
+        assert_eq!(sasl.security_protocol, "SASL_SSL");
+        assert_eq!(sasl.username, "collector");
+        assert_eq!(sasl.password, "s3cret");
+
+        let both = config_content.replace("      password_file:", "      password: \"x\"\n      password_file:");
+        let temp_file = create_temp_config(&both);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Secret(msg)) if msg == "streaming.kafka.sasl.password: set either password or password_file, not both"));
+
+        let unset = config_content.replace("TEST_KAFKA_SASL_USERNAME", "TEST_KAFKA_SASL_UNSET");
+        let temp_file = create_temp_config(&unset);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Secret(msg)) if msg == "streaming.kafka.sasl.username: environment variable TEST_KAFKA_SASL_UNSET is not set"));
+
+        let invalid = config_content.replace("SCRAM-SHA-512", "GSSAPI");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("streaming.kafka.sasl.mechanism: expected one of")));
+    }
+
+    #[test]
+    fn test_ack_mode_defaults_to_broker() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.streaming.ack_mode, AckMode::Broker);
+        assert_eq!(config.streaming.queue.capacity, 10_000);
+        assert_eq!(config.streaming.queue.drain_timeout_ms, 30_000);
+    }
+
+    #[test]
+    fn test_ack_mode_queued() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+  ack_mode: queued
+  queue:
+    capacity: 500
+    workers: 4
+    drain_timeout_ms: 5s
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.streaming.ack_mode, AckMode::Queued);
+        assert_eq!(config.streaming.queue.capacity, 500);
+        assert_eq!(config.streaming.queue.workers, 4);
+        assert_eq!(config.streaming.queue.drain_timeout_ms, 5_000);
+    }
+
+    #[test]
+    fn test_output_format() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+  output:
+    format: flat
+    separator: underscore
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.streaming.output.format, OutputFormat::Flat);
+        assert_eq!(config.streaming.output.separator.as_str(), "_");
+        assert_eq!(StreamingConfig::default().output.format, OutputFormat::Nested);
+        assert_eq!(OutputConfig::default().separator, KeySeparator::Dot);
+    }
+
+    #[test]
+    fn test_sink_wire_format() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+    format: msgpack
+  pulsar:
+    url: "pulsar://localhost:6650"
+    topic: "analytics"
+    format: cbor
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.streaming.kafka.unwrap().format, WireFormat::MsgPack);
+        assert_eq!(config.streaming.pulsar.unwrap().format, WireFormat::Cbor);
+        assert_eq!(KinesisConfig::default().format, WireFormat::Json);
+        assert_eq!(WireFormat::MsgPack.content_type(), "application/msgpack");
+    }
+
+    #[test]
+    fn test_retention_config() {
+        let config_content = |retention: &str| {
+            format!(
+                r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+  retention:
+{}
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#,
+                retention
+            )
+        };
+
+        let temp_file = create_temp_config(&config_content("    default_days: 90\n    projects:\n      trial.example: 7"));
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let retention = config.streaming.retention.unwrap();
+        assert_eq!(retention.ttl_days(Some("trial.example")), Some(7));
+        assert_eq!(retention.ttl_days(Some("other.example")), Some(90));
+        assert_eq!(retention.ttl_days(None), Some(90));
+
+        let temp_file = create_temp_config(&config_content("    projects:\n      trial.example: 0"));
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].field == "streaming.retention.projects.trial.example"));
+    }
+
+    #[test]
+    fn test_residency_rules() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+  residency:
+    - name: eu
+      countries: ["EU", "ch"]
+      service_type: kafka
+      kafka:
+        brokers:
+          - "kafka.eu-central-1.internal:9092"
+        topic: "analytics-eu"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let rule = &config.streaming.residency[0];
+        assert_eq!(rule.name, "eu");
+
+        let codes = rule.country_codes();
+        assert_eq!(codes.len(), 28);
+        assert!(codes.contains(&"DE".to_string()));
+        assert!(codes.contains(&"CH".to_string()));
+        assert_eq!(rule.sink_config().kafka.unwrap().topic, "analytics-eu");
+    }
+
+    #[test]
+    fn test_residency_rule_invalid_sink() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+  residency:
+    - name: eu
+      countries: ["EU"]
+      service_type: kafka
+      kafka:
+        brokers: []
+        topic: "analytics-eu"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert_eq!(issues[0].field, "streaming.residency[0].kafka.brokers");
+            }
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_campaign_alias_unknown_field() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+campaign:
+  aliases:
+    referrer: ["ref"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("campaign.aliases.referrer: expected a campaign field")));
+    }
+
+    #[test]
+    fn test_user_agent_parser_defaults_to_woothee() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.user_agent.parser, UserAgentParserType::Woothee);
+    }
+
+    #[test]
+    fn test_uap_parser_requires_regexes_path() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+user_agent:
+  parser: uap
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        match result {
+            Err(ConfigError::Invalid(issues)) => {
+                assert_eq!(issues[0].to_string(), "user_agent.regexes_path: expected a regexes.yaml path when user_agent.parser is uap");
+            }
+            _ => panic!("Expected Invalid error"),
+        }
+    }
+
+    #[test]
+    fn test_geoip_cache_defaults() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+  cache:
+    ttl_secs: 30
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let cache = config.geoip.cache.unwrap();
+        assert_eq!(cache.ttl_secs, 30);
+        assert_eq!(cache.max_entries, 10_000);
+        assert_eq!(cache.ipv4_prefix, 32);
+        assert_eq!(cache.ipv6_prefix, 128);
+    }
+
+    #[test]
+    fn test_geoip_cache_invalid_prefix() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+  cache:
+    ipv4_prefix: 33
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "geoip.cache.ipv4_prefix: expected a prefix length of at most 32, got 33"));
+    }
+
+    #[test]
+    fn test_enrichment_pipeline_order() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [geoip, user_agent]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.enrichment.pipeline, vec![EnricherKind::Geoip, EnricherKind::UserAgent]);
+    }
+
+    #[test]
+    fn test_enrichment_pipeline_duplicate_stage() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [user_agent, geoip, user_agent]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.pipeline: expected each stage at most once, got user_agent listed twice"));
+    }
+
+    #[test]
+    fn test_http_enricher_requires_key_placeholder() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [http]
+  http:
+    url: "https://crm.internal/customers"
+    key_param: "u_account"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.http.url: expected a URL with a {key} placeholder, got \"https://crm.internal/customers\""));
+    }
+
+    #[test]
+    fn test_script_enricher_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [user_agent, script]
+  script:
+    scripts:
+      - path: "common.rhai"
+      - path: "shop.rhai"
+        projects: ["shop"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let script = config.enrichment.script.unwrap();
+        assert_eq!(script.max_operations, 100_000);
+        assert_eq!(script.scripts.len(), 2);
+        assert!(script.scripts[0].projects.is_empty());
+        assert_eq!(script.scripts[1].projects, vec!["shop".to_string()]);
+    }
+
+    #[test]
+    fn test_script_stage_requires_script_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [script]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.script: expected a script section when the pipeline includes script"));
+    }
+
+    #[test]
+    fn test_fingerprint_config_defaults_and_validation() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+fingerprint:
+  salt: "secret"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let fingerprint = config.fingerprint.unwrap();
+        assert_eq!(fingerprint.components.len(), 4);
+        assert_eq!(fingerprint.ipv4_prefix, 24);
+        assert_eq!(fingerprint.ipv6_prefix, 48);
+
+        let invalid = config_content.replace("salt: \"secret\"", "salt: \"secret\"\n  components: []");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "fingerprint.components: expected at least one component, got an empty list"));
+    }
+
+    #[test]
+    fn test_session_redis_store_requires_url() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+session:
+  timeout_secs: 900
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "session.redis_url: expected a Redis URL when session.store is redis"));
+
+        let valid = config_content.replace("timeout_secs: 900", "timeout_secs: 900\n  redis_url: \"redis://localhost:6379\"");
+        let temp_file = create_temp_config(&valid);
+        let session = load_config(temp_file.path().to_str().unwrap()).unwrap().session.unwrap();
+        assert_eq!(session.timeout_secs, 900);
+        assert_eq!(session.key_prefix, "session:");
+        assert_eq!(session.identity, vec![SessionIdentity::Cookie, SessionIdentity::Fingerprint]);
+    }
+
+    #[test]
+    fn test_geoip_network_databases_require_city_database() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+  isp_database_path: "/data/GeoIP2-ISP.mmdb"
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "geoip.isp_database_path: expected to be unset when geoip.database_path is empty"));
+
+        let valid = config_content.replace("database_path: \"\"", "database_path: \"/data/GeoIP2-City.mmdb\"\n  reload_interval_secs: 300");
+        let temp_file = create_temp_config(&valid);
+        let geoip = load_config(temp_file.path().to_str().unwrap()).unwrap().geoip;
+        assert_eq!(geoip.isp_database_path.as_deref(), Some("/data/GeoIP2-ISP.mmdb"));
+        assert_eq!(geoip.connection_type_database_path, None);
+        assert_eq!(geoip.reload_interval_secs, 300);
+    }
+
+    #[test]
+    fn test_geoip_ip2location_backend() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: "/data/IP2LOCATION-LITE-DB11.IPV6.BIN"
+  backend: ip2location
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.geoip.backend, GeoIpBackendType::Ip2Location);
+
+        let invalid = config_content.replace("backend: ip2location", "backend: ip2location\n  isp_database_path: \"/data/GeoIP2-ISP.mmdb\"");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "geoip.isp_database_path: expected to be unset unless geoip.backend is maxmind"));
+    }
+
+    #[test]
+    fn test_enrichment_exclude_fields() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  exclude_fields: [city, latitude, longitude]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(
+            config.enrichment.exclude_fields,
+            vec![EnrichedField::City, EnrichedField::Latitude, EnrichedField::Longitude]
+        );
+    }
+
+    #[test]
+    fn test_enrichment_eu_countries() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  eu_countries: ["EU", "ch"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let codes = expand_country_codes(&config.enrichment.eu_countries);
+        assert_eq!(codes.len(), 28);
+        assert!(codes.contains(&"CH".to_string()));
+        assert!(!codes.contains(&"GB".to_string()));
+
+        let invalid = config_content.replace("\"ch\"", "\"Swiss\"");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.eu_countries: expected ISO 3166-1 alpha-2 country codes, got 'Swiss'"));
+    }
+
+    #[test]
+    fn test_default_eu_countries_cover_eea_and_uk() {
+        let codes = expand_country_codes(&EnrichmentConfig::default().eu_countries);
+        for code in ["DE", "FR", "NO", "IS", "LI", "GB"] {
+            assert!(codes.contains(&code.to_string()), "{} missing", code);
+        }
+        assert!(!codes.contains(&"CH".to_string()));
+    }
+
+    #[test]
+    fn test_ip_reputation_requires_config_and_valid_ranges() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [geoip, ip_reputation]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.ip_reputation: expected an ip_reputation section when the pipeline includes ip_reputation"));
+
+        let configured = format!("{}  ip_reputation:\n    vpn:\n      ranges: [\"198.51.100.0/24\"]\n", config_content);
+        let temp_file = create_temp_config(&configured);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.enrichment.ip_reputation.unwrap().vpn.ranges, vec!["198.51.100.0/24".to_string()]);
+
+        let invalid = configured.replace("198.51.100.0/24", "198.51.100.0/33");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("enrichment.ip_reputation.vpn.ranges: expected IP addresses or CIDR ranges")));
+    }
+
+    #[test]
+    fn test_enrichment_geohash_precision() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  geohash_precision: 8
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.enrichment.geohash_precision, 8);
+        assert_eq!(EnrichmentConfig::default().geohash_precision, 6);
+        assert!(!config.enrichment.provenance);
+
+        let with_provenance = config_content.replace("geohash_precision: 8", "provenance: true");
+        let temp_file = create_temp_config(&with_provenance);
+        assert!(load_config(temp_file.path().to_str().unwrap()).unwrap().enrichment.provenance);
+
+        let invalid = config_content.replace("geohash_precision: 8", "geohash_precision: 13");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.geohash_precision: expected a precision between 1 and 12, got 13"));
+    }
+
+    #[test]
+    fn test_ip_override_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+  ip_override:
+    allowed_sources: ["192.0.2.0/24"]
+    api_keys: ["sdk-secret"]
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.server.ip_override.allowed_sources, vec!["192.0.2.0/24".to_string()]);
+        assert_eq!(config.server.ip_override.api_keys, vec!["sdk-secret".to_string()]);
+
+        let invalid = config_content.replace("192.0.2.0/24", "192.0.2.0/40");
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("server.ip_override.allowed_sources: expected IP addresses or CIDR ranges")));
+
+        let empty_key = config_content.replace("\"sdk-secret\"", "\"\"");
+        let temp_file = create_temp_config(&empty_key);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "server.ip_override.api_keys: expected non-empty keys, got an empty key"));
+    }
+
+    #[test]
+    fn test_parameter_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+parameters:
+  infer_types: true
+  parse_json: true
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.parameters.infer_types);
+        assert!(config.parameters.parse_json);
+        assert_eq!(config.parameters.max_json_bytes, 4096);
+        assert_eq!(config.parameters.max_json_depth, 5);
+        assert!(!config.parameters.payload.enabled);
+        assert_eq!(config.parameters.payload.max_bytes, 16384);
+        assert_eq!(config.parameters.payload.max_params, 200);
+        assert!(!config.parameters.sanitize_keys.enabled);
+        assert!(config.parameters.sanitize_keys.lowercase);
+        assert_eq!(config.parameters.sanitize_keys.replacement, "_");
+        assert_eq!(config.parameters.sanitize_keys.max_length, 128);
+
+        let sanitize_keys = format!("{}  sanitize_keys:\n    enabled: true\n    replacement: \"-\"\n", config_content);
+        let temp_file = create_temp_config(&sanitize_keys);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "parameters.sanitize_keys.replacement: expected only letters, digits and _, got \"-\""));
+
+        let payload = format!("{}  payload:\n    enabled: true\n    max_params: 0\n", config_content);
+        let temp_file = create_temp_config(&payload);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "parameters.payload.max_params: expected a non-zero limit, got 0"));
+
+        let invalid = format!("{}  max_json_depth: 0\n", config_content);
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "parameters.max_json_depth: expected a non-zero depth when parse_json is enabled, got 0"));
+    }
+
+    #[test]
+    fn test_parameter_prefixes() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+parameters:
+  prefixes:
+    - prefix: "m_"
+      target: marketing
+    - prefix: "e_"
+      target: properties
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let rules = config.parameters.prefix_rules();
+        let target = |prefix: &str| rules.iter().find(|r| r.prefix == prefix).map(|r| r.target.clone());
+        assert_eq!(target("u_"), Some(PrefixTarget::Profile));
+        assert_eq!(target("e_"), Some(PrefixTarget::Object("properties".to_string())));
+        assert_eq!(target("m_"), Some(PrefixTarget::Object("marketing".to_string())));
+        assert_eq!(rules.len(), 5);
+
+        let duplicate = format!("{}    - prefix: \"m_\"\n      target: ads\n", config_content);
+        let temp_file = create_temp_config(&duplicate);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "parameters.prefixes[2].prefix: expected a prefix not configured by an earlier rule, got 'm_'"));
+    }
+
+    #[test]
+    fn test_pii_requires_config_and_salt_for_hashing() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [email, pii]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.pii: expected a pii section when the pipeline includes pii"));
+
+        let unsalted = format!("{}  pii:\n    properties:\n      phone: mask\n    projects:\n      shop:\n        email: hash\n", config_content);
+        let temp_file = create_temp_config(&unsalted);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.pii.salt: expected a salt when properties are hashed"));
+
+        let salted = format!("{}    salt: \"pepper\"\n", unsalted);
+        let temp_file = create_temp_config(&salted);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let pii = config.enrichment.pii.unwrap();
+        assert_eq!(pii.properties.get("phone"), Some(&PiiAction::Mask));
+        assert_eq!(pii.projects["shop"].get("email"), Some(&PiiAction::Hash));
+    }
+
+    #[test]
+    fn test_computed_fields_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+enrichment:
+  pipeline: [user_agent, computed]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.computed: expected at least one field when the pipeline includes computed"));
+
+        let valid = format!("{}  computed:\n    - name: is_mobile\n      expression: 'device == \"Mobile\"'\n", config_content);
+        let temp_file = create_temp_config(&valid);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.enrichment.computed[0].name, "is_mobile");
+        assert_eq!(config.enrichment.computed[0].expression, "device == \"Mobile\"");
+
+        let invalid = format!("{}    - name: landing\n      expression: \"referer is\"\n", valid);
+        let temp_file = create_temp_config(&invalid);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "enrichment.computed[1].expression: expected a valid expression, got \"referer is\" (expected 'empty' after 'is')"));
+    }
+
+    #[test]
+    fn test_event_name_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+events:
+  lowercase: true
+  aliases:
+    "page view": pageview
+  projects:
+    shop: ["pageview", "purchase"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.events.lowercase);
+        assert!(!config.events.trim);
+        assert_eq!(config.events.aliases.get("page view").map(String::as_str), Some("pageview"));
+        assert_eq!(config.events.projects["shop"], vec!["pageview", "purchase"]);
+        assert_eq!(config.events.unknown, UnknownEventAction::Reject);
+        assert_eq!(config.events.bucket_name, "other");
+
+        let empty_bucket = format!("{}  unknown: bucket\n  bucket_name: \"\"\n", config_content);
+        let temp_file = create_temp_config(&empty_bucket);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "events.bucket_name: expected an event name when events.unknown is bucket"));
+    }
+
+    #[test]
+    fn test_field_policy_config() {
+        let config_content = r#"
+server:
+  host: "0.0.0.0"
+  port: 8080
+
+streaming:
+  service_type: kafka
+  kafka:
+    brokers:
+      - "localhost:9092"
+    topic: "analytics"
+
+geoip:
+  database_path: ""
+
+logging:
+  level: "info"
+
+fields:
+  blocked: ["u_ssn"]
+  projects:
+    shop:
+      allowed: ["url", "e_*"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.fields.allowed.is_empty());
+        assert_eq!(config.fields.blocked, vec!["u_ssn"]);
+        assert_eq!(config.fields.projects["shop"].allowed, vec!["url", "e_*"]);
+        assert!(config.fields.projects["shop"].blocked.is_empty());
+
+        let empty_pattern = format!("{}      blocked: [\"\"]\n", config_content);
+        let temp_file = create_temp_config(&empty_pattern);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "fields.projects.shop: expected non-empty patterns, got an empty pattern"));
+    }
+
+    #[test]
+    fn test_listeners_config() {
+        let config_content = r#"
+server:
+  listeners:
+    - type: http
+      address: "0.0.0.0:8080"
+    - type: https
+      address: "[::]:8443"
+      cert_path: "/etc/tls/cert.pem"
+      key_path: "/etc/tls/key.pem"
+    - type: unix
+      path: "/run/analytics.sock"
+      permissions: "660"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.server.listeners.len(), 3);
+        assert_eq!(config.server.listeners[1].describe(), "https://[::]:8443");
+        assert_eq!(
+            config.server.listeners[2],
+            ListenerConfig::Unix {
+                path: "/run/analytics.sock".to_string(),
+                permissions: Some("660".to_string()),
+            }
+        );
+        assert_eq!(parse_socket_permissions("0660"), Some(0o660));
+        assert_eq!(parse_socket_permissions("1777"), None);
+
+        let bad_address = "server:\n  listeners:\n    - type: http\n      address: \"localhost\"\n";
+        let temp_file = create_temp_config(bad_address);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(
+            matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("server.listeners[0].address: expected an IP address and port"))
+        );
+
+        let bad_permissions = "server:\n  listeners:\n    - type: unix\n      path: \"/run/a.sock\"\n      permissions: \"rw\"\n";
+        let temp_file = create_temp_config(bad_permissions);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("server.listeners[0].permissions: expected an octal mode")));
+    }
+
+    #[test]
+    fn test_project_registry_config() {
+        let config_content = r#"
+streaming:
+  service_type: kafka
+  kafka:
+    brokers: ["localhost:9092"]
+    topic: "analytics"
+
+projects:
+  shop:
+    api_keys: ["shop-key"]
+    allowed_origins: ["https://*.shop.example.com"]
+    topic: "shop-{event}"
+    sample_rate: 0.25
+    schema:
+      required: ["cookie"]
+      events: ["pageview", "purchase"]
+      fields:
+        blocked: ["e_internal_*"]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let shop = &config.projects["shop"];
+        assert_eq!(shop.api_keys, vec!["shop-key"]);
+        assert_eq!(shop.sample_rate, Some(0.25));
+        assert_eq!(shop.schema.required, vec!["cookie"]);
+        // Topics and schemas are applied to the settings enforcing them
+        assert_eq!(config.streaming.project_topics["shop"], "shop-{event}");
+        assert_eq!(config.events.projects["shop"], vec!["pageview", "purchase"]);
+        assert_eq!(config.fields.projects["shop"].blocked, vec!["e_internal_*"]);
+
+        let bad_rate = config_content.replace("sample_rate: 0.25", "sample_rate: 1.5");
+        let temp_file = create_temp_config(&bad_rate);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "projects.shop.sample_rate: expected a number between 0 and 1, got 1.5"));
+
+        let bad_origin = config_content.replace("https://*.shop.example.com", "shop.example.com");
+        let temp_file = create_temp_config(&bad_origin);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues[0].to_string().starts_with("projects.shop.allowed_origins: expected origins")));
+
+        let duplicate_events = format!("{}\nevents:\n  projects:\n    shop: [\"pageview\"]\n", config_content);
+        let temp_file = create_temp_config(&duplicate_events);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "projects.shop.schema.events: expected to be unset when events.projects.shop is set"
+        ));
+    }
+
+    #[test]
+    fn test_otlp_config() {
+        let config_content = r#"
+logging:
+  level: "info"
+  otlp:
+    endpoint: "http://otel-collector:4318"
+    interval_secs: 30s
+    headers:
+      x-api-key: "key"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let otlp = config.logging.otlp.unwrap();
+        assert_eq!(otlp.interval_secs, 30);
+        assert_eq!(otlp.timeout_ms, 10_000);
+        assert_eq!(otlp.service_name, "rust-analytics-api");
+        assert_eq!(otlp.headers["x-api-key"], "key");
+
+        let bad_endpoint = config_content.replace("http://otel-collector:4318", "otel-collector:4317");
+        let temp_file = create_temp_config(&bad_endpoint);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "logging.otlp.endpoint: expected an http:// or https:// URL, got \"otel-collector:4317\""));
+    }
+
+    #[test]
+    fn test_statsd_config() {
+        let config_content = r#"
+logging:
+  level: "info"
+  statsd:
+    address: "datadog-agent:8125"
+    prefix: "penrose"
+    tags:
+      env: "production"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        let statsd = config.logging.statsd.unwrap();
+        assert_eq!(statsd.flavor, crate::config::StatsdFlavor::Dogstatsd);
+        assert_eq!(statsd.interval_secs, 10);
+        assert_eq!(statsd.max_packet_bytes, 1432);
+        assert_eq!(statsd.tags["env"], "production");
+
+        let bad_address = config_content.replace("datadog-agent:8125", "datadog-agent");
+        let temp_file = create_temp_config(&bad_address);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].to_string() == "logging.statsd.address: expected a host:port address, got \"datadog-agent\""));
+
+        let bad_tag = config_content.replace("env: \"production\"", "\"env|x\": \"production\"");
+        let temp_file = create_temp_config(&bad_tag);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].field == "logging.statsd.tags"));
+    }
+
+    #[test]
+    fn test_project_metrics_config() {
+        let temp_file = create_temp_config("logging:\n  level: \"info\"\n");
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.logging.project_metrics.allowed_projects.is_empty());
+        assert_eq!(config.logging.project_metrics.max_projects, 100);
+
+        let config_content = r#"
+logging:
+  level: "info"
+  project_metrics:
+    allowed_projects: ["shop", ""]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].field == "logging.project_metrics.allowed_projects"));
+    }
+
+    #[test]
+    fn test_topic_metrics_config() {
+        let temp_file = create_temp_config("logging:\n  level: \"info\"\n");
+        let config = load_config(temp_file.path().to_str().unwrap()).unwrap();
+        assert!(config.logging.topic_metrics.allowed_topics.is_empty());
+        assert_eq!(config.logging.topic_metrics.max_topics, 100);
+
+        let config_content = r#"
+logging:
+  level: "info"
+  topic_metrics:
+    allowed_topics: ["analytics", ""]
+"#;
+        let temp_file = create_temp_config(config_content);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 1 && issues[0].field == "logging.topic_metrics.allowed_topics"));
+    }
+
+    #[test]
+    fn test_audit_config() {
+        let config_content = r#"
+logging:
+  level: "info"
+  audit:
+    path: "/var/log/analytics/audit.jsonl"
+"#;
+        let temp_file = create_temp_config(config_content);
+        let audit = load_config(temp_file.path().to_str().unwrap()).unwrap().logging.audit.unwrap();
+        assert_eq!(audit.sample_rate, 0.001);
+        assert_eq!(audit.redact_params, vec!["u_*", "ip", "payload"]);
+        assert!(audit.redact_headers.contains(&"authorization".to_string()));
+
+        // A topic needs the Kafka brokers, and only one destination is allowed
+        let topic_only = config_content.replace("path: \"/var/log/analytics/audit.jsonl\"", "topic: \"analytics-audit\"\n    sample_rate: 2");
+        let temp_file = create_temp_config(&topic_only);
+        let result = load_config(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(ConfigError::Invalid(issues)) if issues.len() == 2
+            && issues[0].field == "logging.audit.sample_rate"
+            && issues[1].field == "logging.audit.topic"));
+    }
 }
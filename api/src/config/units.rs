@@ -0,0 +1,192 @@
+// Duration and size values
+// This module parses settings such as "5s", "500ms" or "10MiB" into the unit of the setting they fill
+
+use serde::{Deserialize, Deserializer};
+
+/// A setting written as a bare number (in the setting's own unit) or as text with a unit
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Number(i64),
+    Text(String),
+}
+
+/// Duration suffixes and their length in milliseconds
+const DURATION_UNITS: &[(&str, u64)] = &[
+    ("ms", 1),
+    ("s", 1_000),
+    ("m", 60_000),
+    ("h", 3_600_000),
+    ("d", 86_400_000),
+];
+
+/// Size suffixes (case-insensitive) and their length in bytes
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("kb", 1_000),
+    ("kib", 1_024),
+    ("mb", 1_000_000),
+    ("mib", 1_048_576),
+    ("gb", 1_000_000_000),
+    ("gib", 1_073_741_824),
+];
+
+/// Parse `text` as a duration in units of `unit_ms` milliseconds
+///
+/// A bare number is already in the setting's unit. Otherwise the number takes one of
+/// the suffixes ms, s, m, h or d and must come to a whole number of units:
+/// `parse_duration("2m", 1000)` is 120, `parse_duration("1500ms", 1000)` is an error.
+pub fn parse_duration(text: &str, unit_ms: u64) -> Result<u64, String> {
+    let unit_name = if unit_ms == 1 { "milliseconds" } else { "seconds" };
+    parse_quantity(text, DURATION_UNITS, false, unit_ms, unit_name)
+        .map_err(|e| format!("invalid duration {:?}: {}", text, e))
+}
+
+/// Parse `text` as a size in bytes, e.g. "16KiB", "10MB" or "512"
+///
+/// Suffixes are B, KB, MB and GB (powers of 1000) and KiB, MiB and GiB (powers of 1024),
+/// in any case.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    parse_quantity(text, SIZE_UNITS, true, 1, "bytes").map_err(|e| format!("invalid size {:?}: {}", text, e))
+}
+
+fn parse_quantity(
+    text: &str,
+    units: &[(&str, u64)],
+    ignore_case: bool,
+    unit: u64,
+    unit_name: &str,
+) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(split);
+    let (numerator, denominator) = parse_decimal(number).ok_or_else(|| "expected a number".to_string())?;
+    let suffix = suffix.trim();
+    let scale = if suffix.is_empty() {
+        unit
+    } else {
+        units
+            .iter()
+            .find(|(name, _)| {
+                if ignore_case {
+                    name.eq_ignore_ascii_case(suffix)
+                } else {
+                    *name == suffix
+                }
+            })
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| format!("unknown unit {:?}", suffix))?
+    };
+    // Exact arithmetic: "8.11s" is 811 / 100 seconds, 8110 milliseconds
+    let numerator = numerator.checked_mul(scale as u128).ok_or_else(|| "too large".to_string())?;
+    let denominator = denominator * unit as u128;
+    if numerator % denominator != 0 {
+        return Err(format!("not a whole number of {}", unit_name));
+    }
+    u64::try_from(numerator / denominator).map_err(|_| "too large".to_string())
+}
+
+/// `number` ("12", "1.5", ".5") as a fraction of its digits over a power of ten
+fn parse_decimal(number: &str) -> Option<(u128, u128)> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let fraction = fraction.trim_end_matches('0');
+    if number.is_empty() || number == "." || fraction.contains('.') {
+        return None;
+    }
+    let digits = format!("{}{}", whole, fraction);
+    let numerator = if digits.is_empty() { 0 } else { digits.parse().ok()? };
+    let denominator = 10u128.checked_pow(u32::try_from(fraction.len()).ok()?)?;
+    Some((numerator, denominator))
+}
+
+fn unsigned<E: serde::de::Error>(number: i64) -> Result<u64, E> {
+    u64::try_from(number).map_err(|_| E::custom(format!("invalid value {}: must not be negative", number)))
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D, unit_ms: u64) -> Result<u64, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(number) => unsigned(number),
+        Value::Text(text) => parse_duration(&text, unit_ms).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Deserialize a duration in seconds: `30` or `"30s"`, `"5m"`
+pub fn secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    duration(deserializer, 1_000)
+}
+
+/// Deserialize a duration in milliseconds: `500` or `"500ms"`, `"2s"`
+pub fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    duration(deserializer, 1)
+}
+
+/// Deserialize an optional duration in milliseconds
+pub fn opt_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    millis(deserializer).map(Some)
+}
+
+/// Deserialize an optional duration in milliseconds where a negative number (-1) means unlimited
+pub fn opt_signed_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    let millis = match Value::deserialize(deserializer)? {
+        Value::Number(number) => number,
+        Value::Text(text) => match text.trim().parse::<i64>() {
+            Ok(number) => number,
+            Err(_) => {
+                let millis = parse_duration(&text, 1).map_err(serde::de::Error::custom)?;
+                i64::try_from(millis).map_err(|_| serde::de::Error::custom(format!("invalid duration {:?}: too large", text)))?
+            }
+        },
+    };
+    Ok(Some(millis))
+}
+
+/// Deserialize a size in bytes: `16384` or `"16KiB"`
+pub fn bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let bytes = match Value::deserialize(deserializer)? {
+        Value::Number(number) => unsigned(number)?,
+        Value::Text(text) => parse_size(&text).map_err(serde::de::Error::custom)?,
+    };
+    usize::try_from(bytes).map_err(|_| serde::de::Error::custom(format!("invalid size {}: too large", bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30", 1_000), Ok(30));
+        assert_eq!(parse_duration("5s", 1_000), Ok(5));
+        assert_eq!(parse_duration("2m", 1_000), Ok(120));
+        assert_eq!(parse_duration("1h", 1_000), Ok(3_600));
+        assert_eq!(parse_duration("500ms", 1), Ok(500));
+        assert_eq!(parse_duration("1.5s", 1), Ok(1_500));
+        assert_eq!(parse_duration("8.11s", 1), Ok(8_110));
+        assert_eq!(parse_duration("1.1s", 1), Ok(1_100));
+        assert_eq!(parse_duration("0.5m", 1_000), Ok(30));
+        assert_eq!(parse_duration("2.50s", 1), Ok(2_500));
+        assert!(parse_duration("1.0005s", 1).is_err());
+        assert!(parse_duration("1.2.3s", 1).is_err());
+        assert_eq!(parse_duration(" 7d ", 1), Ok(604_800_000));
+        assert_eq!(
+            parse_duration("1500ms", 1_000),
+            Err("invalid duration \"1500ms\": not a whole number of seconds".to_string())
+        );
+        assert_eq!(parse_duration("5 parsecs", 1), Err("invalid duration \"5 parsecs\": unknown unit \"parsecs\"".to_string()));
+        assert_eq!(parse_duration("s", 1), Err("invalid duration \"s\": expected a number".to_string()));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("16KiB"), Ok(16_384));
+        assert_eq!(parse_size("10 MB"), Ok(10_000_000));
+        assert_eq!(parse_size("10mib"), Ok(10_485_760));
+        assert_eq!(parse_size("1GiB"), Ok(1_073_741_824));
+        assert!(parse_size("0.5B").is_err());
+        assert!(parse_size("10 MiBs").is_err());
+    }
+}
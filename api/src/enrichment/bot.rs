@@ -0,0 +1,113 @@
+// Crawler identification
+// This module names known bots and crawlers and sorts them into categories
+
+/// A bot recognized from its User-Agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownBot {
+    /// Display name (e.g. "Googlebot")
+    pub name: &'static str,
+    /// Category: "search_engine", "advertising", "social", "seo", "feed_reader",
+    /// "monitoring", "ai" or "other"
+    pub category: &'static str,
+}
+
+/// Category of crawlers that are not in `KNOWN_BOTS`
+pub const OTHER_BOT_CATEGORY: &str = "other";
+
+/// (lowercase User-Agent token, name, category), checked in order
+///
+/// More specific tokens come first ("adsbot-google" before "google").
+const KNOWN_BOTS: &[(&str, &str, &str)] = &[
+    ("adsbot-google", "AdsBot-Google", "advertising"),
+    ("mediapartners-google", "Google Mediapartners", "advertising"),
+    ("googlebot", "Googlebot", "search_engine"),
+    ("bingbot", "Bingbot", "search_engine"),
+    ("msnbot", "Msnbot", "search_engine"),
+    ("bingpreview", "BingPreview", "search_engine"),
+    ("yahoo! slurp", "Yahoo! Slurp", "search_engine"),
+    ("duckduckbot", "DuckDuckBot", "search_engine"),
+    ("baiduspider", "Baiduspider", "search_engine"),
+    ("yandexbot", "YandexBot", "search_engine"),
+    ("applebot", "Applebot", "search_engine"),
+    ("yeti", "Naver Yeti", "search_engine"),
+    ("petalbot", "PetalBot", "search_engine"),
+    ("facebookexternalhit", "Facebook", "social"),
+    ("twitterbot", "Twitterbot", "social"),
+    ("linkedinbot", "LinkedInBot", "social"),
+    ("slackbot", "Slackbot", "social"),
+    ("discordbot", "Discordbot", "social"),
+    ("telegrambot", "TelegramBot", "social"),
+    ("whatsapp", "WhatsApp", "social"),
+    ("pinterestbot", "Pinterestbot", "social"),
+    ("ahrefsbot", "AhrefsBot", "seo"),
+    ("semrushbot", "SemrushBot", "seo"),
+    ("mj12bot", "MJ12bot", "seo"),
+    ("dotbot", "DotBot", "seo"),
+    ("rogerbot", "Rogerbot", "seo"),
+    ("feedfetcher", "Feedfetcher", "feed_reader"),
+    ("feedburner", "FeedBurner", "feed_reader"),
+    ("uptimerobot", "UptimeRobot", "monitoring"),
+    ("pingdom", "Pingdom", "monitoring"),
+    ("statuscake", "StatusCake", "monitoring"),
+    ("site24x7", "Site24x7", "monitoring"),
+    ("datadog", "Datadog", "monitoring"),
+    ("newrelicpinger", "New Relic", "monitoring"),
+    ("elb-healthchecker", "ELB-HealthChecker", "monitoring"),
+    ("kube-probe", "kube-probe", "monitoring"),
+    ("gptbot", "GPTBot", "ai"),
+    ("chatgpt-user", "ChatGPT-User", "ai"),
+    ("claudebot", "ClaudeBot", "ai"),
+    ("ccbot", "CCBot", "ai"),
+    ("perplexitybot", "PerplexityBot", "ai"),
+];
+
+/// Identify a known bot from its User-Agent (case-insensitive)
+///
+/// # Returns
+/// The bot, or `None` if the User-Agent contains no known bot token. Crawlers the
+/// parser flags but this list does not know keep their parser name and fall into
+/// `OTHER_BOT_CATEGORY`.
+pub fn identify_bot(user_agent: &str) -> Option<KnownBot> {
+    let user_agent = user_agent.to_lowercase();
+    KNOWN_BOTS
+        .iter()
+        .find(|(token, _, _)| user_agent.contains(token))
+        .map(|(_, name, category)| KnownBot { name, category })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_search_engines() {
+        let bot = identify_bot("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)").unwrap();
+        assert_eq!(bot.name, "Googlebot");
+        assert_eq!(bot.category, "search_engine");
+
+        let bot = identify_bot("Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)").unwrap();
+        assert_eq!(bot.name, "Bingbot");
+    }
+
+    #[test]
+    fn test_specific_tokens_win() {
+        let bot = identify_bot("AdsBot-Google (+http://www.google.com/adsbot.html)").unwrap();
+        assert_eq!(bot.category, "advertising");
+    }
+
+    #[test]
+    fn test_identify_monitoring() {
+        let bot = identify_bot("Mozilla/5.0+(compatible; UptimeRobot/2.0; http://www.uptimerobot.com/)").unwrap();
+        assert_eq!(bot, KnownBot { name: "UptimeRobot", category: "monitoring" });
+        assert_eq!(identify_bot("kube-probe/1.29").map(|b| b.category), Some("monitoring"));
+    }
+
+    #[test]
+    fn test_browsers_are_not_bots() {
+        assert_eq!(
+            identify_bot("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"),
+            None
+        );
+        assert_eq!(identify_bot(""), None);
+    }
+}
@@ -0,0 +1,477 @@
+// Computed fields
+// This module evaluates config-defined expressions over the event and stores their results in attributes
+
+use std::fmt;
+
+use async_trait::async_trait;
+use serde_json::{Map, Number, Value};
+
+use super::pipeline::{EnrichmentContext, Enricher};
+use crate::config::ComputedFieldConfig;
+use crate::transformer::AnalyticsEvent;
+
+/// Error produced when an expression cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionError(pub String);
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Comparison operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// Parsed computed field expression
+///
+/// Supported syntax:
+/// * literals: `"text"`, `'text'`, `42`, `1.5`, `true`, `false`, `null`
+/// * field paths: `device`, `visit.referer`, `event_param.price`, `attributes.tier`;
+///   a bare name missing from the event root is looked up in `visit`
+/// * comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`, `contains`
+/// * `x is empty` / `x is not empty` (null, `""`, `[]` and `{}` are empty)
+/// * `and`, `or`, `not` and parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Path(Vec<String>),
+    Compare(Box<Expression>, Comparison, Box<Expression>),
+    IsEmpty(Box<Expression>, bool),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(Number),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Dot,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExpressionError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c == '.' {
+            tokens.push(Token::Dot);
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ExpressionError("unterminated string literal".to_string())),
+                    Some('\\') if i + 1 < chars.len() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&ch) if ch == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(text));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text
+                .parse::<i64>()
+                .ok()
+                .map(Number::from)
+                .or_else(|| text.parse::<f64>().ok().and_then(Number::from_f64))
+                .ok_or_else(|| ExpressionError(format!("invalid number '{}'", text)))?;
+            tokens.push(Token::Num(number));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let op = match two.as_str() {
+                "==" => "==",
+                "!=" => "!=",
+                "<=" => "<=",
+                ">=" => ">=",
+                _ => match c {
+                    '<' => "<",
+                    '>' => ">",
+                    _ => return Err(ExpressionError(format!("unexpected character '{}'", c))),
+                },
+            };
+            i += op.len();
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(ident)) if ident == word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("or") {
+            left = Expression::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.parse_not()?;
+        while self.keyword("and") {
+            left = Expression::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, ExpressionError> {
+        if self.keyword("not") {
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let left = self.parse_primary()?;
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("empty") {
+                return Err(ExpressionError("expected 'empty' after 'is'".to_string()));
+            }
+            return Ok(Expression::IsEmpty(Box::new(left), !negated));
+        }
+        let op = match self.peek() {
+            Some(Token::Op("==")) => Comparison::Eq,
+            Some(Token::Op("!=")) => Comparison::Ne,
+            Some(Token::Op("<")) => Comparison::Lt,
+            Some(Token::Op("<=")) => Comparison::Le,
+            Some(Token::Op(">")) => Comparison::Gt,
+            Some(Token::Op(">=")) => Comparison::Ge,
+            Some(Token::Ident(ident)) if ident == "contains" => Comparison::Contains,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_primary()?;
+        Ok(Expression::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.advance() {
+            Some(Token::Str(text)) => Ok(Expression::Literal(Value::String(text))),
+            Some(Token::Num(number)) => Ok(Expression::Literal(Value::Number(number))),
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(ExpressionError("expected ')'".to_string())),
+                }
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                "null" => Ok(Expression::Literal(Value::Null)),
+                "and" | "or" | "not" | "is" | "empty" | "contains" => {
+                    Err(ExpressionError(format!("unexpected keyword '{}'", ident)))
+                }
+                _ => {
+                    let mut path = vec![ident];
+                    while self.peek() == Some(&Token::Dot) {
+                        self.pos += 1;
+                        match self.advance() {
+                            Some(Token::Ident(part)) => path.push(part),
+                            _ => return Err(ExpressionError("expected a field name after '.'".to_string())),
+                        }
+                    }
+                    Ok(Expression::Path(path))
+                }
+            },
+            Some(token) => Err(ExpressionError(format!("unexpected token {:?}", token))),
+            None => Err(ExpressionError("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+/// Whether a value counts as true in `and`, `or` and `not`
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        other => !is_empty(other),
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        _ => false,
+    }
+}
+
+fn compare(left: &Value, op: Comparison, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().zip(b.as_f64()).and_then(|(a, b)| a.partial_cmp(&b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    match op {
+        Comparison::Eq => ordering.map_or(left == right, |o| o.is_eq()),
+        Comparison::Ne => ordering.map_or(left != right, |o| o.is_ne()),
+        Comparison::Lt => ordering.is_some_and(|o| o.is_lt()),
+        Comparison::Le => ordering.is_some_and(|o| o.is_le()),
+        Comparison::Gt => ordering.is_some_and(|o| o.is_gt()),
+        Comparison::Ge => ordering.is_some_and(|o| o.is_ge()),
+        Comparison::Contains => match (left, right) {
+            (Value::String(haystack), Value::String(needle)) => haystack.contains(needle.as_str()),
+            (Value::Array(items), item) => items.contains(item),
+            _ => false,
+        },
+    }
+}
+
+impl Expression {
+    /// Parse an expression
+    pub fn parse(input: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(ExpressionError(format!("unexpected token {:?}", token))),
+        }
+    }
+
+    /// Evaluate against an event serialized as a JSON object
+    pub fn evaluate(&self, event: &Map<String, Value>) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Path(path) => lookup(event, path).cloned().unwrap_or(Value::Null),
+            Expression::Compare(left, op, right) => {
+                Value::Bool(compare(&left.evaluate(event), *op, &right.evaluate(event)))
+            }
+            Expression::IsEmpty(inner, empty) => Value::Bool(is_empty(&inner.evaluate(event)) == *empty),
+            Expression::Not(inner) => Value::Bool(!truthy(&inner.evaluate(event))),
+            Expression::And(left, right) => {
+                Value::Bool(truthy(&left.evaluate(event)) && truthy(&right.evaluate(event)))
+            }
+            Expression::Or(left, right) => {
+                Value::Bool(truthy(&left.evaluate(event)) || truthy(&right.evaluate(event)))
+            }
+        }
+    }
+}
+
+/// Resolve a field path; a single name missing from the root falls back to `visit`
+fn lookup<'a>(event: &'a Map<String, Value>, path: &[String]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    let root = match event.get(first) {
+        Some(value) => value,
+        None if rest.is_empty() => return event.get("visit").and_then(|visit| visit.get(first)),
+        None => return None,
+    };
+    rest.iter().try_fold(root, |value, part| value.get(part))
+}
+
+/// Adds config-defined computed fields (`enrichment.computed`) to `attributes`
+///
+/// Fields are evaluated in order against the event as it is after the earlier stages,
+/// so a field can use the result of a previous one (`attributes.<name>`). A field whose
+/// expression evaluates to null is not set.
+pub struct ComputedEnricher {
+    fields: Vec<(String, Expression)>,
+}
+
+impl ComputedEnricher {
+    /// Parse every configured expression
+    ///
+    /// # Errors
+    /// Returns the name of the first field whose expression does not parse, with the error
+    pub fn from_config(fields: &[ComputedFieldConfig]) -> Result<Self, (String, ExpressionError)> {
+        let fields = fields
+            .iter()
+            .map(|field| {
+                Expression::parse(&field.expression)
+                    .map(|expression| (field.name.clone(), expression))
+                    .map_err(|e| (field.name.clone(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ComputedEnricher { fields })
+    }
+}
+
+#[async_trait]
+impl Enricher for ComputedEnricher {
+    fn name(&self) -> &str {
+        "computed"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+        let mut snapshot = match serde_json::to_value(&*event) {
+            Ok(Value::Object(fields)) => fields,
+            _ => return,
+        };
+        for (name, expression) in &self.fields {
+            let value = expression.evaluate(&snapshot);
+            if value.is_null() {
+                continue;
+            }
+            if let Value::Object(attributes) = snapshot
+                .entry("attributes")
+                .or_insert_with(|| Value::Object(Map::new()))
+            {
+                attributes.insert(name.clone(), value.clone());
+            }
+            event.attributes.insert(name.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformer::VisitObject;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn eval(expression: &str, event: &Value) -> Value {
+        Expression::parse(expression)
+            .unwrap()
+            .evaluate(event.as_object().unwrap())
+    }
+
+    #[test]
+    fn test_evaluate_expressions() {
+        let event = json!({
+            "device": "Mobile",
+            "visit": {"referer": "", "url": "https://example.com/checkout"},
+            "event_param": {"price": 25, "tags": ["sale"]}
+        });
+        assert_eq!(eval(r#"device == "Mobile""#, &event), json!(true));
+        assert_eq!(eval("referer is empty", &event), json!(true));
+        assert_eq!(eval("visit.url is not empty", &event), json!(true));
+        assert_eq!(eval("event_param.price >= 20 and not (device != 'Mobile')", &event), json!(true));
+        assert_eq!(eval(r#"url contains "checkout" or false"#, &event), json!(true));
+        assert_eq!(eval(r#"event_param.tags contains "sale""#, &event), json!(true));
+        assert_eq!(eval("event_param.price < 10.5", &event), json!(false));
+        assert_eq!(eval("event_param.price", &event), json!(25));
+        assert_eq!(eval("missing.field", &event), Value::Null);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expression::parse("device ==").is_err());
+        assert!(Expression::parse("device is full").is_err());
+        assert!(Expression::parse("(device").is_err());
+        assert!(Expression::parse("\"open").is_err());
+        assert!(Expression::parse("device = 1").is_err());
+        assert!(Expression::parse("a b").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enricher_adds_attributes() {
+        let enricher = ComputedEnricher::from_config(&[
+            ComputedFieldConfig {
+                name: "is_mobile".to_string(),
+                expression: r#"device == "Mobile""#.to_string(),
+            },
+            ComputedFieldConfig {
+                name: "landing".to_string(),
+                expression: "referer is empty".to_string(),
+            },
+            ComputedFieldConfig {
+                name: "mobile_landing".to_string(),
+                expression: "attributes.is_mobile and attributes.landing".to_string(),
+            },
+            ComputedFieldConfig {
+                name: "unset".to_string(),
+                expression: "country".to_string(),
+            },
+        ])
+        .unwrap();
+
+        let mut event = AnalyticsEvent {
+            device: Some("Mobile".to_string()),
+            visit: VisitObject::default(),
+            ..Default::default()
+        };
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "127.0.0.1".parse().unwrap(), "", &params);
+        enricher.enrich(&mut event, &mut ctx).await;
+
+        assert_eq!(event.attributes.get("is_mobile"), Some(&json!(true)));
+        assert_eq!(event.attributes.get("landing"), Some(&json!(true)));
+        assert_eq!(event.attributes.get("mobile_landing"), Some(&json!(true)));
+        assert!(!event.attributes.contains_key("unset"));
+
+        let invalid = ComputedEnricher::from_config(&[ComputedFieldConfig {
+            name: "broken".to_string(),
+            expression: "device ==".to_string(),
+        }]);
+        assert_eq!(invalid.err().map(|(name, _)| name), Some("broken".to_string()));
+    }
+}
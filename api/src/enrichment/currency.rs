@@ -0,0 +1,112 @@
+// Country to currency mapping
+// This module maps ISO 3166-1 country codes to the ISO 4217 currency in use there and lists valid currency codes
+
+/// (country code, currency code) pairs, sorted by country code
+///
+/// Countries with several legal tenders map to the one most used for prices.
+const COUNTRY_CURRENCIES: &[(&str, &str)] = &[
+    ("AD", "EUR"), ("AE", "AED"), ("AF", "AFN"), ("AG", "XCD"), ("AI", "XCD"), ("AL", "ALL"),
+    ("AM", "AMD"), ("AO", "AOA"), ("AR", "ARS"), ("AS", "USD"), ("AT", "EUR"), ("AU", "AUD"),
+    ("AW", "AWG"), ("AX", "EUR"), ("AZ", "AZN"), ("BA", "BAM"), ("BB", "BBD"), ("BD", "BDT"),
+    ("BE", "EUR"), ("BF", "XOF"), ("BG", "BGN"), ("BH", "BHD"), ("BI", "BIF"), ("BJ", "XOF"),
+    ("BL", "EUR"), ("BM", "BMD"), ("BN", "BND"), ("BO", "BOB"), ("BQ", "USD"), ("BR", "BRL"),
+    ("BS", "BSD"), ("BT", "BTN"), ("BV", "NOK"), ("BW", "BWP"), ("BY", "BYN"), ("BZ", "BZD"),
+    ("CA", "CAD"), ("CC", "AUD"), ("CD", "CDF"), ("CF", "XAF"), ("CG", "XAF"), ("CH", "CHF"),
+    ("CI", "XOF"), ("CK", "NZD"), ("CL", "CLP"), ("CM", "XAF"), ("CN", "CNY"), ("CO", "COP"),
+    ("CR", "CRC"), ("CU", "CUP"), ("CV", "CVE"), ("CW", "ANG"), ("CX", "AUD"), ("CY", "EUR"),
+    ("CZ", "CZK"), ("DE", "EUR"), ("DJ", "DJF"), ("DK", "DKK"), ("DM", "XCD"), ("DO", "DOP"),
+    ("DZ", "DZD"), ("EC", "USD"), ("EE", "EUR"), ("EG", "EGP"), ("EH", "MAD"), ("ER", "ERN"),
+    ("ES", "EUR"), ("ET", "ETB"), ("FI", "EUR"), ("FJ", "FJD"), ("FK", "FKP"), ("FM", "USD"),
+    ("FO", "DKK"), ("FR", "EUR"), ("GA", "XAF"), ("GB", "GBP"), ("GD", "XCD"), ("GE", "GEL"),
+    ("GF", "EUR"), ("GG", "GBP"), ("GH", "GHS"), ("GI", "GIP"), ("GL", "DKK"), ("GM", "GMD"),
+    ("GN", "GNF"), ("GP", "EUR"), ("GQ", "XAF"), ("GR", "EUR"), ("GS", "GBP"), ("GT", "GTQ"),
+    ("GU", "USD"), ("GW", "XOF"), ("GY", "GYD"), ("HK", "HKD"), ("HM", "AUD"), ("HN", "HNL"),
+    ("HR", "EUR"), ("HT", "HTG"), ("HU", "HUF"), ("ID", "IDR"), ("IE", "EUR"), ("IL", "ILS"),
+    ("IM", "GBP"), ("IN", "INR"), ("IO", "USD"), ("IQ", "IQD"), ("IR", "IRR"), ("IS", "ISK"),
+    ("IT", "EUR"), ("JE", "GBP"), ("JM", "JMD"), ("JO", "JOD"), ("JP", "JPY"), ("KE", "KES"),
+    ("KG", "KGS"), ("KH", "KHR"), ("KI", "AUD"), ("KM", "KMF"), ("KN", "XCD"), ("KP", "KPW"),
+    ("KR", "KRW"), ("KW", "KWD"), ("KY", "KYD"), ("KZ", "KZT"), ("LA", "LAK"), ("LB", "LBP"),
+    ("LC", "XCD"), ("LI", "CHF"), ("LK", "LKR"), ("LR", "LRD"), ("LS", "LSL"), ("LT", "EUR"),
+    ("LU", "EUR"), ("LV", "EUR"), ("LY", "LYD"), ("MA", "MAD"), ("MC", "EUR"), ("MD", "MDL"),
+    ("ME", "EUR"), ("MF", "EUR"), ("MG", "MGA"), ("MH", "USD"), ("MK", "MKD"), ("ML", "XOF"),
+    ("MM", "MMK"), ("MN", "MNT"), ("MO", "MOP"), ("MP", "USD"), ("MQ", "EUR"), ("MR", "MRU"),
+    ("MS", "XCD"), ("MT", "EUR"), ("MU", "MUR"), ("MV", "MVR"), ("MW", "MWK"), ("MX", "MXN"),
+    ("MY", "MYR"), ("MZ", "MZN"), ("NA", "NAD"), ("NC", "XPF"), ("NE", "XOF"), ("NF", "AUD"),
+    ("NG", "NGN"), ("NI", "NIO"), ("NL", "EUR"), ("NO", "NOK"), ("NP", "NPR"), ("NR", "AUD"),
+    ("NU", "NZD"), ("NZ", "NZD"), ("OM", "OMR"), ("PA", "PAB"), ("PE", "PEN"), ("PF", "XPF"),
+    ("PG", "PGK"), ("PH", "PHP"), ("PK", "PKR"), ("PL", "PLN"), ("PM", "EUR"), ("PN", "NZD"),
+    ("PR", "USD"), ("PS", "ILS"), ("PT", "EUR"), ("PW", "USD"), ("PY", "PYG"), ("QA", "QAR"),
+    ("RE", "EUR"), ("RO", "RON"), ("RS", "RSD"), ("RU", "RUB"), ("RW", "RWF"), ("SA", "SAR"),
+    ("SB", "SBD"), ("SC", "SCR"), ("SD", "SDG"), ("SE", "SEK"), ("SG", "SGD"), ("SH", "SHP"),
+    ("SI", "EUR"), ("SJ", "NOK"), ("SK", "EUR"), ("SL", "SLE"), ("SM", "EUR"), ("SN", "XOF"),
+    ("SO", "SOS"), ("SR", "SRD"), ("SS", "SSP"), ("ST", "STN"), ("SV", "USD"), ("SX", "ANG"),
+    ("SY", "SYP"), ("SZ", "SZL"), ("TC", "USD"), ("TD", "XAF"), ("TF", "EUR"), ("TG", "XOF"),
+    ("TH", "THB"), ("TJ", "TJS"), ("TK", "NZD"), ("TL", "USD"), ("TM", "TMT"), ("TN", "TND"),
+    ("TO", "TOP"), ("TR", "TRY"), ("TT", "TTD"), ("TV", "AUD"), ("TW", "TWD"), ("TZ", "TZS"),
+    ("UA", "UAH"), ("UG", "UGX"), ("UM", "USD"), ("US", "USD"), ("UY", "UYU"), ("UZ", "UZS"),
+    ("VA", "EUR"), ("VC", "XCD"), ("VE", "VES"), ("VG", "USD"), ("VI", "USD"), ("VN", "VND"),
+    ("VU", "VUV"), ("WF", "XPF"), ("WS", "WST"), ("XK", "EUR"), ("YE", "YER"), ("YT", "EUR"),
+    ("ZA", "ZAR"), ("ZM", "ZMW"), ("ZW", "ZWL"),
+];
+
+/// Active ISO 4217 currency codes, sorted (the test and no-currency codes XTS and XXX excluded)
+const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN",
+    "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF",
+    "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP", "CVE", "CZK", "DJF",
+    "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD",
+    "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD",
+    "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK",
+    "MXN", "MXV", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK",
+    "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK",
+    "SGD", "SHP", "SLE", "SLL", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS",
+    "VED", "VES", "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG",
+    "XDR", "XOF", "XPD", "XPF", "XPT", "XSU", "XUA", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+/// ISO 4217 currency code used in a country (case-insensitive ISO 3166-1 alpha-2 code)
+pub fn currency_for_country(country_code: &str) -> Option<&'static str> {
+    let code = country_code.to_ascii_uppercase();
+    COUNTRY_CURRENCIES
+        .binary_search_by(|(country, _)| (*country).cmp(code.as_str()))
+        .ok()
+        .map(|index| COUNTRY_CURRENCIES[index].1)
+}
+
+/// Whether `code` is an active ISO 4217 currency code (upper case, e.g. "EUR")
+pub fn is_currency_code(code: &str) -> bool {
+    CURRENCY_CODES.binary_search(&code).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(COUNTRY_CURRENCIES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(CURRENCY_CODES.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_currency_for_country() {
+        assert_eq!(currency_for_country("DE"), Some("EUR"));
+        assert_eq!(currency_for_country("us"), Some("USD"));
+        assert_eq!(currency_for_country("GB"), Some("GBP"));
+        assert_eq!(currency_for_country("JP"), Some("JPY"));
+        assert_eq!(currency_for_country("ZZ"), None);
+    }
+
+    #[test]
+    fn test_is_currency_code() {
+        assert!(is_currency_code("EUR"));
+        assert!(is_currency_code("JPY"));
+        assert!(!is_currency_code("eur"));
+        assert!(!is_currency_code("XXX"));
+        assert!(!is_currency_code("ABC"));
+        // Every mapped country currency is a valid code
+        assert!(COUNTRY_CURRENCIES.iter().all(|(_, currency)| is_currency_code(currency)));
+    }
+}
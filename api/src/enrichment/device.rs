@@ -0,0 +1,152 @@
+// Device brand and model extraction
+// This module derives the manufacturer and model of mobile devices from User-Agent headers
+
+/// Model prefixes of Android devices and their brand, checked in order
+///
+/// Prefixes are matched case-sensitively against the model token of the User-Agent.
+const ANDROID_MODEL_BRANDS: &[(&str, &str)] = &[
+    ("SM-", "Samsung"),
+    ("GT-", "Samsung"),
+    ("SAMSUNG", "Samsung"),
+    ("Galaxy", "Samsung"),
+    ("Pixel", "Google"),
+    ("Nexus", "Google"),
+    ("Redmi", "Xiaomi"),
+    ("POCO", "Xiaomi"),
+    ("Mi ", "Xiaomi"),
+    ("MI ", "Xiaomi"),
+    ("Xiaomi", "Xiaomi"),
+    ("HUAWEI", "Huawei"),
+    ("Huawei", "Huawei"),
+    ("HONOR", "Honor"),
+    ("ONEPLUS", "OnePlus"),
+    ("OnePlus", "OnePlus"),
+    ("CPH", "OPPO"),
+    ("OPPO", "OPPO"),
+    ("RMX", "realme"),
+    ("vivo", "vivo"),
+    ("moto", "Motorola"),
+    ("Moto", "Motorola"),
+    ("motorola", "Motorola"),
+    ("XT", "Motorola"),
+    ("Nokia", "Nokia"),
+    ("LM-", "LG"),
+    ("LG-", "LG"),
+    ("Lenovo", "Lenovo"),
+    ("ASUS", "ASUS"),
+    ("Infinix", "Infinix"),
+    ("TECNO", "Tecno"),
+    ("KF", "Amazon"),
+    ("AFT", "Amazon"),
+];
+
+/// Manufacturer and model of the device behind a User-Agent
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeviceModel {
+    /// Manufacturer (e.g. "Samsung", "Apple")
+    pub brand: Option<String>,
+    /// Model (e.g. "SM-S918B", "iPhone")
+    pub model: Option<String>,
+}
+
+/// Extract the device brand and model from a User-Agent
+///
+/// Apple devices are recognized by their platform token (iPhone, iPad, iPod). For
+/// Android the model is the token following the Android version, and the brand is
+/// looked up from its prefix. Desktop User-Agents yield no device.
+pub fn extract_device(user_agent: &str) -> DeviceModel {
+    for apple in ["iPhone", "iPad", "iPod"] {
+        if user_agent.contains(&format!("({}", apple)) {
+            return DeviceModel {
+                brand: Some("Apple".to_string()),
+                model: Some(apple.to_string()),
+            };
+        }
+    }
+
+    match android_model(user_agent) {
+        Some(model) => DeviceModel {
+            brand: ANDROID_MODEL_BRANDS
+                .iter()
+                .find(|(prefix, _)| model.starts_with(prefix))
+                .map(|(_, brand)| brand.to_string()),
+            model: Some(model.to_string()),
+        },
+        None => DeviceModel::default(),
+    }
+}
+
+/// Model token of an Android User-Agent: "Linux; Android 13; SM-S918B Build/TP1A)" → "SM-S918B"
+fn android_model(user_agent: &str) -> Option<&str> {
+    let start = user_agent.find("Android")?;
+    let platform = &user_agent[start..];
+    let platform = &platform[..platform.find(')')?];
+    let model = platform
+        .split(';')
+        .skip(1)
+        .map(str::trim)
+        .find(|part| !part.is_empty() && !is_locale(part) && *part != "wv" && *part != "U")?;
+    let model = model.split(" Build/").next().unwrap_or(model).trim();
+    // Chrome's reduced User-Agent replaces the model with "K"
+    (model.len() > 1).then_some(model)
+}
+
+/// Whether a User-Agent token is a locale such as "en-us" or "de_DE"
+fn is_locale(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 5
+        && bytes[..2].iter().all(u8::is_ascii_alphabetic)
+        && (bytes[2] == b'-' || bytes[2] == b'_')
+        && bytes[3..].iter().all(u8::is_ascii_alphabetic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samsung() {
+        let device = extract_device(
+            "Mozilla/5.0 (Linux; Android 13; SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+        );
+        assert_eq!(device.brand.as_deref(), Some("Samsung"));
+        assert_eq!(device.model.as_deref(), Some("SM-S918B"));
+    }
+
+    #[test]
+    fn test_model_with_build_and_locale() {
+        let device = extract_device(
+            "Mozilla/5.0 (Linux; U; Android 4.4.2; en-us; Pixel 7 Build/TQ3A.230805.001) AppleWebKit/534.30 (KHTML, like Gecko) Version/4.0 Mobile Safari/534.30",
+        );
+        assert_eq!(device.brand.as_deref(), Some("Google"));
+        assert_eq!(device.model.as_deref(), Some("Pixel 7"));
+    }
+
+    #[test]
+    fn test_iphone_and_ipad() {
+        let device = extract_device(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+        );
+        assert_eq!(device.brand.as_deref(), Some("Apple"));
+        assert_eq!(device.model.as_deref(), Some("iPhone"));
+
+        let device = extract_device("Mozilla/5.0 (iPad; CPU OS 17_0 like Mac OS X) AppleWebKit/605.1.15");
+        assert_eq!(device.model.as_deref(), Some("iPad"));
+    }
+
+    #[test]
+    fn test_unknown_brand_keeps_model() {
+        let device = extract_device("Mozilla/5.0 (Linux; Android 12; ACME-X1) AppleWebKit/537.36");
+        assert_eq!(device.brand, None);
+        assert_eq!(device.model.as_deref(), Some("ACME-X1"));
+    }
+
+    #[test]
+    fn test_reduced_and_desktop_user_agents() {
+        let device = extract_device("Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36");
+        assert_eq!(device, DeviceModel::default());
+
+        let device = extract_device("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        assert_eq!(device, DeviceModel::default());
+    }
+}
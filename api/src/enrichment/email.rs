@@ -0,0 +1,86 @@
+// Email address checks
+// This module validates and normalizes profile email addresses and classifies their domain
+
+/// Domains of widely used free email providers
+pub const FREE_EMAIL_PROVIDERS: &[&str] = &[
+    "126.com", "163.com", "aol.com", "fastmail.com", "free.fr", "gmail.com", "gmx.at", "gmx.ch",
+    "gmx.com", "gmx.de", "gmx.net", "googlemail.com", "hanmail.net", "hey.com", "hotmail.co.uk",
+    "hotmail.com", "hotmail.de", "hotmail.fr", "icloud.com", "laposte.net", "libero.it", "live.com",
+    "mail.com", "mail.ru", "me.com", "mac.com", "msn.com", "naver.com", "orange.fr", "outlook.com",
+    "proton.me", "protonmail.com", "qq.com", "rediffmail.com", "t-online.de", "tutanota.com",
+    "web.de", "yahoo.co.jp", "yahoo.co.uk", "yahoo.com", "yahoo.de", "yahoo.fr", "yandex.com",
+    "yandex.ru", "zoho.com",
+];
+
+/// Trim and lowercase an email address
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Whether `email` is a plausible address: one "@", a non-empty local part of at most
+/// 64 characters without whitespace, and a domain of dot-separated labels made of
+/// letters, digits and inner hyphens
+///
+/// This is a syntax check only; it does not check that the mailbox exists.
+pub fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    if local.is_empty() || local.len() > 64 || local.chars().any(|c| c.is_whitespace() || c == '@') {
+        return false;
+    }
+    if domain.len() > 253 || !domain.contains('.') {
+        return false;
+    }
+    domain.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    })
+}
+
+/// Domain part of an email address (everything after the last "@")
+pub fn email_domain(email: &str) -> Option<&str> {
+    email.rsplit_once('@').map(|(_, domain)| domain).filter(|d| !d.is_empty())
+}
+
+/// Whether `domain` belongs to a free email provider (built-in list or `extra`)
+pub fn is_free_provider(domain: &str, extra: &[String]) -> bool {
+    FREE_EMAIL_PROVIDERS.iter().any(|p| p.eq_ignore_ascii_case(domain))
+        || extra.iter().any(|p| p.eq_ignore_ascii_case(domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(normalize_email("  Jane.Doe@Example.COM "), "jane.doe@example.com");
+    }
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("jane@example.com"));
+        assert!(is_valid_email("jane+news@mail.example.co.uk"));
+        assert!(!is_valid_email("jane"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("jane@localhost"));
+        assert!(!is_valid_email("jane@exa mple.com"));
+        assert!(!is_valid_email("jane@-example.com"));
+        assert!(!is_valid_email("jane@@example.com"));
+        assert!(!is_valid_email("jane@example..com"));
+    }
+
+    #[test]
+    fn test_email_domain_and_free_provider() {
+        assert_eq!(email_domain("jane@gmail.com"), Some("gmail.com"));
+        assert_eq!(email_domain("jane"), None);
+        assert!(is_free_provider("gmail.com", &[]));
+        assert!(is_free_provider("GMX.de", &[]));
+        assert!(!is_free_provider("example.com", &[]));
+        assert!(is_free_provider("example.com", &["example.com".to_string()]));
+    }
+}
@@ -0,0 +1,164 @@
+// Device fingerprint hashing
+// This module derives a best-effort visitor key for deployments that do not use cookies
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use super::geoip_cache::truncate_ip;
+use crate::config::{FingerprintComponent, FingerprintConfig};
+
+/// Request attributes a fingerprint can be built from
+#[derive(Debug, Clone, Copy)]
+pub struct FingerprintInput<'a> {
+    pub user_agent: &'a str,
+    pub screen: Option<&'a str>,
+    pub language: Option<&'a str>,
+    pub client_ip: IpAddr,
+}
+
+/// Produces salted SHA-256 hashes of the configured request attributes
+///
+/// The client IP is truncated to a network prefix before hashing so that visitors
+/// keep their fingerprint when their address changes within the same network.
+#[derive(Debug, Clone)]
+pub struct Fingerprinter {
+    config: FingerprintConfig,
+}
+
+impl Fingerprinter {
+    pub fn new(config: FingerprintConfig) -> Self {
+        Fingerprinter { config }
+    }
+
+    /// Hex-encoded fingerprint of `input` at the current time
+    pub fn fingerprint(&self, input: &FingerprintInput<'_>) -> Option<String> {
+        self.fingerprint_at(input, Utc::now())
+    }
+
+    /// Hex-encoded fingerprint of `input` as computed at `now`
+    ///
+    /// Returns `None` when none of the configured components has a value.
+    pub fn fingerprint_at(&self, input: &FingerprintInput<'_>, now: DateTime<Utc>) -> Option<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.config.salt.as_bytes());
+        if self.config.rotate_daily {
+            hasher.update(now.format("|%Y-%m-%d|").to_string().as_bytes());
+        }
+
+        let mut has_value = false;
+        for component in &self.config.components {
+            let value = match component {
+                FingerprintComponent::UserAgent => Some(input.user_agent.to_string()).filter(|ua| !ua.is_empty()),
+                FingerprintComponent::Screen => input.screen.map(str::to_string),
+                FingerprintComponent::Language => input.language.map(|lang| lang.to_ascii_lowercase()),
+                FingerprintComponent::Ip => {
+                    let ip = match input.client_ip {
+                        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(input.client_ip),
+                        v4 => v4,
+                    };
+                    Some(truncate_ip(ip, self.config.ipv4_prefix, self.config.ipv6_prefix).to_string())
+                }
+            };
+            has_value |= value.is_some();
+            // Separate components so that shifting text between them changes the hash
+            hasher.update(component.as_str().as_bytes());
+            hasher.update(b"=");
+            hasher.update(value.unwrap_or_default().as_bytes());
+            hasher.update(b"\n");
+        }
+        if !has_value {
+            return None;
+        }
+
+        Some(
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn input<'a>(ip: &str) -> FingerprintInput<'a> {
+        FingerprintInput {
+            user_agent: "Mozilla/5.0 (X11; Linux x86_64) Firefox/121.0",
+            screen: Some("1920x1080"),
+            language: Some("en-US"),
+            client_ip: ip.parse().unwrap(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_same_network_same_fingerprint() {
+        let fingerprinter = Fingerprinter::new(FingerprintConfig {
+            salt: "secret".to_string(),
+            ..Default::default()
+        });
+        let hash = fingerprinter.fingerprint_at(&input("203.0.113.7"), now()).unwrap();
+
+        assert_eq!(hash.len(), 64);
+        assert_eq!(Some(hash.clone()), fingerprinter.fingerprint_at(&input("203.0.113.200"), now()));
+        assert_ne!(Some(hash), fingerprinter.fingerprint_at(&input("198.51.100.7"), now()));
+    }
+
+    #[test]
+    fn test_recipe_selects_components() {
+        let fingerprinter = Fingerprinter::new(FingerprintConfig {
+            salt: "secret".to_string(),
+            components: vec![FingerprintComponent::UserAgent, FingerprintComponent::Language],
+            ..Default::default()
+        });
+
+        let mut other_screen = input("198.51.100.7");
+        other_screen.screen = Some("390x844");
+        assert_eq!(
+            fingerprinter.fingerprint_at(&input("203.0.113.7"), now()),
+            fingerprinter.fingerprint_at(&other_screen, now())
+        );
+    }
+
+    #[test]
+    fn test_salt_and_rotation() {
+        let config = FingerprintConfig {
+            salt: "secret".to_string(),
+            rotate_daily: true,
+            ..Default::default()
+        };
+        let fingerprinter = Fingerprinter::new(config.clone());
+        let other_salt = Fingerprinter::new(FingerprintConfig {
+            salt: "other".to_string(),
+            ..config
+        });
+        let hash = fingerprinter.fingerprint_at(&input("203.0.113.7"), now());
+
+        assert_ne!(hash, other_salt.fingerprint_at(&input("203.0.113.7"), now()));
+        assert_ne!(
+            hash,
+            fingerprinter.fingerprint_at(&input("203.0.113.7"), now() + chrono::Duration::days(1))
+        );
+    }
+
+    #[test]
+    fn test_no_values_no_fingerprint() {
+        let fingerprinter = Fingerprinter::new(FingerprintConfig {
+            salt: "secret".to_string(),
+            components: vec![FingerprintComponent::Screen],
+            ..Default::default()
+        });
+        let mut without_screen = input("203.0.113.7");
+        without_screen.screen = None;
+        assert_eq!(fingerprinter.fingerprint_at(&without_screen, now()), None);
+    }
+}
@@ -0,0 +1,76 @@
+// Geohash encoding
+// This module encodes coordinates as geohashes so events can be bucketed by area
+
+/// Geohash base32 alphabet
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Longest supported geohash (about 3.7 cm × 1.9 cm cells)
+pub const MAX_PRECISION: usize = 12;
+
+/// Encode coordinates as a geohash of `precision` characters
+///
+/// Precision is clamped to `1..=MAX_PRECISION`. Coordinates outside the valid
+/// latitude/longitude ranges yield `None`.
+pub fn encode(latitude: f64, longitude: f64, precision: usize) -> Option<String> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+
+    let precision = precision.clamp(1, MAX_PRECISION);
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut even_bit = true;
+    let (mut bits, mut value) = (0, 0usize);
+
+    while hash.len() < precision {
+        // Bits alternate between longitude and latitude, starting with longitude
+        let (range, coordinate) = if even_bit {
+            (&mut lon_range, longitude)
+        } else {
+            (&mut lat_range, latitude)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        value <<= 1;
+        if coordinate >= mid {
+            value |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even_bit = !even_bit;
+
+        bits += 1;
+        if bits == 5 {
+            hash.push(BASE32[value] as char);
+            bits = 0;
+            value = 0;
+        }
+    }
+
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_geohashes() {
+        assert_eq!(encode(57.64911, 10.40744, 11).as_deref(), Some("u4pruydqqvj"));
+        assert_eq!(encode(37.7749, -122.4194, 5).as_deref(), Some("9q8yy"));
+        assert_eq!(encode(52.52, 13.40, 6).as_deref(), Some("u33dbb"));
+    }
+
+    #[test]
+    fn test_precision_is_clamped() {
+        assert_eq!(encode(57.64911, 10.40744, 0).as_deref(), Some("u"));
+        assert_eq!(encode(57.64911, 10.40744, 20).map(|h| h.len()), Some(MAX_PRECISION));
+    }
+
+    #[test]
+    fn test_invalid_coordinates() {
+        assert_eq!(encode(91.0, 0.0, 6), None);
+        assert_eq!(encode(0.0, -180.5, 6), None);
+        assert_eq!(encode(f64::NAN, 0.0, 6), None);
+    }
+}
@@ -1,11 +1,16 @@
 // GeoIP lookup implementation
-// This module performs IP address geolocation using MaxMind database
+// This module performs IP address geolocation using MaxMind or IP2Location databases
 
-use maxminddb::Reader;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 use std::path::Path;
 
+use super::geoip_cache::GeoIpCache;
+use super::ip2location::Ip2LocationBackend;
+use super::mmdb::ReloadableReader;
+use crate::config::{GeoIpBackendType, GeoIpConfig};
+
 /// Error types for GeoIP operations
 #[derive(Debug)]
 pub enum GeoIpError {
@@ -36,17 +41,121 @@ impl From<maxminddb::MaxMindDBError> for GeoIpError {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct GeoLocation {
     pub country: Option<String>,
+    /// ISO 3166-1 alpha-2 country code (e.g. "DE")
+    pub country_code: Option<String>,
+    /// Two-letter continent code (e.g. "EU", "NA")
+    pub continent_code: Option<String>,
     pub region: Option<String>,
     pub city: Option<String>,
+    /// Postal or ZIP code
+    pub postal_code: Option<String>,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    /// Radius in kilometers around the coordinates the address is likely in
+    pub accuracy_radius: Option<u16>,
+    /// IANA time zone of the location (e.g. "Europe/Berlin")
+    pub time_zone: Option<String>,
+    /// Network details from the ISP, Connection-Type and Anonymous-IP databases
+    pub network: Option<NetworkInfo>,
+}
+
+/// Network information about an IP address
+///
+/// Fields are filled from whichever of the ISP, Connection-Type and Anonymous-IP
+/// databases are configured; fields of databases that are not loaded stay `None`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct NetworkInfo {
+    /// Internet service provider (ISP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isp: Option<String>,
+    /// Organization the address is assigned to (ISP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// Autonomous system number (ISP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    /// Autonomous system organization (ISP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_organization: Option<String>,
+    /// Connection type, e.g. "Cable/DSL", "Cellular" (Connection-Type database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_type: Option<String>,
+    /// Any anonymizer (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_anonymous: Option<bool>,
+    /// VPN provider (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_anonymous_vpn: Option<bool>,
+    /// Hosting or VPS provider (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_hosting_provider: Option<bool>,
+    /// Public proxy (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_public_proxy: Option<bool>,
+    /// Residential proxy (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_residential_proxy: Option<bool>,
+    /// Tor exit node (Anonymous-IP database)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_tor_exit_node: Option<bool>,
+    /// Private, loopback, link-local or CGNAT client address (`geoip.tag_internal_ips`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_internal: Option<bool>,
+}
+
+impl NetworkInfo {
+    /// Whether no field is set
+    pub fn is_empty(&self) -> bool {
+        *self == NetworkInfo::default()
+    }
 }
 
 
 
-/// GeoIP lookup service using MaxMind database
+/// ISO 3166-1 alpha-2 codes of the European Union member states
+pub const EU_COUNTRY_CODES: [&str; 27] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU",
+    "IE", "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+/// Whether an ISO country code belongs to an EU member state (case-insensitive)
+pub fn is_eu_country(country_code: &str) -> bool {
+    EU_COUNTRY_CODES
+        .iter()
+        .any(|code| code.eq_ignore_ascii_case(country_code))
+}
+
+/// GeoIP database backend
+///
+/// Implemented for MaxMind (`MaxMindBackend`) and IP2Location
+/// (`Ip2LocationBackend`) databases; selected by `geoip.backend`.
+pub trait GeoIpBackend: Send + Sync {
+    /// Look up `ip`, returning all fields as None if it is not found
+    fn lookup(&self, ip: IpAddr) -> GeoLocation;
+
+    /// Reload database files that changed on disk, returning how many were reloaded
+    fn reload_changed(&self) -> usize;
+
+    /// Loaded database files
+    fn databases(&self) -> Vec<GeoIpDatabase> {
+        Vec::new()
+    }
+}
+
+/// A loaded GeoIP database file
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GeoIpDatabase {
+    pub path: String,
+    /// Database type, e.g. "GeoLite2-City"
+    pub database_type: String,
+    /// When the database was built, if the file records it
+    pub build_date: Option<DateTime<Utc>>,
+}
+
+/// GeoIP lookup service with an optional result cache
 pub struct GeoIpLookup {
-    reader: Reader<Vec<u8>>,
+    backend: Box<dyn GeoIpBackend>,
+    cache: Option<GeoIpCache>,
 }
 
 impl GeoIpLookup {
@@ -58,8 +167,67 @@ impl GeoIpLookup {
     /// # Errors
     /// Returns an error if the database file cannot be read or is invalid
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, maxminddb::MaxMindDBError> {
-        let reader = Reader::open_readfile(db_path)?;
-        Ok(Self { reader })
+        Ok(Self::from_backend(Box::new(MaxMindBackend::new(db_path)?)))
+    }
+
+    /// Load the configured GeoIP backend and, for MaxMind, any ISP, Connection-Type and
+    /// Anonymous-IP databases (without the result cache)
+    ///
+    /// # Errors
+    /// Returns an error if a configured database file cannot be read or is invalid
+    pub fn from_config(config: &GeoIpConfig) -> Result<Self, GeoIpError> {
+        if config.backend == GeoIpBackendType::Ip2Location {
+            let backend = Ip2LocationBackend::new(&config.database_path)?;
+            return Ok(Self::from_backend(Box::new(backend)));
+        }
+
+        let mut backend = MaxMindBackend::new(&config.database_path)?;
+        if let Some(path) = &config.isp_database_path {
+            tracing::info!(database_path = %path, "Loading GeoIP ISP database");
+            backend = backend.with_isp_database(path)?;
+        }
+        if let Some(path) = &config.connection_type_database_path {
+            tracing::info!(database_path = %path, "Loading GeoIP Connection-Type database");
+            backend = backend.with_connection_type_database(path)?;
+        }
+        if let Some(path) = &config.anonymous_ip_database_path {
+            tracing::info!(database_path = %path, "Loading GeoIP Anonymous-IP database");
+            backend = backend.with_anonymous_ip_database(path)?;
+        }
+        Ok(Self::from_backend(Box::new(backend)))
+    }
+
+    /// Create a lookup service over any backend
+    pub fn from_backend(backend: Box<dyn GeoIpBackend>) -> Self {
+        Self { backend, cache: None }
+    }
+
+    /// Cache lookup results in `cache`
+    pub fn with_cache(mut self, cache: GeoIpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Reload database files that changed on disk
+    ///
+    /// A database that fails to reload keeps serving its previous version. The result
+    /// cache is cleared when any database changed.
+    ///
+    /// # Returns
+    /// Number of databases reloaded
+    pub fn reload_changed(&self) -> usize {
+        let reloaded = self.backend.reload_changed();
+        if reloaded > 0 {
+            if let Some(cache) = &self.cache {
+                cache.clear();
+            }
+        }
+        reloaded
+    }
+
+    /// Loaded database files, the main database first
+    pub fn databases(&self) -> Vec<GeoIpDatabase> {
+        self.backend.databases()
     }
 
     /// Look up geographic location for an IP address
@@ -71,6 +239,82 @@ impl GeoIpLookup {
     /// Returns a `GeoLocation` with available fields populated, or all fields set to None
     /// if the IP is not found in the database or an error occurs
     pub fn lookup(&self, ip: IpAddr) -> GeoLocation {
+        let Some(cache) = &self.cache else {
+            return self.backend.lookup(ip);
+        };
+        if let Some(location) = cache.get(ip) {
+            tracing::debug!(ip = %ip, "GeoIP cache hit");
+            return location;
+        }
+        let location = self.backend.lookup(ip);
+        cache.insert(ip, location.clone());
+        location
+    }
+}
+
+/// GeoIP backend using MaxMind databases
+///
+/// The City database is required; ISP, Connection-Type and Anonymous-IP databases
+/// can be added to fill the `network` section. Every database can be reloaded from
+/// disk while the service runs.
+pub struct MaxMindBackend {
+    city: ReloadableReader,
+    isp: Option<ReloadableReader>,
+    connection_type: Option<ReloadableReader>,
+    anonymous_ip: Option<ReloadableReader>,
+}
+
+impl MaxMindBackend {
+    /// Load the MaxMind City database into memory
+    ///
+    /// # Errors
+    /// Returns an error if the database file cannot be read or is invalid
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, maxminddb::MaxMindDBError> {
+        Ok(Self {
+            city: ReloadableReader::open(db_path)?,
+            isp: None,
+            connection_type: None,
+            anonymous_ip: None,
+        })
+    }
+
+    /// Add a GeoIP2-ISP (or GeoLite2-ASN) database
+    ///
+    /// # Errors
+    /// Returns an error if the database file cannot be read or is invalid
+    pub fn with_isp_database<P: AsRef<Path>>(mut self, db_path: P) -> Result<Self, maxminddb::MaxMindDBError> {
+        self.isp = Some(ReloadableReader::open(db_path)?);
+        Ok(self)
+    }
+
+    /// Add a GeoIP2-Connection-Type database
+    ///
+    /// # Errors
+    /// Returns an error if the database file cannot be read or is invalid
+    pub fn with_connection_type_database<P: AsRef<Path>>(mut self, db_path: P) -> Result<Self, maxminddb::MaxMindDBError> {
+        self.connection_type = Some(ReloadableReader::open(db_path)?);
+        Ok(self)
+    }
+
+    /// Add a GeoIP2-Anonymous-IP database
+    ///
+    /// # Errors
+    /// Returns an error if the database file cannot be read or is invalid
+    pub fn with_anonymous_ip_database<P: AsRef<Path>>(mut self, db_path: P) -> Result<Self, maxminddb::MaxMindDBError> {
+        self.anonymous_ip = Some(ReloadableReader::open(db_path)?);
+        Ok(self)
+    }
+
+    /// Loaded databases
+    fn readers(&self) -> impl Iterator<Item = &ReloadableReader> {
+        std::iter::once(&self.city)
+            .chain(self.isp.as_ref())
+            .chain(self.connection_type.as_ref())
+            .chain(self.anonymous_ip.as_ref())
+    }
+
+    /// Look up `ip` in the City database and the network databases
+    fn lookup_location(&self, ip: IpAddr) -> GeoLocation {
         tracing::debug!(
             ip = %ip,
             ip_version = if ip.is_ipv4() { "IPv4" } else { "IPv6" },
@@ -79,8 +323,21 @@ impl GeoIpLookup {
         
         // Attempt to look up the IP in the database
         // If lookup fails or data is missing, return default (all None values)
-        match self.reader.lookup::<maxminddb::geoip2::City>(ip) {
+        let reader = self.city.current();
+        let location = match reader.lookup::<maxminddb::geoip2::City>(ip) {
             Ok(city) => {
+                let country_code = city
+                    .country
+                    .as_ref()
+                    .and_then(|c| c.iso_code)
+                    .map(|code| code.to_string());
+
+                let continent_code = city
+                    .continent
+                    .as_ref()
+                    .and_then(|c| c.code)
+                    .map(|code| code.to_string());
+
                 let country = city
                     .country
                     .and_then(|c| c.names)
@@ -97,10 +354,19 @@ impl GeoIpLookup {
                     .and_then(|c| c.names)
                     .and_then(|n| n.get("en").map(|s| s.to_string()));
 
-                let (latitude, longitude) = city
+                let postal_code = city.postal.and_then(|p| p.code).map(|code| code.to_string());
+
+                let (latitude, longitude, accuracy_radius, time_zone) = city
                     .location
-                    .map(|loc| (loc.latitude, loc.longitude))
-                    .unwrap_or((None, None));
+                    .map(|loc| {
+                        (
+                            loc.latitude,
+                            loc.longitude,
+                            loc.accuracy_radius,
+                            loc.time_zone.map(|tz| tz.to_string()),
+                        )
+                    })
+                    .unwrap_or((None, None, None, None));
 
                 tracing::debug!(
                     ip = %ip,
@@ -113,10 +379,16 @@ impl GeoIpLookup {
 
                 GeoLocation {
                     country,
+                    country_code,
+                    continent_code,
                     region,
                     city: city_name,
+                    postal_code,
                     latitude,
                     longitude,
+                    accuracy_radius,
+                    time_zone,
+                    network: None,
                 }
             }
             Err(e) => {
@@ -127,7 +399,90 @@ impl GeoIpLookup {
                 );
                 GeoLocation::default()
             }
+        };
+
+        let network = self.lookup_network(ip);
+        GeoLocation {
+            network: (!network.is_empty()).then_some(network),
+            ..location
+        }
+    }
+
+    /// Look up `ip` in the ISP, Connection-Type and Anonymous-IP databases
+    fn lookup_network(&self, ip: IpAddr) -> NetworkInfo {
+        let mut network = NetworkInfo::default();
+
+        if let Some(isp_db) = &self.isp {
+            if let Ok(isp) = isp_db.current().lookup::<maxminddb::geoip2::Isp>(ip) {
+                network.isp = isp.isp.map(str::to_string);
+                network.organization = isp.organization.map(str::to_string);
+                network.asn = isp.autonomous_system_number;
+                network.as_organization = isp.autonomous_system_organization.map(str::to_string);
+            }
+        }
+
+        if let Some(connection_type_db) = &self.connection_type {
+            if let Ok(connection) = connection_type_db
+                .current()
+                .lookup::<maxminddb::geoip2::ConnectionType>(ip)
+            {
+                network.connection_type = connection.connection_type.map(str::to_string);
+            }
+        }
+
+        if let Some(anonymous_ip_db) = &self.anonymous_ip {
+            if let Ok(anonymous) = anonymous_ip_db.current().lookup::<maxminddb::geoip2::AnonymousIp>(ip) {
+                network.is_anonymous = anonymous.is_anonymous;
+                network.is_anonymous_vpn = anonymous.is_anonymous_vpn;
+                network.is_hosting_provider = anonymous.is_hosting_provider;
+                network.is_public_proxy = anonymous.is_public_proxy;
+                network.is_residential_proxy = anonymous.is_residential_proxy;
+                network.is_tor_exit_node = anonymous.is_tor_exit_node;
+            }
+        }
+
+        network
+    }
+}
+
+impl GeoIpBackend for MaxMindBackend {
+    fn lookup(&self, ip: IpAddr) -> GeoLocation {
+        self.lookup_location(ip)
+    }
+
+    fn databases(&self) -> Vec<GeoIpDatabase> {
+        self.readers()
+            .map(|database| GeoIpDatabase {
+                path: database.path().display().to_string(),
+                database_type: database.database_type(),
+                build_date: database.build_date(),
+            })
+            .collect()
+    }
+
+    fn reload_changed(&self) -> usize {
+        let mut reloaded = 0;
+        for database in self.readers() {
+            match database.reload_if_changed() {
+                Ok(true) => {
+                    tracing::info!(
+                        database_path = %database.path().display(),
+                        database_type = %database.database_type(),
+                        "GeoIP database reloaded"
+                    );
+                    reloaded += 1;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        database_path = %database.path().display(),
+                        error = %e,
+                        "Failed to reload GeoIP database, keeping previous version"
+                    );
+                }
+            }
         }
+        reloaded
     }
 }
 
@@ -154,10 +509,16 @@ mod tests {
     fn test_geolocation_with_values() {
         let geo = GeoLocation {
             country: Some("United States".to_string()),
+            country_code: Some("US".to_string()),
+            continent_code: Some("NA".to_string()),
             region: Some("California".to_string()),
             city: Some("San Francisco".to_string()),
+            postal_code: Some("94107".to_string()),
             latitude: Some(37.7749),
             longitude: Some(-122.4194),
+            accuracy_radius: Some(10),
+            time_zone: None,
+            network: None,
         };
 
         assert_eq!(geo.country, Some("United States".to_string()));
@@ -165,6 +526,28 @@ mod tests {
         assert_eq!(geo.city, Some("San Francisco".to_string()));
         assert_eq!(geo.latitude, Some(37.7749));
         assert_eq!(geo.longitude, Some(-122.4194));
+        assert_eq!(geo.continent_code, Some("NA".to_string()));
+        assert_eq!(geo.postal_code, Some("94107".to_string()));
+        assert_eq!(geo.accuracy_radius, Some(10));
+    }
+
+    #[test]
+    fn test_network_info_is_empty() {
+        assert!(NetworkInfo::default().is_empty());
+        let network = NetworkInfo {
+            connection_type: Some("Cellular".to_string()),
+            ..Default::default()
+        };
+        assert!(!network.is_empty());
+        assert_eq!(serde_json::to_value(&network).unwrap(), serde_json::json!({"connection_type": "Cellular"}));
+    }
+
+    #[test]
+    fn test_is_eu_country() {
+        assert!(is_eu_country("DE"));
+        assert!(is_eu_country("fr"));
+        assert!(!is_eu_country("GB"));
+        assert!(!is_eu_country("US"));
     }
 
     #[test]
@@ -208,10 +591,16 @@ mod tests {
         // Test GeoLocation with only some fields populated
         let geo = GeoLocation {
             country: Some("United States".to_string()),
+            country_code: None,
+            continent_code: None,
             region: None,
             city: None,
+            postal_code: None,
             latitude: Some(37.0),
             longitude: Some(-122.0),
+            accuracy_radius: None,
+            time_zone: None,
+            network: None,
         };
 
         assert!(geo.country.is_some());
@@ -0,0 +1,142 @@
+// GeoIP lookup result cache
+// This module caches recent GeoIP results, since repeat visitors hit the same IP within seconds
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use super::geoip::GeoLocation;
+use super::ttl_cache::TtlCache;
+
+/// Bounded, time-limited cache of GeoIP results keyed by (optionally truncated) IP
+pub struct GeoIpCache {
+    entries: TtlCache<IpAddr, GeoLocation>,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+}
+
+impl GeoIpCache {
+    /// Create a cache
+    ///
+    /// # Arguments
+    /// * `max_entries` - Maximum number of cached results (minimum 1)
+    /// * `ttl` - How long a result stays valid
+    /// * `ipv4_prefix` - IPv4 bits kept in the cache key (32 caches each address separately)
+    /// * `ipv6_prefix` - IPv6 bits kept in the cache key (128 caches each address separately)
+    pub fn new(max_entries: usize, ttl: Duration, ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        GeoIpCache {
+            entries: TtlCache::new(max_entries, ttl),
+            ipv4_prefix: ipv4_prefix.min(32),
+            ipv6_prefix: ipv6_prefix.min(128),
+        }
+    }
+
+    /// Cache key for `ip`: the address with host bits beyond the prefix cleared
+    pub fn key(&self, ip: IpAddr) -> IpAddr {
+        truncate_ip(ip, self.ipv4_prefix, self.ipv6_prefix)
+    }
+
+    /// Cached result for `ip`, if present and not expired
+    pub fn get(&self, ip: IpAddr) -> Option<GeoLocation> {
+        self.entries.get(&self.key(ip))
+    }
+
+    /// Store the result for `ip`, evicting expired and then oldest entries as needed
+    pub fn insert(&self, ip: IpAddr, location: GeoLocation) {
+        self.entries.insert(self.key(ip), location);
+    }
+
+    /// Remove every cached result (e.g. after a database reload)
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    /// Number of cached results (including expired ones not yet evicted)
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no results
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// `ip` with the host bits beyond the given prefix lengths cleared
+pub(crate) fn truncate_ip(ip: IpAddr, ipv4_prefix: u8, ipv6_prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask_u32(ipv4_prefix.min(32)))),
+        IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask_u128(ipv6_prefix.min(128)))),
+    }
+}
+
+fn mask_u32(prefix: u8) -> u32 {
+    if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - u32::from(prefix))
+    }
+}
+
+fn mask_u128(prefix: u8) -> u128 {
+    if prefix == 0 {
+        0
+    } else {
+        u128::MAX << (128 - u32::from(prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn location(city: &str) -> GeoLocation {
+        GeoLocation {
+            city: Some(city.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_after_insert() {
+        let cache = GeoIpCache::new(10, Duration::from_secs(60), 32, 128);
+        assert_eq!(cache.get(ip("203.0.113.7")), None);
+
+        cache.insert(ip("203.0.113.7"), location("Berlin"));
+        assert_eq!(cache.get(ip("203.0.113.7")), Some(location("Berlin")));
+        assert_eq!(cache.get(ip("203.0.113.8")), None);
+    }
+
+    #[test]
+    fn test_truncated_keys_share_entries() {
+        let cache = GeoIpCache::new(10, Duration::from_secs(60), 24, 48);
+        assert_eq!(cache.key(ip("203.0.113.7")), ip("203.0.113.0"));
+        assert_eq!(cache.key(ip("2001:db8:1:2::1")), ip("2001:db8:1::"));
+
+        cache.insert(ip("203.0.113.7"), location("Berlin"));
+        assert_eq!(cache.get(ip("203.0.113.200")), Some(location("Berlin")));
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = GeoIpCache::new(10, Duration::from_millis(10), 32, 128);
+        cache.insert(ip("203.0.113.7"), location("Berlin"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(ip("203.0.113.7")), None);
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let cache = GeoIpCache::new(2, Duration::from_secs(60), 32, 128);
+        cache.insert(ip("203.0.113.1"), location("A"));
+        cache.insert(ip("203.0.113.2"), location("B"));
+        cache.insert(ip("203.0.113.3"), location("C"));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(ip("203.0.113.1")), None);
+        assert_eq!(cache.get(ip("203.0.113.3")), Some(location("C")));
+    }
+}
@@ -0,0 +1,65 @@
+// Headless browser detection
+// This module flags automated browsers from User-Agent signatures and client-side hints
+
+use std::collections::HashMap;
+
+/// Lowercase User-Agent signatures of headless and automated browsers
+const HEADLESS_SIGNATURES: &[&str] = &[
+    "headless",
+    "puppeteer",
+    "playwright",
+    "selenium",
+    "webdriver",
+    "phantomjs",
+    "slimerjs",
+    "htmlunit",
+    "electron/",
+];
+
+/// Request parameter carrying the SDK's `navigator.webdriver` value
+pub const WEBDRIVER_PARAM: &str = "webdriver";
+
+/// Whether the User-Agent carries a headless or automation signature (case-insensitive)
+pub fn is_headless_user_agent(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_lowercase();
+    HEADLESS_SIGNATURES.iter().any(|signature| user_agent.contains(signature))
+}
+
+/// Whether the client reported `navigator.webdriver` ("1" or "true" in the `webdriver` parameter)
+pub fn webdriver_hint(params: &HashMap<String, String>) -> bool {
+    params
+        .get(WEBDRIVER_PARAM)
+        .is_some_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_signatures() {
+        assert!(is_headless_user_agent(
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/120.0.6099.109 Safari/537.36"
+        ));
+        assert!(is_headless_user_agent("Mozilla/5.0 (Unknown; Linux x86_64) AppleWebKit/538.1 (KHTML, like Gecko) PhantomJS/2.1.1 Safari/538.1"));
+        assert!(is_headless_user_agent("Mozilla/5.0 Playwright/1.40"));
+    }
+
+    #[test]
+    fn test_regular_browsers_are_not_headless() {
+        assert!(!is_headless_user_agent(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"
+        ));
+        assert!(!is_headless_user_agent(""));
+    }
+
+    #[test]
+    fn test_webdriver_hint() {
+        let mut params = HashMap::new();
+        assert!(!webdriver_hint(&params));
+        params.insert(WEBDRIVER_PARAM.to_string(), "0".to_string());
+        assert!(!webdriver_hint(&params));
+        params.insert(WEBDRIVER_PARAM.to_string(), "true".to_string());
+        assert!(webdriver_hint(&params));
+    }
+}
@@ -0,0 +1,253 @@
+// External HTTP enrichment stage
+// This module adds fields returned by an external HTTP service (e.g. customer tier) to events
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::pipeline::{EnrichmentContext, Enricher};
+use super::ttl_cache::TtlCache;
+use crate::config::HttpEnricherConfig;
+use crate::streaming::CircuitBreaker;
+use crate::transformer::AnalyticsEvent;
+
+/// Fields returned for a lookup key; `None` when the service does not know the key
+type LookupResult = Option<HashMap<String, Value>>;
+
+/// Enricher that looks up a request parameter in an external HTTP service
+///
+/// The value of `key_param` is substituted into the `{key}` placeholder of the URL and
+/// the JSON object in the response is merged into the event's `attributes`. Failed
+/// calls are retried, results (including "not found") are cached, and a circuit
+/// breaker stops calling a failing service. Enrichment never fails the request: when
+/// the service is unavailable the event is streamed without the extra fields.
+pub struct HttpEnricher {
+    client: reqwest::Client,
+    config: HttpEnricherConfig,
+    cache: Option<TtlCache<String, LookupResult>>,
+    breaker: CircuitBreaker,
+}
+
+/// Why a single lookup attempt failed
+#[derive(Debug)]
+enum LookupError {
+    Request(String),
+    Status(u16),
+    Body(String),
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LookupError::Request(msg) => write!(f, "request failed: {}", msg),
+            LookupError::Status(status) => write!(f, "unexpected status {}", status),
+            LookupError::Body(msg) => write!(f, "invalid response body: {}", msg),
+        }
+    }
+}
+
+impl HttpEnricher {
+    /// Create the enricher
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built
+    pub fn new(config: HttpEnricherConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        let cache = (config.cache_ttl_secs > 0)
+            .then(|| TtlCache::new(config.cache_max_entries, Duration::from_secs(config.cache_ttl_secs)));
+        let breaker = CircuitBreaker::new(config.failure_threshold, Duration::from_secs(config.open_secs));
+        Ok(HttpEnricher {
+            client,
+            config,
+            cache,
+            breaker,
+        })
+    }
+
+    /// Lookup URL for `key`
+    fn url_for(&self, key: &str) -> String {
+        let encoded: String = url::form_urlencoded::byte_serialize(key.as_bytes()).collect();
+        self.config.url.replace("{key}", &encoded)
+    }
+
+    /// Single lookup attempt
+    async fn fetch(&self, key: &str) -> Result<LookupResult, LookupError> {
+        let mut request = self.client.get(self.url_for(key));
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request.send().await.map_err(|e| LookupError::Request(e.to_string()))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(LookupError::Status(status.as_u16()));
+        }
+
+        match response.json::<Value>().await {
+            Ok(Value::Object(fields)) => Ok(Some(fields.into_iter().collect())),
+            Ok(_) => Err(LookupError::Body("expected a JSON object".to_string())),
+            Err(e) => Err(LookupError::Body(e.to_string())),
+        }
+    }
+
+    /// Look up `key`, using the cache, retries and the circuit breaker
+    async fn lookup(&self, key: &str) -> Option<LookupResult> {
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&key.to_string())) {
+            return Some(cached);
+        }
+        if !self.breaker.allow_request() {
+            tracing::debug!(url = %self.config.url, "HTTP enrichment skipped, circuit breaker open");
+            return None;
+        }
+
+        for attempt in 0..=self.config.retries {
+            match self.fetch(key).await {
+                Ok(result) => {
+                    self.breaker.record_success();
+                    if let Some(cache) = &self.cache {
+                        cache.insert(key.to_string(), result.clone());
+                    }
+                    return Some(result);
+                }
+                Err(e) => {
+                    tracing::debug!(
+                        url = %self.config.url,
+                        attempt = attempt + 1,
+                        error = %e,
+                        "HTTP enrichment attempt failed"
+                    );
+                }
+            }
+        }
+
+        self.breaker.record_failure();
+        tracing::warn!(
+            url = %self.config.url,
+            attempts = self.config.retries + 1,
+            "HTTP enrichment failed, event sent without external fields"
+        );
+        None
+    }
+}
+
+#[async_trait]
+impl Enricher for HttpEnricher {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        Some(&self.breaker)
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let Some(key) = ctx.params.get(&self.config.key_param).filter(|k| !k.is_empty()) else {
+            return;
+        };
+        let Some(Some(fields)) = self.lookup(key).await else {
+            return;
+        };
+
+        for (name, value) in fields {
+            if self.config.fields.is_empty() || self.config.fields.contains(&name) {
+                event.attributes.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+
+    /// Start a lookup service on a random port and return its base URL and call counter
+    async fn start_service() -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/customers/:id",
+            get(move |Path(id): Path<String>| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    match id.as_str() {
+                        "acme" => Ok(Json(serde_json::json!({"tier": "enterprise", "account_id": 42, "internal": true}))),
+                        "broken" => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                        _ => Err(StatusCode::NOT_FOUND),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), calls)
+    }
+
+    fn config(base_url: &str) -> HttpEnricherConfig {
+        HttpEnricherConfig {
+            url: format!("{}/customers/{{key}}", base_url),
+            key_param: "u_account".to_string(),
+            fields: vec!["tier".to_string(), "account_id".to_string()],
+            retries: 1,
+            failure_threshold: 1,
+            ..Default::default()
+        }
+    }
+
+    async fn enrich(enricher: &HttpEnricher, account: &str) -> AnalyticsEvent {
+        let params = HashMap::from([("u_account".to_string(), account.to_string())]);
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+        enricher.enrich(&mut event, &mut ctx).await;
+        event
+    }
+
+    #[tokio::test]
+    async fn test_adds_selected_fields_and_caches() {
+        let (base_url, calls) = start_service().await;
+        let enricher = HttpEnricher::new(config(&base_url)).unwrap();
+
+        let event = enrich(&enricher, "acme").await;
+        assert_eq!(event.attributes.get("tier"), Some(&Value::from("enterprise")));
+        assert_eq!(event.attributes.get("account_id"), Some(&Value::from(42)));
+        assert!(!event.attributes.contains_key("internal"));
+
+        enrich(&enricher, "acme").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_key_adds_nothing() {
+        let (base_url, _) = start_service().await;
+        let enricher = HttpEnricher::new(config(&base_url)).unwrap();
+        assert!(enrich(&enricher, "nobody").await.attributes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_opens_breaker() {
+        let (base_url, calls) = start_service().await;
+        let enricher = HttpEnricher::new(config(&base_url)).unwrap();
+
+        assert!(enrich(&enricher, "broken").await.attributes.is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The breaker opened after the failed lookup, so the service is not called again
+        enrich(&enricher, "acme").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,160 @@
+// IP2Location GeoIP backend
+// This module looks up client IPs in IP2Location BIN databases
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use ip2location::{Record, DB};
+
+use super::geoip::{GeoIpBackend, GeoIpDatabase, GeoIpError, GeoLocation, NetworkInfo};
+
+/// GeoIP backend using an IP2Location BIN database (DB1 to DB26, IPv4 or IPv6)
+///
+/// Fields the database edition does not include are left as None. IP2Location time
+/// zones are UTC offsets rather than IANA names, so `time_zone` is not filled.
+pub struct Ip2LocationBackend {
+    path: PathBuf,
+    state: Mutex<BackendState>,
+}
+
+struct BackendState {
+    db: DB,
+    modified: Option<SystemTime>,
+}
+
+impl Ip2LocationBackend {
+    /// Load the BIN database at `path`
+    ///
+    /// # Errors
+    /// Returns `GeoIpError::DatabaseError` if the file cannot be read or is invalid
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, GeoIpError> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified_time(&path);
+        let db = open(&path)?;
+        Ok(Ip2LocationBackend {
+            path,
+            state: Mutex::new(BackendState { db, modified }),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BackendState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl GeoIpBackend for Ip2LocationBackend {
+    fn lookup(&self, ip: IpAddr) -> GeoLocation {
+        let mut state = self.lock();
+        let record = match state.db.ip_lookup(ip) {
+            Ok(Record::LocationDb(record)) => record,
+            Ok(_) => return GeoLocation::default(),
+            Err(e) => {
+                tracing::debug!(
+                    ip = %ip,
+                    error = ?e,
+                    "IP2Location lookup failed, returning default location"
+                );
+                return GeoLocation::default();
+            }
+        };
+
+        let network = NetworkInfo {
+            isp: available(record.isp.as_deref()),
+            asn: available(record.asn.as_deref()).and_then(|asn| asn.parse().ok()),
+            as_organization: available(record.as_.as_deref()),
+            ..Default::default()
+        };
+        GeoLocation {
+            country: record
+                .country
+                .as_ref()
+                .and_then(|country| available(Some(&country.long_name))),
+            country_code: record
+                .country
+                .as_ref()
+                .and_then(|country| available(Some(&country.short_name))),
+            // IP2Location databases carry neither continents nor accuracy radii
+            continent_code: None,
+            region: available(record.region.as_deref()),
+            city: available(record.city.as_deref()),
+            postal_code: available(record.zip_code.as_deref()),
+            latitude: record.latitude.map(f64::from),
+            longitude: record.longitude.map(f64::from),
+            accuracy_radius: None,
+            time_zone: None,
+            network: (!network.is_empty()).then_some(network),
+        }
+    }
+
+    fn reload_changed(&self) -> usize {
+        let mut state = self.lock();
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == state.modified {
+            return 0;
+        }
+        match open(&self.path) {
+            Ok(db) => {
+                state.db = db;
+                state.modified = modified;
+                tracing::info!(database_path = %self.path.display(), "IP2Location database reloaded");
+                1
+            }
+            Err(e) => {
+                tracing::warn!(
+                    database_path = %self.path.display(),
+                    error = %e,
+                    "Failed to reload IP2Location database, keeping previous version"
+                );
+                0
+            }
+        }
+    }
+
+    /// The BIN file does not record a build date in a form the reader exposes
+    fn databases(&self) -> Vec<GeoIpDatabase> {
+        vec![GeoIpDatabase {
+            path: self.path.display().to_string(),
+            database_type: "IP2Location".to_string(),
+            build_date: None,
+        }]
+    }
+}
+
+fn open(path: &Path) -> Result<DB, GeoIpError> {
+    DB::from_file(path).map_err(|e| GeoIpError::DatabaseError(format!("{}: {:?}", path.display(), e)))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// `value` unless it is one of IP2Location's placeholders for fields missing from
+/// the database edition ("-" or "This parameter is unavailable ...")
+fn available(value: Option<&str>) -> Option<String> {
+    value
+        .filter(|v| !v.is_empty() && *v != "-" && !v.starts_with("This parameter is unavailable"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available_filters_placeholders() {
+        assert_eq!(available(Some("Berlin")), Some("Berlin".to_string()));
+        assert_eq!(available(Some("-")), None);
+        assert_eq!(
+            available(Some("This parameter is unavailable for selected data file. Please upgrade the data file.")),
+            None
+        );
+        assert_eq!(available(None), None);
+    }
+
+    #[test]
+    fn test_new_with_invalid_path() {
+        assert!(Ip2LocationBackend::new("/nonexistent/IP2LOCATION-LITE-DB11.BIN").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+// Salted IP hashing
+// This module derives a pseudonymous per-visitor identifier from the client IP
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// Produces salted SHA-256 hashes of client IP addresses
+///
+/// With daily rotation the effective salt also includes the current UTC date, so
+/// hashes of the same IP cannot be linked across days.
+#[derive(Debug, Clone)]
+pub struct IpHasher {
+    salt: String,
+    rotate_daily: bool,
+}
+
+impl IpHasher {
+    /// Create a hasher
+    ///
+    /// # Arguments
+    /// * `salt` - Secret salt mixed into every hash
+    /// * `rotate_daily` - Whether to derive a new salt for each UTC day
+    pub fn new(salt: &str, rotate_daily: bool) -> Self {
+        IpHasher {
+            salt: salt.to_string(),
+            rotate_daily,
+        }
+    }
+
+    /// Hex-encoded hash of `ip` at the current time
+    pub fn hash(&self, ip: IpAddr) -> String {
+        self.hash_at(ip, Utc::now())
+    }
+
+    /// Hex-encoded hash of `ip` as computed at `now`
+    pub fn hash_at(&self, ip: IpAddr, now: DateTime<Utc>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        if self.rotate_daily {
+            hasher.update(now.format("|%Y-%m-%d|").to_string().as_bytes());
+        }
+        // Hash IPv4-mapped IPv6 addresses like their IPv4 form
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        hasher.update(ip.to_string().as_bytes());
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_hash_is_stable_and_salted() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let hasher = IpHasher::new("secret", false);
+        let hash = hasher.hash_at(ip("203.0.113.7"), now);
+
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hasher.hash_at(ip("203.0.113.7"), now));
+        assert_ne!(hash, hasher.hash_at(ip("203.0.113.8"), now));
+        assert_ne!(hash, IpHasher::new("other", false).hash_at(ip("203.0.113.7"), now));
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let hasher = IpHasher::new("secret", true);
+        let morning = Utc.with_ymd_and_hms(2024, 1, 1, 0, 5, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2024, 1, 1, 23, 55, 0).unwrap();
+        let next_day = Utc.with_ymd_and_hms(2024, 1, 2, 0, 5, 0).unwrap();
+
+        assert_eq!(hasher.hash_at(ip("203.0.113.7"), morning), hasher.hash_at(ip("203.0.113.7"), evening));
+        assert_ne!(hasher.hash_at(ip("203.0.113.7"), morning), hasher.hash_at(ip("203.0.113.7"), next_day));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_hashes_like_ipv4() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let hasher = IpHasher::new("secret", false);
+        assert_eq!(
+            hasher.hash_at(ip("::ffff:203.0.113.7"), now),
+            hasher.hash_at(ip("203.0.113.7"), now)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Anonymizer and datacenter IP detection
+// This module flags client IPs inside configured VPN, Tor and hosting provider ranges
+
+use std::fmt;
+
+use async_trait::async_trait;
+
+use super::geoip::NetworkInfo;
+use super::pipeline::{EnrichmentContext, Enricher};
+use crate::cidr::{parse_cidrs, IpCidr};
+use crate::config::{IpRangeListConfig, IpReputationConfig};
+use crate::transformer::AnalyticsEvent;
+
+/// Error types for loading IP range lists
+#[derive(Debug)]
+pub enum IpReputationError {
+    /// Range file cannot be read
+    IoError(String),
+    /// A range is not valid CIDR notation
+    InvalidRange(String),
+}
+
+impl fmt::Display for IpReputationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpReputationError::IoError(msg) => write!(f, "IO error: {}", msg),
+            IpReputationError::InvalidRange(msg) => write!(f, "Invalid range: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for IpReputationError {}
+
+/// Load a range list: the inline ranges followed by the lines of the range file
+///
+/// Range files hold one CIDR per line; blank lines and `#` comments are ignored.
+fn load_ranges(name: &str, list: &IpRangeListConfig) -> Result<Vec<IpCidr>, IpReputationError> {
+    let mut values = list.ranges.clone();
+    if let Some(path) = &list.file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| IpReputationError::IoError(format!("{}: {}", path, e)))?;
+        values.extend(
+            contents
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim())
+                .filter(|line| !line.is_empty())
+                .map(str::to_string),
+        );
+    }
+    parse_cidrs(&values).map_err(|e| IpReputationError::InvalidRange(format!("{}: {}", name, e)))
+}
+
+/// Flags client IPs inside the configured VPN, Tor and hosting ranges in the event's
+/// `network` section (`is_anonymous_vpn`, `is_tor_exit_node`, `is_hosting_provider`,
+/// `is_anonymous`); must run after `geoip` so flags from the Anonymous-IP database are kept
+pub struct IpReputationEnricher {
+    vpn: Vec<IpCidr>,
+    tor: Vec<IpCidr>,
+    hosting: Vec<IpCidr>,
+}
+
+impl IpReputationEnricher {
+    /// Load the range lists from configuration
+    ///
+    /// # Errors
+    /// Returns `IpReputationError` if a range file cannot be read or a range is invalid
+    pub fn from_config(config: &IpReputationConfig) -> Result<Self, IpReputationError> {
+        let enricher = IpReputationEnricher {
+            vpn: load_ranges("vpn", &config.vpn)?,
+            tor: load_ranges("tor", &config.tor)?,
+            hosting: load_ranges("hosting", &config.hosting)?,
+        };
+        tracing::info!(
+            vpn_ranges = enricher.vpn.len(),
+            tor_ranges = enricher.tor.len(),
+            hosting_ranges = enricher.hosting.len(),
+            "IP reputation ranges loaded"
+        );
+        Ok(enricher)
+    }
+}
+
+#[async_trait]
+impl Enricher for IpReputationEnricher {
+    fn name(&self) -> &str {
+        "ip_reputation"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let ip = ctx.client_ip;
+        let is_vpn = self.vpn.iter().any(|range| range.contains(ip));
+        let is_tor = self.tor.iter().any(|range| range.contains(ip));
+        let is_hosting = self.hosting.iter().any(|range| range.contains(ip));
+        if !(is_vpn || is_tor || is_hosting) {
+            return;
+        }
+
+        let network = event.network.get_or_insert_with(NetworkInfo::default);
+        if is_vpn {
+            network.is_anonymous_vpn = Some(true);
+        }
+        if is_tor {
+            network.is_tor_exit_node = Some(true);
+        }
+        if is_hosting {
+            network.is_hosting_provider = Some(true);
+        }
+        if is_vpn || is_tor {
+            network.is_anonymous = Some(true);
+        }
+        tracing::debug!(
+            endpoint = ctx.endpoint,
+            event_id = ?event.id,
+            is_vpn,
+            is_tor,
+            is_hosting,
+            "Client IP in anonymizer or hosting range"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    async fn enrich(enricher: &IpReputationEnricher, ip: &str) -> AnalyticsEvent {
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", ip.parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+        enricher.enrich(&mut event, &mut ctx).await;
+        event
+    }
+
+    #[tokio::test]
+    async fn test_flags_configured_ranges() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"# hosting providers\n198.51.100.0/24\n\n2001:db8::/32 # documentation\n").unwrap();
+        let config = IpReputationConfig {
+            vpn: IpRangeListConfig {
+                ranges: vec!["192.0.2.0/24".to_string()],
+                file: None,
+            },
+            tor: IpRangeListConfig {
+                ranges: vec!["192.0.2.7".to_string()],
+                file: None,
+            },
+            hosting: IpRangeListConfig {
+                ranges: Vec::new(),
+                file: Some(file.path().to_str().unwrap().to_string()),
+            },
+        };
+        let enricher = IpReputationEnricher::from_config(&config).unwrap();
+
+        let network = enrich(&enricher, "192.0.2.7").await.network.unwrap();
+        assert_eq!(network.is_anonymous_vpn, Some(true));
+        assert_eq!(network.is_tor_exit_node, Some(true));
+        assert_eq!(network.is_anonymous, Some(true));
+        assert_eq!(network.is_hosting_provider, None);
+
+        let network = enrich(&enricher, "2001:db8::1").await.network.unwrap();
+        assert_eq!(network.is_hosting_provider, Some(true));
+        assert_eq!(network.is_anonymous, None);
+
+        assert_eq!(enrich(&enricher, "203.0.113.1").await.network, None);
+    }
+
+    #[test]
+    fn test_invalid_ranges_are_rejected() {
+        let config = IpReputationConfig {
+            vpn: IpRangeListConfig {
+                ranges: vec!["192.0.2.0/40".to_string()],
+                file: None,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            IpReputationEnricher::from_config(&config),
+            Err(IpReputationError::InvalidRange(_))
+        ));
+
+        let config = IpReputationConfig {
+            tor: IpRangeListConfig {
+                ranges: Vec::new(),
+                file: Some("/nonexistent/tor-exits.txt".to_string()),
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            IpReputationEnricher::from_config(&config),
+            Err(IpReputationError::IoError(_))
+        ));
+    }
+}
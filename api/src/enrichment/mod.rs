@@ -1,9 +1,49 @@
 // Data enrichment module
-// This module handles User-Agent parsing and GeoIP lookup
+// This module handles User-Agent parsing, GeoIP lookup and the enrichment pipeline
 
 pub mod user_agent;
+pub mod uap;
+pub mod bot;
+pub mod computed;
+pub mod currency;
+pub mod device;
+pub mod email;
+pub mod fingerprint;
 pub mod geoip;
+pub mod headless;
+pub mod geoip_cache;
+pub mod geohash;
+pub mod http;
+pub mod ip2location;
+pub mod ip_hash;
+pub mod ip_reputation;
+pub mod mmdb;
+pub mod os_family;
+pub mod pii;
+pub mod pipeline;
+pub mod script;
+pub mod stages;
+pub mod timezone;
+pub mod ttl_cache;
+pub mod webview;
 
 // Re-export commonly used types
-pub use user_agent::{UserAgentInfo, UserAgentParser, WootheeParser};
-pub use geoip::{GeoLocation, GeoIpLookup, GeoIpError};
+pub use user_agent::{create_user_agent_parser, UserAgentError, UserAgentInfo, UserAgentParser, WootheeParser};
+pub use uap::UapParser;
+pub use bot::{identify_bot, KnownBot};
+pub use computed::{ComputedEnricher, Expression, ExpressionError};
+pub use currency::{currency_for_country, is_currency_code};
+pub use device::{extract_device, DeviceModel};
+pub use fingerprint::{Fingerprinter, FingerprintInput};
+pub use geoip::{GeoIpBackend, GeoLocation, GeoIpLookup, GeoIpError, MaxMindBackend, NetworkInfo};
+pub use ip2location::Ip2LocationBackend;
+pub use geoip_cache::GeoIpCache;
+pub use headless::{is_headless_user_agent, webdriver_hint};
+pub use ip_hash::IpHasher;
+pub use ip_reputation::{IpReputationEnricher, IpReputationError};
+pub use os_family::{NormalizedOs, OsNormalizer};
+pub use pii::PiiEnricher;
+pub use pipeline::{Enricher, EnrichmentContext, EnrichmentPipeline, EventUpdate};
+pub use script::{ScriptEnricher, ScriptError};
+pub use timezone::{resolve_timezone, TimezoneInfo};
+pub use webview::detect_in_app_browser;
@@ -0,0 +1,130 @@
+// Operating system normalization
+// This module maps parser OS names to stable families and major versions across parser backends
+
+use std::collections::HashMap;
+
+/// Lowercase OS name prefixes and their family, checked in order
+///
+/// More specific prefixes come first ("windows phone" before "windows").
+const OS_FAMILIES: &[(&str, &str)] = &[
+    ("windows phone", "Windows Phone"),
+    ("windows", "Windows"),
+    ("mac os", "macOS"),
+    ("macos", "macOS"),
+    ("iphone", "iOS"),
+    ("ipad", "iOS"),
+    ("ipod", "iOS"),
+    ("ios", "iOS"),
+    ("android", "Android"),
+    ("chromeos", "Chrome OS"),
+    ("chrome os", "Chrome OS"),
+    ("linux", "Linux"),
+    ("ubuntu", "Linux"),
+    ("fedora", "Linux"),
+    ("debian", "Linux"),
+];
+
+/// OS family and major version
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NormalizedOs {
+    /// Stable family: "Windows", "macOS", "iOS", "Android", "Linux", ... or the parser name
+    pub family: Option<String>,
+    /// Major version (e.g. "10", "17", "XP")
+    pub major: Option<String>,
+}
+
+/// Maps parser OS names to families, with configured overrides taking precedence
+#[derive(Debug, Clone, Default)]
+pub struct OsNormalizer {
+    /// Lowercase parser OS name → family
+    overrides: HashMap<String, String>,
+}
+
+impl OsNormalizer {
+    /// Create a normalizer with `overrides` (parser OS name → family, case-insensitive)
+    pub fn new(overrides: &HashMap<String, String>) -> Self {
+        OsNormalizer {
+            overrides: overrides
+                .iter()
+                .map(|(os, family)| (os.to_lowercase(), family.clone()))
+                .collect(),
+        }
+    }
+
+    /// Normalize the OS name and version reported by the User-Agent parser
+    ///
+    /// OS names not in the overrides or the built-in table keep the parser name as
+    /// family. Windows versions come from the name ("Windows 10", "Windows XP") when
+    /// present, other majors from the first component of the version.
+    pub fn normalize(&self, os: Option<&str>, os_version: Option<&str>) -> NormalizedOs {
+        let Some(os) = os.map(str::trim).filter(|os| !os.is_empty()) else {
+            return NormalizedOs::default();
+        };
+        let lower = os.to_lowercase();
+        let family = self
+            .overrides
+            .get(&lower)
+            .cloned()
+            .or_else(|| {
+                OS_FAMILIES
+                    .iter()
+                    .find(|(prefix, _)| lower.starts_with(prefix))
+                    .map(|(_, family)| family.to_string())
+            })
+            .unwrap_or_else(|| os.to_string());
+
+        // Woothee reports "Windows 10" with version "NT 10.0"
+        let windows_release = (family == "Windows" && lower.starts_with("windows "))
+            .then(|| os.split_whitespace().nth(1))
+            .flatten();
+        let major = windows_release
+            .or(os_version)
+            .and_then(|version| version.split(['.', '_']).next())
+            .map(str::trim)
+            // Woothee reports missing versions as "UNKNOWN"
+            .filter(|major| !major.is_empty() && !major.eq_ignore_ascii_case("unknown"))
+            .map(str::to_string);
+
+        NormalizedOs {
+            family: Some(family),
+            major,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(os: &str, version: Option<&str>) -> (Option<String>, Option<String>) {
+        let normalized = OsNormalizer::default().normalize(Some(os), version);
+        (normalized.family, normalized.major)
+    }
+
+    #[test]
+    fn test_woothee_names() {
+        assert_eq!(normalize("Windows 10", Some("NT 10.0")), (Some("Windows".into()), Some("10".into())));
+        assert_eq!(normalize("Windows XP", Some("NT 5.1")), (Some("Windows".into()), Some("XP".into())));
+        assert_eq!(normalize("Mac OSX", Some("10.15.7")), (Some("macOS".into()), Some("10".into())));
+        assert_eq!(normalize("iPhone", Some("17.0")), (Some("iOS".into()), Some("17".into())));
+        assert_eq!(normalize("Android", Some("13")), (Some("Android".into()), Some("13".into())));
+        assert_eq!(normalize("Linux", Some("UNKNOWN")), (Some("Linux".into()), None));
+    }
+
+    #[test]
+    fn test_uap_names() {
+        assert_eq!(normalize("Windows", Some("10")), (Some("Windows".into()), Some("10".into())));
+        assert_eq!(normalize("Mac OS X", Some("14.2.1")), (Some("macOS".into()), Some("14".into())));
+        assert_eq!(normalize("Ubuntu", None), (Some("Linux".into()), None));
+        assert_eq!(normalize("Windows Phone", Some("8.1")), (Some("Windows Phone".into()), Some("8".into())));
+    }
+
+    #[test]
+    fn test_overrides_and_unknown_names() {
+        let overrides = HashMap::from([("ChromeOS".to_string(), "Linux".to_string())]);
+        let normalizer = OsNormalizer::new(&overrides);
+        assert_eq!(normalizer.normalize(Some("ChromeOS"), None).family.as_deref(), Some("Linux"));
+        assert_eq!(normalizer.normalize(Some("BlackBerry"), Some("10.3")).family.as_deref(), Some("BlackBerry"));
+        assert_eq!(normalizer.normalize(None, Some("10")), NormalizedOs::default());
+    }
+}
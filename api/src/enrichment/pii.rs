@@ -0,0 +1,185 @@
+// Profile PII handling
+// This module hashes, masks or removes personal data in profile properties before events are streamed
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::pipeline::{EnrichmentContext, Enricher};
+use crate::config::{PiiAction, PiiConfig};
+use crate::transformer::AnalyticsEvent;
+
+/// Characters left visible at the end of masked values
+const MASK_VISIBLE_CHARS: usize = 4;
+
+/// Hex-encoded SHA-256 hash of the salt and the trimmed, lowercased value
+///
+/// Normalizing first makes " Jane@Example.com" and "jane@example.com" hash alike, so
+/// hashed properties can still be joined across events.
+pub fn hash_value(salt: &str, value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(value.trim().to_lowercase().as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Mask a value, keeping just enough to recognize it
+///
+/// Email addresses keep the first character of the local part and the domain
+/// ("j***@example.com"); other values keep their last four characters when they are
+/// at least twice that long ("*******4567") and are fully masked otherwise.
+pub fn mask_value(value: &str) -> String {
+    let value = value.trim();
+    if let Some((local, domain)) = value.rsplit_once('@') {
+        if let Some(first) = local.chars().next() {
+            return format!("{}***@{}", first, domain);
+        }
+    }
+    let len = value.chars().count();
+    let visible = if len >= 2 * MASK_VISIBLE_CHARS { MASK_VISIBLE_CHARS } else { 0 };
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < len - visible { '*' } else { c })
+        .collect()
+}
+
+/// Applies the configured PII actions to profile properties (`enrichment.pii`); must
+/// run after `email` so the email domain is extracted from the original address
+pub struct PiiEnricher {
+    salt: String,
+    properties: HashMap<String, PiiAction>,
+    projects: HashMap<String, HashMap<String, PiiAction>>,
+}
+
+impl PiiEnricher {
+    pub fn new(config: PiiConfig) -> Self {
+        PiiEnricher {
+            salt: config.salt,
+            properties: config.properties,
+            projects: config.projects,
+        }
+    }
+
+    /// Action for a profile property of an event from `project`
+    pub fn action(&self, project: Option<&str>, property: &str) -> PiiAction {
+        project
+            .and_then(|project| self.projects.get(project))
+            .and_then(|policy| policy.get(property))
+            .or_else(|| self.properties.get(property))
+            .copied()
+            .unwrap_or(PiiAction::Keep)
+    }
+}
+
+#[async_trait]
+impl Enricher for PiiEnricher {
+    fn name(&self) -> &str {
+        "pii"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+        let project = event.project.as_deref();
+        let Some(profile) = event.profile.as_mut() else {
+            return;
+        };
+
+        profile.properties.retain(|property, value| {
+            let action = self.action(project, property);
+            let text = match value {
+                Value::Null => return action != PiiAction::Remove,
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            match action {
+                PiiAction::Keep => {}
+                PiiAction::Hash => *value = Value::String(hash_value(&self.salt, &text)),
+                PiiAction::Mask => *value = Value::String(mask_value(&text)),
+                PiiAction::Remove => return false,
+            }
+            true
+        });
+        // Removed properties are no longer set once
+        if let Some(operations) = event.profile_operations.as_mut() {
+            operations.set_once.retain(|name| profile.properties.contains_key(name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformer::ProfileObject;
+    use serde_json::json;
+
+    fn enricher() -> PiiEnricher {
+        PiiEnricher::new(PiiConfig {
+            salt: "pepper".to_string(),
+            properties: HashMap::from([
+                ("email".to_string(), PiiAction::Hash),
+                ("phone".to_string(), PiiAction::Mask),
+                ("name".to_string(), PiiAction::Remove),
+            ]),
+            projects: HashMap::from([(
+                "crm".to_string(),
+                HashMap::from([("email".to_string(), PiiAction::Keep)]),
+            )]),
+        })
+    }
+
+    async fn enrich(project: &str) -> HashMap<String, Value> {
+        let mut event = AnalyticsEvent {
+            project: Some(project.to_string()),
+            profile: Some(ProfileObject {
+                properties: HashMap::from([
+                    ("email".to_string(), json!(" Jane@Example.com")),
+                    ("phone".to_string(), json!("+81 90 1234 5678")),
+                    ("name".to_string(), json!("Jane Doe")),
+                    ("plan".to_string(), json!("pro")),
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/identify", "127.0.0.1".parse().unwrap(), "", &params);
+        enricher().enrich(&mut event, &mut ctx).await;
+        event.profile.unwrap().properties
+    }
+
+    #[test]
+    fn test_hash_is_normalized_and_salted() {
+        assert_eq!(hash_value("s", " Jane@Example.com "), hash_value("s", "jane@example.com"));
+        assert_ne!(hash_value("s", "jane@example.com"), hash_value("t", "jane@example.com"));
+        assert_eq!(hash_value("s", "x").len(), 64);
+    }
+
+    #[test]
+    fn test_mask_value() {
+        assert_eq!(mask_value("jane@example.com"), "j***@example.com");
+        assert_eq!(mask_value("09012345678"), "*******5678");
+        assert_eq!(mask_value("1234"), "****");
+    }
+
+    #[tokio::test]
+    async fn test_applies_default_policy() {
+        let properties = enrich("shop").await;
+        assert_eq!(properties.get("email"), Some(&json!(hash_value("pepper", "jane@example.com"))));
+        assert_eq!(properties.get("phone"), Some(&json!("************5678")));
+        assert_eq!(properties.get("name"), None);
+        assert_eq!(properties.get("plan"), Some(&json!("pro")));
+    }
+
+    #[tokio::test]
+    async fn test_project_policy_overrides_default() {
+        let properties = enrich("crm").await;
+        assert_eq!(properties.get("email"), Some(&json!(" Jane@Example.com")));
+        assert_eq!(properties.get("name"), None);
+    }
+}
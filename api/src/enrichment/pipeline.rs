@@ -0,0 +1,634 @@
+// Enrichment pipeline
+// This module runs an ordered list of enrichment stages over each event
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+
+use super::computed::ComputedEnricher;
+use super::fingerprint::Fingerprinter;
+use super::geoip::{GeoIpLookup, GeoLocation};
+use super::http::HttpEnricher;
+use super::ip_hash::IpHasher;
+use super::ip_reputation::IpReputationEnricher;
+use super::pii::PiiEnricher;
+use super::script::ScriptEnricher;
+use super::stages::{
+    CampaignEnricher, CurrencyEnricher, EmailEnricher, EuEnricher, FingerprintEnricher, GeoIpEnricher,
+    GeohashEnricher, IpHashEnricher, SessionEnricher, TimezoneEnricher, UserAgentEnricher,
+};
+use super::user_agent::{UserAgentInfo, UserAgentParser};
+use crate::config::{Config, EnrichedField, EnricherKind};
+use crate::features::FeatureFlags;
+use crate::metrics::StageMetrics;
+use crate::session::{create_session_store, SessionStore};
+use crate::streaming::CircuitBreaker;
+use crate::transformer::AnalyticsEvent;
+
+/// Request data available to enrichers, plus results shared between stages
+pub struct EnrichmentContext<'a> {
+    /// Endpoint that received the event (for logging)
+    pub endpoint: &'static str,
+    /// Resolved client IP address
+    pub client_ip: IpAddr,
+    /// Raw User-Agent header
+    pub user_agent: &'a str,
+    /// Merged request parameters
+    pub params: &'a HashMap<String, String>,
+    /// Parsed User-Agent, set by the `user_agent` stage
+    pub user_agent_info: Option<UserAgentInfo>,
+    /// GeoIP result, set by the `geoip` stage
+    pub geo_location: Option<GeoLocation>,
+    /// Set by a stage that decided the event must not be streamed
+    pub drop_reason: Option<String>,
+}
+
+impl<'a> EnrichmentContext<'a> {
+    /// Create a context with no stage results yet
+    pub fn new(
+        endpoint: &'static str,
+        client_ip: IpAddr,
+        user_agent: &'a str,
+        params: &'a HashMap<String, String>,
+    ) -> Self {
+        EnrichmentContext {
+            endpoint,
+            client_ip,
+            user_agent,
+            params,
+            user_agent_info: None,
+            geo_location: None,
+            drop_reason: None,
+        }
+    }
+}
+
+/// Changes computed by `Enricher::lookup`, applied to the event in pipeline order
+pub type EventUpdate = Box<dyn FnOnce(&mut AnalyticsEvent, &mut EnrichmentContext<'_>) + Send>;
+
+/// A single enrichment stage
+///
+/// Stages run in pipeline order and may read results left in the context by earlier
+/// stages. Enrichment is best-effort: a stage that cannot enrich an event leaves it
+/// unchanged rather than failing the request.
+///
+/// Stages that only read the request (client IP, User-Agent, parameters) can declare
+/// themselves independent and implement `lookup` instead of `enrich`; consecutive
+/// independent stages then run concurrently.
+#[async_trait]
+pub trait Enricher: Send + Sync {
+    /// Stage name used in logs
+    fn name(&self) -> &str;
+
+    /// Whether the stage depends on neither the event nor earlier stages
+    fn is_independent(&self) -> bool {
+        false
+    }
+
+    /// Compute the stage's changes from the request alone (independent stages)
+    ///
+    /// # Returns
+    /// The update to apply to the event, or None when there is nothing to change
+    async fn lookup(&self, _ctx: &EnrichmentContext<'_>) -> Option<EventUpdate> {
+        None
+    }
+
+    /// Add fields to `event`; the default applies the result of `lookup`
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        if let Some(update) = self.lookup(ctx).await {
+            update(event, ctx);
+        }
+    }
+
+    /// Outcome recorded in the event's `provenance` once the stage ran, e.g. "parsed"
+    /// or "miss"; "applied" by default
+    fn outcome(&self, _event: &AnalyticsEvent, _ctx: &EnrichmentContext<'_>) -> &'static str {
+        "applied"
+    }
+
+    /// Circuit breaker protecting the service the stage calls, if any
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        None
+    }
+
+    /// Session store the stage keeps sessions in, if any
+    fn session_store(&self) -> Option<&dyn SessionStore> {
+        None
+    }
+}
+
+/// Ordered list of enrichment stages
+#[derive(Default)]
+pub struct EnrichmentPipeline {
+    stages: Vec<Arc<dyn Enricher>>,
+    excluded_fields: Vec<EnrichedField>,
+    provenance: bool,
+    features: Arc<FeatureFlags>,
+    stage_metrics: Arc<StageMetrics>,
+}
+
+impl EnrichmentPipeline {
+    /// Create a pipeline running `stages` in order
+    pub fn new(stages: Vec<Arc<dyn Enricher>>) -> Self {
+        EnrichmentPipeline {
+            stages,
+            excluded_fields: Vec::new(),
+            provenance: false,
+            features: Arc::default(),
+            stage_metrics: Arc::default(),
+        }
+    }
+
+    /// Record the time each stage takes into `stage_metrics`, as `enrichment.<stage>`
+    pub fn with_stage_metrics(mut self, stage_metrics: Arc<StageMetrics>) -> Self {
+        self.stage_metrics = stage_metrics;
+        self
+    }
+
+    /// Skip the stages switched off in `features` (all stages run by default)
+    pub fn with_features(mut self, features: Arc<FeatureFlags>) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Remove `fields` from every event once all stages ran
+    pub fn with_excluded_fields(mut self, fields: Vec<EnrichedField>) -> Self {
+        self.excluded_fields = fields;
+        self
+    }
+
+    /// Record the outcome of every stage in the event's `provenance`
+    pub fn with_provenance(mut self, provenance: bool) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Record the outcome of a processing step outside the pipeline (e.g. request
+    /// validation), if provenance is enabled
+    pub fn record(&self, event: &mut AnalyticsEvent, step: &str, outcome: &str) {
+        if self.provenance {
+            event.provenance.insert(step.to_string(), outcome.to_string());
+        }
+    }
+
+    /// Append a stage (e.g. a custom enricher) to the end of the pipeline
+    pub fn with_stage(mut self, stage: Arc<dyn Enricher>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Circuit breaker of the stage named `name`, if it has one
+    pub fn circuit_breaker(&self, name: &str) -> Option<&CircuitBreaker> {
+        self.stages
+            .iter()
+            .find(|stage| stage.name() == name)
+            .and_then(|stage| stage.circuit_breaker())
+    }
+
+    /// Session store of the session stage, if the pipeline has one
+    pub fn session_store(&self) -> Option<&dyn SessionStore> {
+        self.stages.iter().find_map(|stage| stage.session_store())
+    }
+
+    /// Names of the stages, in order
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Run every stage over `event`, stopping early if a stage drops the event, then
+    /// remove the excluded fields
+    ///
+    /// Stages switched off by a feature flag are skipped and recorded as "disabled".
+    ///
+    /// Runs of consecutive independent stages (e.g. `campaign`, `user_agent`, `geoip`)
+    /// look up their results concurrently; the updates are then applied in pipeline
+    /// order, so the event is the same as with sequential execution.
+    pub async fn run(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let mut enabled = Vec::with_capacity(self.stages.len());
+        for stage in &self.stages {
+            if self.features.stage_enabled(stage.name()) {
+                enabled.push(stage);
+            } else {
+                self.record(event, stage.name(), "disabled");
+            }
+        }
+        let mut remaining = enabled.as_slice();
+        while let Some(stage) = remaining.first() {
+            let independent = remaining.iter().take_while(|stage| stage.is_independent()).count();
+            if independent > 1 {
+                let (group, rest) = remaining.split_at(independent);
+                let shared: &EnrichmentContext<'_> = ctx;
+                let updates = join_all(group.iter().map(|stage| async move {
+                    let started = Instant::now();
+                    let update = stage.lookup(shared).await;
+                    (update, started.elapsed())
+                }))
+                .await;
+                for (stage, (update, lookup_time)) in group.iter().zip(updates) {
+                    let started = Instant::now();
+                    if let Some(update) = update {
+                        update(event, ctx);
+                    }
+                    self.observe(stage.name(), lookup_time + started.elapsed());
+                    self.record(event, stage.name(), stage.outcome(event, ctx));
+                    if ctx.drop_reason.is_some() {
+                        break;
+                    }
+                }
+                remaining = rest;
+            } else {
+                let started = Instant::now();
+                stage.enrich(event, ctx).await;
+                self.observe(stage.name(), started.elapsed());
+                self.record(event, stage.name(), stage.outcome(event, ctx));
+                remaining = &remaining[1..];
+            }
+            if ctx.drop_reason.is_some() {
+                break;
+            }
+        }
+        for field in &self.excluded_fields {
+            clear_field(event, *field);
+        }
+    }
+
+    /// Record the time spent in the stage named `stage`
+    ///
+    /// Stages of a concurrent group are timed separately, so their times add up to
+    /// more than the group took.
+    fn observe(&self, stage: &str, duration: Duration) {
+        self.stage_metrics.observe(&format!("enrichment.{}", stage), duration);
+    }
+
+    /// Build the pipeline declared in `enrichment.pipeline`
+    ///
+    /// Stages whose backing service is not available (no GeoIP database loaded, no
+    /// `ip_hash`, `fingerprint`, `session`, `ip_reputation` or `pii` configuration) are skipped
+    /// with a warning.
+    ///
+    /// # Arguments
+    /// * `config` - Application configuration
+    /// * `user_agent_parser` - User-Agent parser for the `user_agent` stage
+    /// * `geoip_lookup` - GeoIP lookup for the `geoip` stage, if loaded
+    pub fn from_config(
+        config: &Config,
+        user_agent_parser: Arc<dyn UserAgentParser>,
+        geoip_lookup: Option<Arc<GeoIpLookup>>,
+    ) -> Self {
+        let mut stages: Vec<Arc<dyn Enricher>> = Vec::new();
+        for kind in &config.enrichment.pipeline {
+            match kind {
+                EnricherKind::Campaign => {
+                    stages.push(Arc::new(CampaignEnricher::new(config.campaign.clone())));
+                }
+                EnricherKind::UserAgent => {
+                    stages.push(Arc::new(
+                        UserAgentEnricher::new(user_agent_parser.clone())
+                            .with_os_families(&config.user_agent.os_families),
+                    ));
+                }
+                EnricherKind::Geoip => match &geoip_lookup {
+                    Some(lookup) => stages.push(Arc::new(
+                        GeoIpEnricher::new(lookup.clone())
+                            .with_internal_ips(config.geoip.lookup_internal_ips, config.geoip.tag_internal_ips),
+                    )),
+                    None => tracing::warn!("GeoIP enrichment stage skipped (no database loaded)"),
+                },
+                EnricherKind::Timezone => stages.push(Arc::new(TimezoneEnricher)),
+                EnricherKind::Currency => stages.push(Arc::new(CurrencyEnricher)),
+                EnricherKind::IsEu => stages.push(Arc::new(EuEnricher::new(&config.enrichment.eu_countries))),
+                EnricherKind::Geohash => stages.push(Arc::new(GeohashEnricher::new(config.enrichment.geohash_precision))),
+                EnricherKind::IpHash => match &config.ip_hash {
+                    Some(ip_hash) => stages.push(Arc::new(IpHashEnricher::new(IpHasher::new(
+                        &ip_hash.salt,
+                        ip_hash.rotate_daily,
+                    )))),
+                    None => tracing::warn!("IP hash enrichment stage skipped (ip_hash not configured)"),
+                },
+                EnricherKind::Fingerprint => match &config.fingerprint {
+                    Some(fingerprint) => stages.push(Arc::new(FingerprintEnricher::new(Fingerprinter::new(fingerprint.clone())))),
+                    None => tracing::warn!("Fingerprint enrichment stage skipped (fingerprint not configured)"),
+                },
+                EnricherKind::Session => match config.session.as_ref().map(|c| (c, create_session_store(c))) {
+                    Some((session, Ok(store))) => {
+                        stages.push(Arc::new(SessionEnricher::new(Arc::from(store), session.identity.clone())))
+                    }
+                    Some((_, Err(e))) => tracing::error!(error = %e, "Session enrichment stage skipped (store setup failed)"),
+                    None => tracing::warn!("Session enrichment stage skipped (session not configured)"),
+                },
+                EnricherKind::IpReputation => match config.enrichment.ip_reputation.as_ref().map(IpReputationEnricher::from_config) {
+                    Some(Ok(enricher)) => stages.push(Arc::new(enricher)),
+                    Some(Err(e)) => tracing::error!(error = %e, "IP reputation enrichment stage skipped (ranges failed to load)"),
+                    None => tracing::warn!("IP reputation enrichment stage skipped (enrichment.ip_reputation not configured)"),
+                },
+                EnricherKind::Email => stages.push(Arc::new(EmailEnricher::new(config.enrichment.email.clone()))),
+                EnricherKind::Pii => match &config.enrichment.pii {
+                    Some(pii) => stages.push(Arc::new(PiiEnricher::new(pii.clone()))),
+                    None => tracing::warn!("PII enrichment stage skipped (enrichment.pii not configured)"),
+                },
+                EnricherKind::Http => match config.enrichment.http.as_ref().map(|c| HttpEnricher::new(c.clone())) {
+                    Some(Ok(enricher)) => stages.push(Arc::new(enricher)),
+                    Some(Err(e)) => tracing::error!(error = %e, "HTTP enrichment stage skipped (client setup failed)"),
+                    None => tracing::warn!("HTTP enrichment stage skipped (enrichment.http not configured)"),
+                },
+                EnricherKind::Script => match config.enrichment.script.as_ref().map(ScriptEnricher::from_config) {
+                    Some(Ok(enricher)) => stages.push(Arc::new(enricher)),
+                    Some(Err(e)) => tracing::error!(error = %e, "Script enrichment stage skipped (script failed to load)"),
+                    None => tracing::warn!("Script enrichment stage skipped (enrichment.script not configured)"),
+                },
+                EnricherKind::Computed => match ComputedEnricher::from_config(&config.enrichment.computed) {
+                    Ok(enricher) => stages.push(Arc::new(enricher)),
+                    Err((name, e)) => tracing::error!(field = %name, error = %e, "Computed enrichment stage skipped (invalid expression)"),
+                },
+            }
+        }
+        EnrichmentPipeline::new(stages)
+            .with_excluded_fields(config.enrichment.exclude_fields.clone())
+            .with_provenance(config.enrichment.provenance)
+    }
+}
+
+/// Remove an enriched field from `event`
+fn clear_field(event: &mut AnalyticsEvent, field: EnrichedField) {
+    match field {
+        EnrichedField::Browser => event.browser = None,
+        EnrichedField::BrowserVersion => event.browser_version = None,
+        EnrichedField::Os => event.os = None,
+        EnrichedField::OsVersion => event.os_version = None,
+        EnrichedField::OsFamily => event.os_family = None,
+        EnrichedField::OsMajor => event.os_major = None,
+        EnrichedField::Device => event.device = None,
+        EnrichedField::DeviceBrand => event.device_brand = None,
+        EnrichedField::DeviceModel => event.device_model = None,
+        EnrichedField::InAppBrowser => event.in_app_browser = None,
+        EnrichedField::IsHeadless => event.is_headless = None,
+        EnrichedField::BotName => event.bot_name = None,
+        EnrichedField::BotCategory => event.bot_category = None,
+        EnrichedField::Country => event.country = None,
+        EnrichedField::CountryCode => event.country_code = None,
+        EnrichedField::ContinentCode => event.continent_code = None,
+        EnrichedField::Region => event.region = None,
+        EnrichedField::City => event.city = None,
+        EnrichedField::PostalCode => event.postal_code = None,
+        EnrichedField::Latitude => event.latitude = None,
+        EnrichedField::Longitude => event.longitude = None,
+        EnrichedField::AccuracyRadius => event.accuracy_radius = None,
+        EnrichedField::Network => event.network = None,
+        EnrichedField::Timezone => event.timezone = None,
+        EnrichedField::UtcOffsetMinutes => event.utc_offset_minutes = None,
+        EnrichedField::LocalHour => event.local_hour = None,
+        EnrichedField::LocalDayOfWeek => event.local_day_of_week = None,
+        EnrichedField::Currency => event.currency = None,
+        EnrichedField::IsEu => event.is_eu = None,
+        EnrichedField::Geohash => event.geohash = None,
+        EnrichedField::Campaign => event.campaign = None,
+        EnrichedField::ClickIds => event.click_ids = None,
+        EnrichedField::IpHash => event.ip_hash = None,
+        EnrichedField::Fingerprint => event.fingerprint = None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enrichment::user_agent::WootheeParser;
+    use std::collections::BTreeMap;
+
+    struct Tag(&'static str);
+
+    #[async_trait]
+    impl Enricher for Tag {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+            event.event.push_str(self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_stages_in_order() {
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(Tag("a")), Arc::new(Tag("b"))]).with_stage(Arc::new(Tag("c")));
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+
+        pipeline.run(&mut event, &mut ctx).await;
+
+        assert_eq!(event.event, "abc");
+        assert_eq!(pipeline.stage_names(), vec!["a", "b", "c"]);
+    }
+
+    struct Reject;
+
+    #[async_trait]
+    impl Enricher for Reject {
+        fn name(&self) -> &str {
+            "reject"
+        }
+
+        async fn enrich(&self, _event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+            ctx.drop_reason = Some("test".to_string());
+        }
+    }
+
+    /// Independent stage that waits for its siblings before tagging the event
+    struct Rendezvous(&'static str, Arc<tokio::sync::Barrier>);
+
+    #[async_trait]
+    impl Enricher for Rendezvous {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn is_independent(&self) -> bool {
+            true
+        }
+
+        async fn lookup(&self, _ctx: &EnrichmentContext<'_>) -> Option<EventUpdate> {
+            self.1.wait().await;
+            let tag = self.0;
+            Some(Box::new(move |event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>| {
+                event.event.push_str(tag)
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_independent_stages_run_concurrently() {
+        // Sequential lookups would wait on the barrier forever
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let pipeline = EnrichmentPipeline::new(vec![
+            Arc::new(Rendezvous("a", barrier.clone())),
+            Arc::new(Rendezvous("b", barrier)),
+            Arc::new(Tag("c")),
+        ]);
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), pipeline.run(&mut event, &mut ctx))
+            .await
+            .expect("independent stages did not run concurrently");
+
+        assert_eq!(event.event, "abc");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_event_skips_later_stages() {
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(Tag("a")), Arc::new(Reject), Arc::new(Tag("b"))]);
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+
+        pipeline.run(&mut event, &mut ctx).await;
+
+        assert_eq!(event.event, "a");
+        assert_eq!(ctx.drop_reason.as_deref(), Some("test"));
+    }
+
+    #[tokio::test]
+    async fn test_excluded_fields_are_removed() {
+        struct Locate;
+
+        #[async_trait]
+        impl Enricher for Locate {
+            fn name(&self) -> &str {
+                "locate"
+            }
+
+            async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+                event.country = Some("Germany".to_string());
+                event.city = Some("Berlin".to_string());
+                event.latitude = Some(52.52);
+                event.longitude = Some(13.40);
+            }
+        }
+
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(Locate)]).with_excluded_fields(vec![
+            EnrichedField::City,
+            EnrichedField::Latitude,
+            EnrichedField::Longitude,
+        ]);
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+
+        pipeline.run(&mut event, &mut ctx).await;
+
+        assert_eq!(event.country.as_deref(), Some("Germany"));
+        assert_eq!(event.city, None);
+        assert_eq!(event.latitude, None);
+        assert_eq!(event.longitude, None);
+    }
+
+    #[tokio::test]
+    async fn test_geoip_skips_internal_ips() {
+        use crate::enrichment::geoip::GeoIpBackend;
+
+        struct Germany;
+
+        impl GeoIpBackend for Germany {
+            fn lookup(&self, _ip: IpAddr) -> GeoLocation {
+                GeoLocation {
+                    country_code: Some("DE".to_string()),
+                    ..Default::default()
+                }
+            }
+
+            fn reload_changed(&self) -> usize {
+                0
+            }
+        }
+
+        let lookup = Arc::new(GeoIpLookup::from_backend(Box::new(Germany)));
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(GeoIpEnricher::new(lookup.clone()).with_internal_ips(false, true))])
+            .with_provenance(true);
+        let params = HashMap::new();
+
+        let mut ctx = EnrichmentContext::new("/track/", "10.0.0.7".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+        pipeline.run(&mut event, &mut ctx).await;
+        assert_eq!(event.country_code, None);
+        assert_eq!(event.network.and_then(|network| network.is_internal), Some(true));
+        assert!(ctx.geo_location.is_none());
+        assert_eq!(event.provenance["geoip"], "skipped");
+
+        let mut ctx = EnrichmentContext::new("/track/", "8.8.8.8".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+        pipeline.run(&mut event, &mut ctx).await;
+        assert_eq!(event.country_code.as_deref(), Some("DE"));
+        assert_eq!(event.provenance["geoip"], "hit");
+
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(GeoIpEnricher::new(lookup).with_internal_ips(true, true))]);
+        let mut ctx = EnrichmentContext::new("/track/", "10.0.0.7".parse().unwrap(), "", &params);
+        let mut event = AnalyticsEvent::default();
+        pipeline.run(&mut event, &mut ctx).await;
+        assert_eq!(event.country_code.as_deref(), Some("DE"));
+    }
+
+    #[tokio::test]
+    async fn test_provenance_records_stage_outcomes() {
+        let pipeline = EnrichmentPipeline::new(vec![
+            Arc::new(UserAgentEnricher::new(Arc::new(WootheeParser::new()))),
+            Arc::new(Tag("a")),
+        ])
+        .with_provenance(true);
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "Mozilla/5.0", &params);
+        let mut event = AnalyticsEvent::default();
+
+        pipeline.record(&mut event, "schema", "validated");
+        pipeline.run(&mut event, &mut ctx).await;
+
+        assert_eq!(
+            event.provenance,
+            BTreeMap::from([
+                ("a".to_string(), "applied".to_string()),
+                ("schema".to_string(), "validated".to_string()),
+                ("user_agent".to_string(), "parsed".to_string()),
+            ])
+        );
+
+        let pipeline = EnrichmentPipeline::new(vec![Arc::new(Tag("a"))]);
+        let mut event = AnalyticsEvent::default();
+        pipeline.record(&mut event, "schema", "validated");
+        pipeline.run(&mut event, &mut ctx).await;
+        assert!(event.provenance.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_stages_are_skipped() {
+        let features = Arc::new(FeatureFlags::from_config(&crate::config::FeaturesConfig {
+            user_agent: false,
+            ..Default::default()
+        }));
+        let pipeline = EnrichmentPipeline::new(vec![
+            Arc::new(UserAgentEnricher::new(Arc::new(WootheeParser::new()))),
+            Arc::new(Tag("a")),
+        ])
+        .with_provenance(true)
+        .with_features(features);
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "Mozilla/5.0", &params);
+        let mut event = AnalyticsEvent::default();
+
+        pipeline.run(&mut event, &mut ctx).await;
+
+        assert!(ctx.user_agent_info.is_none());
+        assert_eq!(event.provenance["user_agent"], "disabled");
+        assert_eq!(event.provenance["a"], "applied");
+    }
+
+    #[test]
+    fn test_from_config_skips_unavailable_stages() {
+        let config = Config::default();
+        let pipeline = EnrichmentPipeline::from_config(&config, Arc::new(WootheeParser::new()), None);
+        assert_eq!(pipeline.stage_names(), vec!["campaign", "user_agent", "timezone", "currency", "is_eu", "geohash"]);
+    }
+}
@@ -0,0 +1,287 @@
+// Scripted (Rhai) enrichment stage
+// This module runs user-provided Rhai scripts that can modify events, derive fields or drop events
+
+use std::fmt;
+
+use async_trait::async_trait;
+use rhai::{Dynamic, Engine, Scope, AST};
+
+use super::pipeline::{EnrichmentContext, Enricher};
+use crate::config::ScriptEnricherConfig;
+use crate::transformer::AnalyticsEvent;
+
+/// Largest string a script may build, in bytes
+const MAX_STRING_SIZE: usize = 1 << 20;
+/// Largest array a script may build
+const MAX_ARRAY_SIZE: usize = 10_000;
+/// Largest object map a script may build
+const MAX_MAP_SIZE: usize = 10_000;
+/// Deepest function call nesting (guards against runaway recursion)
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Error types for loading enrichment scripts
+#[derive(Debug)]
+pub enum ScriptError {
+    /// Script file cannot be read
+    IoError(String),
+    /// Script does not compile
+    CompileError(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::IoError(msg) => write!(f, "IO error: {}", msg),
+            ScriptError::CompileError(msg) => write!(f, "Compile error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A compiled script and the projects it applies to
+struct CompiledScript {
+    name: String,
+    projects: Vec<String>,
+    ast: AST,
+}
+
+impl CompiledScript {
+    fn applies_to(&self, event: &AnalyticsEvent) -> bool {
+        self.projects.is_empty()
+            || event
+                .project
+                .as_ref()
+                .is_some_and(|project| self.projects.contains(project))
+    }
+}
+
+/// Enricher running Rhai scripts against each event
+///
+/// Each script sees the event as the object-map variable `event` (same shape as the
+/// streamed JSON) and may modify it in place, e.g. `event.attributes.plan = "pro";`.
+/// Setting the variable `drop` to `true` drops the event. A script that fails at
+/// runtime, exceeds the operation or size limits or leaves `event` in a shape that no longer
+/// matches the event schema is logged and its changes are discarded.
+pub struct ScriptEnricher {
+    engine: Engine,
+    scripts: Vec<CompiledScript>,
+}
+
+impl ScriptEnricher {
+    /// Create an enricher without scripts
+    ///
+    /// # Arguments
+    /// * `max_operations` - Operation budget per script run (guards against runaway loops)
+    ///
+    /// Strings, arrays, maps and call depth are capped as well, so a script cannot
+    /// exhaust memory within its operation budget.
+    pub fn new(max_operations: u64) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        ScriptEnricher {
+            engine,
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Compile `source` and add it after the existing scripts
+    ///
+    /// # Arguments
+    /// * `name` - Name used in logs and drop reasons (usually the file path)
+    /// * `source` - Rhai source code
+    /// * `projects` - Projects the script applies to (all projects when empty)
+    ///
+    /// # Errors
+    /// Returns `ScriptError::CompileError` if the script does not compile
+    pub fn add_script(&mut self, name: &str, source: &str, projects: Vec<String>) -> Result<(), ScriptError> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| ScriptError::CompileError(format!("{}: {}", name, e)))?;
+        self.scripts.push(CompiledScript {
+            name: name.to_string(),
+            projects,
+            ast,
+        });
+        Ok(())
+    }
+
+    /// Load and compile the configured script files
+    ///
+    /// # Errors
+    /// Returns `ScriptError` if a file cannot be read or does not compile
+    pub fn from_config(config: &ScriptEnricherConfig) -> Result<Self, ScriptError> {
+        let mut enricher = ScriptEnricher::new(config.max_operations);
+        for script in &config.scripts {
+            let source = std::fs::read_to_string(&script.path)
+                .map_err(|e| ScriptError::IoError(format!("{}: {}", script.path, e)))?;
+            enricher.add_script(&script.path, &source, script.projects.clone())?;
+        }
+        Ok(enricher)
+    }
+
+    /// Run one script; returns the modified event and whether it should be dropped
+    fn run(&self, script: &CompiledScript, event: &AnalyticsEvent) -> Result<(AnalyticsEvent, bool), String> {
+        let mut json = serde_json::to_value(event).map_err(|e| e.to_string())?;
+        // `attributes` is omitted when empty; make it available for derived fields
+        if let Some(fields) = json.as_object_mut() {
+            fields.entry("attributes").or_insert_with(|| serde_json::Value::Object(Default::default()));
+        }
+        let dynamic = rhai::serde::to_dynamic(json).map_err(|e| e.to_string())?;
+
+        let mut scope = Scope::new();
+        scope.push("event", dynamic);
+        scope.push("drop", false);
+        self.engine
+            .run_ast_with_scope(&mut scope, &script.ast)
+            .map_err(|e| e.to_string())?;
+
+        let drop = scope.get_value::<bool>("drop").unwrap_or(false);
+        let dynamic = scope.get_value::<Dynamic>("event").ok_or("script removed `event`")?;
+        let json: serde_json::Value = rhai::serde::from_dynamic(&dynamic).map_err(|e| e.to_string())?;
+        let event = serde_json::from_value(json).map_err(|e| e.to_string())?;
+        Ok((event, drop))
+    }
+}
+
+#[async_trait]
+impl Enricher for ScriptEnricher {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        for script in self.scripts.iter().filter(|script| script.applies_to(event)) {
+            match self.run(script, event) {
+                Ok((modified, drop)) => {
+                    *event = modified;
+                    if drop {
+                        ctx.drop_reason = Some(format!("dropped by script {}", script.name));
+                        return;
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        endpoint = ctx.endpoint,
+                        script = %script.name,
+                        event_id = ?event.id,
+                        error = %e,
+                        "Enrichment script failed, changes discarded"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    async fn run(enricher: &ScriptEnricher, event: &mut AnalyticsEvent) -> Option<String> {
+        let params = HashMap::new();
+        let mut ctx = EnrichmentContext::new("/track/", "203.0.113.1".parse().unwrap(), "", &params);
+        enricher.enrich(event, &mut ctx).await;
+        ctx.drop_reason
+    }
+
+    fn event(project: &str, name: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            project: Some(project.to_string()),
+            event: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mutates_and_derives_fields() {
+        let mut enricher = ScriptEnricher::new(10_000);
+        enricher
+            .add_script(
+                "derive.rhai",
+                r#"
+                    event.event = event.event.to_upper();
+                    event.attributes.is_checkout = event.event == "CHECKOUT";
+                "#,
+                Vec::new(),
+            )
+            .unwrap();
+
+        let mut event = event("shop", "checkout");
+        assert_eq!(run(&enricher, &mut event).await, None);
+        assert_eq!(event.event, "CHECKOUT");
+        assert_eq!(event.attributes.get("is_checkout"), Some(&serde_json::Value::Bool(true)));
+    }
+
+    #[tokio::test]
+    async fn test_drop_decision() {
+        let mut enricher = ScriptEnricher::new(10_000);
+        enricher
+            .add_script("drop.rhai", r#"if event.event.starts_with("test_") { drop = true; }"#, Vec::new())
+            .unwrap();
+
+        assert_eq!(
+            run(&enricher, &mut event("shop", "test_click")).await.as_deref(),
+            Some("dropped by script drop.rhai")
+        );
+        assert_eq!(run(&enricher, &mut event("shop", "click")).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_per_project_scripts() {
+        let mut enricher = ScriptEnricher::new(10_000);
+        enricher
+            .add_script("shop.rhai", r#"event.event = "changed";"#, vec!["shop".to_string()])
+            .unwrap();
+
+        let mut other = event("blog", "click");
+        run(&enricher, &mut other).await;
+        assert_eq!(other.event, "click");
+    }
+
+    #[tokio::test]
+    async fn test_failing_script_leaves_event_unchanged() {
+        let mut enricher = ScriptEnricher::new(1_000);
+        enricher
+            .add_script("loop.rhai", r#"event.event = "changed"; loop { }"#, Vec::new())
+            .unwrap();
+        enricher
+            .add_script("schema.rhai", r#"event.timestamp = "not a number";"#, Vec::new())
+            .unwrap();
+
+        let mut event = event("shop", "click");
+        assert_eq!(run(&enricher, &mut event).await, None);
+        assert_eq!(event.event, "click");
+    }
+
+    #[test]
+    fn test_size_and_depth_limits() {
+        // Each script finishes well within the operation budget when its limit is missing
+        let mut enricher = ScriptEnricher::new(1_000_000);
+        for (name, source) in [
+            ("string.rhai", r#"let s = "x"; for i in 0..25 { s += s; }"#),
+            ("array.rhai", "let a = []; for i in 0..20000 { a.push(i); }"),
+            ("map.rhai", r#"let m = #{}; for i in 0..20000 { m["k" + i] = i; }"#),
+            ("recursion.rhai", "fn deeper(n) { if n > 0 { deeper(n - 1); } } deeper(40);"),
+        ] {
+            enricher.add_script(name, source, Vec::new()).unwrap();
+            let script = enricher.scripts.last().unwrap();
+            assert!(enricher.run(script, &event("shop", "click")).is_err(), "{} was not stopped", name);
+        }
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let mut enricher = ScriptEnricher::new(10_000);
+        assert!(matches!(
+            enricher.add_script("bad.rhai", "let = ;", Vec::new()),
+            Err(ScriptError::CompileError(_))
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+// Built-in enrichment stages
+// This module adapts the User-Agent, GeoIP, timezone, currency, EU flag, geohash, email, IP hash, fingerprint, session and campaign enrichments to the Enricher trait
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::currency::currency_for_country;
+use super::email::{email_domain, is_free_provider, is_valid_email, normalize_email};
+use super::fingerprint::{FingerprintInput, Fingerprinter};
+use super::geohash;
+use super::geoip::{GeoIpLookup, NetworkInfo};
+use super::headless::{is_headless_user_agent, webdriver_hint};
+use super::ip_hash::IpHasher;
+use super::os_family::OsNormalizer;
+use super::pipeline::{EnrichmentContext, Enricher, EventUpdate};
+use super::timezone::resolve_timezone;
+use super::user_agent::UserAgentParser;
+use super::webview::detect_in_app_browser;
+use crate::cidr::is_internal_ip;
+use crate::config::{expand_country_codes, CampaignConfig, EmailConfig, SessionIdentity};
+use crate::session::SessionStore;
+use crate::transformer::{extract_campaign, extract_click_ids, AnalyticsEvent};
+
+/// Extracts UTM campaign attribution (`campaign`) and ad click identifiers (`click_ids`)
+pub struct CampaignEnricher {
+    config: CampaignConfig,
+}
+
+impl CampaignEnricher {
+    pub fn new(config: CampaignConfig) -> Self {
+        CampaignEnricher { config }
+    }
+}
+
+#[async_trait]
+impl Enricher for CampaignEnricher {
+    fn name(&self) -> &str {
+        "campaign"
+    }
+
+    fn is_independent(&self) -> bool {
+        true
+    }
+
+    async fn lookup(&self, ctx: &EnrichmentContext<'_>) -> Option<EventUpdate> {
+        let campaign = extract_campaign(ctx.params, &self.config);
+        let click_ids = extract_click_ids(ctx.params);
+        Some(Box::new(move |event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>| {
+            event.campaign = campaign;
+            event.click_ids = click_ids;
+        }))
+    }
+}
+
+/// Parses the User-Agent header (`browser`, `os`, `os_family`, `device`, `in_app_browser`, `is_headless`, ...)
+///
+/// Parsing runs on the blocking thread pool so it overlaps with the GeoIP lookup.
+pub struct UserAgentEnricher {
+    parser: Arc<dyn UserAgentParser>,
+    os_normalizer: OsNormalizer,
+}
+
+impl UserAgentEnricher {
+    pub fn new(parser: Arc<dyn UserAgentParser>) -> Self {
+        UserAgentEnricher {
+            parser,
+            os_normalizer: OsNormalizer::default(),
+        }
+    }
+
+    /// Override the `os_family` of parser OS names (case-insensitive)
+    pub fn with_os_families(mut self, overrides: &HashMap<String, String>) -> Self {
+        self.os_normalizer = OsNormalizer::new(overrides);
+        self
+    }
+}
+
+#[async_trait]
+impl Enricher for UserAgentEnricher {
+    fn name(&self) -> &str {
+        "user_agent"
+    }
+
+    fn is_independent(&self) -> bool {
+        true
+    }
+
+    async fn lookup(&self, ctx: &EnrichmentContext<'_>) -> Option<EventUpdate> {
+        tracing::debug!(
+            endpoint = ctx.endpoint,
+            user_agent = %ctx.user_agent,
+            "Enriching with User-Agent parsing"
+        );
+        let parser = self.parser.clone();
+        let user_agent = ctx.user_agent.to_string();
+        let ua_info = match tokio::task::spawn_blocking(move || parser.parse(&user_agent)).await {
+            Ok(ua_info) => ua_info,
+            Err(e) => {
+                tracing::error!(endpoint = ctx.endpoint, error = %e, "User-Agent parsing failed");
+                return None;
+            }
+        };
+        let normalized_os = self
+            .os_normalizer
+            .normalize(ua_info.os.as_deref(), ua_info.os_version.as_deref());
+        let in_app_browser = detect_in_app_browser(ctx.user_agent).map(str::to_string);
+        let is_headless = is_headless_user_agent(ctx.user_agent) || webdriver_hint(ctx.params);
+
+        Some(Box::new(move |event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>| {
+            event.browser = ua_info.browser.clone();
+            event.browser_version = ua_info.browser_version.clone();
+            event.os = ua_info.os.clone();
+            event.os_version = ua_info.os_version.clone();
+            event.os_family = normalized_os.family;
+            event.os_major = normalized_os.major;
+            event.device = ua_info.device.clone();
+            event.device_brand = ua_info.device_brand.clone();
+            event.device_model = ua_info.device_model.clone();
+            event.in_app_browser = in_app_browser;
+            event.is_headless = Some(is_headless);
+            event.bot_name = ua_info.bot_name.clone();
+            event.bot_category = ua_info.bot_category.clone();
+
+            tracing::debug!(
+                endpoint = ctx.endpoint,
+                browser = ?ua_info.browser,
+                os = ?ua_info.os,
+                device = ?ua_info.device,
+                in_app_browser = ?event.in_app_browser,
+                is_headless = ?event.is_headless,
+                bot_name = ?ua_info.bot_name,
+                "User-Agent enrichment complete"
+            );
+            ctx.user_agent_info = Some(ua_info);
+        }))
+    }
+
+    fn outcome(&self, _event: &AnalyticsEvent, ctx: &EnrichmentContext<'_>) -> &'static str {
+        if ctx.user_agent_info.is_some() {
+            "parsed"
+        } else {
+            "failed"
+        }
+    }
+}
+
+/// Looks up the client IP in the GeoIP databases (`country`, `city`, coordinates, `network`, ...)
+///
+/// Internal client addresses (private, loopback, link-local, CGNAT) are not looked up
+/// unless `lookup_internal` is set. Lookups run on the blocking thread pool so they
+/// overlap with User-Agent parsing.
+pub struct GeoIpEnricher {
+    lookup: Arc<GeoIpLookup>,
+    lookup_internal: bool,
+    tag_internal: bool,
+}
+
+impl GeoIpEnricher {
+    pub fn new(lookup: Arc<GeoIpLookup>) -> Self {
+        GeoIpEnricher {
+            lookup,
+            lookup_internal: false,
+            tag_internal: false,
+        }
+    }
+
+    /// Whether to look up internal addresses, and whether to tag skipped ones with
+    /// `network.is_internal`
+    pub fn with_internal_ips(mut self, lookup_internal: bool, tag_internal: bool) -> Self {
+        self.lookup_internal = lookup_internal;
+        self.tag_internal = tag_internal;
+        self
+    }
+}
+
+#[async_trait]
+impl Enricher for GeoIpEnricher {
+    fn name(&self) -> &str {
+        "geoip"
+    }
+
+    fn is_independent(&self) -> bool {
+        true
+    }
+
+    async fn lookup(&self, ctx: &EnrichmentContext<'_>) -> Option<EventUpdate> {
+        // Unix socket clients have no IP address to look up
+        if ctx.client_ip.is_unspecified() {
+            return None;
+        }
+        if !self.lookup_internal && is_internal_ip(ctx.client_ip) {
+            tracing::debug!(
+                endpoint = ctx.endpoint,
+                client_ip = %ctx.client_ip,
+                "Internal client IP, GeoIP lookup skipped"
+            );
+            if !self.tag_internal {
+                return None;
+            }
+            return Some(Box::new(|event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>| {
+                event.network = Some(NetworkInfo {
+                    is_internal: Some(true),
+                    ..Default::default()
+                });
+            }));
+        }
+
+        tracing::debug!(
+            endpoint = ctx.endpoint,
+            client_ip = %ctx.client_ip,
+            "Enriching with GeoIP lookup"
+        );
+        let lookup = self.lookup.clone();
+        let client_ip = ctx.client_ip;
+        let geo_location = match tokio::task::spawn_blocking(move || lookup.lookup(client_ip)).await {
+            Ok(geo_location) => geo_location,
+            Err(e) => {
+                tracing::error!(endpoint = ctx.endpoint, error = %e, "GeoIP lookup failed");
+                return None;
+            }
+        };
+
+        Some(Box::new(move |event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>| {
+            event.country = geo_location.country.clone();
+            event.country_code = geo_location.country_code.clone();
+            event.continent_code = geo_location.continent_code.clone();
+            event.region = geo_location.region.clone();
+            event.city = geo_location.city.clone();
+            event.postal_code = geo_location.postal_code.clone();
+            event.latitude = geo_location.latitude;
+            event.longitude = geo_location.longitude;
+            event.accuracy_radius = geo_location.accuracy_radius;
+            event.network = geo_location.network.clone();
+
+            tracing::debug!(
+                endpoint = ctx.endpoint,
+                country = ?geo_location.country,
+                city = ?geo_location.city,
+                "GeoIP enrichment complete"
+            );
+            ctx.geo_location = Some(geo_location);
+        }))
+    }
+
+    fn outcome(&self, _event: &AnalyticsEvent, ctx: &EnrichmentContext<'_>) -> &'static str {
+        match &ctx.geo_location {
+            Some(location) if location.country_code.is_some() => "hit",
+            Some(_) => "miss",
+            None if ctx.client_ip.is_unspecified() => "skipped",
+            None if is_internal_ip(ctx.client_ip) && !self.lookup_internal => "skipped",
+            None => "failed",
+        }
+    }
+}
+
+/// Resolves the visitor time zone from the `tz` parameter or the GeoIP result
+/// (`timezone`, `utc_offset_minutes`, `local_hour`, `local_day_of_week`); must run after `geoip` to use the GeoIP zone
+pub struct TimezoneEnricher;
+
+#[async_trait]
+impl Enricher for TimezoneEnricher {
+    fn name(&self) -> &str {
+        "timezone"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let client_tz = ctx.params.get("tz").map(String::as_str);
+        let geo_tz = ctx.geo_location.as_ref().and_then(|geo| geo.time_zone.as_deref());
+        if let Some(tz) = resolve_timezone(client_tz, geo_tz, event.timestamp) {
+            event.timezone = Some(tz.timezone);
+            event.utc_offset_minutes = Some(tz.utc_offset_minutes);
+            event.local_hour = Some(tz.local_hour);
+            event.local_day_of_week = Some(tz.local_day_of_week);
+        }
+    }
+}
+
+/// Adds the currency of the visitor's country (`currency`); must run after `geoip`
+pub struct CurrencyEnricher;
+
+#[async_trait]
+impl Enricher for CurrencyEnricher {
+    fn name(&self) -> &str {
+        "currency"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+        event.currency = event
+            .country_code
+            .as_deref()
+            .and_then(currency_for_country)
+            .map(str::to_string);
+    }
+}
+
+/// Flags visitors from the configured EU/EEA/UK countries (`is_eu`); must run after `geoip`
+pub struct EuEnricher {
+    /// Sorted upper-case country codes
+    countries: Vec<String>,
+}
+
+impl EuEnricher {
+    /// Create the stage for `countries` ("EU" expands to all EU member states)
+    pub fn new(countries: &[String]) -> Self {
+        EuEnricher {
+            countries: expand_country_codes(countries),
+        }
+    }
+}
+
+#[async_trait]
+impl Enricher for EuEnricher {
+    fn name(&self) -> &str {
+        "is_eu"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+        event.is_eu = event
+            .country_code
+            .as_deref()
+            .map(|code| self.countries.binary_search(&code.to_ascii_uppercase()).is_ok());
+    }
+}
+
+/// Adds the geohash of the GeoIP coordinates (`geohash`); must run after `geoip`
+pub struct GeohashEnricher {
+    precision: usize,
+}
+
+impl GeohashEnricher {
+    /// Create the stage producing geohashes of `precision` characters
+    pub fn new(precision: usize) -> Self {
+        GeohashEnricher { precision }
+    }
+}
+
+#[async_trait]
+impl Enricher for GeohashEnricher {
+    fn name(&self) -> &str {
+        "geohash"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, _ctx: &mut EnrichmentContext<'_>) {
+        event.geohash = match (event.latitude, event.longitude) {
+            (Some(latitude), Some(longitude)) => geohash::encode(latitude, longitude, self.precision),
+            _ => None,
+        };
+    }
+}
+
+/// Cleans the profile `email` property and adds `email_domain`, `email_free_provider`
+/// and `email_valid` to the profile
+pub struct EmailEnricher {
+    config: EmailConfig,
+}
+
+impl EmailEnricher {
+    pub fn new(config: EmailConfig) -> Self {
+        EmailEnricher { config }
+    }
+}
+
+#[async_trait]
+impl Enricher for EmailEnricher {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let Some(profile) = event.profile.as_mut() else {
+            return;
+        };
+        let Some(Value::String(email)) = profile.properties.get_mut("email") else {
+            return;
+        };
+
+        if self.config.lowercase {
+            *email = normalize_email(email);
+        }
+        if self.config.validate {
+            let valid = is_valid_email(email.trim());
+            profile.email_valid = Some(valid);
+            if !valid {
+                tracing::debug!(endpoint = ctx.endpoint, event_id = ?event.id, "Invalid profile email");
+                if self.config.remove_invalid {
+                    profile.properties.remove("email");
+                }
+                return;
+            }
+        }
+        if self.config.extract_domain {
+            if let Some(domain) = email_domain(email.trim()).map(str::to_lowercase) {
+                profile.email_free_provider = Some(is_free_provider(&domain, &self.config.free_providers));
+                profile.email_domain = Some(domain);
+            }
+        }
+    }
+}
+
+/// Adds the salted client IP hash (`ip_hash`)
+pub struct IpHashEnricher {
+    hasher: IpHasher,
+}
+
+impl IpHashEnricher {
+    pub fn new(hasher: IpHasher) -> Self {
+        IpHashEnricher { hasher }
+    }
+}
+
+#[async_trait]
+impl Enricher for IpHashEnricher {
+    fn name(&self) -> &str {
+        "ip_hash"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        event.ip_hash = Some(self.hasher.hash(ctx.client_ip));
+    }
+}
+
+/// Adds the salted device fingerprint (`fingerprint`)
+pub struct FingerprintEnricher {
+    fingerprinter: Fingerprinter,
+}
+
+impl FingerprintEnricher {
+    pub fn new(fingerprinter: Fingerprinter) -> Self {
+        FingerprintEnricher { fingerprinter }
+    }
+}
+
+#[async_trait]
+impl Enricher for FingerprintEnricher {
+    fn name(&self) -> &str {
+        "fingerprint"
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let input = FingerprintInput {
+            user_agent: ctx.user_agent,
+            screen: event.visit.screen.as_deref(),
+            language: event.visit.language.as_deref(),
+            client_ip: ctx.client_ip,
+        };
+        event.fingerprint = self.fingerprinter.fingerprint(&input);
+    }
+}
+
+/// Assigns the event to a visitor session (`session_id`, `session_start`,
+/// `session_event_index`); must run after `fingerprint` to use the fingerprint
+pub struct SessionEnricher {
+    store: Arc<dyn SessionStore>,
+    identity: Vec<SessionIdentity>,
+}
+
+impl SessionEnricher {
+    pub fn new(store: Arc<dyn SessionStore>, identity: Vec<SessionIdentity>) -> Self {
+        SessionEnricher { store, identity }
+    }
+
+    /// Store key of the event's visitor, or `None` if the event has no identity
+    fn visitor_key(&self, event: &AnalyticsEvent) -> Option<String> {
+        let visitor = self.identity.iter().find_map(|identity| match identity {
+            SessionIdentity::Cookie => event.visit.cookie.as_deref(),
+            SessionIdentity::Fingerprint => event.fingerprint.as_deref(),
+        });
+        visitor
+            .filter(|visitor| !visitor.is_empty())
+            .map(|visitor| format!("{}:{}", event.project.as_deref().unwrap_or_default(), visitor))
+    }
+}
+
+#[async_trait]
+impl Enricher for SessionEnricher {
+    fn name(&self) -> &str {
+        "session"
+    }
+
+    fn session_store(&self) -> Option<&dyn SessionStore> {
+        Some(self.store.as_ref())
+    }
+
+    async fn enrich(&self, event: &mut AnalyticsEvent, ctx: &mut EnrichmentContext<'_>) {
+        let Some(visitor) = self.visitor_key(event) else {
+            return;
+        };
+        match self.store.touch(&visitor, chrono::Utc::now().timestamp_millis()).await {
+            Ok(session) => {
+                event.session_id = Some(session.session_id);
+                event.session_start = Some(session.session_start);
+                event.session_event_index = Some(session.event_index);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    endpoint = ctx.endpoint,
+                    event_id = ?event.id,
+                    error = %e,
+                    "Session lookup failed, event sent without session"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Timezone enrichment
+// This module resolves the visitor's IANA time zone, its UTC offset and the local time of the event
+
+use std::str::FromStr;
+
+use chrono::{Datelike, Offset, TimeZone, Timelike};
+use chrono_tz::Tz;
+
+/// Resolved visitor time zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimezoneInfo {
+    /// IANA time zone name (e.g. "Europe/Berlin")
+    pub timezone: String,
+    /// Offset from UTC in minutes at the event time, including DST
+    pub utc_offset_minutes: i32,
+    /// Hour of the day in the visitor's time zone (0-23)
+    pub local_hour: u32,
+    /// ISO 8601 day of the week in the visitor's time zone (1 = Monday, 7 = Sunday)
+    pub local_day_of_week: u32,
+}
+
+/// Resolve the visitor's time zone
+///
+/// The client-sent `tz` parameter wins when it is a valid IANA name; otherwise the
+/// time zone of the GeoIP location is used. The UTC offset is computed at the event
+/// timestamp so daylight saving time is reflected, as are the local hour and day of week.
+///
+/// # Arguments
+/// * `client_tz` - Value of the `tz` request parameter, if any
+/// * `geo_tz` - Time zone from the GeoIP lookup, if any
+/// * `timestamp_ms` - Event timestamp in milliseconds since the epoch
+///
+/// # Returns
+/// The resolved time zone, or None when neither source yields a known zone
+pub fn resolve_timezone(client_tz: Option<&str>, geo_tz: Option<&str>, timestamp_ms: i64) -> Option<TimezoneInfo> {
+    let tz = client_tz
+        .and_then(|name| {
+            let parsed = Tz::from_str(name.trim()).ok();
+            if parsed.is_none() {
+                tracing::debug!(tz = %name, "Ignoring unknown client time zone");
+            }
+            parsed
+        })
+        .or_else(|| geo_tz.and_then(|name| Tz::from_str(name).ok()))?;
+
+    let instant = chrono::Utc.timestamp_millis_opt(timestamp_ms).single()?;
+    let offset = tz.offset_from_utc_datetime(&instant.naive_utc()).fix();
+    let local = instant.with_timezone(&tz);
+
+    Some(TimezoneInfo {
+        timezone: tz.name().to_string(),
+        utc_offset_minutes: offset.local_minus_utc() / 60,
+        local_hour: local.hour(),
+        local_day_of_week: local.weekday().number_from_monday(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-15T12:00:00Z and 2024-07-15T12:00:00Z
+    const WINTER: i64 = 1_705_320_000_000;
+    const SUMMER: i64 = 1_721_044_800_000;
+
+    #[test]
+    fn test_client_tz_takes_precedence() {
+        let info = resolve_timezone(Some("Asia/Tokyo"), Some("Europe/Berlin"), WINTER).unwrap();
+        assert_eq!(info.timezone, "Asia/Tokyo");
+        assert_eq!(info.utc_offset_minutes, 540);
+    }
+
+    #[test]
+    fn test_falls_back_to_geoip_with_dst() {
+        let winter = resolve_timezone(None, Some("Europe/Berlin"), WINTER).unwrap();
+        let summer = resolve_timezone(Some("Not/AZone"), Some("Europe/Berlin"), SUMMER).unwrap();
+        assert_eq!(winter.utc_offset_minutes, 60);
+        assert_eq!(summer.timezone, "Europe/Berlin");
+        assert_eq!(summer.utc_offset_minutes, 120);
+    }
+
+    #[test]
+    fn test_local_hour_and_day_of_week() {
+        // Monday 12:00 UTC is Monday 21:00 in Tokyo and Monday 07:00 in New York
+        let tokyo = resolve_timezone(Some("Asia/Tokyo"), None, WINTER).unwrap();
+        assert_eq!((tokyo.local_hour, tokyo.local_day_of_week), (21, 1));
+        let new_york = resolve_timezone(Some("America/New_York"), None, WINTER).unwrap();
+        assert_eq!((new_york.local_hour, new_york.local_day_of_week), (7, 1));
+
+        // Monday 12:00 UTC is already Tuesday 01:00 in Auckland (summer time)
+        let auckland = resolve_timezone(Some("Pacific/Auckland"), None, WINTER).unwrap();
+        assert_eq!((auckland.local_hour, auckland.local_day_of_week), (1, 2));
+    }
+
+    #[test]
+    fn test_half_hour_offset() {
+        let info = resolve_timezone(Some("Asia/Kolkata"), None, WINTER).unwrap();
+        assert_eq!(info.utc_offset_minutes, 330);
+    }
+
+    #[test]
+    fn test_no_source() {
+        assert_eq!(resolve_timezone(None, None, WINTER), None);
+        assert_eq!(resolve_timezone(Some("garbage"), None, WINTER), None);
+    }
+}
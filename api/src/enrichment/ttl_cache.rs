@@ -0,0 +1,129 @@
+// Time-limited cache
+// This module provides the bounded TTL cache shared by enrichers that memoize lookups
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Bounded cache whose entries expire a fixed time after insertion
+///
+/// Because every entry lives for the same time, insertion order is also expiry order,
+/// so a FIFO queue doubles as the eviction list.
+pub struct TtlCache<K, V> {
+    max_entries: usize,
+    ttl: Duration,
+    state: Mutex<CacheState<K, V>>,
+}
+
+struct CacheState<K, V> {
+    entries: HashMap<K, (Instant, V)>,
+    /// Keys in insertion order; may contain stale keys that were re-inserted since
+    order: VecDeque<(K, Instant)>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlCache<K, V> {
+    /// Create a cache holding at most `max_entries` (minimum 1) entries for `ttl` each
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        TtlCache {
+            max_entries: max_entries.max(1),
+            ttl,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Cached value for `key`, if present and not expired
+    pub fn get(&self, key: &K) -> Option<V> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .entries
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Store `value` for `key`, evicting expired and then oldest entries as needed
+    pub fn insert(&self, key: K, value: V) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        while let Some((oldest_key, inserted)) = state.order.front().cloned() {
+            let expired = now.duration_since(inserted) >= self.ttl;
+            if !expired && state.entries.len() < self.max_entries {
+                break;
+            }
+            state.order.pop_front();
+            // Only remove the map entry if it has not been refreshed since
+            if state.entries.get(&oldest_key).map(|(at, _)| *at) == Some(inserted) {
+                state.entries.remove(&oldest_key);
+            }
+        }
+
+        state.entries.insert(key.clone(), (now, value));
+        state.order.push_back((key, now));
+    }
+
+    /// Remove every entry
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    /// Number of cached entries (including expired ones not yet evicted)
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).entries.len()
+    }
+
+    /// Whether the cache holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_after_insert() {
+        let cache = TtlCache::new(10, Duration::from_secs(60));
+        assert_eq!(cache.get(&"a"), None);
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = TtlCache::new(10, Duration::from_millis(10));
+        cache.insert("a", 1);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_refreshed_key_survives_stale_queue_entry() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a", 1);
+        cache.insert("a", 2);
+        cache.insert("b", 3);
+
+        assert_eq!(cache.get(&"a"), Some(2));
+        assert_eq!(cache.get(&"b"), Some(3));
+    }
+}
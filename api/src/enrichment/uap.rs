@@ -0,0 +1,233 @@
+// uap-core based User-Agent parsing
+// This module implements UserAgentParser on top of the ua-parser regexes.yaml definitions
+
+use std::borrow::Cow;
+
+use uaparser::Parser;
+
+use super::device::extract_device;
+use super::user_agent::{UserAgentError, UserAgentInfo, UserAgentParser};
+
+/// Operating systems reported as desktop devices when uap has no device match
+const DESKTOP_OS_FAMILIES: [&str; 7] = ["Windows", "Mac OS X", "Linux", "Ubuntu", "Fedora", "Chrome OS", "FreeBSD"];
+
+/// Operating systems reported as mobile devices
+const MOBILE_OS_FAMILIES: [&str; 4] = ["iOS", "Android", "Windows Phone", "KaiOS"];
+
+/// User-Agent parser backed by the uap-core `regexes.yaml` rule set
+///
+/// uap tracks new browsers, OS releases and devices more closely than woothee, at the
+/// cost of loading a regex file at startup.
+pub struct UapParser {
+    parser: uaparser::UserAgentParser,
+}
+
+impl UapParser {
+    /// Load uap-core rules from a regexes.yaml file
+    ///
+    /// # Arguments
+    /// * `regexes_path` - Path to a uap-core (or compatible custom) regexes.yaml
+    ///
+    /// # Errors
+    /// Returns `UserAgentError::RegexesError` if the file cannot be read or parsed
+    pub fn from_yaml(regexes_path: &str) -> Result<Self, UserAgentError> {
+        let parser = uaparser::UserAgentParser::from_yaml(regexes_path)
+            .map_err(|e| UserAgentError::RegexesError(format!("{}: {:?}", regexes_path, e)))?;
+        Ok(UapParser { parser })
+    }
+}
+
+/// uap reports unmatched fields as "Other"
+fn known(family: &Cow<'_, str>) -> Option<String> {
+    if family.is_empty() || family == "Other" {
+        None
+    } else {
+        Some(family.to_string())
+    }
+}
+
+/// Join the version components that are present ("120.0.6099")
+fn join_version(parts: &[&Option<Cow<'_, str>>]) -> Option<String> {
+    let parts: Vec<&str> = parts
+        .iter()
+        .map_while(|part| part.as_deref())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("."))
+    }
+}
+
+/// Device type from uap device and OS families
+fn classify_device(device_family: &str, os_family: &str) -> Option<String> {
+    if device_family.contains("iPad") || device_family.contains("Tablet") || device_family.contains("Kindle") {
+        Some("Tablet".to_string())
+    } else if MOBILE_OS_FAMILIES.contains(&os_family) {
+        Some("Mobile".to_string())
+    } else if DESKTOP_OS_FAMILIES.contains(&os_family) {
+        Some("Desktop".to_string())
+    } else {
+        None
+    }
+}
+
+impl UserAgentParser for UapParser {
+    fn parse(&self, user_agent: &str) -> UserAgentInfo {
+        if user_agent.trim().is_empty() {
+            tracing::debug!("Empty or whitespace User-Agent string");
+            return UserAgentInfo {
+                browser: None,
+                browser_version: None,
+                os: None,
+                os_version: None,
+                device: None,
+                device_brand: None,
+                device_model: None,
+                is_bot: false,
+                bot_name: None,
+                bot_category: None,
+            };
+        }
+
+        let client = self.parser.parse(user_agent);
+
+        let browser = known(&client.user_agent.family);
+        // Prefer the brand and model from the uap device rules, falling back to
+        // the built-in extraction when the rule set has no device match
+        let fallback = extract_device(user_agent);
+        let device_brand = client.device.brand.as_ref().and_then(known).or(fallback.brand);
+        let device_model = client.device.model.as_ref().and_then(known).or(fallback.model);
+        let mut info = UserAgentInfo {
+            browser: browser.clone(),
+            browser_version: join_version(&[
+                &client.user_agent.major,
+                &client.user_agent.minor,
+                &client.user_agent.patch,
+            ]),
+            os: known(&client.os.family),
+            os_version: join_version(&[&client.os.major, &client.os.minor, &client.os.patch]),
+            device: classify_device(&client.device.family, &client.os.family),
+            device_brand,
+            device_model,
+            ..Default::default()
+        };
+        // uap-core classifies crawlers with the "Spider" device family
+        info.classify_bot(user_agent, client.device.family == "Spider", browser.as_deref());
+
+        tracing::debug!(
+            browser = ?info.browser,
+            browser_version = ?info.browser_version,
+            os = ?info.os,
+            os_version = ?info.os_version,
+            device = ?info.device,
+            device_family = %client.device.family,
+            "User-Agent parsed successfully"
+        );
+
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    const REGEXES: &str = r#"
+user_agent_parsers:
+  - regex: '(Firefox)/(\d+)\.(\d+)'
+  - regex: 'Version/(\d+)\.(\d+)(?:\.(\d+))?.*Mobile/\S+ Safari'
+    family_replacement: 'Mobile Safari'
+  - regex: '(Googlebot)/(\d+)\.(\d+)'
+os_parsers:
+  - regex: 'Windows NT 10\.0'
+    os_replacement: 'Windows'
+    os_v1_replacement: '10'
+  - regex: '(iPhone|iPad).*OS (\d+)_(\d+)'
+    os_replacement: 'iOS'
+    os_v1_replacement: '$2'
+    os_v2_replacement: '$3'
+device_parsers:
+  - regex: '(Googlebot)'
+    device_replacement: 'Spider'
+  - regex: '; (SM-[A-Z0-9]+)[;)]'
+    device_replacement: 'Samsung $1'
+    brand_replacement: 'Samsung'
+    model_replacement: '$1'
+  - regex: '(iPad)'
+    device_replacement: 'iPad'
+  - regex: '(iPhone)'
+    device_replacement: 'iPhone'
+"#;
+
+    fn parser() -> UapParser {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(REGEXES.as_bytes()).unwrap();
+        UapParser::from_yaml(file.path().to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_firefox_windows() {
+        let info = parser().parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:121.0) Gecko/20100101 Firefox/121.0");
+        assert_eq!(info.browser.as_deref(), Some("Firefox"));
+        assert_eq!(info.browser_version.as_deref(), Some("121.0"));
+        assert_eq!(info.os.as_deref(), Some("Windows"));
+        assert_eq!(info.device.as_deref(), Some("Desktop"));
+        assert!(!info.is_bot);
+    }
+
+    #[test]
+    fn test_parse_ipad() {
+        let info = parser().parse(
+            "Mozilla/5.0 (iPad; CPU OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1",
+        );
+        assert_eq!(info.os.as_deref(), Some("iOS"));
+        assert_eq!(info.os_version.as_deref(), Some("17.2"));
+        assert_eq!(info.device.as_deref(), Some("Tablet"));
+    }
+
+    #[test]
+    fn test_parse_device_brand_and_model() {
+        let info = parser().parse(
+            "Mozilla/5.0 (Linux; Android 13; SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+        );
+        assert_eq!(info.device_brand.as_deref(), Some("Samsung"));
+        assert_eq!(info.device_model.as_deref(), Some("SM-S918B"));
+
+        // No uap brand/model for this rule: falls back to the built-in extraction
+        let info = parser().parse(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+        );
+        assert_eq!(info.device_brand.as_deref(), Some("Apple"));
+        assert_eq!(info.device_model.as_deref(), Some("iPhone"));
+    }
+
+    #[test]
+    fn test_parse_bot() {
+        let info = parser().parse("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)");
+        assert!(info.is_bot);
+        assert_eq!(info.device, None);
+        assert_eq!(info.bot_name.as_deref(), Some("Googlebot"));
+        assert_eq!(info.bot_category.as_deref(), Some("search_engine"));
+    }
+
+    #[test]
+    fn test_parse_unknown_and_empty() {
+        let info = parser().parse("curl/8.4.0");
+        assert_eq!(info.browser, None);
+        assert_eq!(info.os, None);
+
+        assert_eq!(parser().parse("  ").browser, None);
+    }
+
+    #[test]
+    fn test_missing_regexes_file() {
+        assert!(matches!(
+            UapParser::from_yaml("/nonexistent/regexes.yaml"),
+            Err(UserAgentError::RegexesError(_))
+        ));
+    }
+}
@@ -1,8 +1,11 @@
 // User-Agent parsing implementation
 // This module extracts browser, OS, and device information from User-Agent headers
 
+use super::bot::{identify_bot, OTHER_BOT_CATEGORY};
+use super::device::extract_device;
+
 /// Information extracted from a User-Agent header
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct UserAgentInfo {
     /// Browser name (e.g., "Chrome", "Firefox", "Safari")
     pub browser: Option<String>,
@@ -14,8 +17,71 @@ pub struct UserAgentInfo {
     pub os_version: Option<String>,
     /// Device type: "Desktop", "Mobile", "Tablet", or None for unknown
     pub device: Option<String>,
+    /// Device manufacturer (e.g. "Samsung", "Apple"), for mobile devices
+    pub device_brand: Option<String>,
+    /// Device model (e.g. "SM-S918B", "iPhone"), for mobile devices
+    pub device_model: Option<String>,
+    /// Whether the User-Agent belongs to a known crawler or bot
+    pub is_bot: bool,
+    /// Bot name (e.g. "Googlebot", "UptimeRobot"), when known
+    pub bot_name: Option<String>,
+    /// Bot category (e.g. "search_engine", "monitoring"), set for every bot
+    pub bot_category: Option<String>,
 }
 
+impl UserAgentInfo {
+    /// Flag the User-Agent as a bot if the parser or the known-bot list says so, and
+    /// add the bot name and category
+    ///
+    /// Known bots the parser misses (uptime monitors, link previews) are flagged too.
+    /// Bots have no device type.
+    ///
+    /// # Arguments
+    /// * `user_agent` - Raw User-Agent header
+    /// * `parser_is_bot` - Whether the parser classified the User-Agent as a crawler
+    /// * `parser_name` - Name the parser reported for it
+    pub fn classify_bot(&mut self, user_agent: &str, parser_is_bot: bool, parser_name: Option<&str>) {
+        let known = identify_bot(user_agent);
+        if !parser_is_bot && known.is_none() {
+            return;
+        }
+        self.is_bot = true;
+        self.device = None;
+        self.device_brand = None;
+        self.device_model = None;
+        self.bot_name = match known {
+            Some(bot) => Some(bot.name.to_string()),
+            None => parser_name
+                .filter(|name| !name.is_empty() && !GENERIC_CRAWLER_NAMES.contains(name))
+                .map(str::to_string),
+        };
+        self.bot_category = Some(known.map_or(OTHER_BOT_CATEGORY, |bot| bot.category).to_string());
+    }
+}
+
+/// Placeholder names parsers report for crawlers they cannot name
+const GENERIC_CRAWLER_NAMES: [&str; 3] = ["misc crawler", "UNKNOWN", "Other"];
+
+/// Error types for User-Agent parser initialization
+#[derive(Debug)]
+pub enum UserAgentError {
+    /// Parser rule file not found or cannot be parsed
+    RegexesError(String),
+    /// Parser configuration is incomplete
+    ConfigError(String),
+}
+
+impl std::fmt::Display for UserAgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserAgentError::RegexesError(msg) => write!(f, "Regexes error: {}", msg),
+            UserAgentError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for UserAgentError {}
+
 /// Trait for parsing User-Agent headers
 /// Must be Send + Sync to be used in async contexts with Arc
 pub trait UserAgentParser: Send + Sync {
@@ -30,6 +96,30 @@ pub trait UserAgentParser: Send + Sync {
     fn parse(&self, user_agent: &str) -> UserAgentInfo;
 }
 
+/// Create the User-Agent parser selected in configuration
+///
+/// # Arguments
+/// * `config` - User-Agent parser configuration
+///
+/// # Errors
+/// Returns `UserAgentError` if the uap backend is selected and its regexes file
+/// is missing or invalid
+pub fn create_user_agent_parser(
+    config: &crate::config::UserAgentConfig,
+) -> Result<std::sync::Arc<dyn UserAgentParser>, UserAgentError> {
+    use crate::config::UserAgentParserType;
+
+    match config.parser {
+        UserAgentParserType::Woothee => Ok(std::sync::Arc::new(WootheeParser::new())),
+        UserAgentParserType::Uap => {
+            let regexes_path = config.regexes_path.as_deref().ok_or_else(|| {
+                UserAgentError::ConfigError("user_agent.regexes_path is required for the uap parser".to_string())
+            })?;
+            Ok(std::sync::Arc::new(super::uap::UapParser::from_yaml(regexes_path)?))
+        }
+    }
+}
+
 /// Woothee-based User-Agent parser implementation
 pub struct WootheeParser;
 
@@ -59,6 +149,11 @@ impl UserAgentParser for WootheeParser {
                 os: None,
                 os_version: None,
                 device: None,
+                device_brand: None,
+                device_model: None,
+                is_bot: false,
+                bot_name: None,
+                bot_category: None,
             };
         }
 
@@ -104,37 +199,43 @@ impl UserAgentParser for WootheeParser {
                     _ => None,
                 };
 
-                tracing::debug!(
-                    browser = ?browser,
-                    browser_version = ?browser_version,
-                    os = ?os,
-                    os_version = ?os_version,
-                    device = ?device,
-                    category = result.category,
-                    "User-Agent parsed successfully"
-                );
-
-                UserAgentInfo {
+                // Woothee has no device models; take them from the device rules
+                let device_model = extract_device(user_agent);
+                let mut info = UserAgentInfo {
                     browser,
                     browser_version,
                     os,
                     os_version,
                     device,
-                }
+                    device_brand: device_model.brand,
+                    device_model: device_model.model,
+                    ..Default::default()
+                };
+                info.classify_bot(user_agent, result.category == "crawler", Some(result.name));
+
+                tracing::debug!(
+                    browser = ?info.browser,
+                    browser_version = ?info.browser_version,
+                    os = ?info.os,
+                    os_version = ?info.os_version,
+                    device = ?info.device,
+                    device_model = ?info.device_model,
+                    bot_name = ?info.bot_name,
+                    category = result.category,
+                    "User-Agent parsed successfully"
+                );
+
+                info
             }
             None => {
-                // Unparseable User-Agent - return all None
+                // Unparseable User-Agent - return all None unless it is a known bot
                 tracing::debug!(
                     user_agent_length = user_agent.len(),
                     "Failed to parse User-Agent string"
                 );
-                UserAgentInfo {
-                    browser: None,
-                    browser_version: None,
-                    os: None,
-                    os_version: None,
-                    device: None,
-                }
+                let mut info = UserAgentInfo::default();
+                info.classify_bot(user_agent, false, None);
+                info
             }
         }
     }
@@ -191,6 +292,8 @@ mod tests {
         assert!(result.browser_version.is_some());
         assert_eq!(result.os, Some("Android".to_string()));
         assert_eq!(result.device, Some("Mobile".to_string()));
+        assert_eq!(result.device_brand, Some("Samsung".to_string()));
+        assert_eq!(result.device_model, Some("SM-S918B".to_string()));
     }
 
     #[test]
@@ -249,6 +352,37 @@ mod tests {
 
         // Bots typically don't have a device classification
         assert_eq!(result.device, None);
+        assert!(result.is_bot);
+    }
+
+    #[test]
+    fn test_bot_name_and_category() {
+        let parser = WootheeParser::new();
+        let ua = "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)";
+        let result = parser.parse(ua);
+
+        assert!(result.is_bot);
+        assert_eq!(result.bot_name.as_deref(), Some("Bingbot"));
+        assert_eq!(result.bot_category.as_deref(), Some("search_engine"));
+    }
+
+    #[test]
+    fn test_monitoring_agent_is_bot() {
+        let parser = WootheeParser::new();
+        let result = parser.parse("kube-probe/1.29");
+
+        assert!(result.is_bot);
+        assert_eq!(result.bot_name.as_deref(), Some("kube-probe"));
+        assert_eq!(result.bot_category.as_deref(), Some("monitoring"));
+    }
+
+    #[test]
+    fn test_browser_is_not_bot() {
+        let parser = WootheeParser::new();
+        let ua = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/121.0";
+        let result = parser.parse(ua);
+        assert!(!result.is_bot);
+        assert_eq!(result.bot_category, None);
     }
 
     #[test]
@@ -0,0 +1,83 @@
+// In-app browser detection
+// This module recognizes webviews embedded in social and messaging apps from User-Agent markers
+
+/// (User-Agent marker, app name), checked in order
+///
+/// Markers are matched case-sensitively; apps that embed another app's marker come first.
+const IN_APP_BROWSERS: &[(&str, &str)] = &[
+    ("Instagram", "Instagram"),
+    ("FBAN/", "Facebook"),
+    ("FBAV/", "Facebook"),
+    ("FB_IAB/", "Facebook"),
+    ("musical_ly", "TikTok"),
+    ("BytedanceWebview", "TikTok"),
+    ("TikTok", "TikTok"),
+    ("MicroMessenger", "WeChat"),
+    ("Gmail", "Gmail"),
+    ("LinkedInApp", "LinkedIn"),
+    ("Snapchat", "Snapchat"),
+    (" Line/", "Line"),
+    ("Twitter for", "Twitter"),
+    ("Pinterest/", "Pinterest"),
+];
+
+/// Name reported for Android webviews of apps not in `IN_APP_BROWSERS`
+pub const GENERIC_WEBVIEW: &str = "webview";
+
+/// Detect the app embedding the browser from its User-Agent
+///
+/// # Returns
+/// The app name (e.g. "Instagram", "WeChat"), `GENERIC_WEBVIEW` for other Android
+/// webviews (`; wv)` marker), or `None` for standalone browsers
+pub fn detect_in_app_browser(user_agent: &str) -> Option<&'static str> {
+    IN_APP_BROWSERS
+        .iter()
+        .find(|(marker, _)| user_agent.contains(marker))
+        .map(|(_, app)| *app)
+        .or_else(|| user_agent.contains("; wv)").then_some(GENERIC_WEBVIEW))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_social_apps() {
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 Instagram 307.0.0.34.111 (iPhone15,2; iOS 17_0; en_US)"),
+            Some("Instagram")
+        );
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/FBIOS;FBAV/440.0.0.33.116;FBBV/541402858]"),
+            Some("Facebook")
+        );
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (Linux; Android 13; SM-S918B Build/TP1A; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/120.0.0.0 Mobile Safari/537.36 musical_ly_2023203030 BytedanceWebview/d8a21c6"),
+            Some("TikTok")
+        );
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 MicroMessenger/8.0.42(0x18002a2f) NetType/WIFI Language/zh_CN"),
+            Some("WeChat")
+        );
+    }
+
+    #[test]
+    fn test_generic_android_webview() {
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (Linux; Android 13; Pixel 7 Build/TQ3A; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/120.0.0.0 Mobile Safari/537.36"),
+            Some(GENERIC_WEBVIEW)
+        );
+    }
+
+    #[test]
+    fn test_standalone_browsers() {
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (Linux; Android 13; SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36"),
+            None
+        );
+        assert_eq!(
+            detect_in_app_browser("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,173 @@
+// Event name normalization
+// This module canonicalizes /track/ event names and enforces per-project allowlists
+
+use std::collections::HashMap;
+
+use crate::config::{EventNameConfig, UnknownEventAction};
+use crate::filter::EventNamePattern;
+use crate::transformer::AnalyticsEvent;
+
+/// Config-driven event name policy applied to /track/ events before enrichment
+#[derive(Debug, Clone, Default)]
+pub struct EventNamePolicy {
+    trim: bool,
+    lowercase: bool,
+    aliases: HashMap<String, String>,
+    allowed: Vec<EventNamePattern>,
+    projects: HashMap<String, Vec<EventNamePattern>>,
+    unknown: UnknownEventAction,
+    bucket_name: String,
+}
+
+impl EventNamePolicy {
+    /// Build the policy from configuration
+    pub fn from_config(config: &EventNameConfig) -> Self {
+        let patterns = |names: &[String]| -> Vec<EventNamePattern> {
+            names.iter().map(|name| EventNamePattern::new(name)).collect()
+        };
+        let mut policy = EventNamePolicy {
+            trim: config.trim,
+            lowercase: config.lowercase,
+            aliases: HashMap::new(),
+            allowed: patterns(&config.allowed),
+            projects: config
+                .projects
+                .iter()
+                .map(|(project, names)| (project.clone(), patterns(names)))
+                .collect(),
+            unknown: config.unknown,
+            bucket_name: config.bucket_name.clone(),
+        };
+        // Aliases are looked up after normalization, so normalize them the same way
+        policy.aliases = config
+            .aliases
+            .iter()
+            .map(|(alias, name)| (policy.normalize(alias), name.clone()))
+            .collect();
+        policy
+    }
+
+    /// Trim and lowercase a name as configured
+    pub fn normalize(&self, name: &str) -> String {
+        let name = if self.trim { name.trim() } else { name };
+        if self.lowercase {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Whether `name` may be tracked for `project`
+    ///
+    /// The project's own list applies when configured, otherwise the global list;
+    /// with neither, every name is allowed.
+    pub fn is_allowed(&self, project: Option<&str>, name: &str) -> bool {
+        let patterns = project
+            .and_then(|project| self.projects.get(project))
+            .unwrap_or(&self.allowed);
+        patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches(name))
+    }
+
+    /// Normalize the event name, resolve aliases and enforce the allowlist
+    ///
+    /// When the name is replaced by an alias target or the bucket, the name as sent
+    /// is kept in `raw_event`.
+    ///
+    /// # Returns
+    /// Ok(()) if the event is accepted, Err with a descriptive message if it must be
+    /// rejected
+    pub fn apply(&self, event: &mut AnalyticsEvent) -> Result<(), String> {
+        let normalized = self.normalize(&event.event);
+        let (name, aliased) = match self.aliases.get(&normalized) {
+            Some(canonical) => (canonical.clone(), true),
+            None => (normalized, false),
+        };
+
+        if self.is_allowed(event.project.as_deref(), &name) {
+            if aliased {
+                event.raw_event = Some(std::mem::replace(&mut event.event, name));
+            } else {
+                event.event = name;
+            }
+            return Ok(());
+        }
+
+        match self.unknown {
+            UnknownEventAction::Reject => Err(format!(
+                "Event name '{}' is not allowed for project '{}'",
+                name,
+                event.project.as_deref().unwrap_or_default()
+            )),
+            UnknownEventAction::Bucket => {
+                event.raw_event = Some(std::mem::replace(&mut event.event, self.bucket_name.clone()));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(unknown: UnknownEventAction) -> EventNamePolicy {
+        EventNamePolicy::from_config(&EventNameConfig {
+            trim: true,
+            lowercase: true,
+            aliases: HashMap::from([("Page View".to_string(), "pageview".to_string())]),
+            allowed: vec!["pageview".to_string(), "click_*".to_string()],
+            projects: HashMap::from([("shop".to_string(), vec!["purchase".to_string()])]),
+            unknown,
+            ..Default::default()
+        })
+    }
+
+    fn event(project: &str, name: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            project: Some(project.to_string()),
+            event: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_policy_keeps_names() {
+        let mut e = event("site", " Page View ");
+        EventNamePolicy::from_config(&EventNameConfig::default()).apply(&mut e).unwrap();
+        assert_eq!(e.event, " Page View ");
+        assert_eq!(e.raw_event, None);
+    }
+
+    #[test]
+    fn test_normalizes_and_resolves_aliases() {
+        let policy = policy(UnknownEventAction::Reject);
+
+        let mut e = event("site", "  page view ");
+        policy.apply(&mut e).unwrap();
+        assert_eq!(e.event, "pageview");
+        assert_eq!(e.raw_event.as_deref(), Some("  page view "));
+
+        let mut e = event("site", "Click_Button");
+        policy.apply(&mut e).unwrap();
+        assert_eq!(e.event, "click_button");
+        assert_eq!(e.raw_event, None);
+    }
+
+    #[test]
+    fn test_project_allowlist_replaces_global_list() {
+        let policy = policy(UnknownEventAction::Reject);
+        assert!(policy.apply(&mut event("shop", "purchase")).is_ok());
+        assert_eq!(
+            policy.apply(&mut event("shop", "pageview")),
+            Err("Event name 'pageview' is not allowed for project 'shop'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_names_can_be_bucketed() {
+        let mut e = event("site", "Debug");
+        policy(UnknownEventAction::Bucket).apply(&mut e).unwrap();
+        assert_eq!(e.event, "other");
+        assert_eq!(e.raw_event.as_deref(), Some("Debug"));
+    }
+}
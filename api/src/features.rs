@@ -0,0 +1,84 @@
+// Feature flags
+// This module switches subsystems on and off at startup and while the collector runs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::FeaturesConfig;
+
+/// Live state of the `features` settings, shared by the handlers and the enrichment pipeline
+#[derive(Debug)]
+pub struct FeatureFlags {
+    geoip: AtomicBool,
+    user_agent: AtomicBool,
+    sessions: AtomicBool,
+    schema_validation: AtomicBool,
+}
+
+impl FeatureFlags {
+    /// Create the flags with their values in `config`
+    pub fn from_config(config: &FeaturesConfig) -> Self {
+        FeatureFlags {
+            geoip: AtomicBool::new(config.geoip),
+            user_agent: AtomicBool::new(config.user_agent),
+            sessions: AtomicBool::new(config.sessions),
+            schema_validation: AtomicBool::new(config.schema_validation),
+        }
+    }
+
+    /// Switch every flag to its value in `config`
+    pub fn apply(&self, config: &FeaturesConfig) {
+        self.geoip.store(config.geoip, Ordering::Relaxed);
+        self.user_agent.store(config.user_agent, Ordering::Relaxed);
+        self.sessions.store(config.sessions, Ordering::Relaxed);
+        self.schema_validation.store(config.schema_validation, Ordering::Relaxed);
+    }
+
+    /// Whether project schemas are checked
+    pub fn schema_validation(&self) -> bool {
+        self.schema_validation.load(Ordering::Relaxed)
+    }
+
+    /// Whether the enrichment stage named `stage` runs
+    ///
+    /// Stages without a flag always run.
+    pub fn stage_enabled(&self, stage: &str) -> bool {
+        let flag = match stage {
+            "geoip" => &self.geoip,
+            "user_agent" => &self.user_agent,
+            "session" => &self.sessions,
+            _ => return true,
+        };
+        flag.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        FeatureFlags::from_config(&FeaturesConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_flags() {
+        let flags = FeatureFlags::from_config(&FeaturesConfig {
+            geoip: false,
+            ..Default::default()
+        });
+        assert!(!flags.stage_enabled("geoip"));
+        assert!(flags.stage_enabled("user_agent"));
+        assert!(flags.stage_enabled("campaign"));
+
+        flags.apply(&FeaturesConfig {
+            sessions: false,
+            schema_validation: false,
+            ..Default::default()
+        });
+        assert!(flags.stage_enabled("geoip"));
+        assert!(!flags.stage_enabled("session"));
+        assert!(!flags.schema_validation());
+    }
+}
@@ -0,0 +1,145 @@
+// Per-project parameter policy
+// This module drops incoming parameters that a project's allowlist or blocklist does not accept
+
+use std::collections::HashMap;
+
+use crate::config::{FieldPolicyConfig, FieldRules};
+use crate::filter::EventNamePattern;
+
+/// Parameters every project may send, whatever its rules
+pub const ALWAYS_ACCEPTED: &[&str] = &["project", "event", "id", "timestamp", "cookie"];
+
+/// Compiled allow and block patterns
+#[derive(Debug, Clone, Default)]
+struct CompiledRules {
+    allowed: Vec<EventNamePattern>,
+    blocked: Vec<EventNamePattern>,
+}
+
+impl CompiledRules {
+    fn new(rules: &FieldRules) -> Self {
+        let patterns = |names: &[String]| names.iter().map(|name| EventNamePattern::new(name)).collect();
+        CompiledRules {
+            allowed: patterns(&rules.allowed),
+            blocked: patterns(&rules.blocked),
+        }
+    }
+
+    fn accepts(&self, name: &str) -> bool {
+        (self.allowed.is_empty() || self.allowed.iter().any(|pattern| pattern.matches(name)))
+            && !self.blocked.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+/// Config-driven policy deciding which request parameters reach the transformer
+#[derive(Debug, Clone, Default)]
+pub struct FieldPolicy {
+    default: CompiledRules,
+    projects: HashMap<String, CompiledRules>,
+}
+
+impl FieldPolicy {
+    /// Build the policy from configuration
+    pub fn from_config(config: &FieldPolicyConfig) -> Self {
+        FieldPolicy {
+            default: CompiledRules::new(&FieldRules {
+                allowed: config.allowed.clone(),
+                blocked: config.blocked.clone(),
+            }),
+            projects: config
+                .projects
+                .iter()
+                .map(|(project, rules)| (project.clone(), CompiledRules::new(rules)))
+                .collect(),
+        }
+    }
+
+    /// Whether parameter `name` is accepted for `project`
+    ///
+    /// The project's own rules apply when configured, otherwise the global ones. A
+    /// parameter is accepted when the allowlist is empty or matches it, and the
+    /// blocklist does not.
+    pub fn accepts(&self, project: Option<&str>, name: &str) -> bool {
+        if ALWAYS_ACCEPTED.contains(&name) {
+            return true;
+        }
+        project
+            .and_then(|project| self.projects.get(project))
+            .unwrap_or(&self.default)
+            .accepts(name)
+    }
+
+    /// Remove the parameters not accepted for the request's project
+    ///
+    /// # Returns
+    /// The names of the dropped parameters, sorted
+    pub fn apply(&self, params: &mut HashMap<String, String>) -> Vec<String> {
+        let project = params.get("project").cloned();
+        let mut dropped: Vec<String> = params
+            .keys()
+            .filter(|name| !self.accepts(project.as_deref(), name))
+            .cloned()
+            .collect();
+        for name in &dropped {
+            params.remove(name);
+        }
+        dropped.sort_unstable();
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> FieldPolicy {
+        FieldPolicy::from_config(&FieldPolicyConfig {
+            allowed: Vec::new(),
+            blocked: vec!["u_ssn".to_string(), "debug_*".to_string()],
+            projects: HashMap::from([(
+                "shop".to_string(),
+                FieldRules {
+                    allowed: vec!["e_*".to_string(), "url".to_string()],
+                    blocked: vec!["e_internal_*".to_string()],
+                },
+            )]),
+        })
+    }
+
+    #[test]
+    fn test_global_blocklist() {
+        let policy = policy();
+        assert!(policy.accepts(Some("blog"), "u_email"));
+        assert!(!policy.accepts(Some("blog"), "u_ssn"));
+        assert!(!policy.accepts(None, "debug_trace"));
+    }
+
+    #[test]
+    fn test_project_rules_replace_global() {
+        let policy = policy();
+        assert!(policy.accepts(Some("shop"), "e_price"));
+        assert!(policy.accepts(Some("shop"), "url"));
+        assert!(!policy.accepts(Some("shop"), "u_email"));
+        assert!(!policy.accepts(Some("shop"), "e_internal_cost"));
+        assert!(policy.accepts(Some("shop"), "timestamp"));
+    }
+
+    #[test]
+    fn test_apply_drops_parameters() {
+        let mut params: HashMap<String, String> = [
+            ("project", "shop"),
+            ("event", "purchase"),
+            ("e_price", "10"),
+            ("u_plan", "pro"),
+            ("s_campaign", "x"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let dropped = policy().apply(&mut params);
+        assert_eq!(dropped, vec!["s_campaign".to_string(), "u_plan".to_string()]);
+        assert_eq!(params.len(), 3);
+        assert!(params.contains_key("e_price"));
+    }
+}
@@ -0,0 +1,377 @@
+// Pre-sink event filtering
+// This module drops unwanted events (bots, internal and anonymized traffic, noisy event names) before they reach the streaming service
+
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+use crate::cidr::{parse_cidrs, CidrParseError, IpCidr};
+use crate::config::FilterConfig;
+use crate::enrichment::user_agent::UserAgentInfo;
+use crate::transformer::AnalyticsEvent;
+
+/// Request context the filter rules are evaluated against, alongside the event itself
+pub struct FilterContext<'a> {
+    /// Client IP address the request came from
+    pub client_ip: IpAddr,
+    /// Parsed User-Agent of the request
+    pub user_agent: &'a UserAgentInfo,
+}
+
+/// Why an event was dropped by the filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropReason {
+    /// The User-Agent is a known bot or crawler
+    Bot,
+    /// The client IP is inside a configured range
+    IpRange(IpCidr),
+    /// The event name matched a configured pattern
+    EventName(String),
+    /// The client IP belongs to a VPN, Tor exit node or proxy
+    AnonymousIp,
+    /// The client IP belongs to a hosting provider
+    HostingIp,
+    /// The browser is headless or automated
+    Headless,
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropReason::Bot => write!(f, "bot user agent"),
+            DropReason::IpRange(range) => write!(f, "client ip in {}", range),
+            DropReason::EventName(pattern) => write!(f, "event name matches '{}'", pattern),
+            DropReason::AnonymousIp => write!(f, "anonymous client ip"),
+            DropReason::HostingIp => write!(f, "hosting provider client ip"),
+            DropReason::Headless => write!(f, "headless browser"),
+        }
+    }
+}
+
+/// Case-insensitive glob pattern for event names
+///
+/// `*` matches any run of characters and `?` matches a single character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventNamePattern {
+    pattern: String,
+}
+
+impl EventNamePattern {
+    /// Create a pattern from its glob source
+    pub fn new(pattern: &str) -> Self {
+        EventNamePattern {
+            pattern: pattern.to_lowercase(),
+        }
+    }
+
+    /// Original (lowercased) glob source
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `name` matches this pattern
+    pub fn matches(&self, name: &str) -> bool {
+        let pattern: Vec<char> = self.pattern.chars().collect();
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+
+        // Iterative wildcard matching with single-star backtracking
+        let (mut p, mut n) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+        while n < name.len() {
+            if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+                p += 1;
+                n += 1;
+            } else if p < pattern.len() && pattern[p] == '*' {
+                star = Some((p, n));
+                p += 1;
+            } else if let Some((star_p, star_n)) = star {
+                p = star_p + 1;
+                n = star_n + 1;
+                star = Some((star_p, star_n + 1));
+            } else {
+                return false;
+            }
+        }
+        pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+/// Config-driven filter evaluated after enrichment and before streaming
+///
+/// Rules are checked in order: bot User-Agent, headless browser, client IP range,
+/// anonymous IP, hosting IP, event name pattern. The first matching rule decides the drop reason.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    drop_bots: bool,
+    ip_ranges: Vec<IpCidr>,
+    drop_anonymous_ips: bool,
+    drop_hosting_ips: bool,
+    drop_headless: bool,
+    event_patterns: Vec<EventNamePattern>,
+}
+
+impl EventFilter {
+    /// Build a filter from configuration
+    ///
+    /// # Returns
+    /// The filter, or an error if an IP range is not valid CIDR notation
+    pub fn from_config(config: &FilterConfig) -> Result<Self, CidrParseError> {
+        Ok(EventFilter {
+            drop_bots: config.drop_bots,
+            ip_ranges: parse_cidrs(&config.drop_ip_ranges)?,
+            drop_anonymous_ips: config.drop_anonymous_ips,
+            drop_hosting_ips: config.drop_hosting_ips,
+            drop_headless: config.drop_headless,
+            event_patterns: config
+                .drop_event_names
+                .iter()
+                .map(|p| EventNamePattern::new(p))
+                .collect(),
+        })
+    }
+
+    /// Whether no rules are configured (every event passes)
+    pub fn is_empty(&self) -> bool {
+        !self.drop_bots
+            && self.ip_ranges.is_empty()
+            && !self.drop_anonymous_ips
+            && !self.drop_hosting_ips
+            && !self.drop_headless
+            && self.event_patterns.is_empty()
+    }
+
+    /// Evaluate the filter rules for an enriched event
+    ///
+    /// # Returns
+    /// `Some(reason)` if the event should be dropped, `None` if it should be sent
+    pub fn evaluate(&self, event: &AnalyticsEvent, context: &FilterContext<'_>) -> Option<DropReason> {
+        if self.drop_bots && context.user_agent.is_bot {
+            return Some(DropReason::Bot);
+        }
+
+        if self.drop_headless && event.is_headless == Some(true) {
+            return Some(DropReason::Headless);
+        }
+
+        // Unix socket clients have the unspecified address, which no range describes
+        let client_ip = Some(context.client_ip).filter(|ip| !ip.is_unspecified());
+        if let Some(range) = client_ip.and_then(|ip| self.ip_ranges.iter().find(|r| r.contains(ip))) {
+            return Some(DropReason::IpRange(*range));
+        }
+
+        if let Some(network) = &event.network {
+            let flagged = |flag: Option<bool>| flag == Some(true);
+            if self.drop_anonymous_ips
+                && (flagged(network.is_anonymous)
+                    || flagged(network.is_anonymous_vpn)
+                    || flagged(network.is_tor_exit_node)
+                    || flagged(network.is_public_proxy)
+                    || flagged(network.is_residential_proxy))
+            {
+                return Some(DropReason::AnonymousIp);
+            }
+            if self.drop_hosting_ips && flagged(network.is_hosting_provider) {
+                return Some(DropReason::HostingIp);
+            }
+        }
+
+        self.event_patterns
+            .iter()
+            .find(|p| p.matches(&event.event))
+            .map(|p| DropReason::EventName(p.as_str().to_string()))
+    }
+}
+
+/// Event filter whose rules can be replaced while requests are being evaluated
+///
+/// Evaluations use a snapshot of the current rules, so a replacement never blocks
+/// or changes an evaluation in progress.
+#[derive(Debug, Default)]
+pub struct SharedEventFilter {
+    current: RwLock<Arc<EventFilter>>,
+}
+
+impl SharedEventFilter {
+    pub fn new(filter: EventFilter) -> Self {
+        SharedEventFilter {
+            current: RwLock::new(Arc::new(filter)),
+        }
+    }
+
+    /// Snapshot of the rules in use
+    pub fn current(&self) -> Arc<EventFilter> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Use `filter` for every evaluation from now on
+    pub fn replace(&self, filter: EventFilter) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(filter);
+    }
+
+    /// Evaluate the current rules, see [`EventFilter::evaluate`]
+    pub fn evaluate(&self, event: &AnalyticsEvent, context: &FilterContext<'_>) -> Option<DropReason> {
+        self.current().evaluate(event, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_agent(is_bot: bool) -> UserAgentInfo {
+        UserAgentInfo {
+            browser: None,
+            browser_version: None,
+            os: None,
+            os_version: None,
+            device: None,
+            device_brand: None,
+            device_model: None,
+            is_bot,
+            bot_name: None,
+            bot_category: None,
+        }
+    }
+
+    fn event(name: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            event: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn filter() -> EventFilter {
+        EventFilter::from_config(&FilterConfig {
+            drop_bots: true,
+            drop_ip_ranges: vec!["10.0.0.0/8".to_string(), "::1".to_string()],
+            drop_event_names: vec!["debug_*".to_string(), "test".to_string()],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        let pattern = EventNamePattern::new("debug_*");
+        assert!(pattern.matches("debug_click"));
+        assert!(pattern.matches("DEBUG_"));
+        assert!(!pattern.matches("pageview"));
+
+        let pattern = EventNamePattern::new("a*b?c");
+        assert!(pattern.matches("axxbyc"));
+        assert!(pattern.matches("abbxc"));
+        assert!(!pattern.matches("abc"));
+    }
+
+    #[test]
+    fn test_default_filter_passes_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.is_empty());
+        let ua = user_agent(true);
+        let context = FilterContext { client_ip: "10.0.0.1".parse().unwrap(), user_agent: &ua };
+        assert_eq!(filter.evaluate(&event("debug_x"), &context), None);
+    }
+
+    #[test]
+    fn test_drops_bots() {
+        let ua = user_agent(true);
+        let context = FilterContext { client_ip: "8.8.8.8".parse().unwrap(), user_agent: &ua };
+        assert_eq!(filter().evaluate(&event("pageview"), &context), Some(DropReason::Bot));
+    }
+
+    #[test]
+    fn test_drops_internal_ip_ranges() {
+        let ua = user_agent(false);
+        let context = FilterContext { client_ip: "10.20.30.40".parse().unwrap(), user_agent: &ua };
+        assert!(matches!(
+            filter().evaluate(&event("pageview"), &context),
+            Some(DropReason::IpRange(_))
+        ));
+
+        let context = FilterContext { client_ip: "::1".parse().unwrap(), user_agent: &ua };
+        assert!(filter().evaluate(&event("pageview"), &context).is_some());
+
+        let everything = EventFilter::from_config(&FilterConfig {
+            drop_ip_ranges: vec!["0.0.0.0/0".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let context = FilterContext { client_ip: "0.0.0.0".parse().unwrap(), user_agent: &ua };
+        assert_eq!(everything.evaluate(&event("pageview"), &context), None);
+    }
+
+    #[test]
+    fn test_drops_matching_event_names() {
+        let ua = user_agent(false);
+        let context = FilterContext { client_ip: "8.8.8.8".parse().unwrap(), user_agent: &ua };
+        assert_eq!(
+            filter().evaluate(&event("Test"), &context),
+            Some(DropReason::EventName("test".to_string()))
+        );
+        assert_eq!(filter().evaluate(&event("pageview"), &context), None);
+    }
+
+    #[test]
+    fn test_drops_anonymous_and_hosting_ips() {
+        use crate::enrichment::NetworkInfo;
+
+        let filter = EventFilter::from_config(&FilterConfig {
+            drop_anonymous_ips: true,
+            drop_hosting_ips: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let ua = user_agent(false);
+        let context = FilterContext { client_ip: "8.8.8.8".parse().unwrap(), user_agent: &ua };
+
+        let mut vpn = event("pageview");
+        vpn.network = Some(NetworkInfo {
+            is_anonymous_vpn: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(filter.evaluate(&vpn, &context), Some(DropReason::AnonymousIp));
+
+        let mut hosting = event("pageview");
+        hosting.network = Some(NetworkInfo {
+            is_hosting_provider: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(filter.evaluate(&hosting, &context), Some(DropReason::HostingIp));
+
+        let mut residential = event("pageview");
+        residential.network = Some(NetworkInfo {
+            is_anonymous: Some(false),
+            connection_type: Some("Cable/DSL".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(filter.evaluate(&residential, &context), None);
+    }
+
+    #[test]
+    fn test_drops_headless_browsers() {
+        let filter = EventFilter::from_config(&FilterConfig {
+            drop_headless: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let ua = user_agent(false);
+        let context = FilterContext { client_ip: "8.8.8.8".parse().unwrap(), user_agent: &ua };
+
+        let mut headless = event("pageview");
+        headless.is_headless = Some(true);
+        assert_eq!(filter.evaluate(&headless, &context), Some(DropReason::Headless));
+
+        let mut browser = event("pageview");
+        browser.is_headless = Some(false);
+        assert_eq!(filter.evaluate(&browser, &context), None);
+    }
+
+    #[test]
+    fn test_invalid_ip_range_is_rejected() {
+        let config = FilterConfig {
+            drop_ip_ranges: vec!["10.0.0.0/40".to_string()],
+            ..Default::default()
+        };
+        assert!(EventFilter::from_config(&config).is_err());
+    }
+}
@@ -1,9 +1,11 @@
 // HTTP request handlers module
 // This module contains handlers for /track/, /identify, and /update endpoints
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::IpAddr;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::extract::{ConnectInfo, Query, State};
 use axum::http::{HeaderMap, Method, StatusCode};
@@ -11,11 +13,33 @@ use axum::response::{IntoResponse, Response};
 use axum::Form;
 use serde_json::json;
 
-use crate::config::Config;
+use crate::audit::AuditLog;
+use crate::client_ip::{constant_time_eq, ClientIpResolver, IpOverride, API_KEY_HEADER};
+use crate::config::{effective_config, Config, EnricherKind};
 use crate::enrichment::geoip::{GeoIpLookup, GeoIpError};
+use crate::enrichment::pipeline::{EnrichmentContext, EnrichmentPipeline};
 use crate::enrichment::user_agent::UserAgentParser;
+use crate::filter::{EventFilter, FilterContext, SharedEventFilter};
+use crate::event_names::EventNamePolicy;
+use crate::features::FeatureFlags;
+use crate::field_policy::FieldPolicy;
+use crate::health::{
+    fallback_health, geoip_health, queue_health, session_health, streaming_health, ComponentState, HealthMonitor,
+    HealthReport,
+};
+use crate::logging::LogLevelHandle;
+use crate::metrics::{
+    IngestMetrics, IngestOutcome, SinkMetrics, StageMetrics, STAGE_SEND, STAGE_TRANSFORMATION, STAGE_VALIDATION,
+};
+use crate::projects::{AccessError, ProjectRegistry};
+use crate::runtime::{RuntimeError, RuntimeSettings};
+use crate::runtime_stats::RuntimeStats;
 use crate::streaming::{StreamingError, StreamingService};
-use crate::transformer::transform_params;
+use crate::transformer::{
+    collect_repeated, merge_payload, transform_update_params, validate_commerce_params,
+    validate_repeated_params, validate_root_collisions, validate_typed_params, DefaultTransformer,
+    RepeatedParams, Transformer,
+};
 
 /// Application state shared across all request handlers
 /// Contains all services and configuration needed to process analytics events
@@ -30,6 +54,36 @@ pub struct AppState {
     pub user_agent_parser: Arc<dyn UserAgentParser>,
     /// Application configuration
     pub config: Arc<Config>,
+    /// Cached streaming service health, updated by a background probe
+    pub health_monitor: Arc<HealthMonitor>,
+    /// Pre-sink filter rules applied after enrichment
+    pub event_filter: Arc<SharedEventFilter>,
+    /// Delivery metrics for the streaming service, served on /metrics
+    pub sink_metrics: Arc<SinkMetrics>,
+    /// Ingest request counts by project, served on /metrics
+    pub ingest_metrics: Arc<IngestMetrics>,
+    /// Time spent in each processing stage, served on /metrics
+    pub stage_metrics: Arc<StageMetrics>,
+    /// Resolves the client IP behind trusted proxies
+    pub client_ip_resolver: Arc<ClientIpResolver>,
+    /// Ordered enrichment stages run on every event
+    pub enrichment: Arc<EnrichmentPipeline>,
+    /// Event name normalization and allowlists for /track/
+    pub event_names: Arc<EventNamePolicy>,
+    /// Per-project parameter allowlists and blocklists
+    pub field_policy: Arc<FieldPolicy>,
+    /// Turns request parameters into events (`DefaultTransformer` unless replaced)
+    pub transformer: Arc<dyn Transformer>,
+    /// Per-project API keys, allowed origins, sampling and required parameters
+    pub projects: Arc<ProjectRegistry>,
+    /// Subsystems switched on or off by `features`
+    pub features: Arc<FeatureFlags>,
+    /// Settings changed at runtime through /admin/settings
+    pub runtime: Arc<RuntimeSettings>,
+    /// Uptime, tokio scheduler and process statistics served on /admin/runtime
+    pub runtime_stats: Arc<RuntimeStats>,
+    /// Sample of raw ingest requests (`logging.audit`), when enabled
+    pub audit_log: Option<Arc<AuditLog>>,
 }
 
 impl AppState {
@@ -40,45 +94,189 @@ impl AppState {
     /// * `geoip_lookup` - GeoIP lookup service
     /// * `user_agent_parser` - User-Agent parser implementation
     /// * `config` - Application configuration
+    /// * `sink_metrics` - Delivery metrics the streaming service's sinks record into
+    /// * `stage_metrics` - Stage durations, shared with the streaming service's sinks
     ///
     /// # Returns
-    /// A new AppState instance with all services wrapped in Arc for shared ownership
+    /// A new AppState instance with all services wrapped in Arc for shared ownership.
+    /// The streaming service is used as given; build it with
+    /// `create_instrumented_streaming_service` so its sends show up in `sink_metrics`.
+    /// The enrichment pipeline is built from `config.enrichment` using the given services.
     pub fn new(
         streaming_service: Arc<dyn StreamingService>,
         geoip_lookup: Option<Arc<GeoIpLookup>>,
         user_agent_parser: Arc<dyn UserAgentParser>,
         config: Arc<Config>,
+        sink_metrics: Arc<SinkMetrics>,
+        stage_metrics: Arc<StageMetrics>,
     ) -> Self {
+        let event_filter = build_event_filter(&config);
+        let client_ip_resolver = build_client_ip_resolver(&config);
+        let event_names = Arc::new(EventNamePolicy::from_config(&config.events));
+        let field_policy = Arc::new(FieldPolicy::from_config(&config.fields));
+        let projects = Arc::new(ProjectRegistry::from_config(&config.projects));
+        let features = Arc::new(FeatureFlags::from_config(&config.features));
+        let enrichment = Arc::new(
+            EnrichmentPipeline::from_config(&config, user_agent_parser.clone(), geoip_lookup.clone())
+                .with_features(features.clone())
+                .with_stage_metrics(stage_metrics.clone()),
+        );
+        let ingest_metrics = Arc::new(IngestMetrics::new(&config.logging.project_metrics));
+        let runtime = Arc::new(RuntimeSettings::new(
+            &config,
+            event_filter.clone(),
+            projects.clone(),
+            features.clone(),
+            streaming_service.clone(),
+            enrichment.clone(),
+        ));
         Self {
             streaming_service,
             geoip_lookup,
             user_agent_parser,
             config,
+            health_monitor: Arc::new(HealthMonitor::new()),
+            event_filter,
+            sink_metrics,
+            ingest_metrics,
+            stage_metrics,
+            client_ip_resolver,
+            enrichment,
+            event_names,
+            field_policy,
+            transformer: Arc::new(DefaultTransformer),
+            projects,
+            features,
+            runtime,
+            runtime_stats: Arc::new(RuntimeStats::new()),
+            audit_log: None,
         }
     }
 
+    /// Replace the parameter transformation, e.g. with an embedding application's own
+    pub fn with_transformer(mut self, transformer: Arc<dyn Transformer>) -> Self {
+        self.transformer = transformer;
+        self
+    }
+
+    /// Record a sample of the raw ingest requests in `audit_log`
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Apply runtime changes of `logging.level` to the installed log subscriber
+    pub fn with_log_level(mut self, handle: LogLevelHandle) -> Self {
+        let runtime = RuntimeSettings::new(
+            &self.config,
+            self.event_filter.clone(),
+            self.projects.clone(),
+            self.features.clone(),
+            self.streaming_service.clone(),
+            self.enrichment.clone(),
+        );
+        self.runtime = Arc::new(runtime.with_log_level(handle));
+        self
+    }
+
     /// Create a new AppState instance for testing without GeoIP
+    ///
+    /// The given service stands in for a broker sink, so it is instrumented like one.
     #[cfg(test)]
     pub fn new_for_testing(
         streaming_service: Arc<dyn StreamingService>,
         user_agent_parser: Arc<dyn UserAgentParser>,
         config: Arc<Config>,
     ) -> Self {
+        let event_filter = build_event_filter(&config);
+        let client_ip_resolver = build_client_ip_resolver(&config);
+        let event_names = Arc::new(EventNamePolicy::from_config(&config.events));
+        let field_policy = Arc::new(FieldPolicy::from_config(&config.fields));
+        let projects = Arc::new(ProjectRegistry::from_config(&config.projects));
+        let features = Arc::new(FeatureFlags::from_config(&config.features));
+        let stage_metrics = Arc::new(StageMetrics::new());
+        let enrichment = Arc::new(
+            EnrichmentPipeline::from_config(&config, user_agent_parser.clone(), None)
+                .with_features(features.clone())
+                .with_stage_metrics(stage_metrics.clone()),
+        );
+        let sink_metrics = Arc::new(
+            SinkMetrics::new(config.streaming.service_type.as_str()).with_topic_limits(&config.logging.topic_metrics),
+        );
+        let ingest_metrics = Arc::new(IngestMetrics::new(&config.logging.project_metrics));
+        let streaming_service: Arc<dyn StreamingService> = Arc::new(
+            crate::streaming::InstrumentedStreaming::new(streaming_service, sink_metrics.clone()).with_stage_metrics(stage_metrics.clone()),
+        );
+        let runtime = Arc::new(RuntimeSettings::new(
+            &config,
+            event_filter.clone(),
+            projects.clone(),
+            features.clone(),
+            streaming_service.clone(),
+            enrichment.clone(),
+        ));
         Self {
             streaming_service,
             geoip_lookup: None,
             user_agent_parser,
             config,
+            health_monitor: Arc::new(HealthMonitor::new()),
+            event_filter,
+            sink_metrics,
+            ingest_metrics,
+            stage_metrics,
+            client_ip_resolver,
+            enrichment,
+            event_names,
+            field_policy,
+            transformer: Arc::new(DefaultTransformer),
+            projects,
+            features,
+            runtime,
+            runtime_stats: Arc::new(RuntimeStats::new()),
+            audit_log: None,
         }
     }
 }
 
+/// Build the client IP resolver from the configured trusted proxies
+///
+/// Invalid ranges are rejected when the configuration is loaded; for hand-built configs
+/// they are logged and forwarding headers are ignored.
+fn build_client_ip_resolver(config: &Config) -> Arc<ClientIpResolver> {
+    let resolver = ClientIpResolver::from_config(&config.server.trusted_proxies).unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Invalid trusted proxy configuration, forwarding headers ignored");
+        ClientIpResolver::default()
+    });
+    let ip_override = IpOverride::from_config(&config.server.ip_override).unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Invalid ip override configuration, ip parameter ignored");
+        IpOverride::default()
+    });
+    Arc::new(resolver.with_ip_override(ip_override))
+}
+
+/// Build the pre-sink event filter from configuration
+///
+/// Filter rules are validated when the configuration is loaded, so an invalid rule here
+/// only happens for hand-built configs; it is logged and filtering is disabled.
+fn build_event_filter(config: &Config) -> Arc<SharedEventFilter> {
+    let filter = EventFilter::from_config(&config.filters).unwrap_or_else(|e| {
+        tracing::error!(error = %e, "Invalid filter configuration, event filtering disabled");
+        EventFilter::default()
+    });
+    Arc::new(SharedEventFilter::new(filter))
+}
+
 /// API error types for HTTP responses
 /// Validates: Requirements 12.3, 12.4
 #[derive(Debug)]
 pub enum ApiError {
     /// Validation error (HTTP 400)
     ValidationError(String),
+    /// Missing or invalid project API key (HTTP 401)
+    Unauthorized(String),
+    /// Origin not allowed for the project (HTTP 403)
+    Forbidden(String),
     /// Streaming service error (HTTP 500)
     StreamingError(StreamingError),
     /// GeoIP lookup error (HTTP 500)
@@ -91,6 +289,8 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, message) = match self {
             ApiError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             ApiError::StreamingError(err) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Failed to send event to streaming service: {}", err),
@@ -139,6 +339,99 @@ pub fn merge_params(
     }
 }
 
+/// Collect every value of the parameters sent more than once
+///
+/// Follows `merge_params`: for POST requests, form values replace the query values of
+/// the same parameter.
+pub fn repeated_params(
+    method: &Method,
+    query_params: &[(String, String)],
+    form_params: &[(String, String)],
+) -> RepeatedParams {
+    let mut repeated = collect_repeated(query_params);
+    if *method == Method::POST {
+        repeated.retain(|key, _| !form_params.iter().any(|(name, _)| name == key));
+        repeated.extend(collect_repeated(form_params));
+    }
+    repeated
+}
+
+/// Merge the decoded `payload` parameter into `params` (`parameters.payload` configuration)
+fn expand_payload(
+    app_state: &AppState,
+    endpoint: &'static str,
+    params: &mut HashMap<String, String>,
+) -> Result<(), ApiError> {
+    merge_payload(params, &app_state.config.parameters.payload).map_err(|e| {
+        tracing::warn!(
+            endpoint = endpoint,
+            error = %e,
+            "Invalid payload parameter"
+        );
+        ApiError::ValidationError(format!("Invalid payload: {}", e))
+    })
+}
+
+/// Check the parameters required by the project's schema, unless `features.schema_validation` is off
+fn validate_project_schema(app_state: &AppState, params: &HashMap<String, String>) -> Result<(), String> {
+    if app_state.features.schema_validation() {
+        app_state.projects.validate(params)
+    } else {
+        Ok(())
+    }
+}
+
+/// Provenance outcome of the schema step on /track/ and /identify (/update is never
+/// checked and records "skipped")
+fn schema_outcome(app_state: &AppState) -> &'static str {
+    if app_state.features.schema_validation() {
+        "validated"
+    } else {
+        "disabled"
+    }
+}
+
+/// Check the request against its project's API keys and allowed origins (`projects` configuration)
+fn authorize_project(
+    app_state: &AppState,
+    endpoint: &'static str,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+) -> Result<(), ApiError> {
+    let project = params.get("project").map(|s| s.as_str());
+    app_state.projects.authorize(project, headers).map_err(|e| {
+        tracing::warn!(
+            endpoint = endpoint,
+            project = project,
+            error = %e,
+            "Request rejected by project access rules"
+        );
+        match e {
+            AccessError::Unauthorized(msg) => ApiError::Unauthorized(msg),
+            AccessError::Forbidden(msg) => ApiError::Forbidden(msg),
+        }
+    })
+}
+
+/// Drop the parameters the request's project may not send (`fields` configuration)
+fn apply_field_policy(
+    app_state: &AppState,
+    endpoint: &'static str,
+    params: &mut HashMap<String, String>,
+    repeated: &mut RepeatedParams,
+) {
+    let dropped = app_state.field_policy.apply(params);
+    repeated.retain(|key, _| params.contains_key(key));
+    if !dropped.is_empty() {
+        tracing::debug!(
+            endpoint = endpoint,
+            project = params.get("project").map(|s| s.as_str()),
+            dropped = ?dropped,
+            "Parameters dropped by field policy"
+        );
+    }
+}
+
 /// Validate required fields for tracking events
 ///
 /// # Arguments
@@ -164,16 +457,23 @@ pub fn validate_track_params(params: &HashMap<String, String>) -> Result<(), Str
     Ok(())
 }
 
-/// Extract client IP address from connection info
+/// Extract client IP address from connection info and proxy headers
 ///
 /// # Arguments
-/// * `connect_info` - Connection information containing the client's socket address
+/// * `resolver` - Client IP resolver configured with the trusted proxy ranges
+/// * `addr` - Socket address of the TCP peer
+/// * `headers` - HTTP request headers (X-Forwarded-For, Forwarded, X-Real-IP, X-Api-Key)
+/// * `params` - Request parameters (the `ip` override of trusted server-side callers)
 ///
 /// # Returns
-/// The client's IP address
-#[allow(dead_code)]
-fn extract_client_ip(connect_info: &ConnectInfo<std::net::SocketAddr>) -> IpAddr {
-    connect_info.0.ip()
+/// The `ip` parameter for authorized callers, otherwise the peer address unless it is a trusted proxy
+fn extract_client_ip(
+    resolver: &ClientIpResolver,
+    addr: std::net::SocketAddr,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+) -> IpAddr {
+    resolver.resolve_with_params(addr.ip(), headers, params)
 }
 
 /// Extract User-Agent header from request headers
@@ -195,41 +495,70 @@ fn extract_user_agent(headers: &HeaderMap) -> String {
 ///
 /// This handler:
 /// 1. Extracts and merges parameters from query string and form body
-/// 2. Validates required fields (project, event, timestamp)
+/// 2. Checks the project's API keys and allowed origins, validates required fields
+///    (project, event, timestamp and the project's own) and applies project sampling
 /// 3. Transforms parameters into structured AnalyticsEvent
-/// 4. Enriches with User-Agent parsing
-/// 5. Enriches with GeoIP lookup
+/// 4. Runs the configured enrichment pipeline (User-Agent, GeoIP, ...)
+/// 5. Drops events matching the configured filter rules (still HTTP 200)
 /// 6. Sends to streaming service
-/// 7. Returns HTTP 200 on success, 400 on validation error, 500 on streaming error
+/// 7. Returns HTTP 200 on success, 400 on validation error, 401/403 when the project's
+///    access rules reject the request, 500 on streaming error
 ///
 /// # Validates
 /// Requirements 1.1, 1.2, 1.3, 1.5, 1.6, 12.3, 12.4, 12.6
 pub async fn track_handler(
     method: Method,
-    Query(query_params): Query<HashMap<String, String>>,
+    Query(query_params): Query<Vec<(String, String)>>,
     headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
     State(app_state): State<AppState>,
-    body: Option<Form<HashMap<String, String>>>,
+    body: Option<Form<Vec<(String, String)>>>,
 ) -> Result<StatusCode, ApiError> {
     // Step 1: Merge query and form parameters
+    let received_at = chrono::Utc::now().timestamp_millis();
     let form_params = body.map(|f| f.0).unwrap_or_default();
-    let params = merge_params(method.clone(), query_params, form_params);
+    if let Some(audit_log) = &app_state.audit_log {
+        audit_log.record("/track/", &method, &headers, &query_params, &form_params);
+    }
+    let mut repeated = repeated_params(&method, &query_params, &form_params);
+    let mut params = merge_params(
+        method.clone(),
+        query_params.into_iter().collect(),
+        form_params.into_iter().collect(),
+    );
     
     // Log incoming request with sanitized parameters
     // Validates: Requirement 10.3
     tracing::info!(
         endpoint = "/track/",
         method = %method,
-        client_ip = %addr.ip(),
+        peer_ip = %addr.ip(),
         project = params.get("project").map(|s| s.as_str()),
         event = params.get("event").map(|s| s.as_str()),
         param_count = params.len(),
         "Incoming track request"
     );
 
-    // Step 2: Validate required fields
-    validate_track_params(&params).map_err(|e| {
+    // Count the request by project and outcome; it counts as rejected until it gets further
+    let mut ingest = app_state.ingest_metrics.request("/track/");
+
+    // Expand the encoded payload parameter, check the project's access rules, then drop
+    // parameters the project does not accept
+    expand_payload(&app_state, "/track/", &mut params)?;
+    ingest.set_project(params.get("project").map(|s| s.as_str()));
+    authorize_project(&app_state, "/track/", &headers, &params)?;
+    apply_field_policy(&app_state, "/track/", &mut params, &mut repeated);
+
+    // Step 2: Validate required fields and typed parameter values
+    let started = Instant::now();
+    let validated = validate_track_params(&params)
+        .and_then(|_| validate_project_schema(&app_state, &params))
+        .and_then(|_| validate_typed_params(&params, &app_state.config.parameters))
+        .and_then(|_| validate_repeated_params(&repeated, &app_state.config.parameters))
+        .and_then(|_| validate_commerce_params(&params))
+        .and_then(|_| validate_root_collisions(&params, &app_state.config.parameters));
+    app_state.stage_metrics.observe(STAGE_VALIDATION, started.elapsed());
+    validated.map_err(|e| {
         tracing::warn!(
             endpoint = "/track/",
             error = %e,
@@ -238,60 +567,73 @@ pub async fn track_handler(
         ApiError::ValidationError(e)
     })?;
 
+    // Keep only the sampled share of the project's visitors (still HTTP 200); /identify
+    // and /update are not sampled
+    if !app_state.projects.is_sampled(&params) {
+        tracing::debug!(
+            endpoint = "/track/",
+            project = params.get("project").map(|s| s.as_str()),
+            "Event dropped by project sampling"
+        );
+        ingest.set_outcome(IngestOutcome::Sampled);
+        return Ok(StatusCode::OK);
+    }
+
     // Step 3: Extract User-Agent and client IP
     let user_agent = extract_user_agent(&headers);
-    let client_ip = addr.ip();
+    let client_ip = extract_client_ip(&app_state.client_ip_resolver, addr, &headers, &params);
 
     // Step 4: Transform parameters into structured event
     tracing::debug!(
         endpoint = "/track/",
         "Transforming parameters"
     );
-    let mut event = transform_params(params);
+    let started = Instant::now();
+    let mut event = app_state
+        .transformer
+        .transform(params.clone(), &repeated, &app_state.config.parameters);
+    event.received_at = Some(received_at);
+    app_state.transformer.post_process(&mut event, &params);
+    app_state.stage_metrics.observe(STAGE_TRANSFORMATION, started.elapsed());
+    app_state.enrichment.record(&mut event, "schema", schema_outcome(&app_state));
+    app_state.event_names.apply(&mut event).map_err(|e| {
+        tracing::warn!(
+            endpoint = "/track/",
+            error = %e,
+            "Event name rejected"
+        );
+        ApiError::ValidationError(e)
+    })?;
 
-    // Step 5: Enrich with User-Agent parsing
-    tracing::debug!(
-        endpoint = "/track/",
-        user_agent = %user_agent,
-        "Enriching with User-Agent parsing"
-    );
-    let ua_info = app_state.user_agent_parser.parse(&user_agent);
-    event.browser = ua_info.browser.clone();
-    event.browser_version = ua_info.browser_version.clone();
-    event.os = ua_info.os.clone();
-    event.os_version = ua_info.os_version.clone();
-    event.device = ua_info.device.clone();
-    
-    tracing::debug!(
-        endpoint = "/track/",
-        browser = ?ua_info.browser,
-        os = ?ua_info.os,
-        device = ?ua_info.device,
-        "User-Agent enrichment complete"
-    );
+    // Step 5: Run the enrichment pipeline
+    let mut enrichment_context = EnrichmentContext::new("/track/", client_ip, &user_agent, &params);
+    app_state.enrichment.run(&mut event, &mut enrichment_context).await;
 
-    // Step 6: Enrich with GeoIP lookup
-    tracing::debug!(
-        endpoint = "/track/",
-        client_ip = %client_ip,
-        "Enriching with GeoIP lookup"
-    );
-    if let Some(geoip) = &app_state.geoip_lookup {
-        let geo_location = geoip.lookup(client_ip);
-        event.country = geo_location.country.clone();
-        event.region = geo_location.region.clone();
-        event.city = geo_location.city.clone();
-        event.latitude = geo_location.latitude;
-        event.longitude = geo_location.longitude;
-        
-        tracing::debug!(
+    // Step 6: Apply enrichment drop decisions and pre-sink filter rules
+    if let Some(reason) = &enrichment_context.drop_reason {
+        tracing::info!(
             endpoint = "/track/",
-            country = ?geo_location.country,
-            city = ?geo_location.city,
-            "GeoIP enrichment complete"
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by enrichment"
         );
-    } else {
-        tracing::debug!("GeoIP lookup skipped (not configured)");
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
+    }
+    let ua_info = enrichment_context.user_agent_info.unwrap_or_default();
+    let filter_context = FilterContext {
+        client_ip,
+        user_agent: &ua_info,
+    };
+    if let Some(reason) = app_state.event_filter.evaluate(&event, &filter_context) {
+        tracing::info!(
+            endpoint = "/track/",
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by filter"
+        );
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
     }
 
     // Step 7: Send to streaming service
@@ -300,19 +642,20 @@ pub async fn track_handler(
         event_id = ?event.id,
         "Sending event to streaming service"
     );
-    app_state
-        .streaming_service
-        .send_event(&event)
-        .await
-        .map_err(|e| {
-            tracing::error!(
-                endpoint = "/track/",
-                event_id = ?event.id,
-                error = %e,
-                "Failed to send event to streaming service"
-            );
-            ApiError::StreamingError(e)
-        })?;
+    let started = Instant::now();
+    let sent = app_state.streaming_service.send_event(&event).await;
+    app_state.stage_metrics.observe(STAGE_SEND, started.elapsed());
+    sent.map_err(|e| {
+        tracing::error!(
+            endpoint = "/track/",
+            event_id = ?event.id,
+            error = %e,
+            "Failed to send event to streaming service"
+        );
+        ingest.set_outcome(IngestOutcome::Failed);
+        ApiError::StreamingError(e)
+    })?;
+    ingest.set_outcome(IngestOutcome::Accepted);
     
     tracing::info!(
         endpoint = "/track/",
@@ -359,8 +702,8 @@ pub fn validate_identify_params(params: &HashMap<String, String>) -> Result<(),
 /// 1. Extracts and merges parameters from query string and form body
 /// 2. Validates required fields (project, timestamp, at least one u_* parameter)
 /// 3. Transforms parameters into structured AnalyticsEvent with focus on profile object
-/// 4. Enriches with User-Agent parsing
-/// 5. Enriches with GeoIP lookup
+/// 4. Runs the configured enrichment pipeline (User-Agent, GeoIP, ...)
+/// 5. Drops events matching the configured filter rules (still HTTP 200)
 /// 6. Sends to streaming service
 /// 7. Returns HTTP 200 on success, 400 on validation error, 500 on streaming error
 ///
@@ -368,29 +711,56 @@ pub fn validate_identify_params(params: &HashMap<String, String>) -> Result<(),
 /// Requirements 2.1, 2.2, 2.3, 2.5, 2.6
 pub async fn identify_handler(
     method: Method,
-    Query(query_params): Query<HashMap<String, String>>,
+    Query(query_params): Query<Vec<(String, String)>>,
     headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
     State(app_state): State<AppState>,
-    body: Option<Form<HashMap<String, String>>>,
+    body: Option<Form<Vec<(String, String)>>>,
 ) -> Result<StatusCode, ApiError> {
     // Step 1: Merge query and form parameters
+    let received_at = chrono::Utc::now().timestamp_millis();
     let form_params = body.map(|f| f.0).unwrap_or_default();
-    let params = merge_params(method.clone(), query_params, form_params);
+    if let Some(audit_log) = &app_state.audit_log {
+        audit_log.record("/identify", &method, &headers, &query_params, &form_params);
+    }
+    let mut repeated = repeated_params(&method, &query_params, &form_params);
+    let mut params = merge_params(
+        method.clone(),
+        query_params.into_iter().collect(),
+        form_params.into_iter().collect(),
+    );
     
     // Log incoming request with sanitized parameters
     // Validates: Requirement 10.3
     tracing::info!(
         endpoint = "/identify",
         method = %method,
-        client_ip = %addr.ip(),
+        peer_ip = %addr.ip(),
         project = params.get("project").map(|s| s.as_str()),
         param_count = params.len(),
         "Incoming identify request"
     );
 
-    // Step 2: Validate required fields
-    validate_identify_params(&params).map_err(|e| {
+    // Count the request by project and outcome; it counts as rejected until it gets further
+    let mut ingest = app_state.ingest_metrics.request("/identify");
+
+    // Expand the encoded payload parameter, check the project's access rules, then drop
+    // parameters the project does not accept
+    expand_payload(&app_state, "/identify", &mut params)?;
+    ingest.set_project(params.get("project").map(|s| s.as_str()));
+    authorize_project(&app_state, "/identify", &headers, &params)?;
+    apply_field_policy(&app_state, "/identify", &mut params, &mut repeated);
+
+    // Step 2: Validate required fields and typed parameter values
+    let started = Instant::now();
+    let validated = validate_identify_params(&params)
+        .and_then(|_| validate_project_schema(&app_state, &params))
+        .and_then(|_| validate_typed_params(&params, &app_state.config.parameters))
+        .and_then(|_| validate_repeated_params(&repeated, &app_state.config.parameters))
+        .and_then(|_| validate_commerce_params(&params))
+        .and_then(|_| validate_root_collisions(&params, &app_state.config.parameters));
+    app_state.stage_metrics.observe(STAGE_VALIDATION, started.elapsed());
+    validated.map_err(|e| {
         tracing::warn!(
             endpoint = "/identify",
             error = %e,
@@ -401,7 +771,7 @@ pub async fn identify_handler(
 
     // Step 3: Extract User-Agent and client IP
     let user_agent = extract_user_agent(&headers);
-    let client_ip = addr.ip();
+    let client_ip = extract_client_ip(&app_state.client_ip_resolver, addr, &headers, &params);
 
     // Step 4: Transform parameters into structured event
     // For identify events, set event type to "identify" if not provided
@@ -414,51 +784,44 @@ pub async fn identify_handler(
         endpoint = "/identify",
         "Transforming parameters"
     );
-    let mut event = transform_params(params_with_event);
+    let started = Instant::now();
+    let mut event = app_state
+        .transformer
+        .transform(params_with_event, &repeated, &app_state.config.parameters);
+    event.received_at = Some(received_at);
+    app_state.transformer.post_process(&mut event, &params);
+    app_state.stage_metrics.observe(STAGE_TRANSFORMATION, started.elapsed());
+    app_state.enrichment.record(&mut event, "schema", schema_outcome(&app_state));
 
-    // Step 5: Enrich with User-Agent parsing
-    tracing::debug!(
-        endpoint = "/identify",
-        user_agent = %user_agent,
-        "Enriching with User-Agent parsing"
-    );
-    let ua_info = app_state.user_agent_parser.parse(&user_agent);
-    event.browser = ua_info.browser.clone();
-    event.browser_version = ua_info.browser_version.clone();
-    event.os = ua_info.os.clone();
-    event.os_version = ua_info.os_version.clone();
-    event.device = ua_info.device.clone();
-    
-    tracing::debug!(
-        endpoint = "/identify",
-        browser = ?ua_info.browser,
-        os = ?ua_info.os,
-        device = ?ua_info.device,
-        "User-Agent enrichment complete"
-    );
+    // Step 5: Run the enrichment pipeline
+    let mut enrichment_context = EnrichmentContext::new("/identify", client_ip, &user_agent, &params);
+    app_state.enrichment.run(&mut event, &mut enrichment_context).await;
 
-    // Step 6: Enrich with GeoIP lookup
-    tracing::debug!(
-        endpoint = "/identify",
-        client_ip = %client_ip,
-        "Enriching with GeoIP lookup"
-    );
-    if let Some(geoip) = &app_state.geoip_lookup {
-        let geo_location = geoip.lookup(client_ip);
-        event.country = geo_location.country.clone();
-        event.region = geo_location.region.clone();
-        event.city = geo_location.city.clone();
-        event.latitude = geo_location.latitude;
-        event.longitude = geo_location.longitude;
-        
-        tracing::debug!(
+    // Step 6: Apply enrichment drop decisions and pre-sink filter rules
+    if let Some(reason) = &enrichment_context.drop_reason {
+        tracing::info!(
             endpoint = "/identify",
-            country = ?geo_location.country,
-            city = ?geo_location.city,
-            "GeoIP enrichment complete"
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by enrichment"
         );
-    } else {
-        tracing::debug!("GeoIP lookup skipped (not configured)");
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
+    }
+    let ua_info = enrichment_context.user_agent_info.unwrap_or_default();
+    let filter_context = FilterContext {
+        client_ip,
+        user_agent: &ua_info,
+    };
+    if let Some(reason) = app_state.event_filter.evaluate(&event, &filter_context) {
+        tracing::info!(
+            endpoint = "/identify",
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by filter"
+        );
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
     }
 
     // Step 7: Send to streaming service
@@ -467,19 +830,20 @@ pub async fn identify_handler(
         event_id = ?event.id,
         "Sending event to streaming service"
     );
-    app_state
-        .streaming_service
-        .send_event(&event)
-        .await
-        .map_err(|e| {
-            tracing::error!(
-                endpoint = "/identify",
-                event_id = ?event.id,
-                error = %e,
-                "Failed to send event to streaming service"
-            );
-            ApiError::StreamingError(e)
-        })?;
+    let started = Instant::now();
+    let sent = app_state.streaming_service.send_event(&event).await;
+    app_state.stage_metrics.observe(STAGE_SEND, started.elapsed());
+    sent.map_err(|e| {
+        tracing::error!(
+            endpoint = "/identify",
+            event_id = ?event.id,
+            error = %e,
+            "Failed to send event to streaming service"
+        );
+        ingest.set_outcome(IngestOutcome::Failed);
+        ApiError::StreamingError(e)
+    })?;
+    ingest.set_outcome(IngestOutcome::Accepted);
     
     tracing::info!(
         endpoint = "/identify",
@@ -516,9 +880,10 @@ pub fn validate_update_params(params: &HashMap<String, String>) -> Result<(), St
 /// 1. Extracts and merges parameters from query string and form body
 /// 2. Validates required fields (id)
 /// 3. Extracts duration and scroll_depth parameters
-/// 4. Transforms parameters into structured AnalyticsEvent
-/// 5. Enriches with User-Agent parsing
-/// 6. Enriches with GeoIP lookup
+/// 4. Builds an update event carrying only the target id and the changed fields,
+///    streamed as a compact `UpdateRecord` (operation=update)
+/// 5. Runs the configured enrichment pipeline (User-Agent, GeoIP, ...)
+/// 6. Drops events matching the configured filter rules (still HTTP 200)
 /// 7. Sends to streaming service
 /// 8. Returns HTTP 200 on success, 400 on validation error, 500 on streaming error
 ///
@@ -526,29 +891,55 @@ pub fn validate_update_params(params: &HashMap<String, String>) -> Result<(), St
 /// Requirements 3.1, 3.2, 3.3, 3.4, 3.5, 3.6, 3.7
 pub async fn update_handler(
     method: Method,
-    Query(query_params): Query<HashMap<String, String>>,
+    Query(query_params): Query<Vec<(String, String)>>,
     headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
     State(app_state): State<AppState>,
-    body: Option<Form<HashMap<String, String>>>,
+    body: Option<Form<Vec<(String, String)>>>,
 ) -> Result<StatusCode, ApiError> {
     // Step 1: Merge query and form parameters
+    let received_at = chrono::Utc::now().timestamp_millis();
     let form_params = body.map(|f| f.0).unwrap_or_default();
-    let params = merge_params(method.clone(), query_params, form_params);
+    if let Some(audit_log) = &app_state.audit_log {
+        audit_log.record("/update", &method, &headers, &query_params, &form_params);
+    }
+    let mut repeated = repeated_params(&method, &query_params, &form_params);
+    let mut params = merge_params(
+        method.clone(),
+        query_params.into_iter().collect(),
+        form_params.into_iter().collect(),
+    );
     
     // Log incoming request with sanitized parameters
     // Validates: Requirement 10.3
     tracing::info!(
         endpoint = "/update",
         method = %method,
-        client_ip = %addr.ip(),
+        peer_ip = %addr.ip(),
         event_id = params.get("id").map(|s| s.as_str()),
         param_count = params.len(),
         "Incoming update request"
     );
 
-    // Step 2: Validate required fields
-    validate_update_params(&params).map_err(|e| {
+    // Count the request by project and outcome; it counts as rejected until it gets further
+    let mut ingest = app_state.ingest_metrics.request("/update");
+
+    // Expand the encoded payload parameter, check the project's access rules, then drop
+    // parameters the project does not accept
+    expand_payload(&app_state, "/update", &mut params)?;
+    ingest.set_project(params.get("project").map(|s| s.as_str()));
+    authorize_project(&app_state, "/update", &headers, &params)?;
+    apply_field_policy(&app_state, "/update", &mut params, &mut repeated);
+
+    // Step 2: Validate required fields and typed parameter values
+    let started = Instant::now();
+    let validated = validate_update_params(&params)
+        .and_then(|_| validate_typed_params(&params, &app_state.config.parameters))
+        .and_then(|_| validate_repeated_params(&repeated, &app_state.config.parameters))
+        .and_then(|_| validate_commerce_params(&params))
+        .and_then(|_| validate_root_collisions(&params, &app_state.config.parameters));
+    app_state.stage_metrics.observe(STAGE_VALIDATION, started.elapsed());
+    validated.map_err(|e| {
         tracing::warn!(
             endpoint = "/update",
             error = %e,
@@ -559,64 +950,50 @@ pub async fn update_handler(
 
     // Step 3: Extract User-Agent and client IP
     let user_agent = extract_user_agent(&headers);
-    let client_ip = addr.ip();
+    let client_ip = extract_client_ip(&app_state.client_ip_resolver, addr, &headers, &params);
 
-    // Step 4: Transform parameters into structured event
-    // For update events, set event type to "update" if not provided
-    let mut params_with_event = params.clone();
-    if !params_with_event.contains_key("event") {
-        params_with_event.insert("event".to_string(), "update".to_string());
-    }
-    
+    // Step 4: Build the update event (target id and changed fields only)
+    // It is streamed as a compact update record rather than a full event
     tracing::debug!(
         endpoint = "/update",
         "Transforming parameters"
     );
-    let mut event = transform_params(params_with_event);
+    let started = Instant::now();
+    let mut event = transform_update_params(&params, &app_state.config.parameters);
+    event.received_at = Some(received_at);
+    app_state.transformer.post_process(&mut event, &params);
+    app_state.stage_metrics.observe(STAGE_TRANSFORMATION, started.elapsed());
+    app_state.enrichment.record(&mut event, "schema", "skipped");
 
-    // Step 5: Enrich with User-Agent parsing
-    tracing::debug!(
-        endpoint = "/update",
-        user_agent = %user_agent,
-        "Enriching with User-Agent parsing"
-    );
-    let ua_info = app_state.user_agent_parser.parse(&user_agent);
-    event.browser = ua_info.browser.clone();
-    event.browser_version = ua_info.browser_version.clone();
-    event.os = ua_info.os.clone();
-    event.os_version = ua_info.os_version.clone();
-    event.device = ua_info.device.clone();
-    
-    tracing::debug!(
-        endpoint = "/update",
-        browser = ?ua_info.browser,
-        os = ?ua_info.os,
-        device = ?ua_info.device,
-        "User-Agent enrichment complete"
-    );
+    // Step 5: Run the enrichment pipeline
+    let mut enrichment_context = EnrichmentContext::new("/update", client_ip, &user_agent, &params);
+    app_state.enrichment.run(&mut event, &mut enrichment_context).await;
 
-    // Step 6: Enrich with GeoIP lookup
-    tracing::debug!(
-        endpoint = "/update",
-        client_ip = %client_ip,
-        "Enriching with GeoIP lookup"
-    );
-    if let Some(geoip) = &app_state.geoip_lookup {
-        let geo_location = geoip.lookup(client_ip);
-        event.country = geo_location.country.clone();
-        event.region = geo_location.region.clone();
-        event.city = geo_location.city.clone();
-        event.latitude = geo_location.latitude;
-        event.longitude = geo_location.longitude;
-        
-        tracing::debug!(
+    // Step 6: Apply enrichment drop decisions and pre-sink filter rules
+    if let Some(reason) = &enrichment_context.drop_reason {
+        tracing::info!(
             endpoint = "/update",
-            country = ?geo_location.country,
-            city = ?geo_location.city,
-            "GeoIP enrichment complete"
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by enrichment"
         );
-    } else {
-        tracing::debug!("GeoIP lookup skipped (not configured)");
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
+    }
+    let ua_info = enrichment_context.user_agent_info.unwrap_or_default();
+    let filter_context = FilterContext {
+        client_ip,
+        user_agent: &ua_info,
+    };
+    if let Some(reason) = app_state.event_filter.evaluate(&event, &filter_context) {
+        tracing::info!(
+            endpoint = "/update",
+            event_id = ?event.id,
+            reason = %reason,
+            "Event dropped by filter"
+        );
+        ingest.set_outcome(IngestOutcome::Dropped);
+        return Ok(StatusCode::OK);
     }
 
     // Step 7: Send to streaming service
@@ -625,19 +1002,20 @@ pub async fn update_handler(
         event_id = ?event.id,
         "Sending event to streaming service"
     );
-    app_state
-        .streaming_service
-        .send_event(&event)
-        .await
-        .map_err(|e| {
-            tracing::error!(
-                endpoint = "/update",
-                event_id = ?event.id,
-                error = %e,
-                "Failed to send event to streaming service"
-            );
-            ApiError::StreamingError(e)
-        })?;
+    let started = Instant::now();
+    let sent = app_state.streaming_service.send_event(&event).await;
+    app_state.stage_metrics.observe(STAGE_SEND, started.elapsed());
+    sent.map_err(|e| {
+        tracing::error!(
+            endpoint = "/update",
+            event_id = ?event.id,
+            error = %e,
+            "Failed to send event to streaming service"
+        );
+        ingest.set_outcome(IngestOutcome::Failed);
+        ApiError::StreamingError(e)
+    })?;
+    ingest.set_outcome(IngestOutcome::Accepted);
     
     tracing::info!(
         endpoint = "/update",
@@ -649,6 +1027,175 @@ pub async fn update_handler(
     Ok(StatusCode::OK)
 }
 
+/// Handler for /health endpoint (liveness)
+///
+/// Always returns HTTP 200 while the process is serving requests.
+pub async fn health_handler() -> impl IntoResponse {
+    (StatusCode::OK, axum::Json(json!({ "status": "ok" })))
+}
+
+/// Handler for /ready endpoint (readiness)
+///
+/// Returns the cached result of the last background streaming health probe:
+/// HTTP 200 if the streaming service was reachable, HTTP 503 otherwise.
+/// The probe result is cached so readiness checks never block on the broker.
+pub async fn ready_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    let status = app_state.health_monitor.status();
+    let code = if status.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        code,
+        axum::Json(json!({
+            "status": if status.healthy { "ready" } else { "unavailable" },
+            "streaming": status,
+        })),
+    )
+}
+
+/// Handler for /health/details endpoint
+///
+/// Reports each component (streaming sink, fallback file, sink queue, GeoIP databases,
+/// session store) as healthy, degraded or unhealthy, with the worst state as the
+/// overall `status`. Components that are not configured are left out. Returns HTTP 503
+/// when a component is unhealthy, HTTP 200 otherwise.
+pub async fn health_details_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    let config = &app_state.config;
+    let health_check = &config.streaming.health_check;
+    let mut components = BTreeMap::new();
+
+    components.insert(
+        "streaming",
+        streaming_health(config.streaming.service_type.as_str(), &app_state.health_monitor.status()),
+    );
+    if let (Some(fallback), Some(breaker)) = (&config.streaming.fallback, app_state.streaming_service.circuit_breaker()) {
+        components.insert("fallback", fallback_health(breaker, Path::new(&fallback.path)));
+    }
+    if let Some(depth) = app_state.streaming_service.queue_depth() {
+        components.insert("queue", queue_health(depth, health_check.max_queue_depth));
+    }
+    if config.enrichment.pipeline.contains(&EnricherKind::Geoip) && app_state.features.stage_enabled("geoip") {
+        components.insert(
+            "geoip",
+            geoip_health(app_state.geoip_lookup.as_deref(), config.geoip.max_age_days, chrono::Utc::now()),
+        );
+    }
+    if let (Some(store), Some(session)) = (app_state.enrichment.session_store(), &config.session) {
+        if app_state.features.stage_enabled("session") {
+            let timeout = Duration::from_secs(health_check.timeout_secs);
+            components.insert("session", session_health(store, session.store.as_str(), timeout).await);
+        }
+    }
+
+    let report = HealthReport::new(components);
+    let code = if report.status == ComponentState::Unhealthy {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, axum::Json(report))
+}
+
+/// Handler for /metrics endpoint
+///
+/// Renders ingest request counts by project, endpoint and outcome, processing stage
+/// durations, and streaming sink delivery metrics (in-flight sends, client queue depth, delivery latency, errors by
+/// kind, payload sizes) labeled by sink and topic, in the Prometheus text exposition format.
+pub async fn metrics_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    let queue_depth = app_state.streaming_service.queue_depth();
+    let mut body = app_state.ingest_metrics.render_prometheus();
+    body.push_str(&app_state.stage_metrics.render_prometheus());
+    body.push_str(&app_state.sink_metrics.render_prometheus(queue_depth));
+    (
+        StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    )
+}
+
+/// Handler for /admin/config endpoint
+///
+/// Returns the effective configuration (defaults included, secrets masked) as JSON.
+/// Requires one of `server.admin_api_keys` in the X-Api-Key header (HTTP 401 otherwise);
+/// the route is only served when admin keys are configured.
+pub async fn config_handler(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(e) = authorize_admin(&app_state, &headers) {
+        return e.into_response();
+    }
+    (StatusCode::OK, axum::Json(effective_config(&app_state.config))).into_response()
+}
+
+/// Handler for /admin/runtime endpoint
+///
+/// Returns uptime, tokio runtime statistics (workers, alive tasks, global queue depth,
+/// busy time, scheduler delay) and process memory, threads, file descriptors and CPU
+/// usage as JSON, for capacity planning and leak detection. Requires an admin key like
+/// /admin/config.
+pub async fn runtime_stats_handler(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(e) = authorize_admin(&app_state, &headers) {
+        return e.into_response();
+    }
+    (StatusCode::OK, axum::Json(app_state.runtime_stats.snapshot())).into_response()
+}
+
+/// Handler for /admin/settings endpoint
+///
+/// GET returns the runtime-tunable settings and the journal of changes. PATCH applies
+/// the JSON document in the body (e.g. `{"logging": {"level": "debug"}}`) and returns
+/// the journal entry; with `?persist=true` the change is also written to
+/// `server.runtime_settings_path`. Requires an admin key like /admin/config.
+pub async fn settings_handler(
+    State(app_state): State<AppState>,
+    method: Method,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    if let Err(e) = authorize_admin(&app_state, &headers) {
+        return e.into_response();
+    }
+    if method != Method::PATCH {
+        let body = json!({
+            "settings": app_state.runtime.settings(),
+            "journal": app_state.runtime.journal(),
+        });
+        return (StatusCode::OK, axum::Json(body)).into_response();
+    }
+
+    let changes: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(changes) => changes,
+        Err(e) => return ApiError::ValidationError(format!("Invalid JSON body: {}", e)).into_response(),
+    };
+    let persist = params.get("persist").is_some_and(|value| value == "true");
+    match app_state.runtime.update(changes, persist) {
+        Ok(change) => (StatusCode::OK, axum::Json(change)).into_response(),
+        Err(e @ RuntimeError::Persist(_)) => ApiError::InternalError(e.to_string()).into_response(),
+        Err(e) => ApiError::ValidationError(e.to_string()).into_response(),
+    }
+}
+
+/// Require one of `server.admin_api_keys` in the X-Api-Key header
+fn authorize_admin(app_state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let authorized = headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|key| {
+            app_state
+                .config
+                .server
+                .admin_api_keys
+                .iter()
+                .any(|allowed| constant_time_eq(allowed.as_bytes(), key.trim().as_bytes()))
+        });
+    if authorized {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("Invalid or missing API key".to_string()))
+    }
+}
 
 #[cfg(test)]
 mod tests;
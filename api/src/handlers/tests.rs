@@ -43,29 +43,126 @@ mod tests {
         }
     }
 
+    // Streaming service that records every event it receives
+    #[derive(Default)]
+    struct CapturingService {
+        events: std::sync::Mutex<Vec<AnalyticsEvent>>,
+    }
+
+    impl CapturingService {
+        fn events(&self) -> Vec<AnalyticsEvent> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl StreamingService for CapturingService {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
     // Helper function to create a test config
     fn create_test_config() -> Config {
         Config {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8080,
+                ..Default::default()
             },
             streaming: StreamingConfig {
                 service_type: StreamingServiceType::Kafka,
                 kafka: Some(KafkaConfig {
                     brokers: vec!["localhost:9092".to_string()],
                     topic: "analytics".to_string(),
+                    ..Default::default()
                 }),
                 kinesis: None,
                 pulsar: None,
+                ..Default::default()
             },
             geoip: GeoIpConfig {
                 database_path: "/path/to/geoip.mmdb".to_string(),
+                ..Default::default()
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
+                ..Default::default()
             },
+            ..Default::default()
+        }
+    }
+
+    // Client address requests are sent from unless a test names another peer
+    const CLIENT_ADDR: &str = "203.0.113.1:12345";
+
+    // AppState over a CapturingService, for tests that inspect the streamed events
+    fn capturing_state(config: Config) -> (Arc<CapturingService>, AppState) {
+        let service = Arc::new(CapturingService::default());
+        let app_state = AppState::new_for_testing(service.clone(), Arc::new(WootheeParser::new()), Arc::new(config));
+        (service, app_state)
+    }
+
+    // Header map from (name, value) pairs
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    // /track/ parameters for project "test", event "pageview" and a fixed timestamp,
+    // overridden or extended by `params`; an empty value removes the parameter
+    fn track_params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        let mut merged = vec![
+            ("project".to_string(), "test".to_string()),
+            ("event".to_string(), "pageview".to_string()),
+            ("timestamp".to_string(), "1700000000000".to_string()),
+        ];
+        for (key, value) in params {
+            merged.retain(|(existing, _)| existing != key);
+            if !value.is_empty() {
+                merged.push((key.to_string(), value.to_string()));
+            }
         }
+        merged
+    }
+
+    // Send one /track/ request from `peer`
+    async fn track_from(
+        app_state: &AppState,
+        peer: &str,
+        params: &[(&str, &str)],
+        headers: HeaderMap,
+    ) -> Result<StatusCode, ApiError> {
+        track_handler(
+            Method::GET,
+            Query(track_params(params)),
+            headers,
+            ConnectInfo(peer.parse().unwrap()),
+            State(app_state.clone()),
+            None,
+        )
+        .await
+    }
+
+    // Send one /track/ request from CLIENT_ADDR
+    async fn track(app_state: &AppState, params: &[(&str, &str)], headers: HeaderMap) -> Result<StatusCode, ApiError> {
+        track_from(app_state, CLIENT_ADDR, params, headers).await
+    }
+
+    // Send one /track/ request to a fresh AppState built from `config` and return the
+    // event it streamed
+    async fn track_captured(config: Config, params: &[(&str, &str)], headers: HeaderMap) -> AnalyticsEvent {
+        let (service, app_state) = capturing_state(config);
+        track(&app_state, params, headers).await.unwrap();
+        service.events().remove(0)
     }
 
     #[test]
@@ -322,6 +419,26 @@ mod tests {
         assert_eq!(result.get("u_email"), Some(&"user@example.com".to_string()));
     }
 
+    #[test]
+    fn test_repeated_params() {
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let query_params = pairs(&[("e_tag", "a"), ("e_tag", "b"), ("e_color", "red"), ("e_color", "blue")]);
+        let form_params = pairs(&[("e_color", "green"), ("e_size", "s"), ("e_size", "m")]);
+
+        let get = repeated_params(&Method::GET, &query_params, &form_params);
+        assert_eq!(get.len(), 2);
+        assert_eq!(get["e_tag"], vec!["a", "b"]);
+
+        // Form values replace the query values of the same parameter
+        let post = repeated_params(&Method::POST, &query_params, &form_params);
+        assert_eq!(post.len(), 2);
+        assert_eq!(post["e_tag"], vec!["a", "b"]);
+        assert_eq!(post["e_size"], vec!["s", "m"]);
+        assert!(!post.contains_key("e_color"));
+    }
+
     // Tests for validation functions
     // Validates: Requirements 1.6, 12.6
 
@@ -656,4 +773,641 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    // Tests for /ready endpoint
+
+    #[tokio::test]
+    async fn test_ready_handler_reflects_cached_health() {
+        let streaming_service: Arc<dyn StreamingService> = Arc::new(MockStreamingService::new_failing());
+        let user_agent_parser: Arc<dyn UserAgentParser> = Arc::new(WootheeParser::new());
+        let app_state = AppState::new_for_testing(
+            streaming_service,
+            user_agent_parser,
+            Arc::new(create_test_config()),
+        );
+
+        // Before any probe the service is assumed ready
+        let response = ready_handler(State(app_state.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A failed probe is cached and reported as 503
+        struct Unreachable;
+        #[async_trait]
+        impl StreamingService for Unreachable {
+            async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+                Ok(())
+            }
+            async fn health_check(&self) -> Result<(), StreamingError> {
+                Err(StreamingError::HealthCheckError("down".to_string()))
+            }
+        }
+        app_state
+            .health_monitor
+            .probe(&Unreachable, std::time::Duration::from_secs(1))
+            .await;
+
+        let response = ready_handler(State(app_state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    // Tests for /health/details endpoint
+
+    #[tokio::test]
+    async fn test_health_details_reports_each_component() {
+        let app_state = AppState::new_for_testing(
+            Arc::new(MockStreamingService::new_failing()),
+            Arc::new(WootheeParser::new()),
+            Arc::new(create_test_config()),
+        );
+
+        let body = |response: Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let response = health_details_handler(State(app_state.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = body(response).await;
+        assert_eq!(report["components"]["streaming"]["status"], "healthy");
+        assert_eq!(report["components"]["streaming"]["sink"], "kafka");
+        assert!(report["checked_at"].is_string());
+
+        struct Unreachable;
+        #[async_trait]
+        impl StreamingService for Unreachable {
+            async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+                Ok(())
+            }
+            async fn health_check(&self) -> Result<(), StreamingError> {
+                Err(StreamingError::HealthCheckError("down".to_string()))
+            }
+        }
+        app_state
+            .health_monitor
+            .probe(&Unreachable, std::time::Duration::from_secs(1))
+            .await;
+
+        let response = health_details_handler(State(app_state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report = body(response).await;
+        assert_eq!(report["status"], "unhealthy");
+        assert_eq!(report["components"]["streaming"]["status"], "unhealthy");
+        assert_eq!(report["components"]["streaming"]["consecutive_failures"], 1);
+    }
+
+    // Tests for pre-sink filtering
+
+    #[tokio::test]
+    async fn test_track_handler_drops_filtered_events() {
+        let mut config = create_test_config();
+        config.filters.drop_event_names = vec!["debug_*".to_string()];
+        let (service, app_state) = capturing_state(config);
+
+        let status = track(&app_state, &[("event", "debug_click")], HeaderMap::new()).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(service.events().is_empty());
+
+        track(&app_state, &[], HeaderMap::new()).await.unwrap();
+        assert_eq!(service.events().len(), 1);
+
+        // Both requests are counted for the project, by outcome
+        let counts = app_state.ingest_metrics.counts();
+        assert!(counts.contains(&("test".to_string(), "/track/", IngestOutcome::Dropped, 1)));
+        assert!(counts.contains(&("test".to_string(), "/track/", IngestOutcome::Accepted, 1)));
+    }
+
+    // Tests for /metrics endpoint
+
+    #[tokio::test]
+    async fn test_metrics_handler_reports_delivery_errors() {
+        let app_state = AppState::new_for_testing(
+            Arc::new(MockStreamingService::new_failing()),
+            Arc::new(WootheeParser::new()),
+            Arc::new(create_test_config()),
+        );
+
+        let event = AnalyticsEvent::default();
+        assert!(app_state.streaming_service.send_event(&event).await.is_err());
+
+        let response = metrics_handler(State(app_state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("analytics_sink_errors_total{sink=\"kafka\",kind=\"send\"} 1"));
+        assert!(text.contains("analytics_sink_in_flight{sink=\"kafka\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_extracts_campaign() {
+        let mut config = create_test_config();
+        config.campaign.aliases.insert("source".to_string(), vec!["ref".to_string()]);
+
+        let event = track_captured(
+            config,
+            &[
+                ("utm_medium", "email"),
+                ("url", "https://example.com/?ref=newsletter&utm_campaign=launch"),
+            ],
+            HeaderMap::new(),
+        )
+        .await;
+
+        let campaign = event.campaign.unwrap();
+        assert_eq!(campaign.source.as_deref(), Some("newsletter"));
+        assert_eq!(campaign.medium.as_deref(), Some("email"));
+        assert_eq!(campaign.campaign.as_deref(), Some("launch"));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_decodes_payload() {
+        let mut config = create_test_config();
+        config.parameters.payload.enabled = true;
+        let (service, app_state) = capturing_state(config);
+        // base64 of {"event":"purchase","e_price":19.99}
+        let payload = "eyJldmVudCI6InB1cmNoYXNlIiwiZV9wcmljZSI6MTkuOTl9";
+
+        track(&app_state, &[("event", ""), ("payload", payload)], HeaderMap::new()).await.unwrap();
+        let event = service.events()[0].clone();
+        assert_eq!(event.event, "purchase");
+        assert_eq!(event.event_param.unwrap().params.get("price"), Some(&serde_json::json!("19.99")));
+
+        let result = track(&app_state, &[("event", ""), ("payload", "not base64!")], HeaderMap::new()).await;
+        assert!(matches!(result, Err(ApiError::ValidationError(msg)) if msg == "Invalid payload: not valid base64"));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_uses_forwarded_client_ip_from_trusted_proxy() {
+        let mut config = create_test_config();
+        config.server.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        config.filters.drop_ip_ranges = vec!["198.51.100.0/24".to_string()];
+        let (service, app_state) = capturing_state(config);
+        let forwarded = || headers(&[("x-forwarded-for", "198.51.100.7, 10.0.0.2")]);
+
+        // Via the trusted proxy, the forwarded client IP is filtered
+        track_from(&app_state, "10.0.0.1:12345", &[], forwarded()).await.unwrap();
+        assert!(service.events().is_empty());
+
+        // From an untrusted peer the header is ignored
+        track(&app_state, &[], forwarded()).await.unwrap();
+        assert_eq!(service.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_adds_ip_hash() {
+        let mut config = create_test_config();
+        config.ip_hash = Some(crate::config::IpHashConfig {
+            salt: "secret".to_string(),
+            rotate_daily: false,
+        });
+
+        let event = track_captured(config, &[], HeaderMap::new()).await;
+
+        let client: std::net::SocketAddr = CLIENT_ADDR.parse().unwrap();
+        let expected = crate::enrichment::IpHasher::new("secret", false).hash(client.ip());
+        assert_eq!(event.ip_hash.as_deref(), Some(expected.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_resolves_client_timezone() {
+        // 2024-07-15T12:00:00Z, during daylight saving time in New York
+        let params = [("timestamp", "1721044800000"), ("tz", "America/New_York")];
+
+        let event = track_captured(create_test_config(), &params, HeaderMap::new()).await;
+
+        assert_eq!(event.timezone.as_deref(), Some("America/New_York"));
+        assert_eq!(event.utc_offset_minutes, Some(-240));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_respects_disabled_enrichers() {
+        let mut config = create_test_config();
+        config.enrichment.pipeline = vec![crate::config::EnricherKind::Campaign];
+        let user_agent = headers(&[(
+            "user-agent",
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+        )]);
+
+        let event = track_captured(config, &[("utm_source", "newsletter")], user_agent).await;
+
+        assert!(event.campaign.is_some());
+        assert_eq!(event.browser, None);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_records_stage_durations() {
+        let mut config = create_test_config();
+        config.enrichment.pipeline = vec![crate::config::EnricherKind::Campaign];
+        let (_service, app_state) = capturing_state(config);
+
+        track(&app_state, &[], HeaderMap::new()).await.unwrap();
+
+        let stages: Vec<String> = app_state.stage_metrics.stages().into_iter().map(|(stage, _)| stage).collect();
+        assert_eq!(stages, vec!["enrichment.campaign", "send", "transformation", "validation"]);
+        assert!(app_state.stage_metrics.stages().iter().all(|(_, histogram)| histogram.count() == 1));
+
+        let response = metrics_handler(State(app_state)).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("analytics_stage_duration_seconds_count{stage=\"validation\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_adds_fingerprint() {
+        let mut config = create_test_config();
+        config.fingerprint = Some(crate::config::FingerprintConfig {
+            salt: "secret".to_string(),
+            ..Default::default()
+        });
+        let (service, app_state) = capturing_state(config);
+        let params = [("screen", "1920x1080"), ("language", "en-US")];
+
+        for peer in ["203.0.113.1:12345", "203.0.113.99:23456"] {
+            let user_agent = headers(&[("user-agent", "Mozilla/5.0 Firefox/121.0")]);
+            track_from(&app_state, peer, &params, user_agent).await.unwrap();
+        }
+
+        let events = service.events();
+        assert!(events[0].fingerprint.is_some());
+        assert_eq!(events[0].fingerprint, events[1].fingerprint);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_assigns_sessions() {
+        let mut config = create_test_config();
+        config.session = Some(crate::config::SessionConfig {
+            store: crate::config::SessionStoreType::Memory,
+            ..Default::default()
+        });
+        let (service, app_state) = capturing_state(config);
+
+        for cookie in ["visitor-1", "visitor-1", "visitor-2"] {
+            track(&app_state, &[("cookie", cookie)], HeaderMap::new()).await.unwrap();
+        }
+
+        let events = service.events();
+        assert_eq!(events[0].session_start, Some(true));
+        assert_eq!(events[1].session_start, Some(false));
+        assert_eq!(events[1].session_event_index, Some(2));
+        assert_eq!(events[0].session_id, events[1].session_id);
+        assert_eq!(events[2].session_start, Some(true));
+        assert_ne!(events[0].session_id, events[2].session_id);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_cleans_profile_email() {
+        let mut config = create_test_config();
+        config.enrichment.pipeline = vec![crate::config::EnricherKind::Email];
+        let (service, app_state) = capturing_state(config);
+
+        for email in [" Jane.Doe@GMail.com ", "not-an-email"] {
+            track(&app_state, &[("event", "signup"), ("u_email", email)], HeaderMap::new()).await.unwrap();
+        }
+
+        let events = service.events();
+        let profile = events[0].profile.as_ref().unwrap();
+        assert_eq!(profile.properties.get("email").and_then(|v| v.as_str()), Some("jane.doe@gmail.com"));
+        assert_eq!(profile.email_domain.as_deref(), Some("gmail.com"));
+        assert_eq!(profile.email_free_provider, Some(true));
+        assert_eq!(profile.email_valid, Some(true));
+
+        let profile = events[1].profile.as_ref().unwrap();
+        assert_eq!(profile.email_valid, Some(false));
+        assert_eq!(profile.email_domain, None);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_keeps_bot_classification() {
+        let user_agent = headers(&[(
+            "user-agent",
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+        )]);
+
+        let event = track_captured(create_test_config(), &[], user_agent).await;
+
+        assert_eq!(event.bot_name.as_deref(), Some("Googlebot"));
+        assert_eq!(event.bot_category.as_deref(), Some("search_engine"));
+        assert_eq!(event.device, None);
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_sets_envelope_timestamps() {
+        let before = chrono::Utc::now().timestamp_millis();
+        let event = track_captured(create_test_config(), &[("sent_at", "1700000060000")], HeaderMap::new()).await;
+
+        assert_eq!(event.timestamp, 1700000000000);
+        assert_eq!(event.sent_at, Some(1700000060000));
+        assert!(event.received_at.is_some_and(|received_at| received_at >= before));
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_uses_custom_transformer() {
+        use crate::config::ParameterConfig;
+        use crate::transformer::{RepeatedParams, Transformer};
+
+        struct Uppercase;
+
+        impl Transformer for Uppercase {
+            fn transform(
+                &self,
+                params: HashMap<String, String>,
+                _repeated: &RepeatedParams,
+                _config: &ParameterConfig,
+            ) -> AnalyticsEvent {
+                AnalyticsEvent {
+                    project: params.get("project").cloned(),
+                    event: params.get("event").map(|e| e.to_uppercase()).unwrap_or_default(),
+                    ..Default::default()
+                }
+            }
+
+            fn post_process(&self, event: &mut AnalyticsEvent, _params: &HashMap<String, String>) {
+                event.attributes.insert("transformer".to_string(), serde_json::json!("uppercase"));
+            }
+        }
+
+        let (service, app_state) = capturing_state(create_test_config());
+        let app_state = app_state.with_transformer(Arc::new(Uppercase));
+
+        track(&app_state, &[("event", "signup")], HeaderMap::new()).await.unwrap();
+
+        let event = &service.events()[0];
+        assert_eq!(event.event, "SIGNUP");
+        assert_eq!(event.attributes["transformer"], "uppercase");
+        assert!(event.received_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_applies_project_registry() {
+        use crate::config::{ProjectConfig, ProjectSchemaConfig};
+
+        let mut config = create_test_config();
+        config.projects.insert(
+            "shop".to_string(),
+            ProjectConfig {
+                api_keys: vec!["shop-key".to_string()],
+                schema: ProjectSchemaConfig {
+                    required: vec!["cookie".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        config.projects.insert(
+            "blog".to_string(),
+            ProjectConfig {
+                allowed_origins: vec!["https://blog.example.com".to_string()],
+                sample_rate: Some(0.0),
+                ..Default::default()
+            },
+        );
+        let (service, app_state) = capturing_state(config);
+        let shop_key = || headers(&[("x-api-key", "shop-key")]);
+
+        let missing_key = track(&app_state, &[("project", "shop"), ("cookie", "v1")], HeaderMap::new()).await;
+        assert!(matches!(missing_key, Err(ApiError::Unauthorized(_))));
+        let missing_cookie = track(&app_state, &[("project", "shop")], shop_key()).await;
+        assert!(matches!(missing_cookie, Err(ApiError::ValidationError(msg)) if msg == "Missing required field: cookie"));
+        let wrong_origin = track(&app_state, &[("project", "blog")], headers(&[("origin", "https://evil.test")])).await;
+        assert!(matches!(wrong_origin, Err(ApiError::Forbidden(_))));
+
+        // blog samples out every visitor; only the shop event reaches the sink
+        let blog_origin = headers(&[("origin", "https://blog.example.com")]);
+        let sampled_out = track(&app_state, &[("project", "blog")], blog_origin).await;
+        assert_eq!(sampled_out.unwrap(), StatusCode::OK);
+        let accepted = track(&app_state, &[("project", "shop"), ("cookie", "v1")], shop_key()).await;
+        assert_eq!(accepted.unwrap(), StatusCode::OK);
+        let events = service.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].project.as_deref(), Some("shop"));
+    }
+
+    #[tokio::test]
+    async fn test_project_sampling_applies_to_track_only() {
+        use crate::config::{ProjectConfig, ProjectSchemaConfig};
+
+        let mut config = create_test_config();
+        config.projects.insert(
+            "shop".to_string(),
+            ProjectConfig {
+                sample_rate: Some(0.0),
+                schema: ProjectSchemaConfig {
+                    required: vec!["cookie".to_string()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let (service, app_state) = capturing_state(config);
+        let query = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let peer = || ConnectInfo(CLIENT_ADDR.parse().unwrap());
+
+        // /track/ drops every visitor of shop
+        track(&app_state, &[("project", "shop"), ("cookie", "v1")], HeaderMap::new()).await.unwrap();
+        assert!(service.events().is_empty());
+
+        // /identify is checked against the schema but not sampled
+        let identify = |pairs: Vec<(String, String)>| {
+            identify_handler(Method::GET, Query(pairs), HeaderMap::new(), peer(), State(app_state.clone()), None)
+        };
+        let identity = [("project", "shop"), ("timestamp", "1700000000000"), ("u_plan", "pro")];
+        let missing_cookie = identify(query(&identity)).await;
+        assert!(matches!(missing_cookie, Err(ApiError::ValidationError(msg)) if msg == "Missing required field: cookie"));
+        let mut with_cookie = query(&identity);
+        with_cookie.extend(query(&[("cookie", "v1")]));
+        identify(with_cookie).await.unwrap();
+        assert_eq!(service.events().len(), 1);
+
+        // /update is neither sampled nor checked against `required`
+        let update = query(&[("project", "shop"), ("id", "evt-1"), ("duration", "5000")]);
+        update_handler(Method::GET, Query(update), HeaderMap::new(), peer(), State(app_state.clone()), None)
+            .await
+            .unwrap();
+        assert_eq!(service.events().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_config_handler_requires_admin_key_and_masks_secrets() {
+        let mut config = create_test_config();
+        config.server.admin_api_keys = vec!["admin-key".to_string()];
+        config.server.ip_override.api_keys = vec!["sdk-secret".to_string()];
+        let app_state = AppState::new_for_testing(
+            Arc::new(MockStreamingService::new()),
+            Arc::new(WootheeParser::new()),
+            Arc::new(config),
+        );
+
+        let response = config_handler(State(app_state.clone()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "admin-key".parse().unwrap());
+        let response = config_handler(State(app_state), headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(config["server"]["host"], "127.0.0.1");
+        assert_eq!(config["server"]["admin_api_keys"][0], crate::config::REDACTED);
+        assert_eq!(config["server"]["ip_override"]["api_keys"][0], crate::config::REDACTED);
+    }
+
+    #[tokio::test]
+    async fn test_runtime_stats_handler_requires_admin_key() {
+        let mut config = create_test_config();
+        config.server.admin_api_keys = vec!["admin-key".to_string()];
+        let app_state = AppState::new_for_testing(
+            Arc::new(MockStreamingService::new()),
+            Arc::new(WootheeParser::new()),
+            Arc::new(config),
+        );
+
+        let response = runtime_stats_handler(State(app_state.clone()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "admin-key".parse().unwrap());
+        let response = runtime_stats_handler(State(app_state), headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(stats["uptime_secs"].is_u64());
+        assert!(stats["tokio"]["workers"].as_u64().unwrap() >= 1);
+        assert_eq!(stats["tokio"]["scheduler_delay"]["samples"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_settings_handler_changes_filters_at_runtime() {
+        let mut config = create_test_config();
+        config.server.admin_api_keys = vec!["admin-key".to_string()];
+        let service = Arc::new(CapturingService::default());
+        let app_state = AppState::new_for_testing(service.clone(), Arc::new(WootheeParser::new()), Arc::new(config));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "admin-key".parse().unwrap());
+        let settings = |method: Method, headers: HeaderMap, body: &'static str| {
+            settings_handler(
+                State(app_state.clone()),
+                method,
+                Query(HashMap::new()),
+                headers,
+                axum::body::Bytes::from_static(body.as_bytes()),
+            )
+        };
+
+        let response = settings(Method::PATCH, HeaderMap::new(), "{}").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = settings(Method::PATCH, headers.clone(), r#"{"server": {"port": 1}}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = settings(Method::PATCH, headers.clone(), r#"{"filters": {"drop_event_names": ["debug_*"]}}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let status = track_from(&app_state, "203.0.113.7:4000", &[("event", "debug_click")], HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(service.events().is_empty());
+
+        let response = settings(Method::GET, headers, "").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["settings"]["filters"]["drop_event_names"][0], "debug_*");
+        assert_eq!(body["journal"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_handler_emits_update_event() {
+        use crate::transformer::{Operation, UpdateRecord};
+
+        let service = Arc::new(CapturingService::default());
+        let app_state = AppState::new_for_testing(
+            service.clone(),
+            Arc::new(WootheeParser::new()),
+            Arc::new(create_test_config()),
+        );
+
+        let mut params = HashMap::new();
+        params.insert("project".to_string(), "test".to_string());
+        params.insert("id".to_string(), "evt-1".to_string());
+        params.insert("duration".to_string(), "5000".to_string());
+        params.insert("scroll_depth".to_string(), "75".to_string());
+        params.insert("url".to_string(), "https://example.com".to_string());
+
+        update_handler(
+            Method::GET,
+            Query(params.into_iter().collect()),
+            HeaderMap::new(),
+            ConnectInfo("203.0.113.1:12345".parse().unwrap()),
+            State(app_state),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let event = &service.events()[0];
+        assert_eq!(event.operation, Some(Operation::Update));
+        assert_eq!(event.visit.url, None);
+        let record = UpdateRecord::from_event(event).unwrap();
+        assert_eq!(record.id, "evt-1");
+        assert_eq!(record.duration, Some(5000));
+        assert_eq!(record.scroll_depth, Some(75));
+        assert!(record.received_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_handler_records_schema_provenance() {
+        let service = Arc::new(CapturingService::default());
+        let mut config = create_test_config();
+        config.enrichment.provenance = true;
+        config.features.schema_validation = false;
+        let app_state = AppState::new_for_testing(service.clone(), Arc::new(WootheeParser::new()), Arc::new(config.clone()));
+
+        let update = |app_state: AppState| {
+            let mut params = HashMap::new();
+            params.insert("project".to_string(), "test".to_string());
+            params.insert("id".to_string(), "evt-1".to_string());
+            params.insert("duration".to_string(), "5000".to_string());
+            update_handler(
+                Method::GET,
+                Query(params.into_iter().collect()),
+                HeaderMap::new(),
+                ConnectInfo("203.0.113.1:12345".parse().unwrap()),
+                State(app_state),
+                None,
+            )
+        };
+
+        // Updates are never checked against the project schema, whatever the flag
+        update(app_state.clone()).await.unwrap();
+        assert_eq!(service.events()[0].provenance["schema"], "skipped");
+
+        config.features.schema_validation = true;
+        app_state.features.apply(&config.features);
+        update(app_state).await.unwrap();
+        assert_eq!(service.events()[1].provenance["schema"], "skipped");
+    }
+
+    #[tokio::test]
+    async fn test_track_handler_routes_custom_prefixes_and_checks_types() {
+        use crate::config::{PrefixRule, PrefixTarget};
+
+        let mut config = create_test_config();
+        config.parameters.prefixes.push(PrefixRule {
+            prefix: "m_".to_string(),
+            target: PrefixTarget::Object("marketing".to_string()),
+        });
+        let (service, app_state) = capturing_state(config);
+
+        let params = [("event", "signup"), ("m_channel", "social"), ("m_budget:n", "120.5")];
+        track(&app_state, &params, HeaderMap::new()).await.unwrap();
+
+        let events = service.events();
+        let marketing = &events[0].objects["marketing"];
+        assert_eq!(marketing.get("channel"), Some(&serde_json::json!("social")));
+        assert_eq!(marketing.get("budget"), Some(&serde_json::json!(120.5)));
+
+        let result = track(&app_state, &[("m_budget:n", "lots")], HeaderMap::new()).await;
+        assert!(matches!(
+            result,
+            Err(ApiError::ValidationError(msg)) if msg == "Invalid value for m_budget:n: expected a number, got 'lots'"
+        ));
+    }
 }
@@ -0,0 +1,441 @@
+// Component health monitoring
+// This module probes the streaming service in the background and reports the health of each component
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::enrichment::geoip::GeoIpLookup;
+use crate::session::SessionStore;
+use crate::streaming::{CircuitBreaker, CircuitState, StreamingService};
+
+/// Snapshot of the most recent streaming service health probe
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HealthStatus {
+    /// Whether the last probe succeeded (true until the first probe completes)
+    pub healthy: bool,
+    /// Unix timestamp (milliseconds) of the last completed probe, None before the first probe
+    pub last_checked: Option<i64>,
+    /// Error message from the last failed probe
+    pub last_error: Option<String>,
+    /// Number of consecutive failed probes
+    pub consecutive_failures: u32,
+}
+
+impl Default for HealthStatus {
+    fn default() -> Self {
+        HealthStatus {
+            healthy: true,
+            last_checked: None,
+            last_error: None,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Caches streaming service health so request paths never wait on a broker round-trip
+pub struct HealthMonitor {
+    status: RwLock<HealthStatus>,
+}
+
+impl HealthMonitor {
+    /// Create a monitor with no probe results yet
+    pub fn new() -> Self {
+        HealthMonitor {
+            status: RwLock::new(HealthStatus::default()),
+        }
+    }
+
+    /// Get the cached result of the most recent probe
+    pub fn status(&self) -> HealthStatus {
+        self.status
+            .read()
+            .map(|s| s.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether the streaming service was healthy at the last probe
+    pub fn is_healthy(&self) -> bool {
+        self.status().healthy
+    }
+
+    /// Run a single health probe and record its result
+    ///
+    /// # Arguments
+    /// * `service` - Streaming service to probe
+    /// * `timeout` - Maximum time to wait for the probe before recording a failure
+    pub async fn probe(&self, service: &dyn StreamingService, timeout: Duration) -> HealthStatus {
+        let result = match tokio::time::timeout(timeout, service.health_check()).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("health check timed out after {:?}", timeout)),
+        };
+        self.record(result)
+    }
+
+    /// Record a probe result, returning the updated status
+    fn record(&self, result: Result<(), String>) -> HealthStatus {
+        let mut status = match self.status.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        status.last_checked = Some(chrono::Utc::now().timestamp_millis());
+        match result {
+            Ok(()) => {
+                if !status.healthy {
+                    tracing::info!(
+                        failures = status.consecutive_failures,
+                        "Streaming service recovered"
+                    );
+                }
+                status.healthy = true;
+                status.last_error = None;
+                status.consecutive_failures = 0;
+            }
+            Err(error) => {
+                status.consecutive_failures += 1;
+                tracing::warn!(
+                    error = %error,
+                    consecutive_failures = status.consecutive_failures,
+                    "Streaming service health check failed"
+                );
+                status.healthy = false;
+                status.last_error = Some(error);
+            }
+        }
+
+        status.clone()
+    }
+
+    /// Spawn a background task that probes the streaming service periodically
+    ///
+    /// The first probe runs immediately. The task runs until the runtime shuts down.
+    pub fn spawn(
+        self: &Arc<Self>,
+        service: Arc<dyn StreamingService>,
+        interval: Duration,
+        timeout: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                monitor.probe(service.as_ref(), timeout).await;
+            }
+        })
+    }
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State of a component, from best to worst
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentState {
+    /// Working normally
+    Healthy,
+    /// Events are still accepted, but with reduced enrichment or durability
+    Degraded,
+    /// Events cannot be delivered
+    Unhealthy,
+}
+
+/// Health of one component on /health/details
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ComponentHealth {
+    pub status: ComponentState,
+    /// Why the component is not healthy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Component-specific fields (e.g. `queue_depth`)
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+impl ComponentHealth {
+    /// A component in `status` with the fields of the `details` object
+    pub fn new(status: ComponentState, message: Option<String>, details: Value) -> Self {
+        let details = match details {
+            Value::Object(details) => details,
+            _ => Map::new(),
+        };
+        ComponentHealth { status, message, details }
+    }
+
+    pub fn healthy(details: Value) -> Self {
+        Self::new(ComponentState::Healthy, None, details)
+    }
+
+    pub fn degraded(message: impl Into<String>, details: Value) -> Self {
+        Self::new(ComponentState::Degraded, Some(message.into()), details)
+    }
+
+    pub fn unhealthy(message: impl Into<String>, details: Value) -> Self {
+        Self::new(ComponentState::Unhealthy, Some(message.into()), details)
+    }
+}
+
+/// Health of every component, served on /health/details
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HealthReport {
+    /// State of the worst component
+    pub status: ComponentState,
+    /// Unix timestamp (milliseconds) of the report
+    pub checked_at: i64,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+impl HealthReport {
+    pub fn new(components: BTreeMap<&'static str, ComponentHealth>) -> Self {
+        let status = components
+            .values()
+            .map(|component| component.status)
+            .max()
+            .unwrap_or(ComponentState::Healthy);
+        HealthReport {
+            status,
+            checked_at: Utc::now().timestamp_millis(),
+            components,
+        }
+    }
+}
+
+/// Streaming sink health from the last background probe
+pub fn streaming_health(sink: &str, status: &HealthStatus) -> ComponentHealth {
+    let details = json!({
+        "sink": sink,
+        "last_checked": status.last_checked,
+        "consecutive_failures": status.consecutive_failures,
+    });
+    if status.healthy {
+        ComponentHealth::healthy(details)
+    } else {
+        let error = status.last_error.clone().unwrap_or_else(|| "health check failed".to_string());
+        ComponentHealth::unhealthy(error, details)
+    }
+}
+
+/// Fallback sink health: degraded while events are diverted to the fallback file or
+/// stored events wait to be replayed
+pub fn fallback_health(breaker: &CircuitBreaker, path: &Path) -> ComponentHealth {
+    let state = breaker.state();
+    let backlog_bytes = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+    let details = json!({
+        "circuit": state,
+        "path": path.display().to_string(),
+        "backlog_bytes": backlog_bytes,
+    });
+    if state != CircuitState::Closed {
+        ComponentHealth::degraded("primary sink circuit breaker is open, events are written to the fallback file", details)
+    } else if backlog_bytes > 0 {
+        ComponentHealth::degraded("fallback events are waiting to be replayed", details)
+    } else {
+        ComponentHealth::healthy(details)
+    }
+}
+
+/// Sink queue health: degraded above `max_depth` buffered events
+pub fn queue_health(depth: i64, max_depth: Option<u64>) -> ComponentHealth {
+    let details = json!({ "queue_depth": depth, "max_queue_depth": max_depth });
+    match max_depth {
+        Some(max) if depth > 0 && depth as u64 > max => {
+            ComponentHealth::degraded(format!("{} events buffered, more than {}", depth, max), details)
+        }
+        _ => ComponentHealth::healthy(details),
+    }
+}
+
+/// GeoIP health: degraded when the database is not loaded (events are sent without
+/// location) or older than `max_age_days`
+pub fn geoip_health(lookup: Option<&GeoIpLookup>, max_age_days: Option<u64>, now: DateTime<Utc>) -> ComponentHealth {
+    let Some(lookup) = lookup else {
+        return ComponentHealth::degraded("database not loaded, events are sent without location", json!({}));
+    };
+    let databases = lookup.databases();
+    let details = json!({ "databases": databases });
+    let stale: Vec<&str> = databases
+        .iter()
+        .filter(|database| match (database.build_date, max_age_days) {
+            (Some(built), Some(max_age_days)) => (now - built).num_days() > max_age_days as i64,
+            _ => false,
+        })
+        .map(|database| database.path.as_str())
+        .collect();
+    if stale.is_empty() {
+        ComponentHealth::healthy(details)
+    } else {
+        let message = format!("built more than {} days ago: {}", max_age_days.unwrap_or_default(), stale.join(", "));
+        ComponentHealth::degraded(message, details)
+    }
+}
+
+/// Session store health: degraded when unreachable (events are sent without session)
+pub async fn session_health(store: &dyn SessionStore, store_type: &str, timeout: Duration) -> ComponentHealth {
+    let details = json!({ "store": store_type });
+    match tokio::time::timeout(timeout, store.health_check()).await {
+        Ok(Ok(())) => ComponentHealth::healthy(details),
+        Ok(Err(e)) => ComponentHealth::degraded(e.to_string(), details),
+        Err(_) => ComponentHealth::degraded(format!("health check timed out after {:?}", timeout), details),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::StreamingError;
+    use crate::transformer::AnalyticsEvent;
+    use async_trait::async_trait;
+
+    struct ProbeStub {
+        fail: bool,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl StreamingService for ProbeStub {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            tokio::time::sleep(self.delay).await;
+            if self.fail {
+                Err(StreamingError::HealthCheckError("broker unreachable".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_initial_status_is_healthy_and_unchecked() {
+        let monitor = HealthMonitor::new();
+        let status = monitor.status();
+        assert!(status.healthy);
+        assert!(status.last_checked.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_is_cached() {
+        let monitor = HealthMonitor::new();
+        let stub = ProbeStub { fail: true, delay: Duration::ZERO };
+
+        monitor.probe(&stub, Duration::from_secs(1)).await;
+        monitor.probe(&stub, Duration::from_secs(1)).await;
+
+        let status = monitor.status();
+        assert!(!status.healthy);
+        assert_eq!(status.consecutive_failures, 2);
+        assert!(status.last_error.unwrap().contains("broker unreachable"));
+        assert!(status.last_checked.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_resets_failures() {
+        let monitor = HealthMonitor::new();
+        monitor
+            .probe(&ProbeStub { fail: true, delay: Duration::ZERO }, Duration::from_secs(1))
+            .await;
+        monitor
+            .probe(&ProbeStub { fail: false, delay: Duration::ZERO }, Duration::from_secs(1))
+            .await;
+
+        let status = monitor.status();
+        assert!(status.healthy);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slow_probe_times_out() {
+        let monitor = HealthMonitor::new();
+        let stub = ProbeStub { fail: false, delay: Duration::from_secs(5) };
+
+        let status = monitor.probe(&stub, Duration::from_millis(10)).await;
+        assert!(!status.healthy);
+        assert!(status.last_error.unwrap().contains("timed out"));
+    }
+
+    #[test]
+    fn test_report_takes_the_worst_component() {
+        let mut components = BTreeMap::new();
+        components.insert("streaming", streaming_health("kafka", &HealthStatus::default()));
+        components.insert("queue", queue_health(150, Some(100)));
+        let report = HealthReport::new(components.clone());
+        assert_eq!(report.status, ComponentState::Degraded);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "degraded");
+        assert_eq!(json["components"]["queue"]["queue_depth"], 150);
+        assert_eq!(json["components"]["queue"]["message"], "150 events buffered, more than 100");
+        assert_eq!(json["components"]["streaming"]["sink"], "kafka");
+        assert!(json["components"]["streaming"].get("message").is_none());
+
+        let failed = HealthStatus {
+            healthy: false,
+            last_error: Some("broker unreachable".to_string()),
+            ..Default::default()
+        };
+        components.insert("streaming", streaming_health("kafka", &failed));
+        assert_eq!(HealthReport::new(components).status, ComponentState::Unhealthy);
+    }
+
+    #[test]
+    fn test_fallback_health() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fallback.jsonl");
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        assert_eq!(fallback_health(&breaker, &path).status, ComponentState::Healthy);
+
+        std::fs::write(&path, "{}\n").unwrap();
+        let health = fallback_health(&breaker, &path);
+        assert_eq!(health.status, ComponentState::Degraded);
+        assert_eq!(health.details["backlog_bytes"], 3);
+
+        breaker.record_failure();
+        assert_eq!(fallback_health(&breaker, &path).details["circuit"], "open");
+    }
+
+    #[test]
+    fn test_stale_geoip_database_is_degraded() {
+        struct Dated;
+        impl crate::enrichment::geoip::GeoIpBackend for Dated {
+            fn lookup(&self, _ip: std::net::IpAddr) -> crate::enrichment::geoip::GeoLocation {
+                Default::default()
+            }
+
+            fn reload_changed(&self) -> usize {
+                0
+            }
+
+            fn databases(&self) -> Vec<crate::enrichment::geoip::GeoIpDatabase> {
+                vec![crate::enrichment::geoip::GeoIpDatabase {
+                    path: "/data/GeoLite2-City.mmdb".to_string(),
+                    database_type: "GeoLite2-City".to_string(),
+                    build_date: "2024-01-01T00:00:00Z".parse().ok(),
+                }]
+            }
+        }
+
+        let lookup = GeoIpLookup::from_backend(Box::new(Dated));
+        let now: DateTime<Utc> = "2024-03-01T00:00:00Z".parse().unwrap();
+        assert_eq!(geoip_health(Some(&lookup), None, now).status, ComponentState::Healthy);
+        let health = geoip_health(Some(&lookup), Some(30), now);
+        assert_eq!(health.status, ComponentState::Degraded);
+        assert_eq!(health.message.as_deref(), Some("built more than 30 days ago: /data/GeoLite2-City.mmdb"));
+        assert_eq!(health.details["databases"][0]["database_type"], "GeoLite2-City");
+        assert_eq!(geoip_health(None, Some(30), now).status, ComponentState::Degraded);
+    }
+}
@@ -1,9 +1,28 @@
 // Library exports for the Rust Analytics API
 // This allows modules to be tested and used as a library
 
+pub mod access_log;
+pub mod audit;
+pub mod check;
+pub mod cidr;
+pub mod client_ip;
 pub mod config;
 pub mod enrichment;
+pub mod event_names;
+pub mod features;
+pub mod field_policy;
+pub mod filter;
 pub mod handlers;
+pub mod health;
+pub mod listener;
 pub mod logging;
+pub mod metrics;
+pub mod otlp;
+pub mod projects;
+pub mod replay;
+pub mod runtime;
+pub mod runtime_stats;
+pub mod session;
+pub mod statsd;
 pub mod streaming;
 pub mod transformer;
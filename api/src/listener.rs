@@ -0,0 +1,310 @@
+// Listeners
+// This module binds the configured HTTP, HTTPS and Unix socket listeners and serves the router on each
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::connect_info::ConnectInfo;
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+
+use crate::config::{ListenerConfig, ServerConfig};
+
+/// Peer address of requests received on a Unix socket
+///
+/// Socket clients have no IP address, so they get the unspecified address, which is
+/// never a trusted proxy, never looked up in GeoIP and never matches an IP filter.
+pub const UNIX_PEER_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+/// Pause after an accept error that is not about a single connection (e.g. too many
+/// open files), so the loop does not spin while the condition lasts
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// How long in-flight requests may take to complete once shutdown starts
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// A bound listener, ready to serve
+pub enum Listener {
+    /// TCP socket, with TLS for HTTPS listeners
+    Tcp {
+        listener: TcpListener,
+        tls: Option<TlsAcceptor>,
+        name: String,
+    },
+    /// Unix domain socket
+    #[cfg(unix)]
+    Unix {
+        listener: tokio::net::UnixListener,
+        name: String,
+    },
+}
+
+/// Stream accepted by a listener
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Connection for T {}
+
+impl Listener {
+    /// Address or socket path, for logs
+    pub fn name(&self) -> &str {
+        match self {
+            Listener::Tcp { name, .. } => name,
+            #[cfg(unix)]
+            Listener::Unix { name, .. } => name,
+        }
+    }
+
+    /// Wait for the next connection, returning it with its peer address
+    ///
+    /// TLS connections are returned before the handshake, which runs in the
+    /// connection's own task so a slow client cannot stall the accept loop.
+    async fn accept(&self) -> io::Result<(Accepted, SocketAddr)> {
+        match self {
+            Listener::Tcp { listener, tls, .. } => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Accepted::Tcp(stream, tls.clone()), peer))
+            }
+            #[cfg(unix)]
+            Listener::Unix { listener, .. } => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Accepted::Unix(stream), UNIX_PEER_ADDR))
+            }
+        }
+    }
+}
+
+enum Accepted {
+    Tcp(tokio::net::TcpStream, Option<TlsAcceptor>),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl Accepted {
+    /// The stream to serve HTTP on, after the TLS handshake if any
+    async fn into_connection(self) -> io::Result<Box<dyn Connection>> {
+        match self {
+            Accepted::Tcp(stream, None) => Ok(Box::new(stream)),
+            Accepted::Tcp(stream, Some(tls)) => Ok(Box::new(tls.accept(stream).await?)),
+            #[cfg(unix)]
+            Accepted::Unix(stream) => Ok(Box::new(stream)),
+        }
+    }
+}
+
+/// Listeners of `config`: `listeners`, or plain HTTP on `host`:`port` when none are set
+pub fn effective_listeners(config: &ServerConfig) -> Vec<ListenerConfig> {
+    if !config.listeners.is_empty() {
+        return config.listeners.clone();
+    }
+    vec![ListenerConfig::Http {
+        address: format!("{}:{}", config.host, config.port),
+    }]
+}
+
+/// Bind the socket of `config`
+///
+/// HTTPS listeners load their certificate and key here. A stale socket file left at a
+/// Unix listener's path is replaced; any other file there is an error.
+pub async fn bind(config: &ListenerConfig) -> io::Result<Listener> {
+    let name = config.describe();
+    match config {
+        ListenerConfig::Http { address } => Ok(Listener::Tcp {
+            listener: TcpListener::bind(address).await?,
+            tls: None,
+            name,
+        }),
+        ListenerConfig::Https {
+            address,
+            cert_path,
+            key_path,
+        } => {
+            let tls = tls_acceptor(cert_path, key_path)?;
+            Ok(Listener::Tcp {
+                listener: TcpListener::bind(address).await?,
+                tls: Some(tls),
+                name,
+            })
+        }
+        #[cfg(unix)]
+        ListenerConfig::Unix { path, permissions } => {
+            use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+            match std::fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} exists and is not a socket", path),
+                    ))
+                }
+                Err(_) => {}
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            if let Some(mode) = permissions.as_deref().and_then(crate::config::parse_socket_permissions) {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+            }
+            Ok(Listener::Unix { listener, name })
+        }
+        #[cfg(not(unix))]
+        ListenerConfig::Unix { .. } => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform",
+        )),
+    }
+}
+
+/// TLS acceptor offering HTTP/2 and HTTP/1.1 with the given PEM files
+fn tls_acceptor(cert_path: &str, key_path: &str) -> io::Result<TlsAcceptor> {
+    let invalid = |path: &str, e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e));
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert_path, &e))?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(key_path, &e))?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| invalid(cert_path, &e))?;
+    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(tls_config)))
+}
+
+/// Serve `app` on `listener` until `shutdown` changes, then let in-flight requests finish
+///
+/// Requests carry the peer address as `ConnectInfo<SocketAddr>`, like with
+/// `axum::serve`.
+pub async fn serve(listener: Listener, app: Router, mut shutdown: watch::Receiver<bool>) {
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    loop {
+        let (accepted, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if is_connection_error(&e) => continue,
+                Err(e) => {
+                    tracing::warn!(listener = listener.name(), error = %e, "Failed to accept connection");
+                    tokio::select! {
+                        _ = tokio::time::sleep(ACCEPT_ERROR_DELAY) => continue,
+                        _ = shutdown.changed() => break,
+                    }
+                }
+            },
+            _ = shutdown.changed() => break,
+        };
+
+        let builder = builder.clone();
+        let watcher = graceful.watcher();
+        let app = app.clone();
+        let name = listener.name().to_string();
+        tokio::spawn(async move {
+            let connection = match accepted.into_connection().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::debug!(listener = %name, peer = %peer, error = %e, "TLS handshake failed");
+                    return;
+                }
+            };
+            let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                app.clone().call(request)
+            });
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(connection), service);
+            if let Err(e) = watcher.watch(connection.into_owned()).await {
+                tracing::debug!(listener = %name, peer = %peer, error = %e, "Connection closed with an error");
+            }
+        });
+    }
+
+    let name = listener.name().to_string();
+    drop(listener);
+    if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, graceful.shutdown()).await.is_err() {
+        tracing::warn!(listener = %name, "In-flight requests did not finish before the shutdown grace period");
+    }
+}
+
+/// Whether an accept error only concerns the connection being accepted
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_listeners_default_to_host_and_port() {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 9090,
+            ..Default::default()
+        };
+        assert_eq!(
+            effective_listeners(&config),
+            vec![ListenerConfig::Http {
+                address: "127.0.0.1:9090".to_string()
+            }]
+        );
+
+        let listeners = vec![ListenerConfig::Unix {
+            path: "/run/analytics.sock".to_string(),
+            permissions: None,
+        }];
+        let config = ServerConfig {
+            listeners: listeners.clone(),
+            ..config
+        };
+        assert_eq!(effective_listeners(&config), listeners);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_on_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api.sock");
+        let config = ListenerConfig::Unix {
+            path: path.to_str().unwrap().to_string(),
+            permissions: Some("660".to_string()),
+        };
+        let app = Router::new().route(
+            "/peer",
+            axum::routing::get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }),
+        );
+        let listener = bind(&config).await.unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let server = tokio::spawn(serve(listener, app, shutdown_rx));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /peer HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("0.0.0.0"));
+
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+
+        // A stale socket file is replaced on the next bind
+        assert!(bind(&config).await.is_ok());
+    }
+}
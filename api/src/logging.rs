@@ -1,7 +1,24 @@
 // Structured logging module
 // This module sets up tracing with JSON formatting for structured logs
 
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+/// Handle to change the log level of the installed subscriber
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevelHandle {
+    /// Replace the log filter, e.g. with "debug" or "info,api::enrichment=trace"
+    ///
+    /// # Errors
+    /// Returns a message if `level` is not a valid filter or the subscriber is gone
+    pub fn set_level(&self, level: &str) -> Result<(), String> {
+        let filter = EnvFilter::try_new(level).map_err(|e| format!("invalid log level '{}': {}", level, e))?;
+        self.handle.reload(filter).map_err(|e| e.to_string())
+    }
+}
 
 /// Initialize the tracing subscriber with JSON formatting
 ///
@@ -16,10 +33,17 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 /// tracing::info!("Application started");
 /// ```
 pub fn init_logging(log_level: &str) {
+    init_reloadable_logging(log_level);
+}
+
+/// Initialize the tracing subscriber like [`init_logging`], keeping a handle to
+/// change the level later
+pub fn init_reloadable_logging(log_level: &str) -> LogLevelHandle {
     // Create an EnvFilter from the log level
     // This allows filtering logs by level
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(log_level));
+    let (env_filter, handle) = reload::Layer::new(env_filter);
 
     // Set up the JSON formatter
     // This creates structured logs that can be easily parsed
@@ -27,6 +51,8 @@ pub fn init_logging(log_level: &str) {
         .with(env_filter)
         .with(fmt::layer().json())
         .init();
+
+    LogLevelHandle { handle }
 }
 
 #[cfg(test)]
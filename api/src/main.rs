@@ -1,57 +1,198 @@
+mod access_log;
+mod audit;
+mod check;
+mod cidr;
+mod client_ip;
 mod config;
 mod enrichment;
+mod event_names;
+mod features;
+mod field_policy;
+mod filter;
 mod handlers;
+mod health;
+mod listener;
 mod logging;
+mod metrics;
+mod otlp;
+mod projects;
+mod runtime;
+mod runtime_stats;
+mod session;
+mod statsd;
 mod streaming;
 mod transformer;
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use config::load_config;
+use clap::{Parser, Subcommand};
+use config::load_config_with_profile;
 use enrichment::geoip::GeoIpLookup;
-use enrichment::user_agent::{UserAgentParser, WootheeParser};
+use enrichment::geoip_cache::GeoIpCache;
+use enrichment::user_agent::create_user_agent_parser;
 use handlers::AppState;
-use logging::init_logging;
-use streaming::create_streaming_service;
+use logging::init_reloadable_logging;
+use metrics::{SinkMetrics, StageMetrics};
+use streaming::create_instrumented_streaming_service;
+
+/// Analytics event collection API
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the configuration file (YAML, JSON or TOML) or directory, or an
+    /// http(s):// URL or s3://<bucket>/<key> object holding the configuration
+    #[arg(short, long, default_value = "config.yaml")]
+    config: String,
+
+    /// Profile whose settings override the rest of the configuration (e.g. dev, prod)
+    #[arg(long, env = config::PROFILE_ENV)]
+    profile: Option<String>,
+
+    /// How often to check a remote configuration for changes (e.g. 60s, 5m); the
+    /// server restarts with each new version that loads
+    #[arg(long, env = "ANALYTICS_CONFIG_REFRESH", value_parser = parse_refresh_interval)]
+    config_refresh: Option<Duration>,
+
+    /// Port to listen on, overriding server.port
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    port: Option<u16>,
+
+    /// Log level, overriding logging.level
+    #[arg(long, value_parser = ["trace", "debug", "info", "warn", "error"])]
+    log_level: Option<String>,
+
+    /// Load and validate the configuration, then exit (same as `validate`)
+    #[arg(long)]
+    check: bool,
+
+    /// Print the effective configuration (defaults included, secrets masked) as YAML, then exit
+    #[arg(long)]
+    print_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Validate the configuration and the files it references, listing every problem;
+    /// exits with status 1 when any is found
+    Validate {
+        /// Also connect to the configured streaming sinks and run their health check
+        #[arg(long)]
+        connect: bool,
+    },
+}
 
 #[tokio::main]
 async fn main() {
-    // Load configuration from YAML file
+    let cli = Cli::parse();
+
+    // Note the version of a refreshed remote configuration before loading it, so any
+    // later change is noticed
+    let remote_version = match cli.config_refresh {
+        Some(_) if config::is_remote(&cli.config) => match config::fetch_remote(&cli.config, None).await {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("Failed to load configuration from {}: {}", cli.config, e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    // Load configuration from file or directory
     // Validates: Requirement 8.1
-    let config = match load_config("config.yaml") {
+    let mut config = match load_config_with_profile(&cli.config, cli.profile.as_deref()) {
         Ok(cfg) => cfg,
         Err(e) => {
-            eprintln!("Failed to load configuration: {}", e);
+            // Invalid settings are all listed, one per line
+            eprintln!("Failed to load configuration from {}: {}", cli.config, e);
             std::process::exit(1);
         }
     };
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
+    if let Some(level) = cli.log_level {
+        config.logging.level = level;
+    }
+    if cli.print_config {
+        match serde_yaml::to_string(&config::effective_config(&config)) {
+            Ok(yaml) => print!("{}", yaml),
+            Err(e) => {
+                eprintln!("Failed to render configuration: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let validate = match cli.command {
+        Some(Command::Validate { connect }) => Some(connect),
+        None if cli.check => Some(false),
+        None => None,
+    };
+    if let Some(connect) = validate {
+        let problems = check::check_config(&config, connect).await;
+        if problems.is_empty() {
+            println!("Configuration {} is valid", cli.config);
+            return;
+        }
+        eprintln!("Configuration {} has {} problem(s):", cli.config, problems.len());
+        for problem in &problems {
+            eprintln!("  - {}", problem);
+        }
+        std::process::exit(1);
+    }
 
     // Initialize structured logging with JSON formatting
     // Validates: Requirement 10.1, 10.7
-    init_logging(&config.logging.level);
+    let log_level = init_reloadable_logging(&config.logging.level);
 
     tracing::info!(
         message = "Rust Analytics API starting",
         host = %config.server.host,
         port = config.server.port,
         log_level = %config.logging.level,
+        profile = cli.profile.as_deref().unwrap_or("none"),
         streaming_service = ?config.streaming.service_type,
         geoip_database = %config.geoip.database_path
     );
 
     // Initialize GeoIP lookup with database (optional)
     // Validates: Requirement 6.1, 13.4
-    let geoip_lookup = if config.geoip.database_path.is_empty() {
+    let geoip_lookup = if !config.features.geoip {
+        tracing::warn!("GeoIP switched off by features.geoip, geolocation enrichment will be skipped");
+        None
+    } else if config.geoip.database_path.is_empty() {
         tracing::warn!("GeoIP database path not configured, geolocation enrichment will be skipped");
         None
     } else {
         tracing::info!(
             database_path = %config.geoip.database_path,
+            backend = ?config.geoip.backend,
             "Loading GeoIP database into memory"
         );
-        match GeoIpLookup::new(&config.geoip.database_path) {
+        match GeoIpLookup::from_config(&config.geoip) {
             Ok(lookup) => {
                 tracing::info!("GeoIP database loaded successfully");
+                let lookup = match &config.geoip.cache {
+                    Some(cache) => {
+                        tracing::info!(
+                            max_entries = cache.max_entries,
+                            ttl_secs = cache.ttl_secs,
+                            "Caching GeoIP lookup results"
+                        );
+                        lookup.with_cache(GeoIpCache::new(
+                            cache.max_entries,
+                            std::time::Duration::from_secs(cache.ttl_secs),
+                            cache.ipv4_prefix,
+                            cache.ipv6_prefix,
+                        ))
+                    }
+                    None => lookup,
+                };
                 Some(Arc::new(lookup))
             }
             Err(e) => {
@@ -65,10 +206,37 @@ async fn main() {
         }
     };
 
+    // Check for updated GeoIP database files in the background
+    if let Some(lookup) = &geoip_lookup {
+        let interval_secs = config.geoip.reload_interval_secs;
+        if interval_secs > 0 {
+            let lookup = lookup.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    lookup.reload_changed();
+                }
+            });
+            tracing::info!(interval_secs, "GeoIP database reloading enabled");
+        }
+    }
+
     // Initialize User-Agent parser
     // Validates: Requirement 5.1
-    tracing::info!("Initializing User-Agent parser");
-    let user_agent_parser: Arc<dyn UserAgentParser> = Arc::new(WootheeParser::new());
+    tracing::info!(
+        parser = ?config.user_agent.parser,
+        "Initializing User-Agent parser"
+    );
+    let user_agent_parser = match create_user_agent_parser(&config.user_agent) {
+        Ok(parser) => parser,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to initialize User-Agent parser");
+            eprintln!("Failed to initialize User-Agent parser: {}", e);
+            std::process::exit(1);
+        }
+    };
     tracing::info!("User-Agent parser initialized");
 
     // Initialize streaming service based on config
@@ -77,7 +245,18 @@ async fn main() {
         service_type = ?config.streaming.service_type,
         "Initializing streaming service"
     );
-    let streaming_service = match create_streaming_service(&config.streaming).await {
+    // Every broker sink records its sends into the metrics served on /metrics
+    let sink_metrics = Arc::new(
+        SinkMetrics::new(config.streaming.service_type.as_str()).with_topic_limits(&config.logging.topic_metrics),
+    );
+    let stage_metrics = Arc::new(StageMetrics::new());
+    let streaming_service = match create_instrumented_streaming_service(
+        &config.streaming,
+        sink_metrics.clone(),
+        stage_metrics.clone(),
+    )
+    .await
+    {
         Ok(service) => {
             tracing::info!(
                 service_type = ?config.streaming.service_type,
@@ -99,12 +278,88 @@ async fn main() {
     // Create AppState with all components
     // Validates: Requirement 8.1, 8.5, 13.4
     let config_arc = Arc::new(config.clone());
-    let app_state = AppState::new(
+    let mut app_state = AppState::new(
         streaming_service,
         geoip_lookup,
         user_agent_parser,
         config_arc,
+        sink_metrics,
+        stage_metrics,
+    )
+    .with_log_level(log_level);
+
+    // Record a sample of the raw ingest requests, personal data redacted
+    if let Some(audit_config) = &config.logging.audit {
+        match audit::AuditLog::new(audit_config, config.streaming.kafka.as_ref()).await {
+            Ok(audit_log) => {
+                tracing::info!(
+                    sample_rate = audit_config.sample_rate,
+                    path = ?audit_config.path,
+                    topic = ?audit_config.topic,
+                    "Audit log enabled"
+                );
+                app_state = app_state.with_audit_log(Arc::new(audit_log));
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to initialize the audit log");
+                eprintln!("Failed to initialize the audit log: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Probe the streaming service in the background and cache the result for /ready
+    let health_check = &config.streaming.health_check;
+    app_state.health_monitor.spawn(
+        app_state.streaming_service.clone(),
+        std::time::Duration::from_secs(health_check.interval_secs),
+        std::time::Duration::from_secs(health_check.timeout_secs),
     );
+    tracing::info!(
+        interval_secs = health_check.interval_secs,
+        "Streaming health monitor started"
+    );
+
+    // Sample the scheduler delay and CPU usage for /admin/runtime
+    app_state.runtime_stats.spawn_sampler();
+
+    // Push the ingest, stage and sink metrics to an OpenTelemetry collector
+    if let Some(otlp_config) = config.logging.otlp.clone() {
+        let interval_secs = otlp_config.interval_secs;
+        match otlp::OtlpExporter::new(otlp_config) {
+            Ok(exporter) => {
+                let exporter = exporter
+                    .with_ingest_metrics(app_state.ingest_metrics.clone())
+                    .with_stage_metrics(app_state.stage_metrics.clone());
+                tracing::info!(url = %exporter.url(), interval_secs, "OTLP metrics export enabled");
+                exporter.spawn(app_state.sink_metrics.clone(), app_state.streaming_service.clone());
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to initialize OTLP metrics export");
+                eprintln!("Failed to initialize OTLP metrics export: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Send the same metrics to a StatsD server or DogStatsD agent
+    if let Some(statsd_config) = config.logging.statsd.clone() {
+        let interval_secs = statsd_config.interval_secs;
+        match statsd::StatsdExporter::new(statsd_config).await {
+            Ok(exporter) => {
+                let exporter = exporter
+                    .with_ingest_metrics(app_state.ingest_metrics.clone())
+                    .with_stage_metrics(app_state.stage_metrics.clone());
+                tracing::info!(address = %exporter.address(), interval_secs, "StatsD metrics export enabled");
+                exporter.spawn(app_state.sink_metrics.clone(), app_state.streaming_service.clone());
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to initialize StatsD metrics export");
+                eprintln!("Failed to initialize StatsD metrics export: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     tracing::info!(
         message = "Application initialization complete",
@@ -125,50 +380,92 @@ async fn main() {
         routing::{get, post},
         Router,
     };
-    use handlers::{track_handler, identify_handler, update_handler};
+    use handlers::{track_handler, identify_handler, update_handler, health_handler, health_details_handler, ready_handler, metrics_handler, config_handler, settings_handler, runtime_stats_handler};
     
-    let app = Router::new()
+    let admin_enabled = !config.server.admin_api_keys.is_empty();
+    let mut app = Router::new()
         // /track/ endpoint - accepts both GET and POST
         .route("/track/", get(track_handler).post(track_handler))
         // /identify endpoint - accepts both GET and POST
         .route("/identify", get(identify_handler).post(identify_handler))
         // /update endpoint - accepts both GET and POST
         .route("/update", get(update_handler).post(update_handler))
-        // Add AppState to router
-        .with_state(app_state);
-    
-    tracing::info!("Axum router configured with /track/, /identify, /update endpoints");
+        // Liveness and readiness probes
+        .route("/health", get(health_handler))
+        .route("/health/details", get(health_details_handler))
+        .route("/ready", get(ready_handler))
+        // Prometheus delivery metrics
+        .route("/metrics", get(metrics_handler));
+    // Effective configuration, runtime settings and statistics, only served when admin keys are configured
+    if admin_enabled {
+        app = app
+            .route("/admin/config", get(config_handler))
+            .route("/admin/settings", get(settings_handler).patch(settings_handler))
+            .route("/admin/runtime", get(runtime_stats_handler));
+    }
+    let client_ip_resolver = app_state.client_ip_resolver.clone();
+    let streaming_service = app_state.streaming_service.clone();
+    // Add AppState to router
+    let mut app = app.with_state(app_state);
 
-    // Configure server with host and port from config
-    let bind_addr = format!("{}:{}", config.server.host, config.server.port);
-    tracing::info!(
-        bind_addr = %bind_addr,
-        "Starting Axum server"
-    );
+    // Record every request in the access log, apart from the application logs
+    let access_log_config = &config.logging.access_log;
+    if access_log_config.enabled {
+        match access_log::AccessLogger::new(access_log_config, client_ip_resolver).await {
+            Ok(logger) => {
+                app = app.layer(axum::middleware::from_fn_with_state(Arc::new(logger), access_log::log_requests));
+                tracing::info!(
+                    format = ?access_log_config.format,
+                    path = access_log_config.path.as_deref().unwrap_or("stdout"),
+                    "Access log enabled"
+                );
+            }
+            Err(e) => {
+                tracing::error!(error = %e, path = ?access_log_config.path, "Failed to open the access log");
+                eprintln!("Failed to open the access log: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     
-    println!("\n🚀 Server starting on {}", bind_addr);
+    tracing::info!("Axum router configured with /track/, /identify, /update, /health, /health/details, /ready, /metrics endpoints");
+
+    // Bind the configured listeners (plain HTTP on host:port by default)
+    let mut listeners = Vec::new();
+    for listener_config in listener::effective_listeners(&config.server) {
+        match listener::bind(&listener_config).await {
+            Ok(listener) => {
+                tracing::info!(listener = listener.name(), "Server listening and ready to accept connections");
+                listeners.push(listener);
+            }
+            Err(e) => {
+                let name = listener_config.describe();
+                tracing::error!(
+                    error = %e,
+                    listener = %name,
+                    "Failed to bind listener"
+                );
+                eprintln!("Failed to bind {}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let listener_names: Vec<&str> = listeners.iter().map(|l| l.name()).collect();
+    println!("\n🚀 Server starting on {}", listener_names.join(", "));
     println!("   Endpoints:");
     println!("   - GET/POST /track/");
     println!("   - GET/POST /identify");
     println!("   - GET/POST /update");
-    
-    // Start async server with Tokio runtime
-    let listener = tokio::net::TcpListener::bind(&bind_addr)
-        .await
-        .unwrap_or_else(|e| {
-            tracing::error!(
-                error = %e,
-                bind_addr = %bind_addr,
-                "Failed to bind to address"
-            );
-            eprintln!("Failed to bind to {}: {}", bind_addr, e);
-            std::process::exit(1);
-        });
-    
-    tracing::info!(
-        bind_addr = %bind_addr,
-        "Server listening and ready to accept connections"
-    );
+    println!("   - GET /health");
+    println!("   - GET /health/details");
+    println!("   - GET /ready");
+    println!("   - GET /metrics");
+    if admin_enabled {
+        println!("   - GET /admin/config");
+        println!("   - GET/PATCH /admin/settings");
+        println!("   - GET /admin/runtime");
+    }
     
     // Set up graceful shutdown handling
     // Validates: Requirement 13.6
@@ -202,22 +499,79 @@ async fn main() {
         }
     };
     
-    // Start server with graceful shutdown
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal)
-    .await
-    .unwrap_or_else(|e| {
-        tracing::error!(
-            error = %e,
-            "Server error"
-        );
-        eprintln!("Server error: {}", e);
-        std::process::exit(1);
+    // Serve on every listener until the shutdown signal, then drain in-flight requests
+    // A new version of a remote configuration shuts down the same way, then restarts
+    if cli.config_refresh.is_some() && remote_version.is_none() {
+        tracing::warn!("--config-refresh only applies to remote configurations; ignoring it");
+    }
+    let config_change = {
+        let (location, profile, interval) = (cli.config.clone(), cli.profile.clone(), cli.config_refresh);
+        async move {
+            match (remote_version, interval) {
+                (Some(current), Some(interval)) => config::wait_for_change(location, profile, interval, current).await,
+                _ => std::future::pending().await,
+            }
+        }
+    };
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let restart = tokio::spawn(async move {
+        let restart = tokio::select! {
+            _ = shutdown_signal => false,
+            _ = config_change => {
+                println!("\n🔄 Configuration changed, restarting...");
+                true
+            }
+        };
+        let _ = shutdown_tx.send(true);
+        restart
     });
+    futures::future::join_all(
+        listeners
+            .into_iter()
+            .map(|listener| listener::serve(listener, app.clone(), shutdown_rx.clone())),
+    )
+    .await;
+
+    // Deliver the events already acknowledged from the local queue (ack_mode: queued)
+    let drain_timeout = Duration::from_millis(config.streaming.queue.drain_timeout_ms);
+    if let Err(e) = streaming_service.close(drain_timeout).await {
+        tracing::error!(error = %e, "Streaming service did not close cleanly");
+    }
     
     tracing::info!("Server shutdown complete");
     println!("✅ Server shutdown complete");
+
+    if restart.await.unwrap_or(false) {
+        restart_process();
+    }
+}
+
+/// Parse `--config-refresh`, e.g. "60s" or "5m" (a bare number is seconds)
+fn parse_refresh_interval(value: &str) -> Result<Duration, String> {
+    match config::parse_duration(value, 1_000)? {
+        0 => Err("must be non-zero".to_string()),
+        secs => Ok(Duration::from_secs(secs)),
+    }
+}
+
+/// Replace the process with a new one started with the same arguments
+///
+/// Used once a remote configuration changed; the new process loads it like any other
+/// start. Where processes cannot be replaced (or replacing fails), exits with status 1
+/// so a supervisor restarts it.
+fn restart_process() -> ! {
+    tracing::info!("Restarting with the updated configuration");
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        let error = match std::env::current_exe() {
+            Ok(exe) => std::process::Command::new(exe).args(std::env::args_os().skip(1)).exec(),
+            Err(e) => e,
+        };
+        eprintln!("Failed to restart: {}", error);
+    }
+    #[cfg(not(unix))]
+    eprintln!("Exiting so the supervisor restarts with the updated configuration");
+    std::process::exit(1);
 }
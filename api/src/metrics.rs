@@ -0,0 +1,748 @@
+// Delivery metrics
+// This module records ingest, processing stage and streaming sink metrics and renders them in the Prometheus text format
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::{ProjectMetricsConfig, TopicMetricsConfig};
+
+/// `project` label of the projects beyond the label limit
+pub const OTHER_PROJECT_LABEL: &str = "other";
+
+/// `topic` label of the topics beyond the label limit
+pub const OTHER_TOPIC_LABEL: &str = "other";
+
+/// Upper bounds (seconds) of the delivery latency histogram buckets
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Upper bounds (bytes) of the payload size histogram buckets
+pub const PAYLOAD_SIZE_BUCKETS: [f64; 10] = [
+    128.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0, 8192.0, 16384.0, 65536.0, 262144.0,
+];
+
+/// Upper bounds (seconds) of the processing stage duration histogram buckets
+pub const STAGE_DURATION_BUCKETS: [f64; 14] = [
+    0.00001, 0.000025, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 0.5, 2.5,
+];
+
+/// Stage checking the request parameters
+pub const STAGE_VALIDATION: &str = "validation";
+/// Stage turning the parameters into an event
+pub const STAGE_TRANSFORMATION: &str = "transformation";
+/// Stage encoding the event payload for the sink
+pub const STAGE_SERIALIZATION: &str = "serialization";
+/// Stage handing the event to the streaming service, serialization included
+pub const STAGE_SEND: &str = "send";
+
+/// Cumulative histogram with fixed buckets
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per-bucket (non-cumulative) counts, one extra slot for +Inf
+    buckets: Vec<AtomicU64>,
+    /// Bits of the `f64` sum of all observations
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    /// Create an empty histogram with the given bucket upper bounds
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one observation
+    pub fn observe(&self, value: f64) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+            Some((f64::from_bits(sum) + value).to_bits())
+        });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a duration in seconds
+    pub fn observe_duration(&self, latency: Duration) {
+        self.observe(latency.as_secs_f64());
+    }
+
+    /// Number of observations recorded
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Sum of all observations
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+
+    /// Upper bounds of the buckets, without +Inf
+    pub fn bounds(&self) -> &'static [f64] {
+        self.bounds
+    }
+
+    /// Count of each bucket (not cumulative), ending with the +Inf bucket
+    pub fn bucket_counts(&self) -> Vec<u64> {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Cumulative counts for each bucket bound, ending with +Inf
+    pub fn cumulative(&self) -> Vec<u64> {
+        let mut total = 0;
+        self.buckets
+            .iter()
+            .map(|b| {
+                total += b.load(Ordering::Relaxed);
+                total
+            })
+            .collect()
+    }
+
+    /// Append the histogram `name` with the given labels to `out`
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let cumulative = self.cumulative();
+        for (bound, count) in self.bounds.iter().zip(&cumulative) {
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, count);
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name,
+            labels,
+            cumulative.last().copied().unwrap_or(0)
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum());
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count());
+    }
+}
+
+/// Hands out label values from request-derived names within cardinality limits
+///
+/// Names in `allowed` are their own label. Without an allowlist the first `max`
+/// names seen are, and every later name shares the `other` label.
+#[derive(Debug)]
+struct LabelLimiter {
+    /// Label name, for the limit warning
+    label: &'static str,
+    other: &'static str,
+    allowed: HashSet<String>,
+    max: usize,
+    /// Names given a label so far, when labels are handed out first come first served
+    labeled: Mutex<HashSet<String>>,
+    /// Whether the label limit was reached (logged once)
+    overflowed: AtomicBool,
+}
+
+impl LabelLimiter {
+    fn new(label: &'static str, other: &'static str, allowed: &[String], max: usize) -> Self {
+        LabelLimiter {
+            label,
+            other,
+            allowed: allowed.iter().cloned().collect(),
+            max,
+            labeled: Mutex::new(HashSet::new()),
+            overflowed: AtomicBool::new(false),
+        }
+    }
+
+    /// Label value of `name`: the name while within the limits, `other` beyond
+    fn label(&self, name: &str) -> String {
+        if !self.allowed.is_empty() {
+            return if self.allowed.contains(name) {
+                name.to_string()
+            } else {
+                self.other.to_string()
+            };
+        }
+        let mut labeled = self.labeled.lock().unwrap_or_else(|e| e.into_inner());
+        if labeled.contains(name) {
+            return name.to_string();
+        }
+        if labeled.len() < self.max {
+            labeled.insert(name.to_string());
+            return name.to_string();
+        }
+        if !self.overflowed.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                label = self.label,
+                max = self.max,
+                value = name,
+                "Metric label limit reached, further values are counted as \"{}\"",
+                self.other
+            );
+        }
+        self.other.to_string()
+    }
+}
+
+/// Delivery metrics of the events sent to one topic (or stream)
+#[derive(Debug)]
+pub struct TopicMetrics {
+    delivered: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    latency: Histogram,
+    payload_size: Histogram,
+}
+
+impl TopicMetrics {
+    fn new() -> Self {
+        TopicMetrics {
+            delivered: AtomicU64::new(0),
+            errors: Mutex::new(BTreeMap::new()),
+            latency: Histogram::new(&LATENCY_BUCKETS),
+            payload_size: Histogram::new(&PAYLOAD_SIZE_BUCKETS),
+        }
+    }
+
+    /// Events acknowledged by the sink
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Failed sends grouped by error kind
+    pub fn errors(&self) -> BTreeMap<&'static str, u64> {
+        self.errors
+            .lock()
+            .map(|e| e.clone())
+            .unwrap_or_default()
+    }
+
+    /// Delivery latency histogram (seconds)
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    /// Serialized payload size histogram (bytes)
+    pub fn payload_size(&self) -> &Histogram {
+        &self.payload_size
+    }
+}
+
+/// Counters and gauges for a single streaming sink, by destination topic
+///
+/// Sinks that do not report a topic (stdout, file) record under the empty topic, which
+/// is rendered without a `topic` label. Only a bounded set of topics is labeled by
+/// name (see `TopicMetricsConfig`); the others share the `other` label.
+#[derive(Debug)]
+pub struct SinkMetrics {
+    sink: String,
+    in_flight: AtomicI64,
+    topic_labels: LabelLimiter,
+    topics: Mutex<BTreeMap<String, Arc<TopicMetrics>>>,
+}
+
+impl SinkMetrics {
+    /// Create empty metrics for the sink named `sink` (used as the `sink` label), with
+    /// the default topic label limits
+    pub fn new(sink: &str) -> Self {
+        let limits = TopicMetricsConfig::default();
+        SinkMetrics {
+            sink: sink.to_string(),
+            in_flight: AtomicI64::new(0),
+            topic_labels: LabelLimiter::new("topic", OTHER_TOPIC_LABEL, &limits.allowed_topics, limits.max_topics),
+            topics: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Use the topic label cardinality controls in `config`
+    pub fn with_topic_limits(mut self, config: &TopicMetricsConfig) -> Self {
+        self.topic_labels = LabelLimiter::new("topic", OTHER_TOPIC_LABEL, &config.allowed_topics, config.max_topics);
+        self
+    }
+
+    /// Sink label value
+    pub fn sink(&self) -> &str {
+        &self.sink
+    }
+
+    /// `topic` label of `topic`: its name while within the limits, `other` beyond
+    pub fn topic_label(&self, topic: &str) -> String {
+        if topic.is_empty() {
+            return String::new();
+        }
+        self.topic_labels.label(topic)
+    }
+
+    /// Metrics of the label of `topic`, created on first use
+    pub fn topic(&self, topic: &str) -> Arc<TopicMetrics> {
+        let label = self.topic_label(topic);
+        let mut topics = self.topics.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(metrics) = topics.get(&label) {
+            return metrics.clone();
+        }
+        let metrics = Arc::new(TopicMetrics::new());
+        topics.insert(label, metrics.clone());
+        metrics
+    }
+
+    /// Mark a send as started
+    pub fn send_started(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a send as finished, recording its latency and outcome
+    ///
+    /// # Arguments
+    /// * `topic` - Destination topic of the event, empty if the sink has none
+    /// * `latency` - Time from send start to broker acknowledgement or failure
+    /// * `error_kind` - `None` on success, or the error kind label on failure
+    pub fn send_finished(&self, topic: &str, latency: Duration, error_kind: Option<&'static str>) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        let metrics = self.topic(topic);
+        metrics.latency.observe_duration(latency);
+        match error_kind {
+            None => {
+                metrics.delivered.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(kind) => {
+                let mut errors = match metrics.errors.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                *errors.entry(kind).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Record the size of a payload written to `topic`
+    pub fn payload_written(&self, topic: &str, bytes: usize) {
+        self.topic(topic).payload_size.observe(bytes as f64);
+    }
+
+    /// Sends currently awaiting an acknowledgement
+    pub fn in_flight(&self) -> i64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Events acknowledged by the sink, over all topics
+    pub fn delivered(&self) -> u64 {
+        self.topics().iter().map(|(_, metrics)| metrics.delivered()).sum()
+    }
+
+    /// Failed sends grouped by error kind, over all topics
+    pub fn errors(&self) -> BTreeMap<&'static str, u64> {
+        let mut errors = BTreeMap::new();
+        for (_, metrics) in self.topics() {
+            for (kind, count) in metrics.errors() {
+                *errors.entry(kind).or_insert(0) += count;
+            }
+        }
+        errors
+    }
+
+    /// Metrics of every topic recorded so far, in topic order
+    pub fn topics(&self) -> Vec<(String, Arc<TopicMetrics>)> {
+        self.topics
+            .lock()
+            .map(|topics| topics.iter().map(|(topic, metrics)| (topic.clone(), metrics.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Label set of `topic`: `sink="kafka",topic="analytics"`, or just the sink for the empty topic
+    fn labels(&self, topic: &str) -> String {
+        if topic.is_empty() {
+            format!("sink=\"{}\"", self.sink)
+        } else {
+            format!("sink=\"{}\",topic=\"{}\"", self.sink, escape_label(topic))
+        }
+    }
+
+    /// Render the metrics in the Prometheus text exposition format
+    ///
+    /// # Arguments
+    /// * `queue_depth` - Messages buffered inside the sink (client library or internal
+    ///   queue), if the sink reports it
+    pub fn render_prometheus(&self, queue_depth: Option<i64>) -> String {
+        let sink = &self.sink;
+        let topics = self.topics();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP analytics_sink_in_flight Sends awaiting a sink acknowledgement");
+        let _ = writeln!(out, "# TYPE analytics_sink_in_flight gauge");
+        let _ = writeln!(out, "analytics_sink_in_flight{{sink=\"{}\"}} {}", sink, self.in_flight());
+
+        if let Some(depth) = queue_depth {
+            let _ = writeln!(out, "# HELP analytics_sink_queue_depth Messages buffered in the sink awaiting delivery");
+            let _ = writeln!(out, "# TYPE analytics_sink_queue_depth gauge");
+            let _ = writeln!(out, "analytics_sink_queue_depth{{sink=\"{}\"}} {}", sink, depth);
+        }
+
+        let _ = writeln!(out, "# HELP analytics_sink_delivered_total Events acknowledged by the sink");
+        let _ = writeln!(out, "# TYPE analytics_sink_delivered_total counter");
+        for (topic, metrics) in &topics {
+            let _ = writeln!(out, "analytics_sink_delivered_total{{{}}} {}", self.labels(topic), metrics.delivered());
+        }
+
+        let _ = writeln!(out, "# HELP analytics_sink_errors_total Failed sends by error kind");
+        let _ = writeln!(out, "# TYPE analytics_sink_errors_total counter");
+        for (topic, metrics) in &topics {
+            for (kind, count) in metrics.errors() {
+                let _ = writeln!(
+                    out,
+                    "analytics_sink_errors_total{{{},kind=\"{}\"}} {}",
+                    self.labels(topic),
+                    kind,
+                    count
+                );
+            }
+        }
+
+        let _ = writeln!(out, "# HELP analytics_sink_delivery_latency_seconds Time from send to sink acknowledgement or failure");
+        let _ = writeln!(out, "# TYPE analytics_sink_delivery_latency_seconds histogram");
+        for (topic, metrics) in &topics {
+            metrics.latency.render(&mut out, "analytics_sink_delivery_latency_seconds", &self.labels(topic));
+        }
+
+        let _ = writeln!(out, "# HELP analytics_sink_payload_bytes Size of the serialized event payloads");
+        let _ = writeln!(out, "# TYPE analytics_sink_payload_bytes histogram");
+        for (topic, metrics) in &topics {
+            if metrics.payload_size.count() > 0 {
+                metrics.payload_size.render(&mut out, "analytics_sink_payload_bytes", &self.labels(topic));
+            }
+        }
+
+        out
+    }
+}
+
+/// Time spent in each processing stage of the ingest requests
+///
+/// Stages are `validation`, `transformation`, `enrichment.<stage>` for each enrichment
+/// stage, `serialization` and `send`.
+#[derive(Debug, Default)]
+pub struct StageMetrics {
+    stages: Mutex<BTreeMap<String, Arc<Histogram>>>,
+}
+
+impl StageMetrics {
+    /// Create empty metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `duration` spent in `stage`
+    pub fn observe(&self, stage: &str, duration: Duration) {
+        let histogram = {
+            let mut stages = self.stages.lock().unwrap_or_else(|e| e.into_inner());
+            match stages.get(stage) {
+                Some(histogram) => histogram.clone(),
+                None => {
+                    let histogram = Arc::new(Histogram::new(&STAGE_DURATION_BUCKETS));
+                    stages.insert(stage.to_string(), histogram.clone());
+                    histogram
+                }
+            }
+        };
+        histogram.observe_duration(duration);
+    }
+
+    /// Duration histogram of every stage recorded so far, in stage name order
+    pub fn stages(&self) -> Vec<(String, Arc<Histogram>)> {
+        self.stages
+            .lock()
+            .map(|stages| stages.iter().map(|(stage, histogram)| (stage.clone(), histogram.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// Render the metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP analytics_stage_duration_seconds Time spent in each processing stage");
+        let _ = writeln!(out, "# TYPE analytics_stage_duration_seconds histogram");
+        for (stage, histogram) in self.stages() {
+            histogram.render(&mut out, "analytics_stage_duration_seconds", &format!("stage=\"{}\"", escape_label(&stage)));
+        }
+        out
+    }
+}
+
+/// Final outcome of an ingest request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IngestOutcome {
+    /// Sent to the streaming service
+    Accepted,
+    /// Rejected with a 4xx response (invalid parameters, access rules)
+    Rejected,
+    /// Left out by project sampling
+    Sampled,
+    /// Dropped by an enrichment stage or a filter rule
+    Dropped,
+    /// The streaming service failed to take the event
+    Failed,
+}
+
+impl IngestOutcome {
+    /// Value of the `outcome` label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestOutcome::Accepted => "accepted",
+            IngestOutcome::Rejected => "rejected",
+            IngestOutcome::Sampled => "sampled",
+            IngestOutcome::Dropped => "dropped",
+            IngestOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Ingest request counts by project, endpoint and outcome
+///
+/// Only a bounded set of projects is labeled by name (see `ProjectMetricsConfig`);
+/// the others share the `other` label. Requests without a project have an empty label.
+#[derive(Debug)]
+pub struct IngestMetrics {
+    project_labels: LabelLimiter,
+    counts: Mutex<BTreeMap<(String, &'static str, IngestOutcome), u64>>,
+}
+
+impl IngestMetrics {
+    /// Create empty metrics with the given label cardinality controls
+    pub fn new(config: &ProjectMetricsConfig) -> Self {
+        IngestMetrics {
+            project_labels: LabelLimiter::new("project", OTHER_PROJECT_LABEL, &config.allowed_projects, config.max_projects),
+            counts: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// `project` label of `project`: its name while within the limits, `other` beyond
+    pub fn project_label(&self, project: Option<&str>) -> String {
+        let Some(project) = project.filter(|project| !project.is_empty()) else {
+            return String::new();
+        };
+        self.project_labels.label(project)
+    }
+
+    /// Count one request to `endpoint` for `project` that ended with `outcome`
+    pub fn record(&self, endpoint: &'static str, project: Option<&str>, outcome: IngestOutcome) {
+        let label = self.project_label(project);
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        *counts.entry((label, endpoint, outcome)).or_insert(0) += 1;
+    }
+
+    /// Start recording one request to `endpoint`; the outcome is counted when the
+    /// returned recorder is dropped
+    pub fn request(self: &Arc<Self>, endpoint: &'static str) -> IngestRecorder {
+        IngestRecorder {
+            metrics: self.clone(),
+            endpoint,
+            project: None,
+            outcome: IngestOutcome::Rejected,
+        }
+    }
+
+    /// Counts by (project label, endpoint, outcome)
+    pub fn counts(&self) -> Vec<(String, &'static str, IngestOutcome, u64)> {
+        self.counts
+            .lock()
+            .map(|counts| {
+                counts
+                    .iter()
+                    .map(|((project, endpoint, outcome), count)| (project.clone(), *endpoint, *outcome, *count))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Render the metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP analytics_ingest_requests_total Ingest requests by project, endpoint and outcome");
+        let _ = writeln!(out, "# TYPE analytics_ingest_requests_total counter");
+        for (project, endpoint, outcome, count) in self.counts() {
+            let _ = writeln!(
+                out,
+                "analytics_ingest_requests_total{{project=\"{}\",endpoint=\"{}\",outcome=\"{}\"}} {}",
+                escape_label(&project),
+                endpoint,
+                outcome.as_str(),
+                count
+            );
+        }
+        out
+    }
+}
+
+/// Records the outcome of one ingest request when dropped
+///
+/// Requests count as rejected unless another outcome is set, so every early return
+/// on an invalid request is counted without further bookkeeping.
+pub struct IngestRecorder {
+    metrics: Arc<IngestMetrics>,
+    endpoint: &'static str,
+    project: Option<String>,
+    outcome: IngestOutcome,
+}
+
+impl IngestRecorder {
+    /// Attribute the request to `project`
+    pub fn set_project(&mut self, project: Option<&str>) {
+        self.project = project.map(str::to_string);
+    }
+
+    /// Set the outcome counted for the request
+    pub fn set_outcome(&mut self, outcome: IngestOutcome) {
+        self.outcome = outcome;
+    }
+}
+
+impl Drop for IngestRecorder {
+    fn drop(&mut self) {
+        self.metrics.record(self.endpoint, self.project.as_deref(), self.outcome);
+    }
+}
+
+/// Escape a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&LATENCY_BUCKETS);
+        histogram.observe_duration(Duration::from_micros(500));
+        histogram.observe_duration(Duration::from_millis(30));
+        histogram.observe_duration(Duration::from_secs(20));
+
+        let cumulative = histogram.cumulative();
+        assert_eq!(cumulative[0], 1); // <= 1ms
+        assert_eq!(cumulative[4], 2); // <= 50ms
+        assert_eq!(*cumulative.last().unwrap(), 3); // +Inf
+        assert_eq!(histogram.count(), 3);
+    }
+
+    #[test]
+    fn test_send_outcomes() {
+        let metrics = SinkMetrics::new("kafka");
+        metrics.send_started();
+        metrics.send_started();
+        assert_eq!(metrics.in_flight(), 2);
+
+        metrics.send_finished("analytics", Duration::from_millis(2), None);
+        metrics.send_finished("identify", Duration::from_millis(5000), Some("timeout"));
+
+        assert_eq!(metrics.in_flight(), 0);
+        assert_eq!(metrics.delivered(), 1);
+        assert_eq!(metrics.errors().get("timeout"), Some(&1));
+        assert_eq!(metrics.topic("analytics").delivered(), 1);
+        assert_eq!(metrics.topic("identify").errors().get("timeout"), Some(&1));
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = SinkMetrics::new("kafka");
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(3), Some("send"));
+        metrics.payload_written("analytics", 700);
+
+        let text = metrics.render_prometheus(Some(7));
+        assert!(text.contains("analytics_sink_queue_depth{sink=\"kafka\"} 7"));
+        assert!(text.contains("analytics_sink_errors_total{sink=\"kafka\",topic=\"analytics\",kind=\"send\"} 1"));
+        assert!(text.contains("analytics_sink_delivery_latency_seconds_bucket{sink=\"kafka\",topic=\"analytics\",le=\"+Inf\"} 1"));
+        assert!(text.contains("analytics_sink_payload_bytes_bucket{sink=\"kafka\",topic=\"analytics\",le=\"1024\"} 1"));
+        assert!(text.contains("analytics_sink_payload_bytes_sum{sink=\"kafka\",topic=\"analytics\"} 700"));
+
+        let text = metrics.render_prometheus(None);
+        assert!(!text.contains("analytics_sink_queue_depth"));
+
+        // Sinks without topics are labeled by sink only
+        let metrics = SinkMetrics::new("stdout");
+        metrics.send_started();
+        metrics.send_finished("", Duration::from_millis(1), None);
+        assert!(metrics.render_prometheus(None).contains("analytics_sink_delivered_total{sink=\"stdout\"} 1"));
+    }
+
+    #[test]
+    fn test_topic_label_limits() {
+        let metrics = SinkMetrics::new("kafka").with_topic_limits(&TopicMetricsConfig {
+            allowed_topics: Vec::new(),
+            max_topics: 1,
+        });
+        metrics.send_started();
+        metrics.send_finished("events-view", Duration::from_millis(1), None);
+        metrics.send_started();
+        metrics.send_finished("events-click", Duration::from_millis(1), None);
+        metrics.send_started();
+        metrics.send_finished("events-scroll", Duration::from_millis(1), None);
+
+        let topics: Vec<String> = metrics.topics().into_iter().map(|(topic, _)| topic).collect();
+        assert_eq!(topics, vec!["events-view".to_string(), OTHER_TOPIC_LABEL.to_string()]);
+        assert_eq!(metrics.topic("events-click").delivered(), 2);
+        assert_eq!(metrics.topic_label(""), "");
+
+        let metrics = SinkMetrics::new("kafka").with_topic_limits(&TopicMetricsConfig {
+            allowed_topics: vec!["analytics".to_string()],
+            max_topics: 0,
+        });
+        assert_eq!(metrics.topic_label("analytics"), "analytics");
+        assert_eq!(metrics.topic_label("analytics-shop"), OTHER_TOPIC_LABEL);
+    }
+
+    #[test]
+    fn test_stage_durations() {
+        let metrics = StageMetrics::new();
+        metrics.observe(STAGE_VALIDATION, Duration::from_micros(20));
+        metrics.observe("enrichment.geoip", Duration::from_micros(300));
+        metrics.observe("enrichment.geoip", Duration::from_millis(3));
+
+        let stages = metrics.stages();
+        assert_eq!(stages[0].0, "enrichment.geoip");
+        assert_eq!(stages[0].1.count(), 2);
+
+        let text = metrics.render_prometheus();
+        assert!(text.contains("analytics_stage_duration_seconds_bucket{stage=\"validation\",le=\"0.000025\"} 1"));
+        assert!(text.contains("analytics_stage_duration_seconds_bucket{stage=\"enrichment.geoip\",le=\"0.0005\"} 1"));
+        assert!(text.contains("analytics_stage_duration_seconds_count{stage=\"enrichment.geoip\"} 2"));
+    }
+
+    #[test]
+    fn test_project_label_limits() {
+        let metrics = IngestMetrics::new(&ProjectMetricsConfig {
+            allowed_projects: Vec::new(),
+            max_projects: 2,
+        });
+        assert_eq!(metrics.project_label(Some("shop")), "shop");
+        assert_eq!(metrics.project_label(Some("blog")), "blog");
+        assert_eq!(metrics.project_label(Some("docs")), OTHER_PROJECT_LABEL);
+        assert_eq!(metrics.project_label(Some("shop")), "shop");
+        assert_eq!(metrics.project_label(None), "");
+
+        let metrics = IngestMetrics::new(&ProjectMetricsConfig {
+            allowed_projects: vec!["shop".to_string()],
+            max_projects: 0,
+        });
+        assert_eq!(metrics.project_label(Some("shop")), "shop");
+        assert_eq!(metrics.project_label(Some("blog")), OTHER_PROJECT_LABEL);
+    }
+
+    #[test]
+    fn test_ingest_recorder() {
+        let metrics = Arc::new(IngestMetrics::new(&ProjectMetricsConfig::default()));
+        {
+            let mut request = metrics.request("/track/");
+            request.set_project(Some("shop"));
+            request.set_outcome(IngestOutcome::Accepted);
+        }
+        drop(metrics.request("/track/"));
+
+        let text = metrics.render_prometheus();
+        assert!(text.contains("analytics_ingest_requests_total{project=\"shop\",endpoint=\"/track/\",outcome=\"accepted\"} 1"));
+        assert!(text.contains("analytics_ingest_requests_total{project=\"\",endpoint=\"/track/\",outcome=\"rejected\"} 1"));
+    }
+}
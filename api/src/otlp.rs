@@ -0,0 +1,354 @@
+// OTLP metrics export
+// This module pushes the streaming sink metrics to an OpenTelemetry collector over OTLP/HTTP
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::config::OtlpConfig;
+use crate::metrics::{Histogram, IngestMetrics, SinkMetrics, StageMetrics};
+use crate::streaming::StreamingService;
+
+/// Path of the metrics service under the collector endpoint
+const METRICS_PATH: &str = "/v1/metrics";
+
+/// `AGGREGATION_TEMPORALITY_CUMULATIVE`: counters and histograms are totals since start
+const CUMULATIVE: u8 = 2;
+
+/// Pushes the metrics served on `/metrics` to an OTLP collector
+///
+/// Requests use the OTLP/HTTP JSON encoding, so any collector with the OTLP HTTP
+/// receiver accepts them. Metric names match the Prometheus ones without the `_total`
+/// suffix (the collector's Prometheus exporter adds it back).
+pub struct OtlpExporter {
+    client: reqwest::Client,
+    config: OtlpConfig,
+    /// Ingest request counts, exported next to the sink metrics when set
+    ingest: Option<Arc<IngestMetrics>>,
+    /// Processing stage durations, exported next to the sink metrics when set
+    stages: Option<Arc<StageMetrics>>,
+    /// Process start, the start time of every cumulative data point
+    start_time_nanos: u64,
+}
+
+impl OtlpExporter {
+    /// Create the exporter
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client cannot be built
+    pub fn new(config: OtlpConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        Ok(OtlpExporter {
+            client,
+            config,
+            ingest: None,
+            stages: None,
+            start_time_nanos: unix_nanos(),
+        })
+    }
+
+    /// Also export the ingest request counts
+    pub fn with_ingest_metrics(mut self, ingest: Arc<IngestMetrics>) -> Self {
+        self.ingest = Some(ingest);
+        self
+    }
+
+    /// Also export the processing stage durations
+    pub fn with_stage_metrics(mut self, stages: Arc<StageMetrics>) -> Self {
+        self.stages = Some(stages);
+        self
+    }
+
+    /// URL the metrics are posted to
+    pub fn url(&self) -> String {
+        format!("{}{}", self.config.endpoint.trim_end_matches('/'), METRICS_PATH)
+    }
+
+    /// Build an `ExportMetricsServiceRequest` holding the current value of `metrics`
+    ///
+    /// # Arguments
+    /// * `metrics` - Sink metrics to export
+    /// * `queue_depth` - Messages buffered inside the sink, if the sink reports it
+    pub fn export_request(&self, metrics: &SinkMetrics, queue_depth: Option<i64>) -> Value {
+        let now = unix_nanos();
+        let point = |attributes: Vec<Value>| {
+            json!({
+                "attributes": attributes,
+                "startTimeUnixNano": self.start_time_nanos.to_string(),
+                "timeUnixNano": now.to_string(),
+            })
+        };
+        let with = |mut point: Value, field: &str, value: Value| {
+            point[field] = value;
+            point
+        };
+        let sink_attributes = || vec![attribute("sink", metrics.sink())];
+        let topic_attributes = |topic: &str| {
+            let mut attributes = sink_attributes();
+            if !topic.is_empty() {
+                attributes.push(attribute("topic", topic));
+            }
+            attributes
+        };
+        let topics = metrics.topics();
+
+        let mut exported = vec![gauge(
+            "analytics_sink_in_flight",
+            "Sends awaiting a sink acknowledgement",
+            vec![with(point(sink_attributes()), "asInt", json!(metrics.in_flight().to_string()))],
+        )];
+        if let Some(depth) = queue_depth {
+            exported.push(gauge(
+                "analytics_sink_queue_depth",
+                "Messages buffered in the sink awaiting delivery",
+                vec![with(point(sink_attributes()), "asInt", json!(depth.to_string()))],
+            ));
+        }
+
+        let delivered = topics
+            .iter()
+            .map(|(topic, metrics)| with(point(topic_attributes(topic.as_str())), "asInt", json!(metrics.delivered().to_string())))
+            .collect();
+        exported.push(counter("analytics_sink_delivered", "Events acknowledged by the sink", delivered));
+
+        let mut errors = Vec::new();
+        for (topic, metrics) in &topics {
+            for (kind, count) in metrics.errors() {
+                let mut attributes = topic_attributes(topic.as_str());
+                attributes.push(attribute("kind", kind));
+                errors.push(with(point(attributes), "asInt", json!(count.to_string())));
+            }
+        }
+        exported.push(counter("analytics_sink_errors", "Failed sends by error kind", errors));
+
+        let latency = topics
+            .iter()
+            .map(|(topic, metrics)| histogram_point(point(topic_attributes(topic.as_str())), metrics.latency()))
+            .collect();
+        exported.push(histogram(
+            "analytics_sink_delivery_latency_seconds",
+            "Time from send to sink acknowledgement or failure",
+            "s",
+            latency,
+        ));
+
+        let payload_size: Vec<Value> = topics
+            .iter()
+            .filter(|(_, metrics)| metrics.payload_size().count() > 0)
+            .map(|(topic, metrics)| histogram_point(point(topic_attributes(topic.as_str())), metrics.payload_size()))
+            .collect();
+        if !payload_size.is_empty() {
+            exported.push(histogram(
+                "analytics_sink_payload_bytes",
+                "Size of the serialized event payloads",
+                "By",
+                payload_size,
+            ));
+        }
+
+        if let Some(ingest) = &self.ingest {
+            let requests = ingest
+                .counts()
+                .into_iter()
+                .map(|(project, endpoint, outcome, count)| {
+                    let mut attributes = Vec::new();
+                    if !project.is_empty() {
+                        attributes.push(attribute("project", &project));
+                    }
+                    attributes.push(attribute("endpoint", endpoint));
+                    attributes.push(attribute("outcome", outcome.as_str()));
+                    with(point(attributes), "asInt", json!(count.to_string()))
+                })
+                .collect();
+            exported.push(counter(
+                "analytics_ingest_requests",
+                "Ingest requests by project, endpoint and outcome",
+                requests,
+            ));
+        }
+
+        if let Some(stages) = &self.stages {
+            let durations: Vec<Value> = stages
+                .stages()
+                .iter()
+                .map(|(stage, histogram)| histogram_point(point(vec![attribute("stage", stage)]), histogram))
+                .collect();
+            if !durations.is_empty() {
+                exported.push(histogram(
+                    "analytics_stage_duration_seconds",
+                    "Time spent in each processing stage",
+                    "s",
+                    durations,
+                ));
+            }
+        }
+
+        let mut resource = vec![attribute("service.name", &self.config.service_name)];
+        let mut extra: Vec<(&String, &String)> = self.config.resource_attributes.iter().collect();
+        extra.sort();
+        resource.extend(extra.into_iter().map(|(key, value)| attribute(key, value)));
+
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": resource },
+                "scopeMetrics": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": exported,
+                }],
+            }],
+        })
+    }
+
+    /// Post the current metrics to the collector
+    ///
+    /// # Errors
+    /// Returns a description of the failure when the request fails or is rejected
+    pub async fn push(&self, metrics: &SinkMetrics, queue_depth: Option<i64>) -> Result<(), String> {
+        let mut request = self.client.post(self.url()).json(&self.export_request(metrics, queue_depth));
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let response = request.send().await.map_err(|e| format!("request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("unexpected status {}", response.status().as_u16()));
+        }
+        Ok(())
+    }
+
+    /// Spawn a background task that pushes the metrics every `interval_secs`
+    ///
+    /// Failed pushes are logged and retried at the next interval. The task runs until
+    /// the runtime shuts down.
+    pub fn spawn(self, metrics: Arc<SinkMetrics>, service: Arc<dyn StreamingService>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.push(&metrics, service.queue_depth()).await {
+                    tracing::warn!(url = %self.url(), error = %e, "Failed to push metrics to the OTLP collector");
+                }
+            }
+        })
+    }
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn gauge(name: &str, description: &str, points: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "gauge": { "dataPoints": points },
+    })
+}
+
+fn counter(name: &str, description: &str, points: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "sum": {
+            "dataPoints": points,
+            "aggregationTemporality": CUMULATIVE,
+            "isMonotonic": true,
+        },
+    })
+}
+
+fn histogram(name: &str, description: &str, unit: &str, points: Vec<Value>) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "unit": unit,
+        "histogram": {
+            "dataPoints": points,
+            "aggregationTemporality": CUMULATIVE,
+        },
+    })
+}
+
+fn histogram_point(mut point: Value, histogram: &Histogram) -> Value {
+    let counts: Vec<String> = histogram.bucket_counts().iter().map(u64::to_string).collect();
+    point["count"] = json!(histogram.count().to_string());
+    point["sum"] = json!(histogram.sum());
+    point["bucketCounts"] = json!(counts);
+    point["explicitBounds"] = json!(histogram.bounds());
+    point
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_request() {
+        let exporter = OtlpExporter::new(OtlpConfig {
+            endpoint: "http://collector:4318/".to_string(),
+            resource_attributes: [("deployment.environment".to_string(), "staging".to_string())].into(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(exporter.url(), "http://collector:4318/v1/metrics");
+        let ingest = Arc::new(IngestMetrics::new(&Default::default()));
+        ingest.record("/track/", Some("shop"), crate::metrics::IngestOutcome::Accepted);
+        let stages = Arc::new(StageMetrics::new());
+        stages.observe(crate::metrics::STAGE_VALIDATION, Duration::from_micros(40));
+        let exporter = exporter.with_ingest_metrics(ingest).with_stage_metrics(stages);
+
+        let metrics = SinkMetrics::new("kafka");
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(3), None);
+        metrics.payload_written("analytics", 700);
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(30), Some("timeout"));
+
+        let request = exporter.export_request(&metrics, Some(4));
+        let resource = &request["resourceMetrics"][0];
+        assert_eq!(resource["resource"]["attributes"][0], attribute("service.name", "rust-analytics-api"));
+        assert_eq!(resource["resource"]["attributes"][1], attribute("deployment.environment", "staging"));
+
+        let exported = resource["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        let metric = |name: &str| exported.iter().find(|m| m["name"] == name).unwrap();
+
+        assert_eq!(metric("analytics_sink_queue_depth")["gauge"]["dataPoints"][0]["asInt"], "4");
+        let delivered = &metric("analytics_sink_delivered")["sum"];
+        assert_eq!(delivered["isMonotonic"], true);
+        assert_eq!(delivered["dataPoints"][0]["asInt"], "1");
+        assert_eq!(
+            delivered["dataPoints"][0]["attributes"],
+            json!([attribute("sink", "kafka"), attribute("topic", "analytics")])
+        );
+        let errors = &metric("analytics_sink_errors")["sum"]["dataPoints"][0];
+        assert_eq!(errors["attributes"][2], attribute("kind", "timeout"));
+
+        let latency = &metric("analytics_sink_delivery_latency_seconds")["histogram"]["dataPoints"][0];
+        assert_eq!(latency["count"], "2");
+        assert_eq!(
+            latency["bucketCounts"].as_array().unwrap().len(),
+            latency["explicitBounds"].as_array().unwrap().len() + 1
+        );
+        let payload = &metric("analytics_sink_payload_bytes")["histogram"]["dataPoints"][0];
+        assert_eq!(payload["sum"], 700.0);
+
+        let requests = &metric("analytics_ingest_requests")["sum"]["dataPoints"][0];
+        assert_eq!(requests["asInt"], "1");
+        assert_eq!(requests["attributes"][0], attribute("project", "shop"));
+
+        let validation = &metric("analytics_stage_duration_seconds")["histogram"]["dataPoints"][0];
+        assert_eq!(validation["count"], "1");
+        assert_eq!(validation["attributes"][0], attribute("stage", "validation"));
+    }
+}
@@ -0,0 +1,386 @@
+// Project registry
+// This module applies the per-project API keys, allowed origins, sampling and required parameters
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+
+use crate::client_ip::{constant_time_eq, API_KEY_HEADER};
+use crate::config::ProjectConfig;
+
+/// Why a request was refused by its project's access rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessError {
+    /// The project requires an API key and none of its keys was sent (HTTP 401)
+    Unauthorized(String),
+    /// The request comes from an origin the project does not allow (HTTP 403)
+    Forbidden(String),
+}
+
+impl std::fmt::Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessError::Unauthorized(msg) | AccessError::Forbidden(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// An allowed origin: `https://example.com`, `https://*.example.com` or `*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    /// Any origin
+    Any,
+    /// Exactly this origin
+    Exact(String),
+    /// Any subdomain of `domain` with this scheme (and port, if any)
+    Subdomains { scheme: String, domain: String },
+}
+
+impl OriginPattern {
+    /// Parse an allowed origin
+    ///
+    /// # Errors
+    /// Returns a message when `pattern` is not `*` or an http(s) origin without a path
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().trim_end_matches('/');
+        if pattern == "*" {
+            return Ok(OriginPattern::Any);
+        }
+        let (scheme, host) = pattern
+            .split_once("://")
+            .ok_or_else(|| format!("'{}' is not an origin such as \"https://example.com\"", pattern))?;
+        let scheme = scheme.to_ascii_lowercase();
+        if scheme != "http" && scheme != "https" {
+            return Err(format!("'{}' must use http or https", pattern));
+        }
+        let wildcard = host.strip_prefix("*.");
+        let checked = format!("{}://{}", scheme, wildcard.map(|d| format!("x.{}", d)).unwrap_or_else(|| host.to_string()));
+        let url = url::Url::parse(&checked).map_err(|e| format!("'{}' is not a valid origin: {}", pattern, e))?;
+        if url.path() != "/" || url.query().is_some() || url.fragment().is_some() || host.contains('@') {
+            return Err(format!("'{}' must be an origin without path, query or credentials", pattern));
+        }
+        let host = host.to_ascii_lowercase();
+        Ok(match wildcard {
+            Some(_) => OriginPattern::Subdomains {
+                scheme,
+                domain: host[2..].to_string(),
+            },
+            None => OriginPattern::Exact(format!("{}://{}", scheme, host)),
+        })
+    }
+
+    /// Whether `origin` (`scheme://host[:port]`, lowercased) matches
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Exact(allowed) => allowed == origin,
+            OriginPattern::Subdomains { scheme, domain } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(domain.as_str()))
+                .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        }
+    }
+}
+
+/// Origin of a browser request: the `Origin` header, else the origin of the `Referer`
+///
+/// Image pixels and navigation requests send no `Origin`, only a `Referer`.
+pub fn request_origin(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    if let Some(origin) = header("origin").filter(|o| !o.is_empty() && *o != "null") {
+        return Some(origin.trim_end_matches('/').to_ascii_lowercase());
+    }
+    let referer = url::Url::parse(header("referer")?).ok()?;
+    match referer.origin() {
+        origin @ url::Origin::Tuple(..) => Some(origin.ascii_serialization()),
+        url::Origin::Opaque(_) => None,
+    }
+}
+
+/// Compiled settings of one project
+#[derive(Debug)]
+struct ProjectRules {
+    api_keys: Vec<String>,
+    origins: Vec<OriginPattern>,
+    /// Bits of the `f64` sample rate, which can change at runtime
+    sample_rate: AtomicU64,
+    required: Vec<String>,
+}
+
+/// Per-project access, sampling and validation rules from the `projects` configuration
+///
+/// Projects without an entry are accepted with the global settings.
+#[derive(Debug, Default)]
+pub struct ProjectRegistry {
+    projects: HashMap<String, ProjectRules>,
+}
+
+impl ProjectRegistry {
+    /// Build the registry from configuration
+    ///
+    /// Origins are validated when the configuration is loaded; for hand-built configs an
+    /// invalid origin is logged and ignored.
+    pub fn from_config(projects: &HashMap<String, ProjectConfig>) -> Self {
+        let projects = projects
+            .iter()
+            .map(|(id, project)| {
+                let origins = project
+                    .allowed_origins
+                    .iter()
+                    .filter_map(|origin| {
+                        OriginPattern::parse(origin)
+                            .map_err(|e| tracing::error!(project = %id, error = %e, "Invalid allowed origin ignored"))
+                            .ok()
+                    })
+                    .collect();
+                let rules = ProjectRules {
+                    api_keys: project.api_keys.clone(),
+                    origins,
+                    sample_rate: AtomicU64::new(project.sample_rate.unwrap_or(1.0).to_bits()),
+                    required: project.schema.required.clone(),
+                };
+                (id.clone(), rules)
+            })
+            .collect();
+        ProjectRegistry { projects }
+    }
+
+    /// Check a request against its project's API keys and allowed origins
+    ///
+    /// A project with `api_keys` requires one of them in the `X-Api-Key` header. A project
+    /// with `allowed_origins` requires the `Origin` (or `Referer`) to match one of them,
+    /// unless the request carries a valid API key, as server-side callers do.
+    pub fn authorize(&self, project: Option<&str>, headers: &HeaderMap) -> Result<(), AccessError> {
+        let Some(rules) = project.and_then(|project| self.projects.get(project)) else {
+            return Ok(());
+        };
+        let key_accepted = headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|key| rules.api_keys.iter().any(|allowed| constant_time_eq(allowed.as_bytes(), key.trim().as_bytes())));
+        if !rules.api_keys.is_empty() && !key_accepted {
+            return Err(AccessError::Unauthorized("Invalid or missing API key".to_string()));
+        }
+        if rules.origins.is_empty() || key_accepted {
+            return Ok(());
+        }
+        match request_origin(headers) {
+            Some(origin) if rules.origins.iter().any(|pattern| pattern.matches(&origin)) => Ok(()),
+            Some(origin) => Err(AccessError::Forbidden(format!("Origin {} is not allowed", origin))),
+            None => Err(AccessError::Forbidden("Origin is required".to_string())),
+        }
+    }
+
+    /// Check that the request sends every parameter its project's schema requires
+    pub fn validate(&self, params: &HashMap<String, String>) -> Result<(), String> {
+        let Some(rules) = params.get("project").and_then(|project| self.projects.get(project)) else {
+            return Ok(());
+        };
+        match rules.required.iter().find(|name| !params.contains_key(name.as_str())) {
+            Some(name) => Err(format!("Missing required field: {}", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the event of `params` is kept by its project's `sample_rate`
+    ///
+    /// Sampling is by visitor: the `cookie` (or, without one, the event `id`) is hashed
+    /// with the project, so a visitor's events are all kept or all dropped. Events with
+    /// neither are sampled at random.
+    ///
+    /// Only `/track/` samples: profile updates from `/identify` and the `/update` records
+    /// of already accepted events are always kept.
+    pub fn is_sampled(&self, params: &HashMap<String, String>) -> bool {
+        let Some(project) = params.get("project") else {
+            return true;
+        };
+        let Some(rules) = self.projects.get(project) else {
+            return true;
+        };
+        let sample_rate = f64::from_bits(rules.sample_rate.load(Ordering::Relaxed));
+        if sample_rate >= 1.0 {
+            return true;
+        }
+        let visitor = params
+            .get("cookie")
+            .or_else(|| params.get("id"))
+            .cloned()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        sample_position(project, &visitor) < sample_rate
+    }
+
+    /// Change the sample rate of a configured project
+    ///
+    /// Returns `false` if `project` has no entry in the registry.
+    pub fn set_sample_rate(&self, project: &str, sample_rate: f64) -> bool {
+        match self.projects.get(project) {
+            Some(rules) => {
+                rules.sample_rate.store(sample_rate.to_bits(), Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Position of `visitor` in [0, 1), stable for a project
+fn sample_position(project: &str, visitor: &str) -> f64 {
+    let digest = Sha256::new()
+        .chain_update(project.as_bytes())
+        .chain_update([0u8])
+        .chain_update(visitor.as_bytes())
+        .finalize();
+    let bits = u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"));
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectSchemaConfig;
+
+    fn registry() -> ProjectRegistry {
+        let projects = HashMap::from([
+            (
+                "shop".to_string(),
+                ProjectConfig {
+                    api_keys: vec!["shop-key".to_string()],
+                    allowed_origins: vec!["https://shop.example.com".to_string()],
+                    sample_rate: Some(0.5),
+                    schema: ProjectSchemaConfig {
+                        required: vec!["cookie".to_string()],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ),
+            (
+                "blog".to_string(),
+                ProjectConfig {
+                    allowed_origins: vec!["https://*.example.com".to_string(), "http://localhost:3000".to_string()],
+                    ..Default::default()
+                },
+            ),
+        ]);
+        ProjectRegistry::from_config(&projects)
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        assert!(OriginPattern::parse("*").unwrap().matches("https://any.test"));
+        let exact = OriginPattern::parse("HTTPS://Shop.Example.com/").unwrap();
+        assert!(exact.matches("https://shop.example.com"));
+        assert!(!exact.matches("http://shop.example.com"));
+        let wildcard = OriginPattern::parse("https://*.example.com").unwrap();
+        assert!(wildcard.matches("https://blog.example.com"));
+        assert!(wildcard.matches("https://a.b.example.com"));
+        assert!(!wildcard.matches("https://example.com"));
+        assert!(!wildcard.matches("https://badexample.com"));
+        assert!(OriginPattern::parse("example.com").is_err());
+        assert!(OriginPattern::parse("ftp://example.com").is_err());
+        assert!(OriginPattern::parse("https://example.com/path").is_err());
+    }
+
+    #[test]
+    fn test_request_origin_falls_back_to_referer() {
+        assert_eq!(
+            request_origin(&headers(&[("origin", "https://Shop.example.com")])).as_deref(),
+            Some("https://shop.example.com")
+        );
+        assert_eq!(
+            request_origin(&headers(&[("referer", "https://shop.example.com:8443/cart?x=1")])).as_deref(),
+            Some("https://shop.example.com:8443")
+        );
+        assert_eq!(request_origin(&headers(&[("origin", "null")])), None);
+        assert_eq!(request_origin(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_authorize() {
+        let registry = registry();
+
+        assert!(registry.authorize(Some("other"), &HeaderMap::new()).is_ok());
+        assert!(registry.authorize(None, &HeaderMap::new()).is_ok());
+
+        // API key required, origin check skipped for valid keys
+        assert!(matches!(
+            registry.authorize(Some("shop"), &headers(&[("origin", "https://shop.example.com")])),
+            Err(AccessError::Unauthorized(_))
+        ));
+        assert!(registry.authorize(Some("shop"), &headers(&[("x-api-key", "shop-key")])).is_ok());
+        assert!(matches!(
+            registry.authorize(Some("shop"), &headers(&[("x-api-key", "wrong")])),
+            Err(AccessError::Unauthorized(_))
+        ));
+
+        // Origins only
+        assert!(registry.authorize(Some("blog"), &headers(&[("origin", "https://www.example.com")])).is_ok());
+        assert!(registry.authorize(Some("blog"), &headers(&[("referer", "http://localhost:3000/post/1")])).is_ok());
+        assert_eq!(
+            registry.authorize(Some("blog"), &headers(&[("origin", "https://evil.test")])),
+            Err(AccessError::Forbidden("Origin https://evil.test is not allowed".to_string()))
+        );
+        assert!(matches!(
+            registry.authorize(Some("blog"), &HeaderMap::new()),
+            Err(AccessError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_required_params() {
+        let registry = registry();
+        let mut params = HashMap::from([("project".to_string(), "shop".to_string())]);
+        assert_eq!(registry.validate(&params), Err("Missing required field: cookie".to_string()));
+        params.insert("cookie".to_string(), "v1".to_string());
+        assert!(registry.validate(&params).is_ok());
+        assert!(registry.validate(&HashMap::from([("project".to_string(), "blog".to_string())])).is_ok());
+    }
+
+    #[test]
+    fn test_sampling_is_stable_per_visitor() {
+        let registry = registry();
+        let params = |project: &str, cookie: &str| {
+            HashMap::from([
+                ("project".to_string(), project.to_string()),
+                ("cookie".to_string(), cookie.to_string()),
+            ])
+        };
+
+        let kept = (0..1000)
+            .filter(|i| registry.is_sampled(&params("shop", &format!("visitor-{}", i))))
+            .count();
+        assert!((400..600).contains(&kept), "kept {} of 1000", kept);
+
+        let visitor = params("shop", "visitor-7");
+        let first = registry.is_sampled(&visitor);
+        assert!((0..10).all(|_| registry.is_sampled(&visitor) == first));
+
+        assert!((0..100).all(|i| registry.is_sampled(&params("blog", &format!("visitor-{}", i)))));
+    }
+    #[test]
+    fn test_set_sample_rate() {
+        let registry = registry();
+        let visitor = HashMap::from([
+            ("project".to_string(), "shop".to_string()),
+            ("cookie".to_string(), "visitor-7".to_string()),
+        ]);
+        assert!(registry.set_sample_rate("shop", 0.0));
+        assert!(!registry.is_sampled(&visitor));
+        assert!(registry.set_sample_rate("shop", 1.0));
+        assert!(registry.is_sampled(&visitor));
+        assert!(!registry.set_sample_rate("unknown", 0.5));
+    }
+}
@@ -0,0 +1,268 @@
+// Event replay
+// This module re-sends events stored as JSON lines (file sink / fallback output) through a streaming service
+
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::streaming::file::decode_event_line;
+use crate::streaming::{StreamingError, StreamingService};
+
+/// Options controlling a replay run
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    /// Maximum events sent per second (None for unlimited)
+    pub rate_per_sec: Option<u32>,
+    /// Number of leading lines to skip, used to resume an interrupted replay
+    pub skip_lines: usize,
+    /// Report progress every N processed lines (0 disables progress reports)
+    pub progress_every: usize,
+    /// Decode events without sending them
+    pub dry_run: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            rate_per_sec: None,
+            skip_lines: 0,
+            progress_every: 1000,
+            dry_run: false,
+        }
+    }
+}
+
+/// Progress of a replay run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// Lines read from the file so far (including skipped and invalid lines)
+    pub lines_read: usize,
+    /// Events successfully sent (or decoded, in dry-run mode)
+    pub sent: usize,
+    /// Lines that could not be decoded as events
+    pub invalid: usize,
+}
+
+/// Error returned when a replay stops early
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The input file could not be opened or read
+    IoError(std::io::Error),
+    /// The streaming service rejected an event; `line` is the 1-based line to resume from
+    SendError {
+        line: usize,
+        progress: ReplayProgress,
+        error: StreamingError,
+    },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::IoError(e) => write!(f, "Failed to read replay file: {}", e),
+            ReplayError::SendError { line, progress, error } => write!(
+                f,
+                "Send failed at line {} after {} events (resume with --skip {}): {}",
+                line,
+                progress.sent,
+                line - 1,
+                error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::IoError(err)
+    }
+}
+
+/// Re-send every event in a JSON-lines file through `target`
+///
+/// Lines are sent in order. Undecodable lines are counted and skipped. The replay stops
+/// at the first send failure so it can be resumed from the failing line.
+///
+/// # Arguments
+/// * `path` - JSON-lines file written by the file sink or fallback sink
+/// * `target` - Streaming service to send events to
+/// * `options` - Rate limit, resume offset, progress interval and dry-run flag
+/// * `on_progress` - Called every `options.progress_every` lines and once at the end
+///
+/// # Returns
+/// The final progress counters
+pub async fn replay_file<F>(
+    path: &Path,
+    target: &dyn StreamingService,
+    options: &ReplayOptions,
+    mut on_progress: F,
+) -> Result<ReplayProgress, ReplayError>
+where
+    F: FnMut(&ReplayProgress),
+{
+    let file = tokio::fs::File::open(path).await?;
+    let mut lines = BufReader::new(file).lines();
+    let mut progress = ReplayProgress::default();
+
+    let mut ticker = options
+        .rate_per_sec
+        .filter(|rate| *rate > 0)
+        .map(|rate| {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1) / rate);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker
+        });
+
+    while let Some(line) = lines.next_line().await? {
+        progress.lines_read += 1;
+        let line_number = progress.lines_read;
+
+        if line_number > options.skip_lines && !line.trim().is_empty() {
+            match decode_event_line(&line) {
+                Ok(event) => {
+                    if !options.dry_run {
+                        if let Some(ticker) = ticker.as_mut() {
+                            ticker.tick().await;
+                        }
+                        if let Err(error) = target.send_event(&event).await {
+                            return Err(ReplayError::SendError {
+                                line: line_number,
+                                progress,
+                                error,
+                            });
+                        }
+                    }
+                    progress.sent += 1;
+                }
+                Err(e) => {
+                    progress.invalid += 1;
+                    tracing::warn!(
+                        path = %path.display(),
+                        line = line_number,
+                        error = %e,
+                        "Skipping unreadable line"
+                    );
+                }
+            }
+        }
+
+        if options.progress_every > 0 && line_number % options.progress_every == 0 {
+            on_progress(&progress);
+        }
+    }
+
+    on_progress(&progress);
+    Ok(progress)
+}
+
+/// Format an events-per-second rate for progress output
+pub fn format_rate(sent: usize, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return "-".to_string();
+    }
+    format!("{:.1}/s", sent as f64 / secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::FileStreaming;
+    use crate::transformer::AnalyticsEvent;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    struct Collector {
+        ids: Mutex<Vec<String>>,
+        fail_on: Option<String>,
+    }
+
+    #[async_trait]
+    impl StreamingService for Collector {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            let id = event.id.clone().unwrap_or_default();
+            if self.fail_on.as_deref() == Some(id.as_str()) {
+                return Err(StreamingError::SendError("broker down".to_string()));
+            }
+            self.ids.lock().unwrap().push(id);
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    async fn write_events(path: &Path, ids: &[&str]) {
+        let sink = FileStreaming::new(path);
+        for id in ids {
+            let event = AnalyticsEvent {
+                id: Some(id.to_string()),
+                event: "pageview".to_string(),
+                ..Default::default()
+            };
+            sink.send_event(&event).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replays_all_events_and_skips_invalid_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        write_events(&path, &["a", "b"]).await;
+        tokio::fs::write(
+            &path,
+            format!("{}not json\n", tokio::fs::read_to_string(&path).await.unwrap()),
+        )
+        .await
+        .unwrap();
+
+        let target = Collector { ids: Mutex::new(Vec::new()), fail_on: None };
+        let mut reports = 0;
+        let progress = replay_file(&path, &target, &ReplayOptions::default(), |_| reports += 1)
+            .await
+            .unwrap();
+
+        assert_eq!(progress, ReplayProgress { lines_read: 3, sent: 2, invalid: 1 });
+        assert_eq!(*target.ids.lock().unwrap(), vec!["a", "b"]);
+        assert_eq!(reports, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stops_at_failure_and_resumes_with_skip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        write_events(&path, &["a", "b", "c"]).await;
+
+        let target = Collector { ids: Mutex::new(Vec::new()), fail_on: Some("b".to_string()) };
+        let err = replay_file(&path, &target, &ReplayOptions::default(), |_| {})
+            .await
+            .unwrap_err();
+        let ReplayError::SendError { line, .. } = err else {
+            panic!("expected send error");
+        };
+        assert_eq!(line, 2);
+
+        let target = Collector { ids: Mutex::new(Vec::new()), fail_on: None };
+        let options = ReplayOptions { skip_lines: line - 1, ..Default::default() };
+        replay_file(&path, &target, &options, |_| {}).await.unwrap();
+        assert_eq!(*target.ids.lock().unwrap(), vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_does_not_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        write_events(&path, &["a"]).await;
+
+        let target = Collector { ids: Mutex::new(Vec::new()), fail_on: None };
+        let options = ReplayOptions { dry_run: true, ..Default::default() };
+        let progress = replay_file(&path, &target, &options, |_| {}).await.unwrap();
+
+        assert_eq!(progress.sent, 1);
+        assert!(target.ids.lock().unwrap().is_empty());
+    }
+}
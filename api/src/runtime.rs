@@ -0,0 +1,511 @@
+// Runtime-tunable settings
+// This module changes the log level, sampling rates, filter rules, feature flags and circuit breaker limits of a running collector
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::{parse_duration, validate_runtime_settings, Config, EnricherKind};
+use crate::enrichment::pipeline::EnrichmentPipeline;
+use crate::features::FeatureFlags;
+use crate::filter::{EventFilter, SharedEventFilter};
+use crate::logging::LogLevelHandle;
+use crate::projects::ProjectRegistry;
+use crate::streaming::StreamingService;
+
+/// Number of changes kept in the journal
+const JOURNAL_LIMIT: usize = 100;
+
+/// Why a settings change was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// The setting does not exist or cannot be changed at runtime (HTTP 400)
+    NotTunable(String),
+    /// The new value is not valid (HTTP 400)
+    Invalid(String),
+    /// The change could not be written to `server.runtime_settings_path` (HTTP 500)
+    Persist(String),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::NotTunable(path) => write!(f, "{} cannot be changed at runtime", path),
+            RuntimeError::Invalid(msg) => write!(f, "Invalid setting: {}", msg),
+            RuntimeError::Persist(msg) => write!(f, "Failed to persist settings: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// A change applied through the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChange {
+    /// When the change was applied (RFC 3339)
+    pub at: String,
+    /// The settings changed, as sent
+    pub changes: Value,
+    /// Whether the change was written to `server.runtime_settings_path`
+    pub persisted: bool,
+}
+
+/// Which live components a change touches
+#[derive(Default)]
+struct Touched {
+    log_level: bool,
+    filters: bool,
+    sample_rates: bool,
+    features: bool,
+    fallback: bool,
+    http: bool,
+}
+
+struct State {
+    config: Config,
+    journal: Vec<SettingChange>,
+}
+
+/// Settings of the running collector that can be changed without a restart
+///
+/// Changes apply to the live components immediately and are journaled. They last
+/// until the process restarts, unless persisted to `server.runtime_settings_path`,
+/// which is merged over the configuration at startup.
+pub struct RuntimeSettings {
+    state: Mutex<State>,
+    persist_path: Option<String>,
+    log_level: Option<LogLevelHandle>,
+    event_filter: Arc<SharedEventFilter>,
+    projects: Arc<ProjectRegistry>,
+    features: Arc<FeatureFlags>,
+    streaming_service: Arc<dyn StreamingService>,
+    enrichment: Arc<EnrichmentPipeline>,
+}
+
+impl RuntimeSettings {
+    pub fn new(
+        config: &Config,
+        event_filter: Arc<SharedEventFilter>,
+        projects: Arc<ProjectRegistry>,
+        features: Arc<FeatureFlags>,
+        streaming_service: Arc<dyn StreamingService>,
+        enrichment: Arc<EnrichmentPipeline>,
+    ) -> Self {
+        RuntimeSettings {
+            state: Mutex::new(State {
+                config: config.clone(),
+                journal: Vec::new(),
+            }),
+            persist_path: config.server.runtime_settings_path.clone(),
+            log_level: None,
+            event_filter,
+            projects,
+            features,
+            streaming_service,
+            enrichment,
+        }
+    }
+
+    /// Change the level of the installed log subscriber along with `logging.level`
+    pub fn with_log_level(mut self, handle: LogLevelHandle) -> Self {
+        self.log_level = Some(handle);
+        self
+    }
+
+    /// Current values of the tunable settings
+    ///
+    /// The circuit breaker settings are only listed when the fallback sink or the
+    /// HTTP enricher is configured.
+    pub fn settings(&self) -> Value {
+        let state = self.lock();
+        let config = &state.config;
+        let mut ids: Vec<&String> = config.projects.keys().collect();
+        ids.sort();
+        let projects: Map<String, Value> = ids
+            .into_iter()
+            .map(|id| (id.clone(), json!({ "sample_rate": config.projects[id].sample_rate })))
+            .collect();
+
+        let mut settings = json!({
+            "logging": { "level": config.logging.level },
+            "filters": config.filters,
+            "projects": projects,
+            "features": config.features,
+        });
+        if let Some(fallback) = &config.streaming.fallback {
+            settings["streaming"] = json!({
+                "fallback": {
+                    "failure_threshold": fallback.failure_threshold,
+                    "open_secs": fallback.open_secs,
+                }
+            });
+        }
+        if let Some(http) = &config.enrichment.http {
+            settings["enrichment"] = json!({
+                "http": {
+                    "failure_threshold": http.failure_threshold,
+                    "open_secs": http.open_secs,
+                }
+            });
+        }
+        settings
+    }
+
+    /// Changes applied since startup, oldest first (the last 100)
+    pub fn journal(&self) -> Vec<SettingChange> {
+        self.lock().journal.clone()
+    }
+
+    /// Apply `changes`, a document shaped like the configuration holding only the
+    /// settings to change, e.g. `{"logging": {"level": "debug"}}`
+    ///
+    /// With `persist`, the changes are also merged into `server.runtime_settings_path`
+    /// so they survive a restart. Nothing is applied unless every setting is tunable
+    /// and the result is valid.
+    ///
+    /// # Errors
+    /// Returns a `RuntimeError` naming the first setting that cannot be changed
+    pub fn update(&self, changes: Value, persist: bool) -> Result<SettingChange, RuntimeError> {
+        if !changes.is_object() {
+            return Err(RuntimeError::Invalid("expected an object of settings".to_string()));
+        }
+        let mut state = self.lock();
+        let mut config = state.config.clone();
+        let mut touched = Touched::default();
+        let mut leaves = Vec::new();
+        collect_leaves(&changes, &mut Vec::new(), &mut leaves);
+        for (path, value) in leaves {
+            apply_setting(&mut config, &path, value, &mut touched)?;
+        }
+        let geoip_missing = config.enrichment.pipeline.contains(&EnricherKind::Geoip)
+            && !self.enrichment.stage_names().contains(&"geoip");
+        if config.features.geoip && !state.config.features.geoip && geoip_missing {
+            return Err(RuntimeError::Invalid(
+                "features.geoip: the GeoIP database was not loaded at startup, restart to switch it on".to_string(),
+            ));
+        }
+        validate_runtime_settings(&config).map_err(|e| RuntimeError::Invalid(e.to_string()))?;
+
+        if persist {
+            let path = self
+                .persist_path
+                .as_deref()
+                .ok_or_else(|| RuntimeError::Invalid("server.runtime_settings_path is not configured".to_string()))?;
+            persist_changes(Path::new(path), &changes).map_err(RuntimeError::Persist)?;
+        }
+
+        self.apply_live(&config, &touched);
+        let change = SettingChange {
+            at: chrono::Utc::now().to_rfc3339(),
+            changes,
+            persisted: persist,
+        };
+        tracing::info!(changes = %change.changes, persisted = persist, "Runtime settings changed");
+        state.config = config;
+        if state.journal.len() == JOURNAL_LIMIT {
+            state.journal.remove(0);
+        }
+        state.journal.push(change.clone());
+        Ok(change)
+    }
+
+    fn apply_live(&self, config: &Config, touched: &Touched) {
+        if touched.log_level {
+            if let Some(handle) = &self.log_level {
+                if let Err(e) = handle.set_level(&config.logging.level) {
+                    tracing::error!(error = %e, "Failed to change the log level");
+                }
+            }
+        }
+        if touched.filters {
+            // Validated above, so the rules always compile
+            if let Ok(filter) = EventFilter::from_config(&config.filters) {
+                self.event_filter.replace(filter);
+            }
+        }
+        if touched.sample_rates {
+            for (id, project) in &config.projects {
+                self.projects.set_sample_rate(id, project.sample_rate.unwrap_or(1.0));
+            }
+        }
+        if touched.features {
+            self.features.apply(&config.features);
+        }
+        if touched.fallback {
+            if let (Some(fallback), Some(breaker)) = (&config.streaming.fallback, self.streaming_service.circuit_breaker()) {
+                breaker.set_limits(fallback.failure_threshold, Duration::from_secs(fallback.open_secs));
+            }
+        }
+        if touched.http {
+            if let (Some(http), Some(breaker)) = (&config.enrichment.http, self.enrichment.circuit_breaker("http")) {
+                breaker.set_limits(http.failure_threshold, Duration::from_secs(http.open_secs));
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Every non-object value of `value` with its path of keys
+fn collect_leaves<'a>(value: &'a Value, path: &mut Vec<String>, leaves: &mut Vec<(Vec<String>, &'a Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                path.push(key.clone());
+                collect_leaves(value, path, leaves);
+                path.pop();
+            }
+        }
+        _ => leaves.push((path.clone(), value)),
+    }
+}
+
+/// Set the setting at `path` in `config`
+fn apply_setting(config: &mut Config, path: &[String], value: &Value, touched: &mut Touched) -> Result<(), RuntimeError> {
+    let field = path.join(".");
+    let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+    match keys.as_slice() {
+        ["logging", "level"] => {
+            config.logging.level = parse(&field, value)?;
+            touched.log_level = true;
+        }
+        ["filters", key] => {
+            let mut filters = serde_json::to_value(&config.filters).map_err(|e| RuntimeError::Invalid(e.to_string()))?;
+            match filters.get_mut(*key) {
+                Some(setting) => *setting = value.clone(),
+                None => return Err(RuntimeError::NotTunable(field)),
+            }
+            config.filters = parse(&field, &filters)?;
+            touched.filters = true;
+        }
+        ["projects", id, "sample_rate"] => {
+            let project = config.projects.get_mut(*id).ok_or_else(|| RuntimeError::NotTunable(field.clone()))?;
+            project.sample_rate = parse(&field, value)?;
+            touched.sample_rates = true;
+        }
+        ["features", name] => {
+            let mut features = serde_json::to_value(&config.features).map_err(|e| RuntimeError::Invalid(e.to_string()))?;
+            match features.get_mut(*name) {
+                Some(setting) => *setting = value.clone(),
+                None => return Err(RuntimeError::NotTunable(field)),
+            }
+            config.features = parse(&field, &features)?;
+            touched.features = true;
+        }
+        ["streaming", "fallback", setting] => {
+            let fallback = config.streaming.fallback.as_mut().ok_or_else(|| RuntimeError::NotTunable(field.clone()))?;
+            match *setting {
+                "failure_threshold" => fallback.failure_threshold = parse(&field, value)?,
+                "open_secs" => fallback.open_secs = parse_secs(&field, value)?,
+                _ => return Err(RuntimeError::NotTunable(field)),
+            }
+            touched.fallback = true;
+        }
+        ["enrichment", "http", setting] => {
+            let http = config.enrichment.http.as_mut().ok_or_else(|| RuntimeError::NotTunable(field.clone()))?;
+            match *setting {
+                "failure_threshold" => http.failure_threshold = parse(&field, value)?,
+                "open_secs" => http.open_secs = parse_secs(&field, value)?,
+                _ => return Err(RuntimeError::NotTunable(field)),
+            }
+            touched.http = true;
+        }
+        _ => return Err(RuntimeError::NotTunable(field)),
+    }
+    Ok(())
+}
+
+fn parse<T: DeserializeOwned>(field: &str, value: &Value) -> Result<T, RuntimeError> {
+    serde_json::from_value(value.clone()).map_err(|e| RuntimeError::Invalid(format!("{}: {}", field, e)))
+}
+
+/// A duration in seconds: `30` or `"30s"`, `"5m"`
+fn parse_secs(field: &str, value: &Value) -> Result<u64, RuntimeError> {
+    match value {
+        Value::String(text) => parse_duration(text, 1_000).map_err(|e| RuntimeError::Invalid(format!("{}: {}", field, e))),
+        _ => parse(field, value),
+    }
+}
+
+/// Merge `changes` into the YAML file at `path`, replacing it atomically
+fn persist_changes(path: &Path, changes: &Value) -> Result<(), String> {
+    let mut document = match std::fs::read_to_string(path) {
+        Ok(contents) if !contents.trim().is_empty() => {
+            serde_yaml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        Ok(_) => Value::Object(Map::new()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Value::Object(Map::new()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    merge_json(&mut document, changes.clone());
+    let contents = serde_yaml::to_string(&document).map_err(|e| e.to_string())?;
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    std::fs::rename(&temporary, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Merge `overlay` into `base`, key by key for objects
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FallbackConfig, ProjectConfig};
+    use crate::enrichment::user_agent::{UserAgentParser, WootheeParser};
+    use crate::filter::FilterContext;
+    use crate::streaming::{CircuitBreaker, FallbackStreaming, FileStreaming, StdoutStreaming};
+    use std::collections::HashMap;
+
+    fn runtime(config: &Config) -> RuntimeSettings {
+        let filter = Arc::new(SharedEventFilter::new(EventFilter::from_config(&config.filters).unwrap()));
+        let projects = Arc::new(ProjectRegistry::from_config(&config.projects));
+        let primary: Arc<dyn StreamingService> = Arc::new(StdoutStreaming::new());
+        let fallback = config.streaming.fallback.as_ref().unwrap();
+        let breaker = CircuitBreaker::new(fallback.failure_threshold, Duration::from_secs(fallback.open_secs));
+        let streaming: Arc<dyn StreamingService> =
+            Arc::new(FallbackStreaming::new(primary, FileStreaming::new(&fallback.path), breaker));
+        let features = Arc::new(FeatureFlags::from_config(&config.features));
+        let enrichment = Arc::new(
+            EnrichmentPipeline::from_config(config, Arc::new(WootheeParser::new()), None).with_features(features.clone()),
+        );
+        RuntimeSettings::new(config, filter, projects, features, streaming, enrichment)
+    }
+
+    fn config(dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.logging.level = "info".to_string();
+        config.streaming.fallback = Some(FallbackConfig {
+            path: dir.join("fallback.jsonl").to_str().unwrap().to_string(),
+            failure_threshold: 5,
+            open_secs: 30,
+            replay_interval_secs: 5,
+        });
+        config.projects = HashMap::from([(
+            "shop".to_string(),
+            ProjectConfig {
+                sample_rate: Some(0.5),
+                ..Default::default()
+            },
+        )]);
+        config.server.runtime_settings_path = Some(dir.join("runtime.yaml").to_str().unwrap().to_string());
+        config
+    }
+
+    #[test]
+    fn test_update_applies_and_journals_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = runtime(&config(dir.path()));
+
+        runtime
+            .update(
+                json!({
+                    "filters": { "drop_event_names": ["debug_*"] },
+                    "projects": { "shop": { "sample_rate": 0.0 } },
+                    "streaming": { "fallback": { "failure_threshold": 2, "open_secs": "2m" } },
+                }),
+                false,
+            )
+            .unwrap();
+
+        let settings = runtime.settings();
+        assert_eq!(settings["filters"]["drop_event_names"], json!(["debug_*"]));
+        assert_eq!(settings["projects"]["shop"]["sample_rate"], json!(0.0));
+        assert_eq!(settings["streaming"]["fallback"], json!({ "failure_threshold": 2, "open_secs": 120 }));
+
+        let breaker = runtime.streaming_service.circuit_breaker().unwrap();
+        assert_eq!(breaker.failure_threshold(), 2);
+        assert_eq!(breaker.open_duration(), Duration::from_secs(120));
+        let params = HashMap::from([
+            ("project".to_string(), "shop".to_string()),
+            ("cookie".to_string(), "visitor".to_string()),
+        ]);
+        assert!(!runtime.projects.is_sampled(&params));
+        let event = crate::transformer::AnalyticsEvent {
+            event: "debug_click".to_string(),
+            ..Default::default()
+        };
+        let context = FilterContext {
+            client_ip: "203.0.113.7".parse().unwrap(),
+            user_agent: &WootheeParser::new().parse(""),
+        };
+        assert!(runtime.event_filter.evaluate(&event, &context).is_some());
+
+        let journal = runtime.journal();
+        assert_eq!(journal.len(), 1);
+        assert!(!journal[0].persisted);
+        assert!(!dir.path().join("runtime.yaml").exists());
+    }
+
+    #[test]
+    fn test_update_rejects_untunable_and_invalid_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = runtime(&config(dir.path()));
+
+        let result = runtime.update(json!({ "server": { "port": 9090 } }), false);
+        assert_eq!(result.unwrap_err(), RuntimeError::NotTunable("server.port".to_string()));
+        let result = runtime.update(json!({ "projects": { "blog": { "sample_rate": 0.1 } } }), false);
+        assert_eq!(result.unwrap_err(), RuntimeError::NotTunable("projects.blog.sample_rate".to_string()));
+
+        let result = runtime.update(json!({ "logging": { "level": "loud" }, "filters": { "drop_bots": true } }), false);
+        assert!(matches!(result, Err(RuntimeError::Invalid(msg)) if msg.contains("logging.level")));
+        assert_eq!(runtime.settings()["filters"]["drop_bots"], json!(false));
+        assert!(runtime.journal().is_empty());
+    }
+
+    #[test]
+    fn test_update_switches_features() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.features.geoip = false;
+        let runtime = runtime(&config);
+
+        runtime.update(json!({ "features": { "sessions": false, "schema_validation": false } }), false).unwrap();
+        assert!(!runtime.features.stage_enabled("session"));
+        assert!(!runtime.features.schema_validation());
+        assert_eq!(runtime.settings()["features"]["sessions"], json!(false));
+
+        // The GeoIP database was never loaded, so the stage cannot be switched on
+        let result = runtime.update(json!({ "features": { "geoip": true } }), false);
+        assert!(matches!(result, Err(RuntimeError::Invalid(msg)) if msg.starts_with("features.geoip")));
+        let result = runtime.update(json!({ "features": { "unknown": false } }), false);
+        assert_eq!(result.unwrap_err(), RuntimeError::NotTunable("features.unknown".to_string()));
+    }
+
+    #[test]
+    fn test_persisted_changes_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = runtime(&config(dir.path()));
+
+        runtime.update(json!({ "logging": { "level": "debug" } }), true).unwrap();
+        runtime.update(json!({ "filters": { "drop_bots": true } }), true).unwrap();
+
+        let contents = std::fs::read_to_string(dir.path().join("runtime.yaml")).unwrap();
+        let persisted: serde_yaml::Value = serde_yaml::from_str(&contents).unwrap();
+        let expected: serde_yaml::Value = serde_yaml::from_str("logging: {level: debug}\nfilters: {drop_bots: true}\n").unwrap();
+        assert_eq!(persisted, expected);
+        assert!(runtime.journal().iter().all(|change| change.persisted));
+    }
+}
@@ -0,0 +1,296 @@
+// Runtime statistics
+// This module samples tokio scheduler and process resource usage for the /admin/runtime endpoint
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::metrics::{Histogram, LATENCY_BUCKETS};
+
+/// Time between samples of the scheduler delay and CPU usage
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Clock ticks per second of the CPU times in `/proc/self/stat` (`USER_HZ`, fixed on Linux)
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Tokio runtime statistics
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TokioStats {
+    /// Worker threads of the runtime
+    pub workers: usize,
+    /// Tasks spawned and not yet completed
+    pub alive_tasks: usize,
+    /// Tasks waiting in the global queue for a worker
+    pub global_queue_depth: usize,
+    /// Time the workers spent running tasks since start, over all workers
+    pub busy_secs: f64,
+    pub scheduler_delay: SchedulerDelay,
+}
+
+/// Time a newly spawned task waits before it first runs
+///
+/// Measured by spawning a probe task every `SAMPLE_INTERVAL`; a growing delay means
+/// the workers are saturated or a task blocks its thread.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SchedulerDelay {
+    pub samples: u64,
+    pub last_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Resource usage of the process (Linux only)
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ProcessStats {
+    /// Resident set size
+    pub rss_bytes: Option<u64>,
+    /// Highest resident set size since start
+    pub peak_rss_bytes: Option<u64>,
+    pub virtual_bytes: Option<u64>,
+    pub threads: Option<u64>,
+    pub open_fds: Option<u64>,
+    /// CPU time spent in user mode since start
+    pub cpu_user_secs: Option<f64>,
+    /// CPU time spent in the kernel since start
+    pub cpu_system_secs: Option<f64>,
+    /// CPU usage over the last sample interval, 100 per fully used core
+    pub cpu_percent: Option<f64>,
+}
+
+/// Statistics returned by /admin/runtime
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeSnapshot {
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    pub version: &'static str,
+    /// Absent when not called from a tokio runtime
+    pub tokio: Option<TokioStats>,
+    pub process: ProcessStats,
+}
+
+/// CPU time at one sample
+#[derive(Debug, Clone, Copy)]
+struct CpuSample {
+    at: Instant,
+    cpu_secs: f64,
+}
+
+/// Collects runtime statistics since the process started
+pub struct RuntimeStats {
+    started_at: DateTime<Utc>,
+    started: Instant,
+    scheduler_delay: Histogram,
+    /// Last and highest scheduler delay, in microseconds
+    last_delay_us: AtomicU64,
+    max_delay_us: AtomicU64,
+    /// CPU time at the last two samples, for `cpu_percent`
+    cpu_samples: Mutex<(Option<CpuSample>, Option<CpuSample>)>,
+}
+
+impl RuntimeStats {
+    /// Start collecting; uptime counts from now
+    pub fn new() -> Self {
+        RuntimeStats {
+            started_at: Utc::now(),
+            started: Instant::now(),
+            scheduler_delay: Histogram::new(&LATENCY_BUCKETS),
+            last_delay_us: AtomicU64::new(0),
+            max_delay_us: AtomicU64::new(0),
+            cpu_samples: Mutex::new((None, None)),
+        }
+    }
+
+    /// Time since the statistics were created
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Record the delay of one scheduler probe
+    pub fn record_scheduler_delay(&self, delay: Duration) {
+        let micros = delay.as_micros() as u64;
+        self.scheduler_delay.observe_duration(delay);
+        self.last_delay_us.store(micros, Ordering::Relaxed);
+        self.max_delay_us.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// Record the CPU time used so far
+    fn record_cpu(&self, cpu_secs: f64) {
+        let mut samples = self.cpu_samples.lock().unwrap_or_else(|e| e.into_inner());
+        *samples = (samples.1, Some(CpuSample { at: Instant::now(), cpu_secs }));
+    }
+
+    /// CPU usage between the last two samples
+    fn cpu_percent(&self) -> Option<f64> {
+        let samples = self.cpu_samples.lock().unwrap_or_else(|e| e.into_inner());
+        let (Some(previous), Some(latest)) = *samples else {
+            return None;
+        };
+        let elapsed = latest.at.duration_since(previous.at).as_secs_f64();
+        (elapsed > 0.0).then(|| (latest.cpu_secs - previous.cpu_secs).max(0.0) / elapsed * 100.0)
+    }
+
+    /// Scheduler delay summary
+    pub fn scheduler_delay(&self) -> SchedulerDelay {
+        let samples = self.scheduler_delay.count();
+        SchedulerDelay {
+            samples,
+            last_ms: micros_to_ms(self.last_delay_us.load(Ordering::Relaxed)),
+            mean_ms: if samples == 0 {
+                0.0
+            } else {
+                self.scheduler_delay.sum() * 1000.0 / samples as f64
+            },
+            max_ms: micros_to_ms(self.max_delay_us.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Current statistics
+    pub fn snapshot(&self) -> RuntimeSnapshot {
+        let tokio = tokio::runtime::Handle::try_current().ok().map(|handle| {
+            let metrics = handle.metrics();
+            let workers = metrics.num_workers();
+            TokioStats {
+                workers,
+                alive_tasks: metrics.num_alive_tasks(),
+                global_queue_depth: metrics.global_queue_depth(),
+                busy_secs: (0..workers)
+                    .map(|worker| metrics.worker_total_busy_duration(worker).as_secs_f64())
+                    .sum(),
+                scheduler_delay: self.scheduler_delay(),
+            }
+        });
+        let mut process = process_stats();
+        process.cpu_percent = self.cpu_percent();
+        RuntimeSnapshot {
+            started_at: self.started_at,
+            uptime_secs: self.uptime().as_secs(),
+            version: env!("CARGO_PKG_VERSION"),
+            tokio,
+            process,
+        }
+    }
+
+    /// Spawn a background task sampling the scheduler delay and CPU usage every
+    /// `SAMPLE_INTERVAL`
+    ///
+    /// The task runs until the runtime shuts down.
+    pub fn spawn_sampler(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let stats = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let spawned = Instant::now();
+                if let Ok(delay) = tokio::spawn(async move { spawned.elapsed() }).await {
+                    stats.record_scheduler_delay(delay);
+                }
+                let process = process_stats();
+                if let (Some(user), Some(system)) = (process.cpu_user_secs, process.cpu_system_secs) {
+                    stats.record_cpu(user + system);
+                }
+            }
+        })
+    }
+}
+
+impl Default for RuntimeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read the process resource usage from `/proc/self`; fields are `None` elsewhere
+pub fn process_stats() -> ProcessStats {
+    let mut stats = std::fs::read_to_string("/proc/self/status")
+        .map(|status| parse_status(&status))
+        .unwrap_or_default();
+    if let Some((user, system)) = std::fs::read_to_string("/proc/self/stat")
+        .ok()
+        .and_then(|stat| parse_cpu_times(&stat))
+    {
+        stats.cpu_user_secs = Some(user);
+        stats.cpu_system_secs = Some(system);
+    }
+    stats.open_fds = std::fs::read_dir("/proc/self/fd")
+        .ok()
+        .map(|entries| entries.count() as u64);
+    stats
+}
+
+/// Memory and thread counts from the contents of `/proc/self/status`
+fn parse_status(status: &str) -> ProcessStats {
+    let mut stats = ProcessStats::default();
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let kib = || {
+            value
+                .trim()
+                .strip_suffix("kB")
+                .and_then(|kib| kib.trim().parse::<u64>().ok())
+                .map(|kib| kib * 1024)
+        };
+        match key {
+            "VmRSS" => stats.rss_bytes = kib(),
+            "VmHWM" => stats.peak_rss_bytes = kib(),
+            "VmSize" => stats.virtual_bytes = kib(),
+            "Threads" => stats.threads = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    stats
+}
+
+/// User and system CPU seconds from the contents of `/proc/self/stat`
+fn parse_cpu_times(stat: &str) -> Option<(f64, f64)> {
+    // The command name (field 2) may contain spaces; fields after it are space separated,
+    // utime and stime being fields 14 and 15
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let user: u64 = fields.get(11)?.parse().ok()?;
+    let system: u64 = fields.get(12)?.parse().ok()?;
+    Some((user as f64 / CLOCK_TICKS_PER_SEC, system as f64 / CLOCK_TICKS_PER_SEC))
+}
+
+fn micros_to_ms(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let status = "Name:\tapi\nVmHWM:\t   20480 kB\nVmRSS:\t   10240 kB\nVmSize:\t  204800 kB\nThreads:\t9\n";
+        let stats = parse_status(status);
+        assert_eq!(stats.rss_bytes, Some(10 * 1024 * 1024));
+        assert_eq!(stats.peak_rss_bytes, Some(20 * 1024 * 1024));
+        assert_eq!(stats.virtual_bytes, Some(200 * 1024 * 1024));
+        assert_eq!(stats.threads, Some(9));
+
+        let stat = "4242 (analytics api) S 1 4242 4242 0 -1 4194560 1000 0 0 0 250 75 0 0 20 0 9 0 100";
+        assert_eq!(parse_cpu_times(stat), Some((2.5, 0.75)));
+        assert_eq!(parse_cpu_times("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let stats = RuntimeStats::new();
+        stats.record_scheduler_delay(Duration::from_micros(200));
+        stats.record_scheduler_delay(Duration::from_micros(600));
+
+        let snapshot = stats.snapshot();
+        let tokio = snapshot.tokio.unwrap();
+        assert!(tokio.workers >= 1);
+        assert_eq!(tokio.scheduler_delay.samples, 2);
+        assert_eq!(tokio.scheduler_delay.last_ms, 0.6);
+        assert_eq!(tokio.scheduler_delay.max_ms, 0.6);
+        assert!((tokio.scheduler_delay.mean_ms - 0.4).abs() < 1e-9);
+        assert!(snapshot.process.cpu_percent.is_none());
+    }
+}
@@ -0,0 +1,130 @@
+// In-memory session store
+// This module keeps sessions in process memory, for single-instance deployments and tests
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::{new_session_id, SessionError, SessionState, SessionStore};
+
+/// Sessions held by a single collector instance
+///
+/// Instances do not share sessions; use `RedisSessionStore` when running more than one.
+pub struct MemorySessionStore {
+    timeout_ms: i64,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    id: String,
+    last_seen_ms: i64,
+    events: u64,
+}
+
+/// Number of tracked visitors above which expired sessions are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+impl MemorySessionStore {
+    /// Create a store whose sessions end after `timeout` of inactivity
+    pub fn new(timeout: Duration) -> Self {
+        MemorySessionStore {
+            timeout_ms: timeout.as_millis() as i64,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of tracked visitors (including expired sessions not yet pruned)
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no visitor is tracked
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Session>> {
+        match self.sessions.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn touch(&self, visitor: &str, now_ms: i64) -> Result<SessionState, SessionError> {
+        let mut sessions = self.lock();
+        if sessions.len() >= PRUNE_THRESHOLD {
+            let timeout_ms = self.timeout_ms;
+            sessions.retain(|_, session| now_ms - session.last_seen_ms <= timeout_ms);
+        }
+
+        if let Some(session) = sessions.get_mut(visitor) {
+            if now_ms - session.last_seen_ms <= self.timeout_ms {
+                session.last_seen_ms = session.last_seen_ms.max(now_ms);
+                session.events += 1;
+                return Ok(SessionState {
+                    session_id: session.id.clone(),
+                    session_start: false,
+                    event_index: session.events,
+                });
+            }
+        }
+
+        let session = Session {
+            id: new_session_id(),
+            last_seen_ms: now_ms,
+            events: 1,
+        };
+        let state = SessionState {
+            session_id: session.id.clone(),
+            session_start: true,
+            event_index: 1,
+        };
+        sessions.insert(visitor.to_string(), session);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_within_timeout_share_session() {
+        let store = MemorySessionStore::new(Duration::from_secs(1800));
+        let first = store.touch("visitor", 1_000).await.unwrap();
+        let second = store.touch("visitor", 60_000).await.unwrap();
+
+        assert!(first.session_start);
+        assert_eq!(first.event_index, 1);
+        assert!(!second.session_start);
+        assert_eq!(second.event_index, 2);
+        assert_eq!(first.session_id, second.session_id);
+    }
+
+    #[tokio::test]
+    async fn test_inactivity_starts_new_session() {
+        let store = MemorySessionStore::new(Duration::from_secs(1800));
+        let first = store.touch("visitor", 0).await.unwrap();
+        let second = store.touch("visitor", 1_800_001).await.unwrap();
+
+        assert!(second.session_start);
+        assert_eq!(second.event_index, 1);
+        assert_ne!(first.session_id, second.session_id);
+    }
+
+    #[tokio::test]
+    async fn test_visitors_have_separate_sessions() {
+        let store = MemorySessionStore::new(Duration::from_secs(1800));
+        let a = store.touch("a", 0).await.unwrap();
+        let b = store.touch("b", 0).await.unwrap();
+
+        assert!(b.session_start);
+        assert_ne!(a.session_id, b.session_id);
+        assert_eq!(store.len(), 2);
+    }
+}
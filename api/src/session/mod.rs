@@ -0,0 +1,81 @@
+// Session tracking module
+// This module assigns events to visitor sessions that end after a period of inactivity
+
+use async_trait::async_trait;
+use std::fmt;
+use std::time::Duration;
+
+pub mod memory;
+pub mod redis;
+
+pub use memory::MemorySessionStore;
+pub use self::redis::RedisSessionStore;
+
+use crate::config::{SessionConfig, SessionStoreType};
+
+/// Error types for session store operations
+#[derive(Debug)]
+pub enum SessionError {
+    /// Connection error to the session store
+    ConnectionError(String),
+    /// Error running a store command
+    CommandError(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::ConnectionError(msg) => write!(f, "Connection error: {}", msg),
+            SessionError::CommandError(msg) => write!(f, "Command error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// Session an event was assigned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionState {
+    /// Identifier shared by all events of the session
+    pub session_id: String,
+    /// Whether the event started a new session
+    pub session_start: bool,
+    /// Position of the event within the session (1 for the first event)
+    pub event_index: u64,
+}
+
+/// Store tracking the current session of each visitor
+///
+/// A visitor's session continues while events arrive less than `timeout` apart;
+/// the next event after a longer gap starts a new session.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Record an event of `visitor` at `now_ms` (Unix milliseconds) and return its session
+    async fn touch(&self, visitor: &str, now_ms: i64) -> Result<SessionState, SessionError>;
+
+    /// Check that the store can be reached; always succeeds for in-process stores
+    async fn health_check(&self) -> Result<(), SessionError> {
+        Ok(())
+    }
+}
+
+/// Generate a new random session identifier
+pub(crate) fn new_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Create the session store selected in configuration
+///
+/// # Errors
+/// Returns `SessionError::ConnectionError` if the Redis URL is invalid
+pub fn create_session_store(config: &SessionConfig) -> Result<Box<dyn SessionStore>, SessionError> {
+    let timeout = Duration::from_secs(config.timeout_secs);
+    match config.store {
+        SessionStoreType::Memory => Ok(Box::new(MemorySessionStore::new(timeout))),
+        SessionStoreType::Redis => Ok(Box::new(RedisSessionStore::new(
+            &config.redis_url,
+            &config.key_prefix,
+            timeout,
+        )?)),
+    }
+}
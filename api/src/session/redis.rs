@@ -0,0 +1,106 @@
+// Redis session store
+// This module keeps sessions in Redis so that all collector instances agree on them
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use tokio::sync::OnceCell;
+
+use super::{new_session_id, SessionError, SessionState, SessionStore};
+
+/// Atomically extend the visitor's session or start a new one
+///
+/// KEYS[1]: session hash; ARGV: now (ms), timeout (ms), id for a new session.
+/// Returns {session id, 1 if the session started, event index}.
+const TOUCH_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local timeout = tonumber(ARGV[2])
+local session = redis.call('HMGET', KEYS[1], 'id', 'last')
+if session[1] and now - tonumber(session[2]) <= timeout then
+  local events = redis.call('HINCRBY', KEYS[1], 'events', 1)
+  if now > tonumber(session[2]) then
+    redis.call('HSET', KEYS[1], 'last', now)
+  end
+  redis.call('PEXPIRE', KEYS[1], timeout)
+  return {session[1], 0, events}
+end
+redis.call('HSET', KEYS[1], 'id', ARGV[3], 'last', now, 'events', 1)
+redis.call('PEXPIRE', KEYS[1], timeout)
+return {ARGV[3], 1, 1}
+"#;
+
+/// Sessions shared by all collector instances through Redis
+///
+/// Each visitor's session is a hash under `<key_prefix><visitor>` that expires after
+/// the inactivity timeout. The connection is opened on first use and re-established
+/// automatically after failures.
+pub struct RedisSessionStore {
+    client: redis::Client,
+    connection: OnceCell<ConnectionManager>,
+    key_prefix: String,
+    timeout_ms: i64,
+    script: redis::Script,
+}
+
+impl RedisSessionStore {
+    /// Create a store
+    ///
+    /// # Arguments
+    /// * `url` - Redis URL (e.g. "redis://localhost:6379")
+    /// * `key_prefix` - Prefix of the session keys
+    /// * `timeout` - Inactivity after which a session ends
+    ///
+    /// # Errors
+    /// Returns `SessionError::ConnectionError` if the URL is invalid
+    pub fn new(url: &str, key_prefix: &str, timeout: Duration) -> Result<Self, SessionError> {
+        let client = redis::Client::open(url).map_err(|e| SessionError::ConnectionError(e.to_string()))?;
+        Ok(RedisSessionStore {
+            client,
+            connection: OnceCell::new(),
+            key_prefix: key_prefix.to_string(),
+            timeout_ms: timeout.as_millis() as i64,
+            script: redis::Script::new(TOUCH_SCRIPT),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, SessionError> {
+        self.connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+            .map_err(|e| SessionError::ConnectionError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn touch(&self, visitor: &str, now_ms: i64) -> Result<SessionState, SessionError> {
+        let mut connection = self.connection().await?;
+        let (session_id, started, event_index): (String, i64, u64) = self
+            .script
+            .key(format!("{}{}", self.key_prefix, visitor))
+            .arg(now_ms)
+            .arg(self.timeout_ms)
+            .arg(new_session_id())
+            .invoke_async(&mut connection)
+            .await
+            .map_err(|e| SessionError::CommandError(e.to_string()))?;
+
+        Ok(SessionState {
+            session_id,
+            session_start: started == 1,
+            event_index,
+        })
+    }
+
+    /// PING the Redis server
+    async fn health_check(&self) -> Result<(), SessionError> {
+        let mut connection = self.connection().await?;
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+            .map(|_| ())
+            .map_err(|e| SessionError::CommandError(e.to_string()))
+    }
+}
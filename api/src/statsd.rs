@@ -0,0 +1,392 @@
+// StatsD metrics export
+// This module sends the ingest, stage and streaming sink metrics to a StatsD server or DogStatsD agent over UDP
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::config::{StatsdConfig, StatsdFlavor};
+use crate::metrics::{Histogram, IngestMetrics, SinkMetrics, StageMetrics};
+use crate::streaming::StreamingService;
+
+/// Characters replaced in DogStatsD tag values
+const TAG_VALUE_RESERVED: &[char] = &['|', '#', ','];
+
+/// Characters replaced in label values appended to plain StatsD names
+const NAME_SEGMENT_RESERVED: &[char] = &[':', '|', '@', '#', ',', '.'];
+
+/// Sends the metrics served on `/metrics` as StatsD lines
+///
+/// StatsD aggregates what it receives over its own flush interval, so counters are
+/// sent as their increase since the previous flush. Only bucket counts of the
+/// histograms are kept, not the observed values, so each bucket is sent as a
+/// `<name>_bucket` counter with an `le` label, counting the observations added to that
+/// bucket alone (not cumulative). Metric names are the OTLP ones (the Prometheus names
+/// without `_total`).
+pub struct StatsdExporter {
+    socket: UdpSocket,
+    config: StatsdConfig,
+    /// Ingest request counts, exported next to the sink metrics when set
+    ingest: Option<Arc<IngestMetrics>>,
+    /// Processing stage durations, exported next to the sink metrics when set
+    stages: Option<Arc<StageMetrics>>,
+    /// Counter and histogram bucket totals at the previous flush, by series
+    previous: Mutex<HashMap<String, u64>>,
+}
+
+impl StatsdExporter {
+    /// Resolve `config.address` and open the UDP socket
+    ///
+    /// The address is resolved once; restart to follow a DNS change.
+    ///
+    /// # Errors
+    /// Returns an error if the address does not resolve or the socket cannot be opened
+    pub async fn new(config: StatsdConfig) -> std::io::Result<Self> {
+        let target = tokio::net::lookup_host(config.address.as_str())
+            .await?
+            .next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve"))?;
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(target).await?;
+        Ok(StatsdExporter {
+            socket,
+            config,
+            ingest: None,
+            stages: None,
+            previous: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Also export the ingest request counts
+    pub fn with_ingest_metrics(mut self, ingest: Arc<IngestMetrics>) -> Self {
+        self.ingest = Some(ingest);
+        self
+    }
+
+    /// Also export the processing stage durations
+    pub fn with_stage_metrics(mut self, stages: Arc<StageMetrics>) -> Self {
+        self.stages = Some(stages);
+        self
+    }
+
+    /// Address the lines are sent to
+    pub fn address(&self) -> &str {
+        &self.config.address
+    }
+
+    /// Lines reporting the changes since the previous call
+    ///
+    /// # Arguments
+    /// * `metrics` - Sink metrics to export
+    /// * `queue_depth` - Messages buffered inside the sink, if the sink reports it
+    pub fn lines(&self, metrics: &SinkMetrics, queue_depth: Option<i64>) -> Vec<String> {
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        let mut writer = LineWriter {
+            config: &self.config,
+            previous: &mut previous,
+            lines: Vec::new(),
+        };
+        let sink = metrics.sink();
+
+        writer.gauge("analytics_sink_in_flight", &[("sink", sink)], metrics.in_flight());
+        if let Some(depth) = queue_depth {
+            writer.gauge("analytics_sink_queue_depth", &[("sink", sink)], depth);
+        }
+        for (topic, metrics) in metrics.topics() {
+            let tags = [("sink", sink), ("topic", topic.as_str())];
+            writer.counter("analytics_sink_delivered", &tags, metrics.delivered());
+            for (kind, count) in metrics.errors() {
+                writer.counter("analytics_sink_errors", &[("sink", sink), ("topic", topic.as_str()), ("kind", kind)], count);
+            }
+            writer.histogram("analytics_sink_delivery_latency_seconds", &tags, metrics.latency());
+            writer.histogram("analytics_sink_payload_bytes", &tags, metrics.payload_size());
+        }
+
+        if let Some(ingest) = &self.ingest {
+            for (project, endpoint, outcome, count) in ingest.counts() {
+                writer.counter(
+                    "analytics_ingest_requests",
+                    &[("project", project.as_str()), ("endpoint", endpoint), ("outcome", outcome.as_str())],
+                    count,
+                );
+            }
+        }
+        if let Some(stages) = &self.stages {
+            for (stage, histogram) in stages.stages() {
+                writer.histogram("analytics_stage_duration_seconds", &[("stage", stage.as_str())], &histogram);
+            }
+        }
+        writer.lines
+    }
+
+    /// Send the changes since the previous flush
+    ///
+    /// Lines are batched into datagrams of at most `max_packet_bytes`. Changes in a
+    /// datagram that fails to send are lost, as with any StatsD client.
+    ///
+    /// # Errors
+    /// Returns the error of the first datagram that failed to send
+    pub async fn flush(&self, metrics: &SinkMetrics, queue_depth: Option<i64>) -> std::io::Result<()> {
+        let mut failure = None;
+        for packet in packets(self.lines(metrics, queue_depth), self.config.max_packet_bytes) {
+            if let Err(e) = self.socket.send(packet.as_bytes()).await {
+                failure.get_or_insert(e);
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    /// Spawn a background task that flushes the metrics every `interval_secs`
+    ///
+    /// Failed sends are logged. The task runs until the runtime shuts down.
+    pub fn spawn(self, metrics: Arc<SinkMetrics>, service: Arc<dyn StreamingService>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.flush(&metrics, service.queue_depth()).await {
+                    tracing::warn!(address = %self.config.address, error = %e, "Failed to send metrics to StatsD");
+                }
+            }
+        })
+    }
+}
+
+/// Formats the lines of one flush
+struct LineWriter<'a> {
+    config: &'a StatsdConfig,
+    previous: &'a mut HashMap<String, u64>,
+    lines: Vec<String>,
+}
+
+impl LineWriter<'_> {
+    fn gauge(&mut self, name: &str, tags: &[(&str, &str)], value: i64) {
+        let line = self.format(name, tags, &value.to_string(), "g");
+        self.lines.push(line);
+    }
+
+    /// Send the increase of the counter since the previous flush, if any
+    fn counter(&mut self, name: &str, tags: &[(&str, &str)], total: u64) {
+        let series = self.format(name, tags, "", "c");
+        let increase = self.increase(series, total);
+        if increase > 0 {
+            let line = self.format(name, tags, &increase.to_string(), "c");
+            self.lines.push(line);
+        }
+    }
+
+    /// Send the observations added to each bucket since the previous flush as a
+    /// `<name>_bucket` counter labelled with the bucket's upper bound (`+Inf` for the last)
+    ///
+    /// The observed values themselves are not kept, so sending them as `|h` samples
+    /// would make the agent compute percentiles of the bucket bounds instead.
+    fn histogram(&mut self, name: &str, tags: &[(&str, &str)], histogram: &Histogram) {
+        let name = format!("{}_bucket", name);
+        let bounds = histogram.bounds();
+        for (index, total) in histogram.bucket_counts().into_iter().enumerate() {
+            let le = bounds.get(index).map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+            let mut bucket_tags: Vec<(&str, &str)> = tags.to_vec();
+            bucket_tags.push(("le", &le));
+            self.counter(&name, &bucket_tags, total);
+        }
+    }
+
+    /// Remember `total` for `series`, returning its increase since the previous flush
+    fn increase(&mut self, series: String, total: u64) -> u64 {
+        let previous = self.previous.insert(series, total).unwrap_or(0);
+        total.saturating_sub(previous)
+    }
+
+    /// One line in the configured flavor; labels with an empty value are left out
+    fn format(&self, name: &str, tags: &[(&str, &str)], value: &str, kind: &str) -> String {
+        let mut line = String::new();
+        if !self.config.prefix.is_empty() {
+            line.push_str(&self.config.prefix);
+            line.push('.');
+        }
+        line.push_str(name);
+        let tags = tags.iter().filter(|(_, value)| !value.is_empty());
+        if self.config.flavor == StatsdFlavor::Statsd {
+            for (_, value) in tags.clone() {
+                line.push('.');
+                line.push_str(&sanitize(value, NAME_SEGMENT_RESERVED));
+            }
+        }
+        line.push(':');
+        line.push_str(value);
+        line.push('|');
+        line.push_str(kind);
+        if self.config.flavor == StatsdFlavor::Dogstatsd {
+            let mut all: Vec<String> = tags
+                .map(|(name, value)| format!("{}:{}", name, sanitize(value, TAG_VALUE_RESERVED)))
+                .collect();
+            let mut extra: Vec<(&String, &String)> = self.config.tags.iter().collect();
+            extra.sort();
+            all.extend(extra.into_iter().map(|(name, value)| format!("{}:{}", name, value)));
+            if !all.is_empty() {
+                line.push_str("|#");
+                line.push_str(&all.join(","));
+            }
+        }
+        line
+    }
+}
+
+/// Replace the `reserved` characters and whitespace in a label value with `_`
+fn sanitize(value: &str, reserved: &[char]) -> String {
+    value
+        .chars()
+        .map(|c| if reserved.contains(&c) || c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+/// Join `lines` into newline-separated packets of at most `max_bytes` (a longer line
+/// gets a packet of its own)
+fn packets(lines: Vec<String>, max_bytes: usize) -> Vec<String> {
+    let mut packets: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > max_bytes {
+            packets.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        packets.push(current);
+    }
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{IngestOutcome, STAGE_VALIDATION};
+
+    async fn exporter(config: StatsdConfig) -> StatsdExporter {
+        StatsdExporter::new(config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dogstatsd_lines() {
+        let config = StatsdConfig {
+            prefix: "penrose".to_string(),
+            tags: [("env".to_string(), "staging".to_string())].into(),
+            ..Default::default()
+        };
+        let ingest = Arc::new(IngestMetrics::new(&Default::default()));
+        ingest.record("/track/", Some("shop"), IngestOutcome::Accepted);
+        let exporter = exporter(config).await.with_ingest_metrics(ingest);
+
+        let metrics = SinkMetrics::new("kafka");
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(3), None);
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(4), None);
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(30), Some("timeout"));
+
+        let lines = exporter.lines(&metrics, Some(4));
+        assert!(lines.contains(&"penrose.analytics_sink_in_flight:0|g|#sink:kafka,env:staging".to_string()));
+        assert!(lines.contains(&"penrose.analytics_sink_queue_depth:4|g|#sink:kafka,env:staging".to_string()));
+        assert!(lines.contains(&"penrose.analytics_sink_delivered:2|c|#sink:kafka,topic:analytics,env:staging".to_string()));
+        assert!(lines.contains(
+            &"penrose.analytics_sink_errors:1|c|#sink:kafka,topic:analytics,kind:timeout,env:staging".to_string()
+        ));
+        assert!(lines.contains(
+            &"penrose.analytics_sink_delivery_latency_seconds_bucket:2|c|#sink:kafka,topic:analytics,le:0.005,env:staging"
+                .to_string()
+        ));
+        assert!(lines.contains(
+            &"penrose.analytics_sink_delivery_latency_seconds_bucket:1|c|#sink:kafka,topic:analytics,le:0.05,env:staging"
+                .to_string()
+        ));
+        assert!(lines.contains(
+            &"penrose.analytics_ingest_requests:1|c|#project:shop,endpoint:/track/,outcome:accepted,env:staging".to_string()
+        ));
+
+        // Only the changes are sent at the next flush
+        metrics.send_started();
+        metrics.send_finished("analytics", Duration::from_millis(3), None);
+        let lines = exporter.lines(&metrics, None);
+        assert_eq!(
+            lines,
+            vec![
+                "penrose.analytics_sink_in_flight:0|g|#sink:kafka,env:staging",
+                "penrose.analytics_sink_delivered:1|c|#sink:kafka,topic:analytics,env:staging",
+                "penrose.analytics_sink_delivery_latency_seconds_bucket:1|c|#sink:kafka,topic:analytics,le:0.005,env:staging",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_statsd_lines() {
+        let config = StatsdConfig {
+            flavor: StatsdFlavor::Statsd,
+            tags: [("env".to_string(), "staging".to_string())].into(),
+            ..Default::default()
+        };
+        let stages = Arc::new(StageMetrics::new());
+        stages.observe(STAGE_VALIDATION, Duration::from_micros(40));
+        let exporter = exporter(config).await.with_stage_metrics(stages);
+
+        let metrics = SinkMetrics::new("stdout");
+        metrics.send_started();
+        metrics.send_finished("", Duration::from_millis(3), None);
+        metrics.topic("events.v1");
+        let lines = exporter.lines(&metrics, None);
+        assert_eq!(
+            lines,
+            vec![
+                "analytics_sink_in_flight.stdout:0|g",
+                "analytics_sink_delivered.stdout:1|c",
+                "analytics_sink_delivery_latency_seconds_bucket.stdout.0_005:1|c",
+                "analytics_stage_duration_seconds_bucket.validation.0_00005:1|c",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_sends_datagrams() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = StatsdConfig {
+            address: receiver.local_addr().unwrap().to_string(),
+            max_packet_bytes: 512,
+            ..Default::default()
+        };
+        let exporter = exporter(config).await;
+
+        let metrics = SinkMetrics::new("kafka");
+        for index in 0..20 {
+            metrics.send_started();
+            metrics.send_finished(&format!("topic-{}", index), Duration::from_millis(3), None);
+        }
+        exporter.flush(&metrics, None).await.unwrap();
+
+        let mut lines = 0;
+        let mut buffer = [0u8; 2048];
+        while lines < 41 {
+            let received = tokio::time::timeout(Duration::from_secs(5), receiver.recv(&mut buffer))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(received <= 512);
+            lines += std::str::from_utf8(&buffer[..received]).unwrap().lines().count();
+        }
+        assert_eq!(lines, 41);
+
+        assert_eq!(packets(vec!["a".repeat(600), "b".to_string()], 512), vec!["a".repeat(600), "b".to_string()]);
+    }
+}
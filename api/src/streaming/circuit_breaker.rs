@@ -0,0 +1,228 @@
+// Circuit breaker for streaming sinks
+// This module tracks consecutive send failures and short-circuits sends while a sink is down
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// State of a circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Sends go to the sink normally
+    Closed,
+    /// The sink is considered down; sends are short-circuited
+    Open,
+    /// The open period elapsed; a single trial send is allowed through
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/// Consecutive-failure circuit breaker
+///
+/// Opens after `failure_threshold` consecutive failures, stays open for `open_duration`,
+/// then lets a single trial request through (half-open). A successful trial closes the
+/// breaker; a failed trial re-opens it. Both limits can be changed while in use.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: AtomicU32,
+    /// Open duration in milliseconds
+    open_millis: AtomicU64,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker
+    ///
+    /// # Arguments
+    /// * `failure_threshold` - Consecutive failures that open the breaker (minimum 1)
+    /// * `open_duration` - How long the breaker stays open before allowing a trial request
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: AtomicU32::new(failure_threshold.max(1)),
+            open_millis: AtomicU64::new(open_duration.as_millis() as u64),
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+            }),
+        }
+    }
+
+    /// Consecutive failures that open the breaker
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold.load(Ordering::Relaxed)
+    }
+
+    /// How long the breaker stays open before allowing a trial request
+    pub fn open_duration(&self) -> Duration {
+        Duration::from_millis(self.open_millis.load(Ordering::Relaxed))
+    }
+
+    /// Change both limits; they apply from the next recorded outcome or state check
+    pub fn set_limits(&self, failure_threshold: u32, open_duration: Duration) {
+        self.failure_threshold.store(failure_threshold.max(1), Ordering::Relaxed);
+        self.open_millis.store(open_duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Current breaker state
+    ///
+    /// An open breaker whose open period has elapsed is reported as half-open.
+    pub fn state(&self) -> CircuitState {
+        let state = self.lock();
+        match (state.state, state.opened_at) {
+            (CircuitState::Open, Some(opened_at)) if opened_at.elapsed() >= self.open_duration() => {
+                CircuitState::HalfOpen
+            }
+            (current, _) => current,
+        }
+    }
+
+    /// Whether a request may be sent to the protected sink right now
+    ///
+    /// While half-open only one trial request is allowed until its outcome is recorded.
+    pub fn allow_request(&self) -> bool {
+        let mut state = self.lock();
+        match state.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => {
+                let elapsed = state
+                    .opened_at
+                    .map(|opened_at| opened_at.elapsed() >= self.open_duration())
+                    .unwrap_or(true);
+                if elapsed {
+                    tracing::info!("Circuit breaker half-open, allowing trial request");
+                    state.state = CircuitState::HalfOpen;
+                    state.trial_in_flight = true;
+                    true
+                } else {
+                    false
+                }
+            }
+            CircuitState::HalfOpen => {
+                if state.trial_in_flight {
+                    false
+                } else {
+                    state.trial_in_flight = true;
+                    true
+                }
+            }
+        }
+    }
+
+    /// Record a successful request, closing the breaker
+    pub fn record_success(&self) {
+        let mut state = self.lock();
+        if state.state != CircuitState::Closed {
+            tracing::info!("Circuit breaker closed");
+        }
+        state.state = CircuitState::Closed;
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        state.trial_in_flight = false;
+    }
+
+    /// Record a failed request, opening the breaker once the threshold is reached
+    pub fn record_failure(&self) {
+        let mut state = self.lock();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        state.trial_in_flight = false;
+
+        let should_open = match state.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => state.consecutive_failures >= self.failure_threshold(),
+            CircuitState::Open => false,
+        };
+
+        if should_open {
+            tracing::warn!(
+                consecutive_failures = state.consecutive_failures,
+                open_secs = self.open_duration().as_secs(),
+                "Circuit breaker opened"
+            );
+            state.state = CircuitState::Open;
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow_request());
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_allows_single_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        assert!(breaker.allow_request());
+        assert!(!breaker.allow_request());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow_request());
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let breaker = CircuitBreaker::new(5, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.set_limits(2, Duration::ZERO);
+        assert_eq!(breaker.failure_threshold(), 2);
+        assert_eq!(breaker.open_duration(), Duration::ZERO);
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn test_failed_trial_reopens() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(0));
+        breaker.record_failure();
+        assert!(breaker.allow_request());
+        breaker.record_failure();
+
+        let state = breaker.lock();
+        assert_eq!(state.state, CircuitState::Open);
+    }
+}
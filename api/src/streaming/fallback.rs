@@ -0,0 +1,293 @@
+// Fallback streaming sink
+// This module diverts events to a secondary sink while the primary sink's circuit breaker is open
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::circuit_breaker::{CircuitBreaker, CircuitState};
+use super::file::FileStreaming;
+use super::{StreamingError, StreamingService};
+use crate::transformer::AnalyticsEvent;
+
+/// Streaming service that protects a primary sink with a circuit breaker
+///
+/// Events go to the primary sink while its breaker is closed. A failed primary send, or
+/// any send while the breaker is open, is written to the fallback file instead, so the
+/// HTTP client still gets a success response. Once the primary recovers, stored events
+/// are replayed into it by [`FallbackStreaming::spawn_replay`].
+pub struct FallbackStreaming {
+    primary: Arc<dyn StreamingService>,
+    fallback: FileStreaming,
+    breaker: CircuitBreaker,
+}
+
+impl FallbackStreaming {
+    /// Wrap `primary` with a circuit breaker and a file fallback
+    pub fn new(primary: Arc<dyn StreamingService>, fallback: FileStreaming, breaker: CircuitBreaker) -> Self {
+        FallbackStreaming {
+            primary,
+            fallback,
+            breaker,
+        }
+    }
+
+    /// Current state of the primary sink's circuit breaker
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    /// Replay stored fallback events into the primary sink if it is available
+    ///
+    /// # Returns
+    /// The number of events replayed (0 if the breaker is not closed or nothing is stored)
+    pub async fn replay_pending(&self) -> Result<usize, StreamingError> {
+        if self.breaker.state() != CircuitState::Closed || !self.fallback.has_pending().await {
+            return Ok(0);
+        }
+
+        match self.fallback.replay_into(self.primary.as_ref()).await {
+            Ok(count) => {
+                if count > 0 {
+                    tracing::info!(
+                        replayed = count,
+                        path = %self.fallback.path().display(),
+                        "Replayed fallback events into primary sink"
+                    );
+                }
+                Ok(count)
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                tracing::warn!(
+                    error = %e,
+                    "Replay into primary sink failed, remaining events kept in fallback"
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Spawn a background task that periodically replays fallback events
+    pub fn spawn_replay(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let _ = service.replay_pending().await;
+            }
+        })
+    }
+
+    async fn send_to_fallback(&self, event: &AnalyticsEvent, reason: &str) -> Result<(), StreamingError> {
+        tracing::warn!(
+            event_id = ?event.id,
+            reason = reason,
+            path = %self.fallback.path().display(),
+            "Writing event to fallback sink"
+        );
+        self.fallback.send_event(event).await
+    }
+
+    async fn send_batch_to_fallback(&self, events: &[AnalyticsEvent], reason: &str) -> Result<(), StreamingError> {
+        tracing::warn!(
+            event_count = events.len(),
+            reason = reason,
+            path = %self.fallback.path().display(),
+            "Writing batch to fallback sink"
+        );
+        self.fallback.send_batch(events).await
+    }
+}
+
+#[async_trait]
+impl StreamingService for FallbackStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        if !self.breaker.allow_request() {
+            return self.send_to_fallback(event, "circuit open").await;
+        }
+
+        match self.primary.send_event(event).await {
+            Ok(()) => {
+                self.breaker.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                tracing::error!(
+                    event_id = ?event.id,
+                    error = %e,
+                    "Primary sink send failed"
+                );
+                self.send_to_fallback(event, "primary send failed").await
+            }
+        }
+    }
+
+    /// The batch goes to the primary as one batch, or to the fallback as a whole
+    ///
+    /// A primary that does not write batches atomically may have delivered part of a
+    /// failed batch; those events are written to the fallback too and replayed again.
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        if !self.breaker.allow_request() {
+            return self.send_batch_to_fallback(events, "circuit open").await;
+        }
+
+        match self.primary.send_batch(events).await {
+            Ok(()) => {
+                self.breaker.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                tracing::error!(
+                    event_count = events.len(),
+                    error = %e,
+                    "Primary sink batch send failed"
+                );
+                self.send_batch_to_fallback(events, "primary send failed").await
+            }
+        }
+    }
+
+    /// Healthy as long as events can be accepted by the primary or the fallback
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        match self.primary.health_check().await {
+            Ok(()) => Ok(()),
+            Err(primary_error) => self.fallback.health_check().await.map_err(|fallback_error| {
+                StreamingError::HealthCheckError(format!(
+                    "primary: {}; fallback: {}",
+                    primary_error, fallback_error
+                ))
+            }),
+        }
+    }
+    fn queue_depth(&self) -> Option<i64> {
+        self.primary.queue_depth()
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        Some(&self.breaker)
+    }
+
+    /// Topic on the primary sink; events spilled to the fallback keep its label
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.primary.topic_for(event)
+    }
+
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        self.primary.close(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct FlakyPrimary {
+        down: AtomicBool,
+        sent: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl StreamingService for FlakyPrimary {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            if self.down.load(Ordering::SeqCst) {
+                Err(StreamingError::SendError("broker down".to_string()))
+            } else {
+                self.sent.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    fn event(id: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            id: Some(id.to_string()),
+            event: "pageview".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_sends_go_to_fallback_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Arc::new(FlakyPrimary {
+            down: AtomicBool::new(true),
+            sent: AtomicUsize::new(0),
+        });
+        let service = FallbackStreaming::new(
+            primary.clone(),
+            FileStreaming::new(dir.path().join("fallback.jsonl")),
+            CircuitBreaker::new(2, Duration::ZERO),
+        );
+
+        // Sends succeed from the caller's point of view while the primary is down
+        for id in ["a", "b", "c"] {
+            assert!(service.send_event(&event(id)).await.is_ok());
+        }
+        assert_eq!(primary.sent.load(Ordering::SeqCst), 0);
+        assert_ne!(service.circuit_state(), CircuitState::Closed);
+
+        // Nothing is replayed until the breaker closes again
+        assert_eq!(service.replay_pending().await.unwrap(), 0);
+
+        primary.down.store(false, Ordering::SeqCst);
+        service.send_event(&event("d")).await.unwrap();
+        assert_eq!(service.circuit_state(), CircuitState::Closed);
+
+        assert_eq!(service.replay_pending().await.unwrap(), 3);
+        assert_eq!(primary.sent.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_goes_to_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Arc::new(FlakyPrimary {
+            down: AtomicBool::new(true),
+            sent: AtomicUsize::new(0),
+        });
+        let service = FallbackStreaming::new(
+            primary.clone(),
+            FileStreaming::new(dir.path().join("fallback.jsonl")),
+            CircuitBreaker::new(2, Duration::ZERO),
+        );
+
+        service.send_batch(&[event("a"), event("b")]).await.unwrap();
+        assert_eq!(primary.sent.load(Ordering::SeqCst), 0);
+
+        primary.down.store(false, Ordering::SeqCst);
+        assert_eq!(service.replay_pending().await.unwrap(), 2);
+        assert_eq!(primary.sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_skips_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Arc::new(FlakyPrimary {
+            down: AtomicBool::new(true),
+            sent: AtomicUsize::new(0),
+        });
+        let service = FallbackStreaming::new(
+            primary.clone(),
+            FileStreaming::new(dir.path().join("fallback.jsonl")),
+            CircuitBreaker::new(1, Duration::from_secs(60)),
+        );
+
+        service.send_event(&event("a")).await.unwrap();
+        assert_eq!(service.circuit_state(), CircuitState::Open);
+
+        // Primary has recovered, but the breaker is still open so events keep going to the file
+        primary.down.store(false, Ordering::SeqCst);
+        service.send_event(&event("b")).await.unwrap();
+        assert_eq!(primary.sent.load(Ordering::SeqCst), 0);
+    }
+}
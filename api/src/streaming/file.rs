@@ -0,0 +1,280 @@
+// Local file streaming sink
+// This module appends events as JSON lines to a local file and can replay them later
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+
+use super::{StreamingError, StreamingService};
+use crate::transformer::AnalyticsEvent;
+
+/// Streaming service that appends events to a local JSON-lines file
+///
+/// Used as a fallback sink while the primary broker is unavailable. Events written here
+/// can be replayed into another streaming service with [`FileStreaming::replay_into`].
+pub struct FileStreaming {
+    path: PathBuf,
+    /// Serializes appends and replays so lines are never interleaved or lost
+    write_lock: Mutex<()>,
+}
+
+impl FileStreaming {
+    /// Create a file sink writing to `path`
+    ///
+    /// The file (and its parent directory) must be writable; it is created on first write.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileStreaming {
+            path: path.as_ref().to_path_buf(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Path of the file events are appended to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path used while a replay is in progress
+    fn replay_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(".replaying");
+        PathBuf::from(name)
+    }
+
+    async fn append_lines(&self, lines: &[String]) -> Result<(), StreamingError> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| StreamingError::SendError(format!("{}: {}", self.path.display(), e)))?;
+
+        let mut buffer = String::new();
+        for line in lines {
+            buffer.push_str(line);
+            buffer.push('\n');
+        }
+
+        file.write_all(buffer.as_bytes())
+            .await
+            .map_err(|e| StreamingError::SendError(format!("{}: {}", self.path.display(), e)))?;
+        file.flush()
+            .await
+            .map_err(|e| StreamingError::SendError(format!("{}: {}", self.path.display(), e)))?;
+        Ok(())
+    }
+
+    /// Whether there are stored events waiting to be replayed
+    pub async fn has_pending(&self) -> bool {
+        let pending = |path: PathBuf| async move {
+            tokio::fs::metadata(&path)
+                .await
+                .map(|m| m.len() > 0)
+                .unwrap_or(false)
+        };
+        pending(self.path.clone()).await || pending(self.replay_path()).await
+    }
+
+    /// Re-send every stored event to `target`, removing them from the file
+    ///
+    /// Stored events are moved aside before replay so new fallback writes are not lost.
+    /// If the target fails part-way, the events not yet delivered are appended back to
+    /// the fallback file and the error is returned.
+    ///
+    /// # Returns
+    /// The number of events replayed
+    pub async fn replay_into(&self, target: &dyn StreamingService) -> Result<usize, StreamingError> {
+        let replay_path = self.replay_path();
+
+        // Move the current file aside, unless a previous replay was interrupted
+        {
+            let _guard = self.write_lock.lock().await;
+            let interrupted = tokio::fs::metadata(&replay_path).await.is_ok();
+            if !interrupted {
+                match tokio::fs::rename(&self.path, &replay_path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+                    Err(e) => return Err(StreamingError::SendError(e.to_string())),
+                }
+            }
+        }
+
+        let file = tokio::fs::File::open(&replay_path)
+            .await
+            .map_err(|e| StreamingError::SendError(e.to_string()))?;
+        let mut lines = BufReader::new(file).lines();
+        let mut replayed = 0;
+        let mut failure = None;
+        let mut remaining = Vec::new();
+
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| StreamingError::SendError(e.to_string()))?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if failure.is_some() {
+                remaining.push(line);
+                continue;
+            }
+
+            match decode_event_line(&line) {
+                Ok(event) => match target.send_event(&event).await {
+                    Ok(()) => replayed += 1,
+                    Err(e) => {
+                        failure = Some(e);
+                        remaining.push(line);
+                    }
+                },
+                Err(e) => {
+                    tracing::warn!(
+                        path = %replay_path.display(),
+                        error = %e,
+                        "Skipping unreadable line in fallback file"
+                    );
+                }
+            }
+        }
+
+        let _guard = self.write_lock.lock().await;
+        if !remaining.is_empty() {
+            self.append_lines(&remaining).await?;
+        }
+        tokio::fs::remove_file(&replay_path)
+            .await
+            .map_err(|e| StreamingError::SendError(e.to_string()))?;
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(replayed),
+        }
+    }
+}
+
+/// Decode one stored JSON line back into an event
+///
+/// Session and project properties are both flattened into the root object, so they
+/// cannot be told apart when reading back. All extra root fields are kept as session
+/// properties, which serializes to the same JSON as the original event.
+pub fn decode_event_line(line: &str) -> Result<AnalyticsEvent, StreamingError> {
+    let mut event: AnalyticsEvent = serde_json::from_str(line)?;
+    event.project_properties.clear();
+    Ok(event)
+}
+
+#[async_trait]
+impl StreamingService for FileStreaming {
+    /// Append the event as a single JSON line
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        let payload = serde_json::to_string(event)?;
+        let _guard = self.write_lock.lock().await;
+        self.append_lines(&[payload]).await?;
+
+        tracing::debug!(
+            service = "file",
+            path = %self.path.display(),
+            event_id = ?event.id,
+            "Event written to file"
+        );
+        Ok(())
+    }
+
+    /// Check that the file can be opened for appending
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map(|_| ())
+            .map_err(|e| StreamingError::HealthCheckError(format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex as StdMutex;
+
+    struct Collector {
+        events: StdMutex<Vec<AnalyticsEvent>>,
+        fail_after: Option<usize>,
+    }
+
+    #[async_trait]
+    impl StreamingService for Collector {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            let mut events = self.events.lock().unwrap();
+            if Some(events.len()) == self.fail_after {
+                return Err(StreamingError::SendError("still down".to_string()));
+            }
+            events.push(event.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    fn event(id: &str) -> AnalyticsEvent {
+        let mut session_properties = HashMap::new();
+        session_properties.insert("session_id".to_string(), "sess_1".to_string());
+        AnalyticsEvent {
+            id: Some(id.to_string()),
+            event: "pageview".to_string(),
+            session_properties,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FileStreaming::new(dir.path().join("fallback.jsonl"));
+
+        sink.send_event(&event("a")).await.unwrap();
+        sink.send_event(&event("b")).await.unwrap();
+        assert!(sink.has_pending().await);
+
+        let target = Collector { events: StdMutex::new(Vec::new()), fail_after: None };
+        let replayed = sink.replay_into(&target).await.unwrap();
+
+        assert_eq!(replayed, 2);
+        let events = target.events.lock().unwrap();
+        assert_eq!(events[0].id.as_deref(), Some("a"));
+        assert_eq!(events[1].session_properties.get("session_id").map(String::as_str), Some("sess_1"));
+        assert!(!sink.has_pending().await);
+    }
+
+    #[tokio::test]
+    async fn test_partial_replay_keeps_remaining_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FileStreaming::new(dir.path().join("fallback.jsonl"));
+        for id in ["a", "b", "c"] {
+            sink.send_event(&event(id)).await.unwrap();
+        }
+
+        let target = Collector { events: StdMutex::new(Vec::new()), fail_after: Some(1) };
+        assert!(sink.replay_into(&target).await.is_err());
+        assert!(sink.has_pending().await);
+
+        let target = Collector { events: StdMutex::new(Vec::new()), fail_after: None };
+        assert_eq!(sink.replay_into(&target).await.unwrap(), 2);
+        let ids: Vec<_> = target.events.lock().unwrap().iter().map(|e| e.id.clone().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_file_is_noop() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = FileStreaming::new(dir.path().join("missing.jsonl"));
+        let target = Collector { events: StdMutex::new(Vec::new()), fail_after: None };
+        assert_eq!(sink.replay_into(&target).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+// Event payload encoding
+// This module serializes events for the broker sinks, either nested or as a single flat object,
+// in the sink's wire format (JSON, MessagePack or CBOR)
+
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{record_serialization_time, StreamingError};
+use crate::config::{OutputConfig, OutputFormat, WireFormat};
+use crate::transformer::{AnalyticsEvent, UpdateRecord};
+
+/// Serialize `event` to the payload described by `output`, encoded as `format`
+///
+/// The layout is the same in every wire format: a MessagePack or CBOR payload decodes
+/// to the same map as the JSON one. Update events are encoded as their compact
+/// `UpdateRecord` instead of the full event. The time spent is reported for the
+/// serialization stage metric.
+pub fn encode_event(
+    event: &AnalyticsEvent,
+    output: &OutputConfig,
+    format: WireFormat,
+) -> Result<Vec<u8>, StreamingError> {
+    let started = Instant::now();
+    let payload = match UpdateRecord::from_event(event) {
+        Some(record) => encode(&record, output, format),
+        None => encode(event, output, format),
+    };
+    record_serialization_time(started.elapsed());
+    payload
+}
+
+/// Serialize `value` in the layout described by `output`, encoded as `format`
+fn encode<T: Serialize>(value: &T, output: &OutputConfig, format: WireFormat) -> Result<Vec<u8>, StreamingError> {
+    match output.format {
+        OutputFormat::Nested => serialize(value, format),
+        OutputFormat::Flat => {
+            let value = serde_json::to_value(value)?;
+            serialize(&flatten(value, output.separator.as_str()), format)
+        }
+    }
+}
+
+/// Serialize any value in the given wire format
+pub fn serialize<T: Serialize + ?Sized>(value: &T, format: WireFormat) -> Result<Vec<u8>, StreamingError> {
+    match format {
+        WireFormat::Json => Ok(serde_json::to_vec(value)?),
+        WireFormat::MsgPack => {
+            rmp_serde::to_vec_named(value).map_err(|e| StreamingError::SerializationError(e.to_string()))
+        }
+        WireFormat::Cbor => {
+            let mut payload = Vec::new();
+            ciborium::into_writer(value, &mut payload)
+                .map_err(|e| StreamingError::SerializationError(e.to_string()))?;
+            Ok(payload)
+        }
+    }
+}
+
+/// Flatten nested objects into a single object whose keys are the joined paths
+///
+/// `{"visit": {"url": "..."}}` becomes `{"visit.url": "..."}` with a `.` separator.
+/// Arrays are kept as values and empty objects are dropped. When a flattened key is
+/// produced twice (e.g. a root session property named `visit.url`), the first wins.
+pub fn flatten(value: Value, separator: &str) -> Value {
+    let mut flat = Map::new();
+    match value {
+        Value::Object(object) => flatten_into(&mut flat, None, object, separator),
+        other => return other,
+    }
+    Value::Object(flat)
+}
+
+fn flatten_into(flat: &mut Map<String, Value>, prefix: Option<&str>, object: Map<String, Value>, separator: &str) {
+    for (key, value) in object {
+        let key = match prefix {
+            Some(prefix) => format!("{}{}{}", prefix, separator, key),
+            None => key,
+        };
+        match value {
+            Value::Object(nested) => flatten_into(flat, Some(&key), nested, separator),
+            value => {
+                flat.entry(key).or_insert(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeySeparator;
+    use crate::transformer::VisitObject;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten_joins_nested_keys() {
+        let value = json!({
+            "event": "pageview",
+            "visit": {"url": "https://example.com", "url_parts": {"host": "example.com"}},
+            "objects": {},
+            "tags": ["a", "b"]
+        });
+        assert_eq!(
+            flatten(value.clone(), "."),
+            json!({
+                "event": "pageview",
+                "visit.url": "https://example.com",
+                "visit.url_parts.host": "example.com",
+                "tags": ["a", "b"]
+            })
+        );
+        assert!(flatten(value, "_").get("visit_url_parts_host").is_some());
+    }
+
+    #[test]
+    fn test_encode_event_formats() {
+        let event = AnalyticsEvent {
+            event: "pageview".to_string(),
+            visit: VisitObject {
+                url: Some("https://example.com".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let nested: Value =
+            serde_json::from_slice(&encode_event(&event, &OutputConfig::default(), WireFormat::Json).unwrap()).unwrap();
+        assert_eq!(nested["visit"]["url"], "https://example.com");
+
+        let output = OutputConfig {
+            format: OutputFormat::Flat,
+            separator: KeySeparator::Underscore,
+        };
+        let flat: Value = serde_json::from_slice(&encode_event(&event, &output, WireFormat::Json).unwrap()).unwrap();
+        assert_eq!(flat["visit_url"], "https://example.com");
+        assert_eq!(flat["event"], "pageview");
+        assert!(flat.get("visit").is_none());
+        assert!(flat.as_object().unwrap().values().all(|v| !v.is_object()));
+    }
+
+    #[test]
+    fn test_encode_event_wire_formats() {
+        let mut event = AnalyticsEvent {
+            event: "purchase".to_string(),
+            revenue: Some(rust_decimal::Decimal::new(1999, 2)),
+            visit: VisitObject {
+                url: Some("https://example.com".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        event.session.insert("plan".to_string(), "pro".to_string());
+        let output = OutputConfig::default();
+        let json: Value = serde_json::from_slice(&encode_event(&event, &output, WireFormat::Json).unwrap()).unwrap();
+
+        let msgpack = encode_event(&event, &output, WireFormat::MsgPack).unwrap();
+        let decoded: Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert_eq!(decoded, json);
+
+        let cbor = encode_event(&event, &output, WireFormat::Cbor).unwrap();
+        let decoded: Value = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(decoded, json);
+
+        let flat = OutputConfig {
+            format: OutputFormat::Flat,
+            ..Default::default()
+        };
+        let decoded: Value = rmp_serde::from_slice(&encode_event(&event, &flat, WireFormat::MsgPack).unwrap()).unwrap();
+        assert_eq!(decoded["visit.url"], "https://example.com");
+        assert_eq!(decoded["plan"], "pro");
+    }
+
+    #[test]
+    fn test_encode_update_event_as_record() {
+        let params = std::collections::HashMap::from([
+            ("id".to_string(), "evt-1".to_string()),
+            ("duration".to_string(), "5000".to_string()),
+        ]);
+        let mut event = crate::transformer::transform_update_params(&params, &crate::config::ParameterConfig::default());
+        event.country = Some("France".to_string());
+
+        let nested: Value =
+            serde_json::from_slice(&encode_event(&event, &OutputConfig::default(), WireFormat::Json).unwrap()).unwrap();
+        assert_eq!(nested["operation"], "update");
+        assert_eq!(nested["id"], "evt-1");
+        assert_eq!(nested["duration"], 5000);
+        assert!(nested.get("visit").is_none());
+        assert!(nested.get("country").is_none());
+        assert!(nested.get("event").is_none());
+    }
+}
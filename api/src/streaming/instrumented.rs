@@ -0,0 +1,213 @@
+// Instrumented streaming sink
+// This module wraps a streaming service and records delivery metrics for every send
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::metrics::{SinkMetrics, StageMetrics, STAGE_SERIALIZATION};
+use crate::transformer::AnalyticsEvent;
+
+/// What the sink reported during one instrumented send
+#[derive(Debug, Default)]
+struct SendMeasurements {
+    payload_sizes: Vec<usize>,
+    serialization_times: Vec<Duration>,
+}
+
+tokio::task_local! {
+    /// Measurements of the instrumented send in progress
+    static MEASUREMENTS: RefCell<SendMeasurements>;
+}
+
+/// Report the size of a serialized payload for the payload size metric
+///
+/// Sinks call this once per event they encode; it does nothing outside a send made
+/// through `InstrumentedStreaming` (e.g. during a replay).
+pub fn record_payload_size(bytes: usize) {
+    let _ = MEASUREMENTS.try_with(|measurements| measurements.borrow_mut().payload_sizes.push(bytes));
+}
+
+/// Report the time spent serializing one payload for the serialization stage metric
+///
+/// Called by `encode_event`; like `record_payload_size`, it does nothing outside a
+/// send made through `InstrumentedStreaming`.
+pub fn record_serialization_time(duration: Duration) {
+    let _ = MEASUREMENTS.try_with(|measurements| measurements.borrow_mut().serialization_times.push(duration));
+}
+
+/// Run `send`, collecting the measurements its sink reports
+async fn measure<F: Future<Output = Result<(), StreamingError>>>(send: F) -> (Result<(), StreamingError>, SendMeasurements) {
+    MEASUREMENTS
+        .scope(RefCell::new(SendMeasurements::default()), async {
+            let result = send.await;
+            (result, MEASUREMENTS.with(|measurements| measurements.take()))
+        })
+        .await
+}
+
+/// Streaming service decorator that records in-flight sends, latency, errors and
+/// payload sizes, by destination topic, and the serialization time of each payload
+pub struct InstrumentedStreaming {
+    inner: Arc<dyn StreamingService>,
+    metrics: Arc<SinkMetrics>,
+    stages: Arc<StageMetrics>,
+}
+
+impl InstrumentedStreaming {
+    /// Wrap `inner`, recording into `metrics`
+    pub fn new(inner: Arc<dyn StreamingService>, metrics: Arc<SinkMetrics>) -> Self {
+        InstrumentedStreaming {
+            inner,
+            metrics,
+            stages: Arc::default(),
+        }
+    }
+
+    /// Record serialization times into `stages`
+    pub fn with_stage_metrics(mut self, stages: Arc<StageMetrics>) -> Self {
+        self.stages = stages;
+        self
+    }
+
+    fn record_serialization(&self, measurements: &SendMeasurements) {
+        for duration in &measurements.serialization_times {
+            self.stages.observe(STAGE_SERIALIZATION, *duration);
+        }
+    }
+
+    /// Metrics recorded for the wrapped service
+    pub fn metrics(&self) -> &Arc<SinkMetrics> {
+        &self.metrics
+    }
+}
+
+#[async_trait]
+impl StreamingService for InstrumentedStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        let topic = self.inner.topic_for(event).unwrap_or_default();
+        self.metrics.send_started();
+        let started = Instant::now();
+        let (result, measurements) = measure(self.inner.send_event(event)).await;
+        self.metrics
+            .send_finished(&topic, started.elapsed(), result.as_ref().err().map(StreamingError::kind));
+        for size in &measurements.payload_sizes {
+            self.metrics.payload_written(&topic, *size);
+        }
+        self.record_serialization(&measurements);
+        result
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.inner.health_check().await
+    }
+
+    /// Every event of the batch is recorded with the latency and outcome of the batch
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let topics: Vec<String> = events
+            .iter()
+            .map(|event| self.inner.topic_for(event).unwrap_or_default())
+            .collect();
+        for _ in events {
+            self.metrics.send_started();
+        }
+        let started = Instant::now();
+        let (result, measurements) = measure(self.inner.send_batch(events)).await;
+        let latency = started.elapsed();
+        let error_kind = result.as_ref().err().map(StreamingError::kind);
+        for topic in &topics {
+            self.metrics.send_finished(topic, latency, error_kind);
+        }
+        // Sinks encode a batch in order, so the sizes belong to its first events
+        for (topic, size) in topics.iter().zip(&measurements.payload_sizes) {
+            self.metrics.payload_written(topic, *size);
+        }
+        self.record_serialization(&measurements);
+        result
+    }
+
+    fn queue_depth(&self) -> Option<i64> {
+        self.inner.queue_depth()
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.inner.topic_for(event)
+    }
+
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        self.inner.close(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stub {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl StreamingService for Stub {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            if self.fail {
+                Err(StreamingError::TimeoutError("slow broker".to_string()))
+            } else {
+                record_serialization_time(Duration::from_micros(80));
+                record_payload_size(42);
+                Ok(())
+            }
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+
+        fn topic_for(&self, _event: &AnalyticsEvent) -> Option<String> {
+            Some("events-stub".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_successes_and_errors() {
+        let metrics = Arc::new(SinkMetrics::new("stub"));
+        let ok = InstrumentedStreaming::new(Arc::new(Stub { fail: false }), metrics.clone());
+        let failing = InstrumentedStreaming::new(Arc::new(Stub { fail: true }), metrics.clone());
+
+        ok.send_event(&AnalyticsEvent::default()).await.unwrap();
+        assert!(failing.send_event(&AnalyticsEvent::default()).await.is_err());
+
+        let topic = metrics.topic("events-stub");
+        assert_eq!(topic.delivered(), 1);
+        assert_eq!(topic.errors().get("timeout"), Some(&1));
+        assert_eq!(metrics.in_flight(), 0);
+        assert_eq!(topic.latency().count(), 2);
+        assert_eq!(topic.payload_size().count(), 1);
+        assert_eq!(topic.payload_size().sum(), 42.0);
+    }
+
+    #[tokio::test]
+    async fn test_records_every_event_of_a_batch() {
+        let metrics = Arc::new(SinkMetrics::new("stub"));
+        let stages = Arc::new(StageMetrics::new());
+        let service = InstrumentedStreaming::new(Arc::new(Stub { fail: false }), metrics.clone())
+            .with_stage_metrics(stages.clone());
+
+        service.send_batch(&[AnalyticsEvent::default(), AnalyticsEvent::default()]).await.unwrap();
+
+        let topic = metrics.topic("events-stub");
+        assert_eq!(topic.delivered(), 2);
+        assert_eq!(topic.payload_size().count(), 2);
+        assert_eq!(metrics.in_flight(), 0);
+        assert_eq!(stages.stages()[0].0, STAGE_SERIALIZATION);
+        assert_eq!(stages.stages()[0].1.count(), 2);
+    }
+}
@@ -0,0 +1,170 @@
+// Concurrency-limited streaming sink
+// This module caps the number of in-flight sends to a streaming service with a semaphore
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::transformer::AnalyticsEvent;
+
+/// Streaming service decorator that limits concurrent in-flight sends
+///
+/// Sends beyond the limit wait for a free slot for at most `acquire_timeout`, then fail
+/// with `StreamingError::TimeoutError`. This keeps a slow broker from letting an
+/// unbounded number of pending send futures pile up in memory.
+pub struct ConcurrencyLimitedStreaming {
+    inner: Arc<dyn StreamingService>,
+    permits: Semaphore,
+    max_in_flight: usize,
+    acquire_timeout: Duration,
+}
+
+impl ConcurrencyLimitedStreaming {
+    /// Wrap `inner`, allowing at most `max_in_flight` concurrent sends (minimum 1)
+    pub fn new(inner: Arc<dyn StreamingService>, max_in_flight: usize, acquire_timeout: Duration) -> Self {
+        let max_in_flight = max_in_flight.max(1);
+        ConcurrencyLimitedStreaming {
+            inner,
+            permits: Semaphore::new(max_in_flight),
+            max_in_flight,
+            acquire_timeout,
+        }
+    }
+
+    /// Configured maximum number of concurrent sends
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Number of sends that could start right now without waiting
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+}
+
+impl ConcurrencyLimitedStreaming {
+    /// Wait for a send slot, for at most `acquire_timeout`
+    async fn acquire(&self, event_count: usize) -> Result<SemaphorePermit<'_>, StreamingError> {
+        match tokio::time::timeout(self.acquire_timeout, self.permits.acquire()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            Ok(Err(_)) => Err(StreamingError::SendError("concurrency limiter closed".to_string())),
+            Err(_) => {
+                tracing::warn!(
+                    event_count = event_count,
+                    max_in_flight = self.max_in_flight,
+                    "Concurrency limit reached, rejecting send"
+                );
+                Err(StreamingError::TimeoutError(format!(
+                    "no send slot available within {:?} ({} sends in flight)",
+                    self.acquire_timeout, self.max_in_flight
+                )))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingService for ConcurrencyLimitedStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        let _permit = self.acquire(1).await?;
+        self.inner.send_event(event).await
+    }
+
+    /// A batch is one send and takes a single slot
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let _permit = self.acquire(events.len()).await?;
+        self.inner.send_batch(events).await
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.inner.health_check().await
+    }
+
+    fn queue_depth(&self) -> Option<i64> {
+        self.inner.queue_depth()
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.inner.topic_for(event)
+    }
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        self.inner.close(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct SlowService {
+        active: AtomicUsize,
+        peak: AtomicUsize,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl StreamingService for SlowService {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    fn slow(delay: Duration) -> Arc<SlowService> {
+        Arc::new(SlowService {
+            active: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            delay,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_limits_concurrent_sends() {
+        let inner = slow(Duration::from_millis(20));
+        let limited = Arc::new(ConcurrencyLimitedStreaming::new(inner.clone(), 2, Duration::from_secs(5)));
+
+        let sends: Vec<_> = (0..6)
+            .map(|_| {
+                let limited = limited.clone();
+                tokio::spawn(async move { limited.send_event(&AnalyticsEvent::default()).await })
+            })
+            .collect();
+        for send in sends {
+            send.await.unwrap().unwrap();
+        }
+
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(limited.available(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_no_slot_frees_up_in_time() {
+        let limited = Arc::new(ConcurrencyLimitedStreaming::new(
+            slow(Duration::from_secs(5)),
+            1,
+            Duration::from_millis(10),
+        ));
+
+        let busy = limited.clone();
+        let _first = tokio::spawn(async move { busy.send_event(&AnalyticsEvent::default()).await });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        let result = limited.send_event(&AnalyticsEvent::default()).await;
+        assert!(matches!(result, Err(StreamingError::TimeoutError(_))));
+    }
+}
@@ -0,0 +1,143 @@
+// Collector metadata annotation
+// This module stamps outgoing events with the producing collector's hostname, region and version
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::config::MetadataConfig;
+use crate::transformer::{AnalyticsEvent, EventMetadata};
+
+/// Streaming service decorator that adds a `_meta` object to every event
+pub struct MetadataStreaming {
+    inner: Arc<dyn StreamingService>,
+    template: EventMetadata,
+}
+
+impl MetadataStreaming {
+    /// Wrap `inner`, resolving hostname and version defaults from the environment
+    pub fn new(inner: Arc<dyn StreamingService>, config: &MetadataConfig) -> Self {
+        MetadataStreaming {
+            inner,
+            template: EventMetadata {
+                hostname: config.hostname.clone().unwrap_or_else(local_hostname),
+                region: config.region.clone(),
+                pipeline_version: config
+                    .pipeline_version
+                    .clone()
+                    .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string()),
+                ingested_at: 0,
+            },
+        }
+    }
+
+    /// Copy of `event` annotated with collector metadata and the current time
+    fn annotate(&self, event: &AnalyticsEvent) -> AnalyticsEvent {
+        let mut event = event.clone();
+        event.meta = Some(EventMetadata {
+            ingested_at: chrono::Utc::now().timestamp_millis(),
+            ..self.template.clone()
+        });
+        event
+    }
+}
+
+/// Best-effort hostname of this machine
+fn local_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .filter(|h| !h.is_empty())
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|h| h.trim().to_string())
+                .filter(|h| !h.is_empty())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[async_trait]
+impl StreamingService for MetadataStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        self.inner.send_event(&self.annotate(event)).await
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.inner.health_check().await
+    }
+
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let events: Vec<AnalyticsEvent> = events.iter().map(|e| self.annotate(e)).collect();
+        self.inner.send_batch(&events).await
+    }
+
+    fn queue_depth(&self) -> Option<i64> {
+        self.inner.queue_depth()
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.inner.topic_for(event)
+    }
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        self.inner.close(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Capture {
+        events: Mutex<Vec<AnalyticsEvent>>,
+    }
+
+    #[async_trait]
+    impl StreamingService for Capture {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_annotates_events() {
+        let capture = Arc::new(Capture { events: Mutex::new(Vec::new()) });
+        let service = MetadataStreaming::new(
+            capture.clone(),
+            &MetadataConfig {
+                hostname: Some("collector-1".to_string()),
+                region: Some("eu-west-1".to_string()),
+                pipeline_version: None,
+            },
+        );
+
+        service.send_event(&AnalyticsEvent::default()).await.unwrap();
+
+        let events = capture.events.lock().unwrap();
+        let meta = events[0].meta.as_ref().unwrap();
+        assert_eq!(meta.hostname, "collector-1");
+        assert_eq!(meta.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(meta.pipeline_version, env!("CARGO_PKG_VERSION"));
+        assert!(meta.ingested_at > 0);
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["_meta"]["hostname"], "collector-1");
+    }
+
+    #[test]
+    fn test_events_without_metadata_omit_meta() {
+        let json = serde_json::to_value(AnalyticsEvent::default()).unwrap();
+        assert!(json.get("_meta").is_none());
+    }
+}
@@ -4,13 +4,50 @@
 
 use async_trait::async_trait;
 use serde_json;
+use std::collections::HashMap;
 use std::fmt;
+use std::time::Duration;
 
+use crate::config::{OutputConfig, WireFormat};
+use crate::metrics::{SinkMetrics, StageMetrics};
 use crate::transformer::AnalyticsEvent;
 
+pub mod circuit_breaker;
+pub mod fallback;
+pub mod file;
+pub mod format;
+pub mod instrumented;
+pub mod limit;
+pub mod metadata;
+pub mod queued;
+pub mod residency;
+pub mod retention;
+pub mod stdout;
+pub mod topic;
+
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use fallback::FallbackStreaming;
+pub use file::FileStreaming;
+pub use format::encode_event;
+pub use instrumented::{record_payload_size, record_serialization_time, InstrumentedStreaming};
+pub use limit::ConcurrencyLimitedStreaming;
+pub use metadata::MetadataStreaming;
+pub use queued::QueuedStreaming;
+pub use residency::ResidencyStreaming;
+pub use retention::RetentionStreaming;
+pub use stdout::StdoutStreaming;
+pub use topic::{TopicRouter, TopicTemplate};
+
+/// Message header (Kafka) or property (Pulsar) carrying the event's schema version,
+/// so consumers can branch on the layout before parsing the payload
+pub const SCHEMA_VERSION_HEADER: &str = "schema_version";
+/// Message header (Kafka) or property (Pulsar) carrying the payload's media type
+/// (`application/json`, `application/msgpack` or `application/cbor`)
+pub const CONTENT_TYPE_HEADER: &str = "content_type";
+
 /// Error types for streaming service operations
 /// Validates: Requirement 7.7
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StreamingError {
     /// Connection error to the streaming service
     ConnectionError(String),
@@ -22,6 +59,8 @@ pub enum StreamingError {
     HealthCheckError(String),
     /// Configuration error
     ConfigError(String),
+    /// Send did not complete within the configured send timeout
+    TimeoutError(String),
 }
 
 impl fmt::Display for StreamingError {
@@ -32,6 +71,21 @@ impl fmt::Display for StreamingError {
             StreamingError::SendError(msg) => write!(f, "Send error: {}", msg),
             StreamingError::HealthCheckError(msg) => write!(f, "Health check error: {}", msg),
             StreamingError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            StreamingError::TimeoutError(msg) => write!(f, "Timeout error: {}", msg),
+        }
+    }
+}
+
+impl StreamingError {
+    /// Short label for the error variant, used to group error counts in metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StreamingError::ConnectionError(_) => "connection",
+            StreamingError::SerializationError(_) => "serialization",
+            StreamingError::SendError(_) => "send",
+            StreamingError::HealthCheckError(_) => "health_check",
+            StreamingError::ConfigError(_) => "config",
+            StreamingError::TimeoutError(_) => "timeout",
         }
     }
 }
@@ -44,6 +98,30 @@ impl From<serde_json::Error> for StreamingError {
     }
 }
 
+/// Default bound on how long a single send may take before it is treated as failed
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Resolve a sink's `send_timeout_ms` setting, falling back to the default
+pub fn send_timeout_from_config(send_timeout_ms: Option<u64>) -> Duration {
+    send_timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_SEND_TIMEOUT)
+}
+
+/// Await a send future, failing with `StreamingError::TimeoutError` if it exceeds `timeout`
+async fn with_send_timeout<F, T>(timeout: Duration, send: F) -> Result<T, StreamingError>
+where
+    F: std::future::Future<Output = Result<T, StreamingError>>,
+{
+    match tokio::time::timeout(timeout, send).await {
+        Ok(result) => result,
+        Err(_) => Err(StreamingError::TimeoutError(format!(
+            "send did not complete within {}ms",
+            timeout.as_millis()
+        ))),
+    }
+}
+
 /// Trait defining the interface for streaming service implementations
 /// Validates: Requirement 7.1, 7.8
 #[async_trait]
@@ -55,20 +133,223 @@ pub trait StreamingService: Send + Sync {
     /// Check the health of the streaming service connection
     /// Validates: Requirement 7.1
     async fn health_check(&self) -> Result<(), StreamingError>;
+
+    /// Send a batch of analytics events
+    ///
+    /// The default implementation sends events one at a time and stops at the first
+    /// failure. Implementations that support atomic writes (e.g. Kafka transactions)
+    /// override this so the batch is delivered all-or-nothing.
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        for event in events {
+            self.send_event(event).await?;
+        }
+        Ok(())
+    }
+
+    /// Number of messages buffered inside the client library awaiting delivery
+    ///
+    /// Returns None for sinks that don't expose an internal queue.
+    fn queue_depth(&self) -> Option<i64> {
+        None
+    }
+
+    /// Circuit breaker guarding the primary sink, when a fallback is configured
+    ///
+    /// Wrappers return the breaker of the service they wrap.
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        None
+    }
+
+    /// Topic (or stream) an event is written to, used to label its metrics
+    ///
+    /// Returns None for sinks without topics. Wrappers return the topic of the
+    /// service the event is handed to.
+    fn topic_for(&self, _event: &AnalyticsEvent) -> Option<String> {
+        None
+    }
+
+    /// Stop accepting events and deliver the ones already acknowledged
+    ///
+    /// Called once on shutdown, after the server stopped taking requests, and waits at
+    /// most `timeout`. The default does nothing; wrappers close the service they wrap.
+    async fn close(&self, _timeout: Duration) -> Result<(), StreamingError> {
+        Ok(())
+    }
 }
 
 // Kafka streaming service implementation
 // Validates: Requirements 7.2, 13.3
 
 use rdkafka::config::ClientConfig;
-use rdkafka::producer::{FutureProducer, FutureRecord};
-use std::time::Duration;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer as _};
+
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::types::RDKafkaErrorCode;
+
+use crate::config::{KafkaConfig, KafkaTopicCreationConfig};
+
+/// Kafka headers sent with every event
+fn kafka_headers(event: &AnalyticsEvent, format: WireFormat) -> OwnedHeaders {
+    let schema_version = event.schema_version.to_string();
+    OwnedHeaders::new()
+        .insert(Header {
+            key: SCHEMA_VERSION_HEADER,
+            value: Some(schema_version.as_str()),
+        })
+        .insert(Header {
+            key: CONTENT_TYPE_HEADER,
+            value: Some(format.content_type()),
+        })
+}
+
+/// Set the SASL settings of `config`, if any, on a producer or admin client
+fn apply_kafka_sasl(client_config: &mut ClientConfig, config: &KafkaConfig) {
+    if let Some(sasl) = &config.sasl {
+        client_config
+            .set("security.protocol", &sasl.security_protocol)
+            .set("sasl.mechanism", &sasl.mechanism)
+            .set("sasl.username", &sasl.username)
+            .set("sasl.password", &sasl.password);
+    }
+}
+
+/// Create a plain producer on the brokers of `config`, for records that are not
+/// analytics events (e.g. the audit log)
+///
+/// The producer is never transactional and ignores the topic settings of `config`.
+pub fn create_kafka_producer(config: &KafkaConfig) -> Result<FutureProducer, StreamingError> {
+    let send_timeout = send_timeout_from_config(config.send_timeout_ms);
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set("bootstrap.servers", config.brokers.join(","))
+        .set("message.timeout.ms", send_timeout.as_millis().to_string());
+    apply_kafka_sasl(&mut client_config, config);
+    client_config
+        .create()
+        .map_err(|e| StreamingError::ConnectionError(e.to_string()))
+}
+
+/// How long a Kafka health check waits for cluster metadata
+const KAFKA_METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long topic creation waits for the brokers to confirm
+const KAFKA_ADMIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most events committed together in one Kafka transaction
+const KAFKA_TRANSACTION_MAX_EVENTS: usize = 1000;
+
+/// Sends waiting for the next Kafka transaction before further senders wait for room
+const KAFKA_TRANSACTION_QUEUE: usize = 1000;
+
+/// Create the configured Kafka topics if they do not exist yet
+///
+/// Only static topic names can be created up front; templated topics are skipped
+/// with a warning. Topics that already exist are left untouched.
+///
+/// # Returns
+/// The names of the topics that were created
+///
+/// # Errors
+/// Returns `StreamingError::ConnectionError` if the admin client cannot be created or
+/// the brokers reject a topic for a reason other than it already existing
+pub async fn create_kafka_topics(
+    config: &KafkaConfig,
+    settings: &KafkaTopicCreationConfig,
+) -> Result<Vec<String>, StreamingError> {
+    let router = TopicRouter::new(TopicTemplate::parse(&config.topic)?)
+        .with_routes(&config.event_topics)?;
+    if !router.is_static() {
+        tracing::warn!(
+            service = "kafka",
+            "Templated Kafka topics cannot be created at startup, skipping them"
+        );
+    }
+
+    let mut topics: Vec<&str> = router.static_topics();
+    topics.sort_unstable();
+    topics.dedup();
+    if topics.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut client_config = ClientConfig::new();
+    client_config.set("bootstrap.servers", config.brokers.join(","));
+    apply_kafka_sasl(&mut client_config, config);
+    let admin: AdminClient<DefaultClientContext> = client_config
+        .create()
+        .map_err(|e| StreamingError::ConnectionError(e.to_string()))?;
+
+    let retention_ms = settings.retention_ms.map(|ms| ms.to_string());
+    let new_topics: Vec<NewTopic> = topics
+        .iter()
+        .map(|topic| {
+            let new_topic = NewTopic::new(
+                topic,
+                settings.partitions,
+                TopicReplication::Fixed(settings.replication_factor),
+            );
+            match &retention_ms {
+                Some(retention_ms) => new_topic.set("retention.ms", retention_ms),
+                None => new_topic,
+            }
+        })
+        .collect();
+
+    let options = AdminOptions::new().operation_timeout(Some(KAFKA_ADMIN_TIMEOUT));
+    let results = admin
+        .create_topics(&new_topics, &options)
+        .await
+        .map_err(|e| StreamingError::ConnectionError(format!("Failed to create Kafka topics: {}", e)))?;
+
+    let mut created = Vec::new();
+    for result in results {
+        match result {
+            Ok(topic) => {
+                tracing::info!(
+                    service = "kafka",
+                    topic = %topic,
+                    partitions = settings.partitions,
+                    replication_factor = settings.replication_factor,
+                    "Created Kafka topic"
+                );
+                created.push(topic);
+            }
+            Err((topic, RDKafkaErrorCode::TopicAlreadyExists)) => {
+                tracing::debug!(
+                    service = "kafka",
+                    topic = %topic,
+                    "Kafka topic already exists"
+                );
+            }
+            Err((topic, code)) => {
+                return Err(StreamingError::ConnectionError(format!(
+                    "Failed to create Kafka topic '{}': {}",
+                    topic, code
+                )));
+            }
+        }
+    }
+
+    Ok(created)
+}
 
 /// Kafka streaming service implementation
 /// Validates: Requirement 7.2
 pub struct KafkaStreaming {
     producer: FutureProducer,
-    topic: String,
+    topic: TopicRouter,
+    send_timeout: Duration,
+    /// Hands sends to the transaction task when transactional (exactly-once) mode is enabled
+    /// A producer can only have one open transaction at a time, so one task runs them all
+    transactions: Option<tokio::sync::mpsc::Sender<TransactionJob>>,
+    /// Transactional id to initialize transactions with in `connect`
+    transactional_id: Option<String>,
+    /// Payload layout (nested or flat)
+    output: OutputConfig,
+    /// Payload serialization (JSON, MessagePack or CBOR)
+    format: WireFormat,
 }
 
 impl KafkaStreaming {
@@ -76,85 +357,236 @@ impl KafkaStreaming {
     /// 
     /// # Arguments
     /// * `brokers` - List of Kafka broker addresses (e.g., ["localhost:9092"])
-    /// * `topic` - Kafka topic to send events to
+    /// * `topic` - Kafka topic to send events to, optionally templated (e.g. "analytics-{project}")
     /// 
     /// # Returns
     /// * `Ok(KafkaStreaming)` - Successfully created Kafka streaming service
-    /// * `Err(StreamingError)` - Failed to create Kafka producer
+    /// * `Err(StreamingError)` - Failed to create Kafka producer or invalid topic template
     /// 
     /// # Validates
     /// * Requirement 13.3 - Connection pooling and reuse
     pub fn new(brokers: &[String], topic: String) -> Result<Self, StreamingError> {
-        let broker_list = brokers.join(",");
+        Self::from_config(&KafkaConfig {
+            brokers: brokers.to_vec(),
+            topic,
+            ..Default::default()
+        })
+    }
+
+    /// Create a Kafka streaming service from its configuration section
+    ///
+    /// Applies the topic template, per-event topic routes, and send timeout.
+    /// The send timeout also bounds librdkafka's `message.timeout.ms`, so a message
+    /// is never retried internally past the point the caller gave up on it.
+    ///
+    /// When `transactional_id` is set the producer is idempotent and transactional,
+    /// but transactions are only initialized by `connect`.
+    pub fn from_config(config: &KafkaConfig) -> Result<Self, StreamingError> {
+        let topic = TopicRouter::new(TopicTemplate::parse(&config.topic)?)
+            .with_routes(&config.event_topics)?;
+        let send_timeout = send_timeout_from_config(config.send_timeout_ms);
+        let broker_list = config.brokers.join(",");
         
         // Create Kafka producer with connection pooling
         // Validates: Requirement 13.3
-        let producer: FutureProducer = ClientConfig::new()
+        let mut client_config = ClientConfig::new();
+        client_config
             .set("bootstrap.servers", &broker_list)
-            .set("message.timeout.ms", "5000")
+            .set("message.timeout.ms", send_timeout.as_millis().to_string())
             .set("queue.buffering.max.messages", "100000")
             .set("queue.buffering.max.kbytes", "1048576")
-            .set("batch.num.messages", "10000")
+            .set("batch.num.messages", "10000");
+
+        apply_kafka_sasl(&mut client_config, config);
+
+        if let Some(transactional_id) = &config.transactional_id {
+            client_config
+                .set("transactional.id", transactional_id)
+                .set("enable.idempotence", "true");
+        }
+
+        let producer: FutureProducer = client_config
             .create()
             .map_err(|e| StreamingError::ConnectionError(e.to_string()))?;
         
-        Ok(KafkaStreaming { producer, topic })
+        Ok(KafkaStreaming {
+            producer,
+            topic,
+            send_timeout,
+            transactions: None,
+            transactional_id: config.transactional_id.clone(),
+            output: OutputConfig::default(),
+            format: config.format,
+        })
+    }
+
+    /// Create a Kafka streaming service from its configuration section and, when
+    /// `transactional_id` is set, initialize transactions
+    ///
+    /// Initializing contacts the transaction coordinator (off the async workers) and
+    /// starts the task that commits the sends: sends arriving while a transaction is
+    /// committed are grouped into the next one, up to `KAFKA_TRANSACTION_MAX_EVENTS`
+    /// events, and a batch always lands in a single transaction.
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConnectionError` if transactions cannot be initialized
+    pub async fn connect(config: &KafkaConfig) -> Result<Self, StreamingError> {
+        let mut service = Self::from_config(config)?;
+        if service.transactional_id.is_none() {
+            return Ok(service);
+        }
+
+        let producer = service.producer.clone();
+        let timeout = service.send_timeout;
+        tokio::task::spawn_blocking(move || producer.init_transactions(timeout))
+            .await
+            .map_err(|e| StreamingError::ConnectionError(e.to_string()))?
+            .map_err(|e| StreamingError::ConnectionError(format!(
+                "Failed to initialize Kafka transactions: {}",
+                e
+            )))?;
+        tracing::info!(
+            service = "kafka",
+            transactional_id = ?service.transactional_id,
+            "Kafka transactional producer initialized"
+        );
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(KAFKA_TRANSACTION_QUEUE);
+        tokio::spawn(run_transactions(service.producer.clone(), timeout, receiver));
+        service.transactions = Some(sender);
+        Ok(service)
+    }
+
+    /// Encode payloads in the given layout instead of nested JSON
+    pub fn with_output(mut self, output: &OutputConfig) -> Self {
+        self.output = output.clone();
+        self
+    }
+
+    /// Whether this producer writes with exactly-once transactions
+    pub fn is_transactional(&self) -> bool {
+        self.transactions.is_some()
+    }
+
+    /// Send events in a Kafka transaction, together with any other sends waiting for one
+    ///
+    /// Returns once the transaction holding the events is committed or aborted. The
+    /// commit is bounded by the send timeout; the send itself is not cut short, so a
+    /// transaction is never aborted while its commit may still be in flight.
+    async fn send_transactional(
+        &self,
+        transactions: &tokio::sync::mpsc::Sender<TransactionJob>,
+        events: &[AnalyticsEvent],
+    ) -> Result<(), StreamingError> {
+        let records = events
+            .iter()
+            .map(|event| {
+                Ok(KafkaRecord {
+                    topic: self.topic.render(event),
+                    payload: encode_event(event, &self.output, self.format).inspect(|payload| record_payload_size(payload.len()))?,
+                    key: event.id.clone().unwrap_or_default(),
+                    headers: kafka_headers(event, self.format),
+                })
+            })
+            .collect::<Result<Vec<_>, StreamingError>>()?;
+
+        let (done, outcome) = tokio::sync::oneshot::channel();
+        transactions
+            .send(TransactionJob { records, done })
+            .await
+            .map_err(|_| StreamingError::SendError("Kafka transaction task stopped".to_string()))?;
+        outcome
+            .await
+            .map_err(|_| StreamingError::SendError("Kafka transaction task stopped".to_string()))?
+    }
+
+    /// Route specific event names (e.g. identify, revenue) to their own topics
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a route topic is not a valid template
+    pub fn with_topic_routes(mut self, routes: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.topic = self.topic.with_routes(routes)?;
+        Ok(self)
+    }
+
+    /// Send the events of specific projects to their own topics
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a project topic is not a valid template
+    pub fn with_project_topics(mut self, topics: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.topic = self.topic.with_project_routes(topics)?;
+        Ok(self)
     }
 }
 
 #[async_trait]
 impl StreamingService for KafkaStreaming {
     /// Send an analytics event to Kafka
-    /// Serializes the event to JSON and sends it to the configured topic
+    /// Serializes the event and sends it to the configured topic
     /// Validates: Requirements 7.2, 7.6
     async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        // In transactional mode the event is committed with the other pending sends
+        if let Some(transactions) = &self.transactions {
+            return self.send_transactional(transactions, std::slice::from_ref(event)).await;
+        }
+
+        // Resolve the destination topic (templated topics vary per event)
+        // A single FutureProducer can write to any topic, so no per-topic producer is needed
+        let topic = self.topic.render(event);
+
         tracing::debug!(
             service = "kafka",
-            topic = %self.topic,
+            topic = %topic,
             event_id = ?event.id,
             "Serializing event for Kafka"
         );
         
-        // Serialize event to JSON
+        // Serialize event in the configured layout and wire format
         // Validates: Requirement 7.2
-        let payload = serde_json::to_string(event)?;
+        let payload = encode_event(event, &self.output, self.format)?;
+        record_payload_size(payload.len());
         
         // Use event ID as key for partitioning, or empty string if no ID
         let key = event.id.as_deref().unwrap_or("");
         
         tracing::debug!(
             service = "kafka",
-            topic = %self.topic,
+            topic = %topic,
             event_id = ?event.id,
             payload_size = payload.len(),
             "Sending event to Kafka"
         );
         
-        // Create Kafka record
-        let record = FutureRecord::to(&self.topic)
+        // Create Kafka record, tagged with the schema version header
+        let record = FutureRecord::to(&topic)
             .payload(&payload)
-            .key(key);
+            .key(key)
+            .headers(kafka_headers(event, self.format));
         
-        // Send to Kafka with timeout
+        // Send to Kafka, bounded by the configured send timeout
         // The producer is reused across requests (connection pooling)
         // Validates: Requirement 13.3
-        self.producer
-            .send(record, Duration::from_secs(0))
-            .await
-            .map_err(|(err, _)| {
-                tracing::error!(
-                    service = "kafka",
-                    topic = %self.topic,
-                    event_id = ?event.id,
-                    error = %err,
-                    "Failed to send event to Kafka"
-                );
-                StreamingError::SendError(err.to_string())
-            })?;
+        with_send_timeout(self.send_timeout, async {
+            self.producer
+                .send(record, Duration::from_secs(0))
+                .await
+                .map_err(|(err, _)| StreamingError::SendError(err.to_string()))
+        })
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                service = "kafka",
+                topic = %topic,
+                event_id = ?event.id,
+                error = %err,
+                "Failed to send event to Kafka"
+            );
+            err
+        })?;
         
         tracing::info!(
             service = "kafka",
-            topic = %self.topic,
+            topic = %topic,
             event_id = ?event.id,
             "Event sent to Kafka successfully"
         );
@@ -165,13 +597,191 @@ impl StreamingService for KafkaStreaming {
     /// Check Kafka connection health
     /// Validates: Requirement 7.1
     async fn health_check(&self) -> Result<(), StreamingError> {
-        // For Kafka, we check if the producer is still valid
-        // A more thorough check would query cluster metadata
-        // but for now we just verify the producer exists
-        Ok(())
+        // Fetch cluster metadata and verify that every statically known topic exists
+        // Metadata requests are blocking in librdkafka, so run them off the async runtime
+        let producer = self.producer.clone();
+        let topics: Vec<String> = self
+            .topic
+            .static_topics()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        tokio::task::spawn_blocking(move || {
+            let metadata = producer
+                .client()
+                .fetch_metadata(None, KAFKA_METADATA_TIMEOUT)
+                .map_err(|e| StreamingError::HealthCheckError(e.to_string()))?;
+
+            if metadata.brokers().is_empty() {
+                return Err(StreamingError::HealthCheckError(
+                    "Kafka cluster metadata contains no brokers".to_string(),
+                ));
+            }
+
+            for topic in &topics {
+                let found = metadata
+                    .topics()
+                    .iter()
+                    .any(|t| t.name() == topic && t.error().is_none());
+                if !found {
+                    return Err(StreamingError::HealthCheckError(format!(
+                        "Kafka topic '{}' not found in cluster metadata",
+                        topic
+                    )));
+                }
+            }
+
+            Ok(())
+        })
+        .await
+        .map_err(|e| StreamingError::HealthCheckError(e.to_string()))?
+    }
+
+    /// Send a batch of events to Kafka
+    /// In transactional mode the whole batch is committed in one transaction
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        match &self.transactions {
+            Some(transactions) => self.send_transactional(transactions, events).await,
+            None => {
+                for event in events {
+                    self.send_event(event).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+    /// Messages queued in librdkafka that have not yet been acknowledged by the broker
+    fn queue_depth(&self) -> Option<i64> {
+        Some(i64::from(self.producer.in_flight_count()))
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        Some(self.topic.render(event))
+    }
+}
+
+/// One encoded Kafka message
+struct KafkaRecord {
+    topic: String,
+    payload: Vec<u8>,
+    key: String,
+    headers: OwnedHeaders,
+}
+
+/// Messages of one send waiting for a transaction, and where to report its outcome
+struct TransactionJob {
+    records: Vec<KafkaRecord>,
+    done: tokio::sync::oneshot::Sender<Result<(), StreamingError>>,
+}
+
+/// Commit the queued sends in transactions until every sender is dropped
+///
+/// Each transaction takes the next send and every send already waiting behind it,
+/// up to `KAFKA_TRANSACTION_MAX_EVENTS` events; all of them get its outcome.
+async fn run_transactions(
+    producer: FutureProducer,
+    timeout: Duration,
+    mut jobs: tokio::sync::mpsc::Receiver<TransactionJob>,
+) {
+    while let Some(job) = jobs.recv().await {
+        let mut event_count = job.records.len();
+        let mut batch = vec![job];
+        while event_count < KAFKA_TRANSACTION_MAX_EVENTS {
+            match jobs.try_recv() {
+                Ok(job) => {
+                    event_count += job.records.len();
+                    batch.push(job);
+                }
+                Err(_) => break,
+            }
+        }
+
+        let records: Vec<&KafkaRecord> = batch.iter().flat_map(|job| &job.records).collect();
+        let result = commit_records(&producer, timeout, &records).await;
+        match &result {
+            Ok(()) => tracing::info!(
+                service = "kafka",
+                event_count = event_count,
+                "Kafka transaction committed"
+            ),
+            Err(e) => tracing::error!(
+                service = "kafka",
+                event_count = event_count,
+                error = %e,
+                "Kafka transaction failed"
+            ),
+        }
+        for job in batch {
+            let _ = job.done.send(result.clone());
+        }
     }
 }
 
+/// Write `records` in one transaction, aborting it if anything fails
+async fn commit_records(producer: &FutureProducer, timeout: Duration, records: &[&KafkaRecord]) -> Result<(), StreamingError> {
+    producer
+        .begin_transaction()
+        .map_err(|e| StreamingError::SendError(format!("Failed to begin Kafka transaction: {}", e)))?;
+
+    match produce_and_commit(producer, timeout, records).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            abort_transaction(producer, timeout).await;
+            Err(e)
+        }
+    }
+}
+
+/// Produce all records inside the currently open transaction and commit it
+///
+/// The commit runs to completion (it is bounded by `timeout`) before this returns.
+async fn produce_and_commit(producer: &FutureProducer, timeout: Duration, records: &[&KafkaRecord]) -> Result<(), StreamingError> {
+    // Enqueue every record first so librdkafka can batch them
+    let mut deliveries = Vec::with_capacity(records.len());
+    for record in records {
+        let message = FutureRecord::to(&record.topic)
+            .payload(&record.payload)
+            .key(&record.key)
+            .headers(record.headers.clone());
+        let delivery = producer
+            .send_result(message)
+            .map_err(|(err, _)| StreamingError::SendError(err.to_string()))?;
+        deliveries.push(delivery);
+    }
+
+    // Commit flushes all outstanding messages of the transaction
+    let committing = producer.clone();
+    tokio::task::spawn_blocking(move || committing.commit_transaction(timeout))
+        .await
+        .map_err(|e| StreamingError::SendError(e.to_string()))?
+        .map_err(|e| StreamingError::SendError(format!("Failed to commit Kafka transaction: {}", e)))?;
+
+    for delivery in deliveries {
+        delivery
+            .await
+            .map_err(|_| StreamingError::SendError("Kafka delivery canceled".to_string()))?
+            .map_err(|(err, _)| StreamingError::SendError(err.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Abort the currently open transaction, logging (not returning) any abort failure
+async fn abort_transaction(producer: &FutureProducer, timeout: Duration) {
+    let producer = producer.clone();
+    let error = match tokio::task::spawn_blocking(move || producer.abort_transaction(timeout)).await {
+        Ok(Ok(())) => return,
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+    tracing::error!(
+        service = "kafka",
+        error = %error,
+        "Failed to abort Kafka transaction"
+    );
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -185,7 +795,12 @@ use aws_sdk_kinesis::primitives::Blob;
 /// Validates: Requirement 7.3
 pub struct KinesisStreaming {
     client: KinesisClient,
-    stream_name: String,
+    streams: TopicRouter,
+    send_timeout: Duration,
+    /// Payload layout (nested or flat)
+    output: OutputConfig,
+    /// Payload serialization (JSON, MessagePack or CBOR)
+    format: WireFormat,
 }
 
 impl KinesisStreaming {
@@ -193,77 +808,129 @@ impl KinesisStreaming {
     ///
     /// # Arguments
     /// * `client` - AWS Kinesis client (configured with credentials and region)
-    /// * `stream_name` - Kinesis stream name to send events to
+    /// * `stream_name` - Kinesis stream name to send events to, optionally templated
     ///
     /// # Returns
     /// * `KinesisStreaming` - Kinesis streaming service instance
     ///
+    /// An invalid template is treated as a literal stream name; templates are
+    /// validated when the configuration is loaded.
+    ///
     /// # Validates
     /// * Requirement 13.3 - Connection pooling and reuse (AWS SDK handles this internally)
     pub fn new(client: KinesisClient, stream_name: String) -> Self {
+        let stream_template = TopicTemplate::parse(&stream_name)
+            .unwrap_or_else(|_| TopicTemplate::literal(&stream_name));
         KinesisStreaming {
             client,
-            stream_name,
+            streams: TopicRouter::new(stream_template),
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+            output: OutputConfig::default(),
+            format: WireFormat::default(),
         }
     }
+
+    /// Encode payloads in the given layout instead of nested JSON
+    pub fn with_output(mut self, output: &OutputConfig) -> Self {
+        self.output = output.clone();
+        self
+    }
+
+    /// Serialize payloads as `format` instead of JSON
+    pub fn with_format(mut self, format: WireFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Bound how long a single PutRecord call may take before it is treated as failed
+    pub fn with_send_timeout(mut self, send_timeout: Duration) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
+    /// Route specific event names (e.g. identify, revenue) to their own streams
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a route stream name is not a valid template
+    pub fn with_stream_routes(mut self, routes: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.streams = self.streams.with_routes(routes)?;
+        Ok(self)
+    }
+
+    /// Send the events of specific projects to their own streams
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a project stream name is not a valid template
+    pub fn with_project_streams(mut self, streams: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.streams = self.streams.with_project_routes(streams)?;
+        Ok(self)
+    }
 }
 
 #[async_trait]
 impl StreamingService for KinesisStreaming {
     /// Send an analytics event to Kinesis
-    /// Serializes the event to JSON and sends it to the configured stream
+    /// Serializes the event and sends it to the configured stream
     /// Validates: Requirements 7.3, 7.6
     async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        // Resolve the destination stream (templated stream names vary per event)
+        let stream_name = self.streams.render(event);
+
         tracing::debug!(
             service = "kinesis",
-            stream = %self.stream_name,
+            stream = %stream_name,
             event_id = ?event.id,
             "Serializing event for Kinesis"
         );
         
-        // Serialize event to JSON
+        // Serialize event in the configured layout and wire format
         // Validates: Requirement 7.3
-        let payload = serde_json::to_string(event)?;
+        let payload = encode_event(event, &self.output, self.format)?;
+        record_payload_size(payload.len());
 
         // Convert to AWS Blob
-        let blob = Blob::new(payload.as_bytes());
+        let blob = Blob::new(payload.as_slice());
 
         // Use event ID as partition key, or "default" if no ID
         let partition_key = event.id.as_deref().unwrap_or("default");
 
         tracing::debug!(
             service = "kinesis",
-            stream = %self.stream_name,
+            stream = %stream_name,
             event_id = ?event.id,
             payload_size = payload.len(),
             partition_key = partition_key,
             "Sending event to Kinesis"
         );
 
-        // Send to Kinesis
+        // Send to Kinesis, bounded by the configured send timeout
         // The client is reused across requests (connection pooling)
         // Validates: Requirement 13.3
-        self.client
-            .put_record()
-            .stream_name(&self.stream_name)
-            .data(blob)
-            .partition_key(partition_key)
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!(
-                    service = "kinesis",
-                    stream = %self.stream_name,
-                    event_id = ?event.id,
-                    error = %e,
-                    "Failed to send event to Kinesis"
-                );
-                StreamingError::SendError(e.to_string())
-            })?;
+        with_send_timeout(self.send_timeout, async {
+            self.client
+                .put_record()
+                .stream_name(&stream_name)
+                .data(blob)
+                .partition_key(partition_key)
+                .send()
+                .await
+                .map_err(|e| StreamingError::SendError(e.to_string()))
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                service = "kinesis",
+                stream = %stream_name,
+                event_id = ?event.id,
+                error = %e,
+                "Failed to send event to Kinesis"
+            );
+            e
+        })?;
 
         tracing::info!(
             service = "kinesis",
-            stream = %self.stream_name,
+            stream = %stream_name,
             event_id = ?event.id,
             "Event sent to Kinesis successfully"
         );
@@ -274,29 +941,59 @@ impl StreamingService for KinesisStreaming {
     /// Check Kinesis stream health
     /// Validates: Requirement 7.1
     async fn health_check(&self) -> Result<(), StreamingError> {
-        // Check if the stream exists and is active
-        self.client
-            .describe_stream()
-            .stream_name(&self.stream_name)
-            .send()
-            .await
-            .map_err(|e| StreamingError::HealthCheckError(e.to_string()))?;
+        // Templated stream names have no single stream to describe,
+        // so only verify that the Kinesis API is reachable
+        if !self.streams.is_static() {
+            self.client
+                .list_streams()
+                .limit(1)
+                .send()
+                .await
+                .map_err(|e| StreamingError::HealthCheckError(e.to_string()))?;
+            return Ok(());
+        }
+
+        // Check if every configured stream exists and is active
+        for stream_name in self.streams.static_topics() {
+            self.client
+                .describe_stream()
+                .stream_name(stream_name)
+                .send()
+                .await
+                .map_err(|e| StreamingError::HealthCheckError(e.to_string()))?;
+        }
 
         Ok(())
     }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        Some(self.streams.render(event))
+    }
 }
 
 // Pulsar streaming service implementation
 // Validates: Requirements 7.4, 13.3
 
-use pulsar::{Producer, Pulsar, TokioExecutor};
+use pulsar::{producer, Producer, Pulsar, TokioExecutor};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 /// Pulsar streaming service implementation
 /// Validates: Requirement 7.4
 pub struct PulsarStreaming {
-    producer: Arc<Mutex<Producer<TokioExecutor>>>,
+    client: Pulsar<TokioExecutor>,
+    topic: TopicRouter,
+    /// Producers keyed by rendered topic name, created lazily on first use
+    producers: Mutex<HashMap<String, Arc<Mutex<Producer<TokioExecutor>>>>>,
+    send_timeout: Duration,
+    /// Topic receiving events that exhausted their delivery attempts
+    dead_letter_topic: Option<TopicTemplate>,
+    /// Additional delivery attempts after the first failure
+    max_redeliveries: u32,
+    /// Payload layout (nested or flat)
+    output: OutputConfig,
+    /// Payload serialization (JSON, MessagePack or CBOR)
+    format: WireFormat,
 }
 
 impl PulsarStreaming {
@@ -304,79 +1001,279 @@ impl PulsarStreaming {
     ///
     /// # Arguments
     /// * `pulsar_url` - Pulsar broker URL (e.g., "pulsar://localhost:6650")
-    /// * `topic` - Pulsar topic to send events to
+    /// * `topic` - Pulsar topic to send events to, optionally templated
+    ///   (e.g., "persistent://public/default/analytics-{project}")
     ///
     /// # Returns
     /// * `Ok(PulsarStreaming)` - Successfully created Pulsar streaming service
     /// * `Err(StreamingError)` - Failed to create Pulsar producer
     ///
+    /// For a static topic the producer is created eagerly so connection problems surface
+    /// at startup; for templated topics a producer is created per topic on first use.
+    ///
     /// # Validates
     /// * Requirement 13.3 - Connection pooling and reuse
     pub async fn new(pulsar_url: &str, topic: &str) -> Result<Self, StreamingError> {
+        let topic = TopicRouter::new(TopicTemplate::parse(topic)?);
+
         // Create Pulsar client
         let pulsar: Pulsar<TokioExecutor> = Pulsar::builder(pulsar_url, TokioExecutor)
             .build()
             .await
             .map_err(|e| StreamingError::ConnectionError(e.to_string()))?;
 
+        let service = PulsarStreaming {
+            client: pulsar,
+            topic,
+            producers: Mutex::new(HashMap::new()),
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+            dead_letter_topic: None,
+            max_redeliveries: 0,
+            output: OutputConfig::default(),
+            format: WireFormat::default(),
+        };
+
+        if service.topic.is_static() {
+            service.producer_for(service.topic.default_topic().as_str()).await?;
+        }
+
+        Ok(service)
+    }
+
+    /// Bound how long a single send (including lazy producer creation) may take
+    pub fn with_send_timeout(mut self, send_timeout: Duration) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
+    /// Encode payloads in the given layout instead of nested JSON
+    pub fn with_output(mut self, output: &OutputConfig) -> Self {
+        self.output = output.clone();
+        self
+    }
+
+    /// Serialize payloads as `format` instead of JSON
+    pub fn with_format(mut self, format: WireFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Capture undeliverable events on a dead-letter topic instead of failing the send
+    ///
+    /// The topic may be templated like the main topic. Its producer is created lazily.
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if the topic is not a valid template
+    pub fn with_dead_letter_topic(mut self, topic: &str) -> Result<Self, StreamingError> {
+        self.dead_letter_topic = Some(TopicTemplate::parse(topic)?);
+        Ok(self)
+    }
+
+    /// Retry a failed send up to `max_redeliveries` more times before giving up
+    pub fn with_max_redeliveries(mut self, max_redeliveries: u32) -> Self {
+        self.max_redeliveries = max_redeliveries;
+        self
+    }
+
+    /// Whether sends wait for the broker receipt so delivery failures can be retried
+    /// or dead-lettered
+    fn confirms_delivery(&self) -> bool {
+        self.dead_letter_topic.is_some() || self.max_redeliveries > 0
+    }
+
+    /// Send a message to a single topic, bounded by the send timeout
+    async fn send_to_topic(&self, topic: &str, message: &producer::Message) -> Result<(), StreamingError> {
+        with_send_timeout(self.send_timeout, async {
+            let producer = self.producer_for(topic).await?;
+            let receipt = {
+                let mut producer = producer.lock().await;
+                producer
+                    .send_non_blocking(message.clone())
+                    .await
+                    .map_err(|e| StreamingError::SendError(e.to_string()))?
+            };
+            if self.confirms_delivery() {
+                receipt
+                    .await
+                    .map_err(|e| StreamingError::SendError(e.to_string()))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Route specific event names (e.g. identify, revenue) to their own topics
+    ///
+    /// Producers for route topics are created lazily on the first matching event.
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a route topic is not a valid template
+    pub fn with_topic_routes(mut self, routes: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.topic = self.topic.with_routes(routes)?;
+        Ok(self)
+    }
+
+    /// Send the events of specific projects to their own topics, created lazily like routes
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if a project topic is not a valid template
+    pub fn with_project_topics(mut self, topics: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        self.topic = self.topic.with_project_routes(topics)?;
+        Ok(self)
+    }
+
+    /// Namespace of the default topic (`tenant/namespace`), "public/default" for short
+    /// topic names or when the tenant or namespace is templated
+    fn namespace(&self) -> String {
+        pulsar_namespace(self.topic.default_topic().as_str())
+    }
+
+    /// Get the producer for a topic, creating it if this is the first event for the topic
+    ///
+    /// The cache is not locked while a producer is built, so a slow or unreachable
+    /// topic does not hold up sends to the others. When two sends race to create the
+    /// same producer, the first one cached is kept.
+    async fn producer_for(
+        &self,
+        topic: &str,
+    ) -> Result<Arc<Mutex<Producer<TokioExecutor>>>, StreamingError> {
+        if let Some(producer) = self.producers.lock().await.get(topic) {
+            return Ok(producer.clone());
+        }
+
+        tracing::info!(
+            service = "pulsar",
+            topic = %topic,
+            "Creating Pulsar producer for topic"
+        );
+
         // Create producer with connection pooling
         // The producer maintains a connection pool internally
         // Validates: Requirement 13.3
-        let producer = pulsar
+        let producer = self
+            .client
             .producer()
             .with_topic(topic)
             .build()
             .await
             .map_err(|e| StreamingError::ConnectionError(e.to_string()))?;
 
-        Ok(PulsarStreaming { 
-            producer: Arc::new(Mutex::new(producer))
-        })
+        let producer = Arc::new(Mutex::new(producer));
+        let mut producers = self.producers.lock().await;
+        Ok(producers.entry(topic.to_string()).or_insert(producer).clone())
+    }
+}
+
+/// Namespace of a topic name or template, "public/default" when it cannot be told
+fn pulsar_namespace(topic: &str) -> String {
+    let name = topic
+        .strip_prefix("persistent://")
+        .or_else(|| topic.strip_prefix("non-persistent://"));
+    let segments: Vec<&str> = name.map(|name| name.split('/').collect()).unwrap_or_default();
+    match segments.as_slice() {
+        [tenant, namespace, _, ..] if !tenant.contains('{') && !namespace.contains('{') => {
+            format!("{}/{}", tenant, namespace)
+        }
+        _ => "public/default".to_string(),
     }
 }
 
 #[async_trait]
 impl StreamingService for PulsarStreaming {
     /// Send an analytics event to Pulsar
-    /// Serializes the event to JSON and sends it to the configured topic
+    /// Serializes the event and sends it to the configured topic
     /// Validates: Requirements 7.4, 7.6
     async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        // Resolve the destination topic (templated topics vary per event)
+        let topic = self.topic.render(event);
+
         tracing::debug!(
             service = "pulsar",
+            topic = %topic,
             event_id = ?event.id,
             "Serializing event for Pulsar"
         );
         
-        // Serialize event to JSON
+        // Serialize event in the configured layout and wire format
         // Validates: Requirement 7.4
-        let payload = serde_json::to_string(event)?;
+        let payload = encode_event(event, &self.output, self.format)?;
+        record_payload_size(payload.len());
+        let message = producer::Message {
+            payload,
+            properties: HashMap::from([
+                (SCHEMA_VERSION_HEADER.to_string(), event.schema_version.to_string()),
+                (CONTENT_TYPE_HEADER.to_string(), self.format.content_type().to_string()),
+            ]),
+            ..Default::default()
+        };
 
         tracing::debug!(
             service = "pulsar",
+            topic = %topic,
             event_id = ?event.id,
-            payload_size = payload.len(),
+            payload_size = message.payload.len(),
             "Sending event to Pulsar"
         );
 
-        // Send to Pulsar using non-blocking send
+        // Send to Pulsar using non-blocking send, bounded by the configured send timeout
         // The producer is reused across requests (connection pooling)
         // Validates: Requirement 13.3
-        let mut producer = self.producer.lock().await;
-        producer
-            .send_non_blocking(payload.as_bytes())
-            .await
-            .map_err(|e| {
-                tracing::error!(
-                    service = "pulsar",
-                    event_id = ?event.id,
-                    error = %e,
-                    "Failed to send event to Pulsar"
-                );
-                StreamingError::SendError(e.to_string())
-            })?;
+        let mut attempt = 0;
+        let result = loop {
+            match self.send_to_topic(&topic, &message).await {
+                Ok(()) => break Ok(()),
+                Err(e) if attempt < self.max_redeliveries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        service = "pulsar",
+                        topic = %topic,
+                        event_id = ?event.id,
+                        attempt = attempt,
+                        error = %e,
+                        "Retrying Pulsar send"
+                    );
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::error!(
+                service = "pulsar",
+                topic = %topic,
+                event_id = ?event.id,
+                error = %e,
+                "Failed to send event to Pulsar"
+            );
+
+            let Some(dead_letter_topic) = &self.dead_letter_topic else {
+                return Err(e);
+            };
+
+            // Capture the undeliverable event rather than failing the HTTP request
+            let dead_letter_topic = dead_letter_topic.render(event);
+            self.send_to_topic(&dead_letter_topic, &message)
+                .await
+                .map_err(|dlq_error| {
+                    StreamingError::SendError(format!(
+                        "{}; dead-letter topic '{}' also failed: {}",
+                        e, dead_letter_topic, dlq_error
+                    ))
+                })?;
+
+            tracing::warn!(
+                service = "pulsar",
+                topic = %topic,
+                dead_letter_topic = %dead_letter_topic,
+                event_id = ?event.id,
+                "Event sent to Pulsar dead-letter topic"
+            );
+            return Ok(());
+        }
 
         tracing::info!(
             service = "pulsar",
+            topic = %topic,
             event_id = ?event.id,
             "Event sent to Pulsar successfully"
         );
@@ -387,33 +1284,229 @@ impl StreamingService for PulsarStreaming {
     /// Check Pulsar connection health
     /// Validates: Requirement 7.1
     async fn health_check(&self) -> Result<(), StreamingError> {
-        // For Pulsar, we verify the producer is still valid
-        // A more thorough check would query broker metadata
-        // but for now we just verify the producer exists
+        // Look up every statically known topic plus every topic we already produce to
+        // A lookup round-trips to the broker, so it also verifies the connection
+        let mut topics: Vec<String> = self
+            .topic
+            .static_topics()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        for topic in self.producers.lock().await.keys() {
+            if !topics.contains(topic) {
+                topics.push(topic.clone());
+            }
+        }
+
+        // With only templated topics and nothing sent yet there is no topic to look up,
+        // so list the topics of the namespace instead, which also round-trips to the broker
+        if topics.is_empty() {
+            let namespace = self.namespace();
+            self.client
+                .get_topics_of_namespace(namespace.clone(), pulsar::proto::command_get_topics_of_namespace::Mode::All)
+                .await
+                .map_err(|e| StreamingError::HealthCheckError(format!(
+                    "Pulsar namespace lookup failed for '{}': {}",
+                    namespace, e
+                )))?;
+            return Ok(());
+        }
+
+        for topic in topics {
+            self.client
+                .lookup_topic(topic.as_str())
+                .await
+                .map_err(|e| StreamingError::HealthCheckError(format!(
+                    "Pulsar topic lookup failed for '{}': {}",
+                    topic, e
+                )))?;
+        }
+
         Ok(())
     }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        Some(self.topic.render(event))
+    }
 }
 
 
 /// Create a streaming service based on configuration
 /// Returns Arc<dyn StreamingService> for the configured service type
+///
+/// When `residency` rules are configured, events are routed by visitor country to
+/// the matching rule's sink, with the configured sink as the default. All broker sinks
+/// share the configured `output` layout and `project_topics`; the fallback file always stores nested events.
+/// When a fallback is configured, the primary sink is wrapped in a `FallbackStreaming`
+/// and a background task replaying stored events into the primary is started.
+/// When `metadata` is configured, events are annotated with collector metadata.
+/// When `retention` is configured, events are stamped with their `expires_at`.
+/// With `ack_mode: queued` the result is wrapped in a `QueuedStreaming`, so sends
+/// return once the event is in the local queue.
 /// Validates: Requirement 7.5
 pub async fn create_streaming_service(
     config: &crate::config::StreamingConfig,
+) -> Result<std::sync::Arc<dyn StreamingService>, StreamingError> {
+    build_streaming_service(config, None).await
+}
+
+/// Create a streaming service like `create_streaming_service`, recording the sends of
+/// every broker sink into `metrics` and their serialization times into `stages`
+///
+/// Each sink is instrumented where it talks to the broker, so latency covers delivery
+/// rather than the local queue, sends made by the queue workers are recorded, and a
+/// primary failure is counted even when the fallback then stores the event.
+pub async fn create_instrumented_streaming_service(
+    config: &crate::config::StreamingConfig,
+    metrics: Arc<SinkMetrics>,
+    stages: Arc<StageMetrics>,
+) -> Result<std::sync::Arc<dyn StreamingService>, StreamingError> {
+    build_streaming_service(config, Some((&metrics, &stages))).await
+}
+
+/// Build the configured streaming service, instrumenting each broker sink if
+/// `instruments` are given
+async fn build_streaming_service(
+    config: &crate::config::StreamingConfig,
+    instruments: Option<(&Arc<SinkMetrics>, &Arc<StageMetrics>)>,
+) -> Result<std::sync::Arc<dyn StreamingService>, StreamingError> {
+    let mut primary = create_sink(config, instruments).await?;
+
+    if !config.residency.is_empty() {
+        let mut router = ResidencyStreaming::new(primary);
+        for rule in &config.residency {
+            let countries = rule.country_codes();
+            tracing::info!(
+                route = %rule.name,
+                service_type = rule.service_type.as_str(),
+                country_count = countries.len(),
+                "Enabling data-residency route"
+            );
+            let sink_config = crate::config::StreamingConfig {
+                output: config.output.clone(),
+                project_topics: config.project_topics.clone(),
+                ..rule.sink_config()
+            };
+            let sink = create_sink(&sink_config, instruments).await?;
+            router = router.with_route(&rule.name, &countries, sink);
+        }
+        primary = Arc::new(router);
+    }
+
+    let service: Arc<dyn StreamingService> = match &config.fallback {
+        Some(fallback_config) => {
+            tracing::info!(
+                path = %fallback_config.path,
+                failure_threshold = fallback_config.failure_threshold,
+                open_secs = fallback_config.open_secs,
+                "Enabling fallback sink for streaming service"
+            );
+
+            let service = Arc::new(FallbackStreaming::new(
+                primary,
+                FileStreaming::new(&fallback_config.path),
+                CircuitBreaker::new(
+                    fallback_config.failure_threshold,
+                    Duration::from_secs(fallback_config.open_secs),
+                ),
+            ));
+            service.spawn_replay(Duration::from_secs(fallback_config.replay_interval_secs));
+            service
+        }
+        None => primary,
+    };
+
+    let service: Arc<dyn StreamingService> = match &config.metadata {
+        Some(metadata_config) => Arc::new(MetadataStreaming::new(service, metadata_config)),
+        None => service,
+    };
+
+    let service: Arc<dyn StreamingService> = match &config.retention {
+        Some(retention_config) => Arc::new(RetentionStreaming::new(service, retention_config)),
+        None => service,
+    };
+
+    match config.ack_mode {
+        crate::config::AckMode::Broker => Ok(service),
+        crate::config::AckMode::Queued => {
+            tracing::info!(
+                capacity = config.queue.capacity,
+                workers = config.queue.workers,
+                "Acknowledging events once queued locally"
+            );
+            Ok(Arc::new(QueuedStreaming::new(
+                service,
+                config.queue.capacity,
+                config.queue.workers,
+            )))
+        }
+    }
+}
+
+/// Create the configured broker sink, capped to its in-flight limit if one is set and
+/// recording its sends into `instruments` if given
+async fn create_sink(
+    config: &crate::config::StreamingConfig,
+    instruments: Option<(&Arc<SinkMetrics>, &Arc<StageMetrics>)>,
+) -> Result<Arc<dyn StreamingService>, StreamingError> {
+    let sink = create_primary_streaming_service(config).await?;
+
+    // Cap in-flight sends to the broker; waiting for a slot counts against the send timeout
+    let sink: Arc<dyn StreamingService> = match sink_concurrency_limit(config) {
+        Some((max_in_flight, acquire_timeout)) => {
+            tracing::info!(max_in_flight = max_in_flight, "Limiting concurrent streaming sends");
+            Arc::new(ConcurrencyLimitedStreaming::new(sink, max_in_flight, acquire_timeout))
+        }
+        None => sink,
+    };
+
+    // Outside the limit, so a send timing out while waiting for a slot is recorded too
+    match instruments {
+        Some((metrics, stages)) => Ok(Arc::new(
+            InstrumentedStreaming::new(sink, metrics.clone()).with_stage_metrics(stages.clone()),
+        )),
+        None => Ok(sink),
+    }
+}
+
+/// Concurrency limit and send timeout of the configured sink, if a limit is set
+fn sink_concurrency_limit(config: &crate::config::StreamingConfig) -> Option<(usize, Duration)> {
+    use crate::config::StreamingServiceType;
+
+    let (max_in_flight, send_timeout_ms) = match config.service_type {
+        StreamingServiceType::Kafka => config.kafka.as_ref().map(|c| (c.max_in_flight, c.send_timeout_ms))?,
+        StreamingServiceType::Kinesis => config.kinesis.as_ref().map(|c| (c.max_in_flight, c.send_timeout_ms))?,
+        StreamingServiceType::Pulsar => config.pulsar.as_ref().map(|c| (c.max_in_flight, c.send_timeout_ms))?,
+        StreamingServiceType::Stdout => return None,
+    };
+    max_in_flight.map(|max| (max, send_timeout_from_config(send_timeout_ms)))
+}
+
+/// Create the configured primary (broker) streaming service
+async fn create_primary_streaming_service(
+    config: &crate::config::StreamingConfig,
 ) -> Result<std::sync::Arc<dyn StreamingService>, StreamingError> {
     use crate::config::StreamingServiceType;
 
     match config.service_type {
+        StreamingServiceType::Stdout => {
+            tracing::warn!("Streaming events to stdout; configure a broker for production");
+            Ok(std::sync::Arc::new(StdoutStreaming::new().with_output(&config.output)))
+        }
         StreamingServiceType::Kafka => {
             let kafka_config = config.kafka.as_ref()
                 .ok_or_else(|| StreamingError::ConfigError(
                     "Kafka configuration is missing".to_string()
                 ))?;
 
-            let service = KafkaStreaming::new(
-                &kafka_config.brokers,
-                kafka_config.topic.clone(),
-            )?;
+            if let Some(settings) = &kafka_config.create_topics {
+                create_kafka_topics(kafka_config, settings).await?;
+            }
+
+            let service = KafkaStreaming::connect(kafka_config)
+                .await?
+                .with_project_topics(&config.project_topics)?
+                .with_output(&config.output);
 
             Ok(std::sync::Arc::new(service))
         }
@@ -430,7 +1523,12 @@ pub async fn create_streaming_service(
                 .await;
 
             let client = KinesisClient::new(&aws_config);
-            let service = KinesisStreaming::new(client, kinesis_config.stream_name.clone());
+            let service = KinesisStreaming::new(client, kinesis_config.stream_name.clone())
+                .with_stream_routes(&kinesis_config.event_streams)?
+                .with_project_streams(&config.project_topics)?
+                .with_send_timeout(send_timeout_from_config(kinesis_config.send_timeout_ms))
+                .with_output(&config.output)
+                .with_format(kinesis_config.format);
 
             Ok(std::sync::Arc::new(service))
         }
@@ -443,7 +1541,18 @@ pub async fn create_streaming_service(
             let service = PulsarStreaming::new(
                 &pulsar_config.url,
                 &pulsar_config.topic,
-            ).await?;
+            ).await?
+            .with_topic_routes(&pulsar_config.event_topics)?
+            .with_project_topics(&config.project_topics)?
+            .with_send_timeout(send_timeout_from_config(pulsar_config.send_timeout_ms))
+            .with_max_redeliveries(pulsar_config.max_redeliveries)
+            .with_output(&config.output)
+            .with_format(pulsar_config.format);
+
+            let service = match &pulsar_config.dead_letter_topic {
+                Some(dead_letter_topic) => service.with_dead_letter_topic(dead_letter_topic)?,
+                None => service,
+            };
 
             Ok(std::sync::Arc::new(service))
         }
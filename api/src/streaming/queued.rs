@@ -0,0 +1,302 @@
+// Queued (fire-and-forget) streaming sink
+// This module acknowledges events once they are in a local bounded queue and delivers them in the background
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, Mutex};
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::transformer::AnalyticsEvent;
+
+/// Streaming service that returns as soon as an event is accepted into a local queue
+///
+/// A fixed pool of background workers drains the queue into the inner service.
+/// Delivery failures are logged rather than returned, because the HTTP client has
+/// already been answered. When the queue is full, sends fail immediately so callers
+/// see backpressure instead of unbounded memory growth. A batch is queued, and
+/// handed to the inner service, as a whole.
+///
+/// `close` stops accepting events and waits for the workers to deliver the queue,
+/// so the events acknowledged before a shutdown are not lost.
+pub struct QueuedStreaming {
+    inner: Arc<dyn StreamingService>,
+    /// Taken by `close`; the workers stop once the queue is drained
+    sender: std::sync::Mutex<Option<mpsc::Sender<Vec<AnalyticsEvent>>>>,
+    capacity: usize,
+    /// Events waiting in the local queue
+    queued: Arc<AtomicUsize>,
+    workers: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+}
+
+impl QueuedStreaming {
+    /// Create the queue and spawn its delivery workers
+    ///
+    /// # Arguments
+    /// * `inner` - Service events are delivered to
+    /// * `capacity` - Maximum number of queued events (minimum 1)
+    /// * `workers` - Number of concurrent delivery tasks (minimum 1)
+    pub fn new(inner: Arc<dyn StreamingService>, capacity: usize, workers: usize) -> Self {
+        let capacity = capacity.max(1);
+        // Every queued send holds at least one event, so the channel never fills first
+        let (sender, receiver) = mpsc::channel::<Vec<AnalyticsEvent>>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let queued = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..workers.max(1)).map(|_| {
+            let receiver = receiver.clone();
+            let inner = inner.clone();
+            let queued = queued.clone();
+            tokio::spawn(async move {
+                loop {
+                    // Hold the lock only while waiting for the next send
+                    let next = receiver.lock().await.recv().await;
+                    let Some(events) = next else { break };
+                    queued.fetch_sub(events.len(), Ordering::Relaxed);
+                    let result = match events.as_slice() {
+                        [event] => inner.send_event(event).await,
+                        events => inner.send_batch(events).await,
+                    };
+                    if let Err(e) = result {
+                        tracing::error!(
+                            event_id = ?events[0].id,
+                            event_count = events.len(),
+                            error = %e,
+                            "Queued event could not be delivered"
+                        );
+                    }
+                }
+            })
+        }).collect();
+
+        QueuedStreaming {
+            inner,
+            sender: std::sync::Mutex::new(Some(sender)),
+            capacity,
+            queued,
+            workers: Mutex::new(workers),
+        }
+    }
+
+    /// Number of events waiting in the local queue
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Accept `events` into the local queue if they all fit
+    fn enqueue(&self, events: Vec<AnalyticsEvent>) -> Result<(), StreamingError> {
+        let count = events.len();
+        if count == 0 {
+            return Ok(());
+        }
+        let reserved = self.queued.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+            (queued + count <= self.capacity).then_some(queued + count)
+        });
+        if reserved.is_err() {
+            tracing::warn!(
+                event_id = ?events[0].id,
+                event_count = count,
+                capacity = self.capacity,
+                "Local event queue is full"
+            );
+            return Err(StreamingError::SendError(format!("local event queue is full ({} events)", self.capacity)));
+        }
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let sent = match sender.as_ref() {
+            Some(sender) => sender.try_send(events).is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.queued.fetch_sub(count, Ordering::Relaxed);
+            return Err(StreamingError::SendError("local event queue is closed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StreamingService for QueuedStreaming {
+    /// Accept the event into the local queue without waiting for the broker
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        self.enqueue(vec![event.clone()])
+    }
+
+    /// Accept the whole batch into the local queue, or none of it when it does not fit
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        self.enqueue(events.to_vec())
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.inner.health_check().await
+    }
+
+    /// Events in the local queue plus whatever the inner client is buffering
+    fn queue_depth(&self) -> Option<i64> {
+        Some(self.queued() as i64 + self.inner.queue_depth().unwrap_or(0))
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.inner.topic_for(event)
+    }
+
+    /// Stop accepting events, wait for the workers to deliver the queued ones, then
+    /// close the inner service with the time left
+    ///
+    /// # Errors
+    /// Returns `StreamingError::TimeoutError` if events were still queued at `timeout`
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        drop(self.sender.lock().unwrap_or_else(|e| e.into_inner()).take());
+        tracing::info!(queued = self.queued(), "Delivering the queued events before shutdown");
+
+        let workers = std::mem::take(&mut *self.workers.lock().await);
+        if tokio::time::timeout_at(deadline, futures::future::join_all(workers)).await.is_err() {
+            let lost = self.queued();
+            tracing::error!(
+                queued = lost,
+                timeout_ms = timeout.as_millis() as u64,
+                "Queued events were not delivered before the drain timeout"
+            );
+            return Err(StreamingError::TimeoutError(format!(
+                "{} queued events not delivered within {:?}",
+                lost, timeout
+            )));
+        }
+
+        self.inner
+            .close(deadline.saturating_duration_since(tokio::time::Instant::now()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    struct GatedService {
+        gate: Notify,
+        sent: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl StreamingService for GatedService {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.gate.notified().await;
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acknowledges_before_delivery() {
+        let inner = Arc::new(GatedService { gate: Notify::new(), sent: AtomicUsize::new(0) });
+        let queued = QueuedStreaming::new(inner.clone(), 10, 1);
+
+        queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+        assert_eq!(inner.sent.load(Ordering::SeqCst), 0);
+
+        inner.gate.notify_one();
+        for _ in 0..100 {
+            if inner.sent.load(Ordering::SeqCst) == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(inner.sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects_sends() {
+        let inner = Arc::new(GatedService { gate: Notify::new(), sent: AtomicUsize::new(0) });
+        let queued = QueuedStreaming::new(inner, 1, 1);
+
+        // The worker takes the first event and blocks on the gate; the second fills the queue
+        queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+
+        assert!(queued.send_event(&AnalyticsEvent::default()).await.is_err());
+        assert_eq!(queued.queued(), 1);
+    }
+
+    struct BatchRecorder {
+        batches: std::sync::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl StreamingService for BatchRecorder {
+        async fn send_event(&self, _event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.batches.lock().unwrap().push(1);
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+
+        async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+            self.batches.lock().unwrap().push(events.len());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_delivered_whole() {
+        let inner = Arc::new(BatchRecorder { batches: std::sync::Mutex::new(Vec::new()) });
+        let queued = QueuedStreaming::new(inner.clone(), 3, 1);
+
+        // A batch larger than the free room is rejected as a whole
+        let events: Vec<AnalyticsEvent> = (0..4).map(|_| AnalyticsEvent::default()).collect();
+        assert!(queued.send_batch(&events).await.is_err());
+        queued.send_batch(&events[..3]).await.unwrap();
+
+        for _ in 0..100 {
+            if !inner.batches.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(*inner.batches.lock().unwrap(), vec![3]);
+        assert_eq!(queued.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_delivers_queued_events() {
+        let inner = Arc::new(BatchRecorder { batches: std::sync::Mutex::new(Vec::new()) });
+        let queued = QueuedStreaming::new(inner.clone(), 10, 2);
+
+        for _ in 0..5 {
+            queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+        }
+        queued.close(Duration::from_secs(5)).await.unwrap();
+
+        assert_eq!(inner.batches.lock().unwrap().len(), 5);
+        assert_eq!(queued.queued(), 0);
+        assert!(queued.send_event(&AnalyticsEvent::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_close_gives_up_after_timeout() {
+        let inner = Arc::new(GatedService { gate: Notify::new(), sent: AtomicUsize::new(0) });
+        let queued = QueuedStreaming::new(inner.clone(), 10, 1);
+
+        queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+        queued.send_event(&AnalyticsEvent::default()).await.unwrap();
+
+        let result = queued.close(Duration::from_millis(50)).await;
+        assert!(matches!(result, Err(StreamingError::TimeoutError(_))));
+        assert_eq!(inner.sent.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,223 @@
+// Data-residency routing
+// This module sends events to a region-specific sink based on the visitor's GeoIP country
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::transformer::AnalyticsEvent;
+
+/// A named sink that receives events from a set of countries
+pub struct ResidencyRoute {
+    pub name: String,
+    pub service: Arc<dyn StreamingService>,
+}
+
+/// Streaming service that routes events by `country_code`
+///
+/// Events from a country covered by a route go to that route's sink; everything else,
+/// including events without a resolved country, goes to the default sink.
+pub struct ResidencyStreaming {
+    default: Arc<dyn StreamingService>,
+    routes: Vec<ResidencyRoute>,
+    /// Upper-cased country code → index into `routes`
+    countries: HashMap<String, usize>,
+}
+
+impl ResidencyStreaming {
+    /// Create a router with no routes; every event goes to `default`
+    pub fn new(default: Arc<dyn StreamingService>) -> Self {
+        ResidencyStreaming {
+            default,
+            routes: Vec::new(),
+            countries: HashMap::new(),
+        }
+    }
+
+    /// Add a route for `countries`
+    ///
+    /// Countries already claimed by an earlier route keep their earlier route.
+    pub fn with_route(mut self, name: &str, countries: &[String], service: Arc<dyn StreamingService>) -> Self {
+        let index = self.routes.len();
+        for country in countries {
+            self.countries
+                .entry(country.to_ascii_uppercase())
+                .or_insert(index);
+        }
+        self.routes.push(ResidencyRoute {
+            name: name.to_string(),
+            service,
+        });
+        self
+    }
+
+    /// Route an event would be sent to, or `None` for the default sink
+    pub fn route_for(&self, event: &AnalyticsEvent) -> Option<&ResidencyRoute> {
+        self.route_index(event).map(|index| &self.routes[index])
+    }
+
+    fn route_index(&self, event: &AnalyticsEvent) -> Option<usize> {
+        let code = event.country_code.as_deref()?;
+        self.countries.get(&code.to_ascii_uppercase()).copied()
+    }
+
+    fn sink_for(&self, event: &AnalyticsEvent) -> &Arc<dyn StreamingService> {
+        match self.route_for(event) {
+            Some(route) => {
+                tracing::debug!(
+                    event_id = ?event.id,
+                    country_code = ?event.country_code,
+                    route = %route.name,
+                    "Routing event to residency sink"
+                );
+                &route.service
+            }
+            None => &self.default,
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingService for ResidencyStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        self.sink_for(event).send_event(event).await
+    }
+
+    /// Each sink gets the events routed to it as one batch, in their original order
+    ///
+    /// Batches to different sinks are independent: a failure stops the remaining
+    /// sinks, but batches already sent stay delivered.
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let mut batches: Vec<(Option<usize>, Vec<AnalyticsEvent>)> = Vec::new();
+        for event in events {
+            let route = self.route_index(event);
+            match batches.iter_mut().find(|(batch_route, _)| *batch_route == route) {
+                Some((_, batch)) => batch.push(event.clone()),
+                None => batches.push((route, vec![event.clone()])),
+            }
+        }
+        for (route, batch) in batches {
+            let sink = match route {
+                Some(index) => &self.routes[index].service,
+                None => &self.default,
+            };
+            sink.send_batch(&batch).await?;
+        }
+        Ok(())
+    }
+
+    /// All sinks must be healthy
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.default.health_check().await?;
+        for route in &self.routes {
+            route.service.health_check().await.map_err(|e| {
+                StreamingError::HealthCheckError(format!("residency sink '{}': {}", route.name, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Buffered events across all sinks
+    fn queue_depth(&self) -> Option<i64> {
+        std::iter::once(&self.default)
+            .chain(self.routes.iter().map(|route| &route.service))
+            .filter_map(|service| service.queue_depth())
+            .reduce(|a, b| a + b)
+    }
+
+    /// Breaker of the default sink, which `streaming.fallback` configures
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.default.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        match self.route_for(event) {
+            Some(route) => route.service.topic_for(event),
+            None => self.default.topic_for(event),
+        }
+    }
+
+    /// Closes every sink, each with the full timeout
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        let results = futures::future::join_all(
+            std::iter::once(&self.default)
+                .chain(self.routes.iter().map(|route| &route.service))
+                .map(|service| service.close(timeout)),
+        )
+        .await;
+        results.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Capture {
+        events: Mutex<Vec<AnalyticsEvent>>,
+    }
+
+    #[async_trait]
+    impl StreamingService for Capture {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    fn event_from(country_code: Option<&str>) -> AnalyticsEvent {
+        AnalyticsEvent {
+            country_code: country_code.map(|c| c.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_country_code() {
+        let default = Arc::new(Capture::default());
+        let eu = Arc::new(Capture::default());
+        let router = ResidencyStreaming::new(default.clone())
+            .with_route("eu", &["DE".to_string(), "FR".to_string()], eu.clone());
+
+        router.send_event(&event_from(Some("de"))).await.unwrap();
+        router.send_event(&event_from(Some("US"))).await.unwrap();
+        router.send_event(&event_from(None)).await.unwrap();
+
+        assert_eq!(eu.events.lock().unwrap().len(), 1);
+        assert_eq!(default.events.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_splits_batches_by_route() {
+        let default = Arc::new(Capture::default());
+        let eu = Arc::new(Capture::default());
+        let router = ResidencyStreaming::new(default.clone())
+            .with_route("eu", &["DE".to_string()], eu.clone());
+
+        router
+            .send_batch(&[event_from(Some("DE")), event_from(Some("US")), event_from(Some("DE"))])
+            .await
+            .unwrap();
+
+        assert_eq!(eu.events.lock().unwrap().len(), 2);
+        assert_eq!(default.events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_first_route_wins() {
+        let router = ResidencyStreaming::new(Arc::new(Capture::default()))
+            .with_route("first", &["DE".to_string()], Arc::new(Capture::default()))
+            .with_route("second", &["DE".to_string()], Arc::new(Capture::default()));
+
+        assert_eq!(router.route_for(&event_from(Some("DE"))).unwrap().name, "first");
+    }
+}
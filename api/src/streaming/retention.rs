@@ -0,0 +1,143 @@
+// Retention TTL stamping
+// This module stamps outgoing events with the time storage may expire them, per project
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::{CircuitBreaker, StreamingError, StreamingService};
+use crate::config::RetentionConfig;
+use crate::transformer::AnalyticsEvent;
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Streaming service decorator that sets `expires_at` from the project's retention TTL
+pub struct RetentionStreaming {
+    inner: Arc<dyn StreamingService>,
+    config: RetentionConfig,
+}
+
+impl RetentionStreaming {
+    /// Wrap `inner`, stamping events according to `config`
+    pub fn new(inner: Arc<dyn StreamingService>, config: &RetentionConfig) -> Self {
+        RetentionStreaming {
+            inner,
+            config: config.clone(),
+        }
+    }
+
+    /// Copy of `event` with `expires_at` set to its timestamp plus the project's TTL
+    ///
+    /// Events of projects without a TTL are returned unchanged.
+    fn stamp(&self, event: &AnalyticsEvent) -> AnalyticsEvent {
+        let mut event = event.clone();
+        if let Some(days) = self.config.ttl_days(event.project.as_deref()) {
+            event.expires_at = Some(event.timestamp.saturating_add(i64::from(days) * MILLIS_PER_DAY));
+        }
+        event
+    }
+}
+
+#[async_trait]
+impl StreamingService for RetentionStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        self.inner.send_event(&self.stamp(event)).await
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        self.inner.health_check().await
+    }
+
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let events: Vec<AnalyticsEvent> = events.iter().map(|e| self.stamp(e)).collect();
+        self.inner.send_batch(&events).await
+    }
+
+    fn queue_depth(&self) -> Option<i64> {
+        self.inner.queue_depth()
+    }
+
+    fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.inner.circuit_breaker()
+    }
+
+    fn topic_for(&self, event: &AnalyticsEvent) -> Option<String> {
+        self.inner.topic_for(event)
+    }
+    async fn close(&self, timeout: Duration) -> Result<(), StreamingError> {
+        self.inner.close(timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    struct Capture {
+        events: Mutex<Vec<AnalyticsEvent>>,
+    }
+
+    #[async_trait]
+    impl StreamingService for Capture {
+        async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), StreamingError> {
+            Ok(())
+        }
+    }
+
+    fn event(project: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            project: Some(project.to_string()),
+            timestamp: 1_700_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stamps_project_ttl() {
+        let capture = Arc::new(Capture { events: Mutex::new(Vec::new()) });
+        let service = RetentionStreaming::new(
+            capture.clone(),
+            &RetentionConfig {
+                default_days: Some(30),
+                projects: HashMap::from([("short.example".to_string(), 1)]),
+            },
+        );
+
+        service
+            .send_batch(&[event("short.example"), event("other.example")])
+            .await
+            .unwrap();
+
+        let events = capture.events.lock().unwrap();
+        assert_eq!(events[0].expires_at, Some(1_700_000_000_000 + MILLIS_PER_DAY));
+        assert_eq!(events[1].expires_at, Some(1_700_000_000_000 + 30 * MILLIS_PER_DAY));
+    }
+
+    #[tokio::test]
+    async fn test_projects_without_ttl_are_not_stamped() {
+        let capture = Arc::new(Capture { events: Mutex::new(Vec::new()) });
+        let service = RetentionStreaming::new(
+            capture.clone(),
+            &RetentionConfig {
+                default_days: None,
+                projects: HashMap::from([("short.example".to_string(), 1)]),
+            },
+        );
+
+        service.send_event(&event("other.example")).await.unwrap();
+        service.send_event(&AnalyticsEvent::default()).await.unwrap();
+
+        let events = capture.events.lock().unwrap();
+        assert!(events.iter().all(|e| e.expires_at.is_none()));
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert!(json.get("expires_at").is_none());
+    }
+}
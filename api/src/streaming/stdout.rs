@@ -0,0 +1,110 @@
+// Standard output streaming sink
+// This module prints events as JSON lines, for local development without a broker
+
+use async_trait::async_trait;
+use tokio::io::{AsyncWriteExt, Stdout};
+use tokio::sync::Mutex;
+
+use super::{encode_event, record_payload_size, StreamingError, StreamingService};
+use crate::config::{OutputConfig, WireFormat};
+use crate::transformer::AnalyticsEvent;
+
+/// Streaming service that writes every event as one JSON line to standard output
+///
+/// The default sink when `streaming.service_type` is not set. Events use the
+/// configured `streaming.output` layout.
+pub struct StdoutStreaming {
+    output: OutputConfig,
+    /// Serializes writes so lines are never interleaved
+    stdout: Mutex<Stdout>,
+}
+
+impl StdoutStreaming {
+    pub fn new() -> Self {
+        StdoutStreaming {
+            output: OutputConfig::default(),
+            stdout: Mutex::new(tokio::io::stdout()),
+        }
+    }
+
+    /// Set the payload layout (nested or flat JSON)
+    pub fn with_output(mut self, output: &OutputConfig) -> Self {
+        self.output = output.clone();
+        self
+    }
+
+    /// `events` encoded as newline-terminated JSON lines
+    fn encode_lines(&self, events: &[AnalyticsEvent]) -> Result<Vec<u8>, StreamingError> {
+        let mut buffer = Vec::new();
+        for event in events {
+            let line = encode_event(event, &self.output, WireFormat::Json)?;
+            record_payload_size(line.len());
+            buffer.extend(line);
+            buffer.push(b'\n');
+        }
+        Ok(buffer)
+    }
+
+    async fn write(&self, buffer: &[u8]) -> Result<(), StreamingError> {
+        let mut stdout = self.stdout.lock().await;
+        stdout
+            .write_all(buffer)
+            .await
+            .map_err(|e| StreamingError::SendError(format!("stdout: {}", e)))?;
+        stdout
+            .flush()
+            .await
+            .map_err(|e| StreamingError::SendError(format!("stdout: {}", e)))
+    }
+}
+
+impl Default for StdoutStreaming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StreamingService for StdoutStreaming {
+    async fn send_event(&self, event: &AnalyticsEvent) -> Result<(), StreamingError> {
+        let buffer = self.encode_lines(std::slice::from_ref(event))?;
+        self.write(&buffer).await
+    }
+
+    async fn health_check(&self) -> Result<(), StreamingError> {
+        Ok(())
+    }
+
+    async fn send_batch(&self, events: &[AnalyticsEvent]) -> Result<(), StreamingError> {
+        let buffer = self.encode_lines(events)?;
+        self.write(&buffer).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_lines() {
+        let events = vec![
+            AnalyticsEvent {
+                event: "pageview".to_string(),
+                ..Default::default()
+            },
+            AnalyticsEvent {
+                event: "click".to_string(),
+                ..Default::default()
+            },
+        ];
+        let buffer = StdoutStreaming::new().encode_lines(&events).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(text.ends_with('\n'));
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["event"], "pageview");
+        let second: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(second["event"], "click");
+    }
+}
@@ -26,6 +26,7 @@ fn create_test_event() -> AnalyticsEvent {
             language: Some("en-US".to_string()),
             referer: Some("https://google.com".to_string()),
             app: Some("web".to_string()),
+            ..Default::default()
         },
         event_param: None,
         profile: None,
@@ -39,6 +40,7 @@ fn create_test_event() -> AnalyticsEvent {
         city: Some("San Francisco".to_string()),
         latitude: Some(37.7749),
         longitude: Some(-122.4194),
+        ..Default::default()
     }
 }
 
@@ -87,6 +89,9 @@ fn test_streaming_error_display() {
     
     let err = StreamingError::ConfigError("Invalid config".to_string());
     assert_eq!(err.to_string(), "Configuration error: Invalid config");
+    
+    let err = StreamingError::TimeoutError("send did not complete within 5000ms".to_string());
+    assert_eq!(err.to_string(), "Timeout error: send did not complete within 5000ms");
 }
 
 #[test]
@@ -108,15 +113,18 @@ fn test_event_serialization() {
 
 #[tokio::test]
 async fn test_kafka_health_check() {
-    // Test Kafka health check
-    let brokers = vec!["localhost:9092".to_string()];
+    // Test Kafka health check against a port with no broker listening
+    let brokers = vec!["127.0.0.1:1".to_string()];
     let topic = "analytics-events".to_string();
     
     let kafka = KafkaStreaming::new(&brokers, topic).expect("Failed to create Kafka service");
     let result = kafka.health_check().await;
     
-    // Health check should succeed (basic implementation always returns Ok)
-    assert!(result.is_ok());
+    // Fetching the cluster metadata fails before any topic is checked
+    match result {
+        Err(StreamingError::HealthCheckError(msg)) => assert!(!msg.contains("analytics-events"), "{}", msg),
+        other => panic!("expected a metadata fetch error, got {:?}", other),
+    }
 }
 
 // Note: Integration tests that actually send to Kafka should be in tests/integration_tests.rs
@@ -139,7 +147,7 @@ async fn test_kinesis_streaming_creation() {
     let kinesis = KinesisStreaming::new(client, stream_name);
     
     // Should successfully create the streaming service
-    assert_eq!(kinesis.stream_name, "analytics-events");
+    assert_eq!(kinesis.streams.default_topic().as_str(), "analytics-events");
 }
 
 #[tokio::test]
@@ -189,6 +197,15 @@ async fn test_pulsar_streaming_creation() {
     }
 }
 
+#[test]
+fn test_pulsar_namespace() {
+    // Namespace listed by the health check when no topic can be looked up yet
+    assert_eq!(pulsar_namespace("persistent://acme/analytics/events-{project}"), "acme/analytics");
+    assert_eq!(pulsar_namespace("non-persistent://acme/analytics/events"), "acme/analytics");
+    assert_eq!(pulsar_namespace("persistent://{project}/analytics/events"), "public/default");
+    assert_eq!(pulsar_namespace("analytics-{project}"), "public/default");
+}
+
 #[tokio::test]
 async fn test_pulsar_event_serialization() {
     // Test that events can be serialized for Pulsar
@@ -219,9 +236,11 @@ async fn test_create_kafka_streaming_service() {
         kafka: Some(KafkaConfig {
             brokers: vec!["localhost:9092".to_string()],
             topic: "analytics-events".to_string(),
+            ..Default::default()
         }),
         kinesis: None,
         pulsar: None,
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -241,8 +260,10 @@ async fn test_create_kinesis_streaming_service() {
         kinesis: Some(KinesisConfig {
             region: "us-east-1".to_string(),
             stream_name: "analytics-events".to_string(),
+            ..Default::default()
         }),
         pulsar: None,
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -263,7 +284,9 @@ async fn test_create_pulsar_streaming_service() {
         pulsar: Some(PulsarConfig {
             url: "pulsar://localhost:6650".to_string(),
             topic: "persistent://public/default/analytics-events".to_string(),
+            ..Default::default()
         }),
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -292,6 +315,7 @@ async fn test_create_streaming_service_missing_kafka_config() {
         kafka: None,
         kinesis: None,
         pulsar: None,
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -316,6 +340,7 @@ async fn test_create_streaming_service_missing_kinesis_config() {
         kafka: None,
         kinesis: None,
         pulsar: None,
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -340,6 +365,7 @@ async fn test_create_streaming_service_missing_pulsar_config() {
         kafka: None,
         kinesis: None,
         pulsar: None,
+        ..Default::default()
     };
     
     let result = create_streaming_service(&config).await;
@@ -364,14 +390,222 @@ async fn test_factory_returns_arc_dyn_trait() {
         kafka: Some(KafkaConfig {
             brokers: vec!["localhost:9092".to_string()],
             topic: "analytics-events".to_string(),
+            ..Default::default()
         }),
         kinesis: None,
         pulsar: None,
+        ..Default::default()
     };
     
     let service = create_streaming_service(&config).await.expect("Failed to create service");
     
     // Should be able to call trait methods
+    // (the health check only succeeds when a broker is running)
     let health_result = service.health_check().await;
-    assert!(health_result.is_ok());
+    if let Err(e) = health_result {
+        assert!(matches!(e, StreamingError::HealthCheckError(_)));
+    }
+}
+
+#[tokio::test]
+async fn test_create_kafka_streaming_service_with_event_topics() {
+    // Test that per-event topic overrides are accepted by the factory
+    use crate::config::{StreamingConfig, StreamingServiceType, KafkaConfig};
+
+    let mut event_topics = HashMap::new();
+    event_topics.insert("identify".to_string(), "analytics-profiles".to_string());
+    event_topics.insert("revenue".to_string(), "analytics-revenue-{project}".to_string());
+
+    let config = StreamingConfig {
+        service_type: StreamingServiceType::Kafka,
+        kafka: Some(KafkaConfig {
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "analytics-events".to_string(),
+            event_topics,
+            ..Default::default()
+        }),
+        kinesis: None,
+        pulsar: None,
+        ..Default::default()
+    };
+
+    let result = create_streaming_service(&config).await;
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_kafka_streaming_rejects_invalid_event_topic() {
+    let brokers = vec!["localhost:9092".to_string()];
+    let mut routes = HashMap::new();
+    routes.insert("identify".to_string(), "profiles-{unknown}".to_string());
+
+    let result = KafkaStreaming::new(&brokers, "analytics-events".to_string())
+        .and_then(|kafka| kafka.with_topic_routes(&routes));
+
+    assert!(matches!(result, Err(StreamingError::ConfigError(_))));
+}
+
+#[test]
+fn test_send_timeout_from_config() {
+    assert_eq!(send_timeout_from_config(None), DEFAULT_SEND_TIMEOUT);
+    assert_eq!(send_timeout_from_config(Some(250)), Duration::from_millis(250));
+}
+
+#[tokio::test]
+async fn test_with_send_timeout_expires() {
+    // A send that outlives the timeout is classified as a TimeoutError
+    let result: Result<(), StreamingError> = with_send_timeout(Duration::from_millis(10), async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok(())
+    })
+    .await;
+
+    assert!(matches!(result, Err(StreamingError::TimeoutError(_))));
+}
+
+#[tokio::test]
+async fn test_with_send_timeout_passes_through_result() {
+    let result = with_send_timeout(Duration::from_secs(1), async {
+        Err::<(), _>(StreamingError::SendError("broker rejected".to_string()))
+    })
+    .await;
+
+    assert!(matches!(result, Err(StreamingError::SendError(_))));
+}
+
+#[test]
+fn test_kafka_streaming_from_config_with_send_timeout() {
+    use crate::config::KafkaConfig;
+
+    let config = KafkaConfig {
+        brokers: vec!["localhost:9092".to_string()],
+        topic: "analytics-events".to_string(),
+        send_timeout_ms: Some(1500),
+        ..Default::default()
+    };
+
+    let kafka = KafkaStreaming::from_config(&config).expect("Failed to create Kafka service");
+    assert_eq!(kafka.send_timeout, Duration::from_millis(1500));
+}
+
+#[test]
+fn test_kafka_streaming_is_not_transactional_by_default() {
+    let brokers = vec!["localhost:9092".to_string()];
+    let kafka = KafkaStreaming::new(&brokers, "analytics-events".to_string())
+        .expect("Failed to create Kafka service");
+    assert!(!kafka.is_transactional());
+}
+
+#[tokio::test]
+async fn test_kafka_transactional_mode_requires_coordinator() {
+    // Initializing transactions contacts the transaction coordinator,
+    // so without a reachable broker connecting fails with a ConnectionError
+    use crate::config::KafkaConfig;
+
+    let config = KafkaConfig {
+        brokers: vec!["localhost:1".to_string()],
+        topic: "analytics-events".to_string(),
+        send_timeout_ms: Some(100),
+        transactional_id: Some("analytics-api-test".to_string()),
+        ..Default::default()
+    };
+
+    // Creating the producer does not initialize transactions yet
+    assert!(!KafkaStreaming::from_config(&config).unwrap().is_transactional());
+
+    match KafkaStreaming::connect(&config).await {
+        Ok(kafka) => assert!(kafka.is_transactional()),
+        Err(e) => assert!(matches!(e, StreamingError::ConnectionError(_))),
+    }
+}
+
+#[tokio::test]
+async fn test_create_streaming_service_with_fallback() {
+    // Test that a fallback section wraps the primary sink
+    use crate::config::{StreamingConfig, StreamingServiceType, KafkaConfig, FallbackConfig};
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = StreamingConfig {
+        service_type: StreamingServiceType::Kafka,
+        kafka: Some(KafkaConfig {
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "analytics-events".to_string(),
+            ..Default::default()
+        }),
+        fallback: Some(FallbackConfig {
+            path: dir.path().join("fallback.jsonl").to_string_lossy().to_string(),
+            failure_threshold: 3,
+            open_secs: 30,
+            replay_interval_secs: 60,
+        }),
+        ..Default::default()
+    };
+
+    let result = create_streaming_service(&config).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_create_kafka_topics_skips_templated_topics() {
+    use crate::config::{KafkaConfig, KafkaTopicCreationConfig};
+
+    // Nothing static to create, so no broker connection is attempted
+    let config = KafkaConfig {
+        brokers: vec!["localhost:9092".to_string()],
+        topic: "analytics-{project}".to_string(),
+        ..Default::default()
+    };
+    let settings = KafkaTopicCreationConfig {
+        partitions: 3,
+        replication_factor: 1,
+        retention_ms: None,
+    };
+
+    let created = create_kafka_topics(&config, &settings).await.unwrap();
+    assert!(created.is_empty());
+}
+
+#[test]
+fn test_sink_concurrency_limit_from_config() {
+    use crate::config::{KafkaConfig, StreamingConfig, StreamingServiceType};
+
+    let mut config = StreamingConfig {
+        service_type: StreamingServiceType::Kafka,
+        kafka: Some(KafkaConfig {
+            brokers: vec!["localhost:9092".to_string()],
+            topic: "analytics".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    assert_eq!(sink_concurrency_limit(&config), None);
+
+    let kafka = config.kafka.as_mut().unwrap();
+    kafka.max_in_flight = Some(64);
+    kafka.send_timeout_ms = Some(250);
+    assert_eq!(
+        sink_concurrency_limit(&config),
+        Some((64, Duration::from_millis(250)))
+    );
+}
+
+#[tokio::test]
+async fn test_instrumented_sinks_record_queued_delivery() {
+    use crate::config::{AckMode, StreamingConfig};
+
+    let config = StreamingConfig {
+        ack_mode: AckMode::Queued,
+        ..Default::default()
+    };
+    let metrics = Arc::new(SinkMetrics::new("stdout"));
+    let service = create_instrumented_streaming_service(&config, metrics.clone(), Arc::new(StageMetrics::new()))
+        .await
+        .unwrap();
+
+    // Recorded by the queue worker once the stdout sink has written the event
+    service.send_event(&create_test_event()).await.unwrap();
+    service.close(Duration::from_secs(5)).await.unwrap();
+
+    assert_eq!(metrics.topic("").delivered(), 1);
+    assert_eq!(metrics.in_flight(), 0);
 }
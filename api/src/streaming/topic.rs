@@ -0,0 +1,376 @@
+// Topic name templating
+// This module renders per-event topic/stream names from templates like "analytics-{project}"
+
+use std::collections::HashMap;
+
+use super::StreamingError;
+use crate::transformer::AnalyticsEvent;
+
+/// Maximum length of a single substituted value
+/// Keeps rendered names well below broker limits (Kafka: 249, Kinesis: 128)
+const MAX_SUBSTITUTION_LENGTH: usize = 64;
+
+/// Value substituted when the event has no value for a placeholder
+const MISSING_VALUE: &str = "unknown";
+
+/// A single piece of a parsed topic template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Literal text copied verbatim into the rendered name
+    Literal(String),
+    /// `{project}` placeholder, replaced with the event's project
+    Project,
+    /// `{event}` placeholder, replaced with the event name
+    Event,
+}
+
+/// Topic/stream name template
+///
+/// Supported placeholders:
+/// * `{project}` - the event's project identifier
+/// * `{event}` - the event name (e.g. pageview, identify)
+///
+/// A template without placeholders is static and always renders to the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicTemplate {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl TopicTemplate {
+    /// Parse a topic template
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if the template is empty, contains an
+    /// unknown placeholder, or has unbalanced braces
+    pub fn parse(template: &str) -> Result<Self, StreamingError> {
+        if template.is_empty() {
+            return Err(StreamingError::ConfigError(
+                "Topic template is empty".to_string(),
+            ));
+        }
+
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err(StreamingError::ConfigError(format!(
+                    "Unbalanced '}}' in topic template: {}",
+                    template
+                )));
+            }
+
+            literal.push_str(&rest[..start]);
+            let after_brace = &rest[start + 1..];
+            let end = after_brace.find('}').ok_or_else(|| {
+                StreamingError::ConfigError(format!(
+                    "Unclosed '{{' in topic template: {}",
+                    template
+                ))
+            })?;
+
+            let segment = match &after_brace[..end] {
+                "project" => Segment::Project,
+                "event" => Segment::Event,
+                other => {
+                    return Err(StreamingError::ConfigError(format!(
+                        "Unknown placeholder '{{{}}}' in topic template: {}",
+                        other, template
+                    )))
+                }
+            };
+
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(segment);
+            rest = &after_brace[end + 1..];
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(TopicTemplate {
+            raw: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Build a static template that renders to `name` verbatim
+    pub fn literal(name: &str) -> Self {
+        TopicTemplate {
+            raw: name.to_string(),
+            segments: vec![Segment::Literal(name.to_string())],
+        }
+    }
+
+    /// The template string as configured
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Whether the template contains no placeholders
+    pub fn is_static(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|s| matches!(s, Segment::Literal(_)))
+    }
+
+    /// Render the topic name for an event
+    ///
+    /// Substituted values are sanitized so they can only contribute characters that are
+    /// valid in Kafka topic, Pulsar topic, and Kinesis stream names.
+    pub fn render(&self, event: &AnalyticsEvent) -> String {
+        if self.is_static() {
+            return self.raw.clone();
+        }
+
+        let mut rendered = String::with_capacity(self.raw.len() + MAX_SUBSTITUTION_LENGTH);
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => rendered.push_str(text),
+                Segment::Project => {
+                    rendered.push_str(&sanitize_topic_value(event.project.as_deref()))
+                }
+                Segment::Event => rendered.push_str(&sanitize_topic_value(Some(&event.event))),
+            }
+        }
+        rendered
+    }
+}
+
+/// Selects the destination topic for an event
+///
+/// Events of a project with its own topic go to that topic; otherwise events whose name
+/// has an entry in the route table go to that route's topic, and all other events go to
+/// the default topic. Every topic may be a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicRouter {
+    default: TopicTemplate,
+    routes: HashMap<String, TopicTemplate>,
+    projects: HashMap<String, TopicTemplate>,
+}
+
+impl TopicRouter {
+    /// Create a router that sends every event to `default`
+    pub fn new(default: TopicTemplate) -> Self {
+        TopicRouter {
+            default,
+            routes: HashMap::new(),
+            projects: HashMap::new(),
+        }
+    }
+
+    /// Add event name → topic routes
+    ///
+    /// Event names are matched case-insensitively.
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if any route topic is not a valid template
+    pub fn with_routes(mut self, routes: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        for (event_name, topic) in routes {
+            self.routes
+                .insert(event_name.to_lowercase(), TopicTemplate::parse(topic)?);
+        }
+        Ok(self)
+    }
+
+    /// Add project → topic overrides, taking precedence over the event name routes
+    ///
+    /// # Errors
+    /// Returns `StreamingError::ConfigError` if any project topic is not a valid template
+    pub fn with_project_routes(mut self, projects: &HashMap<String, String>) -> Result<Self, StreamingError> {
+        for (project, topic) in projects {
+            self.projects.insert(project.clone(), TopicTemplate::parse(topic)?);
+        }
+        Ok(self)
+    }
+
+    /// The default topic template
+    pub fn default_topic(&self) -> &TopicTemplate {
+        &self.default
+    }
+
+    /// Whether every possible destination is a static topic
+    pub fn is_static(&self) -> bool {
+        self.default.is_static()
+            && self.routes.values().all(TopicTemplate::is_static)
+            && self.projects.values().all(TopicTemplate::is_static)
+    }
+
+    /// All statically known topic names (default plus routes without placeholders)
+    pub fn static_topics(&self) -> Vec<&str> {
+        std::iter::once(&self.default)
+            .chain(self.routes.values())
+            .chain(self.projects.values())
+            .filter(|t| t.is_static())
+            .map(TopicTemplate::as_str)
+            .collect()
+    }
+
+    /// Render the destination topic for an event
+    pub fn render(&self, event: &AnalyticsEvent) -> String {
+        let project_route = event.project.as_ref().and_then(|project| self.projects.get(project));
+        if let Some(template) = project_route {
+            return template.render(event);
+        }
+        let template = if self.routes.is_empty() {
+            &self.default
+        } else {
+            self.routes
+                .get(&event.event.to_lowercase())
+                .unwrap_or(&self.default)
+        };
+        template.render(event)
+    }
+}
+
+/// Sanitize a value before substituting it into a topic name
+///
+/// Characters outside `[A-Za-z0-9._-]` are replaced with `_`, the result is lowercased
+/// and truncated to 64 characters. Missing or empty values become "unknown".
+pub fn sanitize_topic_value(value: Option<&str>) -> String {
+    let value = value.map(str::trim).unwrap_or("");
+    if value.is_empty() {
+        return MISSING_VALUE.to_string();
+    }
+
+    let sanitized: String = value
+        .chars()
+        .take(MAX_SUBSTITUTION_LENGTH)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // "." and ".." are reserved names in Kafka
+    if sanitized.chars().all(|c| c == '.') {
+        return MISSING_VALUE.to_string();
+    }
+
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_for(project: Option<&str>, event: &str) -> AnalyticsEvent {
+        AnalyticsEvent {
+            project: project.map(|p| p.to_string()),
+            event: event.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_static_template() {
+        let template = TopicTemplate::parse("analytics-events").unwrap();
+        assert!(template.is_static());
+        assert_eq!(template.render(&event_for(Some("acme"), "pageview")), "analytics-events");
+    }
+
+    #[test]
+    fn test_project_placeholder() {
+        let template = TopicTemplate::parse("analytics-{project}").unwrap();
+        assert!(!template.is_static());
+        assert_eq!(template.render(&event_for(Some("acme"), "pageview")), "analytics-acme");
+    }
+
+    #[test]
+    fn test_multiple_placeholders() {
+        let template = TopicTemplate::parse("persistent://public/default/{project}.{event}").unwrap();
+        assert_eq!(
+            template.render(&event_for(Some("acme"), "identify")),
+            "persistent://public/default/acme.identify"
+        );
+    }
+
+    #[test]
+    fn test_substituted_value_is_sanitized() {
+        let template = TopicTemplate::parse("analytics-{project}").unwrap();
+        assert_eq!(
+            template.render(&event_for(Some("Acme Corp/../x"), "pageview")),
+            "analytics-acme_corp_.._x"
+        );
+    }
+
+    #[test]
+    fn test_missing_project_uses_fallback() {
+        let template = TopicTemplate::parse("analytics-{project}").unwrap();
+        assert_eq!(template.render(&event_for(None, "pageview")), "analytics-unknown");
+        assert_eq!(template.render(&event_for(Some("  "), "pageview")), "analytics-unknown");
+    }
+
+    #[test]
+    fn test_long_value_is_truncated() {
+        let long = "a".repeat(500);
+        let sanitized = sanitize_topic_value(Some(&long));
+        assert_eq!(sanitized.len(), MAX_SUBSTITUTION_LENGTH);
+    }
+
+    #[test]
+    fn test_dot_only_value_is_rejected() {
+        assert_eq!(sanitize_topic_value(Some("..")), "unknown");
+    }
+
+    #[test]
+    fn test_router_routes_by_event_name() {
+        let mut routes = HashMap::new();
+        routes.insert("identify".to_string(), "analytics-profiles".to_string());
+        routes.insert("Revenue".to_string(), "analytics-revenue-{project}".to_string());
+
+        let router = TopicRouter::new(TopicTemplate::parse("analytics-events").unwrap())
+            .with_routes(&routes)
+            .unwrap();
+
+        assert_eq!(router.render(&event_for(Some("acme"), "pageview")), "analytics-events");
+        assert_eq!(router.render(&event_for(Some("acme"), "identify")), "analytics-profiles");
+        assert_eq!(router.render(&event_for(Some("acme"), "revenue")), "analytics-revenue-acme");
+        assert!(!router.is_static());
+        assert_eq!(router.static_topics().len(), 2);
+    }
+
+    #[test]
+    fn test_project_routes_take_precedence() {
+        let routes = HashMap::from([("identify".to_string(), "analytics-profiles".to_string())]);
+        let projects = HashMap::from([("acme".to_string(), "acme-{event}".to_string())]);
+
+        let router = TopicRouter::new(TopicTemplate::parse("analytics-events").unwrap())
+            .with_routes(&routes)
+            .unwrap()
+            .with_project_routes(&projects)
+            .unwrap();
+
+        assert_eq!(router.render(&event_for(Some("acme"), "identify")), "acme-identify");
+        assert_eq!(router.render(&event_for(Some("other"), "identify")), "analytics-profiles");
+        assert_eq!(router.render(&event_for(None, "pageview")), "analytics-events");
+        assert!(!router.is_static());
+    }
+
+    #[test]
+    fn test_router_rejects_invalid_route() {
+        let mut routes = HashMap::new();
+        routes.insert("identify".to_string(), "profiles-{nope}".to_string());
+
+        let result = TopicRouter::new(TopicTemplate::parse("analytics-events").unwrap())
+            .with_routes(&routes);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(TopicTemplate::parse("").is_err());
+        assert!(TopicTemplate::parse("analytics-{tenant}").is_err());
+        assert!(TopicTemplate::parse("analytics-{project").is_err());
+        assert!(TopicTemplate::parse("analytics-project}").is_err());
+    }
+}
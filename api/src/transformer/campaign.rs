@@ -0,0 +1,273 @@
+// Campaign (UTM) parameter extraction
+// This module builds the structured `campaign` object from utm_* parameters and the page URL,
+// and collects ad click identifiers (gclid, fbclid, ...) into `click_ids`
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::CampaignConfig;
+
+/// Campaign fields, in the order they are documented and validated
+pub const CAMPAIGN_FIELDS: [&str; 5] = ["source", "medium", "campaign", "term", "content"];
+
+/// Marketing campaign attribution extracted from UTM parameters
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct CampaignObject {
+    pub source: Option<String>,
+    pub medium: Option<String>,
+    pub campaign: Option<String>,
+    pub term: Option<String>,
+    pub content: Option<String>,
+}
+
+impl CampaignObject {
+    fn field_mut(&mut self, field: &str) -> Option<&mut Option<String>> {
+        match field {
+            "source" => Some(&mut self.source),
+            "medium" => Some(&mut self.medium),
+            "campaign" => Some(&mut self.campaign),
+            "term" => Some(&mut self.term),
+            "content" => Some(&mut self.content),
+            _ => None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &CampaignObject::default()
+    }
+}
+
+/// Ad click identifiers appended to landing page URLs by ad platforms
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ClickIds {
+    /// Google Ads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gclid: Option<String>,
+    /// Google Ads, iOS app campaigns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gbraid: Option<String>,
+    /// Google Ads, iOS web campaigns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wbraid: Option<String>,
+    /// Google Display & Video 360 / Campaign Manager
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dclid: Option<String>,
+    /// Meta (Facebook, Instagram)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fbclid: Option<String>,
+    /// Microsoft Advertising
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msclkid: Option<String>,
+    /// TikTok
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttclid: Option<String>,
+    /// X (Twitter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub twclid: Option<String>,
+    /// LinkedIn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub li_fat_id: Option<String>,
+    /// Pinterest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epik: Option<String>,
+    /// Snapchat (`ScCid` parameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sccid: Option<String>,
+    /// Yandex Direct
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yclid: Option<String>,
+}
+
+impl ClickIds {
+    /// Field for each recognized click id parameter name
+    fn field_mut(&mut self, param: &str) -> Option<&mut Option<String>> {
+        match param {
+            "gclid" => Some(&mut self.gclid),
+            "gbraid" => Some(&mut self.gbraid),
+            "wbraid" => Some(&mut self.wbraid),
+            "dclid" => Some(&mut self.dclid),
+            "fbclid" => Some(&mut self.fbclid),
+            "msclkid" => Some(&mut self.msclkid),
+            "ttclid" => Some(&mut self.ttclid),
+            "twclid" => Some(&mut self.twclid),
+            "li_fat_id" => Some(&mut self.li_fat_id),
+            "epik" => Some(&mut self.epik),
+            "ScCid" | "sccid" => Some(&mut self.sccid),
+            "yclid" => Some(&mut self.yclid),
+            _ => None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &ClickIds::default()
+    }
+}
+
+/// Extract ad click identifiers from request parameters
+///
+/// Each identifier is read from the explicit request parameters first and from the
+/// query string of the `url` parameter otherwise. Empty values are ignored.
+///
+/// # Returns
+/// The click ids, or None when no click id parameter is present
+pub fn extract_click_ids(params: &HashMap<String, String>) -> Option<ClickIds> {
+    let url_params: HashMap<String, String> = params
+        .get("url")
+        .map(|url| url_query_params(url))
+        .unwrap_or_default();
+
+    let mut click_ids = ClickIds::default();
+    // URL values first so explicit parameters overwrite them
+    for source in [&url_params, params] {
+        for (name, value) in source {
+            if value.is_empty() {
+                continue;
+            }
+            if let Some(slot) = click_ids.field_mut(name) {
+                *slot = Some(value.clone());
+            }
+        }
+    }
+
+    if click_ids.is_empty() {
+        None
+    } else {
+        Some(click_ids)
+    }
+}
+
+/// Extract campaign attribution from request parameters
+///
+/// Each field is looked up as `utm_<field>` followed by its configured aliases, first
+/// in the explicit request parameters and then in the query string of the `url`
+/// parameter. Empty values are ignored.
+///
+/// # Arguments
+/// * `params` - Merged request parameters
+/// * `config` - Campaign alias configuration
+///
+/// # Returns
+/// The campaign object, or None when no campaign parameter is present
+pub fn extract_campaign(params: &HashMap<String, String>, config: &CampaignConfig) -> Option<CampaignObject> {
+    let url_params: HashMap<String, String> = params
+        .get("url")
+        .map(|url| url_query_params(url))
+        .unwrap_or_default();
+
+    let mut campaign = CampaignObject::default();
+    for field in CAMPAIGN_FIELDS {
+        let canonical = format!("utm_{}", field);
+        let aliases = config.aliases.get(field).map(Vec::as_slice).unwrap_or_default();
+        let names: Vec<&str> = std::iter::once(canonical.as_str())
+            .chain(aliases.iter().map(String::as_str))
+            .collect();
+
+        let value = [params, &url_params].into_iter().find_map(|source| {
+            names
+                .iter()
+                .filter_map(|name| source.get(*name))
+                .find(|value| !value.is_empty())
+        });
+
+        if let (Some(value), Some(slot)) = (value, campaign.field_mut(field)) {
+            *slot = Some(value.clone());
+        }
+    }
+
+    if campaign.is_empty() {
+        None
+    } else {
+        Some(campaign)
+    }
+}
+
+/// Decoded query-string parameters of a (possibly relative) URL; first occurrence wins
+fn url_query_params(url: &str) -> HashMap<String, String> {
+    let Some((_, rest)) = url.split_once('?') else {
+        return HashMap::new();
+    };
+    let query = rest.split('#').next().unwrap_or("");
+
+    let mut params = HashMap::new();
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        params.entry(key.into_owned()).or_insert_with(|| value.into_owned());
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_extracts_explicit_utm_params() {
+        let campaign = extract_campaign(
+            &params(&[("utm_source", "newsletter"), ("utm_medium", "email")]),
+            &CampaignConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(campaign.source.as_deref(), Some("newsletter"));
+        assert_eq!(campaign.medium.as_deref(), Some("email"));
+        assert_eq!(campaign.campaign, None);
+    }
+
+    #[test]
+    fn test_extracts_from_page_url() {
+        let campaign = extract_campaign(
+            &params(&[(
+                "url",
+                "https://example.com/landing?utm_source=google&utm_campaign=spring%20sale#top",
+            )]),
+            &CampaignConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(campaign.source.as_deref(), Some("google"));
+        assert_eq!(campaign.campaign.as_deref(), Some("spring sale"));
+    }
+
+    #[test]
+    fn test_explicit_params_take_precedence_over_url() {
+        let campaign = extract_campaign(
+            &params(&[("utm_source", "explicit"), ("url", "/page?utm_source=from-url")]),
+            &CampaignConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(campaign.source.as_deref(), Some("explicit"));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let config = CampaignConfig {
+            aliases: HashMap::from([("source".to_string(), vec!["ref".to_string()])]),
+        };
+        let campaign = extract_campaign(&params(&[("url", "/page?ref=partner")]), &config).unwrap();
+        assert_eq!(campaign.source.as_deref(), Some("partner"));
+    }
+
+    #[test]
+    fn test_extracts_click_ids() {
+        let click_ids = extract_click_ids(&params(&[
+            ("gclid", "Cj0KCQ"),
+            ("url", "https://example.com/landing?fbclid=IwAR2x&msclkid=&ScCid=snap-1&gclid=from-url"),
+        ]))
+        .unwrap();
+        assert_eq!(click_ids.gclid.as_deref(), Some("Cj0KCQ"));
+        assert_eq!(click_ids.fbclid.as_deref(), Some("IwAR2x"));
+        assert_eq!(click_ids.sccid.as_deref(), Some("snap-1"));
+        assert_eq!(click_ids.msclkid, None);
+
+        assert_eq!(extract_click_ids(&params(&[("url", "/page?utm_source=google")])), None);
+    }
+
+    #[test]
+    fn test_no_campaign_params() {
+        assert_eq!(
+            extract_campaign(&params(&[("url", "/page?q=1"), ("utm_term", "")]), &CampaignConfig::default()),
+            None
+        );
+    }
+}
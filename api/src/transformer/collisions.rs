@@ -0,0 +1,200 @@
+// Root-level property collisions
+// This module keeps flattened session/project properties from overwriting standard event fields
+
+use std::collections::HashMap;
+
+use super::prefixes::match_rule;
+use crate::config::{ParameterConfig, PrefixTarget, RootCollisionStrategy};
+
+/// Root-level names serialized by AnalyticsEvent itself; session and project
+/// properties must not use them
+pub const RESERVED_ROOT_FIELDS: &[&str] = &[
+    "schema_version", "project", "event", "raw_event", "operation", "id", "timestamp",
+    "timestamp_unit", "received_at", "sent_at", "expires_at", "revenue", "revenue_currency",
+    "quantity", "visit", "event_param", "profile", "profile_operations", "objects", "campaign",
+    "click_ids", "context", "session", "project_props", "browser", "browser_version", "os",
+    "os_version", "os_family", "os_major", "device", "device_brand", "device_model",
+    "in_app_browser", "is_headless", "bot_name", "bot_category", "country", "country_code",
+    "continent_code", "region", "city", "postal_code", "latitude", "longitude", "accuracy_radius",
+    "network", "timezone", "utc_offset_minutes", "local_hour", "local_day_of_week", "currency",
+    "is_eu", "geohash", "ip_hash", "fingerprint", "session_id", "session_start",
+    "session_event_index", "attributes", "warnings", "provenance", "_meta",
+];
+
+/// Session and project properties moved out of the root by `RootCollisionStrategy::Nest`
+#[derive(Debug, Default, PartialEq)]
+pub struct NestedProperties {
+    /// Colliding session properties (the event's `session` object)
+    pub session: HashMap<String, String>,
+    /// Colliding project properties (the event's `project_props` object)
+    pub project: HashMap<String, String>,
+}
+
+/// Whether `name` is a standard root-level field
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED_ROOT_FIELDS.contains(&name)
+}
+
+/// Move session and project properties that collide with a standard field, or with
+/// each other, out of the way according to `strategy`
+///
+/// A project property collides with a session property of the same name. With
+/// `Prefix`, colliding names get the built-in prefix back (`s_event`, `p_event`);
+/// with `Nest`, they are returned for the `session` / `project_props` objects.
+/// `Reject` is enforced by request validation, so anything left here is dropped.
+/// Every change is described in `warnings`.
+pub fn resolve_root_collisions(
+    session: &mut HashMap<String, String>,
+    project: &mut HashMap<String, String>,
+    strategy: RootCollisionStrategy,
+    warnings: &mut Vec<String>,
+) -> NestedProperties {
+    let mut nested = NestedProperties::default();
+    let mut colliding: Vec<String> = session.keys().filter(|name| is_reserved(name)).cloned().collect();
+    colliding.sort_unstable();
+    move_colliding(session, colliding, "session", "s_", strategy, &mut nested.session, warnings);
+
+    let mut colliding: Vec<String> = project
+        .keys()
+        .filter(|name| is_reserved(name) || session.contains_key(*name))
+        .cloned()
+        .collect();
+    colliding.sort_unstable();
+    move_colliding(project, colliding, "project", "p_", strategy, &mut nested.project, warnings);
+
+    nested
+}
+
+fn move_colliding(
+    properties: &mut HashMap<String, String>,
+    colliding: Vec<String>,
+    kind: &str,
+    prefix: &str,
+    strategy: RootCollisionStrategy,
+    nested: &mut HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) {
+    for name in colliding {
+        let Some(value) = properties.remove(&name) else {
+            continue;
+        };
+        match strategy {
+            RootCollisionStrategy::Prefix => {
+                let renamed = format!("{}{}", prefix, name);
+                if properties.contains_key(&renamed) {
+                    warnings.push(format!("{} property {} dropped: collides with a root field", kind, name));
+                    continue;
+                }
+                warnings.push(format!("{} property {} renamed to {}: collides with a root field", kind, name, renamed));
+                properties.insert(renamed, value);
+            }
+            RootCollisionStrategy::Nest => {
+                warnings.push(format!("{} property {} nested: collides with a root field", kind, name));
+                nested.insert(name, value);
+            }
+            RootCollisionStrategy::Reject => {
+                warnings.push(format!("{} property {} dropped: collides with a root field", kind, name));
+            }
+        }
+    }
+}
+
+/// Reject requests whose session or project parameters collide with a root field
+/// when `parameters.root_collisions` is `reject`
+///
+/// # Returns
+/// Ok(()) if no parameter collides (or another strategy is configured), Err with a
+/// descriptive message naming the first colliding parameter otherwise
+pub fn validate_root_collisions(params: &HashMap<String, String>, config: &ParameterConfig) -> Result<(), String> {
+    if config.root_collisions != RootCollisionStrategy::Reject {
+        return Ok(());
+    }
+
+    let rules = config.prefix_rules();
+    let mut session = Vec::new();
+    let mut project = Vec::new();
+    for key in params.keys() {
+        match match_rule(&rules, key) {
+            Some(rule) if rule.target == PrefixTarget::Session => session.push((key, &key[rule.prefix.len()..])),
+            Some(rule) if rule.target == PrefixTarget::Project => project.push((key, &key[rule.prefix.len()..])),
+            _ => {}
+        }
+    }
+
+    let mut colliding: Vec<&String> = session
+        .iter()
+        .filter(|(_, name)| is_reserved(name))
+        .chain(
+            project
+                .iter()
+                .filter(|(_, name)| is_reserved(name) || session.iter().any(|(_, other)| other == name)),
+        )
+        .map(|(key, _)| *key)
+        .collect();
+    colliding.sort_unstable();
+    match colliding.first() {
+        Some(key) => Err(format!("Parameter {} collides with a root-level event field", key)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformer::AnalyticsEvent;
+
+    fn props(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_reserved_fields_cover_serialized_event() {
+        let value = serde_json::to_value(AnalyticsEvent::default()).unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(is_reserved(key), "{} is missing from RESERVED_ROOT_FIELDS", key);
+        }
+    }
+
+    #[test]
+    fn test_prefix_strategy_renames_collisions() {
+        let mut session = props(&[("event", "a"), ("plan", "pro")]);
+        let mut project = props(&[("plan", "team"), ("region", "eu")]);
+        let mut warnings = Vec::new();
+        let nested = resolve_root_collisions(&mut session, &mut project, RootCollisionStrategy::Prefix, &mut warnings);
+
+        assert_eq!(session, props(&[("s_event", "a"), ("plan", "pro")]));
+        assert_eq!(project, props(&[("p_plan", "team"), ("p_region", "eu")]));
+        assert_eq!(nested, NestedProperties::default());
+        assert_eq!(warnings[0], "session property event renamed to s_event: collides with a root field");
+    }
+
+    #[test]
+    fn test_nest_strategy_moves_collisions() {
+        let mut session = props(&[("event", "a"), ("plan", "pro")]);
+        let mut project = props(&[("city", "Tokyo")]);
+        let mut warnings = Vec::new();
+        let nested = resolve_root_collisions(&mut session, &mut project, RootCollisionStrategy::Nest, &mut warnings);
+
+        assert_eq!(session, props(&[("plan", "pro")]));
+        assert!(project.is_empty());
+        assert_eq!(nested.session, props(&[("event", "a")]));
+        assert_eq!(nested.project, props(&[("city", "Tokyo")]));
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_rejects_collisions() {
+        let reject = ParameterConfig {
+            root_collisions: RootCollisionStrategy::Reject,
+            ..Default::default()
+        };
+        let params = props(&[("event", "view"), ("s_plan", "pro"), ("p_plan", "team")]);
+        assert_eq!(
+            validate_root_collisions(&params, &reject),
+            Err("Parameter p_plan collides with a root-level event field".to_string())
+        );
+        assert!(validate_root_collisions(&props(&[("s_event", "x")]), &reject).is_err());
+        assert!(validate_root_collisions(&props(&[("s_plan", "pro")]), &reject).is_ok());
+        assert!(validate_root_collisions(&params, &ParameterConfig::default()).is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+// Revenue, currency and quantity
+// This module reads and validates the well-known commerce parameters stored as typed root fields
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+
+use crate::enrichment::is_currency_code;
+
+/// Parameter holding the monetary value of the event
+pub const REVENUE_PARAM: &str = "revenue";
+/// Parameter holding the ISO 4217 currency of the revenue
+pub const CURRENCY_PARAM: &str = "currency";
+/// Parameter holding the number of items
+pub const QUANTITY_PARAM: &str = "quantity";
+
+/// Parse a revenue amount: a plain decimal such as `19.99`, `-5` or `0.5`
+///
+/// Negative amounts are accepted for refunds; exponents, thousands separators and a
+/// leading `+` are not.
+///
+/// # Returns
+/// The exact amount, keeping the scale sent (`"5.50"` is 5.50, not 5.5), or Err if it
+/// has more than 28 significant digits
+pub fn parse_revenue(raw: &str) -> Result<Decimal, String> {
+    let raw = raw.trim();
+    let unsigned = raw.strip_prefix('-').unwrap_or(raw);
+    let (int_part, fraction) = match unsigned.split_once('.') {
+        Some((int_part, fraction)) => (int_part, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(int_part) || !fraction.is_none_or(digits) {
+        return Err(format!("expected a decimal amount, got '{}'", raw));
+    }
+    Decimal::from_str_exact(raw).map_err(|_| format!("amount out of range, got '{}'", raw))
+}
+
+/// Parse an ISO 4217 currency code, case-insensitively
+///
+/// # Returns
+/// The upper-case code, e.g. "EUR" for "eur"
+pub fn parse_currency(raw: &str) -> Result<String, String> {
+    let code = raw.trim().to_ascii_uppercase();
+    if is_currency_code(&code) {
+        Ok(code)
+    } else {
+        Err(format!("expected an ISO 4217 currency code, got '{}'", raw))
+    }
+}
+
+/// Parse an item quantity: a whole number
+pub fn parse_quantity(raw: &str) -> Result<i64, String> {
+    raw.trim()
+        .parse::<i64>()
+        .map_err(|_| format!("expected a whole number, got '{}'", raw))
+}
+
+/// Validate the `revenue`, `currency` and `quantity` parameters when present
+///
+/// # Returns
+/// Ok(()) if every commerce parameter sent holds a valid value, Err with a
+/// descriptive message otherwise
+pub fn validate_commerce_params(params: &HashMap<String, String>) -> Result<(), String> {
+    let check = |name: &str, parse: fn(&str) -> Result<(), String>| match params.get(name) {
+        Some(raw) => parse(raw).map_err(|e| format!("Invalid value for {}: {}", name, e)),
+        None => Ok(()),
+    };
+    check(REVENUE_PARAM, |raw| parse_revenue(raw).map(|_| ()))?;
+    check(CURRENCY_PARAM, |raw| parse_currency(raw).map(|_| ()))?;
+    check(QUANTITY_PARAM, |raw| parse_quantity(raw).map(|_| ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_revenue() {
+        assert_eq!(parse_revenue("19.99"), Ok(Decimal::new(1999, 2)));
+        assert_eq!(parse_revenue(" 42 "), Ok(Decimal::new(42, 0)));
+        assert_eq!(parse_revenue("-5.50").map(|amount| amount.to_string()), Ok("-5.50".to_string()));
+        assert_eq!(parse_revenue("007"), Ok(Decimal::new(7, 0)));
+        // Amounts beyond float precision are kept exactly
+        assert_eq!(parse_revenue("90071992547409.93"), Ok(Decimal::new(9007199254740993, 2)));
+        let too_long = format!("1{}", "0".repeat(30));
+        assert!(parse_revenue(&too_long).is_err());
+        for raw in ["", "abc", "1e3", "+1", "1,000.00", "1.", ".5", "NaN", "inf", "-"] {
+            assert!(parse_revenue(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_parse_currency_and_quantity() {
+        assert_eq!(parse_currency("eur"), Ok("EUR".to_string()));
+        assert_eq!(parse_currency("USD"), Ok("USD".to_string()));
+        assert!(parse_currency("EURO").is_err());
+        assert!(parse_currency("ABC").is_err());
+        assert_eq!(parse_quantity("3"), Ok(3));
+        assert!(parse_quantity("2.5").is_err());
+    }
+
+    #[test]
+    fn test_validate_commerce_params() {
+        let params = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert!(validate_commerce_params(&params(&[("event", "purchase")])).is_ok());
+        assert!(validate_commerce_params(&params(&[("revenue", "10"), ("currency", "jpy"), ("quantity", "1")])).is_ok());
+        assert_eq!(
+            validate_commerce_params(&params(&[("revenue", "10"), ("currency", "XYZ")])),
+            Err("Invalid value for currency: expected an ISO 4217 currency code, got 'XYZ'".to_string())
+        );
+        assert_eq!(
+            validate_commerce_params(&params(&[("revenue", "$10")])),
+            Err("Invalid value for revenue: expected a decimal amount, got '$10'".to_string())
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// SDK context extraction
+// This module builds the structured `context` object from c_* parameters and the JSON `context` parameter
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// App and SDK context sent by mobile and server SDKs
+///
+/// Known keys get dedicated fields; other context keys are kept in `extra`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ContextObject {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_build: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    /// Other context keys
+    #[serde(flatten)]
+    pub extra: HashMap<String, String>,
+}
+
+impl ContextObject {
+    /// Set a context key, routing known keys to their field
+    fn set(&mut self, key: &str, value: String) {
+        let slot = match key {
+            "app_name" => &mut self.app_name,
+            "app_version" => &mut self.app_version,
+            "app_build" => &mut self.app_build,
+            "sdk_name" => &mut self.sdk_name,
+            "sdk_version" => &mut self.sdk_version,
+            "device_model" => &mut self.device_model,
+            _ => {
+                self.extra.insert(key.to_string(), value);
+                return;
+            }
+        };
+        *slot = Some(value);
+    }
+
+    fn is_empty(&self) -> bool {
+        self == &ContextObject::default()
+    }
+}
+
+/// Flatten a JSON context object into `prefix_key` entries
+///
+/// Nested objects join their keys with `_` (`{"app": {"version": "2.1"}}` becomes
+/// `app_version`); arrays and nulls are skipped.
+fn flatten_json(prefix: &str, value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}_{}", prefix, name)
+        }
+    };
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, value) in map {
+                flatten_json(&key(name), value, out);
+            }
+        }
+        serde_json::Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        serde_json::Value::Number(n) => out.push((prefix.to_string(), n.to_string())),
+        serde_json::Value::Bool(b) => out.push((prefix.to_string(), b.to_string())),
+        serde_json::Value::Array(_) | serde_json::Value::Null => {}
+    }
+}
+
+/// Extract SDK context from request parameters
+///
+/// The `context` parameter may hold a JSON object (flat keys such as `app_version`
+/// or nested objects such as `{"app": {"version": ...}}`); `c_*` parameters are
+/// applied afterwards and win over it. Empty values and keys are ignored.
+///
+/// # Returns
+/// The context object, or None when no context is present
+pub fn extract_context(params: &HashMap<String, String>) -> Option<ContextObject> {
+    let mut entries = Vec::new();
+    if let Some(raw) = params.get("context") {
+        match serde_json::from_str::<serde_json::Value>(raw) {
+            Ok(value @ serde_json::Value::Object(_)) => flatten_json("", &value, &mut entries),
+            _ => tracing::debug!("Ignoring context parameter that is not a JSON object"),
+        }
+    }
+    entries.extend(
+        params
+            .iter()
+            .filter_map(|(key, value)| key.strip_prefix("c_").map(|key| (key.to_string(), value.clone()))),
+    );
+
+    let mut context = ContextObject::default();
+    for (key, value) in entries {
+        if !key.is_empty() && !value.is_empty() {
+            context.set(&key, value);
+        }
+    }
+
+    if context.is_empty() {
+        None
+    } else {
+        Some(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_prefixed_params() {
+        let context = extract_context(&params(&[
+            ("c_app_name", "Shop"),
+            ("c_app_version", "2.1.0"),
+            ("c_sdk_name", "penrose-ios"),
+            ("c_locale", "en_US"),
+            ("e_item", "ignored"),
+        ]))
+        .unwrap();
+        assert_eq!(context.app_name.as_deref(), Some("Shop"));
+        assert_eq!(context.app_version.as_deref(), Some("2.1.0"));
+        assert_eq!(context.sdk_name.as_deref(), Some("penrose-ios"));
+        assert_eq!(context.extra, HashMap::from([("locale".to_string(), "en_US".to_string())]));
+    }
+
+    #[test]
+    fn test_json_context_flattens_nested_objects() {
+        let context = extract_context(&params(&[(
+            "context",
+            r#"{"app": {"name": "Shop", "build": 412}, "sdk": {"name": "penrose-android", "version": "1.4.0"}, "device": {"model": "SM-S918B"}, "tags": ["a"]}"#,
+        )]))
+        .unwrap();
+        assert_eq!(context.app_name.as_deref(), Some("Shop"));
+        assert_eq!(context.app_build.as_deref(), Some("412"));
+        assert_eq!(context.sdk_version.as_deref(), Some("1.4.0"));
+        assert_eq!(context.device_model.as_deref(), Some("SM-S918B"));
+        assert!(context.extra.is_empty());
+    }
+
+    #[test]
+    fn test_prefixed_params_override_json() {
+        let context = extract_context(&params(&[
+            ("context", r#"{"app_version": "1.0"}"#),
+            ("c_app_version", "1.1"),
+        ]))
+        .unwrap();
+        assert_eq!(context.app_version.as_deref(), Some("1.1"));
+    }
+
+    #[test]
+    fn test_no_context() {
+        assert_eq!(extract_context(&params(&[("event", "pageview")])), None);
+        assert_eq!(extract_context(&params(&[("context", "not json"), ("c_app_name", "")])), None);
+    }
+}
@@ -0,0 +1,118 @@
+// Parameter size limits
+// This module bounds the names, values and number of prefixed parameters so one client cannot create oversized events
+
+use crate::config::ParameterLimits;
+
+/// A prefixed parameter routed to a target: full name, name without prefix, raw value
+pub type PrefixedEntry<'a> = (&'a str, &'a str, &'a str);
+
+/// Apply `limits` to the parameters of one target object
+///
+/// Parameters with over-long names are dropped, over-long values are cut at a
+/// character boundary and, when more than `max_properties` remain, those past the
+/// limit in name order are dropped. Every change is described in `warnings`.
+///
+/// # Returns
+/// `(name without prefix, value)` pairs that fit the limits
+pub fn limit_entries<'a>(
+    mut entries: Vec<PrefixedEntry<'a>>,
+    limits: &ParameterLimits,
+    warnings: &mut Vec<String>,
+) -> Vec<(&'a str, &'a str)> {
+    entries.retain(|(key, _, _)| {
+        let fits = key.len() <= limits.max_key_length;
+        if !fits {
+            warnings.push(format!(
+                "parameter {:.64} dropped: name longer than {} bytes",
+                key, limits.max_key_length
+            ));
+        }
+        fits
+    });
+
+    entries.sort_unstable_by_key(|(key, _, _)| *key);
+    if entries.len() > limits.max_properties {
+        let dropped: Vec<&str> = entries[limits.max_properties..].iter().map(|(key, _, _)| *key).collect();
+        warnings.push(format!(
+            "{} parameters dropped over the limit of {}: {}",
+            dropped.len(),
+            limits.max_properties,
+            dropped.join(", ")
+        ));
+        entries.truncate(limits.max_properties);
+    }
+
+    entries
+        .into_iter()
+        .map(|(key, name, raw)| {
+            if raw.len() <= limits.max_value_length {
+                return (name, raw);
+            }
+            warnings.push(format!(
+                "parameter {} truncated to {} bytes",
+                key, limits.max_value_length
+            ));
+            (name, truncate(raw, limits.max_value_length))
+        })
+        .collect()
+}
+
+/// Longest prefix of `value` of at most `max_bytes` bytes that ends on a character boundary
+pub fn truncate(value: &str, max_bytes: usize) -> &str {
+    if value.len() <= max_bytes {
+        return value;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> ParameterLimits {
+        ParameterLimits {
+            max_key_length: 8,
+            max_value_length: 5,
+            max_properties: 2,
+            max_repeated_values: 2,
+        }
+    }
+
+    #[test]
+    fn test_within_limits_is_unchanged() {
+        let mut warnings = Vec::new();
+        let limited = limit_entries(vec![("e_a", "a", "1"), ("e_b", "b", "2")], &limits(), &mut warnings);
+        assert_eq!(limited, vec![("a", "1"), ("b", "2")]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_drops_long_names_and_truncates_values() {
+        let mut warnings = Vec::new();
+        let limited = limit_entries(
+            vec![("e_toolong_name", "toolong_name", "x"), ("e_desc", "desc", "abc€")],
+            &limits(),
+            &mut warnings,
+        );
+        // The euro sign spans bytes 3-5, so the cut falls back to 3 bytes
+        assert_eq!(limited, vec![("desc", "abc")]);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1], "parameter e_desc truncated to 5 bytes");
+    }
+
+    #[test]
+    fn test_drops_properties_over_the_limit() {
+        let mut warnings = Vec::new();
+        let limited = limit_entries(
+            vec![("e_c", "c", "3"), ("e_a", "a", "1"), ("e_b", "b", "2")],
+            &limits(),
+            &mut warnings,
+        );
+        assert_eq!(limited.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(warnings, vec!["1 parameters dropped over the limit of 2: e_c".to_string()]);
+    }
+}
@@ -2,17 +2,121 @@
 // This module transforms flat query parameters into structured JSON with nested objects
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+pub mod campaign;
+pub mod collisions;
+pub mod commerce;
+pub mod context;
+pub mod limits;
+pub mod page_url;
+pub mod payload;
+pub mod plugin;
+pub mod prefixes;
+pub mod profile_ops;
+pub mod record;
+pub mod repeated;
+pub mod sanitize;
+pub mod screen;
+pub mod timestamp;
+pub mod update;
+pub mod values;
+pub mod visit_metrics;
+
+pub use campaign::{extract_campaign, extract_click_ids, CampaignObject, ClickIds};
+pub use collisions::{resolve_root_collisions, validate_root_collisions, NestedProperties};
+pub use commerce::{parse_currency, parse_quantity, parse_revenue, validate_commerce_params};
+pub use context::{extract_context, ContextObject};
+pub use limits::limit_entries;
+pub use page_url::{canonical_url, parse_url, UrlParts};
+pub use payload::{decode_payload, merge_payload, PayloadError, PAYLOAD_PARAM};
+pub use plugin::{DefaultTransformer, Transformer};
+pub use prefixes::{match_rule, split_prefixed, PrefixedParams};
+pub use profile_ops::{extract_profile_operations, ProfileOperations};
+pub use record::{avro_schema, parquet_schema, Column, ColumnType, EventRecord, RECORD_COLUMNS};
+pub use repeated::{apply_repeated, collect_repeated, validate_repeated_params, RepeatedParams};
+pub use sanitize::{sanitize_key, sanitize_keys, sanitize_prefixed};
+pub use screen::{parse_pixel_ratio, parse_screen, viewport_bucket};
+pub use timestamp::{parse_timestamp, parse_timestamp_with_unit, TimestampUnit};
+pub use update::{transform_update_params, Operation, UpdateRecord, UPDATE_EVENT};
+pub use values::{collect_values, infer_value, param_value, validate_typed_params, ParamType};
+pub use visit_metrics::{parse_duration, parse_scroll_depth};
+
+use crate::config::ParameterConfig;
+use crate::enrichment::NetworkInfo;
+
+/// Version of the AnalyticsEvent layout, bumped whenever its structure changes in a
+/// way consumers need to branch on
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Schema version carried by an event; defaults to the current `SCHEMA_VERSION`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct SchemaVersion(pub u32);
+
+impl Default for SchemaVersion {
+    fn default() -> Self {
+        SchemaVersion(SCHEMA_VERSION)
+    }
+}
+
+impl std::fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
 /// Main analytics event structure with root-level fields and nested objects
 /// Validates: Requirements 4.1, 4.2, 4.3, 4.6
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct AnalyticsEvent {
+    /// Layout version of this event (events stored before versioning read as version 1)
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
     // Standard root-level fields (Requirement 4.6)
     pub project: Option<String>,
     pub event: String,
+    /// Event name as sent, when `events` config renamed it (alias or unknown-name bucket)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_event: Option<String>,
+    /// Set on update events, which are streamed as a compact `UpdateRecord`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<Operation>,
     pub id: Option<String>,
     pub timestamp: i64,
+    /// Unit the `timestamp` parameter was sent in, when it was not milliseconds and
+    /// was converted (e.g. epoch seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_unit: Option<TimestampUnit>,
+    /// When the tracker received the request, in epoch milliseconds (set by the handlers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<i64>,
+    /// When the client sent the request (`sent_at` parameter), in epoch milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent_at: Option<i64>,
+    /// When storage may drop the event, in epoch milliseconds (`streaming.retention`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Monetary value of the event (`revenue` parameter)
+    ///
+    /// Held as an exact decimal and serialized as a JSON number; amounts of up to 15
+    /// significant digits keep their exact digits on the wire.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "rust_decimal::serde::float_option"
+    )]
+    pub revenue: Option<rust_decimal::Decimal>,
+    /// ISO 4217 currency of `revenue` (`currency` parameter, upper case)
+    ///
+    /// Not named `currency`, which holds the currency of the visitor's country (set by
+    /// the `currency` enrichment stage).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revenue_currency: Option<String>,
+    /// Number of items (`quantity` parameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<i64>,
     
     // Session properties (s_* prefix removed, placed at root - Requirement 4.4)
     #[serde(flatten)]
@@ -21,38 +125,182 @@ pub struct AnalyticsEvent {
     // Project properties (p_* prefix removed, placed at root - Requirement 4.5)
     #[serde(flatten)]
     pub project_properties: HashMap<String, String>,
+    /// Session properties moved off the root because their names collide with a
+    /// standard field (`parameters.root_collisions: nest`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub session: HashMap<String, String>,
+    /// Project properties moved off the root (`parameters.root_collisions: nest`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub project_props: HashMap<String, String>,
     
     // Nested objects
     pub visit: VisitObject,
     pub event_param: Option<EventParamObject>,
     pub profile: Option<ProfileObject>,
+    /// Merge semantics of the profile properties (u_set_*, u_set_once_*, u_unset_* parameters)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_operations: Option<ProfileOperations>,
+    /// Objects built from custom parameter prefixes (`parameters.prefixes`), by name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub objects: HashMap<String, HashMap<String, Value>>,
+    /// UTM campaign attribution (populated by the handlers via `extract_campaign`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<CampaignObject>,
+    /// Ad click identifiers (gclid, fbclid, msclkid, ...) from the parameters or page URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_ids: Option<ClickIds>,
+    /// App and SDK context (c_* parameters or the JSON `context` parameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<ContextObject>,
     
     // Enriched fields (added by User-Agent parser and GeoIP lookup)
     pub browser: Option<String>,
     pub browser_version: Option<String>,
     pub os: Option<String>,
     pub os_version: Option<String>,
+    /// Normalized OS family, e.g. "Windows", "macOS", "iOS", "Android", "Linux"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_family: Option<String>,
+    /// Major OS version, e.g. "10", "17"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_major: Option<String>,
     pub device: Option<String>,
+    /// Device manufacturer, e.g. "Samsung", "Apple" (mobile devices only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_brand: Option<String>,
+    /// Device model, e.g. "SM-S918B", "iPhone" (mobile devices only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    /// App embedding the browser, e.g. "Instagram", "WeChat", "webview" (in-app browsers only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_app_browser: Option<String>,
+    /// Whether the browser is headless or automated (User-Agent signature or `webdriver` hint)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_headless: Option<bool>,
+    /// Crawler name, e.g. "Googlebot" (bot traffic only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot_name: Option<String>,
+    /// Crawler category, e.g. "search_engine", "monitoring" (bot traffic only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot_category: Option<String>,
     pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    /// Two-letter continent code, e.g. "EU" (MaxMind only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continent_code: Option<String>,
     pub region: Option<String>,
     pub city: Option<String>,
+    /// Postal or ZIP code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    /// Radius in kilometers around the coordinates the visitor is likely in (MaxMind only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy_radius: Option<u16>,
+    /// ISP, connection type and anonymizer flags (when network databases are configured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkInfo>,
+    /// Visitor IANA time zone (from the `tz` parameter or GeoIP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Visitor offset from UTC in minutes at the event time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset_minutes: Option<i32>,
+    /// Hour of the day (0-23) in the visitor's time zone at the event time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_hour: Option<u32>,
+    /// ISO 8601 day of the week (1 = Monday, 7 = Sunday) in the visitor's time zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_day_of_week: Option<u32>,
+    /// ISO 4217 currency of the visitor's country (hint for revenue normalization)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Whether the visitor's country is in the EU/EEA/UK list (GDPR handling)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_eu: Option<bool>,
+    /// Geohash of the GeoIP coordinates (`enrichment.geohash_precision` characters)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geohash: Option<String>,
+    /// Salted hash of the client IP (when IP hashing is configured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_hash: Option<String>,
+    /// Salted device fingerprint (when fingerprinting is configured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Visitor session (when session tracking is configured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Whether the event started its session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_start: Option<bool>,
+    /// Position of the event within its session (1 for the first event)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_event_index: Option<u64>,
+    /// Fields added by external enrichers (e.g. customer tier from the HTTP stage)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, serde_json::Value>,
+    /// Parameters dropped or truncated by `parameters.limits`, and visit metrics
+    /// dropped or clamped as implausible
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Outcome of each processing stage, e.g. `geoip: miss` (`enrichment.provenance`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provenance: BTreeMap<String, String>,
+
+    // Collector metadata (added just before the event is streamed)
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<EventMetadata>,
+}
+
+/// Metadata describing the collector instance that produced an event
+///
+/// Lets consumers trace a record back to the host, region and build that ingested it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct EventMetadata {
+    /// Hostname of the collector instance
+    pub hostname: String,
+    /// Deployment region of the collector, if configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Version of the ingestion pipeline
+    pub pipeline_version: String,
+    /// Unix timestamp (milliseconds) at which the event was handed to the streaming service
+    pub ingested_at: i64,
 }
 
 /// Visit-level data containing session and page information
 /// Validates: Requirement 4.1
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct VisitObject {
     pub cookie: Option<String>,
     pub timestamp: Option<i64>,
     pub url: Option<String>,
+    /// Normalized components of `url` (None when `url` is missing or invalid)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_parts: Option<UrlParts>,
+    /// Normalized `url` without tracking parameters (`parameters.canonical_url`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
     pub title: Option<String>,
     pub domain: Option<String>,
     pub uri: Option<String>,
     pub duration: Option<i64>,
     pub scroll_depth: Option<i32>,
     pub screen: Option<String>,
+    /// Screen width in pixels, parsed from `screen`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen_width: Option<u32>,
+    /// Screen height in pixels, parsed from `screen`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen_height: Option<u32>,
+    /// Device pixel ratio (`dpr` parameter)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixel_ratio: Option<f64>,
+    /// Viewport size bucket derived from the screen width ("mobile", "tablet", "desktop", "large")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub viewport_bucket: Option<String>,
     pub language: Option<String>,
     pub referer: Option<String>,
     pub app: Option<String>,
@@ -62,27 +310,58 @@ pub struct VisitObject {
 /// Validates: Requirement 4.2
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct EventParamObject {
+    /// Parameter values; strings unless `parameters.infer_types` is enabled
     #[serde(flatten)]
-    pub params: HashMap<String, String>,
+    pub params: HashMap<String, Value>,
 }
 
 /// User profile properties (u_* prefixed parameters with prefix removed)
 /// Validates: Requirement 4.3
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct ProfileObject {
+    /// Property values; strings unless `parameters.infer_types` is enabled
     #[serde(flatten)]
-    pub properties: HashMap<String, String>,
+    pub properties: HashMap<String, Value>,
+    /// Domain of the `email` property (set by the `email` enrichment stage)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_domain: Option<String>,
+    /// Whether the email domain belongs to a free provider (set by the `email` stage)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_free_provider: Option<bool>,
+    /// Whether `email` passed validation (set by the `email` stage when validating)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_valid: Option<bool>,
 }
 
 /// Transform flat query parameters into structured AnalyticsEvent
 /// Validates: Requirements 4.1, 4.2, 4.3, 4.4, 4.5, 4.6
 pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
+    transform_params_with(params, &ParameterConfig::default())
+}
+
+/// Transform flat query parameters into structured AnalyticsEvent, converting
+/// e_*/u_* values according to the `parameters` configuration
+pub fn transform_params_with(params: HashMap<String, String>, config: &ParameterConfig) -> AnalyticsEvent {
+    transform_params_with_repeated(params, &RepeatedParams::new(), config)
+}
+
+/// Transform request parameters like `transform_params_with`, storing every value of
+/// repeated event parameters (`e_tag=a&e_tag=b`) as an array in `event_param`
+///
+/// `params` holds the last value of each parameter and `repeated` every value of
+/// the parameters sent more than once.
+pub fn transform_params_with_repeated(
+    params: HashMap<String, String>,
+    repeated: &RepeatedParams,
+    config: &ParameterConfig,
+) -> AnalyticsEvent {
     tracing::debug!(
         param_count = params.len(),
         has_event_params = params.keys().any(|k| k.starts_with("e_")),
         has_profile_params = params.keys().any(|k| k.starts_with("u_")),
         has_session_params = params.keys().any(|k| k.starts_with("s_")),
         has_project_params = params.keys().any(|k| k.starts_with("p_")),
+        has_context_params = params.keys().any(|k| k.starts_with("c_") || k == "context"),
         "Starting parameter transformation"
     );
     
@@ -90,33 +369,65 @@ pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
     let project = params.get("project").cloned();
     let event = params.get("event").cloned().unwrap_or_else(|| "unknown".to_string());
     let id = params.get("id").cloned();
-    let timestamp = params.get("timestamp")
-        .and_then(|t| t.parse::<i64>().ok())
+    // Epoch seconds/milliseconds/microseconds or ISO-8601, normalized to epoch milliseconds
+    let parsed_timestamp = params.get("timestamp").and_then(|t| parse_timestamp_with_unit(t));
+    let timestamp = parsed_timestamp
+        .map(|(millis, _)| millis)
         .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    let timestamp_unit = parsed_timestamp
+        .map(|(_, unit)| unit)
+        .filter(|unit| *unit != TimestampUnit::Milliseconds);
+    if let Some(unit) = timestamp_unit {
+        tracing::debug!(unit = ?unit, timestamp, "Converted timestamp to milliseconds");
+    }
     
     // Extract visit-level fields (Requirement 4.1)
+    // Implausible durations are dropped and scroll depths clamped, with a warning
+    let mut metric_warnings = Vec::new();
+    let screen_size = params.get("screen").and_then(|s| parse_screen(s));
+    let url_parts = params.get("url").and_then(|u| parse_url(u));
     let visit = VisitObject {
         cookie: params.get("cookie").cloned(),
-        timestamp: params.get("timestamp").and_then(|t| t.parse::<i64>().ok()),
+        timestamp: parsed_timestamp.map(|(millis, _)| millis),
         url: params.get("url").cloned(),
         title: params.get("title").cloned(),
-        domain: params.get("domain").cloned(),
-        uri: params.get("uri").cloned(),
-        duration: params.get("duration").and_then(|d| d.parse::<i64>().ok()),
-        scroll_depth: params.get("scroll_depth").and_then(|s| s.parse::<i32>().ok()),
+        // domain and uri default to the URL's host and path when not sent explicitly
+        domain: params
+            .get("domain")
+            .cloned()
+            .or_else(|| url_parts.as_ref().and_then(|parts| parts.host.clone())),
+        uri: params
+            .get("uri")
+            .cloned()
+            .or_else(|| url_parts.as_ref().map(|parts| parts.path.clone())),
+        url_parts,
+        canonical_url: params
+            .get("url")
+            .filter(|_| config.canonical_url.enabled)
+            .and_then(|u| canonical_url(u, &config.canonical_url)),
+        duration: params
+            .get("duration")
+            .and_then(|d| parse_duration(d, config.limits.max_duration_ms, &mut metric_warnings)),
+        scroll_depth: params
+            .get("scroll_depth")
+            .and_then(|s| parse_scroll_depth(s, &mut metric_warnings)),
         screen: params.get("screen").cloned(),
+        screen_width: screen_size.map(|(width, _)| width),
+        screen_height: screen_size.map(|(_, height)| height),
+        pixel_ratio: params.get("dpr").and_then(|d| parse_pixel_ratio(d)),
+        viewport_bucket: screen_size.map(|(width, _)| viewport_bucket(width).to_string()),
         language: params.get("language").cloned(),
         referer: params.get("referer").cloned(),
         app: params.get("app").cloned(),
     };
     
-    // Extract e_* prefixed params into EventParamObject (Requirement 4.2)
-    let mut event_params = HashMap::new();
-    for (key, value) in params.iter() {
-        if let Some(unprefixed) = key.strip_prefix("e_") {
-            event_params.insert(unprefixed.to_string(), value.clone());
-        }
-    }
+    // Route prefixed params to their targets (parameters.prefixes; built-in e_, u_, s_, p_)
+    let mut prefixed = split_prefixed(&params, config);
+    apply_repeated(&mut prefixed.event_params, repeated, config, &mut prefixed.warnings);
+    sanitize_prefixed(&mut prefixed, &config.sanitize_keys);
+
+    // e_* params into EventParamObject (Requirement 4.2)
+    let event_params = prefixed.event_params;
     let event_param = if event_params.is_empty() {
         None
     } else {
@@ -127,13 +438,10 @@ pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
         Some(EventParamObject { params: event_params })
     };
     
-    // Extract u_* prefixed params into ProfileObject (Requirement 4.3)
-    let mut profile_props = HashMap::new();
-    for (key, value) in params.iter() {
-        if let Some(unprefixed) = key.strip_prefix("u_") {
-            profile_props.insert(unprefixed.to_string(), value.clone());
-        }
-    }
+    // u_* params into ProfileObject (Requirement 4.3)
+    let mut profile_props = prefixed.profile;
+    // u_set_*, u_set_once_* and u_unset_* into profile operations
+    let profile_operations = extract_profile_operations(&mut profile_props);
     let profile = if profile_props.is_empty() {
         None
     } else {
@@ -141,16 +449,25 @@ pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
             profile_prop_count = profile_props.len(),
             "Extracted profile properties"
         );
-        Some(ProfileObject { properties: profile_props })
+        Some(ProfileObject {
+            properties: profile_props,
+            ..Default::default()
+        })
     };
     
-    // Extract s_* prefixed params to root level (Requirement 4.4)
-    let mut session_properties = HashMap::new();
-    for (key, value) in params.iter() {
-        if let Some(unprefixed) = key.strip_prefix("s_") {
-            session_properties.insert(unprefixed.to_string(), value.clone());
-        }
-    }
+    // s_* and p_* params to root level, keeping standard fields intact
+    let mut session_properties = prefixed.session;
+    let mut project_properties = prefixed.project;
+    let mut warnings = metric_warnings;
+    warnings.extend(prefixed.warnings);
+    let nested = resolve_root_collisions(
+        &mut session_properties,
+        &mut project_properties,
+        config.root_collisions,
+        &mut warnings,
+    );
+
+    // s_* params to root level (Requirement 4.4)
     if !session_properties.is_empty() {
         tracing::debug!(
             session_prop_count = session_properties.len(),
@@ -158,19 +475,30 @@ pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
         );
     }
     
-    // Extract p_* prefixed params to root level (Requirement 4.5)
-    let mut project_properties = HashMap::new();
-    for (key, value) in params.iter() {
-        if let Some(unprefixed) = key.strip_prefix("p_") {
-            project_properties.insert(unprefixed.to_string(), value.clone());
-        }
-    }
+    // p_* params to root level (Requirement 4.5)
     if !project_properties.is_empty() {
         tracing::debug!(
             project_prop_count = project_properties.len(),
             "Extracted project properties"
         );
     }
+
+    // Custom prefixes into named objects
+    let objects = prefixed.objects;
+    if !objects.is_empty() {
+        tracing::debug!(
+            object_count = objects.len(),
+            "Extracted custom prefix objects"
+        );
+    }
+
+    if !warnings.is_empty() {
+        tracing::warn!(
+            project = ?project,
+            warnings = ?warnings,
+            "Parameters exceeded limits or collided with root fields"
+        );
+    }
     
     tracing::debug!(
         event_type = %event,
@@ -179,26 +507,72 @@ pub fn transform_params(params: HashMap<String, String>) -> AnalyticsEvent {
     );
     
     AnalyticsEvent {
+        schema_version: SchemaVersion(SCHEMA_VERSION),
         project,
         event,
+        raw_event: None,
+        operation: None,
         id,
         timestamp,
+        timestamp_unit,
+        received_at: None,
+        sent_at: params.get("sent_at").and_then(|t| parse_timestamp(t)),
+        expires_at: None,
+        revenue: params.get(commerce::REVENUE_PARAM).and_then(|r| parse_revenue(r).ok()),
+        revenue_currency: params.get(commerce::CURRENCY_PARAM).and_then(|c| parse_currency(c).ok()),
+        quantity: params.get(commerce::QUANTITY_PARAM).and_then(|q| parse_quantity(q).ok()),
         session_properties,
         project_properties,
+        session: nested.session,
+        project_props: nested.project,
         visit,
         event_param,
         profile,
+        profile_operations,
+        objects,
+        campaign: None,
+        click_ids: None,
+        context: extract_context(&params),
         // Enriched fields are initially None, will be populated by enrichment pipeline
         browser: None,
         browser_version: None,
         os: None,
         os_version: None,
+        os_family: None,
+        os_major: None,
         device: None,
+        device_brand: None,
+        device_model: None,
+        in_app_browser: None,
+        is_headless: None,
+        bot_name: None,
+        bot_category: None,
         country: None,
+        country_code: None,
+        continent_code: None,
         region: None,
         city: None,
+        postal_code: None,
         latitude: None,
         longitude: None,
+        accuracy_radius: None,
+        network: None,
+        timezone: None,
+        utc_offset_minutes: None,
+        local_hour: None,
+        local_day_of_week: None,
+        currency: None,
+        is_eu: None,
+        geohash: None,
+        ip_hash: None,
+        fingerprint: None,
+        session_id: None,
+        session_start: None,
+        session_event_index: None,
+        attributes: HashMap::new(),
+        warnings,
+        provenance: BTreeMap::new(),
+        meta: None,
     }
 }
 
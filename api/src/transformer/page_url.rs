@@ -0,0 +1,162 @@
+// Page URL decomposition
+// This module splits the visit URL into normalized components and builds its canonical form
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::{form_urlencoded, Url};
+
+use crate::config::CanonicalUrlConfig;
+use crate::filter::EventNamePattern;
+
+/// Normalized components of the page URL
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct UrlParts {
+    /// Lowercase scheme (e.g. "https")
+    pub scheme: String,
+    /// Lowercase host without port (e.g. "example.com")
+    pub host: Option<String>,
+    /// Explicit non-default port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Percent-encoded path (e.g. "/products/shoes")
+    pub path: String,
+    /// Decoded query parameters; the last value wins for repeated keys
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    /// Fragment without the leading "#"
+    pub fragment: Option<String>,
+}
+
+/// Split `url` into normalized components
+///
+/// Returns `None` if the value is not an absolute URL.
+pub fn parse_url(url: &str) -> Option<UrlParts> {
+    let parsed = match Url::parse(url.trim()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            tracing::debug!(url = %url, error = %e, "Visit URL could not be parsed");
+            return None;
+        }
+    };
+
+    Some(UrlParts {
+        scheme: parsed.scheme().to_string(),
+        host: parsed.host_str().map(str::to_string),
+        port: parsed.port(),
+        path: parsed.path().to_string(),
+        query: parsed.query_pairs().into_owned().collect(),
+        fragment: parsed.fragment().map(str::to_string),
+    })
+}
+
+/// Canonical form of `url` (`parameters.canonical_url`)
+///
+/// `HTTPS://Shop.Example.com:443/shoes/?utm_source=mail&color=red#top` becomes
+/// `https://shop.example.com/shoes?color=red` with the default settings. The path keeps
+/// its case and the remaining query parameters keep their order and encoding.
+///
+/// Returns `None` if the value is not an absolute URL with a host.
+pub fn canonical_url(url: &str, config: &CanonicalUrlConfig) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    let host = parsed.host_str()?.to_ascii_lowercase();
+    let strip: Vec<EventNamePattern> = config.strip_params.iter().map(|name| EventNamePattern::new(name)).collect();
+
+    // The url crate already lowercases the scheme and drops default ports
+    let mut canonical = format!("{}://{}", parsed.scheme(), host);
+    if let Some(port) = parsed.port() {
+        canonical.push(':');
+        canonical.push_str(&port.to_string());
+    }
+    canonical.push_str(parsed.path().trim_end_matches('/'));
+
+    let query: Vec<&str> = parsed
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let stripped = form_urlencoded::parse(pair.as_bytes())
+                .next()
+                .is_some_and(|(name, _)| strip.iter().any(|pattern| pattern.matches(&name)));
+            !pair.is_empty() && !stripped
+        })
+        .collect();
+    if !query.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&query.join("&"));
+    }
+
+    if config.keep_fragment {
+        if let Some(fragment) = parsed.fragment().filter(|fragment| !fragment.is_empty()) {
+            canonical.push('#');
+            canonical.push_str(fragment);
+        }
+    }
+    Some(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_components() {
+        let parts = parse_url("HTTPS://Shop.Example.com:8443/products/shoes?color=red&size=42#reviews").unwrap();
+        assert_eq!(parts.scheme, "https");
+        assert_eq!(parts.host.as_deref(), Some("shop.example.com"));
+        assert_eq!(parts.port, Some(8443));
+        assert_eq!(parts.path, "/products/shoes");
+        assert_eq!(parts.query.get("color").map(String::as_str), Some("red"));
+        assert_eq!(parts.query.get("size").map(String::as_str), Some("42"));
+        assert_eq!(parts.fragment.as_deref(), Some("reviews"));
+    }
+
+    #[test]
+    fn test_parse_url_defaults() {
+        let parts = parse_url("https://example.com").unwrap();
+        assert_eq!(parts.port, None);
+        assert_eq!(parts.path, "/");
+        assert!(parts.query.is_empty());
+        assert_eq!(parts.fragment, None);
+    }
+
+    #[test]
+    fn test_parse_url_decodes_query() {
+        let parts = parse_url("https://example.com/search?q=caf%C3%A9+au+lait").unwrap();
+        assert_eq!(parts.query.get("q").map(String::as_str), Some("café au lait"));
+    }
+
+    #[test]
+    fn test_parse_url_invalid() {
+        assert_eq!(parse_url("not a url"), None);
+        assert_eq!(parse_url("/relative/path"), None);
+        assert_eq!(parse_url(""), None);
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let config = CanonicalUrlConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            canonical_url("HTTPS://Shop.Example.com:443/Shoes/?utm_source=mail&color=red&GCLID=abc#top", &config).as_deref(),
+            Some("https://shop.example.com/Shoes?color=red")
+        );
+        assert_eq!(
+            canonical_url("http://user:pw@example.com:8080/?q=a%20b&fbclid=x", &config).as_deref(),
+            Some("http://example.com:8080?q=a%20b")
+        );
+        assert_eq!(canonical_url("https://example.com/", &config).as_deref(), Some("https://example.com"));
+        assert_eq!(canonical_url("/relative", &config), None);
+
+        let config = CanonicalUrlConfig {
+            strip_params: vec!["ref".to_string()],
+            keep_fragment: true,
+            ..config
+        };
+        assert_eq!(
+            canonical_url("https://example.com/app/#/cart?ref=x", &config).as_deref(),
+            Some("https://example.com/app#/cart?ref=x")
+        );
+    }
+}
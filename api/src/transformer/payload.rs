@@ -0,0 +1,190 @@
+// Encoded payload parameter
+// This module decodes the base64(JSON) `payload` parameter into ordinary request parameters
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::config::PayloadConfig;
+
+/// Name of the parameter carrying the encoded payload
+pub const PAYLOAD_PARAM: &str = "payload";
+
+/// Error returned when the `payload` parameter cannot be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadError {
+    /// The encoded payload is longer than `max_bytes`
+    TooLarge { len: usize, max: usize },
+    /// The payload is not valid base64
+    InvalidBase64,
+    /// The decoded payload is not valid JSON
+    InvalidJson(String),
+    /// The decoded JSON is not an object
+    NotAnObject,
+    /// The object holds more than `max_params` members
+    TooManyParams { count: usize, max: usize },
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::TooLarge { len, max } => write!(f, "{} bytes exceeds the limit of {}", len, max),
+            PayloadError::InvalidBase64 => write!(f, "not valid base64"),
+            PayloadError::InvalidJson(e) => write!(f, "not valid JSON: {}", e),
+            PayloadError::NotAnObject => write!(f, "not a JSON object"),
+            PayloadError::TooManyParams { count, max } => {
+                write!(f, "{} parameters exceeds the limit of {}", count, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {}
+
+/// Decode a base64(JSON object) payload into parameters
+///
+/// Both the standard and URL-safe alphabets are accepted, with or without padding;
+/// spaces are read as `+`, which form encoding turns them into. Strings are kept,
+/// numbers and booleans become their JSON text, objects and arrays their compact JSON
+/// (see `parameters.parse_json`) and null members are skipped.
+pub fn decode_payload(raw: &str, config: &PayloadConfig) -> Result<HashMap<String, String>, PayloadError> {
+    let raw = raw.trim();
+    if raw.len() > config.max_bytes {
+        return Err(PayloadError::TooLarge {
+            len: raw.len(),
+            max: config.max_bytes,
+        });
+    }
+    let normalized: String = raw
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            ' ' | '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    let bytes = STANDARD_NO_PAD
+        .decode(normalized)
+        .map_err(|_| PayloadError::InvalidBase64)?;
+    let value: Value = serde_json::from_slice(&bytes).map_err(|e| PayloadError::InvalidJson(e.to_string()))?;
+    let Value::Object(members) = value else {
+        return Err(PayloadError::NotAnObject);
+    };
+    if members.len() > config.max_params {
+        return Err(PayloadError::TooManyParams {
+            count: members.len(),
+            max: config.max_params,
+        });
+    }
+
+    Ok(members
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::Null => return None,
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            Some((key, value))
+        })
+        .collect())
+}
+
+/// Replace the `payload` parameter with the parameters it encodes
+///
+/// Parameters sent directly win over payload members of the same name. Does nothing
+/// when `parameters.payload` is disabled or no payload was sent.
+pub fn merge_payload(params: &mut HashMap<String, String>, config: &PayloadConfig) -> Result<(), PayloadError> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(raw) = params.remove(PAYLOAD_PARAM) else {
+        return Ok(());
+    };
+    for (key, value) in decode_payload(&raw, config)? {
+        if key != PAYLOAD_PARAM {
+            params.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+
+    fn config() -> PayloadConfig {
+        PayloadConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_decode_payload_values() {
+        let raw = STANDARD.encode(r#"{"event":"purchase","e_price":19.99,"e_gift":true,"e_items":[{"sku":"a"}],"e_none":null}"#);
+        let params = decode_payload(&raw, &config()).unwrap();
+        assert_eq!(params.len(), 4);
+        assert_eq!(params["event"], "purchase");
+        assert_eq!(params["e_price"], "19.99");
+        assert_eq!(params["e_gift"], "true");
+        assert_eq!(params["e_items"], r#"[{"sku":"a"}]"#);
+    }
+
+    #[test]
+    fn test_decode_payload_alphabets() {
+        let json = r#"{"title":"a>b?"}"#;
+        let url_safe = URL_SAFE_NO_PAD.encode(json);
+        assert_eq!(decode_payload(&url_safe, &config()).unwrap()["title"], "a>b?");
+        let form_decoded = STANDARD.encode(json).replace('+', " ");
+        assert_eq!(decode_payload(&form_decoded, &config()).unwrap()["title"], "a>b?");
+    }
+
+    #[test]
+    fn test_decode_payload_errors() {
+        let limited = PayloadConfig {
+            enabled: true,
+            max_bytes: 8,
+            max_params: 1,
+        };
+        assert_eq!(
+            decode_payload(&STANDARD.encode(r#"{"a":"1"}"#), &limited),
+            Err(PayloadError::TooLarge { len: 12, max: 8 })
+        );
+        assert_eq!(decode_payload("not base64!", &config()), Err(PayloadError::InvalidBase64));
+        assert_eq!(decode_payload(&STANDARD.encode("[1]"), &config()), Err(PayloadError::NotAnObject));
+        assert!(matches!(
+            decode_payload(&STANDARD.encode("{"), &config()),
+            Err(PayloadError::InvalidJson(_))
+        ));
+        let limited = PayloadConfig {
+            max_bytes: 100,
+            ..limited
+        };
+        assert_eq!(
+            decode_payload(&STANDARD.encode(r#"{"a":"1","b":"2"}"#), &limited),
+            Err(PayloadError::TooManyParams { count: 2, max: 1 })
+        );
+    }
+
+    #[test]
+    fn test_merge_payload_direct_params_win() {
+        let mut params = HashMap::from([
+            ("event".to_string(), "click".to_string()),
+            (PAYLOAD_PARAM.to_string(), STANDARD.encode(r#"{"event":"purchase","e_sku":"a"}"#)),
+        ]);
+        merge_payload(&mut params, &config()).unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params["event"], "click");
+        assert_eq!(params["e_sku"], "a");
+
+        // Disabled: the parameter is left untouched
+        let mut params = HashMap::from([(PAYLOAD_PARAM.to_string(), "x".to_string())]);
+        merge_payload(&mut params, &PayloadConfig::default()).unwrap();
+        assert_eq!(params[PAYLOAD_PARAM], "x");
+    }
+}
@@ -0,0 +1,80 @@
+// Pluggable transformation
+// This module lets applications embedding the crate replace or extend how parameters become events
+
+use std::collections::HashMap;
+
+use super::repeated::RepeatedParams;
+use super::{transform_params_with_repeated, AnalyticsEvent};
+use crate::config::ParameterConfig;
+
+/// Parameter transformation used by the handlers
+///
+/// Implement `transform` to replace the built-in transformation of /track/ and
+/// /identify requests, or only `post_process` to extend it. Install the implementation
+/// with `AppState::with_transformer`.
+pub trait Transformer: Send + Sync {
+    /// Build the event from the request parameters of /track/ or /identify
+    ///
+    /// The default implementation is `transform_params_with_repeated`.
+    fn transform(
+        &self,
+        params: HashMap<String, String>,
+        repeated: &RepeatedParams,
+        config: &ParameterConfig,
+    ) -> AnalyticsEvent {
+        transform_params_with_repeated(params, repeated, config)
+    }
+
+    /// Adjust an event once it is built, before enrichment (every endpoint, /update included)
+    ///
+    /// `params` are the request parameters the event was built from. Does nothing by default.
+    fn post_process(&self, _event: &mut AnalyticsEvent, _params: &HashMap<String, String>) {}
+}
+
+/// The built-in transformation, without post-processing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTransformer;
+
+impl Transformer for DefaultTransformer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tagging;
+
+    impl Transformer for Tagging {
+        fn post_process(&self, event: &mut AnalyticsEvent, params: &HashMap<String, String>) {
+            if let Some(tenant) = params.get("x_tenant") {
+                event.attributes.insert("tenant".to_string(), serde_json::json!(tenant));
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_transform_matches_built_in() {
+        let params = HashMap::from([
+            ("event".to_string(), "pageview".to_string()),
+            ("timestamp".to_string(), "1700000000000".to_string()),
+            ("e_plan".to_string(), "pro".to_string()),
+        ]);
+        let config = ParameterConfig::default();
+        let repeated = RepeatedParams::new();
+        assert_eq!(
+            DefaultTransformer.transform(params.clone(), &repeated, &config),
+            transform_params_with_repeated(params, &repeated, &config)
+        );
+    }
+
+    #[test]
+    fn test_post_process_extends_default_transform() {
+        let params = HashMap::from([
+            ("event".to_string(), "pageview".to_string()),
+            ("x_tenant".to_string(), "acme".to_string()),
+        ]);
+        let mut event = Tagging.transform(params.clone(), &RepeatedParams::new(), &ParameterConfig::default());
+        Tagging.post_process(&mut event, &params);
+        assert_eq!(event.event, "pageview");
+        assert_eq!(event.attributes["tenant"], "acme");
+    }
+}
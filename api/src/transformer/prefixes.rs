@@ -0,0 +1,135 @@
+// Parameter prefix routing
+// This module groups prefixed parameters (e_*, u_*, s_*, p_* and configured prefixes) by their target
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::limits::{limit_entries, PrefixedEntry};
+use super::values::collect_values;
+use crate::config::{ParameterConfig, PrefixRule, PrefixTarget};
+
+/// Prefixed parameters grouped by target, prefixes removed
+#[derive(Debug, Default)]
+pub struct PrefixedParams {
+    /// Values for the `event_param` object
+    pub event_params: HashMap<String, Value>,
+    /// Values for the `profile` object
+    pub profile: HashMap<String, Value>,
+    /// Root-level session properties
+    pub session: HashMap<String, String>,
+    /// Root-level project properties
+    pub project: HashMap<String, String>,
+    /// Custom objects by name
+    pub objects: HashMap<String, HashMap<String, Value>>,
+    /// Parameters dropped or truncated by `parameters.limits`
+    pub warnings: Vec<String>,
+}
+
+/// Rule with the longest prefix matching `key`
+///
+/// A parameter that is exactly the prefix (no name after it) matches no rule.
+pub fn match_rule<'a>(rules: &'a [PrefixRule], key: &str) -> Option<&'a PrefixRule> {
+    rules
+        .iter()
+        .filter(|rule| key.len() > rule.prefix.len() && key.starts_with(&rule.prefix))
+        .max_by_key(|rule| rule.prefix.len())
+}
+
+/// Route every prefixed parameter to its target according to `parameters.prefixes`,
+/// enforcing `parameters.limits` per target
+pub fn split_prefixed(params: &HashMap<String, String>, config: &ParameterConfig) -> PrefixedParams {
+    let rules = config.prefix_rules();
+    let mut grouped: HashMap<&PrefixTarget, Vec<PrefixedEntry<'_>>> = HashMap::new();
+    for (key, raw) in params {
+        if let Some(rule) = match_rule(&rules, key) {
+            grouped
+                .entry(&rule.target)
+                .or_default()
+                .push((key.as_str(), &key[rule.prefix.len()..], raw.as_str()));
+        }
+    }
+
+    let mut prefixed = PrefixedParams::default();
+    for (target, entries) in grouped {
+        let entries = limit_entries(entries, &config.limits, &mut prefixed.warnings);
+        let strings = || entries.iter().map(|(key, raw)| (key.to_string(), raw.to_string()));
+        match target {
+            PrefixTarget::EventParam => prefixed.event_params = collect_values(entries.iter().copied(), config),
+            PrefixTarget::Profile => prefixed.profile = collect_values(entries.iter().copied(), config),
+            PrefixTarget::Session => prefixed.session = strings().collect(),
+            PrefixTarget::Project => prefixed.project = strings().collect(),
+            PrefixTarget::Object(name) => {
+                prefixed
+                    .objects
+                    .insert(name.clone(), collect_values(entries.iter().copied(), config));
+            }
+        }
+    }
+    prefixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ParameterLimits;
+    use serde_json::json;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn rule(prefix: &str, target: &str) -> PrefixRule {
+        PrefixRule {
+            prefix: prefix.to_string(),
+            target: PrefixTarget::from(target.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_builtin_prefixes() {
+        let prefixed = split_prefixed(
+            &params(&[("e_a", "1"), ("u_b", "2"), ("s_c", "3"), ("p_d", "4"), ("m_e", "5"), ("e_", "x")]),
+            &ParameterConfig::default(),
+        );
+        assert_eq!(prefixed.event_params, HashMap::from([("a".to_string(), json!("1"))]));
+        assert_eq!(prefixed.profile, HashMap::from([("b".to_string(), json!("2"))]));
+        assert_eq!(prefixed.session, HashMap::from([("c".to_string(), "3".to_string())]));
+        assert_eq!(prefixed.project, HashMap::from([("d".to_string(), "4".to_string())]));
+        assert!(prefixed.objects.is_empty());
+    }
+
+    #[test]
+    fn test_custom_prefixes_and_overrides() {
+        let config = ParameterConfig {
+            prefixes: vec![rule("m_", "marketing"), rule("e_", "properties"), rule("ev_", "event_param")],
+            ..Default::default()
+        };
+        let prefixed = split_prefixed(&params(&[("m_channel", "social"), ("e_a", "1"), ("ev_b", "2")]), &config);
+        assert_eq!(prefixed.objects["marketing"], HashMap::from([("channel".to_string(), json!("social"))]));
+        assert_eq!(prefixed.objects["properties"], HashMap::from([("a".to_string(), json!("1"))]));
+        assert_eq!(prefixed.event_params, HashMap::from([("b".to_string(), json!("2"))]));
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let rules = vec![rule("e_", "event_param"), rule("e_mkt_", "marketing")];
+        assert_eq!(match_rule(&rules, "e_mkt_source").map(|r| r.prefix.as_str()), Some("e_mkt_"));
+        assert_eq!(match_rule(&rules, "e_source").map(|r| r.prefix.as_str()), Some("e_"));
+        assert!(match_rule(&rules, "source").is_none());
+    }
+
+    #[test]
+    fn test_limits_are_reported() {
+        let config = ParameterConfig {
+            limits: ParameterLimits {
+                max_value_length: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let prefixed = split_prefixed(&params(&[("e_a", "1234"), ("s_b", "ok")]), &config);
+        assert_eq!(prefixed.event_params.get("a"), Some(&json!("123")));
+        assert_eq!(prefixed.session.get("b").map(String::as_str), Some("ok"));
+        assert_eq!(prefixed.warnings, vec!["parameter e_a truncated to 3 bytes".to_string()]);
+    }
+}
@@ -0,0 +1,139 @@
+// Profile update operations
+// This module turns operation-prefixed profile properties (u_set_once_plan, u_unset_phone) into merge semantics
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Property name prefix of an explicit `$set` (`u_set_plan=pro`)
+pub const SET_PREFIX: &str = "set_";
+/// Property name prefix of a `$set_once` (`u_set_once_plan=free`)
+pub const SET_ONCE_PREFIX: &str = "set_once_";
+/// Property name prefix of an `$unset` (`u_unset_phone=1`, the value is ignored)
+pub const UNSET_PREFIX: &str = "unset_";
+
+/// How a profile store should merge the event's profile properties
+///
+/// Properties in `profile` are set (`$set`), except those listed in `set_once`, which
+/// are only set when the stored profile does not have them yet. Properties in `unset`
+/// are removed from the stored profile.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ProfileOperations {
+    /// Properties to set only when missing from the stored profile (`$set_once`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set_once: Vec<String>,
+    /// Properties to remove from the stored profile (`$unset`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unset: Vec<String>,
+}
+
+impl ProfileOperations {
+    /// Whether no property is listed
+    pub fn is_empty(&self) -> bool {
+        self.set_once.is_empty() && self.unset.is_empty()
+    }
+}
+
+/// Resolve operation-prefixed profile properties
+///
+/// `set_<name>` is stored as `<name>`, winning over a plain property of that name.
+/// `set_once_<name>` is stored as `<name>` and listed in `set_once`, unless the
+/// property is also set. `unset_<name>` removes `<name>` and lists it in `unset`,
+/// winning over any set.
+///
+/// # Returns
+/// The operations, or `None` when no property used an operation prefix
+pub fn extract_profile_operations(properties: &mut HashMap<String, Value>) -> Option<ProfileOperations> {
+    let mut set = Vec::new();
+    let mut set_once = Vec::new();
+    let mut unset = Vec::new();
+    let keys: Vec<String> = properties.keys().cloned().collect();
+    for key in keys {
+        // set_once_ is checked first, as it also starts with set_
+        let (target, name) = if let Some(name) = key.strip_prefix(SET_ONCE_PREFIX) {
+            (&mut set_once, name)
+        } else if let Some(name) = key.strip_prefix(SET_PREFIX) {
+            (&mut set, name)
+        } else if let Some(name) = key.strip_prefix(UNSET_PREFIX) {
+            (&mut unset, name)
+        } else {
+            continue;
+        };
+        if name.is_empty() {
+            continue;
+        }
+        if let Some(value) = properties.remove(&key) {
+            target.push((name.to_string(), value));
+        }
+    }
+    if set.is_empty() && set_once.is_empty() && unset.is_empty() {
+        return None;
+    }
+
+    for (name, value) in set {
+        properties.insert(name, value);
+    }
+    let mut operations = ProfileOperations::default();
+    for (name, value) in set_once {
+        if !properties.contains_key(&name) {
+            properties.insert(name.clone(), value);
+            operations.set_once.push(name);
+        }
+    }
+    for (name, _) in unset {
+        properties.remove(&name);
+        operations.set_once.retain(|set_once| *set_once != name);
+        operations.unset.push(name);
+    }
+    operations.set_once.sort_unstable();
+    operations.unset.sort_unstable();
+    operations.unset.dedup();
+    Some(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn properties(pairs: &[(&str, &str)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), json!(v))).collect()
+    }
+
+    #[test]
+    fn test_extracts_operations() {
+        let mut props = properties(&[
+            ("name", "Ann"),
+            ("set_once_plan", "free"),
+            ("set_once_first_seen", "2024-01-01"),
+            ("set_tier", "gold"),
+            ("tier", "silver"),
+            ("unset_phone", "1"),
+        ]);
+        let operations = extract_profile_operations(&mut props).unwrap();
+
+        assert_eq!(operations.set_once, vec!["first_seen", "plan"]);
+        assert_eq!(operations.unset, vec!["phone"]);
+        assert_eq!(
+            props,
+            properties(&[("name", "Ann"), ("plan", "free"), ("first_seen", "2024-01-01"), ("tier", "gold")])
+        );
+    }
+
+    #[test]
+    fn test_set_and_unset_win_over_set_once() {
+        let mut props = properties(&[("plan", "pro"), ("set_once_plan", "free"), ("set_once_phone", "x"), ("unset_phone", "1")]);
+        let operations = extract_profile_operations(&mut props).unwrap();
+
+        assert!(operations.set_once.is_empty());
+        assert_eq!(operations.unset, vec!["phone"]);
+        assert_eq!(props, properties(&[("plan", "pro")]));
+    }
+
+    #[test]
+    fn test_plain_properties_have_no_operations() {
+        let mut props = properties(&[("plan", "pro"), ("settings", "dark"), ("set_", "x")]);
+        assert_eq!(extract_profile_operations(&mut props), None);
+        assert_eq!(props.len(), 3);
+    }
+}
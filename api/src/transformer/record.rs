@@ -0,0 +1,690 @@
+// Columnar event record
+// This module maps AnalyticsEvent onto a fixed, fully typed column layout for Parquet and Avro
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::AnalyticsEvent;
+use ColumnType::*;
+
+/// Type of a record column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Boolean,
+    Int32,
+    Int64,
+    Double,
+    /// UTF-8 string
+    Text,
+    /// List of strings
+    StringList,
+    /// Map from string keys to strings
+    StringMap,
+    /// Map from string keys to doubles
+    DoubleMap,
+    /// Map from string keys to booleans
+    BooleanMap,
+    /// Map from object name to a map of strings (custom prefix objects)
+    NestedStringMap,
+}
+
+/// A column of the event record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub column_type: ColumnType,
+    /// Whether the column may be null; map and list columns are empty instead
+    pub nullable: bool,
+}
+
+const fn required(name: &'static str, column_type: ColumnType) -> Column {
+    Column { name, column_type, nullable: false }
+}
+
+const fn optional(name: &'static str, column_type: ColumnType) -> Column {
+    Column { name, column_type, nullable: true }
+}
+
+/// Columns of `EventRecord`, in record order
+pub const RECORD_COLUMNS: &[Column] = &[
+    required("schema_version", Int32),
+    optional("project", Text),
+    required("event", Text),
+    optional("raw_event", Text),
+    optional("operation", Text),
+    optional("id", Text),
+    required("timestamp", Int64),
+    optional("timestamp_unit", Text),
+    optional("received_at", Int64),
+    optional("sent_at", Int64),
+    optional("expires_at", Int64),
+    optional("revenue", Double),
+    optional("revenue_currency", Text),
+    optional("quantity", Int64),
+    optional("visit_cookie", Text),
+    optional("visit_timestamp", Int64),
+    optional("visit_url", Text),
+    optional("visit_canonical_url", Text),
+    optional("visit_url_scheme", Text),
+    optional("visit_url_host", Text),
+    optional("visit_url_port", Int32),
+    optional("visit_url_path", Text),
+    required("visit_url_query", StringMap),
+    optional("visit_url_fragment", Text),
+    optional("visit_title", Text),
+    optional("visit_domain", Text),
+    optional("visit_uri", Text),
+    optional("visit_duration", Int64),
+    optional("visit_scroll_depth", Int32),
+    optional("visit_screen", Text),
+    optional("visit_screen_width", Int32),
+    optional("visit_screen_height", Int32),
+    optional("visit_pixel_ratio", Double),
+    optional("visit_viewport_bucket", Text),
+    optional("visit_language", Text),
+    optional("visit_referer", Text),
+    optional("visit_app", Text),
+    required("event_param_string", StringMap),
+    required("event_param_number", DoubleMap),
+    required("event_param_boolean", BooleanMap),
+    required("event_param_json", StringMap),
+    required("profile_string", StringMap),
+    required("profile_number", DoubleMap),
+    required("profile_boolean", BooleanMap),
+    required("profile_json", StringMap),
+    optional("profile_email_domain", Text),
+    optional("profile_email_free_provider", Boolean),
+    optional("profile_email_valid", Boolean),
+    required("profile_set_once", StringList),
+    required("profile_unset", StringList),
+    required("session_properties", StringMap),
+    required("project_properties", StringMap),
+    required("objects", NestedStringMap),
+    optional("campaign_source", Text),
+    optional("campaign_medium", Text),
+    optional("campaign_campaign", Text),
+    optional("campaign_term", Text),
+    optional("campaign_content", Text),
+    required("click_ids", StringMap),
+    optional("context_app_name", Text),
+    optional("context_app_version", Text),
+    optional("context_app_build", Text),
+    optional("context_sdk_name", Text),
+    optional("context_sdk_version", Text),
+    optional("context_device_model", Text),
+    required("context_extra", StringMap),
+    optional("browser", Text),
+    optional("browser_version", Text),
+    optional("os", Text),
+    optional("os_version", Text),
+    optional("os_family", Text),
+    optional("os_major", Text),
+    optional("device", Text),
+    optional("device_brand", Text),
+    optional("device_model", Text),
+    optional("in_app_browser", Text),
+    optional("is_headless", Boolean),
+    optional("bot_name", Text),
+    optional("bot_category", Text),
+    optional("country", Text),
+    optional("country_code", Text),
+    optional("continent_code", Text),
+    optional("region", Text),
+    optional("city", Text),
+    optional("postal_code", Text),
+    optional("latitude", Double),
+    optional("longitude", Double),
+    optional("accuracy_radius", Int32),
+    optional("network_isp", Text),
+    optional("network_organization", Text),
+    optional("network_asn", Int64),
+    optional("network_as_organization", Text),
+    optional("network_connection_type", Text),
+    optional("network_is_anonymous", Boolean),
+    optional("network_is_anonymous_vpn", Boolean),
+    optional("network_is_hosting_provider", Boolean),
+    optional("network_is_public_proxy", Boolean),
+    optional("network_is_residential_proxy", Boolean),
+    optional("network_is_tor_exit_node", Boolean),
+    optional("network_is_internal", Boolean),
+    optional("timezone", Text),
+    optional("utc_offset_minutes", Int32),
+    optional("local_hour", Int32),
+    optional("local_day_of_week", Int32),
+    optional("currency", Text),
+    optional("is_eu", Boolean),
+    optional("geohash", Text),
+    optional("ip_hash", Text),
+    optional("fingerprint", Text),
+    optional("session_id", Text),
+    optional("session_start", Boolean),
+    optional("session_event_index", Int64),
+    required("attributes_string", StringMap),
+    required("attributes_number", DoubleMap),
+    required("attributes_boolean", BooleanMap),
+    required("attributes_json", StringMap),
+    required("warnings", StringList),
+    required("provenance", StringMap),
+    optional("meta_hostname", Text),
+    optional("meta_region", Text),
+    optional("meta_pipeline_version", Text),
+    optional("meta_ingested_at", Int64),
+];
+
+/// Custom property values split by type into one map column per type
+///
+/// Objects and arrays are stored as JSON text; nulls are dropped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypedProperties {
+    pub strings: BTreeMap<String, String>,
+    pub numbers: BTreeMap<String, f64>,
+    pub booleans: BTreeMap<String, bool>,
+    pub json: BTreeMap<String, String>,
+}
+
+impl TypedProperties {
+    /// Split `properties` by value type
+    pub fn from_values<'a, I>(properties: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a String, &'a Value)>,
+    {
+        let mut typed = TypedProperties::default();
+        for (key, value) in properties {
+            match value {
+                Value::Null => {}
+                Value::String(s) => {
+                    typed.strings.insert(key.clone(), s.clone());
+                }
+                Value::Bool(b) => {
+                    typed.booleans.insert(key.clone(), *b);
+                }
+                Value::Number(n) => {
+                    if let Some(n) = n.as_f64() {
+                        typed.numbers.insert(key.clone(), n);
+                    }
+                }
+                Value::Array(_) | Value::Object(_) => {
+                    typed.json.insert(key.clone(), value.to_string());
+                }
+            }
+        }
+        typed
+    }
+}
+
+/// AnalyticsEvent as a fixed, fully typed row
+///
+/// Every column in `RECORD_COLUMNS` is always present, nested objects are spread into
+/// prefixed columns and custom properties live in typed map columns, so the layout
+/// does not depend on the parameters an event carried. Session and project properties
+/// moved off the root by `parameters.root_collisions: nest` are merged back into
+/// their map columns, where they cannot collide.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EventRecord {
+    pub schema_version: i32,
+    pub project: Option<String>,
+    pub event: String,
+    pub raw_event: Option<String>,
+    pub operation: Option<String>,
+    pub id: Option<String>,
+    pub timestamp: i64,
+    pub timestamp_unit: Option<String>,
+    pub received_at: Option<i64>,
+    pub sent_at: Option<i64>,
+    pub expires_at: Option<i64>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub revenue: Option<rust_decimal::Decimal>,
+    pub revenue_currency: Option<String>,
+    pub quantity: Option<i64>,
+    pub visit_cookie: Option<String>,
+    pub visit_timestamp: Option<i64>,
+    pub visit_url: Option<String>,
+    pub visit_canonical_url: Option<String>,
+    pub visit_url_scheme: Option<String>,
+    pub visit_url_host: Option<String>,
+    pub visit_url_port: Option<i32>,
+    pub visit_url_path: Option<String>,
+    pub visit_url_query: BTreeMap<String, String>,
+    pub visit_url_fragment: Option<String>,
+    pub visit_title: Option<String>,
+    pub visit_domain: Option<String>,
+    pub visit_uri: Option<String>,
+    pub visit_duration: Option<i64>,
+    pub visit_scroll_depth: Option<i32>,
+    pub visit_screen: Option<String>,
+    pub visit_screen_width: Option<i32>,
+    pub visit_screen_height: Option<i32>,
+    pub visit_pixel_ratio: Option<f64>,
+    pub visit_viewport_bucket: Option<String>,
+    pub visit_language: Option<String>,
+    pub visit_referer: Option<String>,
+    pub visit_app: Option<String>,
+    pub event_param_string: BTreeMap<String, String>,
+    pub event_param_number: BTreeMap<String, f64>,
+    pub event_param_boolean: BTreeMap<String, bool>,
+    pub event_param_json: BTreeMap<String, String>,
+    pub profile_string: BTreeMap<String, String>,
+    pub profile_number: BTreeMap<String, f64>,
+    pub profile_boolean: BTreeMap<String, bool>,
+    pub profile_json: BTreeMap<String, String>,
+    pub profile_email_domain: Option<String>,
+    pub profile_email_free_provider: Option<bool>,
+    pub profile_email_valid: Option<bool>,
+    pub profile_set_once: Vec<String>,
+    pub profile_unset: Vec<String>,
+    pub session_properties: BTreeMap<String, String>,
+    pub project_properties: BTreeMap<String, String>,
+    pub objects: BTreeMap<String, BTreeMap<String, String>>,
+    pub campaign_source: Option<String>,
+    pub campaign_medium: Option<String>,
+    pub campaign_campaign: Option<String>,
+    pub campaign_term: Option<String>,
+    pub campaign_content: Option<String>,
+    pub click_ids: BTreeMap<String, String>,
+    pub context_app_name: Option<String>,
+    pub context_app_version: Option<String>,
+    pub context_app_build: Option<String>,
+    pub context_sdk_name: Option<String>,
+    pub context_sdk_version: Option<String>,
+    pub context_device_model: Option<String>,
+    pub context_extra: BTreeMap<String, String>,
+    pub browser: Option<String>,
+    pub browser_version: Option<String>,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub os_family: Option<String>,
+    pub os_major: Option<String>,
+    pub device: Option<String>,
+    pub device_brand: Option<String>,
+    pub device_model: Option<String>,
+    pub in_app_browser: Option<String>,
+    pub is_headless: Option<bool>,
+    pub bot_name: Option<String>,
+    pub bot_category: Option<String>,
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub continent_code: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub accuracy_radius: Option<i32>,
+    pub network_isp: Option<String>,
+    pub network_organization: Option<String>,
+    pub network_asn: Option<i64>,
+    pub network_as_organization: Option<String>,
+    pub network_connection_type: Option<String>,
+    pub network_is_anonymous: Option<bool>,
+    pub network_is_anonymous_vpn: Option<bool>,
+    pub network_is_hosting_provider: Option<bool>,
+    pub network_is_public_proxy: Option<bool>,
+    pub network_is_residential_proxy: Option<bool>,
+    pub network_is_tor_exit_node: Option<bool>,
+    pub network_is_internal: Option<bool>,
+    pub timezone: Option<String>,
+    pub utc_offset_minutes: Option<i32>,
+    pub local_hour: Option<i32>,
+    pub local_day_of_week: Option<i32>,
+    pub currency: Option<String>,
+    pub is_eu: Option<bool>,
+    pub geohash: Option<String>,
+    pub ip_hash: Option<String>,
+    pub fingerprint: Option<String>,
+    pub session_id: Option<String>,
+    pub session_start: Option<bool>,
+    pub session_event_index: Option<i64>,
+    pub attributes_string: BTreeMap<String, String>,
+    pub attributes_number: BTreeMap<String, f64>,
+    pub attributes_boolean: BTreeMap<String, bool>,
+    pub attributes_json: BTreeMap<String, String>,
+    pub warnings: Vec<String>,
+    pub provenance: BTreeMap<String, String>,
+    pub meta_hostname: Option<String>,
+    pub meta_region: Option<String>,
+    pub meta_pipeline_version: Option<String>,
+    pub meta_ingested_at: Option<i64>,
+}
+
+/// Sorted copy of a string map, with `extra` entries added where the key is free
+fn string_map(map: &HashMap<String, String>, extra: &HashMap<String, String>) -> BTreeMap<String, String> {
+    let mut sorted: BTreeMap<String, String> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    for (key, value) in extra {
+        sorted.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    sorted
+}
+
+/// Text form of a property value: strings as is, everything else as JSON
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn to_i32<T: TryInto<i32>>(value: Option<T>) -> Option<i32> {
+    value.and_then(|v| v.try_into().ok())
+}
+
+impl From<&AnalyticsEvent> for EventRecord {
+    fn from(event: &AnalyticsEvent) -> Self {
+        let visit = &event.visit;
+        let url_parts = visit.url_parts.as_ref();
+        let event_param = TypedProperties::from_values(event.event_param.iter().flat_map(|p| p.params.iter()));
+        let profile = TypedProperties::from_values(event.profile.iter().flat_map(|p| p.properties.iter()));
+        let attributes = TypedProperties::from_values(event.attributes.iter());
+        let campaign = event.campaign.clone().unwrap_or_default();
+        let context = event.context.clone().unwrap_or_default();
+        let network = event.network.clone().unwrap_or_default();
+        let meta = event.meta.as_ref();
+        let click_ids = match serde_json::to_value(&event.click_ids) {
+            Ok(Value::Object(ids)) => ids
+                .into_iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k, v.to_string())))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+
+        EventRecord {
+            schema_version: event.schema_version.0 as i32,
+            project: event.project.clone(),
+            event: event.event.clone(),
+            raw_event: event.raw_event.clone(),
+            operation: event.operation.map(|operation| operation.as_str().to_string()),
+            id: event.id.clone(),
+            timestamp: event.timestamp,
+            timestamp_unit: event.timestamp_unit.map(|unit| unit.as_str().to_string()),
+            received_at: event.received_at,
+            sent_at: event.sent_at,
+            expires_at: event.expires_at,
+            revenue: event.revenue,
+            revenue_currency: event.revenue_currency.clone(),
+            quantity: event.quantity,
+            visit_cookie: visit.cookie.clone(),
+            visit_timestamp: visit.timestamp,
+            visit_url: visit.url.clone(),
+            visit_canonical_url: visit.canonical_url.clone(),
+            visit_url_scheme: url_parts.map(|p| p.scheme.clone()),
+            visit_url_host: url_parts.and_then(|p| p.host.clone()),
+            visit_url_port: url_parts.and_then(|p| p.port).map(i32::from),
+            visit_url_path: url_parts.map(|p| p.path.clone()),
+            visit_url_query: url_parts
+                .map(|p| string_map(&p.query, &HashMap::new()))
+                .unwrap_or_default(),
+            visit_url_fragment: url_parts.and_then(|p| p.fragment.clone()),
+            visit_title: visit.title.clone(),
+            visit_domain: visit.domain.clone(),
+            visit_uri: visit.uri.clone(),
+            visit_duration: visit.duration,
+            visit_scroll_depth: visit.scroll_depth,
+            visit_screen: visit.screen.clone(),
+            visit_screen_width: to_i32(visit.screen_width),
+            visit_screen_height: to_i32(visit.screen_height),
+            visit_pixel_ratio: visit.pixel_ratio,
+            visit_viewport_bucket: visit.viewport_bucket.clone(),
+            visit_language: visit.language.clone(),
+            visit_referer: visit.referer.clone(),
+            visit_app: visit.app.clone(),
+            event_param_string: event_param.strings,
+            event_param_number: event_param.numbers,
+            event_param_boolean: event_param.booleans,
+            event_param_json: event_param.json,
+            profile_string: profile.strings,
+            profile_number: profile.numbers,
+            profile_boolean: profile.booleans,
+            profile_json: profile.json,
+            profile_email_domain: event.profile.as_ref().and_then(|p| p.email_domain.clone()),
+            profile_email_free_provider: event.profile.as_ref().and_then(|p| p.email_free_provider),
+            profile_email_valid: event.profile.as_ref().and_then(|p| p.email_valid),
+            profile_set_once: event.profile_operations.as_ref().map(|o| o.set_once.clone()).unwrap_or_default(),
+            profile_unset: event.profile_operations.as_ref().map(|o| o.unset.clone()).unwrap_or_default(),
+            session_properties: string_map(&event.session_properties, &event.session),
+            project_properties: string_map(&event.project_properties, &event.project_props),
+            objects: event
+                .objects
+                .iter()
+                .map(|(name, fields)| {
+                    let fields = fields.iter().map(|(k, v)| (k.clone(), value_text(v))).collect();
+                    (name.clone(), fields)
+                })
+                .collect(),
+            campaign_source: campaign.source,
+            campaign_medium: campaign.medium,
+            campaign_campaign: campaign.campaign,
+            campaign_term: campaign.term,
+            campaign_content: campaign.content,
+            click_ids,
+            context_app_name: context.app_name,
+            context_app_version: context.app_version,
+            context_app_build: context.app_build,
+            context_sdk_name: context.sdk_name,
+            context_sdk_version: context.sdk_version,
+            context_device_model: context.device_model,
+            context_extra: context.extra.into_iter().collect(),
+            browser: event.browser.clone(),
+            browser_version: event.browser_version.clone(),
+            os: event.os.clone(),
+            os_version: event.os_version.clone(),
+            os_family: event.os_family.clone(),
+            os_major: event.os_major.clone(),
+            device: event.device.clone(),
+            device_brand: event.device_brand.clone(),
+            device_model: event.device_model.clone(),
+            in_app_browser: event.in_app_browser.clone(),
+            is_headless: event.is_headless,
+            bot_name: event.bot_name.clone(),
+            bot_category: event.bot_category.clone(),
+            country: event.country.clone(),
+            country_code: event.country_code.clone(),
+            continent_code: event.continent_code.clone(),
+            region: event.region.clone(),
+            city: event.city.clone(),
+            postal_code: event.postal_code.clone(),
+            latitude: event.latitude,
+            longitude: event.longitude,
+            accuracy_radius: event.accuracy_radius.map(i32::from),
+            network_isp: network.isp,
+            network_organization: network.organization,
+            network_asn: network.asn.map(i64::from),
+            network_as_organization: network.as_organization,
+            network_connection_type: network.connection_type,
+            network_is_anonymous: network.is_anonymous,
+            network_is_anonymous_vpn: network.is_anonymous_vpn,
+            network_is_hosting_provider: network.is_hosting_provider,
+            network_is_public_proxy: network.is_public_proxy,
+            network_is_residential_proxy: network.is_residential_proxy,
+            network_is_tor_exit_node: network.is_tor_exit_node,
+            network_is_internal: network.is_internal,
+            timezone: event.timezone.clone(),
+            utc_offset_minutes: event.utc_offset_minutes,
+            local_hour: to_i32(event.local_hour),
+            local_day_of_week: to_i32(event.local_day_of_week),
+            currency: event.currency.clone(),
+            is_eu: event.is_eu,
+            geohash: event.geohash.clone(),
+            ip_hash: event.ip_hash.clone(),
+            fingerprint: event.fingerprint.clone(),
+            session_id: event.session_id.clone(),
+            session_start: event.session_start,
+            session_event_index: event.session_event_index.and_then(|i| i64::try_from(i).ok()),
+            attributes_string: attributes.strings,
+            attributes_number: attributes.numbers,
+            attributes_boolean: attributes.booleans,
+            attributes_json: attributes.json,
+            warnings: event.warnings.clone(),
+            provenance: event.provenance.clone(),
+            meta_hostname: meta.map(|m| m.hostname.clone()),
+            meta_region: meta.and_then(|m| m.region.clone()),
+            meta_pipeline_version: meta.map(|m| m.pipeline_version.clone()),
+            meta_ingested_at: meta.map(|m| m.ingested_at),
+        }
+    }
+}
+
+/// Avro type of a column (without the null union)
+fn avro_type(column_type: ColumnType) -> Value {
+    match column_type {
+        Boolean => json!("boolean"),
+        Int32 => json!("int"),
+        Int64 => json!("long"),
+        Double => json!("double"),
+        Text => json!("string"),
+        StringList => json!({"type": "array", "items": "string"}),
+        StringMap => json!({"type": "map", "values": "string"}),
+        DoubleMap => json!({"type": "map", "values": "double"}),
+        BooleanMap => json!({"type": "map", "values": "boolean"}),
+        NestedStringMap => json!({"type": "map", "values": {"type": "map", "values": "string"}}),
+    }
+}
+
+/// Avro record schema of `EventRecord`
+///
+/// Nullable columns are `["null", type]` unions defaulting to null; map and list
+/// columns default to empty.
+pub fn avro_schema() -> Value {
+    let fields: Vec<Value> = RECORD_COLUMNS
+        .iter()
+        .map(|column| {
+            let avro = avro_type(column.column_type);
+            match column.column_type {
+                _ if column.nullable => json!({"name": column.name, "type": ["null", avro], "default": null}),
+                StringList => json!({"name": column.name, "type": avro, "default": []}),
+                StringMap | DoubleMap | BooleanMap | NestedStringMap => {
+                    json!({"name": column.name, "type": avro, "default": {}})
+                }
+                _ => json!({"name": column.name, "type": avro}),
+            }
+        })
+        .collect();
+    json!({
+        "type": "record",
+        "name": "AnalyticsEvent",
+        "namespace": "penrose.analytics",
+        "fields": fields,
+    })
+}
+
+/// Parquet primitive type annotation of a scalar value type
+fn parquet_primitive(column_type: ColumnType) -> &'static str {
+    match column_type {
+        Boolean => "boolean",
+        Int32 => "int32",
+        Int64 => "int64",
+        Double => "double",
+        _ => "binary",
+    }
+}
+
+fn parquet_map(repetition: &str, name: &str, value: &str) -> String {
+    format!(
+        "{} group {} (MAP) {{ repeated group key_value {{ required binary key (STRING); {} }} }}",
+        repetition, name, value
+    )
+}
+
+/// Parquet message type of `EventRecord`, in the textual schema syntax
+pub fn parquet_schema() -> String {
+    let mut schema = String::from("message analytics_event {\n");
+    for column in RECORD_COLUMNS {
+        let repetition = if column.nullable { "optional" } else { "required" };
+        let line = match column.column_type {
+            Text => format!("{} binary {} (STRING);", repetition, column.name),
+            Boolean | Int32 | Int64 | Double => {
+                format!("{} {} {};", repetition, parquet_primitive(column.column_type), column.name)
+            }
+            StringList => format!(
+                "{} group {} (LIST) {{ repeated group list {{ required binary element (STRING); }} }}",
+                repetition, column.name
+            ),
+            StringMap => parquet_map(repetition, column.name, "required binary value (STRING);"),
+            DoubleMap => parquet_map(repetition, column.name, "required double value;"),
+            BooleanMap => parquet_map(repetition, column.name, "required boolean value;"),
+            NestedStringMap => parquet_map(
+                repetition,
+                column.name,
+                &parquet_map("required", "value", "required binary value (STRING);"),
+            ),
+        };
+        schema.push_str("  ");
+        schema.push_str(&line);
+        schema.push('\n');
+    }
+    schema.push('}');
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformer::{transform_params, ProfileObject};
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_record_matches_columns() {
+        let record = serde_json::to_value(EventRecord::default()).unwrap();
+        let record = record.as_object().unwrap();
+        assert_eq!(record.len(), RECORD_COLUMNS.len());
+        for column in RECORD_COLUMNS {
+            let value = record.get(column.name).unwrap_or_else(|| panic!("{} is not in EventRecord", column.name));
+            match column.column_type {
+                StringList => assert!(value.is_array(), "{}", column.name),
+                StringMap | DoubleMap | BooleanMap | NestedStringMap => assert!(value.is_object(), "{}", column.name),
+                _ if column.nullable => assert!(value.is_null(), "{}", column.name),
+                _ => assert!(!value.is_null(), "{}", column.name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_from_event() {
+        let mut event = transform_params(params(&[
+            ("event", "purchase"),
+            ("url", "https://shop.example.com/cart?step=2"),
+            ("e_sku", "A-1"),
+            ("s_plan", "pro"),
+        ]));
+        event.event_param.as_mut().unwrap().params.extend([
+            ("price".to_string(), json!(19.99)),
+            ("gift".to_string(), json!(true)),
+            ("items".to_string(), json!([{"sku": "A-1"}])),
+        ]);
+        event.profile = Some(ProfileObject::default());
+        event.session.insert("event".to_string(), "nested".to_string());
+
+        let record = EventRecord::from(&event);
+        assert_eq!(record.event, "purchase");
+        assert_eq!(record.visit_url_host.as_deref(), Some("shop.example.com"));
+        assert_eq!(record.visit_url_query.get("step").map(|s| s.as_str()), Some("2"));
+        assert_eq!(record.event_param_string.get("sku").map(|s| s.as_str()), Some("A-1"));
+        assert_eq!(record.event_param_number.get("price"), Some(&19.99));
+        assert_eq!(record.event_param_boolean.get("gift"), Some(&true));
+        assert_eq!(record.event_param_json.get("items").map(|s| s.as_str()), Some(r#"[{"sku":"A-1"}]"#));
+        assert_eq!(record.session_properties.len(), 2);
+        assert_eq!(record.schema_version, 1);
+        assert!(record.profile_string.is_empty());
+    }
+
+    #[test]
+    fn test_schemas_cover_every_column() {
+        let avro = avro_schema();
+        let fields = avro["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), RECORD_COLUMNS.len());
+        assert_eq!(fields[0], json!({"name": "schema_version", "type": "int"}));
+        assert_eq!(fields[1], json!({"name": "project", "type": ["null", "string"], "default": null}));
+        assert!(fields.iter().any(|f| f["name"] == "event_param_number" && f["type"]["values"] == "double"));
+
+        let parquet = parquet_schema();
+        assert_eq!(parquet.lines().count(), RECORD_COLUMNS.len() + 2);
+        assert!(parquet.contains("  required int64 timestamp;\n"));
+        assert!(parquet.contains("  optional binary project (STRING);\n"));
+        assert!(parquet.contains(
+            "  required group warnings (LIST) { repeated group list { required binary element (STRING); } }\n"
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+// Repeated parameters
+// This module turns event parameters sent more than once (e_tag=a&e_tag=b) into JSON arrays
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::limits::truncate;
+use super::prefixes::match_rule;
+use super::values::{param_value, split_typed_key, typed_value, validate_typed_params};
+use crate::config::{ParameterConfig, PrefixTarget};
+
+/// Every value of the parameters sent more than once, in request order
+pub type RepeatedParams = HashMap<String, Vec<String>>;
+
+/// Group `(name, value)` pairs by name, keeping the names that occur more than once
+pub fn collect_repeated(pairs: &[(String, String)]) -> RepeatedParams {
+    let mut grouped: RepeatedParams = HashMap::new();
+    for (key, value) in pairs {
+        grouped.entry(key.clone()).or_default().push(value.clone());
+    }
+    grouped.retain(|_, values| values.len() > 1);
+    grouped
+}
+
+/// Validate the type suffixes and every value of repeated parameters
+///
+/// Applies `validate_typed_params` to each value, so `e_n:i=1&e_n:i=x` is rejected
+/// even though the single-valued parameters only hold the last value.
+pub fn validate_repeated_params(repeated: &RepeatedParams, config: &ParameterConfig) -> Result<(), String> {
+    for (key, values) in repeated {
+        for value in values {
+            validate_typed_params(&HashMap::from([(key.clone(), value.clone())]), config)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the `event_param` values of repeated parameters with arrays of every value sent
+///
+/// Only parameters routed to `event_param` that were kept by `parameters.limits` are
+/// converted. Each value is converted like a single one (type suffix, inference, JSON)
+/// after truncation to `max_value_length`, and values past `max_repeated_values` are
+/// dropped; both cases are described in `warnings`. As in `collect_values`, a typed key
+/// wins over an untyped key of the same name.
+pub fn apply_repeated(
+    event_params: &mut HashMap<String, Value>,
+    repeated: &RepeatedParams,
+    config: &ParameterConfig,
+    warnings: &mut Vec<String>,
+) {
+    let rules = config.prefix_rules();
+    let mut resolved = Vec::new();
+    for (key, values) in repeated {
+        let Some(rule) = match_rule(&rules, key) else {
+            continue;
+        };
+        if !matches!(rule.target, PrefixTarget::EventParam) {
+            continue;
+        }
+        if let Ok((name, param_type)) = split_typed_key(&key[rule.prefix.len()..]) {
+            resolved.push((key.as_str(), name, param_type, values));
+        }
+    }
+    resolved.sort_unstable_by_key(|(key, _, param_type, _)| (param_type.is_some(), *key));
+
+    let limits = &config.limits;
+    for (key, name, param_type, values) in resolved {
+        if !event_params.contains_key(name) {
+            continue;
+        }
+        if values.len() > limits.max_repeated_values {
+            warnings.push(format!(
+                "parameter {} keeps the first {} of {} values",
+                key,
+                limits.max_repeated_values,
+                values.len()
+            ));
+        }
+        let mut truncated = false;
+        let items = values
+            .iter()
+            .take(limits.max_repeated_values)
+            .map(|raw| {
+                truncated |= raw.len() > limits.max_value_length;
+                let raw = truncate(raw, limits.max_value_length);
+                match param_type {
+                    Some(param_type) => typed_value(raw, param_type).unwrap_or_else(|_| Value::String(raw.to_string())),
+                    None => param_value(raw, config),
+                }
+            })
+            .collect();
+        let warning = format!("parameter {} truncated to {} bytes", key, limits.max_value_length);
+        if truncated && !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+        event_params.insert(name.to_string(), Value::Array(items));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ParameterLimits;
+    use serde_json::json;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_collect_repeated_keeps_order() {
+        let repeated = collect_repeated(&pairs(&[("e_tag", "a"), ("event", "click"), ("e_tag", "b"), ("e_tag", "c")]));
+        assert_eq!(repeated.len(), 1);
+        assert_eq!(repeated["e_tag"], vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_apply_repeated_builds_arrays() {
+        let config = ParameterConfig {
+            infer_types: true,
+            ..Default::default()
+        };
+        let repeated = collect_repeated(&pairs(&[
+            ("e_tag", "a"),
+            ("e_tag", "b"),
+            ("e_n:i", "1"),
+            ("e_n:i", "2"),
+            ("u_role", "x"),
+            ("u_role", "y"),
+        ]));
+        let mut event_params = HashMap::from([("tag".to_string(), json!("b")), ("n".to_string(), json!(2))]);
+        let mut warnings = Vec::new();
+
+        apply_repeated(&mut event_params, &repeated, &config, &mut warnings);
+        assert_eq!(event_params["tag"], json!(["a", "b"]));
+        assert_eq!(event_params["n"], json!([1, 2]));
+        assert!(!event_params.contains_key("role"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_apply_repeated_limits() {
+        let config = ParameterConfig {
+            limits: ParameterLimits {
+                max_value_length: 3,
+                max_repeated_values: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let repeated = collect_repeated(&pairs(&[("e_tag", "abcd"), ("e_tag", "b"), ("e_tag", "c")]));
+        let mut event_params = HashMap::from([("tag".to_string(), json!("c"))]);
+        let mut warnings = Vec::new();
+
+        apply_repeated(&mut event_params, &repeated, &config, &mut warnings);
+        assert_eq!(event_params["tag"], json!(["abc", "b"]));
+        assert_eq!(
+            warnings,
+            vec![
+                "parameter e_tag keeps the first 2 of 3 values".to_string(),
+                "parameter e_tag truncated to 3 bytes".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_repeated_params() {
+        let config = ParameterConfig::default();
+        let repeated = collect_repeated(&pairs(&[("e_n:i", "1"), ("e_n:i", "x")]));
+        assert_eq!(
+            validate_repeated_params(&repeated, &config),
+            Err("Invalid value for e_n:i: expected an integer, got 'x'".to_string())
+        );
+    }
+}
@@ -0,0 +1,154 @@
+// Property name sanitization
+// This module normalizes custom property names into valid column names for downstream stores
+
+use std::collections::HashMap;
+
+use super::prefixes::PrefixedParams;
+use crate::config::KeySanitizationConfig;
+
+/// Normalize a property name according to `config`
+///
+/// `"Page Type"` becomes `"page_type"`, `"utm-source.v2"` `"utm_source_v2"` and
+/// `"1st visit"` `"_1st_visit"` with the default settings. Runs of spaces, dashes and
+/// dots are replaced once.
+///
+/// # Returns
+/// The normalized name, or `None` when no valid character remains
+pub fn sanitize_key(name: &str, config: &KeySanitizationConfig) -> Option<String> {
+    let mut sanitized = String::with_capacity(name.len());
+    let mut after_separator = false;
+    for c in name.chars() {
+        match c {
+            ' ' | '-' | '.' => {
+                if !after_separator {
+                    sanitized.push_str(&config.replacement);
+                }
+                after_separator = true;
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                sanitized.push(if config.lowercase { c.to_ascii_lowercase() } else { c });
+                after_separator = false;
+            }
+            _ => {}
+        }
+    }
+    if sanitized.is_empty() {
+        return None;
+    }
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    // Only ASCII characters remain, so any length is a character boundary
+    sanitized.truncate(config.max_length);
+    Some(sanitized)
+}
+
+/// Sanitize the names of one property map
+///
+/// When several names normalize to the same one, a name already in normalized form
+/// wins, then the first in name order; the others are dropped. Dropped properties
+/// are described in `warnings`.
+pub fn sanitize_keys<V>(
+    properties: HashMap<String, V>,
+    kind: &str,
+    config: &KeySanitizationConfig,
+    warnings: &mut Vec<String>,
+) -> HashMap<String, V> {
+    let mut entries: Vec<(String, Option<String>, V)> = properties
+        .into_iter()
+        .map(|(name, value)| {
+            let sanitized = sanitize_key(&name, config);
+            (name, sanitized, value)
+        })
+        .collect();
+    entries.sort_unstable_by(|(a, sanitized_a, _), (b, sanitized_b, _)| {
+        let renamed = |name: &String, sanitized: &Option<String>| sanitized.as_ref() != Some(name);
+        (renamed(a, sanitized_a), a).cmp(&(renamed(b, sanitized_b), b))
+    });
+
+    let mut sanitized_properties = HashMap::with_capacity(entries.len());
+    for (name, sanitized, value) in entries {
+        let Some(sanitized) = sanitized else {
+            warnings.push(format!("{} property {:.64} dropped: no valid characters in name", kind, name));
+            continue;
+        };
+        if sanitized_properties.contains_key(&sanitized) {
+            warnings.push(format!(
+                "{} property {:.64} dropped: name normalizes to {}, already used",
+                kind, name, sanitized
+            ));
+            continue;
+        }
+        sanitized_properties.insert(sanitized, value);
+    }
+    sanitized_properties
+}
+
+/// Sanitize every custom property name of `prefixed` (`parameters.sanitize_keys`)
+pub fn sanitize_prefixed(prefixed: &mut PrefixedParams, config: &KeySanitizationConfig) {
+    if !config.enabled {
+        return;
+    }
+    let warnings = &mut prefixed.warnings;
+    prefixed.event_params = sanitize_keys(std::mem::take(&mut prefixed.event_params), "event_param", config, warnings);
+    prefixed.profile = sanitize_keys(std::mem::take(&mut prefixed.profile), "profile", config, warnings);
+    prefixed.session = sanitize_keys(std::mem::take(&mut prefixed.session), "session", config, warnings);
+    prefixed.project = sanitize_keys(std::mem::take(&mut prefixed.project), "project", config, warnings);
+    for (name, properties) in prefixed.objects.iter_mut() {
+        *properties = sanitize_keys(std::mem::take(properties), name, config, warnings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> KeySanitizationConfig {
+        KeySanitizationConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sanitize_key() {
+        let config = config();
+        assert_eq!(sanitize_key("Page Type", &config).as_deref(), Some("page_type"));
+        assert_eq!(sanitize_key("utm-source.v2", &config).as_deref(), Some("utm_source_v2"));
+        assert_eq!(sanitize_key("a - b", &config).as_deref(), Some("a_b"));
+        assert_eq!(sanitize_key("1st visit", &config).as_deref(), Some("_1st_visit"));
+        assert_eq!(sanitize_key("prix (€)", &config).as_deref(), Some("prix_"));
+        assert_eq!(sanitize_key("日本", &config), None);
+
+        let config = KeySanitizationConfig {
+            lowercase: false,
+            replacement: String::new(),
+            max_length: 6,
+            ..config
+        };
+        assert_eq!(sanitize_key("Page Type", &config).as_deref(), Some("PageTy"));
+    }
+
+    #[test]
+    fn test_sanitize_keys_collisions() {
+        let properties = HashMap::from([
+            ("Page Type".to_string(), json!("a")),
+            ("page_type".to_string(), json!("b")),
+            ("page-type".to_string(), json!("c")),
+            ("€".to_string(), json!("d")),
+        ]);
+        let mut warnings = Vec::new();
+        let sanitized = sanitize_keys(properties, "event_param", &config(), &mut warnings);
+
+        assert_eq!(sanitized, HashMap::from([("page_type".to_string(), json!("b"))]));
+        assert_eq!(
+            warnings,
+            vec![
+                "event_param property Page Type dropped: name normalizes to page_type, already used".to_string(),
+                "event_param property page-type dropped: name normalizes to page_type, already used".to_string(),
+                "event_param property € dropped: no valid characters in name".to_string(),
+            ]
+        );
+    }
+}
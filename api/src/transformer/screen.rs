@@ -0,0 +1,67 @@
+// Screen parameter parsing
+// This module turns the `screen` and `dpr` parameters into numeric visit fields
+
+/// Parse a screen resolution such as "1920x1080" into (width, height)
+///
+/// Accepts "x", "X" or "*" as separator and surrounding whitespace. Returns `None`
+/// for malformed values or a zero dimension.
+pub fn parse_screen(screen: &str) -> Option<(u32, u32)> {
+    let (width, height) = screen.trim().split_once(['x', 'X', '*'])?;
+    let width: u32 = width.trim().parse().ok()?;
+    let height: u32 = height.trim().parse().ok()?;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Parse a device pixel ratio such as "2" or "1.5"
+///
+/// Returns `None` for values that are not a positive number up to 10.
+pub fn parse_pixel_ratio(dpr: &str) -> Option<f64> {
+    dpr.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|ratio| ratio.is_finite() && *ratio > 0.0 && *ratio <= 10.0)
+}
+
+/// Viewport size bucket for a screen width in CSS pixels
+///
+/// "mobile" below 768, "tablet" below 1024, "desktop" below 1920, otherwise "large".
+pub fn viewport_bucket(width: u32) -> &'static str {
+    match width {
+        0..=767 => "mobile",
+        768..=1023 => "tablet",
+        1024..=1919 => "desktop",
+        _ => "large",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_screen() {
+        assert_eq!(parse_screen("1920x1080"), Some((1920, 1080)));
+        assert_eq!(parse_screen(" 390 X 844 "), Some((390, 844)));
+        assert_eq!(parse_screen("1280*720"), Some((1280, 720)));
+        assert_eq!(parse_screen("1920"), None);
+        assert_eq!(parse_screen("0x1080"), None);
+        assert_eq!(parse_screen("widexhigh"), None);
+    }
+
+    #[test]
+    fn test_parse_pixel_ratio() {
+        assert_eq!(parse_pixel_ratio("2"), Some(2.0));
+        assert_eq!(parse_pixel_ratio("1.5"), Some(1.5));
+        assert_eq!(parse_pixel_ratio("0"), None);
+        assert_eq!(parse_pixel_ratio("NaN"), None);
+        assert_eq!(parse_pixel_ratio("abc"), None);
+    }
+
+    #[test]
+    fn test_viewport_bucket() {
+        assert_eq!(viewport_bucket(390), "mobile");
+        assert_eq!(viewport_bucket(768), "tablet");
+        assert_eq!(viewport_bucket(1440), "desktop");
+        assert_eq!(viewport_bucket(2560), "large");
+    }
+}
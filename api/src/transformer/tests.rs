@@ -6,6 +6,8 @@ use serde_json;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{CanonicalUrlConfig, KeySanitizationConfig};
+    use serde_json::json;
 
     #[test]
     fn test_analytics_event_serialization() {
@@ -30,6 +32,7 @@ mod tests {
                 language: Some("en-US".to_string()),
                 referer: None,
                 app: Some("web".to_string()),
+                ..Default::default()
             },
             event_param: None,
             profile: None,
@@ -43,6 +46,7 @@ mod tests {
             city: None,
             latitude: None,
             longitude: None,
+            ..Default::default()
         };
 
         // Serialize to JSON
@@ -59,8 +63,8 @@ mod tests {
     #[test]
     fn test_event_param_object_with_params() {
         let mut params = HashMap::new();
-        params.insert("button_text".to_string(), "Click Me".to_string());
-        params.insert("product_id".to_string(), "prod_123".to_string());
+        params.insert("button_text".to_string(), "Click Me".into());
+        params.insert("product_id".to_string(), "prod_123".into());
         
         let event_param = EventParamObject { params };
         
@@ -77,10 +81,13 @@ mod tests {
     #[test]
     fn test_profile_object_with_properties() {
         let mut properties = HashMap::new();
-        properties.insert("email".to_string(), "user@example.com".to_string());
-        properties.insert("name".to_string(), "John Doe".to_string());
+        properties.insert("email".to_string(), "user@example.com".into());
+        properties.insert("name".to_string(), "John Doe".into());
         
-        let profile = ProfileObject { properties };
+        let profile = ProfileObject {
+            properties,
+            ..Default::default()
+        };
         
         // Serialize to JSON
         let json = serde_json::to_string(&profile).expect("Failed to serialize");
@@ -107,6 +114,7 @@ mod tests {
             language: Some("en-US".to_string()),
             referer: Some("https://google.com".to_string()),
             app: Some("web".to_string()),
+            ..Default::default()
         };
 
         // Serialize and deserialize
@@ -176,9 +184,9 @@ mod tests {
 
         assert!(event.event_param.is_some());
         let event_params = event.event_param.unwrap();
-        assert_eq!(event_params.params.get("button_text"), Some(&"Click Me".to_string()));
-        assert_eq!(event_params.params.get("product_id"), Some(&"prod_123".to_string()));
-        assert_eq!(event_params.params.get("category"), Some(&"electronics".to_string()));
+        assert_eq!(event_params.params.get("button_text"), Some(&json!("Click Me")));
+        assert_eq!(event_params.params.get("product_id"), Some(&json!("prod_123")));
+        assert_eq!(event_params.params.get("category"), Some(&json!("electronics")));
     }
 
     #[test]
@@ -193,9 +201,9 @@ mod tests {
 
         assert!(event.profile.is_some());
         let profile = event.profile.unwrap();
-        assert_eq!(profile.properties.get("email"), Some(&"user@example.com".to_string()));
-        assert_eq!(profile.properties.get("name"), Some(&"John Doe".to_string()));
-        assert_eq!(profile.properties.get("id"), Some(&"user_123".to_string()));
+        assert_eq!(profile.properties.get("email"), Some(&json!("user@example.com")));
+        assert_eq!(profile.properties.get("name"), Some(&json!("John Doe")));
+        assert_eq!(profile.properties.get("id"), Some(&json!("user_123")));
     }
 
     #[test]
@@ -224,6 +232,22 @@ mod tests {
         assert_eq!(event.project_properties.get("environment"), Some(&"production".to_string()));
     }
 
+    #[test]
+    fn test_transform_params_context() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "app_open".to_string());
+        params.insert("c_app_version".to_string(), "2.1.0".to_string());
+        params.insert("context".to_string(), r#"{"sdk": {"name": "penrose-ios", "version": "1.4.0"}}"#.to_string());
+
+        let event = transform_params(params);
+
+        let context = event.context.unwrap();
+        assert_eq!(context.app_version.as_deref(), Some("2.1.0"));
+        assert_eq!(context.sdk_name.as_deref(), Some("penrose-ios"));
+        assert_eq!(context.sdk_version.as_deref(), Some("1.4.0"));
+        assert!(event.project_properties.is_empty());
+    }
+
     #[test]
     fn test_transform_params_all_prefixes() {
         let mut params = HashMap::new();
@@ -252,11 +276,11 @@ mod tests {
 
         // Verify event params
         assert!(event.event_param.is_some());
-        assert_eq!(event.event_param.unwrap().params.get("button_text"), Some(&"Click Me".to_string()));
+        assert_eq!(event.event_param.unwrap().params.get("button_text"), Some(&json!("Click Me")));
 
         // Verify profile
         assert!(event.profile.is_some());
-        assert_eq!(event.profile.unwrap().properties.get("email"), Some(&"user@example.com".to_string()));
+        assert_eq!(event.profile.unwrap().properties.get("email"), Some(&json!("user@example.com")));
 
         // Verify session properties
         assert_eq!(event.session_properties.get("session_id"), Some(&"sess_abc".to_string()));
@@ -296,6 +320,18 @@ mod tests {
         assert_eq!(event.event, "unknown");
     }
 
+    #[test]
+    fn test_transform_params_iso_timestamp() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("timestamp".to_string(), "2024-01-01T09:00:00+09:00".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.timestamp, 1704067200000);
+        assert_eq!(event.visit.timestamp, Some(1704067200000));
+    }
+
     #[test]
     fn test_transform_params_invalid_numeric_fields() {
         let mut params = HashMap::new();
@@ -333,6 +369,77 @@ mod tests {
         assert_eq!(event.latitude, None);
         assert_eq!(event.longitude, None);
     }
+
+    #[test]
+    fn test_transform_params_url_decomposition() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("url".to_string(), "https://Example.com/pricing?plan=pro#faq".to_string());
+
+        let event = transform_params(params);
+
+        let parts = event.visit.url_parts.expect("url_parts should be set");
+        assert_eq!(parts.host.as_deref(), Some("example.com"));
+        assert_eq!(parts.query.get("plan").map(String::as_str), Some("pro"));
+        assert_eq!(parts.fragment.as_deref(), Some("faq"));
+        assert_eq!(event.visit.domain, Some("example.com".to_string()));
+        assert_eq!(event.visit.uri, Some("/pricing".to_string()));
+    }
+
+    #[test]
+    fn test_transform_params_explicit_domain_and_uri_win() {
+        let mut params = HashMap::new();
+        params.insert("url".to_string(), "https://example.com/a".to_string());
+        params.insert("domain".to_string(), "www.example.com".to_string());
+        params.insert("uri".to_string(), "/a?x=1".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.visit.domain, Some("www.example.com".to_string()));
+        assert_eq!(event.visit.uri, Some("/a?x=1".to_string()));
+    }
+
+    #[test]
+    fn test_transform_params_invalid_url() {
+        let mut params = HashMap::new();
+        params.insert("url".to_string(), "not a url".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.visit.url, Some("not a url".to_string()));
+        assert_eq!(event.visit.url_parts, None);
+        assert_eq!(event.visit.domain, None);
+        assert_eq!(event.visit.uri, None);
+    }
+
+    #[test]
+    fn test_transform_params_screen_fields() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("screen".to_string(), "390x844".to_string());
+        params.insert("dpr".to_string(), "3".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.visit.screen, Some("390x844".to_string()));
+        assert_eq!(event.visit.screen_width, Some(390));
+        assert_eq!(event.visit.screen_height, Some(844));
+        assert_eq!(event.visit.pixel_ratio, Some(3.0));
+        assert_eq!(event.visit.viewport_bucket, Some("mobile".to_string()));
+    }
+
+    #[test]
+    fn test_transform_params_invalid_screen() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("screen".to_string(), "unknown".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.visit.screen, Some("unknown".to_string()));
+        assert_eq!(event.visit.screen_width, None);
+        assert_eq!(event.visit.viewport_bucket, None);
+    }
 }
 
 
@@ -378,12 +485,14 @@ mod tests {
         assert!(json.contains("\"email\": \"user@example.com\""));
         assert!(json.contains("\"session_id\": \"sess_abc123\""));
         assert!(json.contains("\"version\": \"1.0.0\""));
+        assert!(json.contains("\"schema_version\": 1"));
 
         // Deserialize back
         let deserialized: AnalyticsEvent = serde_json::from_str(&json)
             .expect("Failed to deserialize");
 
         // Verify round-trip preserves data
+        assert_eq!(deserialized.schema_version, SchemaVersion(SCHEMA_VERSION));
         assert_eq!(event.project, deserialized.project);
         assert_eq!(event.event, deserialized.event);
         assert_eq!(event.id, deserialized.id);
@@ -397,4 +506,184 @@ mod tests {
         
         // Verify project properties
         assert_eq!(event.project_properties.get("version"), deserialized.project_properties.get("version"));
+    
+    #[test]
+    fn test_transform_params_infers_types_when_enabled() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "purchase".to_string());
+        params.insert("e_price".to_string(), "19.99".to_string());
+        params.insert("e_quantity".to_string(), "2".to_string());
+        params.insert("e_sku".to_string(), "007".to_string());
+        params.insert("u_premium".to_string(), "true".to_string());
+
+        let event = transform_params(params.clone());
+        assert_eq!(event.event_param.unwrap().params.get("price"), Some(&json!("19.99")));
+
+        let config = ParameterConfig {
+            infer_types: true,
+            ..Default::default()
+        };
+        let event = transform_params_with(params, &config);
+        let event_params = event.event_param.unwrap();
+        assert_eq!(event_params.params.get("price"), Some(&json!(19.99)));
+        assert_eq!(event_params.params.get("quantity"), Some(&json!(2)));
+        assert_eq!(event_params.params.get("sku"), Some(&json!("007")));
+        assert_eq!(event.profile.unwrap().properties.get("premium"), Some(&json!(true)));
+
+        let json = serde_json::to_string(&event_params).unwrap();
+        assert!(json.contains("\"price\":19.99"));
     }
+
+    #[test]
+    fn test_transform_params_repeated_event_params() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "view".to_string());
+        params.insert("e_tag".to_string(), "b".to_string());
+        params.insert("e_single".to_string(), "x".to_string());
+        let repeated = RepeatedParams::from([("e_tag".to_string(), vec!["a".to_string(), "b".to_string()])]);
+
+        let event = transform_params_with_repeated(params, &repeated, &ParameterConfig::default());
+        let event_params = event.event_param.unwrap();
+        assert_eq!(event_params.params.get("tag"), Some(&json!(["a", "b"])));
+        assert_eq!(event_params.params.get("single"), Some(&json!("x")));
+    }
+
+    #[test]
+    fn test_unset_currency_hint_is_not_serialized() {
+        let mut event = transform_params(HashMap::from([("event".to_string(), "view".to_string())]));
+        assert!(serde_json::to_value(&event).unwrap().get("currency").is_none());
+
+        event.currency = Some("EUR".to_string());
+        assert_eq!(serde_json::to_value(&event).unwrap()["currency"], json!("EUR"));
+    }
+
+    #[test]
+    fn test_transform_params_commerce_fields() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "purchase".to_string());
+        params.insert("revenue".to_string(), "19.99".to_string());
+        params.insert("currency".to_string(), "eur".to_string());
+        params.insert("quantity".to_string(), "2".to_string());
+
+        let event = transform_params(params);
+        assert_eq!(event.revenue, Some(rust_decimal::Decimal::new(1999, 2)));
+        assert_eq!(event.revenue_currency.as_deref(), Some("EUR"));
+        assert_eq!(event.quantity, Some(2));
+        assert!(event.currency.is_none());
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["revenue"], json!(19.99));
+        assert_eq!(json["revenue_currency"], json!("EUR"));
+
+        let event = transform_params(HashMap::from([("event".to_string(), "view".to_string())]));
+        assert!(event.revenue.is_none() && event.revenue_currency.is_none() && event.quantity.is_none());
+    }
+
+    #[test]
+    fn test_transform_params_profile_operations() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "identify".to_string());
+        params.insert("u_email".to_string(), "a@example.com".to_string());
+        params.insert("u_set_once_plan".to_string(), "free".to_string());
+        params.insert("u_unset_phone".to_string(), "1".to_string());
+
+        let event = transform_params(params);
+        let operations = event.profile_operations.clone().unwrap();
+        assert_eq!(operations.set_once, vec!["plan"]);
+        assert_eq!(operations.unset, vec!["phone"]);
+        let properties = event.profile.clone().unwrap().properties;
+        assert_eq!(properties.get("plan"), Some(&json!("free")));
+        assert!(!properties.contains_key("phone"));
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["profile_operations"], json!({"set_once": ["plan"], "unset": ["phone"]}));
+
+        // Only unsets: no profile properties, operations still recorded
+        let event = transform_params(HashMap::from([("u_unset_phone".to_string(), "1".to_string())]));
+        assert!(event.profile.is_none());
+        assert_eq!(event.profile_operations.unwrap().unset, vec!["phone"]);
+    }
+
+    #[test]
+    fn test_transform_params_epoch_seconds_timestamp() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("timestamp".to_string(), "1704067200".to_string());
+        params.insert("sent_at".to_string(), "1704067260".to_string());
+
+        let event = transform_params(params);
+        assert_eq!(event.timestamp, 1704067200000);
+        assert_eq!(event.visit.timestamp, Some(1704067200000));
+        assert_eq!(event.timestamp_unit, Some(TimestampUnit::Seconds));
+        assert_eq!(event.sent_at, Some(1704067260000));
+        assert_eq!(serde_json::to_value(&event).unwrap()["timestamp_unit"], json!("seconds"));
+
+        let event = transform_params(HashMap::from([("timestamp".to_string(), "1704067200000".to_string())]));
+        assert_eq!(event.timestamp_unit, None);
+    }
+
+    #[test]
+    fn test_transform_params_sanitizes_keys() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "view".to_string());
+        params.insert("e_Page Type".to_string(), "home".to_string());
+        params.insert("u_First-Name".to_string(), "Ann".to_string());
+        params.insert("s_ab.test".to_string(), "b".to_string());
+
+        let event = transform_params(params.clone());
+        assert!(event.event_param.unwrap().params.contains_key("Page Type"));
+
+        let config = ParameterConfig {
+            sanitize_keys: KeySanitizationConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let event = transform_params_with(params, &config);
+        assert_eq!(event.event_param.unwrap().params.get("page_type"), Some(&json!("home")));
+        assert_eq!(event.profile.unwrap().properties.get("first_name"), Some(&json!("Ann")));
+        assert_eq!(event.session_properties.get("ab_test").map(String::as_str), Some("b"));
+    }
+
+    #[test]
+    fn test_transform_params_canonical_url() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("url".to_string(), "https://Example.com/pricing/?utm_source=ads&plan=pro".to_string());
+
+        let event = transform_params(params.clone());
+        assert_eq!(event.visit.canonical_url, None);
+
+        let config = ParameterConfig {
+            canonical_url: CanonicalUrlConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let event = transform_params_with(params, &config);
+        assert_eq!(event.visit.url.as_deref(), Some("https://Example.com/pricing/?utm_source=ads&plan=pro"));
+        assert_eq!(event.visit.canonical_url.as_deref(), Some("https://example.com/pricing?plan=pro"));
+    }
+
+    #[test]
+    fn test_transform_params_checks_visit_metrics() {
+        let mut params = HashMap::new();
+        params.insert("event".to_string(), "pageview".to_string());
+        params.insert("duration".to_string(), "-2500".to_string());
+        params.insert("scroll_depth".to_string(), "250".to_string());
+
+        let event = transform_params(params);
+
+        assert_eq!(event.visit.duration, None);
+        assert_eq!(event.visit.scroll_depth, Some(100));
+        assert_eq!(
+            event.warnings,
+            vec![
+                "duration -2500 dropped: negative".to_string(),
+                "scroll_depth 250 clamped to 100".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+// Timestamp parameter parsing
+// This module normalizes epoch seconds/milliseconds/microseconds and ISO-8601 timestamps to epoch milliseconds
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// Date-time layouts without an offset, read as UTC
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// Unit of an epoch timestamp, detected from its magnitude
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampUnit {
+    /// Lowercase name of the unit, as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimestampUnit::Seconds => "seconds",
+            TimestampUnit::Milliseconds => "milliseconds",
+            TimestampUnit::Microseconds => "microseconds",
+            TimestampUnit::Nanoseconds => "nanoseconds",
+        }
+    }
+
+    /// Unit of an epoch value: below 10^11 seconds (until year 5138), below 10^14
+    /// milliseconds, below 10^17 microseconds, nanoseconds above
+    pub fn detect(value: f64) -> Self {
+        match value.abs() {
+            v if v < 1e11 => TimestampUnit::Seconds,
+            v if v < 1e14 => TimestampUnit::Milliseconds,
+            v if v < 1e17 => TimestampUnit::Microseconds,
+            _ => TimestampUnit::Nanoseconds,
+        }
+    }
+
+    /// Convert an epoch value in this unit to milliseconds
+    fn to_millis(self, value: f64) -> f64 {
+        match self {
+            TimestampUnit::Seconds => value * 1_000.0,
+            TimestampUnit::Milliseconds => value,
+            TimestampUnit::Microseconds => value / 1_000.0,
+            TimestampUnit::Nanoseconds => value / 1_000_000.0,
+        }
+    }
+}
+
+/// Parse a timestamp parameter into epoch milliseconds
+///
+/// Accepts epoch seconds, milliseconds, microseconds or nanoseconds ("1704067200",
+/// "1704067200000", fractions allowed), RFC 3339 / ISO-8601 date-times with an
+/// offset ("2024-01-01T00:00:00Z", "2024-01-01T09:00:00.250+09:00"), date-times without
+/// an offset (UTC) and plain dates ("2024-01-01", midnight UTC).
+pub fn parse_timestamp(raw: &str) -> Option<i64> {
+    parse_timestamp_with_unit(raw).map(|(millis, _)| millis)
+}
+
+/// Parse a timestamp parameter like `parse_timestamp`, also returning the unit the
+/// value was sent in (milliseconds for date-time strings)
+pub fn parse_timestamp_with_unit(raw: &str) -> Option<(i64, TimestampUnit)> {
+    let raw = raw.trim();
+    if let Ok(value) = raw.parse::<i64>() {
+        let unit = TimestampUnit::detect(value as f64);
+        let millis = match unit {
+            TimestampUnit::Seconds => value.checked_mul(1_000)?,
+            TimestampUnit::Milliseconds => value,
+            TimestampUnit::Microseconds => value / 1_000,
+            TimestampUnit::Nanoseconds => value / 1_000_000,
+        };
+        return Some((millis, unit));
+    }
+    if is_decimal(raw) {
+        let value = raw.parse::<f64>().ok()?;
+        let unit = TimestampUnit::detect(value);
+        return Some((unit.to_millis(value).round() as i64, unit));
+    }
+    parse_datetime(raw).map(|millis| (millis, TimestampUnit::Milliseconds))
+}
+
+/// `-?[0-9]+\.[0-9]+`
+fn is_decimal(raw: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = raw.strip_prefix('-').unwrap_or(raw);
+    unsigned
+        .split_once('.')
+        .is_some_and(|(int_part, fraction)| digits(int_part) && digits(fraction))
+}
+
+/// Parse an ISO-8601 date-time or date into epoch milliseconds
+fn parse_datetime(raw: &str) -> Option<i64> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(raw) {
+        return Some(datetime.timestamp_millis());
+    }
+    if let Some(datetime) = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+    {
+        return Some(datetime.and_utc().timestamp_millis());
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc().timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_millis() {
+        assert_eq!(parse_timestamp("1704067200000"), Some(1704067200000));
+        assert_eq!(parse_timestamp(" 1704067200000 "), Some(1704067200000));
+    }
+
+    #[test]
+    fn test_epoch_unit_detection() {
+        let parsed = |raw| parse_timestamp_with_unit(raw).unwrap();
+        assert_eq!(parsed("1704067200"), (1704067200000, TimestampUnit::Seconds));
+        assert_eq!(parsed("1704067200.25"), (1704067200250, TimestampUnit::Seconds));
+        assert_eq!(parsed("1704067200000"), (1704067200000, TimestampUnit::Milliseconds));
+        assert_eq!(parsed("1704067200000.4"), (1704067200000, TimestampUnit::Milliseconds));
+        assert_eq!(parsed("1704067200123456"), (1704067200123, TimestampUnit::Microseconds));
+        assert_eq!(parsed("1704067200123456789"), (1704067200123, TimestampUnit::Nanoseconds));
+        assert_eq!(parsed("2024-01-01T00:00:00Z"), (1704067200000, TimestampUnit::Milliseconds));
+        assert_eq!(parse_timestamp("1."), None);
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00Z"), Some(1704067200000));
+        assert_eq!(parse_timestamp("2024-01-01T09:00:00.250+09:00"), Some(1704067200250));
+    }
+
+    #[test]
+    fn test_without_offset_is_utc() {
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00"), Some(1704067200000));
+        assert_eq!(parse_timestamp("2024-01-01 00:00:01.5"), Some(1704067201500));
+        assert_eq!(parse_timestamp("2024-01-01"), Some(1704067200000));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(parse_timestamp("invalid"), None);
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+}
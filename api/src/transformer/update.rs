@@ -0,0 +1,170 @@
+// Update (patch) events
+// This module builds /update events and the compact record streamed for them instead of a full event
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::timestamp::parse_timestamp;
+use super::visit_metrics::{parse_duration, parse_scroll_depth};
+use super::{AnalyticsEvent, EventMetadata, SchemaVersion, VisitObject};
+use crate::config::ParameterConfig;
+
+/// Event name given to update events
+pub const UPDATE_EVENT: &str = "update";
+
+/// What a streamed record does to the stored data
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Patch the fields of an earlier event (identified by `id`)
+    Update,
+}
+
+impl Operation {
+    /// Name used in streamed records and record columns
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Update => "update",
+        }
+    }
+}
+
+/// Compact record streamed for an update event
+///
+/// Carries the target event id and only the fields an update can change; fields that
+/// were not sent are omitted rather than written as null.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpdateRecord {
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    pub operation: Operation,
+    /// Id of the event being updated
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// When the update was made, in epoch milliseconds
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll_depth: Option<i32>,
+    /// Values dropped or clamped as implausible
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<EventMetadata>,
+}
+
+impl UpdateRecord {
+    /// The compact record of `event`, or `None` when it is not an update event
+    pub fn from_event(event: &AnalyticsEvent) -> Option<Self> {
+        if event.operation != Some(Operation::Update) {
+            return None;
+        }
+        Some(UpdateRecord {
+            schema_version: event.schema_version,
+            operation: Operation::Update,
+            id: event.id.clone().unwrap_or_default(),
+            project: event.project.clone(),
+            timestamp: event.timestamp,
+            received_at: event.received_at,
+            expires_at: event.expires_at,
+            duration: event.visit.duration,
+            scroll_depth: event.visit.scroll_depth,
+            warnings: event.warnings.clone(),
+            meta: event.meta.clone(),
+        })
+    }
+}
+
+/// Build the event for an /update request
+///
+/// Only the target `id`, `project`, `timestamp` and the changed `duration` and
+/// `scroll_depth` are read (checked like on /track/, see `parameters.limits`); other
+/// parameters are ignored. The event is streamed as an `UpdateRecord`, but still
+/// passes through enrichment so filter rules and data-residency routing apply to it
+/// like to the event it updates.
+pub fn transform_update_params(params: &HashMap<String, String>, config: &ParameterConfig) -> AnalyticsEvent {
+    let timestamp = params
+        .get("timestamp")
+        .and_then(|t| parse_timestamp(t))
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    let mut warnings = Vec::new();
+    let visit = VisitObject {
+        duration: params
+            .get("duration")
+            .and_then(|d| parse_duration(d, config.limits.max_duration_ms, &mut warnings)),
+        scroll_depth: params
+            .get("scroll_depth")
+            .and_then(|s| parse_scroll_depth(s, &mut warnings)),
+        ..Default::default()
+    };
+    AnalyticsEvent {
+        project: params.get("project").cloned(),
+        event: UPDATE_EVENT.to_string(),
+        operation: Some(Operation::Update),
+        id: params.get("id").cloned(),
+        timestamp,
+        visit,
+        warnings,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_transform_update_params() {
+        let params = params(&[
+            ("id", "evt-1"),
+            ("project", "example.com"),
+            ("timestamp", "1707782400000"),
+            ("duration", "5000"),
+            ("url", "https://example.com"),
+            ("e_label", "x"),
+        ]);
+        let event = transform_update_params(&params, &ParameterConfig::default());
+        assert_eq!(event.event, "update");
+        assert_eq!(event.operation, Some(Operation::Update));
+        assert_eq!(event.id.as_deref(), Some("evt-1"));
+        assert_eq!(event.timestamp, 1707782400000);
+        assert_eq!(event.visit.duration, Some(5000));
+        assert_eq!(event.visit.scroll_depth, None);
+        assert_eq!(event.visit.url, None);
+        assert!(event.event_param.is_none());
+    }
+
+    #[test]
+    fn test_update_record_only_has_changed_fields() {
+        let params = params(&[("id", "evt-1"), ("timestamp", "1707782400000"), ("scroll_depth", "180")]);
+        let event = transform_update_params(&params, &ParameterConfig::default());
+        let record = UpdateRecord::from_event(&event).unwrap();
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            serde_json::json!({
+                "schema_version": 1,
+                "operation": "update",
+                "id": "evt-1",
+                "timestamp": 1707782400000i64,
+                "scroll_depth": 100,
+                "warnings": ["scroll_depth 180 clamped to 100"]
+            })
+        );
+
+        let pageview = AnalyticsEvent {
+            event: "pageview".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(UpdateRecord::from_event(&pageview), None);
+    }
+}
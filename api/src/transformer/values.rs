@@ -0,0 +1,332 @@
+// Event and profile parameter values
+// This module converts raw event, profile and custom object parameter strings into JSON values
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::prefixes::match_rule;
+use crate::config::{ParameterConfig, PrefixTarget};
+
+/// Separator between a parameter name and its declared type, e.g. `e_price:n`
+pub const TYPE_SEPARATOR: char = ':';
+
+/// Type declared with a parameter suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    /// `:s` - kept as a string, even with type inference enabled
+    String,
+    /// `:n` - any finite number
+    Number,
+    /// `:i` - a whole number in the i64 range
+    Integer,
+    /// `:b` - "true", "false", "1" or "0"
+    Boolean,
+}
+
+impl ParamType {
+    /// Parse a type suffix ("s", "n", "i" or "b")
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "s" => Some(ParamType::String),
+            "n" => Some(ParamType::Number),
+            "i" => Some(ParamType::Integer),
+            "b" => Some(ParamType::Boolean),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ParamType::String => "a string",
+            ParamType::Number => "a number",
+            ParamType::Integer => "an integer",
+            ParamType::Boolean => "a boolean",
+        }
+    }
+}
+
+/// Split a parameter key into its name and declared type
+///
+/// # Returns
+/// `(key, None)` for keys without a suffix, `(name, Some(type))` for `name:type`,
+/// or an error naming the key when the suffix is not a known type
+pub fn split_typed_key(key: &str) -> Result<(&str, Option<ParamType>), String> {
+    let Some((name, suffix)) = key.rsplit_once(TYPE_SEPARATOR) else {
+        return Ok((key, None));
+    };
+    match ParamType::from_suffix(suffix) {
+        Some(param_type) => Ok((name, Some(param_type))),
+        None => Err(format!("Unknown type suffix in {} (expected :s, :n, :i or :b)", key)),
+    }
+}
+
+/// Convert a parameter value to its declared type
+///
+/// # Returns
+/// The JSON value, or an error describing the expected type
+pub fn typed_value(raw: &str, param_type: ParamType) -> Result<Value, String> {
+    let value = match param_type {
+        ParamType::String => Some(Value::String(raw.to_string())),
+        ParamType::Integer => raw.trim().parse::<i64>().ok().map(Value::from),
+        ParamType::Number => {
+            let raw = raw.trim();
+            raw.parse::<i64>().ok().map(Value::from).or_else(|| {
+                raw.parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(Value::Number)
+            })
+        }
+        ParamType::Boolean => match raw.trim() {
+            "true" | "1" => Some(Value::Bool(true)),
+            "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+    };
+    value.ok_or_else(|| format!("expected {}, got '{}'", param_type.describe(), raw))
+}
+
+/// Validate the type suffixes and typed values of parameters stored as JSON values
+///
+/// Covers every parameter routed to `event_param`, `profile` or a custom object by
+/// the prefix rules; session and project properties are plain strings.
+///
+/// # Returns
+/// Ok(()) if every typed parameter holds a value of its declared type, Err with a
+/// descriptive message otherwise
+pub fn validate_typed_params(params: &HashMap<String, String>, config: &ParameterConfig) -> Result<(), String> {
+    let rules = config.prefix_rules();
+    for (key, raw) in params {
+        let typed = match match_rule(&rules, key) {
+            Some(rule) => !matches!(rule.target, PrefixTarget::Session | PrefixTarget::Project),
+            None => false,
+        };
+        if !typed {
+            continue;
+        }
+        if let (_, Some(param_type)) = split_typed_key(key)? {
+            typed_value(raw, param_type).map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert `(key, raw value)` pairs, prefixes already removed, into JSON values
+///
+/// Typed keys (`price:n`) are stored under their name and win over an untyped key
+/// of the same name. Values that do not match their declared type, which request
+/// validation rejects, are kept as strings.
+pub fn collect_values<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+    config: &ParameterConfig,
+) -> HashMap<String, Value> {
+    let mut values = HashMap::new();
+    let mut typed = Vec::new();
+    for (key, raw) in entries {
+        match split_typed_key(key) {
+            Ok((name, Some(param_type))) => typed.push((name, param_type, raw)),
+            _ => {
+                values.insert(key.to_string(), param_value(raw, config));
+            }
+        }
+    }
+    for (name, param_type, raw) in typed {
+        let value = typed_value(raw, param_type).unwrap_or_else(|_| Value::String(raw.to_string()));
+        values.insert(name.to_string(), value);
+    }
+    values
+}
+
+/// Convert a raw parameter value into its JSON value
+///
+/// Values stay strings unless `parameters.parse_json` (objects and arrays) or
+/// `parameters.infer_types` (booleans and numbers) is enabled.
+pub fn param_value(raw: &str, config: &ParameterConfig) -> Value {
+    if config.parse_json {
+        if let Some(value) = parse_json_value(raw, config.max_json_bytes, config.max_json_depth) {
+            return value;
+        }
+    }
+    if config.infer_types {
+        infer_value(raw)
+    } else {
+        Value::String(raw.to_string())
+    }
+}
+
+/// Parse a parameter holding a JSON object or array
+///
+/// # Returns
+/// The parsed value, or `None` when the value is not an object or array, is not
+/// valid JSON, is longer than `max_bytes` or nests deeper than `max_depth`
+pub fn parse_json_value(raw: &str, max_bytes: usize, max_depth: usize) -> Option<Value> {
+    let raw = raw.trim();
+    if !(raw.starts_with('{') || raw.starts_with('[')) {
+        return None;
+    }
+    if raw.len() > max_bytes {
+        tracing::debug!(len = raw.len(), max_bytes, "JSON parameter too large, kept as string");
+        return None;
+    }
+    let value: Value = serde_json::from_str(raw).ok()?;
+    if json_depth(&value) > max_depth {
+        tracing::debug!(max_depth, "JSON parameter nested too deeply, kept as string");
+        return None;
+    }
+    Some(value)
+}
+
+/// Nesting depth of objects and arrays; scalars have depth 0
+fn json_depth(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => 1 + items.iter().map(json_depth).max().unwrap_or(0),
+        Value::Object(fields) => 1 + fields.values().map(json_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Infer a JSON boolean or number from a parameter string
+///
+/// "true" and "false" become booleans and plain decimal numbers ("42", "-3", "19.99")
+/// become numbers. Anything else stays a string, including values whose formatting a
+/// number would lose: leading zeros ("007"), a leading "+", exponents and integers
+/// outside the i64 range.
+pub fn infer_value(raw: &str) -> Value {
+    match raw {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    if is_plain_decimal(raw) {
+        if !raw.contains('.') {
+            if let Ok(n) = raw.parse::<i64>() {
+                return Value::from(n);
+            }
+        } else if let Some(n) = raw.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(raw.to_string())
+}
+
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?`
+fn is_plain_decimal(raw: &str) -> bool {
+    let unsigned = raw.strip_prefix('-').unwrap_or(raw);
+    let (int_part, fraction) = match unsigned.split_once('.') {
+        Some((int_part, fraction)) => (int_part, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    digits(int_part)
+        && (int_part == "0" || !int_part.starts_with('0'))
+        && fraction.is_none_or(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infers_booleans_and_numbers() {
+        assert_eq!(infer_value("true"), json!(true));
+        assert_eq!(infer_value("false"), json!(false));
+        assert_eq!(infer_value("42"), json!(42));
+        assert_eq!(infer_value("-3"), json!(-3));
+        assert_eq!(infer_value("0"), json!(0));
+        assert_eq!(infer_value("19.99"), json!(19.99));
+        assert_eq!(infer_value("0.5"), json!(0.5));
+    }
+
+    #[test]
+    fn test_keeps_ambiguous_values_as_strings() {
+        for raw in ["007", "+1", "1e5", "1.", ".5", "True", "", "-", "12abc", "99999999999999999999"] {
+            assert_eq!(infer_value(raw), json!(raw), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_split_typed_key() {
+        assert_eq!(split_typed_key("price"), Ok(("price", None)));
+        assert_eq!(split_typed_key("price:n"), Ok(("price", Some(ParamType::Number))));
+        assert_eq!(split_typed_key("a:b:s"), Ok(("a:b", Some(ParamType::String))));
+        assert!(split_typed_key("price:x").is_err());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        assert_eq!(typed_value("19.99", ParamType::Number), Ok(json!(19.99)));
+        assert_eq!(typed_value("1e3", ParamType::Number), Ok(json!(1000.0)));
+        assert_eq!(typed_value("42", ParamType::Integer), Ok(json!(42)));
+        assert_eq!(typed_value("1", ParamType::Boolean), Ok(json!(true)));
+        assert_eq!(typed_value("007", ParamType::String), Ok(json!("007")));
+        assert!(typed_value("abc", ParamType::Number).is_err());
+        assert!(typed_value("NaN", ParamType::Number).is_err());
+        assert!(typed_value("4.5", ParamType::Integer).is_err());
+        assert!(typed_value("yes", ParamType::Boolean).is_err());
+    }
+
+    #[test]
+    fn test_validate_typed_params() {
+        let config = ParameterConfig::default();
+        let params = |key: &str, value: &str| HashMap::from([(key.to_string(), value.to_string())]);
+        assert!(validate_typed_params(&params("e_price:n", "19.99"), &config).is_ok());
+        assert!(validate_typed_params(&params("p_ratio:x", "1"), &config).is_ok());
+        assert_eq!(
+            validate_typed_params(&params("e_active:b", "maybe"), &config),
+            Err("Invalid value for e_active:b: expected a boolean, got 'maybe'".to_string())
+        );
+        assert_eq!(
+            validate_typed_params(&params("u_age:x", "30"), &config),
+            Err("Unknown type suffix in u_age:x (expected :s, :n, :i or :b)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_collect_values_typed_keys_win() {
+        let entries = [("price", "free"), ("price:n", "19.99"), ("zip:s", "01234")];
+        let config = ParameterConfig {
+            infer_types: true,
+            ..Default::default()
+        };
+        let values = collect_values(entries, &config);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values.get("price"), Some(&json!(19.99)));
+        assert_eq!(values.get("zip"), Some(&json!("01234")));
+    }
+
+    #[test]
+    fn test_inference_is_opt_in() {
+        assert_eq!(param_value("42", &ParameterConfig::default()), json!("42"));
+        let config = ParameterConfig {
+            infer_types: true,
+            ..Default::default()
+        };
+        assert_eq!(param_value("42", &config), json!(42));
+    }
+
+    #[test]
+    fn test_parses_json_objects_and_arrays() {
+        let config = ParameterConfig {
+            parse_json: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            param_value(r#"[{"sku":"a","qty":2}]"#, &config),
+            json!([{"sku": "a", "qty": 2}])
+        );
+        assert_eq!(param_value(r#" {"a":{"b":1}} "#, &config), json!({"a": {"b": 1}}));
+        // Scalars and broken JSON stay strings
+        assert_eq!(param_value("42", &config), json!("42"));
+        assert_eq!(param_value("[1, 2", &config), json!("[1, 2"));
+        assert_eq!(param_value("{}", &ParameterConfig::default()), json!("{}"));
+    }
+
+    #[test]
+    fn test_json_size_and_depth_limits() {
+        assert_eq!(parse_json_value("[1,2,3]", 7, 5), Some(json!([1, 2, 3])));
+        assert_eq!(parse_json_value("[1,2,3]", 6, 5), None);
+        assert_eq!(parse_json_value("[[[1]]]", 100, 3), Some(json!([[[1]]])));
+        assert_eq!(parse_json_value("[[[1]]]", 100, 2), None);
+    }
+}
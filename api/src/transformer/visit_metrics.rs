@@ -0,0 +1,85 @@
+// Visit metric sanity checks
+// This module parses duration and scroll_depth, discarding or clamping values no client can produce
+
+/// Highest meaningful scroll depth, in percent
+pub const MAX_SCROLL_DEPTH: i32 = 100;
+
+/// Parse the `duration` parameter (active time in milliseconds)
+///
+/// Negative durations and durations above `max_duration_ms` are discarded and
+/// described in `warnings`.
+///
+/// # Returns
+/// The duration, or `None` when the value is not a whole number or was discarded
+pub fn parse_duration(raw: &str, max_duration_ms: u64, warnings: &mut Vec<String>) -> Option<i64> {
+    let duration = raw.trim().parse::<i64>().ok()?;
+    if duration < 0 {
+        warnings.push(format!("duration {} dropped: negative", duration));
+        return None;
+    }
+    if duration as u64 > max_duration_ms {
+        warnings.push(format!(
+            "duration {} dropped: exceeds the limit of {}",
+            duration, max_duration_ms
+        ));
+        return None;
+    }
+    Some(duration)
+}
+
+/// Parse the `scroll_depth` parameter (percent of the page), clamped to 0-100
+///
+/// Clamped values are described in `warnings`.
+///
+/// # Returns
+/// The scroll depth, or `None` when the value is not a whole number
+pub fn parse_scroll_depth(raw: &str, warnings: &mut Vec<String>) -> Option<i32> {
+    let depth = raw.trim().parse::<i32>().ok()?;
+    let clamped = depth.clamp(0, MAX_SCROLL_DEPTH);
+    if clamped != depth {
+        warnings.push(format!("scroll_depth {} clamped to {}", depth, clamped));
+    }
+    Some(clamped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let mut warnings = Vec::new();
+        assert_eq!(parse_duration("45000", 86_400_000, &mut warnings), Some(45000));
+        assert_eq!(parse_duration("0", 86_400_000, &mut warnings), Some(0));
+        assert_eq!(parse_duration("abc", 86_400_000, &mut warnings), None);
+        assert!(warnings.is_empty());
+
+        assert_eq!(parse_duration("-5", 86_400_000, &mut warnings), None);
+        assert_eq!(parse_duration("90000000", 86_400_000, &mut warnings), None);
+        assert_eq!(
+            warnings,
+            vec![
+                "duration -5 dropped: negative".to_string(),
+                "duration 90000000 dropped: exceeds the limit of 86400000".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_scroll_depth() {
+        let mut warnings = Vec::new();
+        assert_eq!(parse_scroll_depth("75", &mut warnings), Some(75));
+        assert_eq!(parse_scroll_depth("x", &mut warnings), None);
+        assert!(warnings.is_empty());
+
+        assert_eq!(parse_scroll_depth("150", &mut warnings), Some(100));
+        assert_eq!(parse_scroll_depth("-3", &mut warnings), Some(0));
+        assert_eq!(
+            warnings,
+            vec![
+                "scroll_depth 150 clamped to 100".to_string(),
+                "scroll_depth -3 clamped to 0".to_string(),
+            ]
+        );
+    }
+}
@@ -57,22 +57,27 @@ fn create_test_config(service_type: StreamingServiceType) -> Config {
         server: ServerConfig {
             host: "127.0.0.1".to_string(),
             port: 3000,
+            ..Default::default()
         },
         streaming: StreamingConfig {
             service_type,
             kafka: Some(KafkaConfig {
                 brokers: vec!["localhost:9092".to_string()],
                 topic: "analytics-events".to_string(),
+                ..Default::default()
             }),
             kinesis: None,
             pulsar: None,
+            ..Default::default()
         },
         geoip: GeoIpConfig {
             database_path: "GeoLite2-City.mmdb".to_string(),
+            ..Default::default()
         },
         logging: LoggingConfig {
             level: "info".to_string(),
         },
+        ..Default::default()
     }
 }
 
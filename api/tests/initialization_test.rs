@@ -29,22 +29,27 @@ fn create_test_config() -> Config {
         server: ServerConfig {
             host: "0.0.0.0".to_string(),
             port: 8080,
+            ..Default::default()
         },
         streaming: StreamingConfig {
             service_type: StreamingServiceType::Kafka,
             kafka: Some(KafkaConfig {
                 brokers: vec!["localhost:9092".to_string()],
                 topic: "analytics-events".to_string(),
+                ..Default::default()
             }),
             kinesis: None,
             pulsar: None,
+            ..Default::default()
         },
         geoip: GeoIpConfig {
             database_path: "/path/to/GeoLite2-City.mmdb".to_string(),
+            ..Default::default()
         },
         logging: LoggingConfig {
             level: "info".to_string(),
         },
+        ..Default::default()
     }
 }
 
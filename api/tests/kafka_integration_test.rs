@@ -36,6 +36,7 @@ fn create_test_event() -> AnalyticsEvent {
             language: Some("en-US".to_string()),
             referer: Some("https://google.com".to_string()),
             app: Some("web".to_string()),
+            ..Default::default()
         },
         event_param: None,
         profile: None,
@@ -49,6 +50,7 @@ fn create_test_event() -> AnalyticsEvent {
         city: Some("San Francisco".to_string()),
         latitude: Some(37.7749),
         longitude: Some(-122.4194),
+        ..Default::default()
     }
 }
 
@@ -5,6 +5,7 @@ use api::config::{Config, ServerConfig, StreamingConfig, StreamingServiceType, G
 use api::enrichment::geoip::{GeoIpLookup, GeoLocation};
 use api::enrichment::user_agent::{UserAgentParser, WootheeParser, UserAgentInfo};
 use api::handlers::{AppState, track_handler, identify_handler, update_handler};
+use api::metrics::{SinkMetrics, StageMetrics};
 use api::streaming::{StreamingService, StreamingError};
 use api::transformer::AnalyticsEvent;
 use async_trait::async_trait;
@@ -40,10 +41,13 @@ impl MockGeoIpLookup {
     fn lookup(&self, _ip: IpAddr) -> GeoLocation {
         GeoLocation {
             country: Some("United States".to_string()),
+            country_code: Some("US".to_string()),
             region: Some("California".to_string()),
             city: Some("San Francisco".to_string()),
             latitude: Some(37.7749),
             longitude: Some(-122.4194),
+            time_zone: Some("America/Los_Angeles".to_string()),
+            network: None,
         }
     }
 }
@@ -54,22 +58,27 @@ fn create_test_config() -> Config {
         server: ServerConfig {
             host: "127.0.0.1".to_string(),
             port: 3000,
+            ..Default::default()
         },
         streaming: StreamingConfig {
             service_type: StreamingServiceType::Kafka,
             kafka: Some(KafkaConfig {
                 brokers: vec!["localhost:9092".to_string()],
                 topic: "analytics-events".to_string(),
+                ..Default::default()
             }),
             kinesis: None,
             pulsar: None,
+            ..Default::default()
         },
         geoip: GeoIpConfig {
             database_path: "GeoLite2-City.mmdb".to_string(),
+            ..Default::default()
         },
         logging: LoggingConfig {
             level: "info".to_string(),
         },
+        ..Default::default()
     }
 }
 
@@ -87,6 +96,8 @@ fn create_test_app_state() -> AppState {
         geoip_lookup,
         user_agent_parser,
         config,
+        Arc::new(SinkMetrics::new("kafka")),
+        Arc::new(StageMetrics::new()),
     )
 }
 